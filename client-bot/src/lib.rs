@@ -0,0 +1,202 @@
+//! Headless bot clients. A bot connects like any other client, reads its intel every so often and
+//! hands it to a [`BotController`], which decides what to do by queueing up requests in
+//! [`BotCommands`]. The bot readies up automatically in the lobby.
+
+mod scripted;
+
+pub use scripted::ScriptedBot;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use ftl_protocol::{
+    bullets::{BeamTarget, RoomTarget},
+    events::{
+        AdjustPower, CommandEvent, PowerDir, SetBeamWeaponTarget, SetCrewGoal,
+        SetProjectileWeaponTarget, ShipCommand, WeaponPower,
+    },
+    intel::{SelfIntel, ShipIntel, SystemsIntel, WeaponChargeIntel},
+    lobby::{PlayerReady, ReadyState, Team},
+    ship::Dead,
+};
+use std::time::Duration;
+
+/// Everything a bot is allowed to know when making a decision. This is exactly what a human player
+/// would see: intel chunks that aren't visible to us are `None`.
+pub struct BotView<'a> {
+    pub me: &'a SelfIntel,
+    pub my_ship: &'a ShipIntel,
+    pub my_systems: Option<&'a SystemsIntel>,
+    pub my_weapon_charge: Option<&'a WeaponChargeIntel>,
+    /// The enemy ship entity and its intel, if we've received it yet.
+    pub enemy: Option<(Entity, &'a ShipIntel)>,
+}
+
+/// Requests queued up by a [`BotController`]. These get sent to the server once the controller
+/// has made all its decisions for this round.
+#[derive(Default, Debug)]
+pub struct BotCommands {
+    adjust_power: Vec<AdjustPower>,
+    weapon_power: Vec<WeaponPower>,
+    projectile_targets: Vec<SetProjectileWeaponTarget>,
+    beam_targets: Vec<SetBeamWeaponTarget>,
+    crew_goals: Vec<SetCrewGoal>,
+}
+
+impl BotCommands {
+    pub fn adjust_power(&mut self, event: AdjustPower) {
+        self.adjust_power.push(event);
+    }
+
+    pub fn weapon_power(&mut self, weapon_index: usize, dir: PowerDir) {
+        self.weapon_power.push(WeaponPower { dir, weapon_index });
+    }
+
+    pub fn target_room(&mut self, weapon_index: usize, ship: Entity, room: usize) {
+        self.projectile_targets.push(SetProjectileWeaponTarget {
+            weapon_index,
+            target: Some(RoomTarget { ship, room }),
+        });
+    }
+
+    /// `start` and `dir` are in the target ship's local space, the same space as
+    /// [`ShipType::cell_positions`](ftl_protocol::ship::ShipType::cell_positions).
+    pub fn target_beam(&mut self, weapon_index: usize, ship: Entity, start: Vec2, dir: Dir2) {
+        self.beam_targets.push(SetBeamWeaponTarget {
+            weapon_index,
+            target: Some(BeamTarget { ship, start, dir }),
+        });
+    }
+
+    pub fn crew_goal(&mut self, crew: usize, room: usize) {
+        self.crew_goals.push(SetCrewGoal { crew, room });
+    }
+}
+
+/// Implement this to write a bot. Each round, the decide methods get called in order (power, then
+/// targets, then crew) with the same view. The server validates everything, so there's no harm in
+/// asking for something that turns out to be impossible, it just won't happen.
+pub trait BotController: Send + Sync + 'static {
+    fn decide_power(&mut self, view: &BotView, commands: &mut BotCommands);
+    fn decide_targets(&mut self, view: &BotView, commands: &mut BotCommands);
+    fn decide_crew(&mut self, view: &BotView, commands: &mut BotCommands);
+}
+
+/// The bot driving this client, along with how often it gets to make decisions.
+#[derive(Resource)]
+pub struct Bot {
+    controller: Box<dyn BotController>,
+    timer: Timer,
+}
+
+impl Bot {
+    pub fn new(controller: impl BotController, interval: Duration) -> Self {
+        Self {
+            controller: Box::new(controller),
+            timer: Timer::new(interval, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Add this alongside [`ProtocolPlugin`](ftl_protocol::ProtocolPlugin) and insert a [`Bot`]
+/// resource to drive the client.
+pub fn bot_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            ready_up.run_if(resource_exists::<ReadyState>),
+            run_bot.run_if(not(resource_exists::<ReadyState>)),
+        ),
+    );
+}
+
+fn ready_up(
+    ready_state: Res<ReadyState>,
+    client: Res<RepliconClient>,
+    mut ready: EventWriter<PlayerReady>,
+) {
+    let Some(client_id) = client.id() else {
+        return;
+    };
+    // Only send on change, otherwise we'd spam the server every frame until it acknowledges us
+    if !ready_state.is_changed() {
+        return;
+    }
+    if let ReadyState::AwaitingClients { ready_clients } = ready_state.as_ref() {
+        if !ready_clients.contains(&client_id) {
+            ready.send(default());
+        }
+    }
+}
+
+/// Where the requests in [`BotCommands`] end up.
+#[derive(SystemParam)]
+struct CommandWriters<'w> {
+    adjust_power: EventWriter<'w, ShipCommand<AdjustPower>>,
+    weapon_power: EventWriter<'w, ShipCommand<WeaponPower>>,
+    projectile_targets: EventWriter<'w, ShipCommand<SetProjectileWeaponTarget>>,
+    beam_targets: EventWriter<'w, ShipCommand<SetBeamWeaponTarget>>,
+    crew_goals: EventWriter<'w, ShipCommand<SetCrewGoal>>,
+}
+
+impl CommandWriters<'_> {
+    fn send(&mut self, ship: Entity, commands: BotCommands) {
+        self.adjust_power
+            .send_batch(commands.adjust_power.into_iter().map(|x| x.for_ship(ship)));
+        self.weapon_power
+            .send_batch(commands.weapon_power.into_iter().map(|x| x.for_ship(ship)));
+        self.projectile_targets.send_batch(
+            commands
+                .projectile_targets
+                .into_iter()
+                .map(|x| x.for_ship(ship)),
+        );
+        self.beam_targets
+            .send_batch(commands.beam_targets.into_iter().map(|x| x.for_ship(ship)));
+        self.crew_goals
+            .send_batch(commands.crew_goals.into_iter().map(|x| x.for_ship(ship)));
+    }
+}
+
+/// The intel a [`BotView`] is built from. Bundled, like [`CommandWriters`], to keep [`run_bot`]
+/// within the system parameter limit.
+#[derive(SystemParam)]
+struct BotIntel<'w, 's> {
+    self_intel: Query<'w, 's, &'static SelfIntel>,
+    ships: Query<'w, 's, (Entity, &'static ShipIntel), Without<Dead>>,
+    teams: Query<'w, 's, &'static Team>,
+    systems: Query<'w, 's, &'static SystemsIntel>,
+    weapon_charge: Query<'w, 's, &'static WeaponChargeIntel>,
+}
+
+impl BotIntel<'_, '_> {
+    fn view(&self) -> Option<BotView<'_>> {
+        let me = self.self_intel.get_single().ok()?;
+        let (_, my_ship) = self.ships.get(me.ship).ok()?;
+        let my_team = self.teams.get(me.ship).ok();
+        Some(BotView {
+            me,
+            my_ship,
+            my_systems: self.systems.get(my_ship.systems).ok(),
+            my_weapon_charge: self.weapon_charge.get(my_ship.weapon_charge).ok(),
+            enemy: self
+                .ships
+                .iter()
+                .find(|(x, _)| *x != me.ship && self.teams.get(*x).ok() != my_team),
+        })
+    }
+}
+
+fn run_bot(mut bot: ResMut<Bot>, time: Res<Time>, intel: BotIntel, mut writers: CommandWriters) {
+    if !bot.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(view) = intel.view() else {
+        return;
+    };
+    let mut commands = BotCommands::default();
+    bot.controller.decide_power(&view, &mut commands);
+    bot.controller.decide_targets(&view, &mut commands);
+    bot.controller.decide_crew(&view, &mut commands);
+
+    writers.send(view.me.ship, commands);
+}
@@ -0,0 +1,34 @@
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::RepliconRenetPlugins;
+use client_bot::{bot_plugin, Bot, ScriptedBot};
+use ftl_protocol::{Handshake, PlayerKey, ProtocolPlugin};
+use std::time::Duration;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(5))),
+            RepliconPlugins,
+            RepliconRenetPlugins,
+            ProtocolPlugin,
+            bot_plugin,
+        ))
+        .insert_resource(Bot::new(ScriptedBot, Duration::from_millis(500)))
+        .add_systems(Startup, connect_to_server)
+        .run();
+}
+
+fn connect_to_server(world: &mut World) {
+    // Bots don't need to survive server restarts, so a fresh player key each run is fine
+    let handshake = Handshake::new(PlayerKey(rand::random()));
+    let server_addr = match ftl_protocol::server_addr_from_args() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Couldn't find the server: {e}");
+            world.send_event(AppExit::error());
+            return;
+        }
+    };
+    ftl_protocol::connect(world, server_addr, handshake).unwrap();
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use ftl_protocol::{
+    events::{AdjustPower, PowerDir},
+    ship::{SystemId, SHIPS},
+    weapon::WeaponId,
+};
+
+use crate::{BotCommands, BotController, BotView};
+
+/// A simple fixed-priority bot. Powers shields, then engines, then oxygen, then weapons in order;
+/// shoots everything at the enemy's shields; and parks crew in shields, weapons and engines.
+/// Predictable enough to practice against without being a pushover.
+#[derive(Default)]
+pub struct ScriptedBot;
+
+impl ScriptedBot {
+    const POWER_PRIORITY: [SystemId; 3] = [SystemId::Shields, SystemId::Engines, SystemId::Oxygen];
+    const CREW_STATIONS: [SystemId; 3] = [SystemId::Shields, SystemId::Weapons, SystemId::Engines];
+}
+
+impl BotController for ScriptedBot {
+    fn decide_power(&mut self, view: &BotView, commands: &mut BotCommands) {
+        if view.me.free_power == 0 {
+            return;
+        }
+        let Some(systems) = view.my_systems else {
+            return;
+        };
+        // Only ask for the single most important thing each round. We'll see how much power is
+        // left over in the next intel update.
+        let underpowered = Self::POWER_PRIORITY.into_iter().find(|x| {
+            systems
+                .get(x)
+                .is_some_and(|x| x.current_power < x.upgrade_level.saturating_sub(x.damage))
+        });
+        if let Some(system) = underpowered {
+            commands.adjust_power(AdjustPower::request(system));
+            return;
+        }
+        let Some(weapons) = &view.my_ship.basic.weapons else {
+            return;
+        };
+        if let Some(index) = weapons.weapons.iter().position(|x| !x.powered) {
+            commands.weapon_power(index, PowerDir::Request);
+        }
+    }
+
+    fn decide_targets(&mut self, view: &BotView, commands: &mut BotCommands) {
+        let Some((enemy, enemy_intel)) = view.enemy else {
+            return;
+        };
+        let Some(weapons) = &view.my_ship.basic.weapons else {
+            return;
+        };
+        let ship_type = &SHIPS[enemy_intel.basic.ship_type];
+        let room = enemy_intel
+            .basic
+            .system_locations
+            .get(&SystemId::Shields)
+            .copied()
+            .unwrap_or(0);
+        for (index, weapon) in weapons.weapons.iter().enumerate() {
+            let has_target = view
+                .me
+                .weapon_targets
+                .get(index)
+                .is_some_and(|x| x.is_some());
+            if !weapon.powered || has_target {
+                continue;
+            }
+            match weapon.weapon {
+                WeaponId::Projectile(_) => commands.target_room(index, enemy, room),
+                WeaponId::Beam(_) => {
+                    commands.target_beam(index, enemy, ship_type.room_center(room), Dir2::Y)
+                }
+            }
+        }
+    }
+
+    fn decide_crew(&mut self, view: &BotView, commands: &mut BotCommands) {
+        let ship_type = &SHIPS[view.my_ship.basic.ship_type];
+        let stations = Self::CREW_STATIONS
+            .iter()
+            .filter_map(|x| view.my_ship.basic.system_locations.get(x).copied());
+        for ((index, crew), room) in view.me.crew.iter().enumerate().zip(stations) {
+            if ship_type.cell_room(crew.nav_status.occupied_cell()) != room {
+                commands.crew_goal(index, room);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! A whole 1v1 on this machine: builds the server and client, starts a server on a free loopback
+//! port and opens two client windows pointed at it, each with its own player ID. Ready up in both
+//! windows to start the match. Closing both windows shuts the server down too.
+//!
+//! ```sh
+//! cargo run -p client --example local_duel
+//! ```
+//!
+//! Anything after `--` goes to the server, e.g. `-- --balance server/balance.ron`.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    process::{Child, Command, ExitCode},
+    thread,
+    time::Duration,
+};
+
+/// How long to give the server to bind its socket before the clients start knocking.
+const SERVER_STARTUP: Duration = Duration::from_secs(1);
+/// How often to check whether anything has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn main() -> ExitCode {
+    let server_args = std::env::args().skip(1).collect::<Vec<_>>();
+    // Set when we're run by `cargo run`, and it might not be the `cargo` on the PATH
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    // Build everything up front so the server and clients don't queue up on the build lock
+    let profile = (!cfg!(debug_assertions)).then_some("--release");
+    let built = Command::new(&cargo)
+        .args(["build", "-p", "server", "-p", "client"])
+        .args(profile)
+        .status();
+    match built {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            eprintln!("Build failed ({status}).");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("Couldn't run `{cargo}`: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let addr = match free_loopback_addr() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Couldn't find a free port: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("Starting a server on {addr}.");
+    // Both packages have more than one binary, so name the one we want
+    let run = |package: &str| {
+        let mut command = Command::new(&cargo);
+        command
+            .args(["run", "-q", "-p", package, "--bin", package])
+            .args(profile);
+        command.arg("--");
+        command
+    };
+    let mut server = match run("server")
+        .arg("--bind")
+        .arg(addr.to_string())
+        .args(&server_args)
+        .spawn()
+    {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Couldn't start the server: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    thread::sleep(SERVER_STARTUP);
+
+    let mut clients = Vec::new();
+    for player_id in 1..=2 {
+        let client = run("client")
+            .arg("--server")
+            .arg(addr.to_string())
+            .arg("--player-key")
+            .arg(format!("local duel {player_id}"))
+            .spawn();
+        match client {
+            Ok(client) => clients.push(client),
+            Err(e) => {
+                eprintln!("Couldn't start client {player_id}: {e}");
+                shut_down(clients.iter_mut().chain([&mut server]));
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if let Ok(Some(status)) = server.try_wait() {
+            eprintln!("The server stopped ({status}), closing the clients.");
+            shut_down(&mut clients);
+            return ExitCode::FAILURE;
+        }
+        if clients
+            .iter_mut()
+            .all(|x| matches!(x.try_wait(), Ok(Some(_))))
+        {
+            println!("Both clients closed, stopping the server.");
+            shut_down([&mut server]);
+            return ExitCode::SUCCESS;
+        }
+    }
+}
+
+/// A loopback address with a port nobody's using right now. Someone could grab it before the
+/// server does, but that's unlikely enough for a local match.
+fn free_loopback_addr() -> std::io::Result<SocketAddr> {
+    UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()
+}
+
+fn shut_down<'a>(children: impl IntoIterator<Item = &'a mut Child>) {
+    for child in children {
+        // Already gone is fine
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
@@ -0,0 +1,82 @@
+//! Alert bar along the top of the screen for alarms raised on our ship and hazards currently acting
+//! on it, flashing while they're active and sounding a klaxon whenever a new alarm goes off. Also
+//! where the server's warnings land when we've been idle too long.
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContexts,
+};
+use common::{alarm::Alarm, events::IdleWarning, hazard::HazardState, intel::SelfIntel};
+
+/// How many times per second the alert bar flashes.
+const FLASH_RATE: f32 = 2.0;
+/// The server repeats idle warnings every second, so one that's gone this long without an update
+/// means we're not idle anymore.
+const IDLE_WARNING_TIMEOUT: f32 = 1.5;
+
+pub fn alerts_plugin(app: &mut App) {
+    app.add_systems(Update, (alert_bar, alarm_sounds));
+}
+
+fn alert_bar(
+    mut ui: EguiContexts,
+    self_intel: Single<&SelfIntel>,
+    hazards: Option<Res<HazardState>>,
+    mut idle_warnings: EventReader<IdleWarning>,
+    mut idle_warning: Local<Option<(u32, f32)>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    if let Some(warning) = idle_warnings.read().last() {
+        *idle_warning = Some((warning.forfeit_in, now));
+    }
+    let mut alerts = self_intel
+        .alarms
+        .iter()
+        .map(|x| match (x, self_intel.suffocation_in) {
+            (Alarm::LowOxygen, Some(secs)) => format!("{}, {}s of air left", x.name(), secs.ceil()),
+            _ => x.name().to_string(),
+        })
+        .collect::<Vec<_>>();
+    if hazards.is_some_and(|x| x.ion_surge) {
+        alerts.push("Ion storm surge".into());
+    }
+    if let Some((forfeit_in, received)) = *idle_warning {
+        if now - received < IDLE_WARNING_TIMEOUT {
+            alerts.push(format!("Idle, forfeiting in {forfeit_in}s"));
+        }
+    }
+    if alerts.is_empty() {
+        return;
+    }
+    let lit = (now * FLASH_RATE).fract() < 0.5;
+    let color = if lit { Color32::RED } else { Color32::DARK_RED };
+    egui::Window::new("Alerts")
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2::ZERO)
+        .title_bar(false)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for alert in alerts {
+                    ui.label(RichText::new(format!("⚠ {alert}")).strong().color(color));
+                }
+            });
+        });
+}
+
+fn alarm_sounds(
+    self_intel: Single<&SelfIntel>,
+    mut raised: Local<HashSet<Alarm>>,
+    assets: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let now = self_intel.alarms.iter().copied().collect::<HashSet<_>>();
+    if now.difference(&raised).next().is_some() {
+        commands.spawn((
+            AudioPlayer::new(assets.load("alarm.wav")),
+            PlaybackSettings::DESPAWN,
+        ));
+    }
+    *raised = now;
+}
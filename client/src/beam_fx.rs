@@ -0,0 +1,239 @@
+//! Beam rendering. Each beam gets two glowing quads: one from the firing ship's weapon mount off
+//! the edge of the screen, and one coming back in from offscreen to wherever the beam is cutting
+//! into its target right now, with sparks flying off that point. It's all worked out from the
+//! beam's [`BeamTarget`] and [`Progress`] every frame, so it follows the same sweep the server is
+//! dealing damage along.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use common::{
+    bullets::{BeamTarget, FiredFrom, Incidence, Progress},
+    intel::ShipIntel,
+    util::inverse_lerp,
+    weapon::{BeamStats, BeamWeaponId, WeaponId},
+};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    graphics::Z_BULLETS,
+    weapon_fx::{shot_color, shot_weapon},
+};
+
+pub fn beam_fx_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (add_beam_segments, update_beams, animate_sparks).chain(),
+    );
+}
+
+const SPARK_SECS: f32 = 0.3;
+/// Time between bursts of sparks off a beam's hit point.
+const SPARK_INTERVAL: f32 = 0.03;
+/// Distance from a ship's center to its shield bubble, for beams that can't get through.
+const SHIELD_RADIUS: f32 = 150.0;
+
+/// One of the two halves of a beam, as a child of the beam entity.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum BeamSegment {
+    /// From the firing ship, off the edge of the screen.
+    Outgoing,
+    /// Back in from off the screen, onto the target.
+    Incoming,
+}
+
+#[derive(Component)]
+struct Spark {
+    velocity: Vec2,
+    timer: Timer,
+}
+
+/// Where a beam is drawn this frame, all in world space.
+struct BeamPath {
+    start: Vec3,
+    out_mid: Vec3,
+    in_mid: Vec3,
+    end: Vec3,
+}
+
+fn beam_color(weapon: &BeamStats) -> Color {
+    shot_color(&weapon.common.presentation)
+}
+
+fn beam_width(weapon: &BeamStats) -> f32 {
+    6.0 + 3.0 * weapon.common.damage.hull as f32
+}
+
+/// The beam weapon in the slot a beam was fired from.
+fn beam_weapon(intel: &ShipIntel, origin: &FiredFrom) -> Option<BeamWeaponId> {
+    match shot_weapon(intel, origin)? {
+        WeaponId::Beam(weapon) => Some(weapon),
+        WeaponId::Projectile(_) => None,
+    }
+}
+
+fn beam_path(
+    weapon: &BeamStats,
+    progress: f32,
+    target: &BeamTarget,
+    incidence: &Incidence,
+    firing_ship: &GlobalTransform,
+    target_intel: &ShipIntel,
+    target_ship: &GlobalTransform,
+) -> BeamPath {
+    let weapon_mount_pos = Vec2::ZERO.extend(Z_BULLETS);
+    let start = firing_ship.transform_point(weapon_mount_pos);
+    let out_mid = firing_ship.transform_point(weapon_mount_pos + Vec3::X * 1000.0);
+    let hit_point = target.start + (*target.dir * weapon.length * progress);
+    let in_mid = hit_point + ***incidence * 1000.0;
+    let target_shields = target_intel.basic.shields.map_or(0, |x| x.layers);
+    let blocked = weapon
+        .shield_rule
+        .blocks(weapon.common.damage, target_shields);
+    let hit_point = if blocked {
+        // Find where the line (in_mid, hit_point) crosses the shield bubble
+        let ab = hit_point - in_mid;
+        let a_t = ab * ab.dot(in_mid) / ab.length_squared();
+        let b_t = ab + a_t;
+
+        let d_sqr = (in_mid - a_t).length_squared();
+        let a_t = a_t.dot(ab.normalize());
+        let b_t = b_t.dot(ab.normalize());
+
+        let t = (SHIELD_RADIUS * SHIELD_RADIUS - d_sqr).sqrt();
+        let lerp_low = inverse_lerp(a_t, b_t, -t);
+        in_mid.lerp(hit_point, lerp_low)
+    } else {
+        hit_point
+    };
+    BeamPath {
+        start,
+        out_mid,
+        in_mid: target_ship.transform_point(in_mid.extend(Z_BULLETS)),
+        end: target_ship.transform_point(hit_point.extend(Z_BULLETS)),
+    }
+}
+
+fn add_beam_segments(
+    beams: Query<(Entity, &FiredFrom), Added<BeamTarget>>,
+    ships: Query<&ShipIntel>,
+    assets: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (beam, origin) in &beams {
+        let Some(weapon) = ships
+            .get(origin.ship)
+            .ok()
+            .and_then(|x| beam_weapon(x, origin))
+        else {
+            continue;
+        };
+        let color = beam_color(&weapon);
+        commands
+            .entity(beam)
+            .insert((Transform::default(), Visibility::default()))
+            .with_children(|beam| {
+                for segment in [BeamSegment::Outgoing, BeamSegment::Incoming] {
+                    beam.spawn((
+                        segment,
+                        PickingBehavior::IGNORE,
+                        Sprite {
+                            image: assets.load(weapon.common.presentation.sprite),
+                            color,
+                            ..default()
+                        },
+                    ));
+                }
+            });
+    }
+}
+
+/// Stretch each beam's segments between its endpoints and throw sparks off where it's hitting.
+fn update_beams(
+    ships: Query<(&ShipIntel, &GlobalTransform)>,
+    beams: Query<(&FiredFrom, &Progress, &BeamTarget, &Incidence, &Children)>,
+    mut segments: Query<(&BeamSegment, &mut Transform, &mut Sprite)>,
+    mut since_last_spark: Local<f32>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    *since_last_spark += time.delta_secs();
+    let sparks = *since_last_spark >= SPARK_INTERVAL;
+    if sparks {
+        *since_last_spark = 0.0;
+    }
+    let mut rng = thread_rng();
+    for (origin, &progress, target, incidence, children) in &beams {
+        let (Ok((intel, firing_ship)), Ok((target_intel, target_ship))) =
+            (ships.get(origin.ship), ships.get(target.ship))
+        else {
+            continue;
+        };
+        let Some(weapon) = beam_weapon(intel, origin) else {
+            continue;
+        };
+        let path = beam_path(
+            &weapon,
+            *progress,
+            target,
+            incidence,
+            firing_ship,
+            target_intel,
+            target_ship,
+        );
+        // Swell in as the sweep starts and thin out as it finishes, with a flicker on top
+        let swell = (*progress * 10.0).min(1.0) * ((1.0 - *progress) * 10.0).clamp(0.0, 1.0);
+        let flicker = 1.0 + 0.15 * (time.elapsed_secs() * 40.0).sin();
+        let width = beam_width(&weapon) * (0.3 + 0.7 * swell) * flicker;
+        for &child in children {
+            let Ok((&segment, mut transform, mut sprite)) = segments.get_mut(child) else {
+                continue;
+            };
+            let (from, to) = match segment {
+                BeamSegment::Outgoing => (path.start, path.out_mid),
+                BeamSegment::Incoming => (path.in_mid, path.end),
+            };
+            let delta = (to - from).xy();
+            *transform = Transform::from_translation((from + to) / 2.0)
+                .with_rotation(Quat::from_rotation_z(delta.to_angle()));
+            sprite.custom_size = Some(Vec2::new(delta.length(), width));
+        }
+
+        if !sparks {
+            continue;
+        }
+        let color = beam_color(&weapon).mix(&Color::WHITE, 0.5);
+        for _ in 0..2 {
+            let speed = rng.gen_range(40.0..120.0);
+            commands.spawn((
+                Spark {
+                    velocity: Vec2::from_angle(rng.gen_range(0.0..TAU)) * speed,
+                    timer: Timer::from_seconds(SPARK_SECS, TimerMode::Once),
+                },
+                PickingBehavior::IGNORE,
+                Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(3.0)),
+                    ..default()
+                },
+                Transform::from_translation(path.end + Vec3::Z * 0.1),
+            ));
+        }
+    }
+}
+
+fn animate_sparks(
+    mut sparks: Query<(Entity, &mut Spark, &mut Transform, &mut Sprite)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (e, mut spark, mut transform, mut sprite) in &mut sparks {
+        spark.timer.tick(time.delta());
+        if spark.timer.finished() {
+            commands.entity(e).despawn();
+            continue;
+        }
+        transform.translation += (spark.velocity * time.delta_secs()).extend(0.0);
+        sprite.color.set_alpha(1.0 - spark.timer.fraction());
+    }
+}
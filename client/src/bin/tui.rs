@@ -0,0 +1,453 @@
+//! Terminal client. Shows the same status the graphical client does, minus the pictures, and takes
+//! keyboard input for powering systems and targeting rooms by index. Handy for poking at the
+//! protocol and for playing over SSH. Pass `--server <host>` to play somewhere other than this
+//! machine.
+//!
+//! Controls:
+//! - `a`/`s`/`w`/`f`/`b`: power shields/engines/weapons/oxygen/clone bay, uppercase to depower,
+//!   with `Ctrl` to reroute power from oxygen (or engines, for oxygen itself)
+//! - `1`-`4`: power a weapon, or if it's already powered, pick it for targeting
+//! - While targeting: `0`-`9` to target an enemy room, `Backspace` to depower, `Esc` to cancel
+//! - `v`: toggle autofire
+//! - `z`/`x`: open/close all doors, uppercase to vent unoccupied rooms/close only the airlocks
+//! - `r`: ready up
+//! - `q`: quit
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::RepliconRenetPlugins;
+use common::{
+    bullets::{BeamTarget, RoomTarget},
+    events::{
+        AdjustPower, CommandEvent, CommandRejected, PowerDir, ReroutePower, SetAutofire,
+        SetBeamWeaponTarget, SetDoorsOpen, SetProjectileWeaponTarget, ShipCommand, WeaponPower,
+    },
+    intel::{SelfIntel, ShipIntel, SystemsIntel, WeaponChargeIntel},
+    lobby::{PlayerReady, ReadyState},
+    match_clock::MatchClock,
+    ship::{Dead, SystemId, SHIPS},
+    weapon::{WeaponId, WeaponTarget},
+};
+use ftl_protocol::{Handshake, ProtocolPlugin};
+use ratatui::{
+    crossterm::{
+        event::{self, Event as TermEvent, KeyCode, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    prelude::CrosstermBackend,
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Terminal,
+};
+use std::{
+    io::{stdout, Stdout},
+    time::Duration,
+};
+
+fn main() {
+    let terminal = Tui::new().expect("Failed to set up terminal");
+    // Make sure a panic doesn't leave the terminal in raw mode
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        Tui::restore();
+        default_hook(info);
+    }));
+
+    App::new()
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(16))),
+            RepliconPlugins,
+            RepliconRenetPlugins,
+            ProtocolPlugin,
+        ))
+        .insert_non_send_resource(terminal)
+        .init_resource::<Targeting>()
+        .init_resource::<LastRejection>()
+        .add_systems(Startup, connect_to_server)
+        .add_systems(Update, (handle_input, note_rejections, draw).chain())
+        .run();
+}
+
+fn connect_to_server(world: &mut World) {
+    let server_addr = match ftl_protocol::server_addr_from_args() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Couldn't find the server: {e}");
+            world.send_event(AppExit::error());
+            return;
+        }
+    };
+    let handshake = Handshake::new(ftl_protocol::local_player_key());
+    ftl_protocol::connect(world, server_addr, handshake).unwrap();
+}
+
+/// Owns the terminal for the lifetime of the app and puts it back the way we found it on drop.
+struct Tui(Terminal<CrosstermBackend<Stdout>>);
+
+impl Tui {
+    fn new() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+        Ok(Self(Terminal::new(CrosstermBackend::new(stdout()))?))
+    }
+
+    fn restore() {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
+
+/// The weapon we're currently picking a target for, if any.
+#[derive(Resource, Default)]
+struct Targeting(Option<usize>);
+
+/// How long a rejected command's reason stays on screen, in seconds.
+const REJECTION_TIME: f32 = 3.0;
+
+/// Why the server last turned down one of our commands, and when.
+#[derive(Resource, Default)]
+struct LastRejection(Option<(String, f32)>);
+
+fn note_rejections(
+    mut rejections: EventReader<CommandRejected>,
+    mut last: ResMut<LastRejection>,
+    time: Res<Time>,
+) {
+    if let Some(CommandRejected { reason }) = rejections.read().last() {
+        last.0 = Some((reason.clone(), time.elapsed_secs()));
+    }
+}
+
+fn handle_input(
+    self_intel: Query<&SelfIntel>,
+    ships: Query<(Entity, &ShipIntel), Without<Dead>>,
+    mut targeting: ResMut<Targeting>,
+    mut power: EventWriter<ShipCommand<AdjustPower>>,
+    mut weapon_power: EventWriter<ShipCommand<WeaponPower>>,
+    mut reroute_power: EventWriter<ShipCommand<ReroutePower>>,
+    mut projectile_targeting: EventWriter<ShipCommand<SetProjectileWeaponTarget>>,
+    mut beam_targeting: EventWriter<ShipCommand<SetBeamWeaponTarget>>,
+    mut set_autofire: EventWriter<ShipCommand<SetAutofire>>,
+    mut set_doors_open: EventWriter<ShipCommand<SetDoorsOpen>>,
+    mut ready: EventWriter<PlayerReady>,
+    mut exit: EventWriter<AppExit>,
+) {
+    while event::poll(Duration::ZERO).unwrap_or(false) {
+        let Ok(TermEvent::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if key.code == KeyCode::Char('q') {
+            exit.send(AppExit::Success);
+            continue;
+        }
+        if key.code == KeyCode::Char('r') {
+            ready.send(default());
+            continue;
+        }
+        let Ok(self_intel) = self_intel.get_single() else {
+            continue;
+        };
+        let Ok((_, ship)) = ships.get(self_intel.ship) else {
+            continue;
+        };
+        let weapons = ship.basic.weapons.as_ref().map(|x| &x.weapons[..]);
+
+        if let Some(weapon_index) = targeting.0 {
+            match key.code {
+                KeyCode::Char(c @ '0'..='9') => {
+                    targeting.0 = None;
+                    let room = c.to_digit(10).unwrap() as usize;
+                    let Some((enemy, enemy_intel)) =
+                        ships.iter().find(|(x, _)| *x != self_intel.ship)
+                    else {
+                        continue;
+                    };
+                    let ship_type = &SHIPS[enemy_intel.basic.ship_type];
+                    if room >= ship_type.rooms.len() {
+                        continue;
+                    }
+                    match weapons.and_then(|x| x.get(weapon_index)).map(|x| x.weapon) {
+                        Some(WeaponId::Projectile(_)) => {
+                            projectile_targeting.send(
+                                SetProjectileWeaponTarget {
+                                    weapon_index,
+                                    target: Some(RoomTarget { ship: enemy, room }),
+                                }
+                                .for_ship(self_intel.ship),
+                            );
+                        }
+                        Some(WeaponId::Beam(_)) => {
+                            // No way to draw a line in a terminal, so beams just sweep across the
+                            // middle of the room
+                            beam_targeting.send(
+                                SetBeamWeaponTarget {
+                                    weapon_index,
+                                    target: Some(BeamTarget {
+                                        ship: enemy,
+                                        start: ship_type.room_center(room),
+                                        dir: Dir2::Y,
+                                    }),
+                                }
+                                .for_ship(self_intel.ship),
+                            );
+                        }
+                        None => {}
+                    }
+                }
+                KeyCode::Backspace => {
+                    targeting.0 = None;
+                    weapon_power.send(
+                        WeaponPower {
+                            dir: PowerDir::Remove,
+                            weapon_index,
+                        }
+                        .for_ship(self_intel.ship),
+                    );
+                }
+                KeyCode::Esc => targeting.0 = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        let system = |c: char| match c.to_ascii_lowercase() {
+            'a' => Some(SystemId::Shields),
+            's' => Some(SystemId::Engines),
+            'w' => Some(SystemId::Weapons),
+            'f' => Some(SystemId::Oxygen),
+            'b' => Some(SystemId::CloneBay),
+            _ => None,
+        };
+        match key.code {
+            KeyCode::Char(c) if system(c).is_some() => {
+                let system = system(c).unwrap();
+                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                    reroute_power.send(ReroutePower::to(system).for_ship(self_intel.ship));
+                } else if c.is_ascii_uppercase() {
+                    power.send(AdjustPower::remove(system).for_ship(self_intel.ship));
+                } else {
+                    power.send(AdjustPower::request(system).for_ship(self_intel.ship));
+                }
+            }
+            KeyCode::Char(c @ '1'..='4') => {
+                let weapon_index = c.to_digit(10).unwrap() as usize - 1;
+                let Some(weapon) = weapons.and_then(|x| x.get(weapon_index)) else {
+                    continue;
+                };
+                if weapon.powered {
+                    targeting.0 = Some(weapon_index);
+                } else {
+                    weapon_power.send(
+                        WeaponPower {
+                            dir: PowerDir::Request,
+                            weapon_index,
+                        }
+                        .for_ship(self_intel.ship),
+                    );
+                }
+            }
+            KeyCode::Char('v') => {
+                set_autofire.send(SetAutofire(!self_intel.autofire).for_ship(self_intel.ship));
+            }
+            KeyCode::Char('z') => {
+                set_doors_open.send(SetDoorsOpen::All { open: true }.for_ship(self_intel.ship));
+            }
+            KeyCode::Char('x') => {
+                set_doors_open.send(SetDoorsOpen::All { open: false }.for_ship(self_intel.ship));
+            }
+            KeyCode::Char('Z') => {
+                set_doors_open.send(SetDoorsOpen::VentUnoccupied.for_ship(self_intel.ship));
+            }
+            KeyCode::Char('X') => {
+                set_doors_open.send(SetDoorsOpen::CloseExterior.for_ship(self_intel.ship));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(
+    mut terminal: NonSendMut<Tui>,
+    ready_state: Option<Res<ReadyState>>,
+    clock: Option<Res<MatchClock>>,
+    client: Res<RepliconClient>,
+    targeting: Res<Targeting>,
+    last_rejection: Res<LastRejection>,
+    time: Res<Time>,
+    self_intel: Query<&SelfIntel>,
+    ships: Query<(Entity, &ShipIntel)>,
+    systems: Query<&SystemsIntel>,
+    weapon_charge: Query<&WeaponChargeIntel>,
+) {
+    let mut status = Vec::new();
+    let mut weapon_lines = Vec::new();
+    let mut enemy_lines = Vec::new();
+
+    if let Some(ready_state) = &ready_state {
+        match ready_state.as_ref() {
+            ReadyState::AwaitingClients { ready_clients } => {
+                let me_ready = client.id().is_some_and(|x| ready_clients.contains(&x));
+                status.push(Line::from(if me_ready {
+                    "Waiting for players..."
+                } else {
+                    "Press r to ready up"
+                }));
+            }
+            ReadyState::Starting { countdown } => {
+                status.push(Line::from(format!(
+                    "Starting in {}",
+                    countdown.as_secs() + 1
+                )));
+            }
+        }
+    } else if !client.is_connected() {
+        status.push(Line::from("Connecting..."));
+    }
+    if let Some(clock) = &clock {
+        status.push(Line::from(format!("Clock {}", clock.label())));
+    }
+
+    if let Some((self_intel, (_, ship))) = self_intel
+        .get_single()
+        .ok()
+        .and_then(|x| Some((x, ships.get(x.ship).ok()?)))
+    {
+        let basic = &ship.basic;
+        status.push(Line::from(format!(
+            "Hull {}/{}   Power {}/{}   Missiles {}   Oxygen {:.0}%   Autofire {}",
+            basic.hull,
+            basic.max_hull,
+            self_intel.free_power,
+            self_intel.max_power,
+            self_intel.missiles,
+            self_intel.oxygen * 100.0,
+            if self_intel.autofire { "on" } else { "off" },
+        )));
+        if let Some(shields) = &basic.shields {
+            status.push(Line::from(format!(
+                "Shields {}/{} (next layer {:.0}%)",
+                shields.layers,
+                shields.max_layers,
+                shields.charge * 100.0,
+            )));
+            if shields.super_shield > 0 {
+                status.push(Line::from(format!("Super shield {}", shields.super_shield)));
+            }
+        }
+        if let Ok(systems) = systems.get(ship.systems) {
+            for (system, intel) in systems.iter() {
+                status.push(Line::from(format!(
+                    "  {system:<8} power {}/{} damage {}",
+                    intel.current_power, intel.upgrade_level, intel.damage,
+                )));
+            }
+        }
+        for system in &self_intel.power_queue {
+            status.push(Line::from(format!("  {system} waiting for power")));
+        }
+        for clone in &self_intel.cloning {
+            status.push(Line::from(format!(
+                "  cloning {} {:.0}%",
+                clone.name,
+                clone.progress * 100.0,
+            )));
+        }
+
+        let charge = weapon_charge.get(ship.weapon_charge).ok();
+        if let Some(weapons) = &basic.weapons {
+            for (i, weapon) in weapons.weapons.iter().enumerate() {
+                let common = weapon.weapon.common();
+                let level = charge.and_then(|x| x.levels.get(i)).copied().unwrap_or(0.0);
+                let target = match self_intel.weapon_targets.get(i) {
+                    Some(Some(WeaponTarget::Projectile(target))) => format!("room {}", target.room),
+                    Some(Some(WeaponTarget::Beam(_))) => "beam".into(),
+                    _ => "-".into(),
+                };
+                let selected = if targeting.0 == Some(i) { ">" } else { " " };
+                weapon_lines.push(Line::from(format!(
+                    "{selected}{} {:<18} {} {:>4.1}/{:<4.1} target: {target}",
+                    i + 1,
+                    common.name,
+                    if weapon.powered { "on " } else { "off" },
+                    level,
+                    common.charge_time,
+                )));
+            }
+        }
+
+        for (entity, enemy) in &ships {
+            if entity == self_intel.ship {
+                continue;
+            }
+            let basic = &enemy.basic;
+            enemy_lines.push(Line::from(format!(
+                "Hull {}/{}",
+                basic.hull, basic.max_hull
+            )));
+            if let Some(shields) = &basic.shields {
+                enemy_lines.push(Line::from(format!(
+                    "Shields {}/{}",
+                    shields.layers, shields.max_layers
+                )));
+            }
+            for room in 0..SHIPS[basic.ship_type].rooms.len() {
+                let system = basic
+                    .system_locations
+                    .iter()
+                    .find(|(_, &x)| x == room)
+                    .map(|(x, _)| x.to_string())
+                    .unwrap_or_default();
+                enemy_lines.push(Line::from(format!("  room {room}: {system}")));
+            }
+        }
+    }
+
+    if let Some((reason, at)) = &last_rejection.0 {
+        if time.elapsed_secs() - at < REJECTION_TIME {
+            status.push(Line::from(format!("! {reason}")));
+        }
+    }
+
+    let help = if targeting.0.is_some() {
+        "0-9 target room | Backspace depower | Esc cancel"
+    } else {
+        "a/s/w/f power (shift: depower) | 1-4 weapons | v autofire | z/x doors | r ready | q quit"
+    };
+
+    let _ = terminal.0.draw(|frame| {
+        let [top, bottom, help_area] = Layout::vertical([
+            Constraint::Min(8),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [me, enemy] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(top);
+        frame.render_widget(
+            Paragraph::new(status).block(Block::default().borders(Borders::ALL).title("Ship")),
+            me,
+        );
+        frame.render_widget(
+            Paragraph::new(enemy_lines)
+                .block(Block::default().borders(Borders::ALL).title("Enemy")),
+            enemy,
+        );
+        frame.render_widget(
+            Paragraph::new(weapon_lines)
+                .block(Block::default().borders(Borders::ALL).title("Weapons")),
+            bottom,
+        );
+        frame.render_widget(Paragraph::new(help), help_area);
+    });
+}
@@ -0,0 +1,183 @@
+//! Camera controls: arrow keys or pushing the pointer against the window edge pans, the mouse wheel
+//! zooms, and hotkeys snap the view back to either ship. WASD would be the obvious choice for
+//! panning, but those keys are already taken by power hotkeys.
+//!
+//! There's also a chase camera, toggled with `C` or from the weapons panel, which follows our own
+//! shots from the ship to wherever they land.
+
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use common::{
+    bullets::{BeamTarget, FiredFrom, Progress},
+    intel::{SelfIntel, ShipIntel},
+};
+
+pub fn camera_plugin(app: &mut App) {
+    app.init_resource::<ChaseCamera>().add_systems(
+        Update,
+        (
+            (pan_camera, zoom_camera, focus_ship, toggle_chase_camera),
+            chase_shots,
+        )
+            .chain(),
+    );
+}
+
+/// Pan speed in pixels per second at 1x zoom.
+const PAN_SPEED: f32 = 600.0;
+/// How close to the edge of the window the pointer has to be to start panning.
+const EDGE_PAN_MARGIN: f32 = 12.0;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.0;
+/// See the comment on camera setup in `main.rs`.
+const CAMERA_OFFSET: Vec2 = Vec2::splat(0.25);
+/// How quickly the chase camera closes in on the shot it's following. Higher is snappier.
+const CHASE_RATE: f32 = 6.0;
+
+/// Follow our own shots in flight instead of staying put.
+#[derive(Resource, Default, Debug)]
+pub struct ChaseCamera {
+    pub enabled: bool,
+    /// Only follow shots from this weapon slot, or any of them if `None`.
+    pub weapon: Option<usize>,
+    /// The shot we're following until it lands.
+    following: Option<Entity>,
+}
+
+fn pan_camera(
+    window: Single<&Window>,
+    camera: Single<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    let (mut transform, projection) = camera.into_inner();
+    let mut dir = Vec2::ZERO;
+    if keys.pressed(KeyCode::ArrowLeft) {
+        dir.x -= 1.0;
+    }
+    if keys.pressed(KeyCode::ArrowRight) {
+        dir.x += 1.0;
+    }
+    if keys.pressed(KeyCode::ArrowDown) {
+        dir.y -= 1.0;
+    }
+    if keys.pressed(KeyCode::ArrowUp) {
+        dir.y += 1.0;
+    }
+    if let Some(cursor) = window.cursor_position() {
+        let size = window.size();
+        if cursor.x < EDGE_PAN_MARGIN {
+            dir.x -= 1.0;
+        } else if cursor.x > size.x - EDGE_PAN_MARGIN {
+            dir.x += 1.0;
+        }
+        // Window coordinates have y pointing down
+        if cursor.y < EDGE_PAN_MARGIN {
+            dir.y += 1.0;
+        } else if cursor.y > size.y - EDGE_PAN_MARGIN {
+            dir.y -= 1.0;
+        }
+    }
+    let delta = dir.clamp_length_max(1.0) * PAN_SPEED * projection.scale * time.delta_secs();
+    transform.translation += delta.extend(0.0);
+}
+
+fn zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    mut projection: Single<&mut OrthographicProjection, With<Camera2d>>,
+) {
+    for event in wheel.read() {
+        let scale = projection.scale * 1.1f32.powf(-event.y.signum());
+        projection.scale = scale.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+/// `Q` centers the view on our own ship, `E` on the enemy.
+fn focus_ship(
+    keys: Res<ButtonInput<KeyCode>>,
+    self_intel: Option<Single<&SelfIntel>>,
+    ships: Query<(Entity, &GlobalTransform), With<ShipIntel>>,
+    mut camera: Single<&mut Transform, With<Camera2d>>,
+) {
+    let Some(self_intel) = self_intel else {
+        return;
+    };
+    let target = if keys.just_pressed(KeyCode::KeyQ) {
+        ships.get(self_intel.ship).ok()
+    } else if keys.just_pressed(KeyCode::KeyE) {
+        ships.iter().find(|(e, _)| *e != self_intel.ship)
+    } else {
+        return;
+    };
+    if let Some((_, transform)) = target {
+        center_camera(&mut camera, transform.translation().xy());
+    }
+}
+
+fn toggle_chase_camera(keys: Res<ButtonInput<KeyCode>>, mut chase: ResMut<ChaseCamera>) {
+    if keys.just_pressed(KeyCode::KeyC) {
+        chase.enabled = !chase.enabled;
+    }
+}
+
+/// Ease the camera toward the shot we're following. Once it lands, move on to whichever matching
+/// shot was fired most recently.
+fn chase_shots(
+    mut chase: ResMut<ChaseCamera>,
+    self_intel: Option<Single<&SelfIntel>>,
+    shots: Query<(
+        Entity,
+        &FiredFrom,
+        &Progress,
+        Option<&Transform>,
+        Option<&BeamTarget>,
+    )>,
+    ships: Query<&GlobalTransform, With<ShipIntel>>,
+    mut camera: Single<&mut Transform, (With<Camera2d>, Without<FiredFrom>)>,
+    time: Res<Time>,
+) {
+    let Some(self_intel) = self_intel else {
+        return;
+    };
+    if !chase.enabled {
+        chase.following = None;
+        return;
+    }
+    let ours = |fired_from: &FiredFrom| {
+        fired_from.ship == self_intel.ship
+            && chase.weapon.is_none_or(|x| x == fired_from.weapon_index)
+    };
+    let still_following = chase
+        .following
+        .and_then(|x| shots.get(x).ok())
+        .filter(|(_, fired_from, ..)| ours(fired_from));
+    let Some((shot, _, _, transform, beam)) = still_following.or_else(|| {
+        shots
+            .iter()
+            .filter(|(_, fired_from, ..)| ours(fired_from))
+            .min_by(|(_, _, a, ..), (_, _, b, ..)| a.0.total_cmp(&b.0))
+    }) else {
+        chase.following = None;
+        return;
+    };
+    chase.following = Some(shot);
+    // Beams don't travel, so watch where they land instead
+    let focus = match (beam, transform) {
+        (Some(beam), _) => {
+            let Ok(target_ship) = ships.get(beam.ship) else {
+                return;
+            };
+            target_ship.transform_point(beam.start.extend(0.0)).xy()
+        }
+        (None, Some(transform)) => transform.translation.xy(),
+        (None, None) => return,
+    };
+    let current = camera.translation.xy() - CAMERA_OFFSET;
+    let t = 1.0 - (-CHASE_RATE * time.delta_secs()).exp();
+    center_camera(&mut camera, current.lerp(focus, t));
+}
+
+/// Move the camera so `pos` is in the middle of the view.
+pub fn center_camera(camera: &mut Transform, pos: Vec2) {
+    let pos = pos + CAMERA_OFFSET;
+    camera.translation = pos.extend(camera.translation.z);
+}
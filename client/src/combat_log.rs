@@ -0,0 +1,128 @@
+//! Running log of what's happened this match, fed by the [`CombatLogEntry`]s the server passes on
+//! as it writes them to its journal.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use common::{
+    bullets::HitQuality,
+    intel::SelfIntel,
+    journal::{CombatLogEntry, MatchEvent},
+    lobby::{MatchEpoch, Team},
+};
+
+/// Most entries shown in the panel. The whole match is kept for exporting once it's over.
+const MAX_ENTRIES: usize = 200;
+
+pub fn combat_log_plugin(app: &mut App) {
+    app.init_resource::<CombatLog>().add_systems(
+        Update,
+        (
+            clear_combat_log.run_if(resource_exists_and_changed::<MatchEpoch>),
+            receive_combat_log,
+            combat_log_panel,
+        )
+            .chain(),
+    );
+}
+
+/// This match's log, oldest first.
+#[derive(Resource, Deref, Default)]
+pub struct CombatLog(Vec<CombatLogEntry>);
+
+fn clear_combat_log(mut log: ResMut<CombatLog>) {
+    log.0.clear();
+}
+
+fn receive_combat_log(mut entries: EventReader<CombatLogEntry>, mut log: ResMut<CombatLog>) {
+    log.0.extend(entries.read().cloned());
+}
+
+fn combat_log_panel(
+    mut ui: EguiContexts,
+    log: Res<CombatLog>,
+    self_intel: Query<&SelfIntel>,
+    teams: Query<&Team>,
+) {
+    let Ok(self_intel) = self_intel.get_single() else {
+        return;
+    };
+    egui::Window::new("Combat log")
+        .anchor(egui::Align2::RIGHT_CENTER, egui::Vec2::ZERO)
+        .default_open(false)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    let shown = log.len().saturating_sub(MAX_ENTRIES);
+                    for CombatLogEntry { time, event } in &log[shown..] {
+                        let Some((ship, line)) = describe(event) else {
+                            continue;
+                        };
+                        let side = side(ship, self_intel.ship, &teams);
+                        ui.label(format!("{} [{side}] {line}", match_time(*time)));
+                    }
+                });
+        });
+}
+
+/// `secs` into the match as minutes and seconds, the way every match time is shown.
+pub fn match_time(secs: f32) -> String {
+    let secs = secs as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Whose `ship` is from the point of view of `own_ship`.
+pub fn side(ship: Entity, own_ship: Entity, teams: &Query<&Team>) -> &'static str {
+    let my_team = teams.get(own_ship).ok();
+    if ship == own_ship {
+        "You"
+    } else if my_team.is_some() && teams.get(ship).ok() == my_team {
+        "Ally"
+    } else {
+        "Enemy"
+    }
+}
+
+/// The ship `event` happened to and a line describing it, or `None` if it isn't worth mentioning.
+pub fn describe(event: &MatchEvent) -> Option<(Entity, String)> {
+    let entry = match *event {
+        MatchEvent::ShotFired { ship, weapon, .. } => {
+            (ship, format!("fired {}", weapon.common().name))
+        }
+        MatchEvent::DodgeRoll { .. } => return None,
+        MatchEvent::Dodged { target, .. } => (target, "dodged a shot".into()),
+        MatchEvent::ShieldHit { target, .. } => (target, "shields blocked a shot".into()),
+        MatchEvent::HullHit {
+            target,
+            damage,
+            quality,
+            ..
+        } => {
+            let quality = match quality {
+                HitQuality::Normal => "",
+                HitQuality::Crit => ", critical",
+                HitQuality::Graze => ", grazed",
+            };
+            (target, format!("took {damage} hull damage{quality}"))
+        }
+        MatchEvent::SystemDamaged {
+            target,
+            system,
+            destroyed,
+            ..
+        } => {
+            let what = if destroyed { "destroyed" } else { "damaged" };
+            (target, format!("{system} {what}"))
+        }
+        MatchEvent::FireStarted { target, .. } => (target, "fire broke out".into()),
+        MatchEvent::CrewDied { ship, ref name, .. } => (ship, format!("{name} died")),
+        MatchEvent::ShuttleShotDown { ship, .. } => (ship, "boarding shuttle shot down".into()),
+        MatchEvent::CrewCloned { ship, ref name } => (ship, format!("{name} was cloned")),
+        MatchEvent::HullRepaired { ship, amount } => (ship, format!("repaired {amount} hull")),
+        MatchEvent::ShipDestroyed { ship } => (ship, "destroyed".into()),
+        MatchEvent::WonTiebreak { ship } => (ship, "hit last and held together on 1 hull".into()),
+    };
+    Some(entry)
+}
@@ -0,0 +1,131 @@
+//! Numbers for chasing down protocol and performance problems: frame time, round trip time, how
+//! many entities the server is replicating to us and how often each kind of intel changes. `F12`
+//! toggles it, and it starts out shown in debug builds.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::RenetClient;
+use common::intel::{
+    CrewVisionIntel, DoorsIntel, EnemyWeaponChargeIntel, InteriorIntel, SelfIntel, ShipIntel,
+    SystemsIntel, WeaponChargeIntel,
+};
+
+/// How often the intel update rates are worked out.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+pub fn debug_overlay_plugin(app: &mut App) {
+    app.insert_resource(DebugOverlay(cfg!(debug_assertions)))
+        .init_resource::<IntelRates>()
+        .add_systems(
+            Update,
+            (
+                toggle_debug_overlay,
+                (count_intel_updates, debug_overlay).run_if(resource_equals(DebugOverlay(true))),
+            )
+                .chain(),
+        );
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugOverlay(pub bool);
+
+/// Intel components in the order [`IntelRates`] keeps them.
+const INTEL_NAMES: [&str; 8] = [
+    "SelfIntel",
+    "ShipIntel",
+    "CrewVisionIntel",
+    "InteriorIntel",
+    "WeaponChargeIntel",
+    "EnemyWeaponChargeIntel",
+    "SystemsIntel",
+    "DoorsIntel",
+];
+
+/// Changes seen to each intel component so far this window, and the rates from the last one.
+#[derive(Resource, Default)]
+struct IntelRates {
+    counts: [usize; INTEL_NAMES.len()],
+    rates: [f32; INTEL_NAMES.len()],
+    window: Duration,
+}
+
+fn toggle_debug_overlay(keys: Res<ButtonInput<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(KeyCode::F12) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+fn count_intel_updates(
+    self_intel: Query<(), Changed<SelfIntel>>,
+    ship_intel: Query<(), Changed<ShipIntel>>,
+    crew_vision: Query<(), Changed<CrewVisionIntel>>,
+    interior: Query<(), Changed<InteriorIntel>>,
+    weapon_charge: Query<(), Changed<WeaponChargeIntel>>,
+    enemy_weapon_charge: Query<(), Changed<EnemyWeaponChargeIntel>>,
+    systems: Query<(), Changed<SystemsIntel>>,
+    doors: Query<(), Changed<DoorsIntel>>,
+    mut rates: ResMut<IntelRates>,
+    time: Res<Time<Real>>,
+) {
+    let changed = [
+        self_intel.iter().count(),
+        ship_intel.iter().count(),
+        crew_vision.iter().count(),
+        interior.iter().count(),
+        weapon_charge.iter().count(),
+        enemy_weapon_charge.iter().count(),
+        systems.iter().count(),
+        doors.iter().count(),
+    ];
+    for (count, changed) in rates.counts.iter_mut().zip(changed) {
+        *count += changed;
+    }
+    rates.window += time.delta();
+    if rates.window >= RATE_WINDOW {
+        let secs = rates.window.as_secs_f32();
+        let counts = std::mem::take(&mut rates.counts);
+        rates.rates = counts.map(|x| x as f32 / secs);
+        rates.window = Duration::ZERO;
+    }
+}
+
+fn debug_overlay(
+    mut ui: EguiContexts,
+    replicated: Query<(), With<Replicated>>,
+    client: Option<Res<RenetClient>>,
+    rates: Res<IntelRates>,
+    time: Res<Time<Real>>,
+) {
+    egui::Window::new("Debug")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::ZERO)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            ui.label(format!(
+                "Frame: {:.1} ms",
+                time.delta().as_secs_f32() * 1000.0
+            ));
+            match client {
+                Some(client) if client.is_connected() => {
+                    ui.label(format!("RTT: {:.0} ms", client.rtt() * 1000.0));
+                }
+                _ => {
+                    ui.label("RTT: not connected");
+                }
+            }
+            ui.label(format!(
+                "Replicated entities: {}",
+                replicated.iter().count()
+            ));
+            ui.separator();
+            egui::Grid::new("intel_rates").show(ui, |ui| {
+                for (name, rate) in INTEL_NAMES.iter().zip(rates.rates) {
+                    ui.label(*name);
+                    ui.label(format!("{rate:.1}/s"));
+                    ui.end_row();
+                }
+            });
+        });
+}
@@ -1,26 +1,102 @@
-use crate::interaction::start_targeting;
-use bevy::{color::palettes::basic::*, prelude::*};
+use std::collections::HashMap;
+
+use crate::{
+    camera::{center_camera, ChaseCamera},
+    combat_log::{match_time, CombatLog},
+    graphics::CrewGraphic,
+    interaction::{start_group_targeting, start_targeting, BeamPreview, WeaponGroup},
+    kill_feed::{major_events, timeline_line},
+    log_export::{enemy_labels, save_export, ExportEntry, MatchExport},
+    power_hud::UsePowerHud,
+    profiles::profiles_ui,
+    select::{Selected, SelectionEnabled},
+    theme::{egui_theme, theme_ui, to_egui_color, ColorTheme, Status},
+    vent_guard::{vent_guard_ui, VentGuard},
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_egui::{
     egui::{self, Color32, RichText, Ui},
-    EguiContexts,
+    EguiContexts, EguiSettings,
 };
 use bevy_replicon::prelude::*;
 use common::{
-    compute_dodge_chance,
-    events::{AdjustPower, CrewStations, MoveWeapon, PowerDir, SetAutofire, WeaponPower},
-    intel::{SelfIntel, ShipIntel, SystemDamageIntel, SystemsIntel, WeaponChargeIntel},
-    lobby::{PlayerReady, ReadyState},
-    ship::{Dead, SystemId},
+    alarm::{Alarm, SUFFOCATION_WARNING},
+    augment::{AugmentId, MAX_AUGMENTS},
+    balance::BalanceConfig,
+    events::{
+        AdjustPower, CommandEvent, CrewStations, DepowerOrder, DoorAutomation, InstallWeapon,
+        MoveWeapon, PowerDir, QueuePower, RepairHull, SetAutofire, SetDepowerOrder,
+        SetDoorAutomation, SetMissileFloor, SetRepairPriority, ShipCommand, StoreWeapon,
+        WeaponPower,
+    },
+    fairness::DodgeCommitment,
+    hazard::{Hazard, HazardState, ToggleHazard},
+    intel::{
+        EnemyWeaponChargeIntel, SelfIntel, ShipIntel, SubsystemsIntel, SystemDamageIntel,
+        SystemIntel, SystemsIntel, WeaponChargeIntel,
+    },
+    journal::MatchSummary,
+    lobby::{
+        ChooseTeam, CustomizeCrew, FinalResult, PlayerReady, ReadyState, RequestRematch, Team,
+        MAX_CREW_NAME, MAX_TEAMS,
+    },
+    match_clock::{MatchClock, SuddenDeath, TimeLimit, MAX_TIME_LIMIT_MINUTES},
+    profile::PlayerProfiles,
+    rules::{
+        LobbyHost, MatchRules, Round, RulesPreset, SensorRule, SetMatchRules, Tiebreak, MAX_HULL,
+        MAX_LOADOUT, MAX_REACTOR, MAX_ROUNDS, MAX_SYSTEM_UPGRADES,
+    },
+    ship::{Dead, Quadrant, SubsystemId, SystemId, SHIPS},
+    stats::{MatchStats, PlayerStats},
+    time_scale::{RequestTimeScale, TimeScale, TIME_SCALES},
     util::round_to_usize,
-    weapon::WeaponId,
-    RACES,
+    weapon::{ShieldRule, WeaponId},
+    Crew, CrewColor, RACES,
 };
+use strum::IntoEnumIterator;
+
+/// Scale factor for every egui panel on top of the window's own DPI scaling. Adjust with
+/// `Ctrl+=`/`Ctrl+-`, and reset with `Ctrl+0`.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PanelScale(pub f32);
+
+impl Default for PanelScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+pub fn adjust_panel_scale(keys: Res<ButtonInput<KeyCode>>, mut scale: ResMut<PanelScale>) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        scale.0 = (scale.0 + 0.1).min(2.0);
+    } else if keys.just_pressed(KeyCode::Minus) {
+        scale.0 = (scale.0 - 0.1).max(0.5);
+    } else if keys.just_pressed(KeyCode::Digit0) {
+        *scale = default();
+    }
+}
+
+pub fn apply_panel_scale(scale: Res<PanelScale>, mut settings: Query<&mut EguiSettings>) {
+    for mut settings in &mut settings {
+        settings.scale_factor = scale.0;
+    }
+}
 
 pub fn status_panel(
     mut ui: EguiContexts,
     self_intel: Query<&SelfIntel>,
     ships: Query<&ShipIntel, Without<Dead>>,
     systems: Query<&SystemsIntel>,
+    time_scale: Option<Res<TimeScale>>,
+    mut request_time_scale: EventWriter<RequestTimeScale>,
+    mut theme: ResMut<ColorTheme>,
+    mut power_hud: ResMut<UsePowerHud>,
+    mut vent_guard: ResMut<VentGuard>,
+    mut repair_hull: EventWriter<ShipCommand<RepairHull>>,
+    mut set_door_automation: EventWriter<ShipCommand<SetDoorAutomation>>,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         // No connection to server
@@ -41,37 +117,149 @@ pub fn status_panel(
                 let max = intel.basic.max_hull;
                 let current = intel.basic.hull;
                 let percent = current as f32 / max as f32;
-                let color = if percent > 0.66 {
-                    Color32::GREEN
+                let status = if percent > 0.66 {
+                    Status::Good
                 } else if percent > 0.33 {
-                    Color32::YELLOW
+                    Status::Warning
                 } else {
-                    Color32::RED
+                    Status::Bad
                 };
                 ui.add(
                     egui::ProgressBar::new(percent)
                         .desired_width(400.0)
                         .rounding(0.0)
-                        .fill(color),
+                        .fill(theme.status_egui(status)),
                 );
                 ui.label(format!("{current}/{max}"));
+                if self_intel.hull_repairs > 0 {
+                    let ready = self_intel.hull_repair_cooldown <= 0.0 && current < max;
+                    let label = if self_intel.hull_repair_cooldown > 0.0 {
+                        format!("Repair ({}s)", self_intel.hull_repair_cooldown.ceil())
+                    } else {
+                        format!("Repair ({} left)", self_intel.hull_repairs)
+                    };
+                    let button = ui
+                        .add_enabled(ready, egui::Button::new(label))
+                        // Our own copy of the balance numbers again, see the dodge chance below
+                        .on_hover_text(format!(
+                            "Patch up {} hull",
+                            BalanceConfig::default().hull_repair_amount
+                        ));
+                    if button.clicked() {
+                        repair_hull.send(RepairHull.for_ship(self_intel.ship));
+                    }
+                }
             });
             if let Some(engines) = systems.get(&SystemId::Engines) {
-                let dodge_chance = compute_dodge_chance(engines.current_power);
-                ui.label(format!("Dodge Chance: {dodge_chance}%"));
+                // Worked out from our own copy of the balance numbers, which might not be the ones
+                // the server uses
+                let balance = BalanceConfig::default();
+                let piloted = !matches!(
+                    intel.basic.subsystems.get(&SubsystemId::Piloting),
+                    Some(SystemDamageIntel::Destroyed)
+                );
+                let dodge_chance = if piloted {
+                    balance.dodge_chance(engines.current_power)
+                } else {
+                    0
+                };
+                if self_intel.balance_hash == balance.hash() {
+                    ui.label(format!("Dodge Chance: {dodge_chance}%"));
+                } else {
+                    ui.label(format!("Dodge Chance: ~{dodge_chance}%"))
+                        .on_hover_text(
+                            "The server is using different balance numbers, so this may be off.",
+                        );
+                }
             }
-            let mut oxygen_text =
-                RichText::new(format!("Oxygen: {}%", (self_intel.oxygen * 100.0).round()));
-            if self_intel.oxygen < 0.25 {
-                oxygen_text = oxygen_text.color(Color32::RED);
+            let mut oxygen_text = match self_intel.suffocation_in {
+                Some(x) if x < SUFFOCATION_WARNING => RichText::new(format!(
+                    "Oxygen: {}% ({}s left)",
+                    (self_intel.oxygen * 100.0).round(),
+                    x.ceil()
+                )),
+                _ => RichText::new(format!("Oxygen: {}%", (self_intel.oxygen * 100.0).round())),
+            };
+            if self_intel.alarms.contains(&Alarm::LowOxygen) {
+                oxygen_text = oxygen_text.color(theme.status_egui(Status::Bad));
             }
             ui.label(oxygen_text);
-            let mut missile_text = RichText::new(format!("Missiles: {}", self_intel.missiles));
+            ui.horizontal(|ui| {
+                let mut automation = self_intel.door_automation;
+                ui.label("Door automation:");
+                egui::ComboBox::from_id_salt("door_automation")
+                    .selected_text(automation.name())
+                    .show_ui(ui, |ui| {
+                        for x in DoorAutomation::iter() {
+                            ui.selectable_value(&mut automation, x, x.name());
+                        }
+                    });
+                if automation != self_intel.door_automation {
+                    set_door_automation
+                        .send(SetDoorAutomation(automation).for_ship(self_intel.ship));
+                }
+            })
+            .response
+            .on_hover_text(
+                "Shut rooms that catch fire or get breached, and optionally vent empty burning \
+                 rooms, while door control is up.",
+            );
+            let free_missiles = self_intel
+                .missiles
+                .saturating_sub(self_intel.reserved_missiles);
+            let mut missile_text = RichText::new(format!(
+                "Missiles: {free_missiles} free, {} reserved",
+                self_intel.reserved_missiles
+            ));
             if self_intel.missiles < 4 {
-                missile_text = missile_text.color(Color32::RED);
+                missile_text = missile_text.color(theme.status_egui(Status::Bad));
             }
             ui.label(missile_text);
+            ui.label(format!("Scrap: {}", self_intel.scrap));
+            for augment in &self_intel.augments {
+                ui.label(augment.name())
+                    .on_hover_text(augment.description());
+            }
+            let time_scale = time_scale.map_or(1.0, |x| **x);
+            time_scale_ui(ui, time_scale, &mut request_time_scale);
+            ui.collapsing("Display", |ui| {
+                let mut edited = *theme;
+                theme_ui(ui, &mut edited);
+                theme.set_if_neq(edited);
+                let mut enabled = power_hud.0;
+                ui.checkbox(&mut enabled, "Power bars on the HUD")
+                    .on_hover_text("Instead of the power window. Right click a bar to take power.");
+                power_hud.set_if_neq(UsePowerHud(enabled));
+                let mut guard = *vent_guard;
+                vent_guard_ui(ui, &mut guard);
+                vent_guard.set_if_neq(guard);
+            });
+        });
+}
+
+/// Buttons to step the game speed through [`TIME_SCALES`]. Outside of local matches the other
+/// players have to ask for the same speed before it changes.
+fn time_scale_ui(
+    ui: &mut Ui,
+    time_scale: f32,
+    request_time_scale: &mut EventWriter<RequestTimeScale>,
+) {
+    let current = TIME_SCALES.iter().position(|&x| x == time_scale);
+    ui.horizontal(|ui| {
+        let slower = current.and_then(|x| x.checked_sub(1));
+        ui.add_enabled_ui(slower.is_some(), |ui| {
+            if ui.button("-").clicked() {
+                request_time_scale.send(RequestTimeScale(TIME_SCALES[slower.unwrap()]));
+            }
+        });
+        ui.label(format!("Speed: {time_scale}x"));
+        let faster = current.map(|x| x + 1).filter(|&x| x < TIME_SCALES.len());
+        ui.add_enabled_ui(faster.is_some(), |ui| {
+            if ui.button("+").clicked() {
+                request_time_scale.send(RequestTimeScale(TIME_SCALES[faster.unwrap()]));
+            }
         });
+    });
 }
 
 pub fn power_panel(
@@ -79,7 +267,10 @@ pub fn power_panel(
     self_intel: Query<&SelfIntel>,
     ships: Query<&ShipIntel, Without<Dead>>,
     systems: Query<&SystemsIntel>,
-    mut adjust_power: EventWriter<AdjustPower>,
+    subsystems: Query<&SubsystemsIntel>,
+    mut adjust_power: EventWriter<ShipCommand<AdjustPower>>,
+    mut queue_power: EventWriter<ShipCommand<QueuePower>>,
+    mut set_repair_priority: EventWriter<ShipCommand<SetRepairPriority>>,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         // No connection to server
@@ -90,6 +281,7 @@ pub fn power_panel(
         return;
     };
     let systems = systems.get(intel.systems).unwrap();
+    let subsystems = subsystems.get(intel.systems).ok();
     egui::Window::new("Power")
         .anchor(egui::Align2::LEFT_BOTTOM, egui::Vec2::ZERO)
         .title_bar(false)
@@ -106,71 +298,142 @@ pub fn power_panel(
                 }
             });
 
-            if let Some(shields) = systems.get(&SystemId::Shields) {
-                ui.label("[A] Shields");
-                if let Some(request) = power_bar(
-                    ui,
-                    shields.current_power,
-                    shields.upgrade_level,
-                    shields.damage,
-                    SystemId::Shields,
-                ) {
-                    adjust_power.send(request);
-                }
-            }
-            if let Some(engines) = systems.get(&SystemId::Engines) {
-                ui.label("[S] Engines");
-                if let Some(request) = power_bar(
-                    ui,
-                    engines.current_power,
-                    engines.upgrade_level,
-                    engines.damage,
-                    SystemId::Engines,
-                ) {
-                    adjust_power.send(request);
+            for (system, label) in [
+                (SystemId::Shields, "[A] Shields"),
+                (SystemId::Engines, "[S] Engines"),
+                (SystemId::Weapons, "[W] Weapons"),
+                (SystemId::Oxygen, "[F] Oxygen"),
+                (SystemId::CloneBay, "[B] Clone bay"),
+            ] {
+                let Some(intel) = systems.get(&system) else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    let priority = &self_intel.repair_priority;
+                    if let Some(priority) = repair_priority_buttons(ui, priority, system) {
+                        set_repair_priority
+                            .send(SetRepairPriority(priority).for_ship(self_intel.ship));
+                    }
+                });
+                let queued = self_intel.power_queue.contains(&system);
+                match power_bar(ui, intel, system, self_intel.free_power, queued) {
+                    Some(PowerInput::Adjust(request)) => {
+                        adjust_power.send(request.for_ship(self_intel.ship));
+                    }
+                    Some(PowerInput::Queue(request)) => {
+                        queue_power.send(request.for_ship(self_intel.ship));
+                    }
+                    None => {}
                 }
             }
-            if let Some(weapons) = systems.get(&SystemId::Weapons) {
-                ui.label("[W] Weapons");
-                if let Some(request) = power_bar(
-                    ui,
-                    weapons.current_power,
-                    weapons.upgrade_level,
-                    weapons.damage,
-                    SystemId::Weapons,
-                ) {
-                    adjust_power.send(request);
-                }
+
+            let subsystems = [
+                (SubsystemId::Piloting, "Piloting"),
+                (SubsystemId::Doors, "Doors"),
+                (SubsystemId::Sensors, "Sensors"),
+            ]
+            .into_iter()
+            .filter_map(|(x, label)| Some((subsystems?.get(&x)?, label)))
+            .collect::<Vec<_>>();
+            if !subsystems.is_empty() {
+                ui.separator();
             }
-            if let Some(oxygen) = systems.get(&SystemId::Oxygen) {
-                ui.label("[F] Oxygen");
-                if let Some(request) = power_bar(
-                    ui,
-                    oxygen.current_power,
-                    oxygen.upgrade_level,
-                    oxygen.damage,
-                    SystemId::Oxygen,
-                ) {
-                    adjust_power.send(request);
-                }
+            for (intel, label) in subsystems {
+                ui.label(label);
+                subsystem_bar(ui, intel);
             }
         });
 }
 
-#[allow(unused_must_use)]
-fn power_bar(
+/// Where `system` stands in the repair priority list, with buttons to list it, move it up or take
+/// it off. Returns the new list if any of them were clicked.
+fn repair_priority_buttons(
     ui: &mut Ui,
-    current: usize,
-    max: usize,
-    damage: usize,
+    priority: &[SystemId],
     system: SystemId,
-) -> Option<AdjustPower> {
-    let hotkey = match system {
+) -> Option<Vec<SystemId>> {
+    let mut priority = priority.to_vec();
+    let Some(rank) = priority.iter().position(|&x| x == system) else {
+        let add = ui
+            .small_button("+")
+            .on_hover_text("Send crew to repair this when it's damaged");
+        if add.clicked() {
+            priority.push(system);
+            return Some(priority);
+        }
+        return None;
+    };
+    ui.weak(format!("Repair #{}", rank + 1));
+    if rank > 0
+        && ui
+            .small_button("^")
+            .on_hover_text("Repair sooner")
+            .clicked()
+    {
+        priority.swap(rank - 1, rank);
+        return Some(priority);
+    }
+    if ui
+        .small_button("x")
+        .on_hover_text("Stop sending crew")
+        .clicked()
+    {
+        priority.remove(rank);
+        return Some(priority);
+    }
+    None
+}
+
+/// Like [`power_bar`] without the controls. Subsystems don't take reactor power, so all there is
+/// to show is how much of them is still in one piece.
+#[allow(unused_must_use)]
+fn subsystem_bar(ui: &mut Ui, intel: &SystemIntel) {
+    ui.horizontal(|ui| {
+        for _ in intel.damage..intel.upgrade_level {
+            ui.selectable_label(true, "O");
+        }
+        ui.add_enabled_ui(false, |ui| {
+            for _ in 0..intel.damage {
+                ui.selectable_label(false, "X");
+            }
+        });
+    });
+}
+
+/// Key that adds power to `system`, or removes it with Shift held.
+pub fn power_hotkey(system: SystemId) -> char {
+    match system {
         SystemId::Shields => 'A',
         SystemId::Weapons => 'W',
         SystemId::Engines => 'S',
         SystemId::Oxygen => 'F',
-    };
+        SystemId::CloneBay => 'B',
+    }
+}
+
+enum PowerInput {
+    Adjust(AdjustPower),
+    Queue(QueuePower),
+}
+
+/// When the reactor is empty, asking for more power queues the request instead of failing.
+/// Queued requests show up as a hollow pip, which can be clicked to cancel.
+#[allow(unused_must_use)]
+fn power_bar(
+    ui: &mut Ui,
+    intel: &SystemIntel,
+    system: SystemId,
+    free_power: usize,
+    queued: bool,
+) -> Option<PowerInput> {
+    let SystemIntel {
+        current_power: current,
+        upgrade_level: max,
+        damage,
+        ..
+    } = *intel;
+    let hotkey = power_hotkey(system);
     let mut result = None;
     ui.horizontal(|ui| {
         if ui
@@ -178,20 +441,41 @@ fn power_bar(
             .on_hover_text(format!("Remove power (Hotkey: Shift+{hotkey})"))
             .clicked()
         {
-            result = Some(AdjustPower::remove(system));
+            result = Some(PowerInput::Adjust(AdjustPower::remove(system)));
         }
-        if ui
-            .button("+")
-            .on_hover_text(format!("Add power (Hotkey: {hotkey})"))
-            .clicked()
-        {
-            result = Some(AdjustPower::request(system));
+        let hover = if free_power == 0 {
+            format!("Queue power for when the reactor frees up (Hotkey: {hotkey})")
+        } else {
+            format!("Add power (Hotkey: {hotkey})")
+        };
+        if ui.button("+").on_hover_text(hover).clicked() {
+            result = Some(if free_power == 0 {
+                PowerInput::Queue(QueuePower {
+                    system,
+                    queued: true,
+                })
+            } else {
+                PowerInput::Adjust(AdjustPower::request(system))
+            });
         }
         for _ in 0..current {
             ui.selectable_label(true, "O");
         }
         let undamaged = max - damage;
-        for _ in current..undamaged {
+        for i in current..undamaged {
+            if queued && i == current {
+                if ui
+                    .selectable_label(false, "o")
+                    .on_hover_text("Waiting for reactor power, click to cancel")
+                    .clicked()
+                {
+                    result = Some(PowerInput::Queue(QueuePower {
+                        system,
+                        queued: false,
+                    }));
+                }
+                continue;
+            }
             ui.selectable_label(false, "O");
         }
         ui.add_enabled_ui(false, |ui| {
@@ -238,6 +522,12 @@ pub fn shields_panel(
                     .desired_width(125.0)
                     .rounding(0.0),
             );
+            if shields.super_shield > 0 {
+                ui.colored_label(
+                    egui::Color32::LIGHT_GREEN,
+                    format!("Super shield {}", shields.super_shield),
+                );
+            }
         });
 }
 
@@ -245,8 +535,24 @@ pub fn ready_panel(
     mut ui: EguiContexts,
     ready_state: Res<ReadyState>,
     mut client_ready: EventWriter<PlayerReady>,
+    mut choose_team: EventWriter<ChooseTeam>,
     client: Res<RepliconClient>,
+    self_intel: Query<&SelfIntel>,
+    teams: Query<&Team>,
+    hazards: Option<Res<HazardState>>,
+    mut toggle_hazard: EventWriter<ToggleHazard>,
+    rules: Option<Res<MatchRules>>,
+    host: Option<Res<LobbyHost>>,
+    round: Option<Res<Round>>,
+    mut set_rules: EventWriter<SetMatchRules>,
+    profiles: Option<Res<PlayerProfiles>>,
+    mut customize_crew: EventWriter<CustomizeCrew>,
+    mut crew_names: Local<Vec<String>>,
 ) {
+    let my_team = self_intel
+        .get_single()
+        .ok()
+        .and_then(|x| teams.get(x.ship).ok());
     if let Some(client_id) = client.id() {
         egui::Window::new("Ready phase")
             .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
@@ -254,6 +560,43 @@ pub fn ready_panel(
             .resizable(false)
             .show(ui.ctx_mut(), |ui| match ready_state.as_ref() {
                 ReadyState::AwaitingClients { ready_clients } => {
+                    ui.horizontal(|ui| {
+                        ui.label("Team:");
+                        for team in (0..MAX_TEAMS).map(Team) {
+                            let selected = my_team == Some(&team);
+                            if ui
+                                .selectable_label(selected, (team.0 + 1).to_string())
+                                .clicked()
+                            {
+                                choose_team.send(ChooseTeam(team));
+                            }
+                        }
+                    });
+                    if let Ok(self_intel) = self_intel.get_single() {
+                        crew_ui(ui, &self_intel.crew, &mut crew_names, &mut customize_crew);
+                    }
+                    for hazard in Hazard::iter() {
+                        let enabled = hazards.as_ref().is_some_and(|x| x.is_enabled(hazard));
+                        let mut checked = enabled;
+                        ui.checkbox(&mut checked, hazard.name())
+                            .on_hover_text(hazard.description());
+                        if checked != enabled {
+                            toggle_hazard.send(ToggleHazard(hazard));
+                        }
+                    }
+                    if let Some(rules) = &rules {
+                        let is_host = host.as_ref().is_some_and(|x| x.0 == Some(client_id));
+                        ui.separator();
+                        match_rules_ui(ui, rules, is_host, &mut set_rules);
+                        if let Some(round) = round.as_ref().filter(|_| rules.rounds > 1) {
+                            ui.label(format!("Round {} of {}", round.0, rules.rounds));
+                        }
+                        ui.separator();
+                    }
+                    if let Some(profiles) = &profiles {
+                        profiles_ui(ui, profiles, client_id);
+                        ui.separator();
+                    }
                     if ready_clients.contains(&client_id) {
                         ui.label("Waiting for players...");
                     } else {
@@ -269,28 +612,462 @@ pub fn ready_panel(
     }
 }
 
-pub fn weapon_rearrange_ui(
+/// Rename and recolor our crew before the match. Names are sent once the player's done typing.
+fn crew_ui(
     ui: &mut Ui,
-    index: usize,
-    last_weapon: usize,
-    weapon_ordering: &mut EventWriter<MoveWeapon>,
+    crew: &[Crew],
+    names: &mut Vec<String>,
+    customize_crew: &mut EventWriter<CustomizeCrew>,
 ) {
-    ui.add_enabled_ui(index > 0, |ui| {
-        if ui.button("⬆").clicked() {
-            weapon_ordering.send(MoveWeapon {
-                weapon_index: index,
-                target_index: index - 1,
+    names.resize(crew.len(), String::new());
+    egui::Grid::new("crew_customization").show(ui, |ui| {
+        for (index, (crew, name)) in crew.iter().zip(names.iter_mut()).enumerate() {
+            let edit = ui.add(egui::TextEdit::singleline(name).char_limit(MAX_CREW_NAME));
+            let mut color = crew.color;
+            egui::ComboBox::from_id_salt(("crew_color", index))
+                .selected_text(RichText::new(color.name()).color(crew_color(color)))
+                .show_ui(ui, |ui| {
+                    for x in CrewColor::iter() {
+                        let text = RichText::new(x.name()).color(crew_color(x));
+                        ui.selectable_value(&mut color, x, text);
+                    }
+                });
+            ui.end_row();
+            let renamed = edit.lost_focus() && name.trim() != crew.name;
+            if renamed || color != crew.color {
+                customize_crew.send(CustomizeCrew {
+                    crew: index,
+                    name: if renamed {
+                        name.clone()
+                    } else {
+                        crew.name.clone()
+                    },
+                    color,
+                });
+            } else if !edit.has_focus() {
+                // Keep up with what the server has, including a name it turned down
+                name.clone_from(&crew.name);
+            }
+        }
+    });
+}
+
+fn crew_color(color: CrewColor) -> Color32 {
+    let [r, g, b] = color.rgb().map(|x| (x * 255.0) as u8);
+    Color32::from_rgb(r, g, b)
+}
+
+/// The rules for the coming match. Only the host can change them, everyone else just gets to look.
+fn match_rules_ui(
+    ui: &mut Ui,
+    rules: &MatchRules,
+    is_host: bool,
+    set_rules: &mut EventWriter<SetMatchRules>,
+) {
+    let mut edited = rules.clone();
+    ui.add_enabled_ui(is_host, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Rules:");
+            for preset in RulesPreset::iter() {
+                let selected = rules.preset() == Some(preset);
+                if ui
+                    .selectable_label(selected, preset.name())
+                    .on_hover_text(preset.description())
+                    .clicked()
+                {
+                    edited = preset.rules();
+                }
+            }
+        });
+        egui::Grid::new("match_rules").show(ui, |ui| {
+            ui.label("Reactor");
+            ui.add(egui::Slider::new(&mut edited.reactor, 1..=MAX_REACTOR));
+            ui.end_row();
+            ui.label("System upgrades");
+            ui.add(egui::Slider::new(
+                &mut edited.system_upgrades,
+                0..=MAX_SYSTEM_UPGRADES,
+            ));
+            ui.end_row();
+            ui.label("Hull");
+            ui.add(egui::Slider::new(&mut edited.hull, 1..=MAX_HULL));
+            ui.end_row();
+            ui.label("Scrap");
+            ui.add(egui::DragValue::new(&mut edited.scrap).range(0..=999));
+            ui.end_row();
+            ui.label("Rounds");
+            ui.add(egui::Slider::new(&mut edited.rounds, 1..=MAX_ROUNDS));
+            ui.end_row();
+            ui.label("Sensors");
+            egui::ComboBox::from_id_salt("sensor_rule")
+                .selected_text(edited.sensors.name())
+                .show_ui(ui, |ui| {
+                    for rule in SensorRule::iter() {
+                        ui.selectable_value(&mut edited.sensors, rule, rule.name());
+                    }
+                });
+            ui.end_row();
+            ui.label("Time limit");
+            ui.horizontal(|ui| {
+                let mut limited = edited.time_limit.is_some();
+                ui.checkbox(&mut limited, "");
+                if limited != edited.time_limit.is_some() {
+                    edited.time_limit = limited.then_some(TimeLimit {
+                        minutes: 10,
+                        sudden_death: SuddenDeath::HullBurn,
+                    });
+                }
+                if let Some(limit) = &mut edited.time_limit {
+                    ui.add(
+                        egui::Slider::new(&mut limit.minutes, 1..=MAX_TIME_LIMIT_MINUTES)
+                            .suffix(" min"),
+                    );
+                    egui::ComboBox::from_id_salt("sudden_death")
+                        .selected_text(limit.sudden_death.name())
+                        .show_ui(ui, |ui| {
+                            for rule in SuddenDeath::iter() {
+                                ui.selectable_value(&mut limit.sudden_death, rule, rule.name())
+                                    .on_hover_text(rule.description());
+                            }
+                        });
+                }
             });
+            ui.end_row();
+            ui.label("Tiebreak");
+            egui::ComboBox::from_id_salt("tiebreak")
+                .selected_text(edited.tiebreak.name())
+                .show_ui(ui, |ui| {
+                    for rule in Tiebreak::iter() {
+                        ui.selectable_value(&mut edited.tiebreak, rule, rule.name())
+                            .on_hover_text(rule.description());
+                    }
+                });
+            ui.end_row();
+        });
+        ui.label("Weapons:");
+        for weapon in WeaponId::all() {
+            let installed = edited.weapons.contains(&weapon);
+            let mut checked = installed;
+            let room = installed || edited.weapons.len() < MAX_LOADOUT;
+            ui.add_enabled(
+                room,
+                egui::Checkbox::new(&mut checked, weapon.common().name),
+            )
+            .on_hover_ui(|ui| weapon_tooltip(ui, weapon));
+            if checked && !installed {
+                edited.weapons.push(weapon);
+            } else if !checked && installed {
+                edited.weapons.retain(|&x| x != weapon);
+            }
+        }
+        ui.label("Augments:");
+        for augment in AugmentId::iter() {
+            let installed = edited.augments.contains(&augment);
+            let mut checked = installed;
+            let room = installed || edited.augments.len() < MAX_AUGMENTS;
+            ui.add_enabled(room, egui::Checkbox::new(&mut checked, augment.name()))
+                .on_hover_text(augment.description());
+            if checked && !installed {
+                edited.augments.push(augment);
+            } else if !checked && installed {
+                edited.augments.retain(|&x| x != augment);
+            }
         }
     });
-    ui.add_enabled_ui(index < last_weapon, |ui| {
-        if ui.button("⬇").clicked() {
-            weapon_ordering.send(MoveWeapon {
-                weapon_index: index,
-                target_index: index + 1,
+    if edited != *rules && edited.validate().is_ok() {
+        set_rules.send(SetMatchRules(edited));
+    }
+}
+
+/// Shown once the match is over: who won, both players' stats side by side, a timeline of the
+/// match's big moments, and a button to go again.
+pub fn post_game_panel(
+    mut ui: EguiContexts,
+    summary: Res<MatchSummary>,
+    stats: Option<Res<MatchStats>>,
+    self_intel: Single<&SelfIntel>,
+    teams: Query<&Team>,
+    client: Res<RepliconClient>,
+    mut rematch: EventWriter<RequestRematch>,
+    mut requested: Local<bool>,
+    rules: Option<Res<MatchRules>>,
+    round: Option<Res<Round>>,
+    commitment: Option<Res<DodgeCommitment>>,
+    log: Res<CombatLog>,
+    mut export_status: Local<Option<String>>,
+) {
+    if summary.is_added() {
+        *requested = false;
+        *export_status = None;
+    }
+    let Some(client_id) = client.id() else {
+        return;
+    };
+    let my_team = teams.get(self_intel.ship).ok();
+    // Copilots see their captain's line
+    let mine = stats
+        .as_ref()
+        .and_then(|x| x.player(client_id))
+        .copied()
+        .unwrap_or_default();
+    let mut theirs = stats
+        .as_ref()
+        .map(|x| x.opponents(client_id))
+        .unwrap_or_default();
+    if theirs.is_empty() {
+        theirs.push(default());
+    }
+    egui::Window::new("Match over")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .title_bar(false)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            let headline = match summary.result.winner() {
+                Some(winner) if Some(&winner) == my_team => "Victory",
+                Some(_) => "Defeat",
+                None => "Draw",
+            };
+            ui.label(RichText::new(headline).size(32.0).strong());
+            if let FinalResult::WonTiebreak(_) = summary.result {
+                ui.label("Both ships went down together, decided on the last hull hit");
+            }
+            ui.label(format!(
+                "Match length: {}",
+                match_time(summary.duration.as_secs_f32())
+            ));
+            if let (Some(rules), Some(round)) = (&rules, &round) {
+                if rules.rounds > 1 {
+                    ui.label(format!("Round {} of {}", round.0, rules.rounds));
+                }
+            }
+            let seed = summary.dodge_seed.iter().map(|x| format!("{x:02x}"));
+            let seed = seed.collect::<String>();
+            let dodge_label = if commitment
+                .as_ref()
+                .is_some_and(|x| x.matches(&summary.dodge_seed))
+            {
+                ui.label("Dodge rolls: seed checks out")
+            } else {
+                ui.colored_label(
+                    Color32::RED,
+                    "Dodge rolls: seed doesn't match the commitment",
+                )
+            };
+            dodge_label.on_hover_text(format!(
+                "The server committed to this match's dodge seed before it started and revealed it \
+                at the end: {seed}. Every roll in the match journal can be replayed from it."
+            ));
+            egui::Grid::new("post_game_stats")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("You");
+                    for label in enemy_labels(theirs.len()) {
+                        ui.label(label);
+                    }
+                    ui.end_row();
+                    let mut row = |label: &str, f: &dyn Fn(&PlayerStats) -> String| {
+                        ui.label(label);
+                        ui.label(f(&mine));
+                        for enemy in &theirs {
+                            ui.label(f(enemy));
+                        }
+                        ui.end_row();
+                    };
+                    row("Damage dealt", &|x| x.damage_dealt.to_string());
+                    row("Damage taken", &|x| x.damage_taken.to_string());
+                    row("Shots fired", &|x| x.shots_fired.to_string());
+                    row("Hit rate", &|x| {
+                        format!("{}%", round_to_usize(x.hit_rate() * 100.0))
+                    });
+                    row("Crew kills", &|x| x.crew_kills.to_string());
+                    row("Systems destroyed", &|x| x.systems_destroyed.to_string());
+                    row("Oxygen downtime", &|x| {
+                        format!("{}s", x.oxygen_downtime.as_secs())
+                    });
+                });
+            ui.collapsing("Timeline", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for event in major_events(log.iter()) {
+                            ui.label(timeline_line(&event, self_intel.ship, &teams));
+                        }
+                    });
             });
+            let export = || MatchExport {
+                result: headline,
+                duration: summary.duration.as_secs_f32(),
+                you: mine,
+                enemies: theirs.clone(),
+                log: ExportEntry::from_log(log.iter(), self_intel.ship, &teams),
+            };
+            ui.horizontal(|ui| {
+                let mut save = |contents: String, extension| {
+                    *export_status = Some(match save_export(&contents, extension) {
+                        Ok(path) => format!("Saved to {}", path.display()),
+                        Err(e) => format!("Couldn't save: {e}"),
+                    });
+                };
+                if ui.button("Export JSON").clicked() {
+                    save(export().to_json(), "json");
+                }
+                if ui.button("Export CSV").clicked() {
+                    save(export().to_csv(), "csv");
+                }
+                if ui
+                    .button("Copy")
+                    .on_hover_text("Copy the stats and log as CSV")
+                    .clicked()
+                {
+                    ui.ctx().copy_text(export().to_csv());
+                    *export_status = Some("Copied to clipboard".into());
+                }
+            });
+            if let Some(status) = &*export_status {
+                ui.weak(status);
+            }
+            if *requested {
+                ui.label("Waiting for opponent...");
+            } else if ui.button("Rematch").clicked() {
+                rematch.send(default());
+                *requested = true;
+            }
+        });
+}
+
+/// Time left in the match, big and center top so nobody misses sudden death coming.
+pub fn match_clock_label(mut ui: EguiContexts, clock: Res<MatchClock>, theme: Res<ColorTheme>) {
+    let color = match *clock {
+        MatchClock::Remaining(secs) if secs > 60 => Color32::WHITE,
+        MatchClock::Remaining(_) => theme.status_egui(Status::Warning),
+        MatchClock::SuddenDeath => theme.alert_egui(),
+    };
+    egui::Area::new(egui::Id::new("match_clock"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+        .interactable(false)
+        .show(ui.ctx_mut(), |ui| {
+            ui.label(
+                RichText::new(clock.label())
+                    .size(28.0)
+                    .strong()
+                    .color(color),
+            );
+        });
+}
+
+/// How many rooms the beam being aimed would hit, next to the cursor.
+pub fn beam_preview_label(mut ui: EguiContexts, preview: Res<BeamPreview>) {
+    let ctx = ui.ctx_mut();
+    let Some(pointer) = ctx.pointer_hover_pos() else {
+        return;
+    };
+    egui::Area::new(egui::Id::new("beam_preview"))
+        .fixed_pos(pointer + egui::vec2(16.0, 16.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            let rooms = preview.rooms.len();
+            let plural = if rooms == 1 { "" } else { "s" };
+            ui.label(format!("{rooms} room{plural}"));
+        });
+}
+
+/// Drag-and-drop payload for a weapon row being moved to another slot.
+struct DraggedWeapon(usize);
+
+/// How long in seconds a weapon row stays highlighted after it changes slots.
+const REORDER_FADE: f32 = 1.0;
+
+/// Commands for how the weapons system runs itself, bundled to keep [`weapons_panel`] within the
+/// system parameter limit.
+#[derive(SystemParam)]
+pub struct WeaponSettings<'w> {
+    autofire: EventWriter<'w, ShipCommand<SetAutofire>>,
+    missile_floor: EventWriter<'w, ShipCommand<SetMissileFloor>>,
+    depower_order: EventWriter<'w, ShipCommand<SetDepowerOrder>>,
+}
+
+/// Weapon order as of the last frame, so rows that changed slots can be highlighted. Weapons are
+/// told apart by name, which is enough to spot a reorder.
+#[derive(Default)]
+pub struct WeaponOrder {
+    names: Vec<&'static str>,
+    /// Time each slot last got a different weapon.
+    changed_at: Vec<f32>,
+}
+
+impl WeaponOrder {
+    fn update(&mut self, names: impl IntoIterator<Item = &'static str>, now: f32) {
+        let names = names.into_iter().collect::<Vec<_>>();
+        if names.len() != self.names.len() {
+            // Weapons added or removed rather than reordered, nothing to highlight
+            self.changed_at = vec![f32::NEG_INFINITY; names.len()];
+        } else {
+            for (slot, (old, new)) in self.names.iter().zip(&names).enumerate() {
+                if old != new {
+                    self.changed_at[slot] = now;
+                }
+            }
         }
+        self.names = names;
+    }
+
+    /// How strongly to highlight `slot`, from 1 right after it changed down to 0.
+    fn highlight(&self, slot: usize, now: f32) -> f32 {
+        (1.0 - (now - self.changed_at[slot]) / REORDER_FADE).clamp(0.0, 1.0)
+    }
+}
+
+/// A weapon row the player can drag by its handle and drop onto another row to move the weapon
+/// into that slot.
+pub fn weapon_rearrange_ui(
+    ui: &mut Ui,
+    index: usize,
+    highlight: f32,
+    ship: Entity,
+    weapon_ordering: &mut EventWriter<ShipCommand<MoveWeapon>>,
+    add_contents: impl FnOnce(&mut Ui),
+) {
+    let fill = Color32::from_white_alpha((highlight * 48.0) as u8);
+    let row = egui::Frame::none().fill(fill).show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.dnd_drag_source(
+                egui::Id::new(("weapon", index)),
+                DraggedWeapon(index),
+                |ui| {
+                    ui.label("↕");
+                },
+            );
+            add_contents(ui);
+        });
     });
+    let row = row.response;
+    if let Some(dragged) = row.dnd_hover_payload::<DraggedWeapon>() {
+        if dragged.0 != index {
+            // Show which side of this row the weapon will end up on
+            let y = if dragged.0 > index {
+                row.rect.top()
+            } else {
+                row.rect.bottom()
+            };
+            ui.painter().hline(
+                row.rect.x_range(),
+                y,
+                egui::Stroke::new(2.0, Color32::WHITE),
+            );
+        }
+    }
+    if let Some(dragged) = row.dnd_release_payload::<DraggedWeapon>() {
+        if dragged.0 != index {
+            weapon_ordering.send(
+                MoveWeapon {
+                    weapon_index: dragged.0,
+                    target_index: index,
+                }
+                .for_ship(ship),
+            );
+        }
+    }
 }
 
 pub fn weapon_power_ui(
@@ -298,7 +1075,8 @@ pub fn weapon_power_ui(
     powered: bool,
     index: usize,
     weapon: WeaponId,
-    weapon_power: &mut EventWriter<WeaponPower>,
+    ship: Entity,
+    weapon_power: &mut EventWriter<ShipCommand<WeaponPower>>,
 ) {
     let mut new_powered = powered;
     for _ in 0..weapon.common().power {
@@ -311,17 +1089,72 @@ pub fn weapon_power_ui(
         } else {
             PowerDir::Remove
         };
-        weapon_power.send(WeaponPower {
-            dir,
-            weapon_index: index,
-        });
+        weapon_power.send(
+            WeaponPower {
+                dir,
+                weapon_index: index,
+            }
+            .for_ship(ship),
+        );
     }
 }
 
+/// Stat lines for a weapon's tooltip, as `(label, value)` pairs.
+fn weapon_stats(weapon: WeaponId) -> Vec<(&'static str, String)> {
+    let common = weapon.common();
+    let damage = common.damage;
+    let mut stats = vec![
+        ("Hull damage", damage.hull.to_string()),
+        ("System damage", damage.system.to_string()),
+        ("Crew damage", format!("{:.0}", damage.crew)),
+    ];
+    if damage.fire_chance > 0.0 {
+        stats.push(("Fire chance", format!("{:.0}%", damage.fire_chance * 100.0)));
+    }
+    if damage.breach_chance > 0.0 {
+        stats.push((
+            "Breach chance",
+            format!("{:.0}%", damage.breach_chance * 100.0),
+        ));
+    }
+    if let Some(projectile) = weapon.projectile_stats() {
+        stats.push(("Shots", projectile.volley_size.to_string()));
+        stats.push(("Shield pierce", projectile.shield_pierce.to_string()));
+        if projectile.spread > 0.0 {
+            stats.push(("Spread", format!("{:.0}", projectile.spread)));
+        }
+    }
+    if let Some(beam) = weapon.beam_stats() {
+        stats.push(("Beam length", format!("{:.0}", beam.length)));
+        let pierce = match beam.shield_rule {
+            ShieldRule::PerRoom => "-1 damage per layer, every room",
+            ShieldRule::Soak => "-1 damage per layer, soaked once",
+        };
+        stats.push(("Shield pierce", pierce.into()));
+    }
+    stats.push(("Charge time", format!("{:.1}s", common.charge_time)));
+    stats.push(("Power", common.power.to_string()));
+    if weapon.uses_missile() {
+        stats.push(("Ammo", "1 missile per volley".into()));
+    }
+    stats
+}
+
+fn weapon_tooltip(ui: &mut Ui, weapon: WeaponId) {
+    ui.strong(weapon.common().name);
+    egui::Grid::new("weapon_tooltip").show(ui, |ui| {
+        for (label, value) in weapon_stats(weapon) {
+            ui.label(label);
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+}
+
 pub fn weapon_charge_ui(ui: &mut Ui, charge: f32, weapon: WeaponId) {
     let charge = charge / weapon.common().charge_time;
     let color = if charge == 1.0 {
-        Color32::GREEN
+        egui_theme(ui.ctx()).status_egui(Status::Good)
     } else {
         Color32::WHITE
     };
@@ -337,11 +1170,16 @@ pub fn enemy_panels(
     mut ui: EguiContexts,
     self_intel: Query<&SelfIntel>,
     ships: Query<(Entity, &ShipIntel, Has<Dead>)>,
+    teams: Query<&Team>,
+    charge_intel: Query<&EnemyWeaponChargeIntel>,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         return;
     };
-    let enemies = ships.iter().filter(|(e, _, _)| *e != self_intel.ship);
+    let my_team = teams.get(self_intel.ship).ok();
+    let enemies = ships
+        .iter()
+        .filter(|(e, _, _)| *e != self_intel.ship && teams.get(*e).ok() != my_team);
     for (_, intel, dead) in enemies {
         egui::Window::new(format!("Target"))
             .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::ZERO)
@@ -359,7 +1197,7 @@ pub fn enemy_panels(
                             egui::ProgressBar::new(current as f32 / max as f32)
                                 .desired_width(400.0)
                                 .rounding(0.0)
-                                .fill(Color32::GREEN),
+                                .fill(egui_theme(ui.ctx()).status_egui(Status::Good)),
                         );
                         ui.label(format!("{current}/{max}"));
                     });
@@ -397,7 +1235,8 @@ pub fn enemy_panels(
                                 ui.add_enabled_ui(false, |ui| {
                                     ui.checkbox(&mut powered, "");
                                 });
-                                ui.label(weapon.weapon.common().name);
+                                ui.label(weapon.weapon.common().name)
+                                    .on_hover_ui(|ui| weapon_tooltip(ui, weapon.weapon));
                             });
                         }
                     }
@@ -407,17 +1246,39 @@ pub fn enemy_panels(
                             system_damage_label(ui, oxygen);
                         });
                     }
+                    // Sensors good enough to read their weapon charge can make out the plating too
+                    if charge_intel.contains(intel.enemy_weapon_charge) {
+                        armor_ui(ui, intel.basic.ship_type);
+                    }
                 }
             });
     }
 }
 
-fn system_damage_label(ui: &mut Ui, intel: &SystemDamageIntel) {
-    let color = match intel {
-        SystemDamageIntel::Undamaged => Color32::GREEN,
-        SystemDamageIntel::Damaged => Color32::YELLOW,
-        SystemDamageIntel::Destroyed => Color32::RED,
+fn armor_ui(ui: &mut Ui, ship_type: usize) {
+    let armor = SHIPS[ship_type].armor;
+    ui.label("Armor:").on_hover_text(
+        "Hull damage soaked up by hits on rooms in each quarter of the hull. At least one point \
+        always gets through.",
+    );
+    egui::Grid::new("armor").show(ui, |ui| {
+        for (quadrant, armor) in Quadrant::iter().zip(armor) {
+            ui.label(quadrant.to_string());
+            ui.label(armor.to_string());
+            if matches!(quadrant, Quadrant::ForeStarboard | Quadrant::AftStarboard) {
+                ui.end_row();
+            }
+        }
+    });
+}
+
+pub fn system_damage_label(ui: &mut Ui, intel: &SystemDamageIntel) {
+    let status = match intel {
+        SystemDamageIntel::Undamaged => Status::Good,
+        SystemDamageIntel::Damaged => Status::Warning,
+        SystemDamageIntel::Destroyed => Status::Bad,
     };
+    let color = egui_theme(ui.ctx()).status_egui(status);
     ui.colored_label(color, format!("{intel:?}"));
 }
 
@@ -425,10 +1286,17 @@ pub fn weapons_panel(
     mut ui: EguiContexts,
     self_intel: Query<&SelfIntel>,
     ships: Query<&ShipIntel, Without<Dead>>,
+    systems: Query<&SystemsIntel>,
     charge_intel: Query<&WeaponChargeIntel>,
-    mut weapon_power: EventWriter<WeaponPower>,
-    mut weapon_ordering: EventWriter<MoveWeapon>,
-    mut set_autofire: EventWriter<SetAutofire>,
+    mut weapon_power: EventWriter<ShipCommand<WeaponPower>>,
+    mut weapon_ordering: EventWriter<ShipCommand<MoveWeapon>>,
+    mut store_weapon: EventWriter<ShipCommand<StoreWeapon>>,
+    mut settings: WeaponSettings,
+    mut order: Local<WeaponOrder>,
+    mut group: ResMut<WeaponGroup>,
+    mut chase: ResMut<ChaseCamera>,
+    mut loadout: Local<Vec<WeaponId>>,
+    time: Res<Time>,
     mut commands: Commands,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
@@ -444,87 +1312,377 @@ pub fn weapons_panel(
         return;
     };
     let weapon_charges = charge_intel.get(intel.weapon_charge).unwrap();
+    let ship_type = &SHIPS[intel.basic.ship_type];
+    let slots = systems
+        .get(intel.systems)
+        .unwrap()
+        .get(&SystemId::Weapons)
+        .map_or(0, |x| ship_type.weapon_slots(x.upgrade_level));
+    let now = time.elapsed_secs();
+    order.update(weapons.weapons.iter().map(|x| x.weapon.common().name), now);
+    // Groups are by slot, so they don't survive weapons moving around
+    if weapons
+        .weapons
+        .iter()
+        .map(|x| x.weapon)
+        .ne(loadout.iter().copied())
+    {
+        *loadout = weapons.weapons.iter().map(|x| x.weapon).collect();
+        group.0.clear();
+    }
     egui::Window::new("Weapons")
         .anchor(egui::Align2::CENTER_BOTTOM, egui::Vec2::ZERO)
         .title_bar(false)
         .resizable(false)
         .show(ui.ctx_mut(), |ui| {
-            let last_weapon = weapons.weapons.len() - 1;
             for (weapon_index, weapon) in weapons.weapons.iter().enumerate() {
-                ui.horizontal(|ui| {
-                    weapon_rearrange_ui(ui, weapon_index, last_weapon, &mut weapon_ordering);
-                    weapon_power_ui(
-                        ui,
-                        weapon.powered,
-                        weapon_index,
-                        weapon.weapon,
-                        &mut weapon_power,
-                    );
-                    let (_, color) = size_color(weapon_index);
-                    ui.colored_label(
-                        to_egui_color(color),
-                        format!("[{}] {}", weapon_index + 1, weapon.weapon.common().name),
-                    );
-                    weapon_charge_ui(ui, weapon_charges.levels[weapon_index], weapon.weapon);
-                    if ui.button("Target").clicked() {
-                        commands.queue(start_targeting(weapon_index));
-                    }
-                });
+                let highlight = order.highlight(weapon_index, now);
+                weapon_rearrange_ui(
+                    ui,
+                    weapon_index,
+                    highlight,
+                    self_intel.ship,
+                    &mut weapon_ordering,
+                    |ui| {
+                        weapon_power_ui(
+                            ui,
+                            weapon.powered,
+                            weapon_index,
+                            weapon.weapon,
+                            self_intel.ship,
+                            &mut weapon_power,
+                        );
+                        let (_, color) = egui_theme(ui.ctx()).weapon(weapon_index);
+                        let at_risk = self_intel.depower_next == Some(weapon_index);
+                        // Flash the weapon a hit to the weapons system would take offline
+                        let color = if at_risk && (now * 3.0).fract() < 0.5 {
+                            egui_theme(ui.ctx()).alert_egui()
+                        } else {
+                            to_egui_color(color)
+                        };
+                        ui.colored_label(
+                            color,
+                            format!("[{}] {}", weapon_index + 1, weapon.weapon.common().name),
+                        )
+                        .on_hover_ui(|ui| {
+                            if at_risk {
+                                ui.colored_label(
+                                    egui_theme(ui.ctx()).alert_egui(),
+                                    "Loses power first if weapons take damage",
+                                );
+                            }
+                            weapon_tooltip(ui, weapon.weapon);
+                        });
+                        weapon_charge_ui(ui, weapon_charges.levels[weapon_index], weapon.weapon);
+                        if ui.button("Target").clicked() {
+                            commands.queue(start_targeting(weapon_index));
+                        }
+                        let mut grouped = group.0.contains(&weapon_index);
+                        if ui.checkbox(&mut grouped, "Group").changed() {
+                            group.0.retain(|&x| x != weapon_index);
+                            if grouped {
+                                group.0.push(weapon_index);
+                            }
+                        }
+                        if ui
+                            .button("Store")
+                            .on_hover_text("Unmount this weapon and stow it in cargo")
+                            .clicked()
+                        {
+                            store_weapon
+                                .send(StoreWeapon { weapon_index }.for_ship(self_intel.ship));
+                        }
+                    },
+                );
+            }
+            for slot in weapons.weapons.len()..ship_type.weapon_mounts.len() {
+                if slot < slots {
+                    ui.weak(format!("[{}] Empty", slot + 1));
+                } else {
+                    ui.weak(format!("[{}] Locked", slot + 1))
+                        .on_hover_text("Upgrade the weapons system to unlock this slot");
+                }
+            }
+            let target_group =
+                ui.add_enabled(!group.0.is_empty(), egui::Button::new("[G] Target group"));
+            if target_group.clicked() {
+                commands.queue(start_group_targeting);
             }
             let mut autofire = self_intel.autofire;
             ui.checkbox(&mut autofire, "[V] Autofire");
             if autofire != self_intel.autofire {
-                set_autofire.send(SetAutofire(autofire));
+                settings
+                    .autofire
+                    .send(SetAutofire(autofire).for_ship(self_intel.ship));
             }
+            ui.horizontal(|ui| {
+                let mut floor = self_intel.missile_floor;
+                ui.label("Keep back");
+                ui.add(egui::DragValue::new(&mut floor).range(0..=99));
+                ui.label("missiles")
+                    .on_hover_text("Autofire stops launching missiles once this few are left");
+                if floor != self_intel.missile_floor {
+                    settings
+                        .missile_floor
+                        .send(SetMissileFloor(floor).for_ship(self_intel.ship));
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut depower_order = self_intel.depower_order;
+                ui.label("Depower first");
+                egui::ComboBox::from_id_salt("depower_order")
+                    .selected_text(depower_order.name())
+                    .show_ui(ui, |ui| {
+                        for x in DepowerOrder::iter() {
+                            ui.selectable_value(&mut depower_order, x, x.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Which weapon goes offline when weapons lose power");
+                if depower_order != self_intel.depower_order {
+                    settings
+                        .depower_order
+                        .send(SetDepowerOrder(depower_order).for_ship(self_intel.ship));
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut chase.enabled, "[C] Chase camera")
+                    .on_hover_text("Follow our shots from the ship to wherever they land");
+                if chase.enabled {
+                    ui.selectable_value(&mut chase.weapon, None, "All");
+                    for weapon_index in 0..weapons.weapons.len() {
+                        let label = format!("{}", weapon_index + 1);
+                        ui.selectable_value(&mut chase.weapon, Some(weapon_index), label);
+                    }
+                }
+            });
         });
 }
 
+/// Weapons stowed in cargo. Each one gets a button per weapon slot: an empty slot mounts it, an
+/// occupied one swaps it with whatever's mounted there.
+pub fn cargo_panel(
+    mut ui: EguiContexts,
+    self_intel: Query<&SelfIntel>,
+    ships: Query<&ShipIntel, Without<Dead>>,
+    systems: Query<&SystemsIntel>,
+    mut install_weapon: EventWriter<ShipCommand<InstallWeapon>>,
+) {
+    let Ok(self_intel) = self_intel.get_single() else {
+        // No connection to server
+        return;
+    };
+    if self_intel.cargo.is_empty() {
+        return;
+    }
+    let Ok(intel) = ships.get(self_intel.ship) else {
+        // Ship destroyed
+        return;
+    };
+    let Some(weapons) = &intel.basic.weapons else {
+        // No weapons system
+        return;
+    };
+    let systems = systems.get(intel.systems).unwrap();
+    let slots = systems.get(&SystemId::Weapons).map_or(0, |x| {
+        SHIPS[intel.basic.ship_type].weapon_slots(x.upgrade_level)
+    });
+    egui::Window::new("Cargo")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::ZERO)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            for (cargo_index, &weapon) in self_intel.cargo.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(weapon.common().name)
+                        .on_hover_ui(|ui| weapon_tooltip(ui, weapon));
+                    for slot in 0..slots {
+                        // Weapons fill slots in order, so only the first empty one is usable
+                        let (enabled, hover) = match weapons.weapons.get(slot) {
+                            Some(mounted) => {
+                                (true, format!("Swap with {}", mounted.weapon.common().name))
+                            }
+                            None if slot == weapons.weapons.len() => {
+                                (true, "Mount in empty slot".into())
+                            }
+                            None => (false, "Fill earlier slots first".into()),
+                        };
+                        let button = ui
+                            .add_enabled(enabled, egui::Button::new(format!("{}", slot + 1)))
+                            .on_hover_text(hover);
+                        if button.clicked() {
+                            install_weapon.send(
+                                InstallWeapon { cargo_index, slot }.for_ship(self_intel.ship),
+                            );
+                        }
+                    }
+                });
+            }
+        });
+}
+
+/// Crew portraits down the left side. Click one to select that crew member (shift to add them to
+/// the selection), double-click to center the camera on them too.
 pub fn crew_panel(
     mut ui: EguiContexts,
     self_intel: Query<&SelfIntel>,
-    mut crew_stations: EventWriter<CrewStations>,
+    crew_graphics: Query<(
+        Entity,
+        &CrewGraphic,
+        &Parent,
+        &GlobalTransform,
+        Has<Selected>,
+    )>,
+    selection_enabled: Option<Res<SelectionEnabled>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    assets: Res<AssetServer>,
+    mut camera: Single<&mut Transform, With<Camera2d>>,
+    mut crew_stations: EventWriter<ShipCommand<CrewStations>>,
+    mut commands: Commands,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         return;
     };
+    let graphics = crew_graphics
+        .iter()
+        .filter(|(_, _, parent, _, _)| ***parent == self_intel.ship)
+        .map(|(e, &CrewGraphic(index), _, transform, selected)| {
+            (index, (e, transform.translation().xy(), selected))
+        })
+        .collect::<HashMap<_, _>>();
+    let portrait_texture = ui.add_image(assets.load("crew.png"));
     egui::Window::new("Crew")
         .anchor(egui::Align2::LEFT_TOP, egui::Vec2::new(0.0, 135.0))
         .title_bar(false)
         .resizable(false)
         .show(ui.ctx_mut(), |ui| {
-            for (_crew_index, crew) in self_intel.crew.iter().enumerate() {
-                ui.group(|ui| {
-                    ui.heading(&crew.name);
-                    ui.label(format!(
-                        "Health: {}/{}",
-                        round_to_usize(crew.health),
-                        round_to_usize(RACES[crew.race].max_health)
-                    ));
+            for (crew_index, crew) in self_intel.crew.iter().enumerate() {
+                let graphic = graphics.get(&crew_index).copied();
+                let stroke = if graphic.is_some_and(|(_, _, selected)| selected) {
+                    egui::Stroke::new(2.0, Color32::GREEN)
+                } else {
+                    ui.visuals().widgets.noninteractive.bg_stroke
+                };
+                let portrait = egui::Frame::group(ui.style())
+                    .stroke(stroke)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::Image::new(egui::load::SizedTexture::new(
+                                    portrait_texture,
+                                    [24.0, 24.0],
+                                ))
+                                .tint(crew_color(crew.color)),
+                            );
+                            ui.vertical(|ui| {
+                                ui.label(RichText::new(&crew.name).strong());
+                                let max_health = RACES[crew.race].max_health;
+                                ui.add(
+                                    egui::ProgressBar::new(crew.health / max_health)
+                                        .desired_width(100.0)
+                                        .fill(Color32::DARK_GREEN)
+                                        .text(format!(
+                                            "{}/{}",
+                                            round_to_usize(crew.health),
+                                            round_to_usize(max_health)
+                                        )),
+                                );
+                            });
+                        });
+                    })
+                    .response
+                    .interact(egui::Sense::click());
+                let portrait = if crew_index < 8 {
+                    portrait.on_hover_text(format!("Select (Hotkey: F{})", crew_index + 1))
+                } else {
+                    portrait
+                };
+                let Some((entity, pos, _)) = graphic else {
+                    continue;
+                };
+                if portrait.clicked() && selection_enabled.is_some() {
+                    if !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                        for &(other, _, _) in graphics.values() {
+                            commands.entity(other).remove::<Selected>();
+                        }
+                    }
+                    commands.entity(entity).insert(Selected);
+                }
+                if portrait.double_clicked() {
+                    center_camera(&mut camera, pos);
+                }
+            }
+            for (i, clone) in self_intel.cloning.iter().enumerate() {
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    ui.add_enabled_ui(false, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.image(egui::load::SizedTexture::new(
+                                portrait_texture,
+                                [24.0, 24.0],
+                            ));
+                            ui.vertical(|ui| {
+                                ui.label(RichText::new(&clone.name).strong());
+                                // Only the first in line is being worked on
+                                let text = if i == 0 {
+                                    format!("Cloning {}%", round_to_usize(clone.progress * 100.0))
+                                } else {
+                                    "Waiting to clone".into()
+                                };
+                                ui.add(
+                                    egui::ProgressBar::new(clone.progress)
+                                        .desired_width(100.0)
+                                        .text(text),
+                                );
+                            });
+                        });
+                    });
                 });
             }
             if ui.button("Save stations").clicked() {
-                crew_stations.send(CrewStations::Save);
+                crew_stations.send(CrewStations::Save.for_ship(self_intel.ship));
             }
             if ui.button("Return to stations").clicked() {
-                crew_stations.send(CrewStations::Return);
+                crew_stations.send(CrewStations::Return.for_ship(self_intel.ship));
+            }
+            let mut auto_return = self_intel.auto_return;
+            if ui
+                .checkbox(&mut auto_return, "Return automatically")
+                .on_hover_text("Crew head back to their stations once they're out of work.")
+                .changed()
+            {
+                crew_stations.send(CrewStations::AutoReturn(auto_return).for_ship(self_intel.ship));
             }
         });
 }
 
-pub fn size_color(index: usize) -> (f32, Srgba) {
-    match index {
-        0 => (24.0, RED),
-        1 => (28.0, YELLOW),
-        2 => (32.0, LIME),
-        3 => (36.0, PURPLE),
-        _ => panic!("Index out of range"),
+#[cfg(test)]
+mod tests {
+    use common::weapon::{BREACH_BOMB, BURST_LASER_MK_I};
+
+    use super::*;
+
+    #[test]
+    fn weapon_tooltip_stats() {
+        let stats = weapon_stats(BURST_LASER_MK_I);
+        assert!(stats.contains(&("Shots", "2".into())));
+        assert!(stats.iter().all(|&(label, _)| label != "Breach chance"));
+        let stats = weapon_stats(BREACH_BOMB);
+        assert!(stats.contains(&("Breach chance", "100%".into())));
+        assert!(stats.contains(&("Ammo", "1 missile per volley".into())));
     }
-}
 
-fn to_egui_color(color: Srgba) -> Color32 {
-    Color32::from_rgb(
-        (color.red * 255.0) as u8,
-        (color.green * 255.0) as u8,
-        (color.blue * 255.0) as u8,
-    )
+    #[test]
+    fn reorder_highlights_changed_slots() {
+        let mut order = WeaponOrder::default();
+        order.update(["Heavy Laser", "Burst Laser", "Artemis"], 0.0);
+        assert_eq!(order.highlight(0, 0.0), 0.0);
+        // Move the Artemis to the front
+        order.update(["Artemis", "Heavy Laser", "Burst Laser"], 1.0);
+        assert_eq!(order.highlight(0, 1.0), 1.0);
+        assert_eq!(order.highlight(2, 1.0), 1.0);
+        assert_eq!(order.highlight(1, 1.0 + REORDER_FADE), 0.0);
+        // Swapping the back two leaves the front alone
+        order.update(["Artemis", "Burst Laser", "Heavy Laser"], 5.0);
+        assert_eq!(order.highlight(0, 5.0), 0.0);
+        assert_eq!(order.highlight(1, 5.0), 1.0);
+    }
 }
@@ -1,31 +1,48 @@
 use std::f32::consts::TAU;
 
-use bevy::{color::palettes, prelude::*};
+use bevy::{color::palettes, prelude::*, window::WindowResized};
 use common::{
-    bullets::{BeamTarget, FiredFrom, Progress, RoomTarget},
-    intel::{InteriorIntel, SelfIntel, ShipIntel},
+    bullets::{
+        FiredFrom, HitQuality, HullImpact, Incidence, LaunchedFrom, Progress, ProjectileKind,
+        RoomTarget, ShieldImpact, Shuttle, WeaponDamage,
+    },
+    intel::{
+        CrewNavIntel, DoorsIntel, EnemyWeaponChargeIntel, InteriorIntel, SelfIntel, ShipIntel,
+        SystemDamageIntel, SystemIntel, SystemsIntel, WeaponChargeIntel, CHARGE_PIPS,
+    },
+    lobby::Team,
     nav::{Cell, CrewNavStatus, LineSection, NavLocation, SquareSection},
-    ship::{Dead, Door, DoorDir, SystemId, SHIPS},
-    util::inverse_lerp,
+    ship::{
+        point_at_bearing, Dead, Door, DoorDir, ShipPlacement, SystemId, ENGAGEMENT_RANGE, SHIPS,
+    },
     weapon::{WeaponId, WeaponTarget},
+    DoorState, RACES,
 };
-use rand::{thread_rng, Rng};
 use strum::IntoEnumIterator;
 
 use crate::{
-    egui_panels::size_color,
-    interaction::{handle_cell_click, toggle_door, TargetingWeapon},
+    interaction::{
+        click_system_icon, handle_cell_click, is_friendly, toggle_door, BeamPreview,
+        TargetingWeapon,
+    },
+    prediction::PredictedTargets,
     select::Selectable,
+    smoothing::Smoothed,
+    theme::{ColorTheme, Status},
+    weapon_fx::{shot_color, shot_weapon},
 };
 
 const Z_BG: f32 = 0.0;
 const Z_SHIP: f32 = Z_BG + 1.0;
-const Z_BULLETS: f32 = Z_SHIP + Z_SHIELDS + 1.0;
+pub const Z_BULLETS: f32 = Z_SHIP + Z_SHIELDS + 1.0;
 
 const Z_CELL: f32 = 1.0;
 const Z_ICONS: f32 = Z_CELL + Z_WALLS;
 const Z_CREW: f32 = Z_ICONS + 1.0;
 const Z_SHIELDS: f32 = Z_CREW + 1.0;
+pub const Z_SCORCH: f32 = Z_ICONS - 0.5;
+pub const Z_EXPLOSIONS: f32 = Z_CREW + 0.5;
+pub const Z_PARTICLES: f32 = Z_CREW + 0.25;
 
 const Z_AIR: f32 = 1.0;
 const Z_VACUUM: f32 = Z_AIR + 1.0;
@@ -47,9 +64,18 @@ enum Walls {
 #[derive(Component)]
 pub struct DoorGraphic(pub usize);
 
+/// The last state we saw a door in. Enemy doors are only visible with good enough sensors, so the
+/// rest of the time this is what gets drawn.
+#[derive(Component, Default)]
+pub struct LastSeenDoor(pub DoorState);
+
 #[derive(Component)]
 pub struct CrewGraphic(pub usize);
 
+/// Icon marking the room that houses a system.
+#[derive(Component)]
+pub struct SystemIcon(pub SystemId);
+
 pub fn sync_crew_count(
     self_intel: Query<&SelfIntel>,
     crew: Query<(Entity, &Parent, &CrewGraphic)>,
@@ -83,45 +109,83 @@ pub fn sync_crew_count(
                     ..default()
                 },
                 Transform::from_xyz(0.0, 0.0, Z_CREW),
+                Smoothed::<Vec2>::default(),
             ))
             .id();
         commands.entity(self_intel.ship).add_child(new_crew_member);
     }
 }
 
+/// Crew graphics further than this from where their crew member is (two cells) jump straight there
+/// instead of sliding.
+const CREW_SNAP_DISTANCE: f32 = 70.0;
+
 pub fn sync_crew_positions(
     self_intel: Query<&SelfIntel>,
     ships: Query<&ShipIntel>,
-    mut crew: Query<(&mut Transform, &Parent, &CrewGraphic)>,
+    mut crew: Query<(&mut Transform, &mut Smoothed<Vec2>, &Parent, &CrewGraphic)>,
+    time: Res<Time>,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         return;
     };
-    let ship = &SHIPS[ships.get(self_intel.ship).unwrap().basic.ship_type];
+    let ship_type = ships.get(self_intel.ship).unwrap().basic.ship_type;
     let mut crew_graphics = crew
         .iter_mut()
-        .filter(|&(_, parent, _)| **parent == self_intel.ship)
+        .filter(|&(_, _, parent, _)| **parent == self_intel.ship)
         .collect::<Vec<_>>();
-    crew_graphics.sort_unstable_by_key(|(_, _, x)| x.0);
+    crew_graphics.sort_unstable_by_key(|(_, _, _, x)| x.0);
     let crew = self_intel.crew.iter();
-    let cell_pos = |&Cell(cell)| ship.cell_positions[cell];
-    for (crew, (mut graphic, _, _)) in crew.zip(crew_graphics) {
+    for (crew, (mut graphic, mut smoothed, _, _)) in crew.zip(crew_graphics) {
         let crew_z = graphic.translation.z;
         let crew_xy = match &crew.nav_status {
-            CrewNavStatus::At(x) => cell_pos(x),
-            CrewNavStatus::Navigating(x) => match &x.current_location {
-                NavLocation::Line(LineSection([a, b]), x) => cell_pos(a).lerp(cell_pos(b), *x),
-                NavLocation::Square(SquareSection([[a, b], [c, d]]), x) => {
-                    let bottom = cell_pos(a).lerp(cell_pos(b), x.y);
-                    let top = cell_pos(c).lerp(cell_pos(d), x.y);
-                    bottom.lerp(top, x.x)
-                }
-            },
+            CrewNavStatus::At(x) => SHIPS[ship_type].cell_positions[x.0],
+            CrewNavStatus::Navigating(x) => nav_location_xy(ship_type, &x.current_location),
         };
+        // Crew shuffling down a slot when someone dies, or coming back from the clone bay
+        if graphic.translation.xy().distance(crew_xy) > CREW_SNAP_DISTANCE {
+            smoothed.snap();
+        }
+        let crew_xy = smoothed.update(time.elapsed_secs(), crew_xy);
         graphic.translation = crew_xy.extend(crew_z);
     }
 }
 
+/// Tint our crew's sprites with the colors picked for them in the lobby.
+pub fn sync_crew_colors(
+    self_intel: Query<&SelfIntel>,
+    mut crew: Query<(&mut Sprite, &Parent, &CrewGraphic)>,
+) {
+    let Ok(self_intel) = self_intel.get_single() else {
+        return;
+    };
+    for (mut sprite, parent, &CrewGraphic(index)) in &mut crew {
+        if **parent != self_intel.ship {
+            continue;
+        }
+        let Some(crew) = self_intel.crew.get(index) else {
+            continue;
+        };
+        let color = Color::srgb_from_array(crew.color.rgb());
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+/// Where a crew partway across a nav section is, in its ship's local space.
+pub fn nav_location_xy(ship_type: usize, location: &NavLocation) -> Vec2 {
+    let cell_pos = |&Cell(cell): &Cell| SHIPS[ship_type].cell_positions[cell];
+    match location {
+        NavLocation::Line(LineSection([a, b]), x) => cell_pos(a).lerp(cell_pos(b), *x),
+        NavLocation::Square(SquareSection([[a, b], [c, d]]), x) => {
+            let bottom = cell_pos(a).lerp(cell_pos(b), x.y);
+            let top = cell_pos(c).lerp(cell_pos(d), x.y);
+            bottom.lerp(top, x.x)
+        }
+    }
+}
+
 fn walls_tex(assets: &AssetServer, x: Walls) -> Handle<Image> {
     assets.load(match x {
         Walls::TopRight => "walls-corner.png",
@@ -154,24 +218,51 @@ fn door(ship_type: usize, index: usize) -> Transform {
     }))
 }
 
+/// Where a ship sits on screen, given where the server placed it and where the server placed us.
+/// Ships spread out as the window gets wider; at 1280 pixels the two sides sit at x = -200 and 400.
+fn ship_transform(placement: ShipPlacement, mine: ShipPlacement, window_width: f32) -> Transform {
+    let placement = placement.seen_from(mine);
+    let position = placement.position * window_width / 1280.0 + Vec2::new(100.0, 0.0);
+    Transform::from_translation(position.round().extend(Z_SHIP))
+        .with_rotation(Quat::from_rotation_z(placement.rotation))
+}
+
+pub fn layout_ships(
+    mut resized: EventReader<WindowResized>,
+    window: Single<&Window>,
+    self_intel: Single<&SelfIntel>,
+    placements: Query<&ShipPlacement>,
+    changed: Query<(), Changed<ShipPlacement>>,
+    mut ships: Query<(&ShipPlacement, &mut Transform), (With<ShipIntel>, With<Sprite>)>,
+) {
+    if resized.read().count() == 0 && changed.is_empty() {
+        return;
+    }
+    let Ok(&mine) = placements.get(self_intel.ship) else {
+        return;
+    };
+    for (&placement, mut transform) in &mut ships {
+        *transform = ship_transform(placement, mine, window.width());
+    }
+}
+
 pub fn add_ship_graphic(
+    window: Single<&Window>,
     self_intel: Query<&SelfIntel>,
-    ships: Query<(Entity, &ShipIntel), Without<Sprite>>,
+    placements: Query<&ShipPlacement>,
+    ships: Query<(Entity, &ShipIntel, &ShipPlacement), Without<Sprite>>,
     assets: Res<AssetServer>,
     mut commands: Commands,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
         return;
     };
-    let my_ship = self_intel.ship;
-    for (ship, intel) in &ships {
-        let is_me = ship == my_ship;
-        let transform = if is_me {
-            println!("{ship:?} is me!");
-            Transform::from_xyz(-200.0, 0.0, Z_SHIP)
-        } else {
-            Transform::from_xyz(400.0, 0.0, Z_SHIP).with_rotation(Quat::from_rotation_z(TAU / 4.0))
-        };
+    let Ok(&mine) = placements.get(self_intel.ship) else {
+        return;
+    };
+    for (ship, intel, &placement) in &ships {
+        let is_me = ship == self_intel.ship;
+        let transform = ship_transform(placement, mine, window.width());
 
         commands.entity(ship).insert((
             Sprite {
@@ -187,6 +278,7 @@ pub fn add_ship_graphic(
                 SystemId::Shields => "shields.png",
                 SystemId::Weapons => "weapons.png",
                 SystemId::Oxygen => "oxygen.png",
+                SystemId::CloneBay => "clone-bay.png",
             };
             let room = SHIPS[intel.basic.ship_type]
                 .room_systems
@@ -194,7 +286,7 @@ pub fn add_ship_graphic(
                 .position(|x| *x == Some(system));
             room.map(|room| {
                 (
-                    PickingBehavior::IGNORE,
+                    SystemIcon(system),
                     Sprite {
                         image: assets.load(sprite),
                         ..default()
@@ -210,7 +302,17 @@ pub fn add_ship_graphic(
         };
 
         for x in SystemId::iter().filter_map(icon) {
-            let icon = commands.spawn(x).id();
+            let mut icon = commands.spawn(x);
+            if is_me {
+                icon.insert(PickingBehavior {
+                    should_block_lower: false,
+                    is_hoverable: true,
+                })
+                .observe(click_system_icon);
+            } else {
+                icon.insert(PickingBehavior::IGNORE);
+            }
+            let icon = icon.id();
             commands.entity(ship).add_child(icon);
         }
 
@@ -218,6 +320,7 @@ pub fn add_ship_graphic(
             for i in 0..SHIPS[intel.basic.ship_type].doors.len() {
                 let mut e = ship.spawn((
                     DoorGraphic(i),
+                    LastSeenDoor::default(),
                     Sprite::default(),
                     door(intel.basic.ship_type, i),
                 ));
@@ -282,7 +385,7 @@ pub fn add_ship_graphic(
                 let oxygen = commands
                     .spawn((
                         PickingBehavior::IGNORE,
-                        OxygenGraphic(room_index),
+                        OxygenGraphic(cell),
                         Sprite {
                             image: assets.load("low-oxygen.png"),
                             ..default()
@@ -293,7 +396,7 @@ pub fn add_ship_graphic(
                 let vacuum = commands
                     .spawn((
                         PickingBehavior::IGNORE,
-                        VacuumGraphic(room_index),
+                        VacuumGraphic(cell),
                         Sprite {
                             image: assets.load("vacuum.png"),
                             ..default()
@@ -363,21 +466,82 @@ pub fn add_ship_graphic(
     }
 }
 
+/// Swap door sprites to match whether they're open, and rattle doors that boarders are trying to
+/// break down. Doors we can't see stay the way we last saw them, without the rattling.
 pub fn update_doors(
     ships: Query<&ShipIntel>,
-    mut doors: Query<(&DoorGraphic, &Parent, &mut Sprite)>,
+    doors_intel: Query<&DoorsIntel>,
+    mut doors: Query<(
+        &DoorGraphic,
+        &Parent,
+        &mut LastSeenDoor,
+        &mut Sprite,
+        &mut Transform,
+    )>,
     assets: Res<AssetServer>,
+    time: Res<Time>,
 ) {
-    for (&DoorGraphic(door), parent, mut sprite) in &mut doors {
+    for (&DoorGraphic(index), parent, mut last_seen, mut sprite, mut transform) in &mut doors {
         let Ok(ship) = ships.get(parent.get()) else {
             return;
         };
-        let door = ship.basic.doors[door];
+        let seen = doors_intel
+            .get(ship.doors)
+            .ok()
+            .and_then(|x| x.get(index).copied());
+        if let Some(door) = seen {
+            last_seen.0 = door;
+        }
+        let door = last_seen.0;
         sprite.image = match door.open {
             _ if door.broken() => assets.load("door-broken.png"),
             false => assets.load("door-closed.png"),
             true => assets.load("door-open.png"),
         };
+        // Shake harder the closer it is to giving way
+        let rest = self::door(ship.basic.ship_type, index);
+        let break_progress = seen.map_or(0.0, |x| x.break_progress);
+        let shake = (time.elapsed_secs() * 40.0).sin() * 3.0 * break_progress;
+        transform.translation = rest.translation + rest.rotation * Vec3::X * shake;
+    }
+}
+
+/// Tint each system icon so the ship's state reads without the panels: grey when unpowered, orange
+/// when damaged, red when destroyed, flashing red while enemy crew sabotage it. Damage is known for
+/// every ship, but power and sabotage need [`SystemsIntel`], which enemy ships often don't give us.
+pub fn update_system_icons(
+    ships: Query<&ShipIntel>,
+    systems: Query<&SystemsIntel>,
+    mut icons: Query<(&SystemIcon, &Parent, &mut Sprite)>,
+    theme: Res<ColorTheme>,
+    time: Res<Time>,
+) {
+    for (&SystemIcon(system), parent, mut sprite) in &mut icons {
+        let Ok(ship) = ships.get(**parent) else {
+            continue;
+        };
+        let damage = ship.basic.system_damage(system);
+        let detail = systems.get(ship.systems).ok().and_then(|x| x.get(&system));
+        sprite.color = system_icon_color(&theme, damage, detail, time.elapsed_secs());
+    }
+}
+
+fn system_icon_color(
+    theme: &ColorTheme,
+    damage: Option<SystemDamageIntel>,
+    detail: Option<&SystemIntel>,
+    now: f32,
+) -> Color {
+    const FLASHES_PER_SECOND: f32 = 3.0;
+    let sabotaged = detail.is_some_and(|x| x.damage_progress > 0.0);
+    if sabotaged && (now * FLASHES_PER_SECOND).fract() < 0.5 {
+        return theme.status(Status::Bad).into();
+    }
+    match damage {
+        Some(SystemDamageIntel::Destroyed) => theme.status(Status::Bad).into(),
+        Some(SystemDamageIntel::Damaged) => theme.status(Status::Warning).into(),
+        _ if detail.is_some_and(|x| x.current_power == 0) => palettes::basic::GRAY.into(),
+        _ => Color::WHITE,
     }
 }
 
@@ -387,7 +551,7 @@ pub fn update_oxygen(
     cells: Query<&Parent>,
     mut oxygen: Query<(&OxygenGraphic, &Parent, &mut Sprite)>,
 ) {
-    for (&OxygenGraphic(room), parent, mut sprite) in &mut oxygen {
+    for (&OxygenGraphic(cell), parent, mut sprite) in &mut oxygen {
         let ship = **cells.get(**parent).unwrap();
         let Ok(ship) = ships.get(ship) else {
             continue;
@@ -395,7 +559,10 @@ pub fn update_oxygen(
         let Ok(interior) = interiors.get(ship.interior) else {
             continue;
         };
-        sprite.color.set_alpha(1.0 - interior.rooms[room].oxygen);
+        let Some(cell) = interior.cells.get(cell) else {
+            continue;
+        };
+        sprite.color.set_alpha(1.0 - cell.oxygen);
     }
 }
 
@@ -405,7 +572,7 @@ pub fn update_vacuum(
     cells: Query<&Parent>,
     mut oxygen: Query<(&VacuumGraphic, &Parent, &mut Visibility)>,
 ) {
-    for (&VacuumGraphic(room), parent, mut visibility) in &mut oxygen {
+    for (&VacuumGraphic(cell), parent, mut visibility) in &mut oxygen {
         let ship = **cells.get(**parent).unwrap();
         let Ok(ship) = ships.get(ship) else {
             continue;
@@ -413,7 +580,10 @@ pub fn update_vacuum(
         let Ok(interior) = interiors.get(ship.interior) else {
             continue;
         };
-        *visibility = if interior.rooms[room].oxygen < 0.05 {
+        let Some(cell) = interior.cells.get(cell) else {
+            continue;
+        };
+        *visibility = if cell.oxygen < 0.05 {
             Visibility::Inherited
         } else {
             Visibility::Hidden
@@ -442,41 +612,115 @@ pub fn update_no_intel(
 #[derive(Component, Clone, Copy)]
 pub struct RoomGraphic(pub usize);
 
+/// Low oxygen overlay for the cell with this index.
 #[derive(Component, Clone, Copy)]
 pub struct OxygenGraphic(usize);
 
+/// Vacuum overlay for the cell with this index.
 #[derive(Component, Clone, Copy)]
 pub struct VacuumGraphic(usize);
 
 #[derive(Component, Clone, Copy)]
 pub struct NoIntelGraphic;
 
-#[derive(Component, Deref)]
-pub struct BulletIncidence(Dir2);
+/// Radians per second that bombs and asteroids tumble at.
+const TUMBLE_SPEED: f32 = 4.0;
 
+/// Exhaust streaming out behind a missile, as a child of the missile.
+#[derive(Component)]
+pub struct MissileTrail;
+
+/// Lasers that hit harder glow hotter. For shots whose weapon we can't see.
+fn laser_color(damage: Option<&WeaponDamage>) -> Color {
+    match damage.map_or(1, |x| x.hull) {
+        0 | 1 => palettes::css::RED.into(),
+        _ => palettes::css::ORANGE_RED.into(),
+    }
+}
+
+/// Projectiles are drawn the way their weapon's [`Presentation`] says to. Hazards, and shots from
+/// weapons we can't see, get a stand-in for their kind.
+///
+/// [`Presentation`]: common::weapon::Presentation
 pub fn spawn_projectile_graphics(
-    bullets: Query<Entity, (With<RoomTarget>, Without<Sprite>)>,
+    bullets: Query<
+        (
+            Entity,
+            Has<Shuttle>,
+            Option<&ProjectileKind>,
+            Option<&WeaponDamage>,
+            Option<&FiredFrom>,
+        ),
+        (With<RoomTarget>, Without<Sprite>),
+    >,
+    ships: Query<&ShipIntel>,
     assets: Res<AssetServer>,
     mut commands: Commands,
 ) {
-    for bullet in &bullets {
-        commands.entity(bullet).insert((
-            PickingBehavior::IGNORE,
-            Sprite {
-                image: assets.load("missile-1.png"),
+    for (bullet, is_shuttle, kind, damage, origin) in &bullets {
+        let presentation = origin
+            .and_then(|x| shot_weapon(ships.get(x.ship).ok()?, x))
+            .map(|x| x.common().presentation);
+        let mut bullet = commands.entity(bullet);
+        bullet.insert((PickingBehavior::IGNORE, Smoothed::<f32>::default()));
+        if is_shuttle {
+            // Just a grey box until shuttles get some art
+            bullet.insert(Sprite {
+                color: Color::srgb(0.7, 0.72, 0.75),
+                custom_size: Some(Vec2::new(30.0, 14.0)),
                 ..default()
-            },
-        ));
+            });
+            continue;
+        }
+        match kind.copied().unwrap_or_default() {
+            ProjectileKind::Laser => {
+                let length = 16.0 + 6.0 * damage.map_or(1, |x| x.hull) as f32;
+                let color = presentation.map_or_else(|| laser_color(damage), |x| shot_color(&x));
+                bullet.insert(Sprite {
+                    image: assets.load(presentation.map_or("beam.png", |x| x.sprite)),
+                    color: color.mix(&Color::WHITE, 0.2),
+                    custom_size: Some(Vec2::new(length, 7.0)),
+                    ..default()
+                });
+            }
+            ProjectileKind::Missile => {
+                let sprite = presentation.map_or("missile-1.png", |x| x.sprite);
+                bullet
+                    .insert(Sprite::from_image(assets.load(sprite)))
+                    .with_child((
+                        MissileTrail,
+                        PickingBehavior::IGNORE,
+                        Sprite {
+                            image: assets.load("beam.png"),
+                            color: Color::srgba(1.0, 0.85, 0.6, 0.6),
+                            custom_size: Some(Vec2::new(40.0, 6.0)),
+                            anchor: bevy::sprite::Anchor::CenterRight,
+                            ..default()
+                        },
+                        // Out the back of the missile, just underneath it
+                        Transform::from_xyz(-17.0, 0.0, -0.1),
+                    ));
+            }
+            ProjectileKind::Bomb => {
+                let sprite = presentation.map_or("bomb.png", |x| x.sprite);
+                bullet.insert(Sprite::from_image(assets.load(sprite)));
+            }
+            ProjectileKind::Asteroid => {
+                bullet.insert(Sprite::from_image(assets.load("asteroid.png")));
+            }
+        }
     }
 }
 
-pub fn set_bullet_incidence(
-    bullets: Query<Entity, (With<Progress>, Without<BulletIncidence>)>,
-    mut commands: Commands,
+/// Flicker missile exhaust so it doesn't look like a stick glued to the back.
+pub fn animate_missile_trails(
+    mut trails: Query<(&mut Sprite, &mut Transform), With<MissileTrail>>,
+    time: Res<Time>,
 ) {
-    for bullet in &bullets {
-        let direction = Dir2::new_unchecked(Vec2::from_angle(thread_rng().gen_range(0.0..=TAU)));
-        commands.entity(bullet).insert(BulletIncidence(direction));
+    let flicker = 1.0 + 0.2 * (time.elapsed_secs() * 30.0).sin();
+    for (mut sprite, mut transform) in &mut trails {
+        sprite.color.set_alpha(0.6 * flicker);
+        transform.scale = Vec3::new(flicker, 1.0, 1.0);
     }
 }
 
@@ -486,15 +730,27 @@ pub fn update_bullet_graphic(
     mut bullets: Query<(
         &Progress,
         &RoomTarget,
-        &FiredFrom,
-        &BulletIncidence,
+        Option<&FiredFrom>,
+        Option<&LaunchedFrom>,
+        &Incidence,
+        Option<&ProjectileKind>,
         &mut Transform,
+        &mut Smoothed<f32>,
     )>,
+    time: Res<Time>,
 ) {
-    for (traversal, target, origin, incidence, mut bullet) in &mut bullets {
+    for (traversal, target, fired_from, launched_from, incidence, kind, mut bullet, mut smoothed) in
+        &mut bullets
+    {
+        let traversal = smoothed.update(time.elapsed_secs(), **traversal);
         let (target_intel, target_transform) = targets.get(target.ship).unwrap();
-        let origin = ships.get(origin.ship).unwrap().translation.xy(); // TODO weapon mount
         let out_mid = Vec2::X * 1000.0;
+        // Hazards like asteroids don't come from a ship
+        let origin = fired_from
+            .map(|x| x.ship)
+            .or(launched_from.map(|x| x.0))
+            .and_then(|x| ships.get(x).ok())
+            .map_or(out_mid, |x| x.translation.xy()); // TODO weapon mount
         let room_center = {
             let room = target.room;
             SHIPS[target_intel.basic.ship_type].room_center(room)
@@ -504,13 +760,15 @@ pub fn update_bullet_graphic(
             (target_transform.rotation * room_center + target_transform.translation).xy();
         let in_mid = destination - 1000.0 * ***incidence;
 
-        bullet.translation = if **traversal < 0.5 {
-            origin.lerp(out_mid, **traversal * 2.0)
+        bullet.translation = if traversal < 0.5 {
+            origin.lerp(out_mid, traversal * 2.0)
         } else {
-            in_mid.lerp(destination, **traversal * 2.0 - 1.0)
+            in_mid.lerp(destination, traversal * 2.0 - 1.0)
         }
         .extend(Z_BULLETS);
-        bullet.rotation = if **traversal < 0.5 {
+        bullet.rotation = if let Some(ProjectileKind::Bomb | ProjectileKind::Asteroid) = kind {
+            Quat::from_rotation_z(time.elapsed_secs() * TUMBLE_SPEED)
+        } else if traversal < 0.5 {
             Quat::IDENTITY
         } else {
             Quat::from_rotation_arc_2d(Vec2::X, ***incidence)
@@ -518,60 +776,84 @@ pub fn update_bullet_graphic(
     }
 }
 
-pub fn draw_beams(
-    ships: Query<(&ShipIntel, &GlobalTransform)>,
-    beams: Query<(&FiredFrom, &Progress, &BeamTarget, &BulletIncidence)>,
+/// Health bars over every crew member we can see, ours in green and theirs in red. Enemy crew only
+/// show up with interior intel on their ship and don't have sprites of their own, so they get a
+/// circle as well. So do boarders: enemies aboard our ship, and our side's aboard theirs.
+pub fn draw_crew_health(
+    self_intel: Single<&SelfIntel>,
+    own_crew: Query<(&CrewGraphic, &GlobalTransform, &Parent)>,
+    ships: Query<(Entity, &ShipIntel, &GlobalTransform), Without<Dead>>,
+    interiors: Query<&InteriorIntel>,
+    theme: Res<ColorTheme>,
     mut gizmos: Gizmos,
 ) {
-    for (origin, &progress, target, incidence) in &beams {
-        let (intel, firing_ship) = ships.get(origin.ship).unwrap();
-        let Some(weapons) = &intel.basic.weapons else {
+    for (&CrewGraphic(index), transform, parent) in &own_crew {
+        if **parent != self_intel.ship {
+            continue;
+        }
+        let Some(crew) = self_intel.crew.get(index) else {
             continue;
         };
-        let WeaponId::Beam(weapon) = weapons.weapons[origin.weapon_index].weapon else {
+        let health = crew.health / RACES[crew.race].max_health;
+        let pos = transform.translation().xy();
+        health_bar(&mut gizmos, pos, health, theme.friendly());
+    }
+    for (ship, intel, ship_transform) in &ships {
+        let Ok(interior) = interiors.get(intel.interior) else {
             continue;
         };
-        let beam_length = weapon.length;
-        let (target_intel, target_ship) = ships.get(target.ship).unwrap();
-
-        let weapon_mount_pos = Vec2::ZERO.extend(Z_BULLETS);
-        let beam_start = firing_ship.transform_point(weapon_mount_pos);
-        let out_mid = firing_ship.transform_point(weapon_mount_pos + Vec3::X * 1000.0);
-        let hit_point = target.start + (*target.dir * beam_length * *progress);
-        let in_mid = hit_point + ***incidence * 1000.0;
-        let target_shields = target_intel.basic.shields.map_or(0, |x| x.layers);
-        let hull_damage = weapon.common.damage.saturating_sub(target_shields);
-        let hit_point = if hull_damage == 0 {
-            // find the intersection of the line (in_mid, hit_point) with a circle at 150
-            let ab = hit_point - in_mid;
-            let a_t = ab * ab.dot(in_mid) / ab.length_squared();
-            let b_t = ab + a_t;
-
-            let d_sqr = (in_mid - a_t).length_squared();
-            let a_t = a_t.dot(ab.normalize());
-            let b_t = b_t.dot(ab.normalize());
-
-            let target = 150.0;
-            let t = (target * target - d_sqr).sqrt();
-            let lerp_low = inverse_lerp(a_t, b_t, -t);
-            in_mid.lerp(hit_point, lerp_low)
+        let ship_type = intel.basic.ship_type;
+        let own_ship = ship == self_intel.ship;
+        // Our own crew already have sprites
+        let crew = interior
+            .rooms
+            .iter()
+            .flat_map(|x| &x.crew)
+            .filter(|_| !own_ship)
+            .map(|x| (x, theme.hostile()));
+        let intruder_color = if own_ship {
+            theme.hostile()
         } else {
-            hit_point
+            theme.friendly()
         };
-        let in_mid = target_ship.transform_point(in_mid.extend(Z_BULLETS));
-        let beam_end = target_ship.transform_point(hit_point.extend(Z_BULLETS));
-
-        gizmos.line(beam_start, out_mid, palettes::basic::RED);
-        gizmos.line(in_mid, beam_end, palettes::basic::RED);
+        let intruders = interior
+            .rooms
+            .iter()
+            .flat_map(|x| &x.intruders)
+            .map(|x| (x, intruder_color));
+        for (crew, color) in crew.chain(intruders) {
+            let local = match &crew.nav_status {
+                CrewNavIntel::At(cell) => SHIPS[ship_type].cell_positions[cell.0],
+                CrewNavIntel::Navigating(location) => nav_location_xy(ship_type, location),
+            };
+            let pos = ship_transform.transform_point(local.extend(0.0)).xy();
+            let health = crew.health / RACES[crew.race].max_health;
+            gizmos.circle_2d(pos, 8.0, color);
+            // The color their player picked, inside the ring that shows whose they are
+            gizmos.circle_2d(pos, 5.0, Color::srgb_from_array(crew.color.rgb()));
+            health_bar(&mut gizmos, pos, health, color);
+        }
     }
 }
 
+fn health_bar(gizmos: &mut Gizmos, crew_pos: Vec2, health: f32, color: impl Into<Color>) {
+    const WIDTH: f32 = 20.0;
+    let left = crew_pos + Vec2::new(-WIDTH / 2.0, 14.0);
+    gizmos.line_2d(left, left + Vec2::X * WIDTH, palettes::basic::GRAY);
+    let filled = left + Vec2::X * WIDTH * health.clamp(0.0, 1.0);
+    gizmos.line_2d(left, filled, color);
+}
+
 pub fn draw_targets(
     window: Single<&Window>,
+    camera: Single<(&Camera, &GlobalTransform)>,
     self_intel: Single<&SelfIntel>,
     ships: Query<&ShipIntel>,
     targets: Query<(&ShipIntel, &Transform)>,
     targeting_weapon: Option<Res<TargetingWeapon>>,
+    preview: Option<Res<BeamPreview>>,
+    predicted: Res<PredictedTargets>,
+    theme: Res<ColorTheme>,
     mut gizmos: Gizmos,
 ) {
     let Ok(ship) = ships.get(self_intel.ship) else {
@@ -581,12 +863,19 @@ pub fn draw_targets(
         return;
     };
 
-    if let Some(cursor) = window.cursor_position() {
-        let world_cursor = cursor * Vec2::new(1.0, -1.0) + Vec2::new(-640.0, 360.0);
+    let (camera, camera_transform) = *camera;
+    let world_cursor = window
+        .cursor_position()
+        .and_then(|x| camera.viewport_to_world_2d(camera_transform, x).ok());
+    if let Some(world_cursor) = world_cursor {
         match targeting_weapon.as_ref().map(|x| x.as_ref()) {
             Some(&TargetingWeapon::PickStart { weapon_index }) => {
-                let (size, color) = size_color(weapon_index);
-                gizmos.circle(world_cursor.extend(Z_BULLETS), size, color);
+                theme.target_ring(&mut gizmos, world_cursor.extend(Z_BULLETS), weapon_index);
+            }
+            Some(TargetingWeapon::PickGroup { weapons }) => {
+                for &weapon_index in weapons {
+                    theme.target_ring(&mut gizmos, world_cursor.extend(Z_BULLETS), weapon_index);
+                }
             }
             Some(&TargetingWeapon::PickDir {
                 weapon_index,
@@ -597,16 +886,31 @@ pub fn draw_targets(
                     return;
                 };
                 let beam_length = weapon.length;
-                let (_, color) = size_color(weapon_index);
+                let (_, color) = theme.weapon(weapon_index);
                 let dir = Dir2::new(world_cursor - start).unwrap_or(Dir2::Y);
                 let end = start + *dir * beam_length;
                 gizmos.line(start.extend(Z_BULLETS), end.extend(Z_BULLETS), color);
+                // Mark every room the swath would hit
+                let preview = preview
+                    .as_ref()
+                    .and_then(|x| Some((x, targets.get(x.ship).ok()?)));
+                if let Some((preview, (target_intel, target_transform))) = preview {
+                    let target_ship = &SHIPS[target_intel.basic.ship_type];
+                    for &room in &preview.rooms {
+                        let center = target_ship.room_center(room).extend(Z_BULLETS);
+                        let pos = target_transform.rotation * center + target_transform.translation;
+                        theme.target_ring(&mut gizmos, pos, weapon_index);
+                    }
+                }
             }
             _ => {}
         }
     }
 
-    for (i, target) in self_intel.weapon_targets.iter().enumerate() {
+    for i in 0..weapons.weapons.len() {
+        // Targets the server hasn't confirmed yet are drawn dimmed
+        let (target, predicted) = predicted.target(*self_intel, i);
+        let alpha = if predicted { 0.4 } else { 1.0 };
         if let Some(target) = target {
             match target {
                 WeaponTarget::Projectile(target) => {
@@ -618,8 +922,7 @@ pub fn draw_targets(
                     .extend(Z_BULLETS);
                     let pos =
                         target_transform.rotation * room_location + target_transform.translation;
-                    let (size, color) = size_color(i);
-                    gizmos.circle(pos, size, color);
+                    theme.faded_target_ring(&mut gizmos, pos, i, alpha);
                 }
                 WeaponTarget::Beam(target) => {
                     let WeaponId::Beam(weapon) = weapons.weapons[i].weapon else {
@@ -631,10 +934,373 @@ pub fn draw_targets(
                     let end = (target.start + *target.dir * beam_length).extend(Z_BULLETS);
                     let start = target_transform.rotation * start + target_transform.translation;
                     let end = target_transform.rotation * end + target_transform.translation;
-                    let (_, color) = size_color(i);
-                    gizmos.line(start, end, color);
+                    let (_, color) = theme.weapon(i);
+                    gizmos.line(start, end, color.with_alpha(alpha));
+                }
+            }
+        }
+    }
+}
+
+/// How far either side of the middle of a ship the edges of a firing arc are drawn.
+const ARC_EDGE_LENGTH: f32 = 150.0;
+
+/// While picking a room, mark where the firing arcs of the weapons being aimed cut across enemy
+/// ships. Rooms on the far side of a line are out of that weapon's reach.
+pub fn draw_firing_arcs(
+    self_intel: Single<&SelfIntel>,
+    ships: Query<(Entity, &ShipIntel, &Transform)>,
+    teams: Query<&Team>,
+    targeting_weapon: Option<Res<TargetingWeapon>>,
+    theme: Res<ColorTheme>,
+    mut gizmos: Gizmos,
+) {
+    let Some(targeting) = targeting_weapon else {
+        return;
+    };
+    let Ok((_, own_intel, _)) = ships.get(self_intel.ship) else {
+        return;
+    };
+    let mounts = SHIPS[own_intel.basic.ship_type].weapon_mounts;
+    for &slot in targeting.picking_room() {
+        let Some(mount) = mounts.get(slot).filter(|x| !x.is_turret()) else {
+            continue;
+        };
+        let (_, color) = theme.weapon(slot);
+        let edges = [
+            mount.direction - mount.half_arc,
+            mount.direction + mount.half_arc,
+        ];
+        for (ship, _, transform) in &ships {
+            if is_friendly(ship, self_intel.ship, &teams) {
+                continue;
+            }
+            for edge in edges {
+                let [start, end] = [-ARC_EDGE_LENGTH, ARC_EDGE_LENGTH].map(|x| {
+                    let local = point_at_bearing(edge, ENGAGEMENT_RANGE + x).extend(Z_BULLETS);
+                    transform.rotation * local + transform.translation
+                });
+                gizmos.line(start, end, color.with_alpha(0.6));
+            }
+        }
+    }
+}
+
+/// The shield bubble around a ship. It grows a little with each layer the shields will charge to,
+/// and fades as layers get knocked down.
+#[derive(Component)]
+pub struct ShieldGraphic;
+
+/// A green ring around the shield bubble while the ship has any super shield left.
+#[derive(Component)]
+pub struct SuperShieldGraphic;
+
+/// A brief flash on a ship's shields where a projectile was absorbed.
+#[derive(Component)]
+pub struct ShieldFlare(Timer);
+
+const SHIELD_FLARE_SECS: f32 = 0.4;
+
+/// Half extents of the shield ellipse for a ship with one layer of shields.
+fn shield_radii(ship_type: usize) -> Vec2 {
+    SHIPS[ship_type]
+        .cell_positions
+        .iter()
+        .fold(Vec2::ZERO, |extents, x| extents.max(x.abs()))
+        + Vec2::splat(17.5 + 30.0)
+}
+
+/// Each layer past the first makes the bubble this much bigger.
+fn shield_scale(max_layers: usize) -> f32 {
+    1.0 + 0.06 * max_layers.saturating_sub(1) as f32
+}
+
+pub fn add_shield_graphic(
+    ships: Query<(Entity, &ShipIntel), Added<Sprite>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+) {
+    for (ship, intel) in &ships {
+        let radii = shield_radii(intel.basic.ship_type);
+        commands.entity(ship).with_child((
+            ShieldGraphic,
+            PickingBehavior::IGNORE,
+            Mesh2d(meshes.add(Ellipse::new(radii.x, radii.y))),
+            MeshMaterial2d(materials.add(Color::from(palettes::css::DEEP_SKY_BLUE))),
+            Transform::from_xyz(0.0, 0.0, Z_SHIELDS),
+            Visibility::Hidden,
+        ));
+        // A unit ring, stretched out to the ellipse in `update_shields`
+        commands.entity(ship).with_child((
+            SuperShieldGraphic,
+            PickingBehavior::IGNORE,
+            Mesh2d(meshes.add(Annulus::new(0.95, 1.0))),
+            MeshMaterial2d(materials.add(Color::from(palettes::css::LIME).with_alpha(0.6))),
+            Transform::from_xyz(0.0, 0.0, Z_SHIELDS + 0.25),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+pub fn update_super_shields(
+    ships: Query<&ShipIntel>,
+    mut rings: Query<(&Parent, &mut Transform, &mut Visibility), With<SuperShieldGraphic>>,
+) {
+    for (parent, mut transform, mut visibility) in &mut rings {
+        let Ok(intel) = ships.get(**parent) else {
+            continue;
+        };
+        let Some(shields) = intel.basic.shields.filter(|x| x.super_shield > 0) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        // Just outside the biggest the normal bubble gets
+        let radii = shield_radii(intel.basic.ship_type) * shield_scale(shields.max_layers) + 6.0;
+        transform.scale = radii.extend(1.0);
+    }
+}
+
+pub fn update_shields(
+    ships: Query<&ShipIntel>,
+    mut shields: Query<
+        (
+            &Parent,
+            &MeshMaterial2d<ColorMaterial>,
+            &mut Transform,
+            &mut Visibility,
+        ),
+        With<ShieldGraphic>,
+    >,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (parent, material, mut transform, mut visibility) in &mut shields {
+        let Ok(intel) = ships.get(**parent) else {
+            continue;
+        };
+        let Some(shields) = intel.basic.shields.filter(|x| x.max_layers > 0) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        transform.scale = Vec3::splat(shield_scale(shields.max_layers)).with_z(1.0);
+        if let Some(material) = materials.get_mut(&material.0) {
+            let alpha = 0.35 * shields.layers as f32 / shields.max_layers as f32;
+            material.color.set_alpha(alpha);
+        }
+    }
+}
+
+pub fn spawn_shield_flares(
+    mut impacts: EventReader<ShieldImpact>,
+    ships: Query<(&ShipIntel, &Transform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+) {
+    for impact in impacts.read() {
+        let Ok((intel, ship_transform)) = ships.get(impact.ship) else {
+            continue;
+        };
+        // Projectiles come in from `-incidence` in world space, so that's the side of the bubble
+        // they hit
+        let dir = (ship_transform.rotation.inverse() * -impact.incidence.extend(0.0)).xy();
+        let max_layers = intel.basic.shields.map_or(1, |x| x.max_layers);
+        let radii = shield_radii(intel.basic.ship_type) * shield_scale(max_layers);
+        // Scale `dir` out to where it meets the ellipse
+        let point = dir / ((dir / radii).length());
+        commands.entity(impact.ship).with_child((
+            ShieldFlare(Timer::from_seconds(SHIELD_FLARE_SECS, TimerMode::Once)),
+            PickingBehavior::IGNORE,
+            Mesh2d(meshes.add(Circle::new(12.0))),
+            MeshMaterial2d(materials.add(Color::from(palettes::css::LIGHT_CYAN))),
+            Transform::from_translation(point.extend(Z_SHIELDS + 0.5)),
+        ));
+    }
+}
+
+pub fn animate_shield_flares(
+    mut flares: Query<(
+        Entity,
+        &mut ShieldFlare,
+        &MeshMaterial2d<ColorMaterial>,
+        &mut Transform,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (e, mut flare, material, mut transform) in &mut flares {
+        flare.0.tick(time.delta());
+        if flare.0.finished() {
+            commands.entity(e).despawn();
+            materials.remove(&material.0);
+            continue;
+        }
+        let t = flare.0.fraction();
+        transform.scale = Vec3::splat(1.0 + t).with_z(1.0);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color.set_alpha(1.0 - t);
+        }
+    }
+}
+
+/// "CRIT!" or "Graze" floating up off the room a projectile hit.
+#[derive(Component)]
+pub struct HitCallout(Timer);
+
+const HIT_CALLOUT_SECS: f32 = 1.2;
+
+pub fn spawn_hit_callouts(
+    mut impacts: EventReader<HullImpact>,
+    ships: Query<(&ShipIntel, &Transform)>,
+    mut commands: Commands,
+) {
+    for impact in impacts.read() {
+        let (text, color, size) = match impact.quality {
+            HitQuality::Normal => continue,
+            HitQuality::Crit => ("CRIT!", palettes::css::ORANGE, 20.0),
+            HitQuality::Graze => ("Graze", palettes::css::LIGHT_GRAY, 14.0),
+        };
+        let Ok((intel, ship_transform)) = ships.get(impact.ship) else {
+            continue;
+        };
+        let pos = SHIPS[intel.basic.ship_type].room_center(impact.room);
+        commands.entity(impact.ship).with_child((
+            HitCallout(Timer::from_seconds(HIT_CALLOUT_SECS, TimerMode::Once)),
+            PickingBehavior::IGNORE,
+            Text2d::new(text),
+            TextFont::from_font_size(size),
+            TextColor(color.into()),
+            // Keep the text upright however the ship is turned
+            Transform::from_translation(pos.extend(Z_BULLETS))
+                .with_rotation(ship_transform.rotation.inverse()),
+        ));
+    }
+}
+
+pub fn animate_hit_callouts(
+    mut callouts: Query<(Entity, &mut HitCallout, &mut Transform, &mut TextColor)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (e, mut callout, mut transform, mut color) in &mut callouts {
+        callout.0.tick(time.delta());
+        if callout.0.finished() {
+            commands.entity(e).despawn();
+            continue;
+        }
+        transform.translation.y += 24.0 * time.delta_secs();
+        color.0.set_alpha(1.0 - callout.0.fraction());
+    }
+}
+
+/// Charge bars over other ships' weapons rooms, one per weapon. Allies' bars are exact, enemies'
+/// come in pips, and only when our sensors give us [`EnemyWeaponChargeIntel`] for them.
+pub fn draw_enemy_weapon_charge(
+    self_intel: Single<&SelfIntel>,
+    ships: Query<(Entity, &ShipIntel, &GlobalTransform), Without<Dead>>,
+    charge_intel: Query<&WeaponChargeIntel>,
+    enemy_charge_intel: Query<&EnemyWeaponChargeIntel>,
+    theme: Res<ColorTheme>,
+    mut gizmos: Gizmos,
+) {
+    const BAR_WIDTH: f32 = 40.0;
+    const BAR_SPACING: f32 = 6.0;
+    const PIP_GAP: f32 = 2.0;
+
+    for (ship, intel, transform) in &ships {
+        if ship == self_intel.ship {
+            continue;
+        }
+        let exact = charge_intel.get(intel.weapon_charge).ok();
+        let pips = enemy_charge_intel.get(intel.enemy_weapon_charge).ok();
+        if exact.is_none() && pips.is_none() {
+            continue;
+        }
+        let Some(weapons) = &intel.basic.weapons else {
+            continue;
+        };
+        let Some(&room) = intel.basic.system_locations.get(&SystemId::Weapons) else {
+            continue;
+        };
+        let room_center = SHIPS[intel.basic.ship_type].room_center(room);
+        // Bars are stacked above the room in screen space, whichever way the ship is facing
+        let anchor = transform.transform_point(room_center.extend(0.0)).xy() + Vec2::Y * 30.0;
+        for (i, weapon) in weapons.weapons.iter().enumerate() {
+            let fraction = match exact {
+                Some(exact) => {
+                    exact.levels.get(i).copied().unwrap_or_default()
+                        / weapon.weapon.common().charge_time
                 }
+                None => pips.and_then(|x| x.fraction(i)).unwrap_or_default(),
+            };
+            let left = anchor + Vec2::new(-BAR_WIDTH / 2.0, i as f32 * BAR_SPACING);
+            let color = if fraction >= 1.0 {
+                theme.status(Status::Bad)
+            } else if weapon.powered {
+                theme.status(Status::Warning)
+            } else {
+                palettes::basic::GRAY
+            };
+            gizmos.line_2d(left, left + Vec2::X * BAR_WIDTH, palettes::basic::GRAY);
+            if exact.is_some() {
+                gizmos.line_2d(left, left + Vec2::X * BAR_WIDTH * fraction.min(1.0), color);
+                continue;
+            }
+            // Pips with gaps between, so nobody mistakes them for an exact reading
+            let pip_width = BAR_WIDTH / CHARGE_PIPS as f32;
+            let filled = (fraction * CHARGE_PIPS as f32).round() as usize;
+            for pip in 0..filled {
+                let start = left + Vec2::X * pip_width * pip as f32;
+                gizmos.line_2d(start, start + Vec2::X * (pip_width - PIP_GAP), color);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_icon_priorities() {
+        let detail = |current_power, damage_progress| SystemIntel {
+            upgrade_level: 2,
+            damage: 0,
+            current_power,
+            damage_progress,
+        };
+        let theme = ColorTheme::default();
+        let undamaged = Some(SystemDamageIntel::Undamaged);
+        assert_eq!(
+            system_icon_color(&theme, undamaged, None, 0.0),
+            Color::WHITE
+        );
+        assert_eq!(
+            system_icon_color(&theme, undamaged, Some(&detail(0, 0.0)), 0.0),
+            palettes::basic::GRAY.into()
+        );
+        // Damage wins over power, since it's what you need to send crew for
+        assert_eq!(
+            system_icon_color(
+                &theme,
+                Some(SystemDamageIntel::Damaged),
+                Some(&detail(0, 0.0)),
+                0.0
+            ),
+            palettes::css::ORANGE.into()
+        );
+        // Sabotage flashes between red and the usual color
+        let sabotaged = detail(1, 0.5);
+        assert_eq!(
+            system_icon_color(&theme, undamaged, Some(&sabotaged), 0.0),
+            palettes::basic::RED.into()
+        );
+        assert_eq!(
+            system_icon_color(&theme, undamaged, Some(&sabotaged), 0.25),
+            Color::WHITE
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Hover feedback for ship rooms: the room under the pointer gets tinted, and a tooltip shows
+//! whatever we know about it, including how long until it's out of air if it's venting. While a
+//! weapon is being aimed, rooms are tinted by whether it can hit them instead, and area weapons
+//! show how far their shots can stray from the room under the pointer.
+
+use bevy::{color::palettes, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use common::{
+    intel::{InteriorIntel, SelfIntel, ShipIntel},
+    lobby::Team,
+    nav::Cell,
+    ship::SHIPS,
+    weapon::WeaponId,
+};
+
+use crate::{
+    egui_panels::system_damage_label,
+    graphics::RoomGraphic,
+    interaction::{can_target, is_friendly, TargetingWeapon},
+    oxygen_fx::OxygenTrend,
+    theme::{to_egui_color, ColorTheme},
+};
+
+pub fn hover_plugin(app: &mut App) {
+    app.add_observer(hover_room);
+    app.add_observer(unhover_room);
+    app.add_systems(
+        Update,
+        (
+            tint_rooms.run_if(
+                resource_exists_and_changed::<HoveredRoom>
+                    .or(resource_removed::<HoveredRoom>)
+                    .or(resource_exists_and_changed::<TargetingWeapon>)
+                    .or(resource_removed::<TargetingWeapon>)
+                    .or(resource_changed::<ColorTheme>),
+            ),
+            room_tooltip,
+            draw_spread,
+        ),
+    );
+}
+
+/// The room currently under the pointer.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HoveredRoom {
+    pub ship: Entity,
+    pub room: usize,
+}
+
+const HOVER_TINT: Color = Color::srgb(0.75, 0.9, 1.0);
+
+fn hover_room(
+    event: Trigger<Pointer<Over>>,
+    cells: Query<(&RoomGraphic, &Parent)>,
+    mut commands: Commands,
+) {
+    let Ok((&RoomGraphic(room), parent)) = cells.get(event.entity()) else {
+        return;
+    };
+    commands.insert_resource(HoveredRoom {
+        ship: **parent,
+        room,
+    });
+}
+
+fn unhover_room(
+    event: Trigger<Pointer<Out>>,
+    cells: Query<(&RoomGraphic, &Parent)>,
+    hovered: Option<Res<HoveredRoom>>,
+    mut commands: Commands,
+) {
+    let Ok((&RoomGraphic(room), parent)) = cells.get(event.entity()) else {
+        return;
+    };
+    // Moving between two cells of different rooms can fire the new room's `Over` first
+    let left = HoveredRoom {
+        ship: **parent,
+        room,
+    };
+    if hovered.is_some_and(|x| *x == left) {
+        commands.remove_resource::<HoveredRoom>();
+    }
+}
+
+/// Rooms a weapon being aimed can't hit. Clicking them does nothing.
+const INVALID_TARGET_TINT: Color = Color::Srgba(palettes::basic::GRAY);
+
+fn tint_rooms(
+    hovered: Option<Res<HoveredRoom>>,
+    targeting: Option<Res<TargetingWeapon>>,
+    self_intel: Query<&SelfIntel>,
+    ships: Query<&ShipIntel>,
+    teams: Query<&Team>,
+    mut cells: Query<(&RoomGraphic, &Parent, &mut Sprite)>,
+    theme: Res<ColorTheme>,
+) {
+    let hovered = hovered.map(|x| *x);
+    let own_ship = self_intel.get_single().ok().map(|x| x.ship);
+    let own_intel = own_ship.and_then(|x| ships.get(x).ok());
+    let own_type = own_intel.map_or(0, |x| x.basic.ship_type);
+    let own_weapons = own_intel.and_then(|x| x.basic.weapons.as_ref());
+    let picking = targeting.as_ref().map_or(&[][..], |x| x.picking_room());
+    for (&RoomGraphic(room), parent, mut sprite) in &mut cells {
+        let is_hovered = hovered.is_some_and(|x| x.ship == **parent && x.room == room);
+        sprite.color = match (own_ship, own_weapons, ships.get(**parent)) {
+            (Some(own_ship), Some(weapons), Ok(ship)) if !picking.is_empty() => {
+                let friendly = is_friendly(**parent, own_ship, &teams);
+                // Colored after the first weapon that can hit it, brighter under the pointer
+                picking
+                    .iter()
+                    .filter_map(|&i| Some((i, weapons.weapons.get(i)?.weapon)))
+                    .find(|&(i, weapon)| {
+                        can_target(own_type, i, weapon, ship.basic.ship_type, room, friendly)
+                    })
+                    .map_or(INVALID_TARGET_TINT, |(i, weapon)| {
+                        let in_spread = hovered.is_some_and(|x| {
+                            x.ship == **parent
+                                && spread(weapon).is_some_and(|spread| {
+                                    SHIPS[ship.basic.ship_type]
+                                        .rooms_within(x.room, spread)
+                                        .any(|x| x == room)
+                                })
+                        });
+                        let strength = if is_hovered || in_spread { 0.7 } else { 0.35 };
+                        Srgba::WHITE.mix(&theme.weapon(i).1, strength).into()
+                    })
+            }
+            _ if is_hovered => HOVER_TINT,
+            _ => Color::WHITE,
+        };
+    }
+}
+
+/// How far `weapon`'s shots stray from the room they're aimed at, if they do at all.
+fn spread(weapon: WeaponId) -> Option<f32> {
+    weapon
+        .projectile_stats()
+        .map(|x| x.spread)
+        .filter(|&x| x > 0.0)
+}
+
+/// Circle the area the shots of any area weapon being aimed could land in.
+fn draw_spread(
+    hovered: Option<Res<HoveredRoom>>,
+    targeting: Option<Res<TargetingWeapon>>,
+    self_intel: Query<&SelfIntel>,
+    ships: Query<(&ShipIntel, &Transform)>,
+    teams: Query<&Team>,
+    theme: Res<ColorTheme>,
+    mut gizmos: Gizmos,
+) {
+    let (Some(hovered), Some(targeting)) = (hovered, targeting) else {
+        return;
+    };
+    let Ok(self_intel) = self_intel.get_single() else {
+        return;
+    };
+    let (Ok((own_intel, _)), Ok((intel, transform))) =
+        (ships.get(self_intel.ship), ships.get(hovered.ship))
+    else {
+        return;
+    };
+    let Some(weapons) = &own_intel.basic.weapons else {
+        return;
+    };
+    let friendly = is_friendly(hovered.ship, self_intel.ship, &teams);
+    let ship_type = intel.basic.ship_type;
+    let center = SHIPS[ship_type].room_center(hovered.room).extend(0.0);
+    let center = transform.transform_point(center).truncate();
+    for &i in targeting.picking_room() {
+        let Some(weapon) = weapons.weapons.get(i).map(|x| x.weapon) else {
+            continue;
+        };
+        let own_type = own_intel.basic.ship_type;
+        if !can_target(own_type, i, weapon, ship_type, hovered.room, friendly) {
+            continue;
+        }
+        if let Some(spread) = spread(weapon) {
+            gizmos.circle_2d(center, spread, theme.weapon(i).1);
+        }
+    }
+}
+
+fn room_tooltip(
+    mut ui: EguiContexts,
+    hovered: Option<Res<HoveredRoom>>,
+    ships: Query<&ShipIntel>,
+    interiors: Query<&InteriorIntel>,
+    trends: Query<&OxygenTrend>,
+    theme: Res<ColorTheme>,
+) {
+    let Some(hovered) = hovered else {
+        return;
+    };
+    let Ok(intel) = ships.get(hovered.ship) else {
+        return;
+    };
+    let ctx = ui.ctx_mut();
+    let Some(pointer) = ctx.pointer_hover_pos() else {
+        return;
+    };
+    let system = SHIPS[intel.basic.ship_type].room_systems[hovered.room];
+    let subsystem = SHIPS[intel.basic.ship_type].room_subsystems[hovered.room];
+    let interior_intel = interiors.get(intel.interior).ok();
+    let interior = interior_intel.and_then(|x| x.rooms.get(hovered.room));
+    let cells = interior_intel
+        .map(|x| {
+            SHIPS[intel.basic.ship_type].rooms[hovered.room]
+                .cells
+                .iter()
+                .filter_map(|&Cell(cell)| x.cells.get(cell))
+                .copied()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    egui::Area::new(egui::Id::new("room_tooltip"))
+        .fixed_pos(pointer + egui::vec2(16.0, 16.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                match (system, subsystem) {
+                    (Some(system), _) => {
+                        ui.horizontal(|ui| {
+                            ui.strong(format!("{system:?}"));
+                            if let Some(damage) = intel.basic.system_damage(system) {
+                                system_damage_label(ui, &damage);
+                            }
+                        });
+                    }
+                    (None, Some(subsystem)) => {
+                        ui.horizontal(|ui| {
+                            ui.strong(format!("{subsystem:?}"));
+                            if let Some(damage) = intel.basic.subsystems.get(&subsystem) {
+                                system_damage_label(ui, damage);
+                            }
+                        });
+                    }
+                    (None, None) => {
+                        ui.strong("Empty room");
+                    }
+                }
+                let Some(interior) = interior else {
+                    ui.weak("No interior intel");
+                    return;
+                };
+                ui.label(format!("Oxygen: {:.0}%", interior.oxygen * 100.0));
+                if let Ok(trend) = trends.get(hovered.ship) {
+                    let color = to_egui_color(theme.oxygen(trend.rate(hovered.room)));
+                    match trend.eta(hovered.room) {
+                        Some(eta) if trend.rate(hovered.room) < 0.0 => {
+                            ui.colored_label(color, format!("Draining, empty in {}s", eta.ceil()));
+                        }
+                        Some(eta) => {
+                            ui.colored_label(color, format!("Refilling, full in {}s", eta.ceil()));
+                        }
+                        None => {}
+                    }
+                }
+                if cells.iter().any(|x| x.on_fire) {
+                    ui.colored_label(egui::Color32::ORANGE, "On fire");
+                }
+                if cells.iter().any(|x| x.breached) {
+                    ui.colored_label(egui::Color32::LIGHT_BLUE, "Hull breach");
+                }
+                if interior.crew.is_empty() {
+                    ui.label("No crew");
+                }
+                for crew in &interior.crew {
+                    ui.label(format!("{} ({:.0} hp)", crew.name, crew.health));
+                }
+            });
+        });
+}
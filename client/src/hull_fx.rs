@@ -0,0 +1,261 @@
+//! Feedback for hull damage, driven entirely by changes to [`BasicIntel::hull`]: the ship sprite
+//! flashes red, an explosion goes off in the room that was hit, and scorch marks build up as the
+//! hull gets worn down. Once a ship is [`Destroyed`], explosions go off all over it while it fades
+//! out.
+//!
+//! [`BasicIntel::hull`]: common::intel::BasicIntel::hull
+
+use bevy::{color::palettes, prelude::*};
+use common::{
+    bullets::{Progress, RoomTarget, WeaponDamage, SHIELD_PROGRESS},
+    intel::ShipIntel,
+    ship::{Destroyed, SHIPS},
+};
+use rand::{thread_rng, Rng};
+
+use crate::graphics::{Z_EXPLOSIONS, Z_SCORCH};
+
+pub fn hull_fx_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            add_hull_tracker,
+            track_incoming,
+            react_to_hull_damage,
+            animate_hull_flash,
+            destruction_explosions,
+            fade_wrecks,
+            animate_explosions,
+        )
+            .chain(),
+    );
+}
+
+/// Whether every destroyed ship has finished blowing up. Use as a run condition for anything that
+/// should wait until the destruction sequence is over.
+pub fn destruction_finished(wrecks: Query<&Destroyed>) -> bool {
+    wrecks.iter().all(|x| x.is_finished())
+}
+
+const FLASH_SECS: f32 = 0.25;
+const EXPLOSION_SECS: f32 = 0.5;
+/// Time between explosions on a ship that's being destroyed.
+const DESTRUCTION_EXPLOSION_INTERVAL: f32 = 0.15;
+/// Wrecks fade down to this alpha.
+const WRECK_ALPHA: f32 = 0.25;
+
+/// What we knew about a ship's hull last frame.
+#[derive(Component)]
+struct HullTracker {
+    last_hull: usize,
+    /// Room targeted by the projectile closest to hitting this ship. Projectiles are despawned in
+    /// the same tick they deal damage, so by the time we see the hull drop this is the only record
+    /// of where the hit landed.
+    incoming_room: Option<usize>,
+    /// How many rounds of scorch marks have been applied, see [`scorch_level`].
+    scorch_level: usize,
+}
+
+#[derive(Component)]
+struct HullFlash(Timer);
+
+#[derive(Component)]
+struct Explosion(Timer);
+
+#[derive(Component)]
+struct Scorch;
+
+/// 0 above 2/3 hull, 1 above 1/3 and 2 below that.
+fn scorch_level(hull: usize, max_hull: usize) -> usize {
+    if hull * 3 < max_hull {
+        2
+    } else if hull * 3 < max_hull * 2 {
+        1
+    } else {
+        0
+    }
+}
+
+fn add_hull_tracker(ships: Query<(Entity, &ShipIntel), Added<Sprite>>, mut commands: Commands) {
+    for (ship, intel) in &ships {
+        commands.entity(ship).insert(HullTracker {
+            last_hull: intel.basic.hull,
+            incoming_room: None,
+            scorch_level: 0,
+        });
+    }
+}
+
+fn track_incoming(
+    projectiles: Query<(&RoomTarget, &Progress), With<WeaponDamage>>,
+    mut ships: Query<(Entity, &mut HullTracker)>,
+) {
+    for (ship, mut tracker) in &mut ships {
+        let closest = projectiles
+            .iter()
+            .filter(|(target, progress)| target.ship == ship && ***progress >= SHIELD_PROGRESS)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((target, _)) = closest {
+            tracker.incoming_room = Some(target.room);
+        }
+    }
+}
+
+fn react_to_hull_damage(
+    mut ships: Query<(Entity, &ShipIntel, &mut HullTracker)>,
+    assets: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+) {
+    for (ship, intel, mut tracker) in &mut ships {
+        let hull = intel.basic.hull;
+        let ship_type = &SHIPS[intel.basic.ship_type];
+        if hull < tracker.last_hull {
+            commands
+                .entity(ship)
+                .insert(HullFlash(Timer::from_seconds(FLASH_SECS, TimerMode::Once)));
+            // Beams don't leave an obvious single room behind, so those go off at the ship center
+            let pos = tracker
+                .incoming_room
+                .take()
+                .map_or(Vec2::ZERO, |room| ship_type.room_center(room));
+            spawn_explosion(&mut commands, ship, pos, &mut meshes, &mut materials);
+        }
+        tracker.last_hull = hull;
+
+        // Each level scorches a different half of the rooms so they don't pile up in one place
+        let level = scorch_level(hull, intel.basic.max_hull);
+        while tracker.scorch_level < level {
+            let parity = tracker.scorch_level % 2;
+            for room in (0..ship_type.rooms.len()).filter(|x| x % 2 == parity) {
+                commands.entity(ship).with_child((
+                    Scorch,
+                    PickingBehavior::IGNORE,
+                    Sprite {
+                        image: assets.load("scorch.png"),
+                        ..default()
+                    },
+                    Transform::from_translation(ship_type.room_center(room).extend(Z_SCORCH)),
+                ));
+            }
+            tracker.scorch_level += 1;
+        }
+    }
+}
+
+fn spawn_explosion(
+    commands: &mut Commands,
+    ship: Entity,
+    pos: Vec2,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    commands.entity(ship).with_child((
+        Explosion(Timer::from_seconds(EXPLOSION_SECS, TimerMode::Once)),
+        PickingBehavior::IGNORE,
+        Mesh2d(meshes.add(Circle::new(10.0))),
+        MeshMaterial2d(materials.add(Color::from(palettes::css::ORANGE))),
+        Transform::from_translation(pos.extend(Z_EXPLOSIONS)),
+    ));
+}
+
+fn destruction_explosions(
+    wrecks: Query<(Entity, &ShipIntel, &Destroyed)>,
+    mut since_last: Local<f32>,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+) {
+    *since_last += time.delta_secs();
+    if *since_last < DESTRUCTION_EXPLOSION_INTERVAL {
+        return;
+    }
+    *since_last = 0.0;
+    let mut rng = thread_rng();
+    for (ship, intel, destroyed) in &wrecks {
+        if destroyed.is_finished() {
+            continue;
+        }
+        let ship_type = &SHIPS[intel.basic.ship_type];
+        let room = rng.gen_range(0..ship_type.rooms.len());
+        let jitter = Vec2::new(rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0));
+        let pos = ship_type.room_center(room) + jitter;
+        spawn_explosion(&mut commands, ship, pos, &mut meshes, &mut materials);
+    }
+}
+
+/// Fade the wreck and everything drawn on it as the destruction sequence plays out.
+fn fade_wrecks(
+    wrecks: Query<(Entity, &Destroyed), Changed<Destroyed>>,
+    children: Query<&Children>,
+    mut sprites: Query<&mut Sprite>,
+) {
+    for (ship, destroyed) in &wrecks {
+        let alpha = 1.0 - (1.0 - WRECK_ALPHA) * destroyed.progress();
+        for e in std::iter::once(ship).chain(children.iter_descendants(ship)) {
+            if let Ok(mut sprite) = sprites.get_mut(e) {
+                sprite.color.set_alpha(alpha);
+            }
+        }
+    }
+}
+
+fn animate_hull_flash(
+    mut ships: Query<(Entity, &mut HullFlash, &mut Sprite)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (ship, mut flash, mut sprite) in &mut ships {
+        flash.0.tick(time.delta());
+        let t = flash.0.fraction();
+        sprite.color = Color::srgba(1.0, t, t, sprite.color.alpha());
+        if flash.0.finished() {
+            commands.entity(ship).remove::<HullFlash>();
+        }
+    }
+}
+
+fn animate_explosions(
+    mut explosions: Query<(
+        Entity,
+        &mut Explosion,
+        &MeshMaterial2d<ColorMaterial>,
+        &mut Transform,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (e, mut explosion, material, mut transform) in &mut explosions {
+        explosion.0.tick(time.delta());
+        if explosion.0.finished() {
+            commands.entity(e).despawn();
+            materials.remove(&material.0);
+            continue;
+        }
+        let t = explosion.0.fraction();
+        transform.scale = Vec3::splat(1.0 + 2.0 * t).with_z(1.0);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = Color::from(palettes::css::ORANGE)
+                .mix(&Color::from(palettes::css::DARK_RED), t)
+                .with_alpha(1.0 - t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scorch_thresholds() {
+        assert_eq!(scorch_level(30, 30), 0);
+        assert_eq!(scorch_level(20, 30), 0);
+        assert_eq!(scorch_level(19, 30), 1);
+        assert_eq!(scorch_level(10, 30), 1);
+        assert_eq!(scorch_level(9, 30), 2);
+        assert_eq!(scorch_level(0, 30), 2);
+    }
+}
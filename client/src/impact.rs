@@ -0,0 +1,104 @@
+//! Warnings for incoming fire: a countdown next to each projectile headed for our ship showing
+//! how long until it reaches our shields, and an alarm when a big volley is on its way.
+
+use bevy::prelude::*;
+use common::{
+    bullets::{Progress, RoomTarget, TraversalSpeed, SHIELD_PROGRESS},
+    intel::SelfIntel,
+};
+
+/// Play the alarm when at least this many projectiles are inbound at once.
+const LARGE_VOLLEY: usize = 3;
+
+pub fn impact_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (add_impact_labels, update_impact_labels, volley_warning),
+    );
+}
+
+/// Text child of an inbound projectile showing its time to impact.
+#[derive(Component)]
+struct ImpactLabel;
+
+/// Time in seconds until a projectile at `progress` reaches the target's shields, or `None` if it
+/// already has.
+fn time_to_shields(progress: f32, speed: f32) -> Option<f32> {
+    (progress < SHIELD_PROGRESS && speed > 0.0).then(|| (SHIELD_PROGRESS - progress) / speed)
+}
+
+fn add_impact_labels(
+    self_intel: Single<&SelfIntel>,
+    projectiles: Query<(Entity, &RoomTarget), (Added<RoomTarget>, With<TraversalSpeed>)>,
+    mut commands: Commands,
+) {
+    for (projectile, target) in &projectiles {
+        if target.ship != self_intel.ship {
+            continue;
+        }
+        commands.entity(projectile).with_child((
+            ImpactLabel,
+            Text2d::default(),
+            TextFont::from_font_size(12.0),
+            TextColor(Color::srgb(1.0, 0.4, 0.3)),
+            Transform::from_xyz(0.0, 16.0, 0.0),
+        ));
+    }
+}
+
+fn update_impact_labels(
+    projectiles: Query<(&Progress, &TraversalSpeed, &Transform), Without<ImpactLabel>>,
+    mut labels: Query<(&Parent, &mut Text2d, &mut Transform, &mut Visibility), With<ImpactLabel>>,
+) {
+    for (parent, mut text, mut transform, mut visibility) in &mut labels {
+        let Ok((&progress, &speed, projectile)) = projectiles.get(**parent) else {
+            continue;
+        };
+        // Only show the countdown once the projectile is on our half of the screen
+        match time_to_shields(*progress, *speed) {
+            Some(eta) if *progress >= 0.5 => {
+                text.0 = format!("{eta:.1}s");
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+        // Keep the label upright regardless of the projectile's heading
+        transform.rotation = projectile.rotation.inverse();
+    }
+}
+
+fn volley_warning(
+    self_intel: Single<&SelfIntel>,
+    projectiles: Query<(&RoomTarget, &Progress)>,
+    mut was_large: Local<bool>,
+    assets: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let inbound = projectiles
+        .iter()
+        .filter(|(target, progress)| {
+            target.ship == self_intel.ship && ***progress < SHIELD_PROGRESS
+        })
+        .count();
+    let is_large = inbound >= LARGE_VOLLEY;
+    if is_large && !*was_large {
+        commands.spawn((
+            AudioPlayer::new(assets.load("incoming-volley.wav")),
+            PlaybackSettings::DESPAWN,
+        ));
+    }
+    *was_large = is_large;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_to_shields() {
+        let eta = time_to_shields(0.35, 0.5).unwrap();
+        assert!((eta - 1.0).abs() < 1e-5);
+        assert_eq!(time_to_shields(SHIELD_PROGRESS, 0.5), None);
+        assert_eq!(time_to_shields(0.5, 0.0), None);
+    }
+}
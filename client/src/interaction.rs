@@ -1,28 +1,34 @@
-use bevy::{ecs::world::Command, prelude::*};
+use bevy::{ecs::world::Command, math::FloatOrd, prelude::*};
 use common::{
-    bullets::{BeamTarget, RoomTarget},
-    events::{SetBeamWeaponTarget, SetCrewGoal, SetDoorsOpen, SetProjectileWeaponTarget},
-    intel::{SelfIntel, ShipIntel},
-    ship::Dead,
+    bullets::{BeamHits, BeamTarget, RoomTarget},
+    events::{
+        AdjustPower, CommandEvent, LaunchShuttle, SetBeamWeaponTarget, SetCrewGoal, SetDoorsOpen,
+        SetGroupTarget, SetProjectileWeaponTarget, ShipCommand,
+    },
+    intel::{DoorsIntel, SelfIntel, ShipIntel},
+    lobby::Team,
+    ship::{Dead, SHIPS},
     util::{disable, enable},
-    weapon::WeaponId,
+    weapon::{validate_room_target, WeaponId},
 };
 
 use crate::{
-    graphics::{CrewGraphic, DoorGraphic, RoomGraphic},
-    select::{SelectEvent, Selected},
+    graphics::{CrewGraphic, DoorGraphic, RoomGraphic, SystemIcon},
+    hover::HoveredRoom,
+    select::{DeselectAll, SelectEvent, Selected},
+    vent_guard::DoorCommands,
 };
 
 pub fn start_targeting(weapon_index: usize) -> impl Command {
     move |world: &mut World| {
-        let Ok(ship) = world
+        let Ok(my_ship) = world
             .query::<&SelfIntel>()
             .get_single(world)
             .map(|x| x.ship)
         else {
             return;
         };
-        let Ok(ship) = world.query::<&ShipIntel>().get(world, ship) else {
+        let Ok(ship) = world.query::<&ShipIntel>().get(world, my_ship) else {
             return;
         };
         let Some(weapons) = &ship.basic.weapons else {
@@ -30,27 +36,60 @@ pub fn start_targeting(weapon_index: usize) -> impl Command {
         };
         match weapons.weapons[weapon_index].weapon {
             WeaponId::Projectile(_) => {
-                world.send_event(SetProjectileWeaponTarget {
+                let clear = SetProjectileWeaponTarget {
                     weapon_index,
                     target: None,
-                });
+                };
+                world.send_event(clear.for_ship(my_ship));
             }
             WeaponId::Beam(_) => {
-                world.send_event(SetBeamWeaponTarget {
+                let clear = SetBeamWeaponTarget {
                     weapon_index,
                     target: None,
-                });
+                };
+                world.send_event(clear.for_ship(my_ship));
             }
         }
-        world.insert_resource(TargetingWeapon::PickStart { weapon_index });
-        let pick_root = world
-            .query_filtered::<Entity, With<PickRoot>>()
-            .single(world);
-        // Disable pointer down
-        disable::<Observer>(pick_root, world);
+        enter_targeting(world, TargetingWeapon::PickStart { weapon_index });
     }
 }
 
+/// Aim every weapon in the [`WeaponGroup`] at once. Their old targets are cleared straight away,
+/// same as aiming a single weapon.
+pub fn start_group_targeting(world: &mut World) {
+    let weapons = world.resource::<WeaponGroup>().0.clone();
+    if weapons.is_empty() {
+        return;
+    }
+    let Ok(my_ship) = world
+        .query::<&SelfIntel>()
+        .get_single(world)
+        .map(|x| x.ship)
+    else {
+        return;
+    };
+    let clear = SetGroupTarget {
+        weapons: weapons.clone(),
+        target: None,
+    };
+    world.send_event(clear.for_ship(my_ship));
+    enter_targeting(world, TargetingWeapon::PickGroup { weapons });
+}
+
+fn enter_targeting(world: &mut World, targeting: TargetingWeapon) {
+    world.insert_resource(targeting);
+    let pick_root = world
+        .query_filtered::<Entity, With<PickRoot>>()
+        .single(world);
+    // Disable pointer down
+    disable::<Observer>(pick_root, world);
+}
+
+/// Weapon slots the player has ganged together to target with one click. Only the client knows
+/// about groups; the server just gets a [`SetGroupTarget`].
+#[derive(Resource, Default, Debug)]
+pub struct WeaponGroup(pub Vec<usize>);
+
 #[derive(Resource, Debug)]
 pub enum TargetingWeapon {
     PickStart {
@@ -61,17 +100,181 @@ pub enum TargetingWeapon {
         ship: Entity,
         start: Vec2,
     },
+    /// Picking a room for a whole [`WeaponGroup`].
+    PickGroup {
+        weapons: Vec<usize>,
+    },
+}
+
+impl TargetingWeapon {
+    /// The weapon slots still waiting on a room to aim at. Empty once a beam's start is picked.
+    pub fn picking_room(&self) -> &[usize] {
+        match self {
+            Self::PickStart { weapon_index } => std::slice::from_ref(weapon_index),
+            Self::PickGroup { weapons } => weapons,
+            Self::PickDir { .. } => &[],
+        }
+    }
+}
+
+/// Whether `weapon`, in `slot` on a ship of `own_type`, can be aimed at `room` on a ship of
+/// `ship_type`. Same checks the server makes, so we can stay in targeting mode instead of sending a
+/// target it'll turn down.
+pub fn can_target(
+    own_type: usize,
+    slot: usize,
+    weapon: WeaponId,
+    ship_type: usize,
+    room: usize,
+    friendly: bool,
+) -> bool {
+    // Only enemies have to be in the mount's firing arc
+    let in_arc =
+        || friendly || SHIPS[own_type].mount_covers(slot, SHIPS[ship_type].room_center(room));
+    match weapon {
+        WeaponId::Projectile(_) => {
+            validate_room_target(weapon, ship_type, room, friendly).is_ok() && in_arc()
+        }
+        WeaponId::Beam(_) => !friendly && in_arc(),
+    }
+}
+
+/// Whether `ship` is ours or on our team.
+pub fn is_friendly(ship: Entity, own_ship: Entity, teams: &Query<&Team>) -> bool {
+    ship == own_ship
+        || matches!(
+            (teams.get(ship), teams.get(own_ship)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// The rooms the beam we're aiming would hit if we clicked now.
+#[derive(Resource, Debug)]
+pub struct BeamPreview {
+    pub ship: Entity,
+    /// In the order the beam reaches them.
+    pub rooms: Vec<usize>,
 }
 
 #[derive(Component)]
 pub struct PickRoot;
 
+/// A beam aimed at `ship` from `start` towards `cursor`, both in world space. Beam targets are
+/// relative to the ship so they stay put if it moves on screen.
+fn beam_target(
+    ship: Entity,
+    ship_transform: &GlobalTransform,
+    start: Vec2,
+    cursor: Vec2,
+) -> BeamTarget {
+    let world_to_ship = ship_transform.affine().inverse();
+    let start = world_to_ship.transform_point(start.extend(0.0)).xy();
+    let end = world_to_ship.transform_point(cursor.extend(0.0)).xy();
+    let dir = Dir2::new(end - start).unwrap_or(Dir2::Y);
+    BeamTarget { ship, start, dir }
+}
+
+/// Snap a point in a ship's local space to the nearest cell center, edge midpoint or corner, so
+/// beam swaths are easy to line up with the room grid.
+fn snap_beam_start(ship_type: usize, point: Vec2) -> Vec2 {
+    const HALF_CELL: f32 = 17.5;
+    let offsets = [-HALF_CELL, 0.0, HALF_CELL];
+    SHIPS[ship_type]
+        .cell_positions
+        .iter()
+        .flat_map(|&center| {
+            offsets
+                .into_iter()
+                .flat_map(move |x| offsets.into_iter().map(move |y| center + Vec2::new(x, y)))
+        })
+        .min_by_key(|x| FloatOrd(x.distance_squared(point)))
+        .unwrap_or(point)
+}
+
+pub fn update_beam_preview(
+    window: Single<&Window>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    targeting_weapon: Option<Res<TargetingWeapon>>,
+    self_intel: Single<&SelfIntel>,
+    ships: Query<(&ShipIntel, &GlobalTransform)>,
+    mut commands: Commands,
+) {
+    let Some(&TargetingWeapon::PickDir {
+        weapon_index,
+        ship,
+        start,
+    }) = targeting_weapon.as_deref()
+    else {
+        commands.remove_resource::<BeamPreview>();
+        return;
+    };
+    let (camera, camera_transform) = *camera;
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|x| camera.viewport_to_world_2d(camera_transform, x).ok())
+    else {
+        return;
+    };
+    let Ok((own_intel, _)) = ships.get(self_intel.ship) else {
+        return;
+    };
+    let weapon = own_intel
+        .basic
+        .weapons
+        .as_ref()
+        .and_then(|x| x.weapons.get(weapon_index))
+        .map(|x| x.weapon);
+    let Some(WeaponId::Beam(weapon)) = weapon else {
+        return;
+    };
+    let Ok((target_intel, target_transform)) = ships.get(ship) else {
+        return;
+    };
+    let target = beam_target(ship, target_transform, start, cursor);
+    let rooms = BeamHits::compute(target_intel.basic.ship_type, weapon.length, &target)
+        .rooms()
+        .collect();
+    commands.insert_resource(BeamPreview { ship, rooms });
+}
+
+/// A ship dying can end the match or leave us aiming at a wreck, so drop out of targeting mode if
+/// we're in it.
+pub fn cancel_targeting_on_death(dead: Query<(), Added<Dead>>, mut commands: Commands) {
+    if !dead.is_empty() {
+        commands.remove_resource::<TargetingWeapon>();
+    }
+}
+
+/// The server tore down the last match and set up a new one, so anything we were aiming at,
+/// hovering or had selected is gone. Our own ship should have come back with the new match; if it
+/// didn't, something was left pointing into the old one.
+pub fn clear_stale_match_state(
+    self_intel: Query<&SelfIntel>,
+    ships: Query<(), With<ShipIntel>>,
+    mut deselect: EventWriter<DeselectAll>,
+    mut commands: Commands,
+) {
+    commands.remove_resource::<TargetingWeapon>();
+    commands.remove_resource::<BeamPreview>();
+    commands.remove_resource::<HoveredRoom>();
+    deselect.send(DeselectAll);
+    for intel in &self_intel {
+        if !ships.contains(intel.ship) {
+            eprintln!(
+                "Self intel points at ship {:?} from an old match.",
+                intel.ship
+            );
+        }
+    }
+}
+
 pub fn left_click_background(
     event: Trigger<Pointer<Down>>,
     targeting_weapon: Option<Res<TargetingWeapon>>,
     ships: Query<&GlobalTransform>,
     cameras: Query<(&Camera, &GlobalTransform)>,
-    mut beam_targeting: EventWriter<SetBeamWeaponTarget>,
+    self_intel: Query<&SelfIntel>,
+    mut beam_targeting: EventWriter<ShipCommand<SetBeamWeaponTarget>>,
     mut select_events: EventWriter<SelectEvent>,
     mut commands: Commands,
 ) {
@@ -95,18 +298,18 @@ pub fn left_click_background(
                 return;
             };
             commands.remove_resource::<TargetingWeapon>();
-            let Ok(ship_transform) = ships.get(ship) else {
+            let (Ok(ship_transform), Ok(self_intel)) = (ships.get(ship), self_intel.get_single())
+            else {
                 return;
             };
-            let world_to_ship = ship_transform.affine().inverse();
-            let start = world_to_ship.transform_point(start.extend(0.0)).xy();
-            let end = world_to_ship.transform_point(world_cursor.extend(0.0)).xy();
-            let dir = Dir2::new(end - start).unwrap_or(Dir2::Y);
-
-            beam_targeting.send(SetBeamWeaponTarget {
-                weapon_index,
-                target: Some(BeamTarget { ship, start, dir }),
-            });
+            let target = Some(beam_target(ship, ship_transform, start, world_cursor));
+            beam_targeting.send(
+                SetBeamWeaponTarget {
+                    weapon_index,
+                    target,
+                }
+                .for_ship(self_intel.ship),
+            );
         } else {
             select_events.send(SelectEvent::GrowTo(world_cursor));
         }
@@ -118,78 +321,186 @@ pub fn handle_cell_click(
     weapon: Option<Res<TargetingWeapon>>,
     self_intel: Query<&SelfIntel>,
     ships: Query<&ShipIntel>,
+    teams: Query<&Team>,
+    transforms: Query<&GlobalTransform>,
     cells: Query<(&RoomGraphic, &Parent)>,
     selected_crew: Query<&CrewGraphic, With<Selected>>,
     pick_root: Single<Entity, With<PickRoot>>,
-    mut projectile_targeting: EventWriter<SetProjectileWeaponTarget>,
-    mut set_crew_goal: EventWriter<SetCrewGoal>,
+    mut projectile_targeting: EventWriter<ShipCommand<SetProjectileWeaponTarget>>,
+    mut group_targeting: EventWriter<ShipCommand<SetGroupTarget>>,
+    mut set_crew_goal: EventWriter<ShipCommand<SetCrewGoal>>,
+    mut launch_shuttle: EventWriter<ShipCommand<LaunchShuttle>>,
+    mut set_doors_open: DoorCommands,
     mut commands: Commands,
 ) {
     let (&RoomGraphic(room), parent) = cells.get(event.target).unwrap();
     match event.button {
         PointerButton::Primary => {
-            // Target selected weapon at this cell's room
-            let Some(&TargetingWeapon::PickStart { weapon_index }) =
-                weapon.as_ref().map(|x| x.as_ref())
-            else {
+            let Some(targeting) = weapon.as_deref() else {
                 return;
             };
             let ship = **parent;
             let client_ship = self_intel.single().ship;
             let client_intel = ships.get(client_ship).unwrap();
-            let weapon = &client_intel.basic.weapons.as_ref().unwrap().weapons[weapon_index].weapon;
-            if ship == client_ship {
-                // If we're targeting self, make sure that's ok
-                let can_target_self = if let WeaponId::Projectile(weapon) = weapon {
-                    weapon.can_target_self
-                } else {
-                    false
-                };
-                if can_target_self {
+            let weapons = &client_intel.basic.weapons.as_ref().unwrap().weapons;
+            let friendly = is_friendly(ship, client_ship, &teams);
+            let ship_type = ships.get(ship).unwrap().basic.ship_type;
+            let weapon_index = match targeting {
+                &TargetingWeapon::PickStart { weapon_index } => weapon_index,
+                TargetingWeapon::PickGroup { weapons: group } => {
+                    // Good enough if any weapon in the group can hit it, the server skips the rest
+                    let own_type = client_intel.basic.ship_type;
+                    let valid = group
+                        .iter()
+                        .filter_map(|&x| Some((x, weapons.get(x)?)))
+                        .any(|(i, x)| can_target(own_type, i, x.weapon, ship_type, room, friendly));
+                    if valid {
+                        commands.entity(*pick_root).queue(enable::<Observer>);
+                        let target = Some(RoomTarget { ship, room });
+                        group_targeting.send(
+                            SetGroupTarget {
+                                weapons: group.clone(),
+                                target,
+                            }
+                            .for_ship(client_ship),
+                        );
+                        commands.remove_resource::<TargetingWeapon>();
+                    }
                     return;
                 }
+                TargetingWeapon::PickDir { .. } => return,
+            };
+            // Target selected weapon at this cell's room
+            let weapon = &weapons[weapon_index].weapon;
+            let own_type = client_intel.basic.ship_type;
+            if !can_target(own_type, weapon_index, *weapon, ship_type, room, friendly) {
+                return;
             }
             commands.entity(*pick_root).queue(enable::<Observer>);
             match weapon {
                 WeaponId::Projectile(_) => {
-                    projectile_targeting.send(SetProjectileWeaponTarget {
-                        target: Some(RoomTarget { ship, room }),
-                        weapon_index,
-                    });
+                    let target = Some(RoomTarget { ship, room });
+                    projectile_targeting.send(
+                        SetProjectileWeaponTarget {
+                            target,
+                            weapon_index,
+                        }
+                        .for_ship(client_ship),
+                    );
                     commands.remove_resource::<TargetingWeapon>();
                 }
                 WeaponId::Beam(_) => {
+                    let ship_transform = transforms.get(ship).unwrap();
+                    let hit = event.hit.position.unwrap();
+                    let local = ship_transform.affine().inverse().transform_point(hit).xy();
+                    let snapped = snap_beam_start(ship_type, local);
+                    if !SHIPS[own_type].mount_covers(weapon_index, snapped) {
+                        return;
+                    }
+                    let start = ship_transform.transform_point(snapped.extend(0.0)).xy();
                     commands.insert_resource(TargetingWeapon::PickDir {
                         weapon_index,
                         ship,
-                        start: event.hit.position.unwrap().xy(),
+                        start,
                     });
                 }
             }
         }
         PointerButton::Secondary => {
-            // Send selected crew to this cell's room
+            let ship = **parent;
+            let client_ship = self_intel.single().ship;
+            if ship == client_ship {
+                // Send selected crew to this cell's room
+                for &CrewGraphic(crew) in &selected_crew {
+                    set_crew_goal.send(SetCrewGoal { crew, room }.for_ship(client_ship));
+                }
+            } else if !is_friendly(ship, client_ship, &teams) && !selected_crew.is_empty() {
+                // Shuttle selected crew over to board it
+                let crew = selected_crew.iter().map(|&CrewGraphic(x)| x).collect();
+                let target = RoomTarget { ship, room };
+                launch_shuttle.send(LaunchShuttle { crew, target }.for_ship(client_ship));
+            }
+        }
+        PointerButton::Middle => {
+            // Open the doors between selected crew and this cell's room
+            let self_intel = self_intel.single();
+            if **parent != self_intel.ship {
+                return;
+            }
+            let ship_type = ships.get(self_intel.ship).unwrap().basic.ship_type;
             for &CrewGraphic(crew) in &selected_crew {
-                set_crew_goal.send(SetCrewGoal { crew, room });
+                let Some(crew) = self_intel.crew.get(crew) else {
+                    continue;
+                };
+                let from = SHIPS[ship_type].cell_room(crew.nav_status.occupied_cell());
+                set_doors_open.send(SetDoorsOpen::OpenPath { from, to: room }.for_ship(**parent));
             }
         }
+    }
+}
+
+/// Left click a system's icon on our own ship to power it up, right click to power it down. The
+/// icon doesn't block the cells under it, so while a weapon is being targeted or crew are selected
+/// the click is left for [`handle_cell_click`] instead.
+pub fn click_system_icon(
+    event: Trigger<Pointer<Down>>,
+    weapon: Option<Res<TargetingWeapon>>,
+    icons: Query<(&SystemIcon, &Parent)>,
+    selected_crew: Query<(), (With<CrewGraphic>, With<Selected>)>,
+    mut adjust_power: EventWriter<ShipCommand<AdjustPower>>,
+) {
+    let (&SystemIcon(system), parent) = icons.get(event.target).unwrap();
+    match event.button {
+        PointerButton::Primary if weapon.is_none() => {
+            adjust_power.send(AdjustPower::request(system).for_ship(**parent));
+        }
+        PointerButton::Secondary if selected_crew.is_empty() => {
+            adjust_power.send(AdjustPower::remove(system).for_ship(**parent));
+        }
         _ => {}
     }
 }
 
+/// The door the keyboard door controls act on. Only ever points at one of our own doors.
+#[derive(Resource, Default, Debug)]
+pub struct FocusedDoor(pub Option<usize>);
+
+pub fn draw_focused_door(
+    focused: Res<FocusedDoor>,
+    self_intel: Single<&SelfIntel>,
+    doors: Query<(&DoorGraphic, &Parent, &GlobalTransform)>,
+    mut gizmos: Gizmos,
+) {
+    let Some(focused) = focused.0 else {
+        return;
+    };
+    for (&DoorGraphic(door), parent, transform) in &doors {
+        if door == focused && parent.get() == self_intel.ship {
+            gizmos.circle_2d(transform.translation().xy(), 12.0, Color::WHITE);
+        }
+    }
+}
+
 pub fn toggle_door(
     event: Trigger<Pointer<Click>>,
     ships: Query<&ShipIntel, Without<Dead>>,
+    doors_intel: Query<&DoorsIntel>,
     doors: Query<(&DoorGraphic, &Parent)>,
-    mut set_doors_open: EventWriter<SetDoorsOpen>,
+    mut set_doors_open: DoorCommands,
 ) {
     let (&DoorGraphic(door), parent) = doors.get(event.target).unwrap();
     let Ok(ship) = ships.get(**parent) else {
         return;
     };
-    let is_open = ship.basic.doors[door].open;
-    set_doors_open.send(SetDoorsOpen::Single {
-        door,
-        open: !is_open,
-    });
+    let Some(state) = doors_intel.get(ship.doors).ok().and_then(|x| x.get(door)) else {
+        return;
+    };
+    let is_open = state.open;
+    set_doors_open.send(
+        SetDoorsOpen::Single {
+            door,
+            open: !is_open,
+        }
+        .for_ship(**parent),
+    );
 }
@@ -0,0 +1,195 @@
+//! Kill feed: the big moments of the match (volleys, systems going down, crew dying, fires) flash
+//! up in the corner as they happen. The same events make up the timeline on the post-game screen,
+//! with the same timestamps as the [`CombatLog`](crate::combat_log::CombatLog).
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, RichText},
+    EguiContexts,
+};
+use common::{
+    intel::SelfIntel,
+    journal::{CombatLogEntry, MatchEvent},
+    lobby::{MatchEpoch, Team},
+};
+
+use crate::combat_log::{match_time, side};
+
+/// How long a line stays in the feed, in seconds.
+const FEED_TIME: f32 = 6.0;
+/// Lines fade out over this many seconds at the end of their time.
+const FEED_FADE: f32 = 1.0;
+/// Most lines shown at once. Older ones are dropped early to make room.
+const MAX_LINES: usize = 6;
+
+pub fn kill_feed_plugin(app: &mut App) {
+    app.init_resource::<KillFeed>().add_systems(
+        Update,
+        (
+            clear_kill_feed.run_if(resource_exists_and_changed::<MatchEpoch>),
+            receive_kill_feed,
+            draw_kill_feed,
+        )
+            .chain(),
+    );
+}
+
+/// A major event as it's shown in the feed and the timeline.
+#[derive(Clone, Debug)]
+pub struct TimelineEvent {
+    /// Seconds since the match started.
+    pub time: f32,
+    /// The ship it happened to, or that did it for volleys.
+    pub ship: Entity,
+    pub line: String,
+}
+
+/// Lines currently in the feed, oldest first, with the time each one expires.
+#[derive(Resource, Default)]
+struct KillFeed(Vec<(TimelineEvent, f32)>);
+
+fn clear_kill_feed(mut feed: ResMut<KillFeed>) {
+    feed.0.clear();
+}
+
+fn receive_kill_feed(
+    mut entries: EventReader<CombatLogEntry>,
+    mut feed: ResMut<KillFeed>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    feed.0.retain(|&(_, expires)| expires > now);
+    let events = major_events(entries.read());
+    feed.0
+        .extend(events.into_iter().map(|x| (x, now + FEED_TIME)));
+    let excess = feed.0.len().saturating_sub(MAX_LINES);
+    feed.0.drain(..excess);
+}
+
+fn draw_kill_feed(
+    mut ui: EguiContexts,
+    feed: Res<KillFeed>,
+    self_intel: Query<&SelfIntel>,
+    teams: Query<&Team>,
+    time: Res<Time>,
+) {
+    if feed.0.is_empty() {
+        return;
+    }
+    let Ok(self_intel) = self_intel.get_single() else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    egui::Area::new(egui::Id::new("kill_feed"))
+        .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::new(-8.0, 40.0))
+        .interactable(false)
+        .show(ui.ctx_mut(), |ui| {
+            for (event, expires) in &feed.0 {
+                let alpha = ((expires - now) / FEED_FADE).clamp(0.0, 1.0);
+                egui::Frame::popup(ui.style())
+                    .multiply_with_opacity(alpha)
+                    .show(ui, |ui| {
+                        let text = timeline_line(event, self_intel.ship, &teams);
+                        let color = ui.visuals().text_color().gamma_multiply(alpha);
+                        ui.label(RichText::new(text).color(color).small());
+                    });
+            }
+        });
+}
+
+/// `event` as a line of the feed or timeline, as seen from `own_ship`.
+pub fn timeline_line(event: &TimelineEvent, own_ship: Entity, teams: &Query<&Team>) -> String {
+    let side = side(event.ship, own_ship, teams);
+    format!("{} [{side}] {}", match_time(event.time), event.line)
+}
+
+/// The entries worth a line in the feed, in order. Weapons a ship fires together are one volley.
+pub fn major_events<'a>(
+    entries: impl IntoIterator<Item = &'a CombatLogEntry>,
+) -> Vec<TimelineEvent> {
+    let mut events = Vec::<TimelineEvent>::new();
+    // The volley the last entry was part of, so the next weapon fired alongside it joins it
+    let mut volley: Option<usize> = None;
+    for &CombatLogEntry { time, ref event } in entries {
+        let (ship, line) = match *event {
+            MatchEvent::ShotFired { ship, weapon, .. } => {
+                let name = weapon.common().name;
+                match volley {
+                    Some(i) if events[i].ship == ship && events[i].time == time => {
+                        events[i].line.push_str(&format!(", {name}"));
+                    }
+                    _ => {
+                        volley = Some(events.len());
+                        events.push(TimelineEvent {
+                            time,
+                            ship,
+                            line: format!("fired {name}"),
+                        });
+                    }
+                }
+                continue;
+            }
+            MatchEvent::SystemDamaged {
+                target,
+                system,
+                destroyed: true,
+                ..
+            } => (target, format!("{system} destroyed")),
+            MatchEvent::CrewDied {
+                ship,
+                ref name,
+                killer,
+            } => match killer {
+                Some(_) => (ship, format!("{name} was killed")),
+                None => (ship, format!("{name} died")),
+            },
+            MatchEvent::FireStarted { target, .. } => (target, "fire broke out".into()),
+            MatchEvent::ShuttleShotDown { ship, .. } => (ship, "boarding shuttle shot down".into()),
+            MatchEvent::ShipDestroyed { ship } => (ship, "ship destroyed".into()),
+            MatchEvent::WonTiebreak { ship } => (ship, "survived on the last hit".into()),
+            MatchEvent::DodgeRoll { .. } => continue,
+            _ => {
+                volley = None;
+                continue;
+            }
+        };
+        volley = None;
+        events.push(TimelineEvent { time, ship, line });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use common::weapon::BURST_LASER_MK_I;
+
+    use super::*;
+
+    #[test]
+    fn weapons_fired_together_are_one_volley() {
+        let ship = Entity::from_raw(1);
+        let shot = |time, weapon_index| CombatLogEntry {
+            time,
+            event: MatchEvent::ShotFired {
+                ship,
+                weapon_index,
+                weapon: BURST_LASER_MK_I,
+            },
+        };
+        let entries = [
+            shot(1.0, 0),
+            shot(1.0, 1),
+            shot(2.0, 0),
+            CombatLogEntry {
+                time: 2.0,
+                event: MatchEvent::ShipDestroyed { ship },
+            },
+            shot(2.0, 1),
+        ];
+        let events = major_events(&entries);
+        let times = events.iter().map(|x| x.time).collect::<Vec<_>>();
+        assert_eq!(times, vec![1.0, 2.0, 2.0, 2.0]);
+        let name = BURST_LASER_MK_I.common().name;
+        assert_eq!(events[0].line, format!("fired {name}, {name}"));
+    }
+}
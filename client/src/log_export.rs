@@ -0,0 +1,141 @@
+//! Exporting a finished match's combat log and stats for sharing or digging into elsewhere. JSON
+//! has everything, raw events included. CSV has the stats and a readable line per event, ready to
+//! paste into a spreadsheet. Files go in `exports/`, named for when they were saved.
+
+use std::{
+    fmt::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use common::{
+    journal::{CombatLogEntry, MatchEvent},
+    lobby::Team,
+    stats::PlayerStats,
+};
+use serde::Serialize;
+
+use crate::combat_log::{describe, side};
+
+/// A match as we saw it, ready to be written out.
+#[derive(Serialize)]
+pub struct MatchExport<'a> {
+    /// Victory, Defeat or Draw.
+    pub result: &'static str,
+    /// Seconds.
+    pub duration: f32,
+    pub you: PlayerStats,
+    /// One per captain on the other teams.
+    pub enemies: Vec<PlayerStats>,
+    pub log: Vec<ExportEntry<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct ExportEntry<'a> {
+    /// Seconds since the match started.
+    pub time: f32,
+    /// Whose ship it happened to, from our side.
+    pub side: &'static str,
+    pub text: String,
+    pub event: &'a MatchEvent,
+}
+
+impl<'a> ExportEntry<'a> {
+    /// Entries for everything in `log` worth a line in the combat log, from `own_ship`'s side.
+    pub fn from_log(
+        log: impl IntoIterator<Item = &'a CombatLogEntry>,
+        own_ship: Entity,
+        teams: &Query<&Team>,
+    ) -> Vec<Self> {
+        log.into_iter()
+            .filter_map(|CombatLogEntry { time, event }| {
+                let (ship, text) = describe(event)?;
+                Some(Self {
+                    time: *time,
+                    side: side(ship, own_ship, teams),
+                    text,
+                    event,
+                })
+            })
+            .collect()
+    }
+}
+
+impl MatchExport<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The stats, then a blank line, then the log.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("stat,you");
+        for label in enemy_labels(self.enemies.len()) {
+            write!(csv, ",{}", label.to_lowercase()).unwrap();
+        }
+        csv.push('\n');
+        let mut row = |label: &str, f: &dyn Fn(&PlayerStats) -> String| {
+            write!(csv, "{label},{}", f(&self.you)).unwrap();
+            for enemy in &self.enemies {
+                write!(csv, ",{}", f(enemy)).unwrap();
+            }
+            csv.push('\n');
+        };
+        row("Damage dealt", &|x| x.damage_dealt.to_string());
+        row("Damage taken", &|x| x.damage_taken.to_string());
+        row("Shots fired", &|x| x.shots_fired.to_string());
+        row("Shots hit", &|x| x.shots_hit.to_string());
+        row("Crew kills", &|x| x.crew_kills.to_string());
+        row("Systems destroyed", &|x| x.systems_destroyed.to_string());
+        row("Oxygen downtime (s)", &|x| {
+            format!("{:.1}", x.oxygen_downtime.as_secs_f32())
+        });
+        csv.push_str("\ntime,side,event\n");
+        for entry in &self.log {
+            let text = csv_field(&entry.text);
+            writeln!(csv, "{:.2},{},{text}", entry.time, entry.side).unwrap();
+        }
+        csv
+    }
+}
+
+/// Column headings for `count` enemies: just "Enemy" if there's one, numbered otherwise.
+pub fn enemy_labels(count: usize) -> Vec<String> {
+    if count == 1 {
+        return vec!["Enemy".into()];
+    }
+    (1..=count).map(|x| format!("Enemy {x}")).collect()
+}
+
+/// Quote `field` if it would otherwise break the row apart.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+/// Write `contents` to `exports/<unix time>.<extension>`, returning where it went.
+pub fn save_export(contents: &str, extension: &str) -> std::io::Result<PathBuf> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let path = PathBuf::from("exports").join(format!("{}.{extension}", now.as_secs()));
+    std::fs::create_dir_all("exports")?;
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        assert_eq!(csv_field("fired Heavy Laser"), "fired Heavy Laser");
+        assert_eq!(
+            csv_field("took 2 hull damage, critical"),
+            "\"took 2 hull damage, critical\""
+        );
+        assert_eq!(csv_field("\"Ace\" died"), "\"\"\"Ace\"\" died\"");
+    }
+}
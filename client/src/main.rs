@@ -1,38 +1,79 @@
+mod alerts;
+mod beam_fx;
+mod camera;
+mod combat_log;
+mod debug_overlay;
 mod egui_panels;
 mod graphics;
+mod hover;
+mod hull_fx;
+mod impact;
 mod interaction;
+mod kill_feed;
+mod log_export;
+mod main_menu;
+mod oxygen_fx;
+mod particles;
+mod power_hud;
+mod prediction;
+mod profiles;
 mod select;
+#[cfg(feature = "editor")]
+mod ship_editor;
+mod smoothing;
+mod theme;
+mod toasts;
+mod vent_guard;
+mod weapon_fx;
 
 use crate::{
     egui_panels::{
-        crew_panel, enemy_panels, power_panel, ready_panel, shields_panel, status_panel,
-        weapons_panel,
+        adjust_panel_scale, apply_panel_scale, beam_preview_label, cargo_panel, crew_panel,
+        enemy_panels, match_clock_label, post_game_panel, power_panel, ready_panel, shields_panel,
+        status_panel, weapons_panel, PanelScale,
     },
-    select::{selection_plugin, SelectEvent, SelectionEnabled},
+    graphics::CrewGraphic,
+    select::{selection_plugin, SelectEvent, Selected, SelectionEnabled},
 };
-use bevy::{math::vec2, prelude::*};
+use alerts::alerts_plugin;
+use beam_fx::beam_fx_plugin;
+use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 use bevy_replicon::prelude::*;
-use bevy_replicon_renet::{
-    netcode::{ClientAuthentication, NetcodeClientTransport},
-    renet::{ConnectionConfig, RenetClient},
-    RenetChannelsExt as _, RepliconRenetPlugins,
-};
+use bevy_replicon_renet::RepliconRenetPlugins;
+use camera::camera_plugin;
+use combat_log::combat_log_plugin;
 use common::{
-    events::{AdjustPower, CrewStations, PowerDir, SetAutofire, SetDoorsOpen, WeaponPower},
-    intel::{SelfIntel, ShipIntel},
-    lobby::ReadyState,
-    protocol_plugin,
+    events::{
+        AdjustPower, CommandEvent, CrewStations, PowerDir, QueuePower, ReroutePower, SetAutofire,
+        SetDoorsOpen, ShipCommand, WeaponPower,
+    },
+    intel::{DoorsIntel, SelfIntel, ShipIntel, WeaponChargeIntel},
+    journal::MatchSummary,
+    lobby::{MatchEpoch, ReadyState},
+    match_clock::MatchClock,
     ship::SystemId,
     util::{enable, init_resource, remove_resource},
-    PROTOCOL_ID,
 };
+use debug_overlay::debug_overlay_plugin;
+use ftl_protocol::ProtocolPlugin;
 use graphics::{
-    add_ship_graphic, draw_beams, draw_targets, set_bullet_incidence, spawn_projectile_graphics,
-    sync_crew_count, sync_crew_positions, update_bullet_graphic, update_doors, update_no_intel,
-    update_oxygen, update_vacuum,
+    add_shield_graphic, add_ship_graphic, animate_hit_callouts, animate_missile_trails,
+    animate_shield_flares, draw_crew_health, draw_enemy_weapon_charge, draw_firing_arcs,
+    draw_targets, layout_ships, spawn_hit_callouts, spawn_projectile_graphics, spawn_shield_flares,
+    sync_crew_colors, sync_crew_count, sync_crew_positions, update_bullet_graphic, update_doors,
+    update_no_intel, update_oxygen, update_shields, update_super_shields, update_system_icons,
+    update_vacuum,
+};
+use hover::hover_plugin;
+use hull_fx::{destruction_finished, hull_fx_plugin};
+use impact::impact_plugin;
+use interaction::{
+    cancel_targeting_on_death, clear_stale_match_state, draw_focused_door, left_click_background,
+    start_group_targeting, start_targeting, update_beam_preview, BeamPreview, FocusedDoor,
+    PickRoot, TargetingWeapon, WeaponGroup,
 };
-use interaction::{left_click_background, start_targeting, PickRoot, TargetingWeapon};
+use kill_feed::kill_feed_plugin;
 use leafwing_input_manager::{
     action_state::ActionState,
     input_map::InputMap,
@@ -40,10 +81,16 @@ use leafwing_input_manager::{
     prelude::{ButtonlikeChord, ModifierKey},
     Actionlike, InputControlKind, InputManagerBundle,
 };
-use std::{
-    net::{Ipv4Addr, SocketAddr, UdpSocket},
-    time::SystemTime,
-};
+use main_menu::main_menu_plugin;
+use oxygen_fx::oxygen_fx_plugin;
+use particles::particles_plugin;
+use power_hud::{power_hud_plugin, UsePowerHud};
+use prediction::prediction_plugin;
+use profiles::profiles_plugin;
+use theme::theme_plugin;
+use toasts::toasts_plugin;
+use vent_guard::{vent_guard_plugin, DoorCommands};
+use weapon_fx::weapon_fx_plugin;
 
 fn main() {
     App::new()
@@ -53,11 +100,6 @@ fn main() {
                     primary_window: Some(Window {
                         resolution: bevy::window::WindowResolution::new(1280.0, 720.0),
                         title: "PVP: Paster Vhan Pight".into(),
-                        resizable: false,
-                        enabled_buttons: bevy::window::EnabledButtons {
-                            maximize: false,
-                            ..default()
-                        },
                         ..default()
                     }),
                     ..default()
@@ -67,40 +109,112 @@ fn main() {
             InputManagerPlugin::<Controls>::default(),
             RepliconPlugins,
             RepliconRenetPlugins,
-            protocol_plugin,
+            ProtocolPlugin,
             selection_plugin,
+            impact_plugin,
+            hover_plugin,
+            camera_plugin,
+            hull_fx_plugin,
+            beam_fx_plugin,
+            alerts_plugin,
+            toasts_plugin,
+            theme_plugin,
+        ))
+        .add_plugins((
+            power_hud_plugin,
+            vent_guard_plugin,
+            prediction_plugin,
+            debug_overlay_plugin,
+            combat_log_plugin,
+            main_menu_plugin,
+            oxygen_fx_plugin,
+            kill_feed_plugin,
+            profiles_plugin,
+            particles_plugin,
+            weapon_fx_plugin,
         ))
-        .add_systems(Startup, connect_to_server)
+        .init_resource::<PanelScale>()
+        .init_resource::<WeaponGroup>()
+        .init_resource::<FocusedDoor>()
         .add_systems(Startup, setup)
         .add_systems(
             Update,
             (
-                power_panel,
+                power_panel.run_if(resource_equals(UsePowerHud(false))),
                 status_panel,
                 weapons_panel,
+                cargo_panel,
                 shields_panel,
                 enemy_panels,
                 ready_panel.run_if(resource_exists::<ReadyState>),
                 add_ship_controls,
                 add_ship_graphic,
                 crew_panel,
+                post_game_panel.run_if(resource_exists::<MatchSummary>.and(destruction_finished)),
+                beam_preview_label.run_if(resource_exists::<BeamPreview>),
+                match_clock_label.run_if(resource_exists::<MatchClock>),
             ),
         )
-        .add_systems(Update, (sync_crew_count, sync_crew_positions).chain())
         .add_systems(
             Update,
             (
-                set_bullet_incidence,
+                receive_match_summary,
+                remove_resource::<MatchSummary>.run_if(resource_added::<ReadyState>),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                adjust_panel_scale,
+                apply_panel_scale.run_if(resource_changed::<PanelScale>),
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (sync_crew_count, sync_crew_positions, sync_crew_colors).chain(),
+        )
+        .add_systems(
+            Update,
+            (
                 spawn_projectile_graphics,
                 update_bullet_graphic,
-                draw_beams,
+                animate_missile_trails,
                 update_doors,
+                update_system_icons,
                 update_oxygen,
                 update_vacuum,
                 update_no_intel,
             ),
         )
-        .add_systems(Update, (controls, draw_targets))
+        .add_systems(
+            Update,
+            (
+                controls,
+                navigation_controls,
+                draw_focused_door,
+                layout_ships,
+                update_beam_preview,
+                draw_targets,
+                draw_firing_arcs,
+                draw_crew_health,
+                draw_enemy_weapon_charge,
+                cancel_targeting_on_death,
+                clear_stale_match_state.run_if(resource_exists_and_changed::<MatchEpoch>),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                add_shield_graphic,
+                update_shields,
+                update_super_shields,
+                spawn_shield_flares,
+                animate_shield_flares,
+                spawn_hit_callouts,
+                animate_hit_callouts,
+            ),
+        )
         .add_systems(
             Update,
             (
@@ -115,27 +229,10 @@ fn main() {
         .run();
 }
 
-fn connect_to_server(channels: Res<RepliconChannels>, mut commands: Commands) {
-    let current_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap();
-    let client_id = current_time.as_millis() as u64;
-    let server_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 5000);
-    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).unwrap();
-    let authentication = ClientAuthentication::Unsecure {
-        client_id,
-        protocol_id: PROTOCOL_ID,
-        server_addr,
-        user_data: None,
-    };
-    commands.insert_resource(RenetClient::new(ConnectionConfig {
-        server_channels_config: channels.get_server_configs(),
-        client_channels_config: channels.get_client_configs(),
-        ..default()
-    }));
-    commands.insert_resource(
-        NetcodeClientTransport::new(current_time, authentication, socket).unwrap(),
-    );
+fn receive_match_summary(mut summaries: EventReader<MatchSummary>, mut commands: Commands) {
+    if let Some(summary) = summaries.read().last() {
+        commands.insert_resource(summary.clone());
+    }
 }
 
 fn setup(mut commands: Commands, assets: Res<AssetServer>) {
@@ -178,21 +275,32 @@ fn setup(mut commands: Commands, assets: Res<AssetServer>) {
             },
         ))
         .observe(
-            |event: Trigger<Pointer<Up>>, mut select_events: EventWriter<SelectEvent>| {
+            |event: Trigger<Pointer<Up>>,
+             camera: Single<(&Camera, &GlobalTransform)>,
+             mut select_events: EventWriter<SelectEvent>| {
                 if event.button == PointerButton::Primary {
                     select_events.send(SelectEvent::Complete);
                 }
-                select_events.send(SelectEvent::GrowTo(
-                    event.pointer_location.position * vec2(1.0, -1.0) + vec2(-640.0, 360.0),
-                ));
+                let (camera, camera_transform) = *camera;
+                if let Ok(world_pos) =
+                    camera.viewport_to_world_2d(camera_transform, event.pointer_location.position)
+                {
+                    select_events.send(SelectEvent::GrowTo(world_pos));
+                }
             },
         )
         .observe(
-            |event: Trigger<Pointer<Drag>>, mut select_events: EventWriter<SelectEvent>| {
-                if event.button == PointerButton::Primary {
-                    select_events.send(SelectEvent::GrowTo(
-                        event.pointer_location.position * vec2(1.0, -1.0) + vec2(-640.0, 360.0),
-                    ));
+            |event: Trigger<Pointer<Drag>>,
+             camera: Single<(&Camera, &GlobalTransform)>,
+             mut select_events: EventWriter<SelectEvent>| {
+                if event.button != PointerButton::Primary {
+                    return;
+                }
+                let (camera, camera_transform) = *camera;
+                if let Ok(world_pos) =
+                    camera.viewport_to_world_2d(camera_transform, event.pointer_location.position)
+                {
+                    select_events.send(SelectEvent::GrowTo(world_pos));
                 }
             },
         );
@@ -210,29 +318,52 @@ fn add_ship_controls(
             use KeyCode::*;
             use SystemId::*;
             let shift = |key| ButtonlikeChord::modified(ModifierKey::Shift, key);
+            let ctrl = |key| ButtonlikeChord::modified(ModifierKey::Control, key);
             InputMap::default()
                 .with(Controls::Autofire, KeyV)
+                .with(Controls::TargetGroup, KeyG)
                 .with(Controls::AllDoors { open: true }, KeyZ)
                 .with(Controls::AllDoors { open: false }, KeyX)
+                .with(Controls::VentUnoccupied, shift(KeyZ))
+                .with(Controls::CloseExterior, shift(KeyX))
                 .with(Controls::SaveStations, Slash)
                 .with(Controls::ReturnToStations, Enter)
                 .with(Controls::power_system(Shields), KeyA)
                 .with(Controls::power_system(Engines), KeyS)
                 .with(Controls::power_system(Weapons), KeyW)
                 .with(Controls::power_system(Oxygen), KeyF)
+                .with(Controls::power_system(CloneBay), KeyB)
                 .with(Controls::power_weapon(0), Digit1)
                 .with(Controls::power_weapon(1), Digit2)
                 .with(Controls::power_weapon(2), Digit3)
                 .with(Controls::power_weapon(3), Digit4)
                 .with(Controls::depower_system(Shields), shift(KeyA))
-                .with(Controls::depower_system(Shields), shift(KeyA))
                 .with(Controls::depower_system(Engines), shift(KeyS))
                 .with(Controls::depower_system(Weapons), shift(KeyW))
                 .with(Controls::depower_system(Oxygen), shift(KeyF))
+                .with(Controls::depower_system(CloneBay), shift(KeyB))
                 .with(Controls::depower_weapon(0), shift(Digit1))
                 .with(Controls::depower_weapon(1), shift(Digit2))
                 .with(Controls::depower_weapon(2), shift(Digit3))
                 .with(Controls::depower_weapon(3), shift(Digit4))
+                .with(Controls::ReroutePower(Shields), ctrl(KeyA))
+                .with(Controls::ReroutePower(Engines), ctrl(KeyS))
+                .with(Controls::ReroutePower(Weapons), ctrl(KeyW))
+                .with(Controls::ReroutePower(Oxygen), ctrl(KeyF))
+                .with(Controls::ReroutePower(CloneBay), ctrl(KeyB))
+                // F1-F8 are all crew; F12 belongs to the debug overlay, which isn't rebindable
+                .with(Controls::SelectCrew(0), F1)
+                .with(Controls::SelectCrew(1), F2)
+                .with(Controls::SelectCrew(2), F3)
+                .with(Controls::SelectCrew(3), F4)
+                .with(Controls::SelectCrew(4), F5)
+                .with(Controls::SelectCrew(5), F6)
+                .with(Controls::SelectCrew(6), F7)
+                .with(Controls::SelectCrew(7), F8)
+                .with(Controls::CycleDoor, Tab)
+                .with(Controls::ToggleDoor, KeyD)
+                .with(Controls::TargetNextCharged, KeyT)
+                .with(Controls::CancelTargeting, Escape)
         } else {
             default()
         };
@@ -244,12 +375,34 @@ fn add_ship_controls(
 
 #[derive(Reflect, Debug, Clone, Hash, PartialEq, Eq)]
 enum Controls {
-    SystemPower { dir: PowerDir, system: SystemId },
-    WeaponPower { dir: PowerDir, weapon_index: usize },
+    SystemPower {
+        dir: PowerDir,
+        system: SystemId,
+    },
+    WeaponPower {
+        dir: PowerDir,
+        weapon_index: usize,
+    },
+    /// Pull power into a system from its donor, see [`ReroutePower::to`].
+    ReroutePower(SystemId),
+    TargetGroup,
     Autofire,
-    AllDoors { open: bool },
+    AllDoors {
+        open: bool,
+    },
+    VentUnoccupied,
+    CloseExterior,
     SaveStations,
     ReturnToStations,
+    /// Select just the crew member at this index in [`SelfIntel::crew`].
+    SelectCrew(usize),
+    /// Move the door cursor on to the next door, see [`FocusedDoor`].
+    CycleDoor,
+    /// Open or close the door under the cursor.
+    ToggleDoor,
+    /// Aim the next fully charged weapon after the one we're aiming now.
+    TargetNextCharged,
+    CancelTargeting,
 }
 
 impl Actionlike for Controls {
@@ -283,11 +436,13 @@ impl Controls {
 fn controls(
     self_intel: Query<&SelfIntel>,
     ships: Query<(&ShipIntel, &ActionState<Controls>)>,
-    mut power: EventWriter<AdjustPower>,
-    mut weapon_power: EventWriter<WeaponPower>,
-    mut set_autofire: EventWriter<SetAutofire>,
-    mut set_doors_open: EventWriter<SetDoorsOpen>,
-    mut crew_stations: EventWriter<CrewStations>,
+    mut power: EventWriter<ShipCommand<AdjustPower>>,
+    mut queue_power: EventWriter<ShipCommand<QueuePower>>,
+    mut weapon_power: EventWriter<ShipCommand<WeaponPower>>,
+    mut reroute_power: EventWriter<ShipCommand<ReroutePower>>,
+    mut set_autofire: EventWriter<ShipCommand<SetAutofire>>,
+    mut set_doors_open: DoorCommands,
+    mut crew_stations: EventWriter<ShipCommand<CrewStations>>,
     mut commands: Commands,
 ) {
     let Ok(self_intel) = self_intel.get_single() else {
@@ -299,7 +454,13 @@ fn controls(
     for action in actions.get_just_pressed() {
         match action {
             Controls::SystemPower { dir, system } => {
-                power.send(AdjustPower { dir, system });
+                // Same as the power panel's "+" button: with the reactor empty, wait in line
+                if dir == PowerDir::Request && self_intel.free_power == 0 {
+                    let queued = true;
+                    queue_power.send(QueuePower { system, queued }.for_ship(self_intel.ship));
+                } else {
+                    power.send(AdjustPower { dir, system }.for_ship(self_intel.ship));
+                }
             }
             Controls::WeaponPower { dir, weapon_index } => {
                 let Some(weapons) = &ship.basic.weapons else {
@@ -311,21 +472,113 @@ fn controls(
                 if weapons.weapons[weapon_index].powered && dir == PowerDir::Request {
                     commands.queue(start_targeting(weapon_index));
                 } else {
-                    weapon_power.send(WeaponPower { dir, weapon_index });
+                    weapon_power.send(WeaponPower { dir, weapon_index }.for_ship(self_intel.ship));
                 }
             }
+            Controls::ReroutePower(system) => {
+                reroute_power.send(ReroutePower::to(system).for_ship(self_intel.ship));
+            }
+            Controls::TargetGroup => {
+                commands.queue(start_group_targeting);
+            }
             Controls::Autofire => {
-                set_autofire.send(SetAutofire(!self_intel.autofire));
+                set_autofire.send(SetAutofire(!self_intel.autofire).for_ship(self_intel.ship));
             }
             Controls::AllDoors { open } => {
-                set_doors_open.send(SetDoorsOpen::All { open });
+                set_doors_open.send(SetDoorsOpen::All { open }.for_ship(self_intel.ship));
+            }
+            Controls::VentUnoccupied => {
+                set_doors_open.send(SetDoorsOpen::VentUnoccupied.for_ship(self_intel.ship));
+            }
+            Controls::CloseExterior => {
+                set_doors_open.send(SetDoorsOpen::CloseExterior.for_ship(self_intel.ship));
             }
             Controls::SaveStations => {
-                crew_stations.send(CrewStations::Save);
+                crew_stations.send(CrewStations::Save.for_ship(self_intel.ship));
             }
             Controls::ReturnToStations => {
-                crew_stations.send(CrewStations::Return);
+                crew_stations.send(CrewStations::Return.for_ship(self_intel.ship));
+            }
+            Controls::SelectCrew(_)
+            | Controls::CycleDoor
+            | Controls::ToggleDoor
+            | Controls::TargetNextCharged
+            | Controls::CancelTargeting => {
+                // Handled in `navigation_controls`
+            }
+        }
+    }
+}
+
+/// The keyboard stand-ins for clicking around the ship: picking crew, working doors and moving
+/// between weapons while aiming.
+fn navigation_controls(
+    self_intel: Single<&SelfIntel>,
+    ships: Query<(&ShipIntel, &ActionState<Controls>)>,
+    doors_intel: Query<&DoorsIntel>,
+    charge_intel: Query<&WeaponChargeIntel>,
+    crew: Query<(Entity, &CrewGraphic, &Parent)>,
+    selected: Query<Entity, With<Selected>>,
+    targeting: Option<Res<TargetingWeapon>>,
+    selection_enabled: Option<Res<SelectionEnabled>>,
+    mut focused_door: ResMut<FocusedDoor>,
+    mut set_doors_open: DoorCommands,
+    mut commands: Commands,
+) {
+    let Ok((ship, actions)) = ships.get(self_intel.ship) else {
+        return;
+    };
+    let doors = doors_intel.get(ship.doors).map_or(&[][..], |x| &x[..]);
+    for action in actions.get_just_pressed() {
+        match action {
+            Controls::SelectCrew(index) => {
+                if selection_enabled.is_none() {
+                    continue;
+                }
+                for e in &selected {
+                    commands.entity(e).remove::<Selected>();
+                }
+                let member = crew
+                    .iter()
+                    .find(|(_, x, parent)| x.0 == index && parent.get() == self_intel.ship);
+                if let Some((e, _, _)) = member {
+                    commands.entity(e).insert(Selected);
+                }
+            }
+            Controls::CycleDoor if !doors.is_empty() => {
+                focused_door.0 = Some(focused_door.0.map_or(0, |x| (x + 1) % doors.len()));
+            }
+            Controls::ToggleDoor => {
+                let Some(door) = focused_door.0.filter(|&x| x < doors.len()) else {
+                    continue;
+                };
+                let open = !doors[door].open;
+                set_doors_open.send(SetDoorsOpen::Single { door, open }.for_ship(self_intel.ship));
+            }
+            Controls::TargetNextCharged => {
+                let (Some(weapons), Ok(charge)) =
+                    (&ship.basic.weapons, charge_intel.get(ship.weapon_charge))
+                else {
+                    continue;
+                };
+                let current = targeting
+                    .as_ref()
+                    .and_then(|x| x.picking_room().first().copied());
+                let start = current.map_or(0, |x| x + 1);
+                let count = weapons.weapons.len();
+                let next = (0..count).map(|i| (start + i) % count).find(|&i| {
+                    let weapon = weapons.weapons[i];
+                    let level = charge.levels.get(i).copied().unwrap_or_default();
+                    weapon.powered && level >= weapon.weapon.common().charge_time
+                });
+                if let Some(next) = next {
+                    commands.queue(start_targeting(next));
+                }
+            }
+            Controls::CancelTargeting => {
+                commands.remove_resource::<TargetingWeapon>();
             }
+            _ => {}
         }
     }
 }
@@ -0,0 +1,342 @@
+//! What you see before you're in a match: host a game of your own, join one found on the LAN, type
+//! in an address, or queue for a ranked match with the coordinator at that address. Skipped
+//! entirely when `--server` names one on the command line. Builds with the `editor` feature also
+//! get a way into the ship editor.
+
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpStream, UdpSocket},
+    sync::{
+        mpsc::{channel, Receiver, TryRecvError},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32},
+    EguiContexts,
+};
+use common::{
+    lan::{LanBeacon, LAN_DISCOVERY_PORT},
+    queue::{QueueReply, QueueRequest, QUEUE_PORT},
+    DEFAULT_PORT,
+};
+use ftl_protocol::{Handshake, ReservationToken, Role};
+use server::{headless_server, lan::lan_plugin};
+
+/// How long a LAN game stays listed after its last beacon.
+const LAN_GAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn main_menu_plugin(app: &mut App) {
+    #[cfg(feature = "editor")]
+    app.add_plugins(crate::ship_editor::ship_editor_plugin);
+    app.add_systems(Startup, start)
+        .add_systems(
+            Update,
+            (listen_for_lan_games, poll_ranked_search, main_menu)
+                .chain()
+                .run_if(resource_exists::<MainMenu>),
+        )
+        .add_systems(
+            Update,
+            watch_hosted_server.run_if(resource_exists::<HostedServer>),
+        );
+}
+
+#[derive(Resource)]
+struct MainMenu {
+    /// What's typed into the address box.
+    address: String,
+    /// Where beacons come in, or `None` if something else on this machine already has the port.
+    lan: Option<UdpSocket>,
+    games: Vec<LanGame>,
+    ranked: Option<RankedSearch>,
+    error: Option<String>,
+}
+
+/// A place in the ranked queue. Replies from the coordinator are read on a thread of their own.
+struct RankedSearch {
+    host: IpAddr,
+    /// Kept so cancelling can hang up on the coordinator, which also ends the thread.
+    stream: TcpStream,
+    replies: Mutex<Receiver<QueueReply>>,
+    /// Players in the queue as of the coordinator's last update, us included.
+    players: u16,
+    started: Instant,
+}
+
+struct LanGame {
+    addr: SocketAddr,
+    beacon: LanBeacon,
+    last_seen: Instant,
+}
+
+/// The server we started for host-and-play, running on a thread of its own.
+#[derive(Resource)]
+struct HostedServer(JoinHandle<AppExit>);
+
+fn start(world: &mut World) {
+    if std::env::args().any(|x| x == "--server") {
+        match ftl_protocol::server_addr_from_args() {
+            Ok(addr) => join(world, addr, None),
+            Err(e) => {
+                eprintln!("Couldn't find the server: {e}");
+                world.send_event(AppExit::error());
+            }
+        }
+        return;
+    }
+    open(world);
+}
+
+/// Show the menu, listening for LAN games while it's up.
+pub fn open(world: &mut World) {
+    let lan = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, LAN_DISCOVERY_PORT)).and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    });
+    let lan = lan
+        .inspect_err(|e| eprintln!("Not looking for LAN games: {e}"))
+        .ok();
+    world.insert_resource(MainMenu {
+        address: "127.0.0.1".into(),
+        lan,
+        games: Vec::new(),
+        ranked: None,
+        error: None,
+    });
+}
+
+/// Connect to the server at `server_addr`, as a captain unless `--copilot` says otherwise.
+/// `reservation` claims a seat held for us, e.g. in a ranked match.
+fn join(world: &mut World, server_addr: SocketAddr, reservation: Option<ReservationToken>) {
+    // `--copilot` shares a ship with whoever's already playing instead of taking a new one
+    let role = if std::env::args().any(|x| x == "--copilot") {
+        Role::Copilot
+    } else {
+        Role::Captain
+    };
+    let mut handshake = Handshake::new(ftl_protocol::local_player_key()).with_role(role);
+    // `--reservation <token>` claims a seat a matchmaking service held for us
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(token) = reservation {
+        handshake = handshake.with_reservation(token);
+    } else if let Some(token) = args
+        .windows(2)
+        .find(|x| x[0] == "--reservation")
+        .map(|x| x[1].parse())
+    {
+        match token {
+            Ok(token) => handshake = handshake.with_reservation(token),
+            Err(e) => eprintln!("Ignoring bad reservation token: {e}"),
+        }
+    }
+    match ftl_protocol::connect(world, server_addr, handshake) {
+        Ok(()) => {
+            world.remove_resource::<MainMenu>();
+        }
+        Err(e) => match world.get_resource_mut::<MainMenu>() {
+            Some(mut menu) => menu.error = Some(format!("Couldn't connect to {server_addr}: {e}")),
+            None => panic!("Couldn't connect to {server_addr}: {e}"),
+        },
+    }
+}
+
+/// Start a server on this machine that's visible on the LAN, then join it like anyone else would.
+fn host(world: &mut World) {
+    let server = std::thread::spawn(|| {
+        let mut server = headless_server();
+        server.add_plugins(lan_plugin);
+        server.run()
+    });
+    world.insert_resource(HostedServer(server));
+    join(
+        world,
+        SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)),
+        None,
+    );
+}
+
+/// Get in line for a ranked match with the coordinator on `host`.
+fn find_ranked_match(host: IpAddr) -> std::io::Result<RankedSearch> {
+    let coordinator = SocketAddr::new(host, QUEUE_PORT);
+    let mut stream = TcpStream::connect_timeout(&coordinator, Duration::from_secs(3))?;
+    stream.write_all(&QueueRequest::new(ftl_protocol::local_player_key()).to_bytes())?;
+    let mut reader = stream.try_clone()?;
+    let (sender, replies) = channel();
+    std::thread::spawn(move || {
+        let mut bytes = [0; QueueReply::BYTES];
+        while reader.read_exact(&mut bytes).is_ok() {
+            let Some(reply) = QueueReply::from_bytes(&bytes) else {
+                break;
+            };
+            if sender.send(reply).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(RankedSearch {
+        host,
+        stream,
+        replies: Mutex::new(replies),
+        players: 1,
+        started: Instant::now(),
+    })
+}
+
+fn poll_ranked_search(mut menu: ResMut<MainMenu>, mut commands: Commands) {
+    let menu = &mut *menu;
+    let Some(search) = &mut menu.ranked else {
+        return;
+    };
+    loop {
+        match search.replies.get_mut().unwrap().try_recv() {
+            Ok(QueueReply::Waiting { players }) => search.players = players,
+            Ok(QueueReply::MatchFound { port, reservation }) => {
+                let addr = SocketAddr::new(search.host, port);
+                menu.ranked = None;
+                commands.queue(move |world: &mut World| join(world, addr, Some(reservation)));
+                return;
+            }
+            Ok(QueueReply::WrongVersion) => {
+                menu.ranked = None;
+                menu.error = Some("The ranked queue runs a different version.".into());
+                return;
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                menu.ranked = None;
+                menu.error = Some("Lost connection to the ranked queue.".into());
+                return;
+            }
+        }
+    }
+}
+
+fn listen_for_lan_games(mut menu: ResMut<MainMenu>) {
+    let menu = &mut *menu;
+    let now = Instant::now();
+    if let Some(socket) = &menu.lan {
+        let mut buf = [0; 64];
+        while let Ok((len, from)) = socket.recv_from(&mut buf) {
+            let Some(beacon) = LanBeacon::from_bytes(&buf[..len]) else {
+                continue;
+            };
+            let addr = SocketAddr::new(from.ip(), beacon.port);
+            match menu.games.iter_mut().find(|x| x.addr == addr) {
+                Some(game) => {
+                    game.beacon = beacon;
+                    game.last_seen = now;
+                }
+                None => menu.games.push(LanGame {
+                    addr,
+                    beacon,
+                    last_seen: now,
+                }),
+            }
+        }
+    }
+    menu.games
+        .retain(|x| now.duration_since(x.last_seen) < LAN_GAME_TIMEOUT);
+}
+
+fn main_menu(mut ui: EguiContexts, mut menu: ResMut<MainMenu>, mut commands: Commands) {
+    let menu = &mut *menu;
+    egui::Window::new("Main menu")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .title_bar(false)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            if ui.button("Host game").clicked() {
+                commands.queue(host);
+            }
+            ui.separator();
+            ui.label("Games on your network:");
+            if menu.lan.is_none() {
+                ui.weak("Can't look, another client on this machine already is.");
+            } else if menu.games.is_empty() {
+                ui.weak("Looking...");
+            }
+            for game in &menu.games {
+                let LanBeacon {
+                    players, in_lobby, ..
+                } = game.beacon;
+                let status = if in_lobby { "in lobby" } else { "in a match" };
+                let button =
+                    egui::Button::new(format!("{} ({players} players, {status})", game.addr));
+                let clicked = ui
+                    .add_enabled(game.beacon.compatible(), button)
+                    .on_disabled_hover_text("Runs a different version")
+                    .clicked();
+                if clicked {
+                    let addr = game.addr;
+                    commands.queue(move |world: &mut World| join(world, addr, None));
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut menu.address);
+                if ui.button("Join").clicked() {
+                    match ftl_protocol::resolve_server(menu.address.trim()) {
+                        Ok(addr) => {
+                            commands.queue(move |world: &mut World| join(world, addr, None));
+                        }
+                        Err(e) => menu.error = Some(format!("Couldn't find the server: {e}")),
+                    }
+                }
+            });
+            if let Some(search) = &menu.ranked {
+                let mut cancel = false;
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Looking for a ranked match... {}s, {} in queue",
+                        search.started.elapsed().as_secs(),
+                        search.players
+                    ));
+                    cancel = ui.button("Cancel").clicked();
+                });
+                if cancel {
+                    let _ = search.stream.shutdown(Shutdown::Both);
+                    menu.ranked = None;
+                }
+            } else {
+                let find = ui
+                    .button("Find ranked match")
+                    .on_hover_text("Queue with the ranked coordinator at the address above");
+                if find.clicked() {
+                    let search = ftl_protocol::resolve_server(menu.address.trim())
+                        .and_then(|addr| find_ranked_match(addr.ip()));
+                    match search {
+                        Ok(search) => {
+                            menu.ranked = Some(search);
+                            menu.error = None;
+                        }
+                        Err(e) => menu.error = Some(format!("Couldn't join the ranked queue: {e}")),
+                    }
+                }
+            }
+            if let Some(error) = &menu.error {
+                ui.colored_label(Color32::RED, error);
+            }
+            #[cfg(feature = "editor")]
+            {
+                ui.separator();
+                if ui.button("Ship editor").clicked() {
+                    commands.remove_resource::<MainMenu>();
+                    commands.queue(crate::ship_editor::open);
+                }
+            }
+        });
+}
+
+/// The server thread only finishes if something went wrong, like the port already being taken.
+/// There's no match to play without it.
+fn watch_hosted_server(server: Res<HostedServer>, mut exit: EventWriter<AppExit>) {
+    if server.0.is_finished() {
+        eprintln!("The server we were hosting stopped, see above for why.");
+        exit.send(AppExit::error());
+    }
+}
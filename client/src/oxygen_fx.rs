@@ -0,0 +1,233 @@
+//! Feedback for rooms gaining or losing air, so it's clear how long a vent has left to go. How fast
+//! each room's oxygen is changing is worked out here from successive [`InteriorIntel`] updates.
+//! Hovering a ship labels each of its rooms with its oxygen level, and rooms losing air quickly
+//! get chevrons draining out of them.
+
+use bevy::prelude::*;
+use common::{
+    intel::{InteriorIntel, ShipIntel},
+    ship::{Dead, SHIPS},
+};
+
+use crate::{graphics::Z_BULLETS, hover::HoveredRoom, theme::ColorTheme};
+
+pub fn oxygen_fx_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            track_oxygen,
+            add_oxygen_labels,
+            update_oxygen_labels,
+            draw_draining,
+        )
+            .chain(),
+    );
+}
+
+/// Fraction of the way each new measurement pulls the rate towards itself. Intel arrives every
+/// server tick, so this smooths over a handful of them.
+const SMOOTHING: f32 = 0.2;
+/// Rooms changing slower than this, in oxygen per second, are considered steady.
+const STEADY_RATE: f32 = 0.01;
+/// Rooms losing oxygen faster than this per second get the draining animation.
+const RAPID_DRAIN: f32 = 0.05;
+/// Seconds for a chevron to slide the length of the animation.
+const CHEVRON_SECS: f32 = 0.8;
+const CHEVRONS: usize = 3;
+
+/// How the oxygen in each room of the ship has been changing, in room order.
+#[derive(Component)]
+pub struct OxygenTrend {
+    oxygen: Vec<f32>,
+    /// Change in oxygen per second.
+    rates: Vec<f32>,
+    updated: f32,
+}
+
+impl OxygenTrend {
+    fn new(interior: &InteriorIntel, now: f32) -> Self {
+        Self {
+            oxygen: interior.rooms.iter().map(|x| x.oxygen).collect(),
+            rates: vec![0.0; interior.rooms.len()],
+            updated: now,
+        }
+    }
+
+    /// Change in `room`'s oxygen per second, or 0 if it's holding steady.
+    pub fn rate(&self, room: usize) -> f32 {
+        self.rates
+            .get(room)
+            .copied()
+            .filter(|x| x.abs() >= STEADY_RATE)
+            .unwrap_or(0.0)
+    }
+
+    /// Seconds until `room` is out of air if it's draining, or full if it's filling back up.
+    /// `None` if it's holding steady.
+    pub fn eta(&self, room: usize) -> Option<f32> {
+        let rate = self.rate(room);
+        let oxygen = *self.oxygen.get(room)?;
+        if rate < 0.0 {
+            Some(oxygen / -rate)
+        } else if rate > 0.0 {
+            Some((1.0 - oxygen) / rate)
+        } else {
+            None
+        }
+    }
+}
+
+fn track_oxygen(
+    mut ships: Query<(Entity, &ShipIntel, Option<&mut OxygenTrend>), Without<Dead>>,
+    interiors: Query<Ref<InteriorIntel>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_secs();
+    for (ship, intel, trend) in &mut ships {
+        let Ok(interior) = interiors.get(intel.interior) else {
+            continue;
+        };
+        let Some(mut trend) = trend else {
+            commands
+                .entity(ship)
+                .insert(OxygenTrend::new(&interior, now));
+            continue;
+        };
+        if !interior.is_changed() {
+            continue;
+        }
+        // Losing sight of the interior and getting it back shouldn't read as a sudden swing
+        if trend.oxygen.len() != interior.rooms.len() {
+            *trend = OxygenTrend::new(&interior, now);
+            continue;
+        }
+        let dt = now - trend.updated;
+        if dt <= 0.0 {
+            continue;
+        }
+        let trend = &mut *trend;
+        for ((last, rate), room) in trend
+            .oxygen
+            .iter_mut()
+            .zip(&mut trend.rates)
+            .zip(&interior.rooms)
+        {
+            let measured = (room.oxygen - *last) / dt;
+            *rate += (measured - *rate) * SMOOTHING;
+            *last = room.oxygen;
+        }
+        trend.updated = now;
+    }
+}
+
+/// Oxygen readout for the room with this index, as a child of the ship.
+#[derive(Component)]
+struct OxygenLabel(usize);
+
+fn add_oxygen_labels(
+    ships: Query<(Entity, &ShipIntel), Added<OxygenTrend>>,
+    mut commands: Commands,
+) {
+    for (ship, intel) in &ships {
+        let ship_type = &SHIPS[intel.basic.ship_type];
+        commands.entity(ship).with_children(|ship| {
+            for room in 0..ship_type.rooms.len() {
+                // Below the room's icon, if it has one
+                let pos = ship_type.room_center(room) - Vec2::Y * 12.0;
+                ship.spawn((
+                    OxygenLabel(room),
+                    PickingBehavior::IGNORE,
+                    Text2d::default(),
+                    TextFont::from_font_size(11.0),
+                    Transform::from_translation(pos.extend(Z_BULLETS)),
+                    Visibility::Hidden,
+                ));
+            }
+        });
+    }
+}
+
+/// Show the oxygen level of every room of the hovered ship, colored by whether it's draining or
+/// filling back up.
+fn update_oxygen_labels(
+    hovered: Option<Res<HoveredRoom>>,
+    theme: Res<ColorTheme>,
+    ships: Query<(&ShipIntel, &OxygenTrend, &Transform)>,
+    interiors: Query<&InteriorIntel>,
+    mut labels: Query<
+        (
+            &OxygenLabel,
+            &Parent,
+            &mut Text2d,
+            &mut TextColor,
+            &mut Transform,
+            &mut Visibility,
+        ),
+        Without<OxygenTrend>,
+    >,
+) {
+    for (&OxygenLabel(room), parent, mut text, mut color, mut transform, mut visibility) in
+        &mut labels
+    {
+        let shown = hovered.as_ref().is_some_and(|x| x.ship == **parent);
+        let Some((trend, ship_transform, room_intel)) =
+            ships.get(**parent).ok().filter(|_| shown).and_then(
+                |(intel, trend, ship_transform)| {
+                    let interior = interiors.get(intel.interior).ok()?;
+                    Some((trend, ship_transform, interior.rooms.get(room)?))
+                },
+            )
+        else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        text.0 = format!("{:.0}%", room_intel.oxygen * 100.0);
+        color.0 = theme.oxygen(trend.rate(room)).into();
+        // Keep the text upright however the ship is turned
+        transform.rotation = ship_transform.rotation.inverse();
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
+
+/// Chevrons sliding out of rooms that are losing air quickly, faster the quicker it's going.
+fn draw_draining(
+    ships: Query<(&ShipIntel, &OxygenTrend, &Transform), Without<Dead>>,
+    time: Res<Time>,
+    theme: Res<ColorTheme>,
+    mut gizmos: Gizmos,
+) {
+    for (intel, trend, transform) in &ships {
+        let ship_type = &SHIPS[intel.basic.ship_type];
+        for room in 0..ship_type.rooms.len() {
+            let rate = trend.rate(room);
+            if rate > -RAPID_DRAIN {
+                continue;
+            }
+            let center = ship_type.room_center(room);
+            let speed = (-rate / RAPID_DRAIN).min(3.0);
+            for i in 0..CHEVRONS {
+                let phase = (time.elapsed_secs() * speed / CHEVRON_SECS
+                    + i as f32 / CHEVRONS as f32)
+                    .fract();
+                let tip = center + Vec2::new(0.0, 8.0 - 16.0 * phase);
+                let point = |offset: Vec2| {
+                    transform
+                        .transform_point((tip + offset).extend(0.0))
+                        .truncate()
+                };
+                // Fade in at the top and out at the bottom
+                let alpha = 1.0 - (phase * 2.0 - 1.0).abs();
+                let color = theme.oxygen(rate).with_alpha(alpha);
+                gizmos.linestrip_2d(
+                    [
+                        point(Vec2::new(-4.0, 3.0)),
+                        point(Vec2::ZERO),
+                        point(Vec2::new(4.0, 3.0)),
+                    ],
+                    color,
+                );
+            }
+        }
+    }
+}
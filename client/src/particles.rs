@@ -0,0 +1,229 @@
+//! Sprite particles for what's going wrong inside a ship: flames licking up from burning cells, air
+//! rushing out of breached ones, and smoke curling off damaged systems. Driven entirely by
+//! [`InteriorIntel`] and system damage in [`BasicIntel`], so we only see it where we can see the
+//! cells themselves.
+//!
+//! Every particle is a plain untextured sprite, and there are never more than [`PARTICLE_BUDGET`]
+//! of them at once. Fires and breaches get first claim on the budget since they matter more to
+//! whoever's looking than smoke does.
+//!
+//! [`BasicIntel`]: common::intel::BasicIntel
+
+use bevy::{color::palettes, prelude::*};
+use common::{
+    intel::{InteriorIntel, ShipIntel, SystemDamageIntel},
+    ship::{Dead, SystemId, SHIPS},
+};
+use rand::{thread_rng, Rng};
+use strum::IntoEnumIterator;
+
+use crate::graphics::Z_PARTICLES;
+
+pub fn particles_plugin(app: &mut App) {
+    app.add_systems(Update, (emit_particles, animate_particles).chain());
+}
+
+/// Most particles alive at once, across every ship.
+const PARTICLE_BUDGET: usize = 300;
+/// Particles per second from each burning cell.
+const FLAME_RATE: f32 = 12.0;
+/// Particles per second from each breached cell with a full load of air. Slows to nothing as the
+/// cell empties out.
+const AIR_RATE: f32 = 20.0;
+/// Particles per second from a damaged system's room, doubled once it's destroyed.
+const SMOKE_RATE: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ParticleKind {
+    Flame,
+    Air,
+    Smoke,
+}
+
+impl ParticleKind {
+    fn lifetime(self) -> f32 {
+        match self {
+            ParticleKind::Flame => 0.6,
+            ParticleKind::Air => 0.4,
+            ParticleKind::Smoke => 2.0,
+        }
+    }
+
+    /// Size at birth and at death.
+    fn sizes(self) -> (f32, f32) {
+        match self {
+            ParticleKind::Flame => (5.0, 1.0),
+            ParticleKind::Air => (2.0, 3.0),
+            ParticleKind::Smoke => (4.0, 12.0),
+        }
+    }
+
+    /// Color at birth and at death, before fading out.
+    fn colors(self) -> (Color, Color) {
+        match self {
+            ParticleKind::Flame => (palettes::css::YELLOW.into(), palettes::css::RED.into()),
+            ParticleKind::Air => (Color::WHITE, palettes::css::LIGHT_SKY_BLUE.into()),
+            ParticleKind::Smoke => (Color::srgb(0.4, 0.4, 0.4), Color::srgb(0.15, 0.15, 0.15)),
+        }
+    }
+
+    /// How opaque the particle starts out.
+    fn alpha(self) -> f32 {
+        match self {
+            ParticleKind::Flame => 0.9,
+            ParticleKind::Air => 0.6,
+            ParticleKind::Smoke => 0.5,
+        }
+    }
+}
+
+/// A particle drawn as a child of the ship it's coming off, in the ship's own coordinates.
+#[derive(Component)]
+struct Particle {
+    kind: ParticleKind,
+    age: f32,
+    velocity: Vec2,
+    /// Where in its sway smoke starts, so a plume doesn't wobble in lockstep.
+    phase: f32,
+}
+
+/// How many particles to emit this frame from something emitting `rate` per second, where `roll`
+/// is uniform in `[0, 1)`. Leftover fractions of a particle are emitted by chance, so low rates
+/// still average out right at any frame rate.
+fn emit_count(rate: f32, dt: f32, roll: f32) -> usize {
+    let expected = rate * dt;
+    expected as usize + usize::from(roll < expected.fract())
+}
+
+fn emit_particles(
+    ships: Query<(Entity, &ShipIntel), Without<Dead>>,
+    interiors: Query<&InteriorIntel>,
+    particles: Query<(), With<Particle>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_secs();
+    let mut budget = PARTICLE_BUDGET.saturating_sub(particles.iter().count());
+    let mut rng = thread_rng();
+    // Gather every emitter first, so the budget goes to fires and breaches before smoke
+    let mut emitters = Vec::new();
+    for (ship, intel) in &ships {
+        let ship_type = &SHIPS[intel.basic.ship_type];
+        let center = ship_type
+            .cell_positions
+            .iter()
+            .fold(Vec2::ZERO, |sum, &x| sum + x)
+            / ship_type.cell_positions.len() as f32;
+        if let Ok(interior) = interiors.get(intel.interior) {
+            for (cell, x) in interior.cells.iter().enumerate() {
+                let Some(&pos) = ship_type.cell_positions.get(cell) else {
+                    continue;
+                };
+                if x.on_fire {
+                    emitters.push((ship, ParticleKind::Flame, pos, Vec2::Y, FLAME_RATE));
+                }
+                if x.breached && x.oxygen > 0.0 {
+                    // Out through the hull, away from the middle of the ship
+                    let out = (pos - center).normalize_or(Vec2::Y);
+                    emitters.push((ship, ParticleKind::Air, pos, out, AIR_RATE * x.oxygen));
+                }
+            }
+        }
+        for system in SystemId::iter() {
+            let rate = match intel.basic.system_damage(system) {
+                Some(SystemDamageIntel::Damaged) => SMOKE_RATE,
+                Some(SystemDamageIntel::Destroyed) => SMOKE_RATE * 2.0,
+                _ => continue,
+            };
+            let Some(&room) = intel.basic.system_locations.get(&system) else {
+                continue;
+            };
+            let pos = ship_type.room_center(room);
+            emitters.push((ship, ParticleKind::Smoke, pos, Vec2::Y, rate));
+        }
+    }
+    emitters.sort_by_key(|&(_, kind, ..)| kind == ParticleKind::Smoke);
+
+    for (ship, kind, pos, dir, rate) in emitters {
+        let count = emit_count(rate, dt, rng.gen()).min(budget);
+        budget -= count;
+        for _ in 0..count {
+            let (pos, velocity) = match kind {
+                ParticleKind::Flame => (
+                    pos + Vec2::new(rng.gen_range(-14.0..14.0), rng.gen_range(-14.0..8.0)),
+                    dir * rng.gen_range(15.0..30.0),
+                ),
+                ParticleKind::Air => (
+                    pos + Vec2::new(rng.gen_range(-6.0..6.0), rng.gen_range(-6.0..6.0)),
+                    Vec2::from_angle(rng.gen_range(-0.4..0.4)).rotate(dir)
+                        * rng.gen_range(60.0..110.0),
+                ),
+                ParticleKind::Smoke => (
+                    pos + Vec2::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0)),
+                    dir * rng.gen_range(8.0..16.0),
+                ),
+            };
+            let (color, _) = kind.colors();
+            let (size, _) = kind.sizes();
+            commands.entity(ship).with_child((
+                Particle {
+                    kind,
+                    age: 0.0,
+                    velocity,
+                    phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                },
+                PickingBehavior::IGNORE,
+                Sprite {
+                    color: color.with_alpha(kind.alpha()),
+                    custom_size: Some(Vec2::splat(size)),
+                    ..default()
+                },
+                Transform::from_translation(pos.extend(Z_PARTICLES)),
+            ));
+        }
+        if budget == 0 {
+            break;
+        }
+    }
+}
+
+fn animate_particles(
+    mut particles: Query<(Entity, &mut Particle, &mut Sprite, &mut Transform)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_secs();
+    for (e, mut particle, mut sprite, mut transform) in &mut particles {
+        particle.age += dt;
+        let kind = particle.kind;
+        let t = particle.age / kind.lifetime();
+        if t >= 1.0 {
+            commands.entity(e).despawn();
+            continue;
+        }
+        let mut velocity = particle.velocity;
+        if kind == ParticleKind::Smoke {
+            // Curl side to side as it rises
+            velocity.x += (particle.age * 3.0 + particle.phase).sin() * 10.0;
+        }
+        transform.translation += (velocity * dt).extend(0.0);
+        let (start, end) = kind.colors();
+        sprite.color = start.mix(&end, t).with_alpha(kind.alpha() * (1.0 - t));
+        let (start, end) = kind.sizes();
+        sprite.custom_size = Some(Vec2::splat(start + (end - start) * t));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emission_averages_out_at_any_frame_rate() {
+        assert_eq!(emit_count(10.0, 0.5, 0.99), 5);
+        // Half a particle expected: a low roll gets it, a high one doesn't
+        assert_eq!(emit_count(10.0, 0.05, 0.2), 1);
+        assert_eq!(emit_count(10.0, 0.05, 0.7), 0);
+        assert_eq!(emit_count(0.0, 1.0, 0.0), 0);
+    }
+}
@@ -0,0 +1,230 @@
+//! FTL-style power bars along the bottom of the screen: the reactor, then a column of pips for each
+//! system. Left click a system to give it power (or queue it if the reactor's empty), right click
+//! to take power away (or cancel the queued request). Stands in for the egui power panel unless
+//! it's switched off in the display settings.
+
+use bevy::prelude::*;
+use common::{
+    events::{AdjustPower, CommandEvent, QueuePower, ShipCommand},
+    intel::{SelfIntel, ShipIntel, SystemsIntel},
+    ship::{Dead, SystemId},
+};
+
+use crate::{
+    egui_panels::power_hotkey,
+    theme::{ColorTheme, Status},
+};
+
+pub fn power_hud_plugin(app: &mut App) {
+    app.init_resource::<UsePowerHud>()
+        .add_systems(Startup, spawn_power_hud)
+        .add_systems(Update, update_power_hud);
+}
+
+/// Whether power is managed from the HUD rather than the egui power panel.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsePowerHud(pub bool);
+
+impl Default for UsePowerHud {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Systems in the order their bars go, left to right.
+const SYSTEMS: [SystemId; 5] = [
+    SystemId::Shields,
+    SystemId::Engines,
+    SystemId::Weapons,
+    SystemId::Oxygen,
+    SystemId::CloneBay,
+];
+
+const PIP_WIDTH: f32 = 22.0;
+const PIP_HEIGHT: f32 = 8.0;
+const EMPTY_PIP: Color = Color::srgb(0.15, 0.15, 0.15);
+
+#[derive(Component)]
+struct PowerHudRoot;
+
+/// A system's bar, which takes the clicks for it.
+#[derive(Component, Clone, Copy)]
+struct SystemBar(SystemId);
+
+/// Everything the HUD shows, so it only gets rebuilt when something changes.
+#[derive(Debug, Clone, PartialEq)]
+struct HudState {
+    free_power: usize,
+    max_power: usize,
+    /// Current power, upgrade level, damage and whether power is queued, for each system.
+    systems: Vec<(SystemId, usize, usize, usize, bool)>,
+    theme: ColorTheme,
+}
+
+fn spawn_power_hud(mut commands: Commands) {
+    commands.spawn((
+        PowerHudRoot,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::FlexEnd,
+            column_gap: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn update_power_hud(
+    root: Single<Entity, With<PowerHudRoot>>,
+    enabled: Res<UsePowerHud>,
+    self_intel: Query<&SelfIntel>,
+    ships: Query<&ShipIntel, Without<Dead>>,
+    systems: Query<&SystemsIntel>,
+    theme: Res<ColorTheme>,
+    mut shown: Local<Option<HudState>>,
+    mut commands: Commands,
+) {
+    let state = self_intel
+        .get_single()
+        .ok()
+        .filter(|_| enabled.0)
+        .and_then(|x| Some((x, systems.get(ships.get(x.ship).ok()?.systems).ok()?)))
+        .map(|(self_intel, systems)| HudState {
+            free_power: self_intel.free_power,
+            max_power: self_intel.max_power,
+            systems: SYSTEMS
+                .iter()
+                .filter_map(|&system| {
+                    let intel = systems.get(&system)?;
+                    let queued = self_intel.power_queue.contains(&system);
+                    Some((
+                        system,
+                        intel.current_power,
+                        intel.upgrade_level,
+                        intel.damage,
+                        queued,
+                    ))
+                })
+                .collect(),
+            theme: *theme,
+        });
+    if *shown == state {
+        return;
+    }
+    *shown = state.clone();
+    commands.entity(*root).despawn_descendants();
+    let Some(state) = state else {
+        return;
+    };
+
+    let powered = Color::from(state.theme.status(Status::Good));
+    let damaged = Color::from(state.theme.status(Status::Bad));
+    commands.entity(*root).with_children(|root| {
+        root.spawn(bar_node()).with_children(|bar| {
+            for i in (0..state.max_power).rev() {
+                let fill = if i < state.free_power {
+                    powered
+                } else {
+                    EMPTY_PIP
+                };
+                bar.spawn(pip(fill, fill));
+            }
+            bar.spawn(label("Reactor".into()));
+        });
+        for &(system, current, max, damage, queued) in &state.systems {
+            root.spawn((SystemBar(system), bar_node()))
+                .with_children(|bar| {
+                    for i in (0..max).rev() {
+                        bar.spawn(if i < current {
+                            pip(powered, powered)
+                        } else if i >= max - damage {
+                            pip(damaged, damaged)
+                        } else if queued && i == current {
+                            // Hollow, waiting on the reactor
+                            pip(EMPTY_PIP, powered)
+                        } else {
+                            pip(EMPTY_PIP, EMPTY_PIP)
+                        });
+                    }
+                    bar.spawn(label(format!("[{}]\n{system}", power_hotkey(system))));
+                })
+                .observe(click_system_bar);
+        }
+    });
+}
+
+fn bar_node() -> Node {
+    Node {
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        row_gap: Val::Px(2.0),
+        ..default()
+    }
+}
+
+fn pip(fill: Color, border: Color) -> (Node, BackgroundColor, BorderColor) {
+    (
+        Node {
+            width: Val::Px(PIP_WIDTH),
+            height: Val::Px(PIP_HEIGHT),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        BackgroundColor(fill),
+        BorderColor(border),
+    )
+}
+
+fn label(text: String) -> (Text, TextFont, TextLayout) {
+    (
+        Text::new(text),
+        TextFont {
+            font_size: 11.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+    )
+}
+
+fn click_system_bar(
+    trigger: Trigger<Pointer<Click>>,
+    bars: Query<&SystemBar>,
+    self_intel: Single<&SelfIntel>,
+    mut adjust_power: EventWriter<ShipCommand<AdjustPower>>,
+    mut queue_power: EventWriter<ShipCommand<QueuePower>>,
+) {
+    let Ok(&SystemBar(system)) = bars.get(trigger.entity()) else {
+        return;
+    };
+    let queued = self_intel.power_queue.contains(&system);
+    let ship = self_intel.ship;
+    match trigger.event().button {
+        PointerButton::Primary if self_intel.free_power == 0 => {
+            queue_power.send(
+                QueuePower {
+                    system,
+                    queued: true,
+                }
+                .for_ship(ship),
+            );
+        }
+        PointerButton::Primary => {
+            adjust_power.send(AdjustPower::request(system).for_ship(ship));
+        }
+        PointerButton::Secondary if queued => {
+            queue_power.send(
+                QueuePower {
+                    system,
+                    queued: false,
+                }
+                .for_ship(ship),
+            );
+        }
+        PointerButton::Secondary => {
+            adjust_power.send(AdjustPower::remove(system).for_ship(ship));
+        }
+        PointerButton::Middle => {}
+    }
+}
@@ -0,0 +1,86 @@
+//! Shows weapon targets the moment they're picked instead of a round trip later. Targeting
+//! commands we send are remembered as predictions and drawn dimmed until [`SelfIntel`] catches up.
+//! A prediction goes away once the server agrees, turns a command down, or never answers.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use common::{
+    events::{
+        CommandRejected, SetBeamWeaponTarget, SetGroupTarget, SetProjectileWeaponTarget,
+        ShipCommand,
+    },
+    intel::SelfIntel,
+    weapon::WeaponTarget,
+};
+
+/// Seconds to wait on the server before giving up on a prediction.
+const PREDICTION_TIMEOUT: f32 = 1.0;
+
+pub fn prediction_plugin(app: &mut App) {
+    app.init_resource::<PredictedTargets>()
+        .add_systems(Update, (predict_targets, settle_predictions).chain());
+}
+
+/// Targets we've asked for but haven't seen the server confirm, by weapon slot, with the time each
+/// was sent.
+#[derive(Resource, Default)]
+pub struct PredictedTargets(HashMap<usize, (Option<WeaponTarget>, f32)>);
+
+impl PredictedTargets {
+    /// Weapon `index`'s target as far as we know, and whether it's only a prediction.
+    pub fn target(&self, self_intel: &SelfIntel, index: usize) -> (Option<WeaponTarget>, bool) {
+        match self.0.get(&index) {
+            Some(&(target, _)) => (target, true),
+            None => (
+                self_intel.weapon_targets.get(index).copied().flatten(),
+                false,
+            ),
+        }
+    }
+}
+
+fn predict_targets(
+    mut projectile_targeting: EventReader<ShipCommand<SetProjectileWeaponTarget>>,
+    mut beam_targeting: EventReader<ShipCommand<SetBeamWeaponTarget>>,
+    mut group_targeting: EventReader<ShipCommand<SetGroupTarget>>,
+    mut predicted: ResMut<PredictedTargets>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    for ShipCommand { command, .. } in projectile_targeting.read() {
+        let target = command.target.map(WeaponTarget::Projectile);
+        predicted.0.insert(command.weapon_index, (target, now));
+    }
+    for ShipCommand { command, .. } in beam_targeting.read() {
+        let target = command.target.map(WeaponTarget::Beam);
+        predicted.0.insert(command.weapon_index, (target, now));
+    }
+    for ShipCommand { command, .. } in group_targeting.read() {
+        let target = command.target.map(WeaponTarget::Projectile);
+        for &weapon_index in &command.weapons {
+            predicted.0.insert(weapon_index, (target, now));
+        }
+    }
+}
+
+fn settle_predictions(
+    self_intel: Option<Single<&SelfIntel>>,
+    mut rejections: EventReader<CommandRejected>,
+    mut predicted: ResMut<PredictedTargets>,
+    time: Res<Time>,
+) {
+    // Rejections don't say which command they're about, so any of them could have been ours
+    if rejections.read().count() > 0 {
+        predicted.0.clear();
+    }
+    let Some(self_intel) = self_intel else {
+        predicted.0.clear();
+        return;
+    };
+    let now = time.elapsed_secs();
+    predicted.0.retain(|&index, &mut (target, sent)| {
+        let confirmed = self_intel.weapon_targets.get(index).copied().flatten() == target;
+        !confirmed && now - sent < PREDICTION_TIMEOUT
+    });
+}
@@ -0,0 +1,72 @@
+//! Ratings and records of everyone in the lobby, for servers that keep player profiles. Servers
+//! that don't just never answer, and nothing gets shown.
+
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Ui};
+use bevy_replicon::core::ClientId;
+use common::{
+    lobby::ReadyState,
+    profile::{MatchResult, PlayerProfile, PlayerProfiles, RequestProfiles},
+};
+
+pub fn profiles_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            request_profiles.run_if(resource_added::<ReadyState>),
+            receive_profiles,
+        ),
+    );
+}
+
+/// Ask again on every trip to the lobby, since ratings change at the end of each match and
+/// players come and go.
+fn request_profiles(mut requests: EventWriter<RequestProfiles>) {
+    requests.send(RequestProfiles);
+}
+
+fn receive_profiles(mut events: EventReader<PlayerProfiles>, mut commands: Commands) {
+    if let Some(profiles) = events.read().last() {
+        commands.insert_resource(profiles.clone());
+    }
+}
+
+/// Everyone's rating and record, us first, with our recent results under a collapsible header.
+pub fn profiles_ui(ui: &mut Ui, profiles: &PlayerProfiles, client_id: ClientId) {
+    let mine = profiles.players.get(&client_id);
+    let others = profiles.players.iter().filter(|(&x, _)| x != client_id);
+    // Nothing stops a client from sending someone else's player ID
+    ui.weak("Unranked: player IDs aren't verified");
+    egui::Grid::new("lobby_profiles").show(ui, |ui| {
+        for (name, profile) in mine
+            .map(|x| ("You".to_string(), x))
+            .into_iter()
+            .chain(others.map(|(id, x)| (format!("Player {}", id.get()), x)))
+        {
+            ui.label(name);
+            ui.label(format!("{}", profile.rating));
+            ui.label(record(profile));
+            ui.end_row();
+        }
+    });
+    let Some(mine) = mine.filter(|x| !x.recent.is_empty()) else {
+        return;
+    };
+    ui.collapsing("Recent matches", |ui| {
+        for x in &mine.recent {
+            let result = match x.result {
+                MatchResult::Win => "Win",
+                MatchResult::Loss => "Loss",
+                MatchResult::Draw => "Draw",
+            };
+            ui.label(format!(
+                "{result} vs {} ({:+})",
+                x.opponent_rating, x.rating_change
+            ));
+        }
+    });
+}
+
+fn record(profile: &PlayerProfile) -> String {
+    format!("{}W {}L {}D", profile.wins, profile.losses, profile.draws)
+}
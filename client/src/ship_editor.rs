@@ -0,0 +1,675 @@
+//! Ship layout editor, behind the `editor` feature and opened from the main menu. Paint cells into
+//! rooms on a grid, put doors between them, give rooms their systems and the hull its mounts and
+//! armor. The nav mesh and path graph are worked out from the layout the same way the built-in
+//! ships are laid out: 2x2 blocks of a room become squares, any other neighbors in a room get a
+//! line, and so does every interior door.
+//!
+//! Ships are `const`s, so exporting writes out a [`ShipType`] ready to paste into
+//! [`SHIPS`](common::ship::SHIPS). Layouts that fail [`check_ship`] aren't exported.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bevy::{color::palettes, prelude::*};
+use bevy_egui::{
+    egui::{self, Color32},
+    EguiContexts,
+};
+use common::{
+    nav::{Cell, LineSection, SquareSection},
+    ship::{Door, DoorDir, Quadrant, ShipType, SubsystemId, SystemId, WeaponMount, SHIPS},
+    ship_check::{check_ship, ShipLayout, CELL_SIZE},
+};
+use strum::IntoEnumIterator;
+
+use crate::main_menu;
+
+pub fn ship_editor_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (editor_panel, edit_layout, draw_layout)
+            .chain()
+            .run_if(resource_exists::<ShipEditor>),
+    );
+}
+
+/// Leave the main menu for the editor, starting from an empty grid.
+pub fn open(world: &mut World) {
+    world.insert_resource(ShipEditor {
+        layout: Layout {
+            rooms: vec![Purpose::Empty],
+            ..default()
+        },
+        tool: Tool::Cells,
+        room: 0,
+        path: "ship.rs".into(),
+        status: Vec::new(),
+    });
+}
+
+#[derive(Resource)]
+struct ShipEditor {
+    layout: Layout,
+    tool: Tool,
+    /// Room that painted cells go into.
+    room: usize,
+    /// Where to write the exported ship.
+    path: String,
+    /// What came of the last export, one line each.
+    status: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// Left click paints a cell into the current room, right click erases it.
+    Cells,
+    /// Left click puts a door on the nearest cell edge, or takes it away if there's one there.
+    Doors,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    Empty,
+    System(SystemId),
+    Subsystem(SubsystemId),
+}
+
+impl std::fmt::Display for Purpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Purpose::Empty => write!(f, "empty"),
+            Purpose::System(x) => write!(f, "{x}"),
+            Purpose::Subsystem(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+/// A ship as it's being edited. Cells are on a grid, one unit per cell, and doors sit on the edge
+/// between two grid squares. A door with a cell on only one side is an airlock.
+#[derive(Default, Clone)]
+struct Layout {
+    /// The room each cell is in.
+    cells: HashMap<IVec2, usize>,
+    rooms: Vec<Purpose>,
+    /// Both sides of each door, lowest first.
+    doors: HashSet<(IVec2, IVec2)>,
+    mounts: Vec<WeaponMount>,
+    armor: [usize; 4],
+    super_shield: usize,
+}
+
+impl Layout {
+    fn from_ship(ship: &ShipType) -> Self {
+        let min = ship
+            .cell_positions
+            .iter()
+            .fold(Vec2::INFINITY, |min, &x| min.min(x));
+        let to_grid = |pos: Vec2| ((pos - min) / CELL_SIZE).round().as_ivec2();
+        let cells = ship
+            .cells()
+            .map(|x| (to_grid(ship.cell_positions[x.0]), ship.cell_room(x)))
+            .collect();
+        let rooms = ship
+            .room_systems
+            .iter()
+            .zip(ship.room_subsystems)
+            .map(|x| match x {
+                (Some(system), _) => Purpose::System(*system),
+                (None, Some(subsystem)) => Purpose::Subsystem(*subsystem),
+                (None, None) => Purpose::Empty,
+            })
+            .collect();
+        let doors = ship
+            .doors
+            .iter()
+            .map(|&door| match door {
+                Door::Interior(a, b) => edge(
+                    to_grid(ship.cell_positions[a.0]),
+                    to_grid(ship.cell_positions[b.0]),
+                ),
+                Door::Exterior(cell, dir) => {
+                    let a = to_grid(ship.cell_positions[cell.0]);
+                    edge(a, a + (dir.offset() / 17.5).as_ivec2())
+                }
+            })
+            .collect();
+        Self {
+            cells,
+            rooms,
+            doors,
+            mounts: ship.weapon_mounts.to_vec(),
+            armor: ship.armor,
+            super_shield: ship.super_shield,
+        }
+    }
+
+    /// Cells in export order: by room, then bottom to top and left to right within each room.
+    /// Rooms without any cells are left out.
+    fn ordered_cells(&self) -> Vec<IVec2> {
+        let mut cells = self.cells.keys().copied().collect::<Vec<_>>();
+        cells.sort_by_key(|x| (self.cells[x], x.y, x.x));
+        cells
+    }
+
+    /// Turn the layout into the parts of a [`ShipType`].
+    fn build(&self) -> Built {
+        let order = self.ordered_cells();
+        let index = order
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, Cell(i)))
+            .collect::<HashMap<_, _>>();
+        // Renumber rooms, dropping the empty ones
+        let mut used = self.cells.values().copied().collect::<Vec<_>>();
+        used.sort();
+        used.dedup();
+        let rooms = used
+            .iter()
+            .map(|&room| {
+                order
+                    .iter()
+                    .filter(|x| self.cells[x] == room)
+                    .map(|x| index[x])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let purposes = used.iter().map(|&x| self.rooms[x]).collect::<Vec<_>>();
+
+        let (min, max) = order
+            .iter()
+            .fold((IVec2::MAX, IVec2::MIN), |(min, max), &x| {
+                (min.min(x), max.max(x))
+            });
+        let center = (min + max).as_vec2() / 2.0;
+        let cell_positions = order
+            .iter()
+            .map(|x| (x.as_vec2() - center) * CELL_SIZE)
+            .collect::<Vec<_>>();
+
+        let same_room = |a: IVec2, b: IVec2| {
+            let room = self.cells.get(&a);
+            room.is_some() && room == self.cells.get(&b)
+        };
+        // Cover each room with as many 2x2 squares as fit without overlapping
+        let mut squares = Vec::new();
+        let mut in_square = HashMap::new();
+        for &a in &order {
+            let block = [a, a + IVec2::X, a + IVec2::Y, a + IVec2::ONE];
+            let fits = block
+                .iter()
+                .all(|&x| same_room(a, x) && !in_square.contains_key(&x));
+            if fits {
+                for x in block {
+                    in_square.insert(x, squares.len());
+                }
+                // Rows go up the ship, matching the built-in ships
+                squares.push([[block[0], block[1]], [block[2], block[3]]]);
+            }
+        }
+        let mut lines = Vec::new();
+        for &a in &order {
+            for b in [a + IVec2::X, a + IVec2::Y] {
+                let shared = in_square
+                    .get(&a)
+                    .is_some_and(|x| in_square.get(&b) == Some(x));
+                if same_room(a, b) && !shared {
+                    lines.push([a, b]);
+                }
+            }
+        }
+        let mut doors = Vec::new();
+        let mut dropped = 0;
+        let mut sorted_doors = self.doors.iter().copied().collect::<Vec<_>>();
+        sorted_doors.sort_by_key(|&(a, b)| (a.y, a.x, b.y, b.x));
+        for (a, b) in sorted_doors {
+            match (index.get(&a), index.get(&b)) {
+                (Some(&x), Some(&y)) => {
+                    doors.push(Door::Interior(x, y));
+                    lines.push([a, b]);
+                }
+                (Some(&x), None) => doors.push(Door::Exterior(x, door_dir(b - a))),
+                (None, Some(&y)) => doors.push(Door::Exterior(y, door_dir(a - b))),
+                (None, None) => dropped += 1,
+            }
+        }
+
+        let mut path_graph = BTreeMap::<usize, Vec<Cell>>::new();
+        let mut link = |a: IVec2, b: IVec2| {
+            path_graph.entry(index[&a].0).or_default().push(index[&b]);
+            path_graph.entry(index[&b].0).or_default().push(index[&a]);
+        };
+        for &[a, b] in &lines {
+            link(a, b);
+        }
+        for &[[a, b], [c, d]] in &squares {
+            for (x, y) in [(a, b), (a, c), (a, d), (b, c), (b, d), (c, d)] {
+                link(x, y);
+            }
+        }
+        let path_graph = path_graph
+            .into_iter()
+            .map(|(a, mut neighbors)| {
+                neighbors.sort_by_key(|x| x.0);
+                neighbors.dedup();
+                (Cell(a), neighbors)
+            })
+            .collect();
+
+        let layout = ShipLayout {
+            rooms,
+            lines: lines
+                .iter()
+                .map(|x| LineSection(x.map(|x| index[&x])))
+                .collect(),
+            squares: squares
+                .iter()
+                .map(|x| SquareSection(x.map(|row| row.map(|x| index[&x]))))
+                .collect(),
+            path_graph,
+            cell_positions,
+            room_systems: purposes
+                .iter()
+                .map(|x| match *x {
+                    Purpose::System(x) => Some(x),
+                    _ => None,
+                })
+                .collect(),
+            room_subsystems: purposes
+                .iter()
+                .map(|x| match *x {
+                    Purpose::Subsystem(x) => Some(x),
+                    _ => None,
+                })
+                .collect(),
+            doors,
+        };
+        Built {
+            layout,
+            dropped_doors: dropped,
+            weapon_mounts: self.mounts.clone(),
+            armor: self.armor,
+            super_shield: self.super_shield,
+        }
+    }
+}
+
+/// Both grid squares either side of an edge, in a consistent order.
+fn edge(a: IVec2, b: IVec2) -> (IVec2, IVec2) {
+    if (a.y, a.x) <= (b.y, b.x) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Which way an airlock faces, given the step from its cell out into space.
+fn door_dir(out: IVec2) -> DoorDir {
+    match (out.x, out.y) {
+        (1, _) => DoorDir::Right,
+        (-1, _) => DoorDir::Left,
+        (_, 1) => DoorDir::Top,
+        _ => DoorDir::Bottom,
+    }
+}
+
+/// The edge nearest `pos`, a point in grid units.
+fn nearest_edge(pos: Vec2) -> (IVec2, IVec2) {
+    let cell = pos.round().as_ivec2();
+    let offset = pos - cell.as_vec2();
+    let step = if offset.x.abs() > offset.y.abs() {
+        IVec2::new(offset.x.signum() as i32, 0)
+    } else {
+        IVec2::new(0, offset.y.signum() as i32)
+    };
+    edge(cell, cell + step)
+}
+
+/// A [`ShipType`]'s worth of parts, owned.
+struct Built {
+    layout: ShipLayout,
+    /// Doors with no cell on either side, which are left out.
+    dropped_doors: usize,
+    weapon_mounts: Vec<WeaponMount>,
+    armor: [usize; 4],
+    super_shield: usize,
+}
+
+impl Built {
+    /// The ship as a `const`, laid out like the ones in `common/src/ship.rs`.
+    fn to_source(&self) -> String {
+        let cells = |x: &[Cell]| {
+            let cells = x.iter().map(|x| format!("{x:?}")).collect::<Vec<_>>();
+            format!("&[{}]", cells.join(", "))
+        };
+        let mut out = String::from("pub const SHIP: ShipType = ShipType {\n    rooms: &[\n");
+        for x in &self.layout.rooms {
+            out += &format!(
+                "        Room {{\n            cells: {},\n        }},\n",
+                cells(x)
+            );
+        }
+        out += "    ],\n    nav_mesh: (\n        &[\n";
+        for LineSection([a, b]) in &self.layout.lines {
+            out += &format!("            LineSection([{a:?}, {b:?}]),\n");
+        }
+        out += "        ],\n        &[\n";
+        for SquareSection([[a, b], [c, d]]) in &self.layout.squares {
+            out += &format!("            SquareSection([[{a:?}, {b:?}], [{c:?}, {d:?}]]),\n");
+        }
+        out += "        ],\n    ),\n    path_graph: &[\n";
+        for (a, neighbors) in &self.layout.path_graph {
+            out += &format!("        ({a:?}, {}),\n", cells(neighbors));
+        }
+        out += "    ],\n    cell_positions: &[\n";
+        for x in &self.layout.cell_positions {
+            out += &format!("        Vec2::new({:.1}, {:.1}),\n", x.x, x.y);
+        }
+        out += "    ],\n    room_systems: &[\n";
+        for x in &self.layout.room_systems {
+            match x {
+                Some(x) => out += &format!("        Some(SystemId::{x:?}),\n"),
+                None => out += "        None,\n",
+            }
+        }
+        out += "    ],\n    room_subsystems: &[\n";
+        for x in &self.layout.room_subsystems {
+            match x {
+                Some(x) => out += &format!("        Some(SubsystemId::{x:?}),\n"),
+                None => out += "        None,\n",
+            }
+        }
+        out += "    ],\n    doors: &[\n";
+        for x in &self.layout.doors {
+            match x {
+                Door::Interior(a, b) => out += &format!("        Door::Interior({a:?}, {b:?}),\n"),
+                Door::Exterior(a, dir) => {
+                    out += &format!("        Door::Exterior({a:?}, DoorDir::{dir:?}),\n")
+                }
+            }
+        }
+        out += "    ],\n    weapon_mounts: &[\n";
+        for x in &self.weapon_mounts {
+            if *x == WeaponMount::TURRET {
+                out += "        WeaponMount::TURRET,\n";
+            } else {
+                out += "        WeaponMount {\n";
+                out += &format!("            direction: {:?},\n", x.direction);
+                out += &format!("            half_arc: {:?},\n", x.half_arc);
+                out += "        },\n";
+            }
+        }
+        out += &format!(
+            "    ],\n    armor: {:?},\n    super_shield: {},\n}};\n",
+            self.armor, self.super_shield
+        );
+        out
+    }
+}
+
+fn export(editor: &mut ShipEditor) {
+    editor.status.clear();
+    if editor.layout.cells.is_empty() {
+        editor.status.push("There's nothing to export yet.".into());
+        return;
+    }
+    let built = editor.layout.build();
+    if built.dropped_doors > 0 {
+        editor.status.push(format!(
+            "Left out {} doors with no cell on either side.",
+            built.dropped_doors
+        ));
+    }
+    let problems = check_ship(&built.layout);
+    if !problems.is_empty() {
+        editor
+            .status
+            .push(format!("Not exported, {} left to fix:", problems.len()));
+        editor.status.extend(problems);
+        return;
+    }
+    match std::fs::write(&editor.path, built.to_source()) {
+        Ok(()) => editor.status.push(format!("Wrote {}.", editor.path)),
+        Err(e) => editor
+            .status
+            .push(format!("Couldn't write {}: {e}", editor.path)),
+    }
+}
+
+fn editor_panel(mut ui: EguiContexts, mut editor: ResMut<ShipEditor>, mut commands: Commands) {
+    let editor = &mut *editor;
+    egui::SidePanel::left("ship_editor").show(ui.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut editor.tool, Tool::Cells, "Cells");
+                ui.selectable_value(&mut editor.tool, Tool::Doors, "Doors");
+            });
+            ui.weak(match editor.tool {
+                Tool::Cells => "Left click paints, right click erases",
+                Tool::Doors => "Click a cell edge to add or remove a door",
+            });
+            ui.separator();
+
+            ui.label("Rooms:");
+            for (room, purpose) in editor.layout.rooms.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let cells = editor.layout.cells.values().filter(|&&x| x == room).count();
+                    ui.radio_value(&mut editor.room, room, format!("{room} ({cells} cells)"));
+                    egui::ComboBox::from_id_salt(("room_purpose", room))
+                        .selected_text(purpose.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(purpose, Purpose::Empty, "empty");
+                            for x in SystemId::iter() {
+                                ui.selectable_value(purpose, Purpose::System(x), x.to_string());
+                            }
+                            for x in SubsystemId::iter() {
+                                ui.selectable_value(purpose, Purpose::Subsystem(x), x.to_string());
+                            }
+                        });
+                });
+            }
+            if ui.button("New room").clicked() {
+                editor.layout.rooms.push(Purpose::Empty);
+                editor.room = editor.layout.rooms.len() - 1;
+            }
+            ui.separator();
+
+            ui.label("Weapon mounts:");
+            let mut removed = None;
+            for (i, mount) in editor.layout.mounts.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let mut direction = mount.direction.to_degrees();
+                    let mut half_arc = mount.half_arc.to_degrees();
+                    ui.add(
+                        egui::DragValue::new(&mut direction)
+                            .range(-180.0..=180.0)
+                            .suffix("°"),
+                    );
+                    ui.label("±");
+                    ui.add(
+                        egui::DragValue::new(&mut half_arc)
+                            .range(0.0..=180.0)
+                            .suffix("°"),
+                    );
+                    mount.direction = direction.to_radians();
+                    mount.half_arc = half_arc.to_radians();
+                    if ui.small_button("x").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                editor.layout.mounts.remove(i);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add turret").clicked() {
+                    editor.layout.mounts.push(WeaponMount::TURRET);
+                }
+                if ui.button("Add fixed mount").clicked() {
+                    editor.layout.mounts.push(WeaponMount {
+                        direction: 0.0,
+                        half_arc: 0.15,
+                    });
+                }
+            });
+            ui.separator();
+
+            ui.label("Armor:");
+            egui::Grid::new("ship_editor_armor").show(ui, |ui| {
+                for x in Quadrant::iter() {
+                    ui.label(x.to_string());
+                    ui.add(egui::DragValue::new(&mut editor.layout.armor[x as usize]).range(0..=5));
+                    ui.end_row();
+                }
+                ui.label("Super shield");
+                ui.add(egui::DragValue::new(&mut editor.layout.super_shield).range(0..=5));
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Start over from");
+                for (i, ship) in SHIPS.iter().enumerate() {
+                    if ui.button(format!("ship {i}")).clicked() {
+                        editor.layout = Layout::from_ship(ship);
+                        editor.room = 0;
+                    }
+                }
+                if ui.button("nothing").clicked() {
+                    editor.layout = Layout {
+                        rooms: vec![Purpose::Empty],
+                        ..default()
+                    };
+                    editor.room = 0;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut editor.path);
+                if ui.button("Export").clicked() {
+                    export(editor);
+                }
+            });
+            for x in &editor.status {
+                ui.colored_label(Color32::YELLOW, x);
+            }
+            ui.separator();
+            if ui.button("Back to menu").clicked() {
+                commands.remove_resource::<ShipEditor>();
+                commands.queue(main_menu::open);
+            }
+        });
+    });
+}
+
+fn edit_layout(
+    mut ui: EguiContexts,
+    mut editor: ResMut<ShipEditor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Single<&Window>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+) {
+    if ui.ctx_mut().wants_pointer_input() || ui.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let (camera, camera_transform) = *camera;
+    let Some(pos) = window
+        .cursor_position()
+        .and_then(|x| camera.viewport_to_world_2d(camera_transform, x).ok())
+    else {
+        return;
+    };
+    let pos = pos / CELL_SIZE;
+    let editor = &mut *editor;
+    match editor.tool {
+        Tool::Cells => {
+            let cell = pos.round().as_ivec2();
+            if mouse.pressed(MouseButton::Left) {
+                editor.layout.cells.insert(cell, editor.room);
+            } else if mouse.pressed(MouseButton::Right)
+                && editor.layout.cells.remove(&cell).is_some()
+            {
+                // Airlocks can stay, but there's nothing for an interior door to join anymore
+                let cells = &editor.layout.cells;
+                editor
+                    .layout
+                    .doors
+                    .retain(|(a, b)| cells.contains_key(a) || cells.contains_key(b));
+            }
+        }
+        Tool::Doors => {
+            if mouse.just_pressed(MouseButton::Left) {
+                let edge = nearest_edge(pos);
+                if !editor.layout.doors.remove(&edge) {
+                    editor.layout.doors.insert(edge);
+                }
+            }
+        }
+    }
+}
+
+fn draw_layout(editor: Res<ShipEditor>, mut gizmos: Gizmos) {
+    let layout = &editor.layout;
+    let (min, max) = layout
+        .cells
+        .keys()
+        .fold((IVec2::splat(-4), IVec2::splat(4)), |(min, max), &x| {
+            (min.min(x - 2), max.max(x + 2))
+        });
+    // Grid lines sit on cell edges, half a cell off the centers
+    let half = CELL_SIZE / 2.0;
+    let grid = Color::srgba(1.0, 1.0, 1.0, 0.08);
+    for x in min.x..=max.x + 1 {
+        let x = x as f32 * CELL_SIZE - half;
+        gizmos.line_2d(
+            Vec2::new(x, min.y as f32 * CELL_SIZE - half),
+            Vec2::new(x, max.y as f32 * CELL_SIZE + half),
+            grid,
+        );
+    }
+    for y in min.y..=max.y + 1 {
+        let y = y as f32 * CELL_SIZE - half;
+        gizmos.line_2d(
+            Vec2::new(min.x as f32 * CELL_SIZE - half, y),
+            Vec2::new(max.x as f32 * CELL_SIZE + half, y),
+            grid,
+        );
+    }
+    for (&cell, &room) in &layout.cells {
+        let hue = (room as f32 * 67.0) % 360.0;
+        let mut color = Color::hsl(hue, 0.6, 0.5);
+        if room != editor.room {
+            color = color.with_alpha(0.5);
+        }
+        gizmos.rect_2d(
+            cell.as_vec2() * CELL_SIZE,
+            Vec2::splat(CELL_SIZE - 4.0),
+            color,
+        );
+    }
+    for &(a, b) in &layout.doors {
+        let mid = (a + b).as_vec2() / 2.0 * CELL_SIZE;
+        let across = (b - a).as_vec2().perp() * CELL_SIZE * 0.3;
+        let airlock = !(layout.cells.contains_key(&a) && layout.cells.contains_key(&b));
+        let color = if airlock {
+            palettes::css::LIGHT_BLUE
+        } else {
+            palettes::css::ORANGE
+        };
+        gizmos.line_2d(mid - across, mid + across, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuilding_a_ship_checks_out() {
+        let built = Layout::from_ship(&SHIPS[0]).build();
+        assert_eq!(built.dropped_doors, 0);
+        let ship = &built.layout;
+        assert_eq!(check_ship(ship), Vec::<String>::new());
+        assert_eq!(ship.rooms.len(), SHIPS[0].rooms.len());
+        assert_eq!(ship.doors.len(), SHIPS[0].doors.len());
+        assert_eq!(ship.lines.len(), SHIPS[0].nav_mesh.0.len());
+        assert_eq!(ship.squares.len(), SHIPS[0].nav_mesh.1.len());
+    }
+}
@@ -0,0 +1,92 @@
+//! Smooths replicated motion. The server only sends updates every few frames, and after a hitch (a
+//! long frame here, or a burst of updates arriving at once) things can be a long way from where we
+//! last drew them. Drawing a moment in the past, between the values we've seen, turns both of
+//! those into smooth movement instead of stutter and teleporting.
+
+use std::collections::VecDeque;
+
+use bevy::{math::VectorSpace, prelude::*};
+
+/// How far in the past things are drawn, in seconds. Also the longest it takes to catch up after a
+/// gap in updates, however long the gap was.
+const INTERPOLATION_DELAY: f32 = 0.05;
+
+/// Recent values of something replicated, each with the time it showed up.
+#[derive(Component, Debug, Clone)]
+pub struct Smoothed<T> {
+    samples: VecDeque<(f32, T)>,
+}
+
+impl<T> Default for Smoothed<T> {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: VectorSpace + PartialEq> Smoothed<T> {
+    /// Note that `value` is the latest as of `now`, and get back what to draw. Fine to call every
+    /// frame, repeats of the same value are ignored.
+    pub fn update(&mut self, now: f32, value: T) -> T {
+        if self.samples.back().map(|&(_, x)| x) != Some(value) {
+            if let Some((time, _)) = self.samples.back_mut() {
+                // After a gap, start from what's on screen instead of pretending the change has
+                // been under way the whole time, which would jump most of the way there
+                *time = time.max(now - INTERPOLATION_DELAY);
+            }
+            self.samples.push_back((now, value));
+        }
+        self.sample(now - INTERPOLATION_DELAY)
+    }
+
+    /// Forget the history, so the next value is drawn as is. For when something moves too far to
+    /// be the same thing moving.
+    pub fn snap(&mut self) {
+        self.samples.clear();
+    }
+
+    fn sample(&mut self, at: f32) -> T {
+        // Only the last sample from before `at` matters from here on
+        while self.samples.len() > 1 && self.samples[1].0 <= at {
+            self.samples.pop_front();
+        }
+        match (self.samples.front(), self.samples.get(1)) {
+            (Some(&(a_time, a)), Some(&(b_time, b))) if a_time < at => {
+                a.lerp(b, (at - a_time) / (b_time - a_time))
+            }
+            (Some(&(_, a)), _) => a,
+            (None, _) => T::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_are_caught_up_on_smoothly() {
+        let frame = 1.0 / 60.0;
+        let mut progress = Smoothed::default();
+        let mut now = 0.0;
+        let mut value = 0.0;
+        // The server sends a new value every other frame
+        for i in 0..60 {
+            if i % 2 == 0 {
+                value = now;
+            }
+            let drawn = progress.update(now, value);
+            assert!(drawn <= value);
+            now += frame;
+        }
+
+        // Nothing new for a whole second, then a big jump, which doesn't show up all at once...
+        now += 1.0;
+        assert_eq!(progress.update(now, value), value);
+        assert_eq!(progress.update(now + frame, now), value);
+        // ...but is all caught up on after a moment
+        let drawn = progress.update(now + frame + INTERPOLATION_DELAY, now);
+        assert!((drawn - now).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,200 @@
+//! Colors for everything where the color itself means something: weapon target rings, hull and
+//! system status, whose crew is whose. The default palette leans on red/yellow/green, so there's a
+//! colorblind-safe alternative, plus dash patterns on target rings so weapons can be told apart
+//! without going by color at all.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    color::palettes::{basic::*, css},
+    prelude::*,
+};
+use bevy_egui::{
+    egui::{self, Color32, Ui},
+    EguiContexts,
+};
+use strum::{EnumIter, IntoEnumIterator};
+
+pub fn theme_plugin(app: &mut App) {
+    app.init_resource::<ColorTheme>().add_systems(
+        Update,
+        apply_egui_theme.run_if(resource_changed::<ColorTheme>),
+    );
+}
+
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorTheme {
+    pub palette: Palette,
+    /// Draw each weapon's target rings with its own dash pattern.
+    pub patterns: bool,
+}
+
+#[derive(EnumIter, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    #[default]
+    Standard,
+    /// Okabe-Ito colors, which stay distinct under the common kinds of colorblindness.
+    ColorblindSafe,
+}
+
+impl Palette {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Standard => "Standard",
+            Palette::ColorblindSafe => "Colorblind safe",
+        }
+    }
+}
+
+/// What a status color is saying about the thing it's on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Good,
+    Warning,
+    Bad,
+}
+
+const VERMILLION: Srgba = Srgba::rgb(213.0 / 255.0, 94.0 / 255.0, 0.0);
+const ORANGE: Srgba = Srgba::rgb(230.0 / 255.0, 159.0 / 255.0, 0.0);
+const SKY_BLUE: Srgba = Srgba::rgb(86.0 / 255.0, 180.0 / 255.0, 233.0 / 255.0);
+const PALE_YELLOW: Srgba = Srgba::rgb(240.0 / 255.0, 228.0 / 255.0, 66.0 / 255.0);
+const REDDISH_PURPLE: Srgba = Srgba::rgb(204.0 / 255.0, 121.0 / 255.0, 167.0 / 255.0);
+const BLUISH_GREEN: Srgba = Srgba::rgb(0.0, 158.0 / 255.0, 115.0 / 255.0);
+
+impl ColorTheme {
+    /// Size and color of weapon `index`'s target rings.
+    pub fn weapon(&self, index: usize) -> (f32, Srgba) {
+        let colors = match self.palette {
+            Palette::Standard => [RED, YELLOW, LIME, PURPLE],
+            Palette::ColorblindSafe => [VERMILLION, SKY_BLUE, PALE_YELLOW, REDDISH_PURPLE],
+        };
+        assert!(index < colors.len(), "Index out of range");
+        (24.0 + 4.0 * index as f32, colors[index])
+    }
+
+    pub fn status(&self, status: Status) -> Srgba {
+        match (self.palette, status) {
+            (Palette::Standard, Status::Good) => LIME,
+            (Palette::Standard, Status::Warning) => css::ORANGE,
+            (Palette::Standard, Status::Bad) => RED,
+            (Palette::ColorblindSafe, Status::Good) => SKY_BLUE,
+            (Palette::ColorblindSafe, Status::Warning) => ORANGE,
+            (Palette::ColorblindSafe, Status::Bad) => VERMILLION,
+        }
+    }
+
+    pub fn status_egui(&self, status: Status) -> Color32 {
+        to_egui_color(self.status(status))
+    }
+
+    /// Flashes and anything else that has to grab attention right now. The colorblind-safe one is
+    /// kept clear of every weapon color, since it's flashed over them.
+    pub fn alert(&self) -> Srgba {
+        match self.palette {
+            Palette::Standard => RED,
+            Palette::ColorblindSafe => BLUISH_GREEN,
+        }
+    }
+
+    pub fn alert_egui(&self) -> Color32 {
+        to_egui_color(self.alert())
+    }
+
+    /// Oxygen readouts, by which way the room's oxygen is going: one color while it drains, another
+    /// while it refills and white while it holds steady.
+    pub fn oxygen(&self, rate: f32) -> Srgba {
+        let (draining, refilling) = match self.palette {
+            Palette::Standard => (css::LIGHT_SKY_BLUE, css::LIGHT_GREEN),
+            Palette::ColorblindSafe => (SKY_BLUE, PALE_YELLOW),
+        };
+        if rate < 0.0 {
+            draining
+        } else if rate > 0.0 {
+            refilling
+        } else {
+            WHITE
+        }
+    }
+
+    /// Our own crew, and intruders aboard somebody else's ship.
+    pub fn friendly(&self) -> Srgba {
+        self.status(Status::Good)
+    }
+
+    /// Everybody else's crew, and intruders aboard our ship.
+    pub fn hostile(&self) -> Srgba {
+        self.status(Status::Bad)
+    }
+
+    /// The ring marking weapon `index`'s target, centered on `pos`.
+    pub fn target_ring(&self, gizmos: &mut Gizmos, pos: Vec3, index: usize) {
+        self.faded_target_ring(gizmos, pos, index, 1.0);
+    }
+
+    /// [`Self::target_ring`], at `alpha` opacity.
+    pub fn faded_target_ring(&self, gizmos: &mut Gizmos, pos: Vec3, index: usize, alpha: f32) {
+        let (size, color) = self.weapon(index);
+        let color = color.with_alpha(alpha);
+        // Solid for the first weapon, then shorter and shorter dashes
+        let dashes = match index {
+            _ if !self.patterns => 0,
+            0 => 0,
+            1 => 6,
+            2 => 12,
+            _ => 24,
+        };
+        if dashes == 0 {
+            gizmos.circle(pos, size, color);
+            return;
+        }
+        let step = TAU / (2 * dashes) as f32;
+        for dash in 0..dashes {
+            let start = 2.0 * dash as f32 * step;
+            let points = (0..=4).map(|x| {
+                let angle = start + step * x as f32 / 4.0;
+                pos + (Vec2::from_angle(angle) * size).extend(0.0)
+            });
+            gizmos.linestrip(points, color);
+        }
+    }
+}
+
+pub fn to_egui_color(color: Srgba) -> Color32 {
+    Color32::from_rgb(
+        (color.red * 255.0) as u8,
+        (color.green * 255.0) as u8,
+        (color.blue * 255.0) as u8,
+    )
+}
+
+/// Palette picker and pattern toggle, for a settings section.
+pub fn theme_ui(ui: &mut Ui, theme: &mut ColorTheme) {
+    ui.horizontal(|ui| {
+        ui.label("Colors:");
+        egui::ComboBox::from_id_salt("palette")
+            .selected_text(theme.palette.name())
+            .show_ui(ui, |ui| {
+                for palette in Palette::iter() {
+                    ui.selectable_value(&mut theme.palette, palette, palette.name());
+                }
+            });
+    });
+    ui.checkbox(&mut theme.patterns, "Dashed target rings")
+        .on_hover_text("Give each weapon's target rings their own dash pattern.");
+}
+
+/// The theme as of the last time it changed, for code that only has an egui context to go on.
+pub fn egui_theme(ctx: &egui::Context) -> ColorTheme {
+    ctx.data(|x| x.get_temp(egui::Id::NULL)).unwrap_or_default()
+}
+
+/// Make egui's own warning and error text match the palette, and stash the theme where
+/// [`egui_theme`] can find it.
+fn apply_egui_theme(mut ui: EguiContexts, theme: Res<ColorTheme>) {
+    let ctx = ui.ctx_mut();
+    ctx.style_mut(|style| {
+        style.visuals.warn_fg_color = theme.status_egui(Status::Warning);
+        style.visuals.error_fg_color = theme.status_egui(Status::Bad);
+    });
+    ctx.data_mut(|x| x.insert_temp(egui::Id::NULL, *theme));
+}
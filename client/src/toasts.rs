@@ -0,0 +1,58 @@
+//! Short-lived notices under the alert bar, for commands the server turned down.
+
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, RichText},
+    EguiContexts,
+};
+use common::events::CommandRejected;
+
+/// How long a toast stays up, in seconds.
+const TOAST_TIME: f32 = 3.0;
+/// Toasts fade out over this many seconds at the end of their time.
+const TOAST_FADE: f32 = 0.5;
+
+pub fn toasts_plugin(app: &mut App) {
+    app.init_resource::<Toasts>()
+        .add_systems(Update, (receive_rejections, draw_toasts).chain());
+}
+
+/// Toasts currently showing, oldest first, with the time each one expires.
+#[derive(Resource, Default)]
+struct Toasts(Vec<(String, f32)>);
+
+fn receive_rejections(
+    mut rejections: EventReader<CommandRejected>,
+    mut toasts: ResMut<Toasts>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    toasts.0.retain(|&(_, expires)| expires > now);
+    for CommandRejected { reason } in rejections.read() {
+        // Holding a key down can send the same doomed command over and over, so repeats just
+        // keep the one toast up instead of stacking
+        toasts.0.retain(|(x, _)| x != reason);
+        toasts.0.push((reason.clone(), now + TOAST_TIME));
+    }
+}
+
+fn draw_toasts(mut ui: EguiContexts, toasts: Res<Toasts>, time: Res<Time>) {
+    if toasts.0.is_empty() {
+        return;
+    }
+    let now = time.elapsed_secs();
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2::new(0.0, 40.0))
+        .interactable(false)
+        .show(ui.ctx_mut(), |ui| {
+            for (reason, expires) in &toasts.0 {
+                let alpha = ((expires - now) / TOAST_FADE).clamp(0.0, 1.0);
+                let color = Color32::from_rgb(255, 200, 80).gamma_multiply(alpha);
+                egui::Frame::popup(ui.style())
+                    .multiply_with_opacity(alpha)
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(reason).color(color));
+                    });
+            }
+        });
+}
@@ -0,0 +1,233 @@
+//! Keeps a stray click from venting our own crew. Door commands that would open a room with our
+//! crew in it to space either wait on a confirmation or need Shift held, depending on the setting.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{
+    egui::{self, Ui},
+    EguiContexts,
+};
+use common::{
+    events::{SetDoorsOpen, ShipCommand},
+    intel::{DoorsIntel, InteriorIntel, SelfIntel, ShipIntel},
+    ship::{Door, SHIPS},
+    DoorState,
+};
+use strum::{EnumIter, IntoEnumIterator};
+
+/// How long the confirmation waits for an answer before dropping the command, in seconds.
+const CONFIRM_TIME: f32 = 5.0;
+/// How long the reminder to hold Shift stays up, in seconds.
+const HINT_TIME: f32 = 2.0;
+
+pub fn vent_guard_plugin(app: &mut App) {
+    app.init_resource::<VentGuard>()
+        .init_resource::<PendingVent>()
+        .add_systems(Update, confirm_vent);
+}
+
+#[derive(Resource, EnumIter, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VentGuard {
+    /// Doors do whatever they're told.
+    Off,
+    /// Ask before venting crewed rooms.
+    #[default]
+    Confirm,
+    /// Only vent crewed rooms with Shift held.
+    Modifier,
+}
+
+impl VentGuard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VentGuard::Off => "Off",
+            VentGuard::Confirm => "Ask first",
+            VentGuard::Modifier => "Hold Shift",
+        }
+    }
+}
+
+/// A door command held back because it would vent our crew, how many crewed rooms it would vent,
+/// and when it expires. With no command it's only the reminder to hold Shift.
+#[derive(Resource, Default)]
+struct PendingVent(Option<(Option<ShipCommand<SetDoorsOpen>>, usize, f32)>);
+
+/// Sends door commands for our own ship, checking with the player first if the command would open
+/// a room our crew are in to space.
+#[derive(SystemParam)]
+pub struct DoorCommands<'w, 's> {
+    set_doors_open: EventWriter<'w, ShipCommand<SetDoorsOpen>>,
+    self_intel: Query<'w, 's, &'static SelfIntel>,
+    ships: Query<'w, 's, &'static ShipIntel>,
+    interiors: Query<'w, 's, &'static InteriorIntel>,
+    doors_intel: Query<'w, 's, &'static DoorsIntel>,
+    guard: Res<'w, VentGuard>,
+    pending: ResMut<'w, PendingVent>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    time: Res<'w, Time>,
+}
+
+impl DoorCommands<'_, '_> {
+    pub fn send(&mut self, command: ShipCommand<SetDoorsOpen>) {
+        let shift = self
+            .keys
+            .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let crewed = self.crewed_rooms_vented(&command);
+        let now = self.time.elapsed_secs();
+        match *self.guard {
+            _ if crewed == 0 => {}
+            VentGuard::Off => {}
+            VentGuard::Confirm => {
+                self.pending.0 = Some((Some(command), crewed, now + CONFIRM_TIME));
+                return;
+            }
+            VentGuard::Modifier if shift => {}
+            VentGuard::Modifier => {
+                self.pending.0 = Some((None, crewed, now + HINT_TIME));
+                return;
+            }
+        }
+        self.set_doors_open.send(command);
+    }
+
+    /// How many rooms with our crew in them `command` would newly open to space.
+    fn crewed_rooms_vented(&self, command: &ShipCommand<SetDoorsOpen>) -> usize {
+        let Ok(self_intel) = self.self_intel.get_single() else {
+            return 0;
+        };
+        if command.ship != self_intel.ship {
+            return 0;
+        }
+        let Ok(ship) = self.ships.get(self_intel.ship) else {
+            return 0;
+        };
+        let Ok(interior) = self.interiors.get(ship.interior) else {
+            return 0;
+        };
+        let Ok(doors) = self.doors_intel.get(ship.doors) else {
+            return 0;
+        };
+        let ship_type = &SHIPS[ship.basic.ship_type];
+        let after = open_after(ship.basic.ship_type, doors, command.command);
+        let before = ship_type.vented_rooms(|x| doors[x].is_open());
+        ship_type
+            .vented_rooms(|x| after[x])
+            .difference(&before)
+            .filter(|&&x| interior.rooms.get(x).is_some_and(|x| !x.crew.is_empty()))
+            .count()
+    }
+}
+
+/// Which doors would be open once the server carries out `command`. Broken doors stay open
+/// whatever happens.
+fn open_after(ship_type: usize, doors: &[DoorState], command: SetDoorsOpen) -> Vec<bool> {
+    let ship_type = &SHIPS[ship_type];
+    let mut open = doors.iter().map(|x| x.open).collect::<Vec<_>>();
+    let is_interior = |door: usize| matches!(ship_type.doors[door], Door::Interior(_, _));
+    match command {
+        SetDoorsOpen::Single { door, open: x } => {
+            if let Some(open) = open.get_mut(door) {
+                *open = x;
+            }
+        }
+        SetDoorsOpen::All { open: x } => {
+            // Same as the server: interior doors first, then the airlocks on a second press
+            let interior_open = (0..open.len()).filter(|&x| is_interior(x)).all(|x| open[x]);
+            for (door, open) in open.iter_mut().enumerate() {
+                if !x || interior_open || is_interior(door) {
+                    *open = x;
+                }
+            }
+        }
+        SetDoorsOpen::OpenPath { from, to } => {
+            for door in ship_type.door_path(from, to).into_iter().flatten() {
+                open[door] = true;
+            }
+        }
+        // Neither of these ever opens a crewed room to space
+        SetDoorsOpen::VentUnoccupied | SetDoorsOpen::CloseExterior => {}
+    }
+    open.into_iter()
+        .zip(doors)
+        .map(|(open, door)| open || door.broken())
+        .collect()
+}
+
+fn confirm_vent(
+    mut ui: EguiContexts,
+    mut pending: ResMut<PendingVent>,
+    mut set_doors_open: EventWriter<ShipCommand<SetDoorsOpen>>,
+    time: Res<Time>,
+) {
+    let Some((command, crewed, expires)) = &pending.0 else {
+        return;
+    };
+    if *expires <= time.elapsed_secs() {
+        pending.0 = None;
+        return;
+    }
+    let rooms = match crewed {
+        1 => "a room".to_string(),
+        x => format!("{x} rooms"),
+    };
+    let Some(command) = command else {
+        egui::Area::new(egui::Id::new("vent_hint"))
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .interactable(false)
+            .show(ui.ctx_mut(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "That vents {rooms} with our crew inside. Hold Shift to do it anyway."
+                        ),
+                    );
+                });
+            });
+        return;
+    };
+    let mut answer = None;
+    egui::Window::new("Vent crew?")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(ui.ctx_mut(), |ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("This vents {rooms} with our crew inside."),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Vent").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    answer = Some(false);
+                }
+            });
+        });
+    match answer {
+        Some(true) => {
+            set_doors_open.send(command.clone());
+            pending.0 = None;
+        }
+        Some(false) => pending.0 = None,
+        None => {}
+    }
+}
+
+/// Picker for the guard, for a settings section.
+pub fn vent_guard_ui(ui: &mut Ui, guard: &mut VentGuard) {
+    ui.horizontal(|ui| {
+        ui.label("Venting crew:");
+        egui::ComboBox::from_id_salt("vent_guard")
+            .selected_text(guard.name())
+            .show_ui(ui, |ui| {
+                for x in VentGuard::iter() {
+                    ui.selectable_value(guard, x, x.name());
+                }
+            });
+    })
+    .response
+    .on_hover_text(
+        "What to do when a door command would open a room with our crew in it to space.",
+    );
+}
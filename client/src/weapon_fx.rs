@@ -0,0 +1,155 @@
+//! Muzzle flashes and weapon sounds, all from each weapon's [`Presentation`], so a new weapon only
+//! needs its entry in the weapon table to look and sound the way it should. Flashes and fire sounds
+//! go off on [`MatchEvent::ShotFired`] coming in through the combat log, and hit sounds on
+//! [`HullImpact`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use common::{
+    bullets::{FiredFrom, HullImpact, Progress, RoomTarget, WeaponDamage, SHIELD_PROGRESS},
+    intel::ShipIntel,
+    journal::{CombatLogEntry, MatchEvent},
+    weapon::{Presentation, WeaponId},
+};
+
+use crate::graphics::Z_BULLETS;
+
+pub fn weapon_fx_plugin(app: &mut App) {
+    app.init_resource::<IncomingWeapons>().add_systems(
+        Update,
+        (
+            fire_weapons,
+            animate_muzzle_flashes,
+            (track_incoming, play_hit_sounds).chain(),
+        ),
+    );
+}
+
+const FLASH_SECS: f32 = 0.12;
+
+/// The weapon that fired `origin`'s shot, if we can see what's in that slot.
+pub fn shot_weapon(intel: &ShipIntel, origin: &FiredFrom) -> Option<WeaponId> {
+    let weapons = intel.basic.weapons.as_ref()?;
+    Some(weapons.weapons.get(origin.weapon_index)?.weapon)
+}
+
+/// A weapon's shot color.
+pub fn shot_color(presentation: &Presentation) -> Color {
+    Color::srgb_from_array(presentation.color)
+}
+
+#[derive(Component)]
+struct MuzzleFlashFx {
+    timer: Timer,
+    size: f32,
+}
+
+fn fire_weapons(
+    mut entries: EventReader<CombatLogEntry>,
+    assets: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for entry in entries.read() {
+        let MatchEvent::ShotFired { ship, weapon, .. } = entry.event else {
+            continue;
+        };
+        let presentation = weapon.common().presentation;
+        if let Some(sound) = presentation.fire_sound {
+            commands.spawn((
+                AudioPlayer::new(assets.load(sound)),
+                PlaybackSettings::DESPAWN,
+            ));
+        }
+        let Some(flash) = presentation.muzzle_flash else {
+            continue;
+        };
+        let Some(mut ship) = commands.get_entity(ship) else {
+            continue;
+        };
+        // Shots leave from the middle of the ship for now, so that's where the flash goes
+        ship.with_child((
+            MuzzleFlashFx {
+                timer: Timer::from_seconds(FLASH_SECS, TimerMode::Once),
+                size: flash.size,
+            },
+            PickingBehavior::IGNORE,
+            Sprite {
+                color: Color::srgb_from_array(flash.color),
+                custom_size: Some(Vec2::splat(flash.size)),
+                ..default()
+            },
+            Transform::from_xyz(0.0, 0.0, Z_BULLETS),
+        ));
+    }
+}
+
+/// Flashes swell a little and fade out.
+fn animate_muzzle_flashes(
+    mut flashes: Query<(Entity, &mut MuzzleFlashFx, &mut Sprite)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (e, mut flash, mut sprite) in &mut flashes {
+        flash.timer.tick(time.delta());
+        if flash.timer.finished() {
+            commands.entity(e).despawn();
+            continue;
+        }
+        let t = flash.timer.fraction();
+        sprite.custom_size = Some(Vec2::splat(flash.size * (1.0 + 0.5 * t)));
+        sprite.color.set_alpha(1.0 - t);
+    }
+}
+
+/// Weapon that fired the projectile closest to hitting each ship. Projectiles are gone by the time
+/// their [`HullImpact`] comes in, the same as for the hull damage effects.
+#[derive(Resource, Default)]
+struct IncomingWeapons(HashMap<Entity, WeaponId>);
+
+fn track_incoming(
+    projectiles: Query<(&RoomTarget, &Progress, &FiredFrom), With<WeaponDamage>>,
+    ships: Query<&ShipIntel>,
+    mut incoming: ResMut<IncomingWeapons>,
+) {
+    let mut closest = HashMap::<Entity, (f32, WeaponId)>::new();
+    for (target, progress, origin) in &projectiles {
+        let progress = **progress;
+        if progress < SHIELD_PROGRESS {
+            continue;
+        }
+        let Some(weapon) = ships
+            .get(origin.ship)
+            .ok()
+            .and_then(|x| shot_weapon(x, origin))
+        else {
+            continue;
+        };
+        let entry = closest.entry(target.ship).or_insert((progress, weapon));
+        if progress > entry.0 {
+            *entry = (progress, weapon);
+        }
+    }
+    for (ship, (_, weapon)) in closest {
+        incoming.0.insert(ship, weapon);
+    }
+}
+
+fn play_hit_sounds(
+    mut impacts: EventReader<HullImpact>,
+    mut incoming: ResMut<IncomingWeapons>,
+    assets: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for impact in impacts.read() {
+        let Some(weapon) = incoming.0.remove(&impact.ship) else {
+            continue;
+        };
+        if let Some(sound) = weapon.common().presentation.hit_sound {
+            commands.spawn((
+                AudioPlayer::new(assets.load(sound)),
+                PlaybackSettings::DESPAWN,
+            ));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use bevy::math::Vec2;
+use common::nav::{Cell, PathGraph, Pathfinder};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+/// A `width` by `height` grid of cells, each connected to its four neighbors. Much bigger than any
+/// ship we actually have, which is the point.
+fn grid(width: usize, height: usize) -> (PathGraph, Vec<Vec2>) {
+    let index = |x: usize, y: usize| Cell(y * width + x);
+    let mut edges = std::collections::HashMap::new();
+    let mut positions = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            positions.push(Vec2::new(x as f32, y as f32) * 35.0);
+            let mut neighbors = std::collections::HashSet::new();
+            if x > 0 {
+                neighbors.insert(index(x - 1, y));
+            }
+            if x + 1 < width {
+                neighbors.insert(index(x + 1, y));
+            }
+            if y > 0 {
+                neighbors.insert(index(x, y - 1));
+            }
+            if y + 1 < height {
+                neighbors.insert(index(x, y + 1));
+            }
+            edges.insert(index(x, y), neighbors);
+        }
+    }
+    (PathGraph { edges }, positions)
+}
+
+fn pathfinding(c: &mut Criterion) {
+    let (graph, positions) = grid(32, 32);
+    let goal = Cell(0);
+    let start = Cell(32 * 32 - 1);
+
+    // The first order toward a goal floods the whole graph...
+    c.bench_function("route_flood", |b| {
+        b.iter_batched(
+            || Pathfinder::new(graph.clone(), positions.clone()),
+            |mut pathfinder| {
+                black_box(
+                    pathfinder
+                        .pathing_to(black_box(goal), &[black_box(start)])
+                        .goal(),
+                );
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    // ...and every one after that is a lookup
+    let mut pathfinder = Pathfinder::new(graph.clone(), positions.clone());
+    c.bench_function("route_lookup", |b| {
+        b.iter(|| {
+            black_box(
+                pathfinder
+                    .pathing_to(black_box(goal), &[black_box(start)])
+                    .goal(),
+            );
+        })
+    });
+
+    // A cost layer means searching with A* again
+    let with_costs = || {
+        let mut pathfinder = Pathfinder::new(graph.clone(), positions.clone());
+        pathfinder.set_cell_cost(Cell(1), 10.0);
+        pathfinder
+    };
+    c.bench_function("a_star_uncached", |b| {
+        b.iter_batched(
+            with_costs,
+            |mut pathfinder| {
+                black_box(
+                    pathfinder
+                        .pathing_to(black_box(goal), &[black_box(start)])
+                        .goal(),
+                );
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let mut pathfinder = with_costs();
+    c.bench_function("a_star_cached", |b| {
+        b.iter(|| {
+            black_box(
+                pathfinder
+                    .pathing_to(black_box(goal), &[black_box(start)])
+                    .goal(),
+            );
+        })
+    });
+}
+
+criterion_group!(benches, pathfinding);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! Ship-wide alarms. The server raises these for each player's own ship as part of
+//! [`SelfIntel`](crate::intel::SelfIntel) so the client can flash and sound them rather than
+//! relying on the player to notice a number turning red.
+
+use serde::{Deserialize, Serialize};
+
+/// Average oxygen level (0-1) below which [`Alarm::LowOxygen`] is raised.
+pub const LOW_OXYGEN: f32 = 0.25;
+/// Fraction of hull remaining below which [`Alarm::HullCritical`] is raised.
+pub const LOW_HULL: f32 = 1.0 / 3.0;
+/// Seconds until suffocation below which [`Alarm::LowOxygen`] is raised, however much air is left.
+pub const SUFFOCATION_WARNING: f32 = 30.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Alarm {
+    LowOxygen,
+    HullCritical,
+    /// Any cell aboard is burning.
+    Fire,
+    /// Boarders from another ship are aboard.
+    Intruders,
+}
+
+impl Alarm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alarm::LowOxygen => "Low oxygen",
+            Alarm::HullCritical => "Hull critical",
+            Alarm::Fire => "Fire",
+            Alarm::Intruders => "Intruders aboard",
+        }
+    }
+}
+
+/// Alarms that should be raised for a ship with average `oxygen`, `suffocation_in` seconds of air
+/// left (if it's running out at all), `hull` out of `max_hull` hull points left, and whether it's
+/// got a fire or intruders aboard.
+pub fn raised_alarms(
+    oxygen: f32,
+    suffocation_in: Option<f32>,
+    hull: usize,
+    max_hull: usize,
+    fire: bool,
+    intruders: bool,
+) -> Vec<Alarm> {
+    let mut alarms = Vec::new();
+    if oxygen < LOW_OXYGEN || suffocation_in.is_some_and(|x| x < SUFFOCATION_WARNING) {
+        alarms.push(Alarm::LowOxygen);
+    }
+    if (hull as f32) < LOW_HULL * max_hull as f32 {
+        alarms.push(Alarm::HullCritical);
+    }
+    if fire {
+        alarms.push(Alarm::Fire);
+    }
+    if intruders {
+        alarms.push(Alarm::Intruders);
+    }
+    alarms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(raised_alarms(1.0, None, 30, 30, false, false), vec![]);
+        assert_eq!(
+            raised_alarms(0.2, None, 30, 30, false, false),
+            vec![Alarm::LowOxygen]
+        );
+        assert_eq!(raised_alarms(0.25, None, 10, 30, false, false), vec![]);
+        assert_eq!(raised_alarms(0.8, Some(60.0), 30, 30, false, false), vec![]);
+        assert_eq!(
+            raised_alarms(0.8, Some(10.0), 30, 30, false, false),
+            vec![Alarm::LowOxygen]
+        );
+        assert_eq!(
+            raised_alarms(0.0, Some(0.0), 9, 30, false, false),
+            vec![Alarm::LowOxygen, Alarm::HullCritical]
+        );
+        assert_eq!(
+            raised_alarms(1.0, None, 30, 30, true, true),
+            vec![Alarm::Fire, Alarm::Intruders]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Augments: passive upgrades a ship carries around that tweak how its systems behave. Each ship
+//! can hold up to [`MAX_AUGMENTS`]; their effects stack and are folded into [`AugmentEffects`],
+//! which the server consults wherever an augment can have an effect.
+
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, EnumString};
+
+/// How many augments a ship can have installed at once.
+pub const MAX_AUGMENTS: usize = 3;
+
+#[derive(Serialize, Deserialize, EnumIter, EnumString, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "snake_case")]
+pub enum AugmentId {
+    /// Weapons charge 15% faster.
+    AutomatedReloader,
+    /// Shield layers recharge 15% faster.
+    ShieldChargeBooster,
+    /// A super shield that soaks up 5 damage before it gives out.
+    ZoltanShield,
+    /// Weapons start the match powered and fully charged.
+    WeaponPreIgniter,
+    /// Crew take half damage from suffocation.
+    EmergencyRespirators,
+    /// Three hull repairs to use mid-fight, with a
+    /// [cooldown](crate::balance::BalanceConfig::hull_repair_cooldown) between them.
+    HullRepairKit,
+}
+
+impl AugmentId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AugmentId::AutomatedReloader => "Automated Reloader",
+            AugmentId::ShieldChargeBooster => "Shield Charge Booster",
+            AugmentId::ZoltanShield => "Zoltan Shield",
+            AugmentId::WeaponPreIgniter => "Weapon Pre-Igniter",
+            AugmentId::EmergencyRespirators => "Emergency Respirators",
+            AugmentId::HullRepairKit => "Hull Repair Kit",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            AugmentId::AutomatedReloader => "Weapons charge 15% faster.",
+            AugmentId::ShieldChargeBooster => "Shield layers recharge 15% faster.",
+            AugmentId::ZoltanShield => "A super shield absorbs 5 damage and keeps boarders out.",
+            AugmentId::WeaponPreIgniter => "Weapons start the match powered and fully charged.",
+            AugmentId::EmergencyRespirators => "Crew take half damage from suffocation.",
+            AugmentId::HullRepairKit => "Patch up 5 hull on demand, three times a match.",
+        }
+    }
+
+    /// Price in scrap, for when there's a store to buy these from.
+    pub fn cost(&self) -> usize {
+        match self {
+            AugmentId::AutomatedReloader => 60,
+            AugmentId::ShieldChargeBooster => 55,
+            AugmentId::ZoltanShield => 90,
+            AugmentId::WeaponPreIgniter => 120,
+            AugmentId::EmergencyRespirators => 40,
+            AugmentId::HullRepairKit => 70,
+        }
+    }
+
+    /// Fold this augment's effect into `effects`.
+    fn apply(&self, effects: &mut AugmentEffects) {
+        match self {
+            AugmentId::AutomatedReloader => effects.weapon_charge_rate += 0.15,
+            AugmentId::ShieldChargeBooster => effects.shield_charge_rate += 0.15,
+            AugmentId::ZoltanShield => effects.super_shield += 5,
+            AugmentId::WeaponPreIgniter => effects.precharged_weapons = true,
+            AugmentId::EmergencyRespirators => effects.suffocation_damage *= 0.5,
+            AugmentId::HullRepairKit => effects.hull_repairs += 3,
+        }
+    }
+}
+
+/// The combined effect of a set of augments. Rates are multipliers on the base rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AugmentEffects {
+    pub weapon_charge_rate: f32,
+    pub shield_charge_rate: f32,
+    /// Points of super shield the ship goes into a fight with.
+    pub super_shield: usize,
+    /// Whether weapons come into the match powered and charged. Only checked at match start.
+    pub precharged_weapons: bool,
+    /// Multiplier on suffocation damage to the ship's own crew.
+    pub suffocation_damage: f32,
+    /// Hull repairs the ship can make over the match.
+    pub hull_repairs: usize,
+}
+
+impl Default for AugmentEffects {
+    fn default() -> Self {
+        Self {
+            weapon_charge_rate: 1.0,
+            shield_charge_rate: 1.0,
+            super_shield: 0,
+            precharged_weapons: false,
+            suffocation_damage: 1.0,
+            hull_repairs: 0,
+        }
+    }
+}
+
+impl AugmentEffects {
+    pub fn of<'a>(augments: impl IntoIterator<Item = &'a AugmentId>) -> Self {
+        let mut effects = Self::default();
+        for augment in augments {
+            augment.apply(&mut effects);
+        }
+        effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_stack() {
+        let effects =
+            AugmentEffects::of(&[AugmentId::AutomatedReloader, AugmentId::AutomatedReloader]);
+        assert!((effects.weapon_charge_rate - 1.3).abs() < 1e-6);
+        assert_eq!(effects.shield_charge_rate, 1.0);
+        assert_eq!(
+            "shield_charge_booster".parse(),
+            Ok(AugmentId::ShieldChargeBooster)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Tuning numbers for the simulation, gathered in one place so they can be tweaked from a file
+//! instead of by recompiling. The server loads them, the client only keeps the defaults around for
+//! the few things it works out for itself (like dodge chance) and compares [`BalanceConfig::hash`]
+//! against the one in [`SelfIntel`](crate::intel::SelfIntel) to tell whether those are right.
+//!
+//! Rates are per second unless documented otherwise.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::util::{fnv1a, FNV1A_BASIS};
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BalanceConfig {
+    /// Shield layers recovered per second, by how many layers are already up. Anything past the end
+    /// uses the last rate.
+    pub shield_charge_rates: Vec<f32>,
+    /// How fast each cell's oxygen fills, by oxygen system power. Anything past the end uses the
+    /// first rate, same as no power at all.
+    pub oxygen_fill_rates: Vec<f32>,
+    /// How fast each cell's oxygen drains once the oxygen system is destroyed outright, rather than
+    /// just unpowered.
+    pub oxygen_destroyed_rate: f32,
+    /// Oxygen each crew member (or boarder) breathes while the oxygen system isn't making any,
+    /// shared out over the cells of their room, so crowded little rooms run out first.
+    pub crew_oxygen_use: f32,
+    /// How quickly air evens out between neighboring cells of the same room, per unit of
+    /// difference.
+    pub cell_flow: f32,
+    /// How quickly air flows through an open door, either to the next room or out into space. Hull
+    /// breaches vent at the same rate.
+    pub door_flow: f32,
+    /// Crew start suffocating below this much oxygen in their cell.
+    pub suffocation_oxygen: f32,
+    /// Health lost by a suffocating crew member.
+    pub suffocation_damage: f32,
+    /// Oxygen a fire burns through in its cell.
+    pub fire_burn_rate: f32,
+    /// Fires go out once their cell's oxygen drops below this.
+    pub fire_min_oxygen: f32,
+    /// Health lost by crew standing in a burning cell.
+    pub fire_crew_damage: f32,
+    /// Seconds it takes one crew member to put out a fire or patch a breach.
+    pub cell_repair_time: f32,
+    /// Seconds it takes one crew member to repair a point of system damage.
+    pub system_repair_time: f32,
+    /// Percent dodge chance per point of engine power.
+    pub dodge_per_engine_power: usize,
+    /// Seconds it takes the clone bay to bring back a dead crew member.
+    pub clone_time: f32,
+    /// Fraction of their max health clones come back with.
+    pub clone_health: f32,
+    /// How much of its path a boarding shuttle covers per second.
+    pub shuttle_speed: f32,
+    /// Most crew a boarding shuttle can carry.
+    pub shuttle_capacity: usize,
+    /// Percent chance for each powered weapon on the target to shoot down an incoming shuttle.
+    pub point_defense_per_weapon: usize,
+    /// Health lost by crew fighting boarders (or defenders) in the same room.
+    pub crew_combat_damage: f32,
+    /// Seconds it takes one boarder to knock out a point of system power.
+    pub sabotage_time: f32,
+    /// Seconds it takes one boarder to break down a closed door, per working level of the doors
+    /// subsystem.
+    pub door_break_time: f32,
+    /// Seconds a broken door hangs open before it fixes itself.
+    pub door_broken_time: f32,
+    /// Seconds it takes to fix a broken door with someone manning the doors subsystem.
+    pub door_repair_time: f32,
+    /// Hull points each repair from the Hull Repair Kit augment patches up.
+    pub hull_repair_amount: usize,
+    /// Seconds between hull repairs.
+    pub hull_repair_cooldown: f32,
+    /// Chance in `[0, 1]` for a projectile that reaches the hull to only graze it.
+    pub graze_chance: f32,
+    /// Added to a projectile's crit chance when someone was manning the weapons that fired it.
+    pub manned_crit_bonus: f32,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        Self {
+            shield_charge_rates: vec![0.5, 0.5, 0.58, 0.67, 0.75],
+            oxygen_fill_rates: vec![-0.012, 0.012, 0.048, 0.084],
+            oxygen_destroyed_rate: -0.03,
+            crew_oxygen_use: 0.01,
+            cell_flow: 4.0,
+            door_flow: 3.0,
+            suffocation_oxygen: 0.05,
+            suffocation_damage: 6.4,
+            fire_burn_rate: 0.08,
+            fire_min_oxygen: 0.1,
+            fire_crew_damage: 8.0,
+            cell_repair_time: 4.0,
+            system_repair_time: 12.0,
+            dodge_per_engine_power: 5,
+            clone_time: 12.0,
+            clone_health: 0.5,
+            shuttle_speed: 0.12,
+            shuttle_capacity: 2,
+            point_defense_per_weapon: 15,
+            crew_combat_damage: 10.0,
+            sabotage_time: 10.0,
+            door_break_time: 6.0,
+            door_broken_time: 20.0,
+            door_repair_time: 5.0,
+            hull_repair_amount: 5,
+            hull_repair_cooldown: 30.0,
+            graze_chance: 0.1,
+            manned_crit_bonus: 0.05,
+        }
+    }
+}
+
+impl BalanceConfig {
+    pub fn shield_charge_rate(&self, layers: usize) -> f32 {
+        let rates = &self.shield_charge_rates;
+        rates
+            .get(layers)
+            .or(rates.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn oxygen_fill_rate(&self, power: usize) -> f32 {
+        let rates = &self.oxygen_fill_rates;
+        rates
+            .get(power)
+            .or(rates.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Percent chance for a ship with `engine_power` to dodge a projectile.
+    pub fn dodge_chance(&self, engine_power: usize) -> usize {
+        // TODO Change this to also check piloting and manning crew skills
+        engine_power * self.dodge_per_engine_power
+    }
+
+    /// Percent chance for a ship with `powered_weapons` to shoot down a boarding shuttle.
+    pub fn point_defense_chance(&self, powered_weapons: usize) -> usize {
+        (powered_weapons * self.point_defense_per_weapon).min(100)
+    }
+
+    /// Fingerprint of these numbers, so a client can tell whether the server plays by the same
+    /// ones it knows about.
+    pub fn hash(&self) -> u64 {
+        // Over the debug output, which covers every field and prints floats exactly
+        fnv1a(FNV1A_BASIS, format!("{self:?}").as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_past_the_table_fall_back() {
+        let balance = BalanceConfig::default();
+        assert_eq!(balance.shield_charge_rate(9), 0.75);
+        assert_eq!(balance.oxygen_fill_rate(9), balance.oxygen_fill_rate(0));
+        let tweaked = BalanceConfig {
+            fire_crew_damage: 9.0,
+            ..default()
+        };
+        assert_ne!(tweaked.hash(), balance.hash());
+    }
+}
@@ -1,15 +1,128 @@
-use bevy::{ecs::entity::MapEntities, prelude::*};
+use std::collections::BTreeMap;
+
+use bevy::{ecs::entity::MapEntities, math::FloatOrd, prelude::*};
 use serde::{Deserialize, Serialize};
 
-#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq, Eq)]
-pub struct WeaponDamage(pub usize);
+use crate::{
+    nav::Cell,
+    ship::SHIPS,
+    util::{intersect, Aabb},
+    weapon::DamageSpec,
+};
+
+#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq)]
+pub struct WeaponDamage(pub DamageSpec);
 
 #[derive(Component, Serialize, Deserialize, Default, Deref, DerefMut, Debug, Clone, Copy)]
 pub struct Progress(pub f32);
 
+/// Traversal progress at which a projectile reaches the target's shields.
+pub const SHIELD_PROGRESS: f32 = 0.85;
+
 #[derive(Component, Serialize, Deserialize, Default, Clone, Copy)]
 pub struct NeedsDodgeTest;
 
+/// A boarding shuttle. It flies like a projectile, aimed at a [`RoomTarget`], but carries crew
+/// instead of doing damage.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shuttle {
+    /// How many crew are aboard.
+    pub crew: usize,
+}
+
+/// What a projectile looks like in flight. Replicated so clients can pick a sprite without digging
+/// up the weapon that fired it, which hazards don't have.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProjectileKind {
+    /// A short bolt of light.
+    #[default]
+    Laser,
+    Missile,
+    /// Slow, tumbling payloads that do their work after landing.
+    Bomb,
+    Asteroid,
+}
+
+/// Direction a bullet approaches its target from, once it's crossed over to the target's side of
+/// the screen. Picked by the server so that shield impacts line up with what clients draw.
+#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq)]
+pub struct Incidence(pub Dir2);
+
+/// Sent when a projectile is absorbed by a ship's shields, so clients can draw a flare where it
+/// hit.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ShieldImpact {
+    pub ship: Entity,
+    /// The [`Incidence`] of the absorbed projectile.
+    pub incidence: Dir2,
+}
+
+impl MapEntities for ShieldImpact {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.ship = entity_mapper.map_entity(self.ship);
+    }
+}
+
+/// How squarely a projectile struck the hull. Rolled by the server when it lands.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitQuality {
+    #[default]
+    Normal,
+    /// Double system damage, and the cell hit always catches fire.
+    Crit,
+    /// Glancing blow for half damage, rounded up.
+    Graze,
+}
+
+impl HitQuality {
+    /// Turn a `roll` in `[0, 1)` into a hit quality. The bottom `crit_chance` of the range crits,
+    /// the next `graze_chance` grazes.
+    pub fn from_roll(roll: f32, crit_chance: f32, graze_chance: f32) -> Self {
+        if roll < crit_chance {
+            Self::Crit
+        } else if roll < crit_chance + graze_chance {
+            Self::Graze
+        } else {
+            Self::Normal
+        }
+    }
+
+    pub fn apply(self, damage: DamageSpec) -> DamageSpec {
+        match self {
+            Self::Normal => damage,
+            Self::Crit => DamageSpec {
+                system: damage.system * 2,
+                fire_chance: 1.0,
+                ..damage
+            },
+            Self::Graze => DamageSpec {
+                hull: damage.hull.div_ceil(2),
+                system: damage.system.div_ceil(2),
+                crew: damage.crew / 2.0,
+                ..damage
+            },
+        }
+    }
+}
+
+/// Sent when a projectile hits a ship's hull, so clients can call out crits and grazes.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct HullImpact {
+    pub ship: Entity,
+    pub room: usize,
+    pub quality: HitQuality,
+}
+
+impl MapEntities for HullImpact {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.ship = entity_mapper.map_entity(self.ship);
+    }
+}
+
+/// How much of its path a bullet covers per second.
+#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq)]
+pub struct TraversalSpeed(pub f32);
+
 #[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RoomTarget {
     /// The ship this projectile should hit if not dodged. We point to the
@@ -25,7 +138,7 @@ impl MapEntities for RoomTarget {
     }
 }
 
-#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct BeamTarget {
     pub ship: Entity,
     pub start: Vec2,
@@ -38,6 +151,69 @@ impl MapEntities for BeamTarget {
     }
 }
 
+/// The cells a beam sweeps across on its target, keyed by how far along the swath (0 to 1) it
+/// reaches each one. Each entry carries the cell's room, but only for the first cell hit in that
+/// room, since a beam damages a room once no matter how many of its cells it crosses. The server
+/// keeps one of these on each beam and pops entries as the beam progresses; clients compute the
+/// same thing to preview where a beam will land.
+#[derive(Component, Debug, Deref, DerefMut, Clone, PartialEq)]
+pub struct BeamHits(BTreeMap<FloatOrd, (Cell, Option<usize>)>);
+
+impl BeamHits {
+    pub fn compute(ship_type: usize, beam_len: f32, target: &BeamTarget) -> Self {
+        let ship = &SHIPS[ship_type];
+        let dir = *target.dir * beam_len;
+        // find an intersection `t` for each cell, sort them, map each one to a room and then filter duplicate rooms
+        let beam_impact_time = |aabb: Aabb| {
+            // Transform aabb into beam space, meaning scale and translate the aabb such that the
+            // beam moves from `(0, 0)` to `(1, 1)`.
+            let aabb = (aabb - target.start).scale_about_origin(1.0 / dir);
+            intersect(0.0..=1.0, aabb.x_range())
+                .and_then(|x| intersect(0.0..=1.0, aabb.y_range()).map(|y| (x, y)))
+                .and_then(move |(x, y)| intersect(x, y))
+                .map(|x| *x.start())
+        };
+        let mut hits = ship
+            .cells()
+            .map(|x| (ship.cell_aabb(x), x))
+            .filter_map(|(aabb, x)| beam_impact_time(aabb).map(|t| (t, x)))
+            .collect::<Vec<_>>();
+        hits.sort_by_key(|(t, _)| FloatOrd(*t));
+        let mut result = BTreeMap::new();
+        for (t, cell) in hits {
+            let room = ship.cell_room(cell);
+            // if we already hit this room, None, else Some(room)
+            let room = result
+                .values()
+                .all(|&(_, x)| x != Some(room))
+                .then_some(room);
+            result.insert(FloatOrd(t), (cell, room));
+        }
+        Self(result)
+    }
+
+    /// Remaining hits as `(t, cell, room)`, in the order the beam reaches them.
+    pub fn entries(&self) -> Vec<(f32, Cell, Option<usize>)> {
+        self.iter()
+            .map(|(&FloatOrd(t), &(cell, room))| (t, cell, room))
+            .collect()
+    }
+
+    pub fn from_entries(entries: impl IntoIterator<Item = (f32, Cell, Option<usize>)>) -> Self {
+        Self(
+            entries
+                .into_iter()
+                .map(|(t, cell, room)| (FloatOrd(t), (cell, room)))
+                .collect(),
+        )
+    }
+
+    /// The rooms the beam damages, in the order it reaches them.
+    pub fn rooms(&self) -> impl Iterator<Item = usize> + '_ {
+        self.values().filter_map(|&(_, room)| room)
+    }
+}
+
 #[derive(Component, Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct FiredFrom {
     pub ship: Entity,
@@ -49,3 +225,62 @@ impl MapEntities for FiredFrom {
         self.ship = entity_mapper.map_entity(self.ship);
     }
 }
+
+/// The ship a [`Shuttle`] was launched from. Not a [`FiredFrom`], since shuttles don't come out of
+/// a weapon slot and shouldn't be mistaken for that weapon's shots.
+#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaunchedFrom(pub Entity);
+
+impl MapEntities for LaunchedFrom {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0 = entity_mapper.map_entity(self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn target(start: Vec2, dir: Dir2) -> BeamTarget {
+        BeamTarget {
+            ship: Entity::PLACEHOLDER,
+            start,
+            dir,
+        }
+    }
+
+    #[test]
+    fn beam_hits_start_cell_first() {
+        let ship = &SHIPS[0];
+        let start = ship.cell_positions[0];
+        let hits = BeamHits::compute(0, 200.0, &target(start, Dir2::X));
+        let entries = hits.entries();
+        assert_eq!(entries[0], (0.0, Cell(0), Some(ship.cell_room(Cell(0)))));
+        assert!(entries.windows(2).all(|x| x[0].0 <= x[1].0));
+        // Each room is only damaged once
+        let rooms = hits.rooms().collect::<Vec<_>>();
+        assert_eq!(rooms.len(), rooms.iter().collect::<HashSet<_>>().len());
+        assert_eq!(BeamHits::from_entries(entries), hits);
+    }
+
+    #[test]
+    fn crits_and_grazes() {
+        assert_eq!(HitQuality::from_roll(0.05, 0.1, 0.2), HitQuality::Crit);
+        assert_eq!(HitQuality::from_roll(0.25, 0.1, 0.2), HitQuality::Graze);
+        assert_eq!(HitQuality::from_roll(0.3, 0.1, 0.2), HitQuality::Normal);
+        let damage = DamageSpec::standard(3);
+        let crit = HitQuality::Crit.apply(damage);
+        assert_eq!((crit.hull, crit.system, crit.fire_chance), (3, 6, 1.0));
+        let graze = HitQuality::Graze.apply(damage);
+        assert_eq!((graze.hull, graze.system), (2, 2));
+    }
+
+    #[test]
+    fn beam_pointing_away_misses() {
+        let far_away = Vec2::new(10_000.0, 0.0);
+        let hits = BeamHits::compute(0, 200.0, &target(far_away, Dir2::X));
+        assert!(hits.is_empty());
+    }
+}
@@ -1,11 +1,57 @@
 use bevy::{ecs::entity::MapEntities, prelude::*};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use strum::EnumIter;
 
 use crate::{
     bullets::{BeamTarget, RoomTarget},
     ship::SystemId,
 };
 
+/// A command a player gives one of their ships. Commands go over the wire wrapped in a
+/// [`ShipCommand`] naming the ship, and the server checks the sender is allowed to give it orders.
+pub trait CommandEvent: Event + Clone + Serialize + DeserializeOwned {
+    /// Map any entities the command refers to, other than the ship it's addressed to.
+    fn map_targets<M: EntityMapper>(&mut self, _entity_mapper: &mut M) {}
+
+    fn for_ship(self, ship: Entity) -> ShipCommand<Self> {
+        ShipCommand {
+            ship,
+            command: self,
+        }
+    }
+}
+
+/// `command`, addressed to `ship`.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct ShipCommand<C> {
+    pub ship: Entity,
+    pub command: C,
+}
+
+impl<C: CommandEvent> MapEntities for ShipCommand<C> {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.ship = entity_mapper.map_entity(self.ship);
+        self.command.map_targets(entity_mapper);
+    }
+}
+
+impl CommandEvent for AdjustPower {}
+impl CommandEvent for ReroutePower {}
+impl CommandEvent for QueuePower {}
+impl CommandEvent for WeaponPower {}
+impl CommandEvent for MoveWeapon {}
+impl CommandEvent for InstallWeapon {}
+impl CommandEvent for StoreWeapon {}
+impl CommandEvent for SetCrewGoal {}
+impl CommandEvent for SetAutofire {}
+impl CommandEvent for SetMissileFloor {}
+impl CommandEvent for SetDoorsOpen {}
+impl CommandEvent for CrewStations {}
+impl CommandEvent for RepairHull {}
+impl CommandEvent for SetRepairPriority {}
+impl CommandEvent for SetDoorAutomation {}
+impl CommandEvent for SetDepowerOrder {}
+
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct AdjustPower {
     /// Whether to request power from or return power to the reactor.
@@ -32,6 +78,36 @@ impl AdjustPower {
     }
 }
 
+/// Move power out of one system and into another in a single step. The server pulls just enough
+/// power out of `from` to give `to` one more increment, the same increment an [`AdjustPower`]
+/// request would. If `to` can't take it even then, nothing changes.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ReroutePower {
+    pub from: SystemId,
+    pub to: SystemId,
+}
+
+impl ReroutePower {
+    /// Reroute into `to` from the usual donor: oxygen, since it takes the longest to matter, or
+    /// engines when oxygen is the one that needs it.
+    pub fn to(to: SystemId) -> Self {
+        let from = match to {
+            SystemId::Oxygen => SystemId::Engines,
+            _ => SystemId::Oxygen,
+        };
+        Self { from, to }
+    }
+}
+
+/// Ask for one more increment of power to `system` as soon as the reactor can spare it, rather than
+/// right now. Requests are served first come, first served, whenever power frees up. Sending it
+/// again with `queued: false` cancels the request.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct QueuePower {
+    pub system: SystemId,
+    pub queued: bool,
+}
+
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct WeaponPower {
     /// Whether to request power from or return power to the reactor.
@@ -46,8 +122,8 @@ pub struct SetProjectileWeaponTarget {
     pub target: Option<RoomTarget>,
 }
 
-impl MapEntities for SetProjectileWeaponTarget {
-    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+impl CommandEvent for SetProjectileWeaponTarget {
+    fn map_targets<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
         if let Some(target) = &mut self.target {
             target.map_entities(entity_mapper);
         }
@@ -60,8 +136,25 @@ pub struct SetBeamWeaponTarget {
     pub target: Option<BeamTarget>,
 }
 
-impl MapEntities for SetBeamWeaponTarget {
-    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+impl CommandEvent for SetBeamWeaponTarget {
+    fn map_targets<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let Some(target) = &mut self.target {
+            target.map_entities(entity_mapper);
+        }
+    }
+}
+
+/// Aim a group of weapons at one room with a single click. Only projectile weapons can take a room
+/// target, so beams in the group are left alone unless the target is being cleared.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct SetGroupTarget {
+    /// Weapon slots in the group.
+    pub weapons: Vec<usize>,
+    pub target: Option<RoomTarget>,
+}
+
+impl CommandEvent for SetGroupTarget {
+    fn map_targets<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
         if let Some(target) = &mut self.target {
             target.map_entities(entity_mapper);
         }
@@ -74,25 +167,153 @@ pub struct MoveWeapon {
     pub target_index: usize,
 }
 
+/// Mount the weapon in cargo slot `cargo_index` in weapon slot `slot`. Whatever was mounted there
+/// gets stowed in cargo in its place.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct InstallWeapon {
+    pub cargo_index: usize,
+    pub slot: usize,
+}
+
+/// Unmount the weapon in `weapon_index` and stow it in cargo.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct StoreWeapon {
+    pub weapon_index: usize,
+}
+
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct SetCrewGoal {
     pub crew: usize,
     pub room: usize,
 }
 
+/// Load crew onto a boarding shuttle and send it at an enemy room. They stay aboard the enemy ship
+/// once they land.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct LaunchShuttle {
+    pub crew: Vec<usize>,
+    pub target: RoomTarget,
+}
+
+impl CommandEvent for LaunchShuttle {
+    fn map_targets<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.target.map_entities(entity_mapper);
+    }
+}
+
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct SetAutofire(pub bool);
 
+/// Stop autofire launching missiles once the supply is down to this many, keeping them back for
+/// aiming by hand.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SetMissileFloor(pub usize);
+
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum SetDoorsOpen {
-    Single { door: usize, open: bool },
-    All { open: bool },
+    Single {
+        door: usize,
+        open: bool,
+    },
+    All {
+        open: bool,
+    },
+    /// Open every door that doesn't lead into a room our crew are standing in, and close the ones
+    /// that do. Empty rooms vent while the crew keep their air.
+    VentUnoccupied,
+    /// Open the interior doors along the shortest route between rooms `from` and `to`.
+    OpenPath {
+        from: usize,
+        to: usize,
+    },
+    /// Close the airlocks, leaving interior doors alone.
+    CloseExterior,
+}
+
+/// Spend one of the hull repairs the ship's augments allow. See
+/// [`AugmentId::HullRepairKit`](crate::augment::AugmentId::HullRepairKit).
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct RepairHull;
+
+/// Damaged systems crew should go and fix, most important first. Crew always repair the room
+/// they're standing in; this decides where they head next when they finish a job. Systems left off
+/// the list only get fixed by crew who are sent there.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct SetRepairPriority(pub Vec<SystemId>);
+
+/// What door control does by itself when a room catches fire or is breached. It only steps in
+/// while door control is working, and only the moment the trouble starts, so doors can be opened
+/// again by hand afterwards.
+#[derive(Serialize, Deserialize, EnumIter, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorAutomation {
+    /// Doors only move when told to.
+    #[default]
+    Off,
+    /// Shut every door into the room, so the fire can't spread and the breach only drains the one
+    /// room.
+    Seal,
+    /// Like [`Seal`](Self::Seal), but burning rooms nobody's standing in get their airlocks opened
+    /// instead, so the fire runs out of air.
+    SealAndVent,
+}
+
+impl DoorAutomation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Seal => "Seal",
+            Self::SealAndVent => "Seal and vent fires",
+        }
+    }
+}
+
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SetDoorAutomation(pub DoorAutomation);
+
+/// Which weapon gives up its power first when the weapons system loses power it can't keep, from
+/// damage or from power being taken off the system as a whole.
+#[derive(Serialize, Deserialize, EnumIter, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepowerOrder {
+    /// The powered weapon in the last slot.
+    #[default]
+    LastSlot,
+    /// The powered weapon furthest from being charged, so anything about to fire gets to. Ties go
+    /// to the later slot.
+    LeastCharged,
+}
+
+impl DepowerOrder {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LastSlot => "Last slot",
+            Self::LeastCharged => "Least charged",
+        }
+    }
+}
+
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SetDepowerOrder(pub DepowerOrder);
+
+/// Sent back to a client when the server refuses one of its commands, so the player finds out why
+/// nothing happened. `reason` is meant to be shown as-is.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct CommandRejected {
+    pub reason: String,
+}
+
+/// Sent once a second to whoever's aboard a ship nobody has touched in a while, counting down the
+/// seconds until it forfeits.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct IdleWarning {
+    pub forfeit_in: u32,
 }
 
 #[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum CrewStations {
     Save,
     Return,
+    /// Have crew head back to their saved stations on their own whenever they run out of work.
+    AutoReturn(bool),
 }
 
 #[derive(Reflect, Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
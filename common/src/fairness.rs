@@ -0,0 +1,41 @@
+//! Dodge rolls decide a lot of fights, so the server makes them checkable after the fact. Every
+//! match gets a fresh [`DodgeSeed`]. The server publishes a [`DodgeCommitment`] to it when the match
+//! is set up, and reveals the seed itself in the [`MatchSummary`](crate::journal::MatchSummary)
+//! once the match is over. A player can then check the seed against the commitment and replay the
+//! rolls in the match journal. Seed a `ChaCha8Rng` with it and take `gen_range(0..100)` once per
+//! roll, in journal order. Since the commitment came first, the server can't have picked rolls as
+//! it went. Everything else left to chance (crits, where shots land, fires, hazards, point defense)
+//! comes from stream 1 of the same seed, but none of it is journaled, so only the dodge rolls can
+//! be checked this way.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub type DodgeSeed = [u8; 32];
+
+/// Hash of the current match's [`DodgeSeed`], published before any shots are fired.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DodgeCommitment(pub [u8; 32]);
+
+impl DodgeCommitment {
+    pub fn new(seed: &DodgeSeed) -> Self {
+        Self(*blake3::hash(seed).as_bytes())
+    }
+
+    pub fn matches(&self, seed: &DodgeSeed) -> bool {
+        *self == Self::new(seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_only_matches_its_seed() {
+        let seed = [7; 32];
+        let commitment = DodgeCommitment::new(&seed);
+        assert!(commitment.matches(&seed));
+        assert!(!commitment.matches(&[8; 32]));
+    }
+}
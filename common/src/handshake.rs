@@ -0,0 +1,229 @@
+//! Connection handshake. Netcode lets a client attach a fixed-size block of user data to its
+//! connect token, and the server can read it back as soon as the client connects. We use that to
+//! tell the server which version of the protocol a client speaks, so a mismatched client gets
+//! turned away at the door instead of silently failing to deserialize replication messages later.
+//!
+//! There are two halves to that. [`PROTOCOL_VERSION`] is bumped by hand whenever the shape of
+//! something that goes over the wire changes. [`ProtocolHash`] is worked out from which types get
+//! registered with replicon and in what order, so forgetting to bump the version after adding or
+//! reordering a registration still gets caught.
+
+use bevy::prelude::*;
+
+use crate::util::{fnv1a, FNV1A_BASIS};
+
+/// Size of the user data block netcode attaches to each connection.
+pub const USER_DATA_BYTES: usize = 256;
+
+/// Bump this whenever a change to replicated components, events or their registration order would
+/// break clients built against an older version.
+pub const PROTOCOL_VERSION: u32 = 57;
+
+/// Fingerprint of everything [`crate::protocol_plugin`] registers: the kind and name of each
+/// replicated component, resource and event, in order. Client and server only get along if theirs
+/// match.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolHash(pub u64);
+
+impl Default for ProtocolHash {
+    fn default() -> Self {
+        Self(FNV1A_BASIS)
+    }
+}
+
+impl ProtocolHash {
+    /// Fold a registration of `name` as `kind` (`"component"`, `"client event"`...) into the hash.
+    /// Names are spelled out by hand rather than taken from [`std::any::type_name`], which is free
+    /// to differ between compiler versions.
+    pub fn record(&mut self, kind: &str, name: &str) {
+        for part in [kind.as_bytes(), b":", name.as_bytes(), b";"] {
+            self.0 = fnv1a(self.0, part);
+        }
+    }
+}
+
+/// Identifies a player across connections. Unlike the netcode client ID, which only has to be
+/// unique among connected clients, this should stay the same every time a player connects so the
+/// server can hand them back their ship after a restart. Worked out from the player's
+/// [`PlayerKey`], never taken from the client directly.
+pub type PlayerId = u64;
+
+/// A secret only the player holds. Clients send this instead of their [`PlayerId`], so playing
+/// under someone's ID (and with it, their rating) means knowing their key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PlayerKey(pub [u8; 32]);
+
+impl PlayerKey {
+    /// Stretch any secret, say a passphrase, into a key.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self(*blake3::hash(secret).as_bytes())
+    }
+
+    /// The ID this key proves ownership of.
+    pub fn player_id(&self) -> PlayerId {
+        let hash = blake3::derive_key("ftl player id", &self.0);
+        u64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+}
+
+impl std::fmt::Debug for PlayerKey {
+    // Keys end up in handshakes, which get logged
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PlayerKey(player {})", self.player_id())
+    }
+}
+
+/// Handed out by a matchmaking service to a player it sent to a server, so the server can hold a
+/// seat for them until they arrive.
+pub type ReservationToken = u64;
+
+/// How a client wants to take part in the match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    /// Gets a ship of their own.
+    #[default]
+    Captain,
+    /// Shares control of a captain's ship. The captain has the final say over power when the two
+    /// of them disagree.
+    Copilot,
+}
+
+/// Everything a client tells the server about itself when connecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    pub version: u32,
+    /// Filled in from the app's [`ProtocolHash`] when connecting.
+    pub protocol_hash: ProtocolHash,
+    pub key: PlayerKey,
+    pub role: Role,
+    pub reservation: Option<ReservationToken>,
+}
+
+impl Handshake {
+    pub fn new(key: PlayerKey) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            protocol_hash: ProtocolHash(0),
+            key,
+            role: Role::Captain,
+            reservation: None,
+        }
+    }
+
+    pub fn with_role(self, role: Role) -> Self {
+        Self { role, ..self }
+    }
+
+    pub fn with_reservation(self, reservation: ReservationToken) -> Self {
+        Self {
+            reservation: Some(reservation),
+            ..self
+        }
+    }
+
+    pub fn with_protocol_hash(self, protocol_hash: ProtocolHash) -> Self {
+        Self {
+            protocol_hash,
+            ..self
+        }
+    }
+
+    pub fn to_user_data(&self) -> [u8; USER_DATA_BYTES] {
+        let mut user_data = [0; USER_DATA_BYTES];
+        user_data[0..4].copy_from_slice(&self.version.to_le_bytes());
+        user_data[12] = match self.role {
+            Role::Captain => 0,
+            Role::Copilot => 1,
+        };
+        user_data[13..21].copy_from_slice(&self.protocol_hash.0.to_le_bytes());
+        if let Some(reservation) = self.reservation {
+            user_data[21] = 1;
+            user_data[22..30].copy_from_slice(&reservation.to_le_bytes());
+        }
+        user_data[30..62].copy_from_slice(&self.key.0);
+        user_data
+    }
+
+    pub fn from_user_data(user_data: &[u8; USER_DATA_BYTES]) -> Self {
+        Self {
+            version: u32::from_le_bytes(user_data[0..4].try_into().unwrap()),
+            protocol_hash: ProtocolHash(u64::from_le_bytes(user_data[13..21].try_into().unwrap())),
+            key: PlayerKey(user_data[30..62].try_into().unwrap()),
+            role: match user_data[12] {
+                1 => Role::Copilot,
+                _ => Role::Captain,
+            },
+            reservation: (user_data[21] == 1)
+                .then(|| u64::from_le_bytes(user_data[22..30].try_into().unwrap())),
+        }
+    }
+
+    /// Why the server should turn away a client that sent this handshake, if it should.
+    /// `protocol_hash` is the server's own.
+    pub fn mismatch(&self, protocol_hash: ProtocolHash) -> Option<String> {
+        if self.version != PROTOCOL_VERSION {
+            Some(format!(
+                "version mismatch: client speaks protocol {}, server speaks {PROTOCOL_VERSION}",
+                self.version
+            ))
+        } else if self.protocol_hash != protocol_hash {
+            Some(format!(
+                "version mismatch: client registers protocol {:016x}, server registers {:016x}",
+                self.protocol_hash.0, protocol_hash.0
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let hash = ProtocolHash(0x1234_5678_9ABC_DEF0);
+        let handshake = Handshake {
+            version: 6,
+            protocol_hash: hash,
+            key: PlayerKey::from_secret(b"hunter2"),
+            role: Role::Copilot,
+            reservation: Some(42),
+        };
+        let decoded = Handshake::from_user_data(&handshake.to_user_data());
+        assert_eq!(decoded, handshake);
+        assert!(decoded.mismatch(hash).is_some());
+        let current = Handshake::new(PlayerKey([3; 32])).with_protocol_hash(hash);
+        assert_eq!(current.mismatch(hash), None);
+        assert!(current.mismatch(ProtocolHash::default()).is_some());
+    }
+
+    #[test]
+    fn hash_depends_on_registration_order() {
+        let mut a = ProtocolHash::default();
+        a.record("component", "u32");
+        a.record("client event", "Role");
+        let mut b = ProtocolHash::default();
+        b.record("client event", "Role");
+        b.record("component", "u32");
+        assert_ne!(a, b);
+        let mut c = ProtocolHash::default();
+        c.record("component", "u32");
+        c.record("client event", "Role");
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn keys_decide_ids() {
+        let key = PlayerKey::from_secret(b"hunter2");
+        assert_eq!(
+            key.player_id(),
+            PlayerKey::from_secret(b"hunter2").player_id()
+        );
+        assert_ne!(
+            key.player_id(),
+            PlayerKey::from_secret(b"hunter3").player_id()
+        );
+    }
+}
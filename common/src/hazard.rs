@@ -0,0 +1,52 @@
+//! Optional match modifiers, picked in the lobby. The server runs whichever are enabled and
+//! replicates [`HazardState`] so clients can show what the environment is currently doing.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+
+#[derive(Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hazard {
+    /// Every so often a surge knocks half of each ship's reactor offline for a few seconds.
+    IonStorm,
+    /// Stray asteroids periodically hit every ship.
+    AsteroidField,
+    /// Flares every so often set a random cell of every ship on fire.
+    SolarFlare,
+}
+
+impl Hazard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hazard::IonStorm => "Ion storm",
+            Hazard::AsteroidField => "Asteroid field",
+            Hazard::SolarFlare => "Solar flare",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Hazard::IonStorm => "Periodic surges take half of each reactor offline.",
+            Hazard::AsteroidField => "Asteroids strike every ship at random.",
+            Hazard::SolarFlare => "Flares start fires aboard every ship at random.",
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HazardState {
+    /// Hazards in play this match.
+    pub enabled: Vec<Hazard>,
+    /// Whether an ion storm surge is currently holding down reactor output.
+    pub ion_surge: bool,
+}
+
+impl HazardState {
+    pub fn is_enabled(&self, hazard: Hazard) -> bool {
+        self.enabled.contains(&hazard)
+    }
+}
+
+/// Sent during the ready phase to turn a hazard on or off for the coming match.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ToggleHazard(pub Hazard);
@@ -15,21 +15,25 @@
 //! ion.
 //! - **Crew locations**: Exact locations for all crew in all ships. Only available with a slug
 //! crewmember.
+//! - **Doors**: Whether each door is open, broken or being broken down.
 //!
 //! These chunks are given for enemy ships at the following sensor levels:
 //! - **No/disabled sensors**: Basic information for all ships, crew vision for own crew and weapon
-//! charge and systems for own ship.
+//! charge, systems and doors for own ship.
 //! - **Level 1 sensors**: Interior intel for own ship.
-//! - **Level 2 sensors**: Interior intel for enemy ships.
+//! - **Level 2 sensors**: Interior intel and doors for enemy ships.
 //! - **Level 3 sensors**: Weapon charge for enemy ships.
 //! - **Level 3 sensors + manned**: systems for enemy ships.
 //! - **Slug crewmember**: crew locations for enemy ships.
 
 use crate::{
+    alarm::Alarm,
+    augment::AugmentId,
+    events::{DepowerOrder, DoorAutomation},
     nav::{Cell, NavLocation},
-    ship::SystemId,
+    ship::{SubsystemId, SystemId},
     weapon::{WeaponId, WeaponTarget},
-    Crew, DoorState,
+    Crew, CrewColor, DoorState,
 };
 use bevy::{ecs::entity::MapEntities, prelude::*};
 use serde::{Deserialize, Serialize};
@@ -42,7 +46,10 @@ pub struct ShipIntel {
     pub crew_vision: Entity,
     pub interior: Entity,
     pub weapon_charge: Entity,
+    /// Rough weapon charge, for enemies with good enough sensors to see it.
+    pub enemy_weapon_charge: Entity,
     pub systems: Entity,
+    pub doors: Entity,
 }
 
 impl MapEntities for ShipIntel {
@@ -50,7 +57,9 @@ impl MapEntities for ShipIntel {
         self.crew_vision = entity_mapper.map_entity(self.crew_vision);
         self.interior = entity_mapper.map_entity(self.interior);
         self.weapon_charge = entity_mapper.map_entity(self.weapon_charge);
+        self.enemy_weapon_charge = entity_mapper.map_entity(self.enemy_weapon_charge);
         self.systems = entity_mapper.map_entity(self.systems);
+        self.doors = entity_mapper.map_entity(self.doors);
     }
 }
 
@@ -73,7 +82,24 @@ pub struct BasicIntel {
     /// Basic weapons status if the system is installed.
     pub weapons: Option<WeaponsIntel>,
     pub oxygen: Option<SystemDamageIntel>,
-    pub doors: Vec<DoorState>,
+    #[serde(default)]
+    pub clone_bay: Option<SystemDamageIntel>,
+    /// Damage intel for each installed subsystem. Where they are comes from the ship's layout.
+    #[serde(default)]
+    pub subsystems: HashMap<SubsystemId, SystemDamageIntel>,
+}
+
+impl BasicIntel {
+    /// Damage state of `system`, or `None` if it isn't installed.
+    pub fn system_damage(&self, system: SystemId) -> Option<SystemDamageIntel> {
+        match system {
+            SystemId::Shields => self.shields.map(|x| x.damage),
+            SystemId::Weapons => self.weapons.as_ref().map(|x| x.damage),
+            SystemId::Engines => self.engines,
+            SystemId::Oxygen => self.oxygen,
+            SystemId::CloneBay => self.clone_bay,
+        }
+    }
 }
 
 /// Includes everything own crew are able to see. Drones (including hacking drones when powered) and
@@ -83,6 +109,7 @@ pub struct CrewVisionIntel;
 
 #[derive(Component, Serialize, Deserialize, Debug)]
 pub struct InteriorIntel {
+    /// Oxygen here is the average over the room's cells.
     pub rooms: Vec<RoomIntel>,
     pub cells: Vec<CellIntel>,
 }
@@ -92,6 +119,9 @@ pub struct InteriorIntel {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RoomIntel {
     pub crew: Vec<CrewIntel>,
+    /// Boarders from other ships.
+    #[serde(default)]
+    pub intruders: Vec<CrewIntel>,
     pub oxygen: f32,
 }
 
@@ -101,6 +131,7 @@ pub struct CrewIntel {
     pub name: String,
     pub nav_status: CrewNavIntel,
     pub health: f32,
+    pub color: CrewColor,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -114,8 +145,14 @@ pub enum CrewNavIntel {
 pub struct CellIntel {
     pub on_fire: bool,
     pub breached: bool,
+    pub oxygen: f32,
 }
 
+/// State of each door on a ship, in the order of
+/// [`ShipType::doors`](crate::ship::ShipType::doors).
+#[derive(Component, Serialize, Deserialize, Deref, Debug)]
+pub struct DoorsIntel(pub Vec<DoorState>);
+
 #[derive(Component, Serialize, Deserialize)]
 pub struct WeaponChargeIntel {
     /// Stores the current charge level for each weapon. Max charge level should be read from
@@ -123,6 +160,37 @@ pub struct WeaponChargeIntel {
     pub levels: Vec<f32>,
 }
 
+/// How many pips [`EnemyWeaponChargeIntel`] splits a weapon's charge into.
+pub const CHARGE_PIPS: u8 = 4;
+
+/// Weapon charge as enemy sensors see it: whole pips rather than the exact charge, so nobody can
+/// time a volley to the tick off someone else's weapons.
+#[derive(Component, Serialize, Deserialize)]
+pub struct EnemyWeaponChargeIntel {
+    /// Pips out of [`CHARGE_PIPS`] charged for each weapon. Only a fully charged weapon fills
+    /// them all.
+    pub pips: Vec<u8>,
+}
+
+impl EnemyWeaponChargeIntel {
+    /// Round `levels` down to pips, given each weapon's full charge time.
+    pub fn new(levels: impl IntoIterator<Item = (f32, f32)>) -> Self {
+        let pips = levels
+            .into_iter()
+            .map(|(level, charge_time)| {
+                let fraction = (level / charge_time).clamp(0.0, 1.0);
+                (fraction * CHARGE_PIPS as f32).floor() as u8
+            })
+            .collect();
+        Self { pips }
+    }
+
+    /// Charge of weapon `index` in `[0, 1]`, to the nearest pip.
+    pub fn fraction(&self, index: usize) -> Option<f32> {
+        Some(*self.pips.get(index)? as f32 / CHARGE_PIPS as f32)
+    }
+}
+
 /// This component identifies a player's ship and contains intel only they can see like targeting,
 /// FTL drive status and inventory.
 #[derive(Component, Serialize, Deserialize)]
@@ -131,11 +199,56 @@ pub struct SelfIntel {
     pub ship: Entity,
     pub max_power: usize,
     pub free_power: usize,
+    /// Missiles in stock, including the reserved ones.
     pub missiles: usize,
+    /// Missiles set aside for the next shot of each powered missile weapon.
+    pub reserved_missiles: usize,
+    /// Autofire stops launching missiles once the supply is down to this many.
+    pub missile_floor: usize,
+    pub scrap: usize,
     pub weapon_targets: Vec<Option<WeaponTarget>>,
+    /// Weapons on board that aren't mounted.
+    pub cargo: Vec<WeaponId>,
     pub crew: Vec<Crew>,
     pub autofire: bool,
     pub oxygen: f32,
+    /// Roughly how many seconds until the ship's air is too thin to breathe at the rate it's
+    /// going, or `None` if it isn't running out.
+    pub suffocation_in: Option<f32>,
+    pub augments: Vec<AugmentId>,
+    /// Alarms currently raised on this ship.
+    pub alarms: Vec<Alarm>,
+    /// [`BalanceConfig::hash`](crate::balance::BalanceConfig::hash) of the numbers the server is
+    /// simulating with.
+    pub balance_hash: u64,
+    /// Dead crew waiting on the clone bay, in the order they'll come back.
+    pub cloning: Vec<CloningIntel>,
+    /// Systems waiting on reactor power, in the order they'll get it.
+    pub power_queue: Vec<SystemId>,
+    /// Whether crew go back to their stations by themselves, see
+    /// [`CrewStations::AutoReturn`](crate::events::CrewStations::AutoReturn).
+    pub auto_return: bool,
+    /// Hull repairs left this match.
+    pub hull_repairs: usize,
+    /// Seconds until the next hull repair can be made.
+    pub hull_repair_cooldown: f32,
+    /// Which damaged systems crew go and fix first, see
+    /// [`SetRepairPriority`](crate::events::SetRepairPriority).
+    pub repair_priority: Vec<SystemId>,
+    /// What door control does about fires and breaches by itself.
+    pub door_automation: DoorAutomation,
+    /// Which weapon gives up its power first when the weapons system can't keep it all.
+    pub depower_order: DepowerOrder,
+    /// The weapon that would lose its power if the weapons system took damage right now, if any.
+    pub depower_next: Option<usize>,
+}
+
+/// A dead crew member the clone bay is bringing back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CloningIntel {
+    pub name: String,
+    /// How far along the clone is, in `[0, 1]`. Only the first one in line makes progress.
+    pub progress: f32,
 }
 
 impl MapEntities for SelfIntel {
@@ -152,6 +265,11 @@ impl MapEntities for SelfIntel {
 #[derive(Component, Serialize, Deserialize, Deref)]
 pub struct SystemsIntel(pub HashMap<SystemId, SystemIntel>);
 
+/// Full intel about a ship's subsystems. Lives alongside [`SystemsIntel`] and is visible whenever
+/// it is. `current_power` is always zero.
+#[derive(Component, Serialize, Deserialize, Deref)]
+pub struct SubsystemsIntel(pub HashMap<SubsystemId, SystemIntel>);
+
 #[derive(Serialize, Deserialize)]
 pub struct SystemIntel {
     pub upgrade_level: usize,
@@ -184,6 +302,10 @@ pub struct ShieldIntel {
     pub charge: f32,
     /// Basic system damage level.
     pub damage: SystemDamageIntel,
+    /// Points of super shield left, outside the normal layers. See
+    /// [`ShipType::super_shield`](crate::ship::ShipType::super_shield).
+    #[serde(default)]
+    pub super_shield: usize,
 }
 
 /// Basic weapons system status, composed of individual weapon status and basic system damage state.
@@ -199,3 +321,17 @@ pub struct WeaponIntel {
     pub weapon: WeaponId,
     pub powered: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enemy_charge_rounds_down_to_pips() {
+        let intel =
+            EnemyWeaponChargeIntel::new([(0.0, 10.0), (2.4, 10.0), (9.9, 10.0), (10.0, 10.0)]);
+        assert_eq!(intel.pips, [0, 0, 3, 4]);
+        assert_eq!(intel.fraction(2), Some(0.75));
+        assert_eq!(intel.fraction(4), None);
+    }
+}
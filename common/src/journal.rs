@@ -0,0 +1,239 @@
+//! Things that happen during a match. The server records these in a per-match journal, passes them
+//! on to clients as they happen for their combat log, and at the end of a match sends every client a
+//! [`MatchSummary`] built from them.
+
+use std::time::Duration;
+
+use bevy::{ecs::entity::MapEntities, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bullets::HitQuality, fairness::DodgeSeed, lobby::FinalResult, ship::SystemId, weapon::WeaponId,
+};
+
+/// A significant gameplay event. Ships are referred to by entity; the journal file starts with a
+/// record mapping those entities to players.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub enum MatchEvent {
+    ShotFired {
+        ship: Entity,
+        weapon_index: usize,
+        weapon: WeaponId,
+    },
+    /// The target of a projectile rolled to dodge it. Dodged if `roll < chance`.
+    DodgeRoll {
+        attacker: Entity,
+        target: Entity,
+        weapon_index: usize,
+        chance: usize,
+        roll: usize,
+    },
+    /// A projectile missed because the target dodged it.
+    Dodged {
+        attacker: Entity,
+        target: Entity,
+    },
+    /// A projectile was stopped by the target's shields, taking down a layer.
+    ShieldHit {
+        attacker: Entity,
+        target: Entity,
+    },
+    /// A shot got through and damaged the target's hull.
+    HullHit {
+        attacker: Entity,
+        target: Entity,
+        room: usize,
+        damage: usize,
+        /// Always [`HitQuality::Normal`] for beams.
+        #[serde(default)]
+        quality: HitQuality,
+        /// Set on every hull hit from a beam after its first. A beam damages each room it sweeps,
+        /// but it's still only one shot.
+        #[serde(default)]
+        repeat: bool,
+    },
+    SystemDamaged {
+        attacker: Entity,
+        target: Entity,
+        system: SystemId,
+        damage: usize,
+        /// Whether this hit took the system from working to fully destroyed.
+        destroyed: bool,
+    },
+    /// A hit set `room` on fire.
+    FireStarted {
+        attacker: Entity,
+        target: Entity,
+        room: usize,
+    },
+    /// `killer` is the ship whose shot did it, or `None` for environmental deaths (suffocation).
+    CrewDied {
+        ship: Entity,
+        name: String,
+        killer: Option<Entity>,
+    },
+    /// A boarding shuttle was shot down before it landed, taking its crew with it.
+    ShuttleShotDown {
+        ship: Entity,
+        target: Entity,
+    },
+    /// The clone bay brought a dead crew member back.
+    CrewCloned {
+        ship: Entity,
+        name: String,
+    },
+    /// A ship patched up its own hull.
+    HullRepaired {
+        ship: Entity,
+        amount: usize,
+    },
+    ShipDestroyed {
+        ship: Entity,
+    },
+    /// Every ship left went down on the same tick, and the [`Tiebreak`](crate::rules::Tiebreak)
+    /// kept this one in the fight.
+    WonTiebreak {
+        ship: Entity,
+    },
+}
+
+impl MatchEvent {
+    /// The ship whose interior this event gives away, if any. Players only hear about these if
+    /// their sensors can see inside that ship.
+    pub fn interior_of(&self) -> Option<Entity> {
+        match self {
+            MatchEvent::SystemDamaged { target, .. } | MatchEvent::FireStarted { target, .. } => {
+                Some(*target)
+            }
+            MatchEvent::CrewDied { ship, .. } | MatchEvent::CrewCloned { ship, .. } => Some(*ship),
+            _ => None,
+        }
+    }
+}
+
+impl MapEntities for MatchEvent {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        match self {
+            MatchEvent::ShotFired { ship, .. }
+            | MatchEvent::CrewCloned { ship, .. }
+            | MatchEvent::HullRepaired { ship, .. }
+            | MatchEvent::ShipDestroyed { ship }
+            | MatchEvent::WonTiebreak { ship } => {
+                *ship = entity_mapper.map_entity(*ship);
+            }
+            MatchEvent::DodgeRoll {
+                attacker, target, ..
+            }
+            | MatchEvent::Dodged { attacker, target }
+            | MatchEvent::ShieldHit { attacker, target }
+            | MatchEvent::HullHit {
+                attacker, target, ..
+            }
+            | MatchEvent::SystemDamaged {
+                attacker, target, ..
+            }
+            | MatchEvent::FireStarted {
+                attacker, target, ..
+            } => {
+                *attacker = entity_mapper.map_entity(*attacker);
+                *target = entity_mapper.map_entity(*target);
+            }
+            MatchEvent::ShuttleShotDown { ship, target } => {
+                *ship = entity_mapper.map_entity(*ship);
+                *target = entity_mapper.map_entity(*target);
+            }
+            MatchEvent::CrewDied { ship, killer, .. } => {
+                *ship = entity_mapper.map_entity(*ship);
+                if let Some(killer) = killer {
+                    *killer = entity_mapper.map_entity(*killer);
+                }
+            }
+        }
+    }
+}
+
+/// A [`MatchEvent`] as it's passed on to clients for their combat log.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct CombatLogEntry {
+    /// Seconds since the match started, same as in the journal.
+    pub time: f32,
+    pub event: MatchEvent,
+}
+
+impl MapEntities for CombatLogEntry {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.event.map_entities(entity_mapper);
+    }
+}
+
+/// Sent to every client when a match ends.
+#[derive(Event, Resource, Serialize, Deserialize, Debug, Clone)]
+pub struct MatchSummary {
+    pub duration: Duration,
+    pub result: FinalResult,
+    pub ships: Vec<ShipSummary>,
+    /// The seed every dodge roll this match came from, revealed now that it's over.
+    pub dodge_seed: DodgeSeed,
+}
+
+impl MapEntities for MatchSummary {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for ship in &mut self.ships {
+            ship.ship = entity_mapper.map_entity(ship.ship);
+        }
+    }
+}
+
+/// Totals for a single ship over the course of a match.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShipSummary {
+    pub ship: Entity,
+    pub shots_fired: usize,
+    /// Shots that reached the target's hull.
+    pub hits: usize,
+    pub hull_damage_dealt: usize,
+    pub system_damage_dealt: usize,
+    pub crew_lost: usize,
+    pub destroyed: bool,
+}
+
+impl ShipSummary {
+    pub fn new(ship: Entity) -> Self {
+        Self {
+            ship,
+            shots_fired: 0,
+            hits: 0,
+            hull_damage_dealt: 0,
+            system_damage_dealt: 0,
+            crew_lost: 0,
+            destroyed: false,
+        }
+    }
+
+    /// Fold `event` into this ship's totals. Events that don't involve this ship are ignored.
+    pub fn record(&mut self, event: &MatchEvent) {
+        match *event {
+            MatchEvent::ShotFired { ship, .. } if ship == self.ship => {
+                self.shots_fired += 1;
+            }
+            MatchEvent::HullHit {
+                attacker, damage, ..
+            } if attacker == self.ship => {
+                self.hits += 1;
+                self.hull_damage_dealt += damage;
+            }
+            MatchEvent::SystemDamaged {
+                attacker, damage, ..
+            } if attacker == self.ship => {
+                self.system_damage_dealt += damage;
+            }
+            MatchEvent::CrewDied { ship, .. } if ship == self.ship => {
+                self.crew_lost += 1;
+            }
+            MatchEvent::ShipDestroyed { ship } if ship == self.ship => {
+                self.destroyed = true;
+            }
+            _ => {}
+        }
+    }
+}
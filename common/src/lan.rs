@@ -0,0 +1,77 @@
+//! Finding servers on the local network without typing in an address. Servers that want to be found
+//! broadcast a [`LanBeacon`] to [`LAN_DISCOVERY_PORT`] every so often, and clients listen there.
+
+use crate::{handshake::PROTOCOL_VERSION, DEFAULT_PORT};
+
+/// Where servers broadcast their [`LanBeacon`]s.
+pub const LAN_DISCOVERY_PORT: u16 = DEFAULT_PORT + 1;
+
+/// Leads every beacon, so stray broadcasts from other programs on the port get ignored.
+const MAGIC: [u8; 4] = *b"FTLB";
+
+/// A server saying it's here. Clients take the sender's IP and connect to `port` on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LanBeacon {
+    /// The server's [`PROTOCOL_VERSION`], so clients can skip servers they can't talk to.
+    pub version: u32,
+    /// The port the server takes clients on, not the one the beacon came from.
+    pub port: u16,
+    /// Captains connected, copilots don't count.
+    pub players: u8,
+    /// Whether players are still in the lobby, as opposed to a match being underway.
+    pub in_lobby: bool,
+}
+
+impl LanBeacon {
+    pub fn new(port: u16, players: u8, in_lobby: bool) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            port,
+            players,
+            in_lobby,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.port.to_le_bytes());
+        bytes[10] = self.players;
+        bytes[11] = self.in_lobby as u8;
+        bytes
+    }
+
+    /// The beacon in `bytes`, or `None` if they aren't one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 12] = bytes.try_into().ok()?;
+        if bytes[0..4] != MAGIC {
+            return None;
+        }
+        Some(Self {
+            version: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            port: u16::from_le_bytes(bytes[8..10].try_into().unwrap()),
+            players: bytes[10],
+            in_lobby: bytes[11] != 0,
+        })
+    }
+
+    /// Whether a client built from this tree can join the server that sent this.
+    pub fn compatible(&self) -> bool {
+        self.version == PROTOCOL_VERSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beacons_survive_the_trip() {
+        let beacon = LanBeacon::new(DEFAULT_PORT, 1, true);
+        assert_eq!(LanBeacon::from_bytes(&beacon.to_bytes()), Some(beacon));
+        assert!(beacon.compatible());
+        assert_eq!(LanBeacon::from_bytes(b"hello, world"), None);
+        assert_eq!(LanBeacon::from_bytes(&beacon.to_bytes()[..8]), None);
+    }
+}
@@ -1,65 +1,216 @@
+pub mod alarm;
+pub mod augment;
+pub mod balance;
 pub mod bullets;
 pub mod events;
+pub mod fairness;
+pub mod handshake;
+pub mod hazard;
 pub mod intel;
+pub mod journal;
+pub mod lan;
 pub mod lobby;
+pub mod match_clock;
 pub mod nav;
+pub mod profile;
+pub mod queue;
+pub mod rules;
 pub mod ship;
+pub mod ship_check;
+pub mod stats;
+pub mod time_scale;
 pub mod util;
 pub mod weapon;
 
 mod replicate_resource;
 
-use bevy::prelude::*;
+use bevy::{ecs::entity::MapEntities, prelude::*};
 use bevy_replicon::prelude::*;
-use bullets::{BeamTarget, FiredFrom, NeedsDodgeTest, Progress, RoomTarget, WeaponDamage};
+use bullets::{
+    BeamTarget, FiredFrom, HullImpact, Incidence, LaunchedFrom, NeedsDodgeTest, Progress,
+    ProjectileKind, RoomTarget, ShieldImpact, Shuttle, TraversalSpeed, WeaponDamage,
+};
 use events::{
-    AdjustPower, CrewStations, MoveWeapon, SetAutofire, SetBeamWeaponTarget, SetCrewGoal,
-    SetDoorsOpen, SetProjectileWeaponTarget, WeaponPower,
+    AdjustPower, CommandEvent, CommandRejected, CrewStations, IdleWarning, InstallWeapon,
+    LaunchShuttle, MoveWeapon, QueuePower, RepairHull, ReroutePower, SetAutofire,
+    SetBeamWeaponTarget, SetCrewGoal, SetDepowerOrder, SetDoorAutomation, SetDoorsOpen,
+    SetGroupTarget, SetMissileFloor, SetProjectileWeaponTarget, SetRepairPriority, ShipCommand,
+    StoreWeapon, WeaponPower,
 };
+use fairness::DodgeCommitment;
+use handshake::ProtocolHash;
+use hazard::{HazardState, ToggleHazard};
 use intel::{
-    CrewIntel, CrewNavIntel, CrewVisionIntel, InteriorIntel, SelfIntel, ShipIntel, SystemsIntel,
-    WeaponChargeIntel,
+    CrewIntel, CrewNavIntel, CrewVisionIntel, DoorsIntel, EnemyWeaponChargeIntel, InteriorIntel,
+    SelfIntel, ShipIntel, SubsystemsIntel, SystemsIntel, WeaponChargeIntel,
 };
-use lobby::{PlayerReady, ReadyState};
+use journal::{CombatLogEntry, MatchSummary};
+use lobby::{ChooseTeam, CustomizeCrew, MatchEpoch, PlayerReady, ReadyState, RequestRematch, Team};
+use match_clock::MatchClock;
 use nav::{Cell, CrewNavStatus};
+use profile::{PlayerProfiles, RequestProfiles};
 use replicate_resource::ReplicateResExt;
-use serde::{Deserialize, Serialize};
-use ship::{Dead, Room};
+use rules::{LobbyHost, MatchRules, Round, SetMatchRules};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use ship::{Dead, Destroyed, Room, ShipPlacement};
+use stats::MatchStats;
+use strum::EnumIter;
+use time_scale::{RequestTimeScale, TimeScale};
 
+/// Netcode's protocol ID. This deliberately never changes: netcode quietly ignores clients with the
+/// wrong ID, so they'd just time out. Compatibility is checked in the [`handshake`] instead, where
+/// the server can tell what went wrong.
 pub const PROTOCOL_ID: u64 = 1;
 
+/// The UDP port servers listen on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 5000;
+
+/// Registers everything that goes over the wire, and records it all in a [`ProtocolHash`] resource
+/// for the handshake.
 pub fn protocol_plugin(app: &mut App) {
+    let mut protocol = Protocol {
+        app: &mut *app,
+        hash: default(),
+    };
     // Ready state communication
-    app.replicate_resource::<ReadyState>();
-    app.add_client_event::<PlayerReady>(ChannelKind::Ordered);
+    protocol.resource::<ReadyState>("ReadyState");
+    protocol.resource::<MatchEpoch>("MatchEpoch");
+    protocol.client_event::<PlayerReady>("PlayerReady", ChannelKind::Ordered);
+    protocol.resource::<MatchStats>("MatchStats");
+    protocol.client_event::<RequestRematch>("RequestRematch", ChannelKind::Ordered);
+    protocol.client_event::<ChooseTeam>("ChooseTeam", ChannelKind::Ordered);
+    protocol.client_event::<CustomizeCrew>("CustomizeCrew", ChannelKind::Ordered);
+    protocol.resource::<TimeScale>("TimeScale");
+    protocol.client_event::<RequestTimeScale>("RequestTimeScale", ChannelKind::Ordered);
+    protocol.resource::<HazardState>("HazardState");
+    protocol.client_event::<ToggleHazard>("ToggleHazard", ChannelKind::Ordered);
+    protocol.resource::<MatchRules>("MatchRules");
+    protocol.resource::<LobbyHost>("LobbyHost");
+    protocol.resource::<Round>("Round");
+    protocol.resource::<MatchClock>("MatchClock");
+    protocol.resource::<DodgeCommitment>("DodgeCommitment");
+    protocol.client_event::<SetMatchRules>("SetMatchRules", ChannelKind::Ordered);
+    protocol.client_event::<RequestProfiles>("RequestProfiles", ChannelKind::Ordered);
 
     // Make sure intel makes it all the way to clients
-    app.replicate_mapped::<SelfIntel>();
-    app.replicate_mapped::<ShipIntel>();
-    app.replicate::<CrewVisionIntel>();
-    app.replicate::<InteriorIntel>();
-    app.replicate::<WeaponChargeIntel>();
-    app.replicate::<SystemsIntel>();
+    protocol.mapped_component::<SelfIntel>("SelfIntel");
+    protocol.mapped_component::<ShipIntel>("ShipIntel");
+    protocol.component::<CrewVisionIntel>("CrewVisionIntel");
+    protocol.component::<InteriorIntel>("InteriorIntel");
+    protocol.component::<WeaponChargeIntel>("WeaponChargeIntel");
+    protocol.component::<EnemyWeaponChargeIntel>("EnemyWeaponChargeIntel");
+    protocol.component::<SystemsIntel>("SystemsIntel");
+    protocol.component::<SubsystemsIntel>("SubsystemsIntel");
+    protocol.component::<DoorsIntel>("DoorsIntel");
 
     // Miscellaneous
-    app.replicate::<Progress>();
-    app.replicate::<WeaponDamage>();
-    app.replicate::<NeedsDodgeTest>();
-    app.replicate_mapped::<RoomTarget>();
-    app.replicate_mapped::<BeamTarget>();
-    app.replicate_mapped::<FiredFrom>();
-    app.replicate::<Dead>();
+    protocol.component::<Progress>("Progress");
+    protocol.component::<TraversalSpeed>("TraversalSpeed");
+    protocol.component::<Incidence>("Incidence");
+    protocol.component::<WeaponDamage>("WeaponDamage");
+    protocol.component::<NeedsDodgeTest>("NeedsDodgeTest");
+    protocol.component::<Shuttle>("Shuttle");
+    protocol.component::<ProjectileKind>("ProjectileKind");
+    protocol.mapped_component::<RoomTarget>("RoomTarget");
+    protocol.mapped_component::<BeamTarget>("BeamTarget");
+    protocol.mapped_component::<FiredFrom>("FiredFrom");
+    protocol.mapped_component::<LaunchedFrom>("LaunchedFrom");
+    protocol.component::<Dead>("Dead");
+    protocol.component::<Destroyed>("Destroyed");
+    protocol.component::<Team>("Team");
+    protocol.component::<ShipPlacement>("ShipPlacement");
+    protocol.mapped_server_event::<MatchSummary>("MatchSummary", ChannelKind::Ordered);
+    protocol.mapped_server_event::<CombatLogEntry>("CombatLogEntry", ChannelKind::Ordered);
+    protocol.mapped_server_event::<ShieldImpact>("ShieldImpact", ChannelKind::Unordered);
+    protocol.mapped_server_event::<HullImpact>("HullImpact", ChannelKind::Unordered);
+    protocol.server_event::<CommandRejected>("CommandRejected", ChannelKind::Ordered);
+    protocol.server_event::<IdleWarning>("IdleWarning", ChannelKind::Ordered);
+    protocol.server_event::<PlayerProfiles>("PlayerProfiles", ChannelKind::Ordered);
 
     // Player inputs
-    app.add_client_event::<AdjustPower>(ChannelKind::Ordered);
-    app.add_client_event::<WeaponPower>(ChannelKind::Ordered);
-    app.add_mapped_client_event::<SetProjectileWeaponTarget>(ChannelKind::Ordered);
-    app.add_mapped_client_event::<SetBeamWeaponTarget>(ChannelKind::Ordered);
-    app.add_client_event::<MoveWeapon>(ChannelKind::Ordered);
-    app.add_client_event::<SetCrewGoal>(ChannelKind::Ordered);
-    app.add_client_event::<SetAutofire>(ChannelKind::Ordered);
-    app.add_client_event::<SetDoorsOpen>(ChannelKind::Ordered);
-    app.add_client_event::<CrewStations>(ChannelKind::Ordered);
+    protocol.command::<AdjustPower>("AdjustPower");
+    protocol.command::<WeaponPower>("WeaponPower");
+    protocol.command::<ReroutePower>("ReroutePower");
+    protocol.command::<QueuePower>("QueuePower");
+    protocol.command::<SetProjectileWeaponTarget>("SetProjectileWeaponTarget");
+    protocol.command::<SetBeamWeaponTarget>("SetBeamWeaponTarget");
+    protocol.command::<SetGroupTarget>("SetGroupTarget");
+    protocol.command::<MoveWeapon>("MoveWeapon");
+    protocol.command::<InstallWeapon>("InstallWeapon");
+    protocol.command::<StoreWeapon>("StoreWeapon");
+    protocol.command::<SetCrewGoal>("SetCrewGoal");
+    protocol.command::<SetAutofire>("SetAutofire");
+    protocol.command::<SetMissileFloor>("SetMissileFloor");
+    protocol.command::<SetDoorsOpen>("SetDoorsOpen");
+    protocol.command::<CrewStations>("CrewStations");
+    protocol.command::<LaunchShuttle>("LaunchShuttle");
+    protocol.command::<RepairHull>("RepairHull");
+    protocol.command::<SetRepairPriority>("SetRepairPriority");
+    protocol.command::<SetDoorAutomation>("SetDoorAutomation");
+    protocol.command::<SetDepowerOrder>("SetDepowerOrder");
+    let hash = protocol.hash;
+    app.insert_resource(hash);
+}
+
+/// Registers types with replicon, keeping track of each one in a [`ProtocolHash`] under the name it's
+/// given.
+struct Protocol<'a> {
+    app: &'a mut App,
+    hash: ProtocolHash,
+}
+
+impl Protocol<'_> {
+    fn resource<R: Resource + Serialize + DeserializeOwned + Clone>(&mut self, name: &str) {
+        self.hash.record("resource", name);
+        self.app.replicate_resource::<R>();
+    }
+
+    fn component<C: Component + Serialize + DeserializeOwned>(&mut self, name: &str) {
+        self.hash.record("component", name);
+        self.app.replicate::<C>();
+    }
+
+    fn mapped_component<C: Component + Serialize + DeserializeOwned + MapEntities>(
+        &mut self,
+        name: &str,
+    ) {
+        self.hash.record("mapped component", name);
+        self.app.replicate_mapped::<C>();
+    }
+
+    fn client_event<E: Event + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+        channel: ChannelKind,
+    ) {
+        self.hash.record("client event", name);
+        self.app.add_client_event::<E>(channel);
+    }
+
+    /// Player commands are always addressed to a ship and always ordered.
+    fn command<C: CommandEvent>(&mut self, name: &str) {
+        self.hash.record("command", name);
+        self.app
+            .add_mapped_client_event::<ShipCommand<C>>(ChannelKind::Ordered);
+    }
+
+    fn server_event<E: Event + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+        channel: ChannelKind,
+    ) {
+        self.hash.record("server event", name);
+        self.app.add_server_event::<E>(channel);
+    }
+
+    fn mapped_server_event<E: Event + Serialize + DeserializeOwned + MapEntities + Clone>(
+        &mut self,
+        name: &str,
+        channel: ChannelKind,
+    ) {
+        self.hash.record("mapped server event", name);
+        self.app.add_mapped_server_event::<E>(channel);
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
@@ -69,6 +220,10 @@ pub struct DoorState {
     /// timer gets set to some positive amount, and ticks downward every frame. If this value is
     /// zero, this door can't be operated normally.
     pub broken_timer: f32,
+    /// Boarders' progress in `[0, 1]` toward breaking this door down. Goes back to zero as soon as
+    /// nobody's working on it.
+    #[serde(default)]
+    pub break_progress: f32,
 }
 
 impl DoorState {
@@ -92,6 +247,9 @@ pub struct Crew {
     /// health was measured as a percentage of max health, a `[0, 1]` range would make more sense.
     pub task: CrewTask,
     pub station: Option<Cell>,
+    /// Picked by the player in the lobby, see [`CustomizeCrew`](lobby::CustomizeCrew).
+    #[serde(default)]
+    pub color: CrewColor,
 }
 
 impl Crew {
@@ -108,6 +266,47 @@ impl Crew {
                 CrewNavStatus::Navigating(nav) => CrewNavIntel::Navigating(nav.current_location),
             },
             health: self.health,
+            color: self.color,
+        }
+    }
+}
+
+/// Uniform colors crew can be told apart by.
+#[derive(Serialize, Deserialize, EnumIter, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrewColor {
+    #[default]
+    Standard,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl CrewColor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Standard => "Standard",
+            Self::Red => "Red",
+            Self::Orange => "Orange",
+            Self::Yellow => "Yellow",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+            Self::Purple => "Purple",
+        }
+    }
+
+    /// RGB to tint crew sprites and portraits with.
+    pub fn rgb(&self) -> [f32; 3] {
+        match self {
+            Self::Standard => [1.0, 1.0, 1.0],
+            Self::Red => [1.0, 0.35, 0.35],
+            Self::Orange => [1.0, 0.6, 0.25],
+            Self::Yellow => [1.0, 0.95, 0.35],
+            Self::Green => [0.45, 1.0, 0.45],
+            Self::Blue => [0.45, 0.65, 1.0],
+            Self::Purple => [0.8, 0.45, 1.0],
         }
     }
 }
@@ -122,11 +321,10 @@ impl Crew {
 pub enum CrewTask {
     Idle,
     RepairSystem,
-}
-
-// TODO Change this to also check piloting and manning crew skills
-pub fn compute_dodge_chance(engine_power: usize) -> usize {
-    engine_power * 5
+    /// Putting out a fire or patching a breach.
+    RepairHull,
+    /// Fighting boarders in the same room.
+    Fight,
 }
 
 pub struct Race {
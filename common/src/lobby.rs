@@ -1,12 +1,71 @@
 use std::{collections::HashSet, time::Duration};
 
-use bevy::{ecs::event::Event, prelude::Resource};
+use bevy::{
+    ecs::event::Event,
+    prelude::{Component, Deref, Resource},
+};
 use bevy_replicon::core::ClientId;
 use serde::{Deserialize, Serialize};
 
+use crate::{Crew, CrewColor};
+
+/// How many teams players can pick from in the lobby.
+pub const MAX_TEAMS: u8 = 4;
+
+/// Which side a ship fights for. Ships on the same team share intel and can't shoot each other.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Team(pub u8);
+
+/// Sent during the ready phase to move your ship to another team.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ChooseTeam(pub Team);
+
+/// Longest name a crew member can be given, in characters.
+pub const MAX_CREW_NAME: usize = 16;
+
+/// Sent during the ready phase to rename and recolor one of your crew. Anyone whose sensors can see
+/// them sees them this way too.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct CustomizeCrew {
+    /// Index into the ship's crew.
+    pub crew: usize,
+    pub name: String,
+    pub color: CrewColor,
+}
+
+impl CustomizeCrew {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("crew name is empty");
+        }
+        if name.chars().count() > MAX_CREW_NAME {
+            return Err("crew name is too long");
+        }
+        Ok(())
+    }
+
+    /// Give `crew` this name and color.
+    pub fn apply(&self, crew: &mut Crew) {
+        crew.name = self.name.trim().into();
+        crew.color = self.color;
+    }
+}
+
 #[derive(Event, Serialize, Deserialize, Default, Clone, Copy)]
 pub struct PlayerReady;
 
+/// Sent from the post-game screen. Once every player has asked for a rematch, the server resets and
+/// goes back to the ready phase.
+#[derive(Event, Serialize, Deserialize, Default, Clone, Copy)]
+pub struct RequestRematch;
+
+/// Bumped every time the server tears down a match and sets up a fresh one. Anything a client holds
+/// onto from the old match (selections, targeting) points at entities that are gone once this
+/// changes.
+#[derive(Resource, Serialize, Deserialize, Deref, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MatchEpoch(pub u32);
+
 #[derive(Resource, Serialize, Deserialize, Debug, Clone)]
 pub enum ReadyState {
     AwaitingClients { ready_clients: HashSet<ClientId> },
@@ -20,3 +79,131 @@ impl Default for ReadyState {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOutcome {
+    Ongoing,
+    Won(Team),
+    /// Every team is out.
+    Draw,
+}
+
+/// Work out how a match stands from each ship's team and whether it's dead. A team is only out once
+/// every one of its ships is dead.
+pub fn match_outcome(ships: impl IntoIterator<Item = (Team, bool)>) -> MatchOutcome {
+    let standing = ships
+        .into_iter()
+        .filter(|&(_, dead)| !dead)
+        .map(|(team, _)| team)
+        .collect::<HashSet<_>>();
+    let mut standing = standing.into_iter();
+    match (standing.next(), standing.next()) {
+        (None, _) => MatchOutcome::Draw,
+        (Some(team), None) => MatchOutcome::Won(team),
+        (Some(_), Some(_)) => MatchOutcome::Ongoing,
+    }
+}
+
+/// How a finished match came out. Decided once by the server and sent to everyone in the
+/// [`MatchSummary`](crate::journal::MatchSummary), so every client shows the same result.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalResult {
+    Won(Team),
+    /// Every team went down on the same tick, and [`Tiebreak::LastHit`](crate::rules::Tiebreak)
+    /// pulled this one back from the brink.
+    WonTiebreak(Team),
+    Draw,
+}
+
+impl FinalResult {
+    pub fn winner(&self) -> Option<Team> {
+        match *self {
+            FinalResult::Won(team) | FinalResult::WonTiebreak(team) => Some(team),
+            FinalResult::Draw => None,
+        }
+    }
+}
+
+/// For a tick where every team still standing would lose its last ship at once, the team whose
+/// dying ship landed the most recent hull hit. Each entry is a ship's team, whether it's going down
+/// this tick, and when it last hit an enemy's hull, later hits being larger. `None` if somebody
+/// makes it through the tick anyway, or if no one team's hit was the last.
+pub fn last_hit_winner(ships: impl IntoIterator<Item = (Team, bool, Option<u64>)>) -> Option<Team> {
+    let ships = ships.into_iter().collect::<Vec<_>>();
+    if match_outcome(ships.iter().map(|&(team, dying, _)| (team, dying))) != MatchOutcome::Draw {
+        return None;
+    }
+    let dying = ships.iter().filter(|&&(_, dying, _)| dying);
+    if dying
+        .clone()
+        .map(|&(team, ..)| team)
+        .collect::<HashSet<_>>()
+        .len()
+        < 2
+    {
+        return None;
+    }
+    let latest = dying.clone().filter_map(|&(_, _, hit)| hit).max()?;
+    let mut teams = dying
+        .filter(|&&(_, _, hit)| hit == Some(latest))
+        .map(|&(team, ..)| team)
+        .collect::<HashSet<_>>()
+        .into_iter();
+    match (teams.next(), teams.next()) {
+        (Some(team), None) => Some(team),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn team_survives_until_last_ship_dies() {
+        let (a, b) = (Team(0), Team(1));
+        let outcome = match_outcome([(a, true), (a, false), (b, false), (b, false)]);
+        assert_eq!(outcome, MatchOutcome::Ongoing);
+        let outcome = match_outcome([(a, true), (a, true), (b, true), (b, false)]);
+        assert_eq!(outcome, MatchOutcome::Won(b));
+        let outcome = match_outcome([(a, true), (b, true)]);
+        assert_eq!(outcome, MatchOutcome::Draw);
+    }
+
+    #[test]
+    fn crew_names_are_checked() {
+        let customize = |name: &str| CustomizeCrew {
+            crew: 0,
+            name: name.into(),
+            color: CrewColor::Red,
+        };
+        assert_eq!(customize(" Fish ").validate(), Ok(()));
+        assert!(customize("   ").validate().is_err());
+        assert!(customize(&"x".repeat(MAX_CREW_NAME + 1))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn last_hit_breaks_mutual_kills() {
+        let (a, b) = (Team(0), Team(1));
+        assert_eq!(
+            last_hit_winner([(a, true, Some(3)), (b, true, Some(7))]),
+            Some(b)
+        );
+        // Team b still has a ship standing, so there's nothing to break
+        let ships = [(a, true, Some(3)), (b, true, Some(2)), (b, false, Some(9))];
+        assert_eq!(last_hit_winner(ships), None);
+        // Nobody landed a hit, or both on the same one
+        assert_eq!(last_hit_winner([(a, true, None), (b, true, None)]), None);
+        assert_eq!(
+            last_hit_winner([(a, true, Some(4)), (b, true, Some(4))]),
+            None
+        );
+        // Only the last team left is going down
+        assert_eq!(
+            last_hit_winner([(a, true, Some(4)), (a, true, Some(6))]),
+            None
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! The optional match timer. When [`MatchRules::time_limit`](crate::rules::MatchRules) is set the
+//! server counts the match down and replicates [`MatchClock`] so both players can keep an eye on
+//! it. Once it runs out, sudden death settles the match one way or another.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+
+/// Longest time limit the host can set, in minutes.
+pub const MAX_TIME_LIMIT_MINUTES: u32 = 30;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeLimit {
+    pub minutes: u32,
+    pub sudden_death: SuddenDeath,
+}
+
+/// What happens when the clock runs out.
+#[derive(Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuddenDeath {
+    /// Every ship's hull starts burning down until someone's gives out.
+    HullBurn,
+    /// The team with the most hull left wins on the spot, then whoever dealt the most damage. Dead
+    /// even on both and it goes to a hull burn.
+    Score,
+}
+
+impl SuddenDeath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SuddenDeath::HullBurn => "Hull burn",
+            SuddenDeath::Score => "Score",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            SuddenDeath::HullBurn => "Every hull burns down until one gives out.",
+            SuddenDeath::Score => "Most hull left wins, then most damage dealt.",
+        }
+    }
+}
+
+/// Only updated once a second, which is all anyone needs to read off it.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchClock {
+    /// Whole seconds left, rounded up.
+    Remaining(u32),
+    SuddenDeath,
+}
+
+impl MatchClock {
+    /// `m:ss` left on the clock, or a warning once it's run out.
+    pub fn label(&self) -> String {
+        match *self {
+            MatchClock::Remaining(secs) => format!("{}:{:02}", secs / 60, secs % 60),
+            MatchClock::SuddenDeath => "SUDDEN DEATH".into(),
+        }
+    }
+}
@@ -1,14 +1,21 @@
 use crate::util::{round_to_usize, MoveToward};
-use bevy::math::Vec2;
+use bevy::math::{FloatOrd, Vec2};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     task::Poll,
 };
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Cell(pub usize);
 
+/// How far along a nav section crew walk per tick, with the section to themselves.
+const CREW_SPEED: f32 = 1.0 / 36.0;
+/// How much each other crew member on the same nav section slows a crew member down. Two on a
+/// section each walk at `1 / (1 + CONGESTION_SLOWDOWN)` of full speed.
+pub const CONGESTION_SLOWDOWN: f32 = 0.5;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum CrewNavStatus {
     At(Cell),
@@ -16,16 +23,28 @@ pub enum CrewNavStatus {
 }
 
 impl CrewNavStatus {
-    pub fn step(&mut self, nav_mesh: &NavMesh) {
+    /// Advance by one fixed update step. `traffic` is where everyone else walking the same ship is:
+    /// crew slow down on sections they share, and wait at the mouth of a corridor (a line section)
+    /// until whoever's partway down it comes out.
+    pub fn step(&mut self, nav_mesh: &NavMesh, traffic: &[NavLocation]) {
         // Only need to update if we're navigating
         let Self::Navigating(nav) = self else {
             return;
         };
-        if let Poll::Ready(destination) = nav.step(nav_mesh) {
+        if let Poll::Ready(destination) = nav.step(nav_mesh, traffic) {
             *self = Self::At(destination);
         }
     }
 
+    /// Where this crew is partway across a nav section, for other crew's `traffic`. `None` while
+    /// standing on a cell.
+    pub fn nav_location(&self) -> Option<NavLocation> {
+        match self {
+            CrewNavStatus::At(_) => None,
+            CrewNavStatus::Navigating(nav) => Some(nav.current_location),
+        }
+    }
+
     /// This crew's current goal cell. Only one crew can occupy a cell at a time. This crew may or
     /// may not be anywhere near this cell.
     pub fn occupied_cell(&self) -> Cell {
@@ -62,20 +81,33 @@ impl CrewNav {
     /// Advance by one fixed update step. This will move the crew along its current [`NavSection`]
     /// and update its progress along its [`Path`] if it's made it all the way across it. If the
     /// crew has reached the end of the path, this will return [`Poll::Ready`] with the [`Cell`]
-    /// that was reached, or [`Poll::Pending`] otherwise.
-    fn step(&mut self, nav_mesh: &NavMesh) -> Poll<Cell> {
+    /// that was reached, or [`Poll::Pending`] otherwise. See [`CrewNavStatus::step`] for `traffic`.
+    fn step(&mut self, nav_mesh: &NavMesh, traffic: &[NavLocation]) -> Poll<Cell> {
         let current_goal = self.path.next_waypoint().unwrap();
+        let section = self.nav_section();
+        let sharing = traffic.iter().filter(|x| x.section() == section).count();
+        if let NavLocation::Line(line, x) = self.current_location {
+            let entering = x != line.coords_of(current_goal) && (x == 0.0 || x == 1.0);
+            let occupied = traffic.iter().any(|other| {
+                matches!(*other, NavLocation::Line(l, x) if l == line && x > 0.0 && x < 1.0)
+            });
+            if entering && occupied {
+                // Corridors are one crew wide, so wait for it to clear
+                return Poll::Pending;
+            }
+        }
+        let speed = CREW_SPEED / (1.0 + CONGESTION_SLOWDOWN * sharing as f32);
         // Get target coordinate within nav section and step ourselves toward it
         // TODO move this logic to `NavLocation`
         let arrived = match &mut self.current_location {
             NavLocation::Line(line, x) => {
                 let target_x = line.coords_of(current_goal);
-                *x = x.move_toward(target_x, 1.0 / 36.0);
+                *x = x.move_toward(target_x, speed);
                 *x == target_x
             }
             NavLocation::Square(square, x) => {
                 let target_x = square.coords_of(current_goal);
-                *x = x.move_toward(target_x, 1.0 / 36.0);
+                *x = x.move_toward(target_x, speed);
                 *x == target_x
             }
         };
@@ -94,10 +126,7 @@ impl CrewNav {
     }
 
     fn nav_section(&self) -> NavSection {
-        match self.current_location {
-            NavLocation::Line(x, _) => NavSection::Line(x),
-            NavLocation::Square(x, _) => NavSection::Square(x),
-        }
+        self.current_location.section()
     }
 
     fn current_cell(&self) -> Cell {
@@ -108,11 +137,23 @@ impl CrewNav {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum CrewLocation {
     Cell(Cell),
     NavSection(NavSection),
 }
 
+impl CrewLocation {
+    /// The cells a crew at this location could path from. For a crew standing on a cell, that's
+    /// just the cell; for a crew partway across a nav section, it's every cell in the section.
+    pub fn cells(&self) -> Vec<Cell> {
+        match self {
+            CrewLocation::Cell(cell) => vec![*cell],
+            CrewLocation::NavSection(section) => section.cells().collect(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NavMesh {
     pub lines: Vec<LineSection>,
@@ -135,19 +176,15 @@ impl NavMesh {
     /// Find the shortest path from `start` to the goal represented in `pathing`, or `None` if the
     /// goal is unreachable from the given start position (or if the crew is already at the goal).
     pub fn find_path(&self, pathing: &GoalPathing, start: CrewLocation) -> Option<Path> {
-        let cost_to_goal = |mut cell: Cell| {
-            let mut cost = 0usize;
-            while let Some(next) = pathing.came_from.get(&cell) {
-                cell = *next;
-                cost += 1;
-            }
-            return cost;
-        };
         let start = match start {
             // If we start in a cell, our next waypoint is just `came_from[start]`
             CrewLocation::Cell(cell) => pathing.came_from.get(&cell).cloned(),
             // If we start in a nav section, our next waypoint is the cell in that section with the lowest cost-to-goal
-            CrewLocation::NavSection(section) => section.cells().min_by_key(|x| cost_to_goal(*x)),
+            CrewLocation::NavSection(section) => section
+                .cells()
+                .filter_map(|x| pathing.cost_to_goal(x).map(|cost| (x, cost)))
+                .min_by_key(|&(_, cost)| FloatOrd(cost))
+                .map(|(x, _)| x),
         };
         let Some(start) = start else {
             return None;
@@ -171,7 +208,7 @@ impl NavMesh {
 /// mesh by moving their coordinates along a nav section until they are at a shared cell, then
 /// moving to the same cell in a different nav section, repeating until they arrive at their
 /// destination.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum NavSection {
     Line(LineSection),
     Square(SquareSection),
@@ -222,7 +259,7 @@ impl From<[Cell; 4]> for NavSectionCells {
 
 /// A [`NavMesh`] section with one dimension. A crew member on this section should have a single
 /// coordinate in [0, 1]. 0 and 1 correspond to `self.0[0]` and `self.0[1]`, respectively.
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LineSection(pub [Cell; 2]);
 
 impl LineSection {
@@ -238,7 +275,7 @@ impl LineSection {
 }
 
 /// A [`NavMesh`] section with two dimensions.
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct SquareSection(pub [[Cell; 2]; 2]);
 
 impl SquareSection {
@@ -272,6 +309,15 @@ pub enum NavLocation {
     Square(SquareSection, Vec2),
 }
 
+impl NavLocation {
+    pub fn section(&self) -> NavSection {
+        match *self {
+            NavLocation::Line(x, _) => NavSection::Line(x),
+            NavLocation::Square(x, _) => NavSection::Square(x),
+        }
+    }
+}
+
 /// Responsible for generating a [`Path`]. The [`PathGraph`] is generated from a `Cells`, a
 /// [`NavMesh`], and an initial [`NavLocation`]. Stores a set of edges for each [`Cell`].
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -284,30 +330,160 @@ impl PathGraph {
     pub fn neighbors_of(&self, cell: Cell) -> impl Iterator<Item = Cell> + '_ {
         self.edges.get(&cell).unwrap().iter().cloned()
     }
+}
+
+/// Finds paths through a [`PathGraph`] using A*. Edge costs are the distance between cell positions
+/// plus an optional extra cost per cell from the dynamic cost layer, which lets us steer crew away
+/// from cells (congestion, hazards, etc.) without touching the underlying graph.
+///
+/// Ship layouts never change, so with no cost layer the first order toward a goal floods the whole
+/// graph and the result is kept for good, making every later order toward it a lookup. With a cost
+/// layer, recently computed [`GoalPathing`]s are cached by goal instead and thrown out whenever the
+/// cost layer changes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pathfinder {
+    graph: PathGraph,
+    positions: Vec<Vec2>,
+    costs: HashMap<Cell, f32>,
+    /// Full floods toward each goal over the bare graph.
+    #[serde(skip)]
+    routes: HashMap<Cell, GoalPathing>,
+    #[serde(skip)]
+    cache: VecDeque<GoalPathing>,
+}
+
+impl Pathfinder {
+    /// Crew orders tend to cluster around a handful of rooms, so this doesn't need to be large.
+    const CACHE_SIZE: usize = 8;
+
+    /// `positions` is indexed by cell and is used both for edge costs and as the A* heuristic.
+    pub fn new(graph: PathGraph, positions: Vec<Vec2>) -> Self {
+        Self {
+            graph,
+            positions,
+            costs: HashMap::new(),
+            routes: HashMap::new(),
+            cache: VecDeque::new(),
+        }
+    }
+
+    pub fn graph(&self) -> &PathGraph {
+        &self.graph
+    }
+
+    /// Set the extra cost of moving through `cell`. A cost of zero clears it. Changing a cost
+    /// invalidates every cached pathing, so avoid calling this with unchanged values every frame.
+    pub fn set_cell_cost(&mut self, cell: Cell, cost: f32) {
+        let previous = if cost > 0.0 {
+            self.costs.insert(cell, cost)
+        } else {
+            self.costs.remove(&cell)
+        };
+        if previous.unwrap_or(0.0) != cost {
+            self.cache.clear();
+        }
+    }
+
+    /// Get pathing info toward `goal` that's valid for a crew starting on any of `starts`. If
+    /// `starts` is empty, the search floods the entire graph.
+    pub fn pathing_to(&mut self, goal: Cell, starts: &[Cell]) -> &GoalPathing {
+        if self.costs.is_empty() {
+            if !self.routes.contains_key(&goal) {
+                let pathing = self.search(goal, &[]);
+                self.routes.insert(goal, pathing);
+            }
+            return &self.routes[&goal];
+        }
+        let cached = self.cache.iter().position(|x| {
+            x.goal == goal && (x.exhausted || starts.iter().all(|&s| x.cost.contains_key(&s)))
+        });
+        let pathing = match cached {
+            Some(i) => self.cache.remove(i).unwrap(),
+            None => self.search(goal, starts),
+        };
+        self.cache.push_front(pathing);
+        self.cache.truncate(Self::CACHE_SIZE);
+        &self.cache[0]
+    }
 
-    pub fn pathing_to(&self, goal: Cell) -> GoalPathing {
-        let mut frontier = VecDeque::new();
-        frontier.push_back(goal);
+    /// A* outward from `goal` until every cell in `starts` has been settled. Since the heuristic is
+    /// the straight-line distance to the nearest start and every edge costs at least the straight
+    /// line distance between its cells, it's consistent and settled costs are optimal.
+    fn search(&self, goal: Cell, starts: &[Cell]) -> GoalPathing {
+        let position = |Cell(x): Cell| self.positions[x];
+        let heuristic = |cell: Cell| {
+            starts
+                .iter()
+                .map(|&x| position(cell).distance(position(x)))
+                .min_by_key(|&x| FloatOrd(x))
+                .unwrap_or(0.0)
+        };
+        let mut remaining = starts.iter().copied().collect::<HashSet<_>>();
+        let mut frontier = BinaryHeap::new();
+        let mut best = HashMap::new();
         let mut came_from = HashMap::new();
-        while let Some(current) = frontier.pop_front() {
-            for next in self.neighbors_of(current) {
-                if next == goal {
+        let mut cost = HashMap::new();
+        best.insert(goal, 0.0);
+        frontier.push(Reverse((FloatOrd(heuristic(goal)), goal.0)));
+        let mut exhausted = true;
+        while let Some(Reverse((_, current))) = frontier.pop() {
+            let current = Cell(current);
+            if cost.contains_key(&current) {
+                continue;
+            }
+            let current_cost = best[&current];
+            cost.insert(current, current_cost);
+            remaining.remove(&current);
+            if !starts.is_empty() && remaining.is_empty() {
+                exhausted = false;
+                break;
+            }
+            for next in self.graph.neighbors_of(current) {
+                if cost.contains_key(&next) {
                     continue;
                 }
-                if !came_from.contains_key(&next) {
-                    frontier.push_back(next);
+                // We're searching backwards, so this edge is a crew stepping from `next` into `current`
+                let step = position(current).distance(position(next))
+                    + self.costs.get(&current).copied().unwrap_or(0.0);
+                let next_cost = current_cost + step;
+                if best.get(&next).is_none_or(|&x| next_cost < x) {
+                    best.insert(next, next_cost);
                     came_from.insert(next, current);
+                    frontier.push(Reverse((FloatOrd(next_cost + heuristic(next)), next.0)));
                 }
             }
         }
-        GoalPathing { came_from }
+        // Only settled cells are guaranteed to point along a shortest path
+        came_from.retain(|cell, _| cost.contains_key(cell));
+        GoalPathing {
+            goal,
+            came_from,
+            cost,
+            exhausted,
+        }
     }
 }
 
-/// A collection of info on how to get to [`Self::goal`] from anywhere in a ship.
+/// A collection of info on how to get to [`Self::goal`] from the cells explored while searching.
 #[derive(Debug, Clone)]
 pub struct GoalPathing {
+    goal: Cell,
     came_from: HashMap<Cell, Cell>,
+    /// Cost to reach the goal from each settled cell.
+    cost: HashMap<Cell, f32>,
+    /// Whether the search ran out of cells to explore. If so, any cell missing from `cost` is
+    /// unreachable rather than just unexplored.
+    exhausted: bool,
+}
+
+impl GoalPathing {
+    pub fn goal(&self) -> Cell {
+        self.goal
+    }
+
+    pub fn cost_to_goal(&self, cell: Cell) -> Option<f32> {
+        self.cost.get(&cell).copied()
+    }
 }
 
 /// Represents a sequence of waypoints to get from the current cell to a target cell.
@@ -391,7 +567,7 @@ mod tests {
                     break;
                 }
                 _ => {
-                    crew.step(&nav_mesh);
+                    crew.step(&nav_mesh, &[]);
                 }
             }
         }
@@ -416,28 +592,140 @@ mod tests {
                     break;
                 }
                 _ => {
-                    crew.step(&nav_mesh);
+                    crew.step(&nav_mesh, &[]);
                 }
             }
         }
     }
 
+    #[test]
+    fn crew_wait_for_corridors_to_clear() {
+        let nav_mesh = nav_mesh();
+        let line = LineSection([Cell(4), Cell(5)]);
+        let mut crew = CrewNavStatus::Navigating(CrewNav {
+            path: Path(vec![Cell(5)]),
+            current_location: NavLocation::Line(line, 0.0),
+        });
+        let coming_through = [NavLocation::Line(line, 0.5)];
+        crew.step(&nav_mesh, &coming_through);
+        assert!(matches!(crew.nav_location(), Some(NavLocation::Line(_, x)) if x == 0.0));
+
+        // Once they're in, company only slows them down
+        crew.step(&nav_mesh, &[]);
+        let waiting = [NavLocation::Line(line, 1.0)];
+        crew.step(&nav_mesh, &waiting);
+        let Some(NavLocation::Line(_, x)) = crew.nav_location() else {
+            panic!("Crew should still be in the corridor");
+        };
+        assert!((x - CREW_SPEED * (1.0 + 1.0 / (1.0 + CONGESTION_SLOWDOWN))).abs() < 1e-6);
+    }
+
+    fn pathfinder() -> Pathfinder {
+        let positions = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(4.0, 1.0),
+            Vec2::new(3.0, 1.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(11.0, 10.0),
+        ];
+        Pathfinder::new(path_graph(), positions)
+    }
+
+    fn path_from(
+        pathfinder: &mut Pathfinder,
+        nav_mesh: &NavMesh,
+        goal: Cell,
+        start: CrewLocation,
+    ) -> Option<Path> {
+        let pathing = pathfinder.pathing_to(goal, &start.cells());
+        nav_mesh.find_path(pathing, start)
+    }
+
     #[test]
     fn path_to() {
         let nav_mesh = nav_mesh();
-        let path_graph = path_graph();
-        let pathing = path_graph.pathing_to(Cell(6));
+        let mut pathfinder = pathfinder();
 
-        let path = nav_mesh.find_path(&pathing, CrewLocation::Cell(Cell(0)));
-        assert_eq!(path, Some(Path(vec![Cell(6), Cell(7), Cell(5), Cell(3)])));
-        let path = nav_mesh.find_path(
-            &pathing,
-            CrewLocation::NavSection(NavSection::Square(nav_mesh.squares[0])),
+        let path = path_from(
+            &mut pathfinder,
+            &nav_mesh,
+            Cell(6),
+            CrewLocation::Cell(Cell(0)),
         );
         assert_eq!(path, Some(Path(vec![Cell(6), Cell(7), Cell(5), Cell(3)])));
-        let path = nav_mesh.find_path(&pathing, CrewLocation::Cell(Cell(8)));
+        let square = CrewLocation::NavSection(NavSection::Square(nav_mesh.squares[0]));
+        let path = path_from(&mut pathfinder, &nav_mesh, Cell(6), square);
+        assert_eq!(path, Some(Path(vec![Cell(6), Cell(7), Cell(5), Cell(3)])));
+        let path = path_from(
+            &mut pathfinder,
+            &nav_mesh,
+            Cell(6),
+            CrewLocation::Cell(Cell(8)),
+        );
         assert_eq!(path, None);
-        let path = nav_mesh.find_path(&pathing, CrewLocation::Cell(Cell(6)));
+        let path = path_from(
+            &mut pathfinder,
+            &nav_mesh,
+            Cell(6),
+            CrewLocation::Cell(Cell(6)),
+        );
         assert_eq!(path, None);
     }
+
+    #[test]
+    fn cost_layer_reroutes() {
+        // A diamond: 0 on the left, 3 on the right, 1 above and 2 below
+        let graph = PathGraph {
+            edges: [
+                (Cell(0), [Cell(1), Cell(2)].into()),
+                (Cell(1), [Cell(0), Cell(3)].into()),
+                (Cell(2), [Cell(0), Cell(3)].into()),
+                (Cell(3), [Cell(1), Cell(2)].into()),
+            ]
+            .into(),
+        };
+        let positions = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(2.0, 0.0),
+        ];
+        let mut pathfinder = Pathfinder::new(graph, positions);
+        let nav_mesh = NavMesh {
+            lines: Vec::new(),
+            squares: Vec::new(),
+        };
+        let start = CrewLocation::Cell(Cell(0));
+
+        pathfinder.set_cell_cost(Cell(1), 10.0);
+        let path = path_from(&mut pathfinder, &nav_mesh, Cell(3), start);
+        assert_eq!(path, Some(Path(vec![Cell(3), Cell(2)])));
+        // Same goal, so this would be a cache hit if changing costs didn't invalidate the cache
+        pathfinder.set_cell_cost(Cell(1), 0.0);
+        pathfinder.set_cell_cost(Cell(2), 10.0);
+        let path = path_from(&mut pathfinder, &nav_mesh, Cell(3), start);
+        assert_eq!(path, Some(Path(vec![Cell(3), Cell(1)])));
+    }
+
+    #[test]
+    fn routes_match_search() {
+        let mut pathfinder = pathfinder();
+        for goal in (0..10).map(Cell) {
+            for start in (0..10).map(Cell) {
+                let expected = pathfinder.search(goal, &[start]).cost_to_goal(start);
+                let routed = pathfinder.pathing_to(goal, &[start]).cost_to_goal(start);
+                match (routed, expected) {
+                    (Some(a), Some(b)) => assert!((a - b).abs() < 1e-4, "{start:?} to {goal:?}"),
+                    _ => assert_eq!(routed, expected, "{start:?} to {goal:?}"),
+                }
+            }
+        }
+        assert_eq!(pathfinder.routes.len(), 10);
+        assert!(pathfinder.cache.is_empty());
+    }
 }
@@ -0,0 +1,106 @@
+//! Player profiles that outlive a single match: rating, win/loss record and recent matches. Servers
+//! only keep these if they're built with the `profiles` feature. Clients ask for the profiles of
+//! everyone connected with [`RequestProfiles`] and get back [`PlayerProfiles`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_replicon::core::ClientId;
+use serde::{Deserialize, Serialize};
+
+use crate::handshake::PlayerId;
+
+/// Rating a player starts out with.
+pub const STARTING_RATING: i32 = 1200;
+/// Most a single match can move a rating by.
+pub const RATING_K: f32 = 32.0;
+/// Most matches sent along with a profile, newest first.
+pub const RECENT_MATCHES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchResult {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl MatchResult {
+    /// Points towards the rating update: 1 for a win, half for a draw.
+    pub fn score(self) -> f32 {
+        match self {
+            MatchResult::Win => 1.0,
+            MatchResult::Loss => 0.0,
+            MatchResult::Draw => 0.5,
+        }
+    }
+}
+
+/// One finished match, from a single player's point of view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchRecord {
+    /// Unix time in seconds.
+    pub finished_at: u64,
+    pub result: MatchResult,
+    /// Average rating of the other teams' players going in.
+    pub opponent_rating: i32,
+    pub rating_change: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerProfile {
+    pub player_id: PlayerId,
+    pub rating: i32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Newest first, at most [`RECENT_MATCHES`] of them.
+    pub recent: Vec<MatchRecord>,
+}
+
+impl PlayerProfile {
+    /// A player who hasn't finished a match yet.
+    pub fn new(player_id: PlayerId) -> Self {
+        Self {
+            player_id,
+            rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            recent: Vec::new(),
+        }
+    }
+}
+
+/// Elo rating change for a player rated `rating` who got `result` against players averaging
+/// `opponent_rating`.
+pub fn rating_change(rating: i32, opponent_rating: i32, result: MatchResult) -> i32 {
+    let expected = 1.0 / (1.0 + 10f32.powf((opponent_rating - rating) as f32 / 400.0));
+    (RATING_K * (result.score() - expected)).round() as i32
+}
+
+/// Sent by clients that want to show everyone's profiles, usually on entering the lobby.
+#[derive(Event, Serialize, Deserialize, Default, Clone, Copy)]
+pub struct RequestProfiles;
+
+/// Profiles of everyone connected, sent back to whoever asked. Keyed by client, like
+/// [`MatchStats`](crate::stats::MatchStats).
+#[derive(Event, Resource, Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PlayerProfiles {
+    pub players: HashMap<ClientId, PlayerProfile>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsets_move_ratings_further() {
+        assert_eq!(rating_change(1200, 1200, MatchResult::Win), 16);
+        assert_eq!(rating_change(1200, 1200, MatchResult::Loss), -16);
+        assert_eq!(rating_change(1200, 1200, MatchResult::Draw), 0);
+        let upset = rating_change(1000, 1400, MatchResult::Win);
+        let expected = rating_change(1400, 1000, MatchResult::Win);
+        assert!(upset > 16 && expected < 16);
+        assert_eq!(upset + expected, RATING_K as i32);
+    }
+}
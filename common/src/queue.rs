@@ -0,0 +1,127 @@
+//! Wire format for the ranked queue. A client opens a TCP connection to the coordinator on
+//! [`QUEUE_PORT`], sends a [`QueueRequest`], and gets [`QueueReply`]s back until it's given a match
+//! to join. Closing the connection leaves the queue.
+
+use crate::{
+    handshake::{PlayerKey, ReservationToken, PROTOCOL_VERSION},
+    DEFAULT_PORT,
+};
+
+/// Where the coordinator takes queue requests, one past
+/// [`LAN_DISCOVERY_PORT`](crate::lan::LAN_DISCOVERY_PORT).
+pub const QUEUE_PORT: u16 = DEFAULT_PORT + 2;
+
+/// Leads every request, so stray connections from other programs get turned away.
+const MAGIC: [u8; 4] = *b"FTLQ";
+
+/// A player asking to be put in a ranked match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueRequest {
+    /// The client's [`PROTOCOL_VERSION`], so it's only ever sent to servers it can talk to.
+    pub version: u32,
+    /// Whose rating to match on. Has to be the same one the client connects to the match with.
+    pub key: PlayerKey,
+}
+
+impl QueueRequest {
+    pub const BYTES: usize = 40;
+
+    pub fn new(key: PlayerKey) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            key,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut bytes = [0; Self::BYTES];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..40].copy_from_slice(&self.key.0);
+        bytes
+    }
+
+    /// The request in `bytes`, or `None` if they aren't one.
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Option<Self> {
+        if bytes[0..4] != MAGIC {
+            return None;
+        }
+        Some(Self {
+            version: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            key: PlayerKey(bytes[8..40].try_into().unwrap()),
+        })
+    }
+}
+
+/// What the coordinator tells a queued player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueReply {
+    /// Still looking. Sent every so often so the client knows the coordinator hasn't gone away.
+    Waiting {
+        /// Players in the queue, us included.
+        players: u16,
+    },
+    /// A match is ready on `port` of the coordinator's host, and `reservation` is our seat in it.
+    /// The connection closes after this.
+    MatchFound {
+        port: u16,
+        reservation: ReservationToken,
+    },
+    /// The coordinator runs a different version. The connection closes after this.
+    WrongVersion,
+}
+
+impl QueueReply {
+    pub const BYTES: usize = 12;
+
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let (tag, value, reservation) = match *self {
+            QueueReply::Waiting { players } => (0, players, 0),
+            QueueReply::MatchFound { port, reservation } => (1, port, reservation),
+            QueueReply::WrongVersion => (2, 0, 0),
+        };
+        let mut bytes = [0; Self::BYTES];
+        bytes[0] = tag;
+        bytes[2..4].copy_from_slice(&value.to_le_bytes());
+        bytes[4..12].copy_from_slice(&reservation.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::BYTES]) -> Option<Self> {
+        let value = u16::from_le_bytes(bytes[2..4].try_into().unwrap());
+        let reservation = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        match bytes[0] {
+            0 => Some(QueueReply::Waiting { players: value }),
+            1 => Some(QueueReply::MatchFound {
+                port: value,
+                reservation,
+            }),
+            2 => Some(QueueReply::WrongVersion),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_survive_the_trip() {
+        let request = QueueRequest::new(PlayerKey([7; 32]));
+        assert_eq!(QueueRequest::from_bytes(&request.to_bytes()), Some(request));
+        let mut http = [0; QueueRequest::BYTES];
+        http[..16].copy_from_slice(b"GET / HTTP/1.1\r\n");
+        assert_eq!(QueueRequest::from_bytes(&http), None);
+        for reply in [
+            QueueReply::Waiting { players: 3 },
+            QueueReply::MatchFound {
+                port: 5010,
+                reservation: 0xDEAD_BEEF,
+            },
+            QueueReply::WrongVersion,
+        ] {
+            assert_eq!(QueueReply::from_bytes(&reply.to_bytes()), Some(reply));
+        }
+    }
+}
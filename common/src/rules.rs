@@ -0,0 +1,251 @@
+//! How ships are set up for a match. The host picks [`MatchRules`] in the lobby, the server builds
+//! every ship from them and replicates them so everyone can see what they're signing up for.
+
+use bevy::prelude::*;
+use bevy_replicon::core::ClientId;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    augment::{AugmentId, MAX_AUGMENTS},
+    match_clock::{TimeLimit, MAX_TIME_LIMIT_MINUTES},
+    weapon::{WeaponId, BURST_LASER_MK_I, HALBERD_BEAM, HEAVY_LASER, HERMES_MISSILES, PIKE_BEAM},
+};
+
+pub const MAX_REACTOR: usize = 25;
+/// Most upgrades shields, engines and weapons can get on top of their base level.
+pub const MAX_SYSTEM_UPGRADES: usize = 7;
+/// Weapons a ship can start with.
+pub const MAX_LOADOUT: usize = 4;
+pub const MAX_HULL: usize = 60;
+pub const MAX_ROUNDS: u32 = 9;
+
+/// How much each ship's sensors reveal about the others. See [`crate::intel`] for what each sensor
+/// level shows.
+#[derive(Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorRule {
+    /// Level 1 sensors: your own interior, nothing of the enemy's.
+    Normal,
+    /// No sensors at all, not even your own interior.
+    Blind,
+    /// Manned level 3 sensors for everyone.
+    Omniscient,
+}
+
+impl SensorRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SensorRule::Normal => "Normal",
+            SensorRule::Blind => "Blind",
+            SensorRule::Omniscient => "Omniscient",
+        }
+    }
+
+    /// Sensor level every ship gets, 0-4 with 4 being level 3 + manned.
+    pub fn level(&self) -> usize {
+        match self {
+            SensorRule::Normal => 1,
+            SensorRule::Blind => 0,
+            SensorRule::Omniscient => 4,
+        }
+    }
+}
+
+/// What happens when every team loses its last ship on the same tick.
+#[derive(Serialize, Deserialize, EnumIter, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// Nobody wins.
+    #[default]
+    Draw,
+    /// Whoever landed the most recent hull hit limps on with 1 hull and wins.
+    LastHit,
+}
+
+impl Tiebreak {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tiebreak::Draw => "Draw",
+            Tiebreak::LastHit => "Last hit",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Tiebreak::Draw => "Ships that go down together both lose.",
+            Tiebreak::LastHit => {
+                "Ships that go down together: whoever hit the other's hull last survives on 1 hull."
+            }
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchRules {
+    /// Reactor bars each ship starts with.
+    pub reactor: usize,
+    /// Upgrades given to shields, engines and weapons on top of their base level.
+    pub system_upgrades: usize,
+    pub scrap: usize,
+    /// Weapons every ship starts with, in slot order.
+    pub weapons: Vec<WeaponId>,
+    /// Augments every ship starts with. Some of them only do anything as the match starts, see
+    /// [`AugmentEffects`](crate::augment::AugmentEffects).
+    #[serde(default)]
+    pub augments: Vec<AugmentId>,
+    pub hull: usize,
+    pub sensors: SensorRule,
+    /// Matches in a series. Rematches count up through them, then the series starts over.
+    pub rounds: u32,
+    /// Match length before sudden death kicks in. `None` to fight it out however long it takes.
+    #[serde(default)]
+    pub time_limit: Option<TimeLimit>,
+    /// How to settle ships destroying each other at the same moment.
+    #[serde(default)]
+    pub tiebreak: Tiebreak,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        RulesPreset::VanillaDuel.rules()
+    }
+}
+
+impl MatchRules {
+    /// The preset these rules match exactly, if any.
+    pub fn preset(&self) -> Option<RulesPreset> {
+        RulesPreset::iter().find(|x| x.rules() == *self)
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.reactor == 0 || self.reactor > MAX_REACTOR {
+            return Err("reactor out of range");
+        }
+        if self.system_upgrades > MAX_SYSTEM_UPGRADES {
+            return Err("too many system upgrades");
+        }
+        if self.weapons.len() > MAX_LOADOUT {
+            return Err("too many weapons");
+        }
+        if self.augments.len() > MAX_AUGMENTS {
+            return Err("too many augments");
+        }
+        if self.hull == 0 || self.hull > MAX_HULL {
+            return Err("hull out of range");
+        }
+        if self.rounds == 0 || self.rounds > MAX_ROUNDS {
+            return Err("round count out of range");
+        }
+        if let Some(limit) = self.time_limit {
+            if limit.minutes == 0 || limit.minutes > MAX_TIME_LIMIT_MINUTES {
+                return Err("time limit out of range");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesPreset {
+    VanillaDuel,
+    GlassCannon,
+}
+
+impl RulesPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RulesPreset::VanillaDuel => "Vanilla duel",
+            RulesPreset::GlassCannon => "Glass cannon",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            RulesPreset::VanillaDuel => "The standard loadout, one match.",
+            RulesPreset::GlassCannon => "Big guns, a big reactor and a paper-thin hull. Best of 3.",
+        }
+    }
+
+    pub fn rules(&self) -> MatchRules {
+        match self {
+            RulesPreset::VanillaDuel => MatchRules {
+                reactor: 8,
+                system_upgrades: 3,
+                scrap: 0,
+                weapons: vec![HEAVY_LASER, BURST_LASER_MK_I, PIKE_BEAM],
+                augments: vec![],
+                hull: 30,
+                sensors: SensorRule::Normal,
+                rounds: 1,
+                time_limit: None,
+                tiebreak: Tiebreak::Draw,
+            },
+            RulesPreset::GlassCannon => MatchRules {
+                reactor: 14,
+                system_upgrades: 4,
+                scrap: 0,
+                weapons: vec![HEAVY_LASER, HERMES_MISSILES, BURST_LASER_MK_I, HALBERD_BEAM],
+                augments: vec![],
+                hull: 10,
+                sensors: SensorRule::Normal,
+                rounds: 3,
+                time_limit: None,
+                tiebreak: Tiebreak::Draw,
+            },
+        }
+    }
+}
+
+/// Sent by the host during the ready phase to change the rules for the coming match.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct SetMatchRules(pub MatchRules);
+
+/// The client allowed to change [`MatchRules`]: whoever has been connected the longest.
+#[derive(Resource, Serialize, Deserialize, Deref, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LobbyHost(pub Option<ClientId>);
+
+/// Which match of the series this is, starting from 1.
+#[derive(Resource, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Round(pub u32);
+
+impl Default for Round {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl Round {
+    /// The round after this one, starting the series over once `rules.rounds` have been played.
+    pub fn next(self, rules: &MatchRules) -> Self {
+        if self.0 >= rules.rounds {
+            Self(1)
+        } else {
+            Self(self.0 + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for preset in RulesPreset::iter() {
+            assert_eq!(preset.rules().validate(), Ok(()));
+            assert_eq!(preset.rules().preset(), Some(preset));
+        }
+        let rules = MatchRules {
+            weapons: vec![HEAVY_LASER; MAX_LOADOUT + 1],
+            ..default()
+        };
+        assert!(rules.validate().is_err());
+        assert_eq!(rules.preset(), None);
+    }
+
+    #[test]
+    fn rounds_wrap_around() {
+        let rules = RulesPreset::GlassCannon.rules();
+        assert_eq!(Round(1).next(&rules), Round(2));
+        assert_eq!(Round(3).next(&rules), Round(1));
+    }
+}
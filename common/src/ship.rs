@@ -1,9 +1,15 @@
 use crate::{
+    lobby::Team,
     nav::{Cell, LineSection, PathGraph, SquareSection},
     util::{Aabb, IterAvg},
 };
 use bevy::{math::Vec2, prelude::Component, reflect::Reflect};
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    f32::consts::{PI, TAU},
+    time::Duration,
+};
 use strum::EnumIter;
 
 #[derive(Reflect, Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -12,6 +18,8 @@ pub enum SystemId {
     Weapons,
     Engines,
     Oxygen,
+    /// Brings dead crew back after a while.
+    CloneBay,
 }
 
 impl std::fmt::Display for SystemId {
@@ -21,6 +29,94 @@ impl std::fmt::Display for SystemId {
             Self::Weapons => write!(f, "weapons"),
             Self::Engines => write!(f, "engines"),
             Self::Oxygen => write!(f, "oxygen"),
+            Self::CloneBay => write!(f, "clone bay"),
+        }
+    }
+}
+
+/// Systems that take up a room and can be damaged and repaired like any other, but run without
+/// reactor power. They never show up in the power controls.
+#[derive(Reflect, Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubsystemId {
+    /// Flies the ship. Without it there's no dodging, however much power the engines have.
+    Piloting,
+    /// Works the doors. Without it they stay however they were left.
+    Doors,
+    /// Shows what's going on inside our own ship, and the enemy's. Without it we only see what
+    /// our crew can.
+    Sensors,
+}
+
+impl std::fmt::Display for SubsystemId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Piloting => write!(f, "piloting"),
+            Self::Doors => write!(f, "doors"),
+            Self::Sensors => write!(f, "sensors"),
+        }
+    }
+}
+
+/// How far apart ships are taken to be when working out what a [`WeaponMount`] can reach. Ships
+/// always face each other head on, fore to fore.
+pub const ENGAGEMENT_RANGE: f32 = 300.0;
+
+/// A hardpoint on the hull. The weapon in each weapon slot sits in the mount with the same index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeaponMount {
+    /// Which way the mount points in radians, relative to the ship's facing. Positive is toward
+    /// port.
+    pub direction: f32,
+    /// How far the mount swings either side of `direction`, in radians.
+    pub half_arc: f32,
+}
+
+impl WeaponMount {
+    /// A mount that can swing round to aim anywhere.
+    pub const TURRET: Self = Self {
+        direction: 0.0,
+        half_arc: PI,
+    };
+
+    /// Whether this mount can aim at `target`, a point on an enemy ship in that ship's own
+    /// coordinates.
+    pub fn covers(&self, target: Vec2) -> bool {
+        (bearing(target) - self.direction).abs() <= self.half_arc
+    }
+
+    pub fn is_turret(&self) -> bool {
+        self.half_arc >= PI
+    }
+}
+
+/// Bearing of `target`, a point on an enemy ship in that ship's own coordinates, as seen from the
+/// ship shooting at it. The enemy sits [`ENGAGEMENT_RANGE`] dead ahead, turned round to face us.
+pub fn bearing(target: Vec2) -> f32 {
+    Vec2::new(ENGAGEMENT_RANGE - target.x, -target.y).to_angle()
+}
+
+/// The point on an enemy ship, in its own coordinates, at `bearing` and `distance` from the ship
+/// shooting at it. The inverse of [`bearing`].
+pub fn point_at_bearing(bearing: f32, distance: f32) -> Vec2 {
+    Vec2::new(ENGAGEMENT_RANGE, 0.0) - Vec2::from_angle(bearing) * distance
+}
+
+/// A quarter of the hull, split down the middle both ways. Fore is `+x`, port is `+y`.
+#[derive(Serialize, Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quadrant {
+    ForePort,
+    ForeStarboard,
+    AftPort,
+    AftStarboard,
+}
+
+impl std::fmt::Display for Quadrant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ForePort => write!(f, "fore port"),
+            Self::ForeStarboard => write!(f, "fore starboard"),
+            Self::AftPort => write!(f, "aft port"),
+            Self::AftStarboard => write!(f, "aft starboard"),
         }
     }
 }
@@ -36,9 +132,93 @@ impl Room {
     }
 }
 
+/// How far from the middle of the field each side lines up its ships.
+const SIDE_OFFSET: f32 = 300.0;
+/// Space between teammates lined up on the same side.
+const TEAMMATE_SPACING: f32 = 250.0;
+
+/// Where a ship sits in the world. The server decides this as teams are picked, and clients place
+/// ship graphics from it.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ShipPlacement {
+    pub position: Vec2,
+    /// Radians counterclockwise.
+    pub rotation: f32,
+}
+
+impl ShipPlacement {
+    /// The spot for the `index`th ship on `team`'s side. Even teams line up on the left, odd teams
+    /// on the right turned sideways, and ships on the same side stack up and down from the middle.
+    /// `index` counts every ship on the side, not just the team's, or teams sharing a side would
+    /// land on top of each other.
+    pub fn for_team(team: Team, index: usize) -> Self {
+        // 0, 1, -1, 2, -2, ...
+        let row = (index as f32 / 2.0).ceil() * if index % 2 == 1 { 1.0 } else { -1.0 };
+        if team.0.is_multiple_of(2) {
+            Self {
+                position: Vec2::new(-SIDE_OFFSET, row * TEAMMATE_SPACING),
+                rotation: 0.0,
+            }
+        } else {
+            Self {
+                position: Vec2::new(SIDE_OFFSET, row * TEAMMATE_SPACING),
+                rotation: TAU / 4.0,
+            }
+        }
+    }
+
+    /// This placement as seen by someone aboard `viewer`. Everyone sees their own side on the left
+    /// and upright, so players on the right see the field mirrored, with each side's facing swapped.
+    pub fn seen_from(self, viewer: ShipPlacement) -> Self {
+        if viewer.position.x <= 0.0 {
+            return self;
+        }
+        Self {
+            position: Vec2::new(-self.position.x, self.position.y),
+            rotation: TAU / 4.0 - self.rotation,
+        }
+    }
+}
+
 #[derive(Component, Serialize, Deserialize, Debug, Default)]
 pub struct Dead;
 
+/// How long a ship takes to blow up once it's [`Dead`].
+pub const DESTRUCTION_TIME: Duration = Duration::from_secs(3);
+
+/// Inserted alongside [`Dead`]. The server counts `remaining` down to zero while clients play the
+/// ship's destruction sequence, and the wreck stays around afterwards.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Destroyed {
+    pub remaining: Duration,
+}
+
+impl Default for Destroyed {
+    fn default() -> Self {
+        Self {
+            remaining: DESTRUCTION_TIME,
+        }
+    }
+}
+
+impl Destroyed {
+    /// A wreck whose sequence has already played out.
+    pub fn finished() -> Self {
+        Self {
+            remaining: Duration::ZERO,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.remaining.is_zero()
+    }
+
+    /// How far along the sequence is, in `[0, 1]`.
+    pub fn progress(&self) -> f32 {
+        1.0 - self.remaining.as_secs_f32() / DESTRUCTION_TIME.as_secs_f32()
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct ShipType {
     pub rooms: &'static [Room],
@@ -46,7 +226,16 @@ pub struct ShipType {
     pub path_graph: &'static [(Cell, &'static [Cell])],
     pub cell_positions: &'static [Vec2],
     pub room_systems: &'static [Option<SystemId>],
+    /// Which [`SubsystemId`] each room houses. A room has a system or a subsystem, never both.
+    pub room_subsystems: &'static [Option<SubsystemId>],
     pub doors: &'static [Door],
+    /// Hardpoints on the hull. However far the weapons system is upgraded, it can't mount more
+    /// weapons than there are of these.
+    pub weapon_mounts: &'static [WeaponMount],
+    /// Hull damage soaked up by the armor over each [`Quadrant`], indexed by `Quadrant as usize`.
+    pub armor: [usize; 4],
+    /// Points of super shield built into the hull, on top of any from augments.
+    pub super_shield: usize,
 }
 
 impl ShipType {
@@ -59,10 +248,136 @@ impl ShipType {
             .unwrap()
     }
 
+    /// Every room whose center is within `radius` of `room`'s, including `room` itself.
+    pub fn rooms_within(&self, room: usize, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let center = self.room_center(room);
+        (0..self.rooms.len()).filter(move |&x| self.room_center(x).distance(center) <= radius)
+    }
+
+    /// How many weapons a weapons system of `upgrade_level` can mount on this hull: one per level,
+    /// up to [`Self::weapon_mounts`]. The rest have to stay in cargo.
+    pub fn weapon_slots(&self, upgrade_level: usize) -> usize {
+        upgrade_level.min(self.weapon_mounts.len())
+    }
+
+    /// Whether the weapon in `slot` can aim at `target`, a point on an enemy ship in that ship's
+    /// own coordinates.
+    pub fn mount_covers(&self, slot: usize, target: Vec2) -> bool {
+        self.weapon_mounts
+            .get(slot)
+            .is_none_or(|x| x.covers(target))
+    }
+
+    /// Which quarter of the hull `room` sits in. Rooms straddling the middle count as port and fore.
+    pub fn room_quadrant(&self, room: usize) -> Quadrant {
+        let (min, max) = self
+            .cell_positions
+            .iter()
+            .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), &x| {
+                (min.min(x), max.max(x))
+            });
+        let offset = self.room_center(room) - (min + max) / 2.0;
+        match (offset.x >= 0.0, offset.y >= 0.0) {
+            (true, true) => Quadrant::ForePort,
+            (true, false) => Quadrant::ForeStarboard,
+            (false, true) => Quadrant::AftPort,
+            (false, false) => Quadrant::AftStarboard,
+        }
+    }
+
+    pub fn room_armor(&self, room: usize) -> usize {
+        self.armor[self.room_quadrant(room) as usize]
+    }
+
+    /// Hull damage a hit on `room` actually does. Armor soaks up part of it, but anything that
+    /// does hull damage at all always gets at least one point through.
+    pub fn armored_hull_damage(&self, room: usize, hull: usize) -> usize {
+        hull.saturating_sub(self.room_armor(room)).max(hull.min(1))
+    }
+
     pub fn cell_room(&self, cell: Cell) -> usize {
         self.rooms.iter().position(|x| x.has_cell(cell)).unwrap()
     }
 
+    /// Rooms a door opens onto. Exterior doors only have the one.
+    pub fn door_rooms(&self, door: usize) -> Vec<usize> {
+        match self.doors[door] {
+            Door::Interior(a, b) => vec![self.cell_room(a), self.cell_room(b)],
+            Door::Exterior(cell, _) => vec![self.cell_room(cell)],
+        }
+    }
+
+    /// Indices of the interior doors crossed walking from room `from` to room `to` through as few
+    /// doors as possible, in order. `None` if the rooms aren't connected.
+    pub fn door_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        // Breadth-first over rooms, remembering which room and door each was reached through
+        let mut came_from = HashMap::from([(from, None)]);
+        let mut frontier = VecDeque::from([from]);
+        while let Some(room) = frontier.pop_front() {
+            if room == to {
+                let mut doors = Vec::new();
+                let mut current = to;
+                while let Some((previous, door)) = came_from[&current] {
+                    doors.push(door);
+                    current = previous;
+                }
+                doors.reverse();
+                return Some(doors);
+            }
+            for (door, &kind) in self.doors.iter().enumerate() {
+                let Door::Interior(a, b) = kind else {
+                    continue;
+                };
+                let (a, b) = (self.cell_room(a), self.cell_room(b));
+                let next = match room {
+                    x if x == a => b,
+                    x if x == b => a,
+                    _ => continue,
+                };
+                if let Entry::Vacant(x) = came_from.entry(next) {
+                    x.insert(Some((room, door)));
+                    frontier.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Rooms open to space through the doors `open` says are open, either straight out an airlock
+    /// or by way of other rooms.
+    pub fn vented_rooms(&self, open: impl Fn(usize) -> bool) -> HashSet<usize> {
+        let mut vented = HashSet::new();
+        let mut frontier = VecDeque::new();
+        for (door, &kind) in self.doors.iter().enumerate() {
+            if let Door::Exterior(cell, _) = kind {
+                let room = self.cell_room(cell);
+                if open(door) && vented.insert(room) {
+                    frontier.push_back(room);
+                }
+            }
+        }
+        while let Some(room) = frontier.pop_front() {
+            for (door, &kind) in self.doors.iter().enumerate() {
+                let Door::Interior(a, b) = kind else {
+                    continue;
+                };
+                if !open(door) {
+                    continue;
+                }
+                let (a, b) = (self.cell_room(a), self.cell_room(b));
+                let next = match room {
+                    x if x == a => b,
+                    x if x == b => a,
+                    _ => continue,
+                };
+                if vented.insert(next) {
+                    frontier.push_back(next);
+                }
+            }
+        }
+        vented
+    }
+
     pub fn cell_aabb(&self, Cell(cell): Cell) -> Aabb {
         let center = self.cell_positions[cell];
         Aabb::from_corners(center + Vec2::splat(-17.5), center + Vec2::splat(17.5))
@@ -72,6 +387,26 @@ impl ShipType {
         (0..self.cell_positions.len()).map(|x| Cell(x))
     }
 
+    /// Every pair of neighboring cells that share a room, each pair listed once.
+    pub fn room_adjacencies(&self) -> impl Iterator<Item = (Cell, Cell)> + '_ {
+        self.path_graph.iter().flat_map(move |&(a, neighbors)| {
+            neighbors
+                .iter()
+                .filter(move |&&b| a.0 < b.0 && self.cell_room(a) == self.cell_room(b))
+                .map(move |&b| (a, b))
+        })
+    }
+
+    /// Average of a per-cell value over the cells of `room`.
+    pub fn room_average(&self, room: usize, per_cell: &[f32]) -> f32 {
+        self.rooms[room]
+            .cells
+            .iter()
+            .map(|&Cell(x)| per_cell[x])
+            .average()
+            .unwrap()
+    }
+
     pub fn neighbors_of_room(&self, room: usize) -> impl Iterator<Item = usize> {
         let path_graph = PathGraph {
             edges: self
@@ -136,12 +471,10 @@ pub const SHIPS: [ShipType; 1] = [ShipType {
         Room {
             cells: &[Cell(10), Cell(11), Cell(12), Cell(13)],
         },
-        Room {
-            cells: &[Cell(14), Cell(15)],
-        },
-        Room {
-            cells: &[Cell(16), Cell(17)],
-        },
+        Room { cells: &[Cell(15)] },
+        Room { cells: &[Cell(17)] },
+        Room { cells: &[Cell(14)] },
+        Room { cells: &[Cell(16)] },
     ],
     nav_mesh: (
         &[
@@ -205,8 +538,20 @@ pub const SHIPS: [ShipType; 1] = [ShipType {
         Some(SystemId::Engines),
         Some(SystemId::Shields),
         Some(SystemId::Weapons),
+        Some(SystemId::CloneBay),
         None,
         None,
+        None,
+    ],
+    room_subsystems: &[
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(SubsystemId::Piloting),
+        Some(SubsystemId::Doors),
+        Some(SubsystemId::Sensors),
     ],
     doors: &[
         Door::Interior(Cell(1), Cell(6)),
@@ -216,5 +561,130 @@ pub const SHIPS: [ShipType; 1] = [ShipType {
         Door::Interior(Cell(13), Cell(15)),
         Door::Exterior(Cell(0), DoorDir::Bottom),
         Door::Exterior(Cell(16), DoorDir::Top),
+        Door::Interior(Cell(14), Cell(15)),
+        Door::Interior(Cell(16), Cell(17)),
+    ],
+    // Two turrets up top, and a mount down each side that only reaches that side of the enemy
+    weapon_mounts: &[
+        WeaponMount::TURRET,
+        WeaponMount::TURRET,
+        WeaponMount {
+            direction: 0.15,
+            half_arc: 0.15,
+        },
+        WeaponMount {
+            direction: -0.15,
+            half_arc: 0.15,
+        },
     ],
+    // Heavy plating over the weapons and clone bay, and a bit around the oxygen system
+    armor: [1, 0, 0, 1],
+    super_shield: 0,
 }];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_reaches_nearby_rooms() {
+        let ship = &SHIPS[0];
+        assert_eq!(ship.rooms_within(0, 0.0).collect::<Vec<_>>(), [0]);
+        let everywhere = ship.rooms_within(0, f32::INFINITY).count();
+        assert_eq!(everywhere, ship.rooms.len());
+    }
+
+    #[test]
+    fn everyone_sees_themselves_on_the_left() {
+        let left = ShipPlacement::for_team(Team(0), 0);
+        let right = ShipPlacement::for_team(Team(1), 0);
+        assert_eq!(left.seen_from(left), left);
+        assert_eq!(right.seen_from(right).position, left.position);
+        assert_eq!(right.seen_from(right).rotation, 0.0);
+        assert_eq!(left.seen_from(right).rotation, TAU / 4.0);
+        let teammate = ShipPlacement::for_team(Team(2), 1);
+        assert_eq!(teammate.position, Vec2::new(-SIDE_OFFSET, TEAMMATE_SPACING));
+    }
+
+    #[test]
+    fn door_path_crosses_fewest_doors() {
+        let ship = &SHIPS[0];
+        assert_eq!(ship.door_path(0, 4), Some(vec![0, 3, 4]));
+        assert_eq!(ship.door_path(4, 0), Some(vec![4, 3, 0]));
+        assert_eq!(ship.door_path(1, 5), Some(vec![1, 2]));
+        assert_eq!(ship.door_path(3, 3), Some(vec![]));
+    }
+
+    #[test]
+    fn venting_follows_open_doors() {
+        let ship = &SHIPS[0];
+        let airlock = ship
+            .doors
+            .iter()
+            .position(|x| matches!(x, Door::Exterior(Cell(0), _)))
+            .unwrap();
+        assert!(ship.vented_rooms(|_| false).is_empty());
+        // Inner doors open but the airlock shut, nothing vents
+        assert!(ship.vented_rooms(|x| x != airlock && x < 5).is_empty());
+        assert_eq!(
+            ship.vented_rooms(|x| x == airlock),
+            HashSet::from([ship.cell_room(Cell(0))])
+        );
+        assert_eq!(
+            ship.vented_rooms(|x| x == airlock || x == 0),
+            HashSet::from([ship.cell_room(Cell(1)), ship.cell_room(Cell(6))])
+        );
+    }
+
+    #[test]
+    fn one_system_or_subsystem_per_room() {
+        for ship in &SHIPS {
+            assert_eq!(ship.room_systems.len(), ship.rooms.len());
+            assert_eq!(ship.room_subsystems.len(), ship.rooms.len());
+            for (system, subsystem) in ship.room_systems.iter().zip(ship.room_subsystems) {
+                assert!(system.is_none() || subsystem.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn side_mounts_reach_their_own_side() {
+        let ship = &SHIPS[0];
+        // Enemy starboard is on our port side, since it's facing us
+        let starboard = Vec2::new(0.0, -52.5);
+        let port = Vec2::new(0.0, 52.5);
+        assert!(ship.mount_covers(0, starboard) && ship.mount_covers(0, port));
+        assert!(ship.mount_covers(2, starboard) && !ship.mount_covers(2, port));
+        assert!(!ship.mount_covers(3, starboard) && ship.mount_covers(3, port));
+        // Slots past the last mount aren't restricted
+        assert!(ship.mount_covers(ship.weapon_mounts.len(), port));
+        // Every room is in reach of every mount on one side or the other
+        for room in 0..ship.rooms.len() {
+            let center = ship.room_center(room);
+            assert!(ship.mount_covers(2, center) || ship.mount_covers(3, center));
+        }
+        let point = point_at_bearing(0.2, ENGAGEMENT_RANGE);
+        assert!((bearing(point) - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn armor_soaks_all_but_one() {
+        let ship = &SHIPS[0];
+        let weapons = ship
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::Weapons))
+            .unwrap();
+        assert_eq!(ship.room_quadrant(weapons), Quadrant::ForePort);
+        assert_eq!(ship.room_armor(weapons), 1);
+        assert_eq!(ship.armored_hull_damage(weapons, 3), 2);
+        assert_eq!(ship.armored_hull_damage(weapons, 1), 1);
+        assert_eq!(ship.armored_hull_damage(weapons, 0), 0);
+        let shields = ship
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::Shields))
+            .unwrap();
+        assert_eq!(ship.armored_hull_damage(shields, 3), 3);
+    }
+}
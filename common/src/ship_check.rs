@@ -0,0 +1,334 @@
+//! Sanity checks for ship definitions, so a mistake in a new [`ShipType`] shows up as a readable
+//! list of problems instead of a panic halfway through a match. Run the `ship_check` binary in the
+//! server crate to check every ship in [`SHIPS`](crate::ship::SHIPS) and print a diagram of each.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    nav::{Cell, LineSection, SquareSection},
+    ship::{Door, ShipType, SubsystemId, SystemId},
+    weapon::WeaponId,
+};
+
+/// Width of a cell, and the distance between the centers of neighboring cells.
+pub const CELL_SIZE: f32 = 35.0;
+
+/// The parts of a [`ShipType`] that [`check_ship`] looks at, owned, so a layout that isn't a
+/// `const` yet (like one out of the ship editor) can be checked too.
+#[derive(Debug, Clone, Default)]
+pub struct ShipLayout {
+    pub rooms: Vec<Vec<Cell>>,
+    pub lines: Vec<LineSection>,
+    pub squares: Vec<SquareSection>,
+    pub path_graph: Vec<(Cell, Vec<Cell>)>,
+    pub cell_positions: Vec<Vec2>,
+    pub room_systems: Vec<Option<SystemId>>,
+    pub room_subsystems: Vec<Option<SubsystemId>>,
+    pub doors: Vec<Door>,
+}
+
+impl ShipLayout {
+    fn cells(&self) -> impl Iterator<Item = Cell> {
+        (0..self.cell_positions.len()).map(Cell)
+    }
+
+    fn cell_room(&self, cell: Cell) -> usize {
+        self.rooms.iter().position(|x| x.contains(&cell)).unwrap()
+    }
+}
+
+impl From<&ShipType> for ShipLayout {
+    fn from(ship: &ShipType) -> Self {
+        Self {
+            rooms: ship.rooms.iter().map(|x| x.cells.to_vec()).collect(),
+            lines: ship.nav_mesh.0.to_vec(),
+            squares: ship.nav_mesh.1.to_vec(),
+            path_graph: ship
+                .path_graph
+                .iter()
+                .map(|&(a, neighbors)| (a, neighbors.to_vec()))
+                .collect(),
+            cell_positions: ship.cell_positions.to_vec(),
+            room_systems: ship.room_systems.to_vec(),
+            room_subsystems: ship.room_subsystems.to_vec(),
+            doors: ship.doors.to_vec(),
+        }
+    }
+}
+
+/// Everything wrong with `ship`, one line per problem. Empty if it's fine.
+pub fn check_ship(ship: &ShipLayout) -> Vec<String> {
+    let mut problems = Vec::new();
+    let cells = ship.cell_positions.len();
+    let valid = |Cell(x): Cell| x < cells;
+
+    // Rooms
+    if ship.room_systems.len() != ship.rooms.len() {
+        problems.push(format!(
+            "{} rooms but {} room systems",
+            ship.rooms.len(),
+            ship.room_systems.len()
+        ));
+    }
+    if ship.room_subsystems.len() != ship.rooms.len() {
+        problems.push(format!(
+            "{} rooms but {} room subsystems",
+            ship.rooms.len(),
+            ship.room_subsystems.len()
+        ));
+    }
+    for (room, (system, subsystem)) in ship
+        .room_systems
+        .iter()
+        .zip(&ship.room_subsystems)
+        .enumerate()
+    {
+        if let (Some(system), Some(subsystem)) = (system, subsystem) {
+            problems.push(format!("room {room} has both {system} and {subsystem}"));
+        }
+    }
+    for (room, x) in ship.rooms.iter().enumerate() {
+        if x.is_empty() {
+            problems.push(format!("room {room} has no cells"));
+        }
+        for &cell in x.iter().filter(|&&x| !valid(x)) {
+            problems.push(format!("room {room} has {cell:?}, which doesn't exist"));
+        }
+    }
+    for cell in ship.cells() {
+        let rooms = (0..ship.rooms.len())
+            .filter(|&x| ship.rooms[x].contains(&cell))
+            .collect::<Vec<_>>();
+        match rooms.len() {
+            0 => problems.push(format!("{cell:?} isn't in any room")),
+            1 => {}
+            _ => problems.push(format!("{cell:?} is in rooms {rooms:?}")),
+        }
+    }
+    // Everything below looks cells up by room, which would panic
+    if !problems.is_empty() {
+        return problems;
+    }
+
+    // Layout
+    for a in ship.cells() {
+        for b in ship.cells().filter(|&b| a.0 < b.0) {
+            let gap = (ship.cell_positions[a.0] - ship.cell_positions[b.0]).abs();
+            if gap.x < CELL_SIZE - 0.01 && gap.y < CELL_SIZE - 0.01 {
+                problems.push(format!("{a:?} and {b:?} overlap"));
+            }
+        }
+    }
+    let adjacent = |a: Cell, b: Cell| {
+        let gap = (ship.cell_positions[a.0] - ship.cell_positions[b.0]).abs();
+        let touching = |x: f32| (x - CELL_SIZE).abs() < 0.01;
+        (touching(gap.x) && gap.y < 0.01) || (touching(gap.y) && gap.x < 0.01)
+    };
+    // Crew cut corners inside rooms, but never through a wall
+    let diagonal = |a: Cell, b: Cell| {
+        let gap = (ship.cell_positions[a.0] - ship.cell_positions[b.0]).abs();
+        let touching = |x: f32| (x - CELL_SIZE).abs() < 0.01;
+        touching(gap.x) && touching(gap.y) && ship.cell_room(a) == ship.cell_room(b)
+    };
+
+    // Navigation
+    let mut navigable = HashSet::new();
+    for (i, line) in ship.lines.iter().enumerate() {
+        let [a, b] = line.0;
+        if !valid(a) || !valid(b) {
+            problems.push(format!("nav line {i} has cells that don't exist"));
+        } else if !adjacent(a, b) {
+            problems.push(format!(
+                "nav line {i} joins {a:?} and {b:?}, which aren't neighbors"
+            ));
+        }
+        navigable.extend([a, b]);
+    }
+    for (i, square) in ship.squares.iter().enumerate() {
+        let [[a, b], [c, d]] = square.0;
+        if [a, b, c, d].into_iter().any(|x| !valid(x)) {
+            problems.push(format!("nav square {i} has cells that don't exist"));
+        } else if !(adjacent(a, b) && adjacent(c, d) && adjacent(a, c) && adjacent(b, d)) {
+            problems.push(format!("nav square {i} isn't a 2x2 block of cells"));
+        }
+        navigable.extend([a, b, c, d]);
+    }
+    for (room, x) in ship.rooms.iter().enumerate() {
+        for cell in x.iter().filter(|x| !navigable.contains(x)) {
+            problems.push(format!(
+                "{cell:?} in room {room} isn't covered by the nav mesh"
+            ));
+        }
+    }
+    let linked = |a: Cell, b: Cell| {
+        ship.path_graph
+            .iter()
+            .any(|(x, neighbors)| *x == a && neighbors.contains(&b))
+    };
+    for (a, neighbors) in &ship.path_graph {
+        let a = *a;
+        for &b in neighbors {
+            if !valid(a) || !valid(b) {
+                problems.push(format!(
+                    "path graph links {a:?} and {b:?}, which don't both exist"
+                ));
+            } else if !adjacent(a, b) && !diagonal(a, b) {
+                problems.push(format!(
+                    "path graph links {a:?} and {b:?}, which aren't neighbors"
+                ));
+            }
+        }
+    }
+
+    // Doors
+    for (i, &door) in ship.doors.iter().enumerate() {
+        match door {
+            Door::Interior(a, b) => {
+                if !valid(a) || !valid(b) {
+                    problems.push(format!("door {i} has cells that don't exist"));
+                    continue;
+                }
+                if !adjacent(a, b) {
+                    problems.push(format!(
+                        "door {i} joins {a:?} and {b:?}, which aren't neighbors"
+                    ));
+                }
+                if ship.cell_room(a) == ship.cell_room(b) {
+                    problems.push(format!("door {i} joins two cells of the same room"));
+                }
+                if !linked(a, b) || !linked(b, a) {
+                    problems.push(format!(
+                        "crew can't walk through door {i}, it's not in the path graph"
+                    ));
+                }
+            }
+            Door::Exterior(cell, dir) => {
+                if !valid(cell) {
+                    problems.push(format!("door {i} is on {cell:?}, which doesn't exist"));
+                    continue;
+                }
+                let outside = ship.cell_positions[cell.0] + dir.offset() * 2.0;
+                if ship
+                    .cell_positions
+                    .iter()
+                    .any(|x| x.distance(outside) < 0.01)
+                {
+                    problems.push(format!(
+                        "airlock door {i} on {cell:?} opens onto another cell"
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Beams whose swath doesn't suit `ship`'s scale: shorter than a cell, so they might not cross a
+/// room boundary at all, or longer than the whole ship.
+pub fn check_beam_lengths(ship: &ShipType) -> Vec<String> {
+    let (min, max) = ship
+        .cell_positions
+        .iter()
+        .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), &x| {
+            (min.min(x), max.max(x))
+        });
+    let extent = (max - min + CELL_SIZE).length();
+    WeaponId::all()
+        .filter_map(|x| Some((x.common().name, x.beam_stats()?.length)))
+        .filter_map(|(name, length)| {
+            if length < CELL_SIZE {
+                Some(format!("{name} is {length} long, shorter than a cell"))
+            } else if length > extent {
+                Some(format!(
+                    "{name} is {length} long, longer than the ship ({extent:.0})"
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Top-down picture of `ship`, one character per cell: the room it belongs to, `0` to `9` then
+/// `a` to `z`. Airlocks are marked with `@` next to their cell.
+pub fn diagram(ship: &ShipType) -> String {
+    let min = ship
+        .cell_positions
+        .iter()
+        .fold(Vec2::INFINITY, |min, &x| min.min(x));
+    // Two characters per cell across and one down, leaving room for airlocks on every side
+    let to_grid = |pos: Vec2| {
+        let pos = ((pos - min) / CELL_SIZE).round();
+        (pos.x as usize * 2 + 1, pos.y as usize + 1)
+    };
+    let (width, height) = ship
+        .cells()
+        .map(|x| to_grid(ship.cell_positions[x.0]))
+        .fold((0, 0), |(w, h), (x, y)| (w.max(x + 2), h.max(y + 2)));
+    let mut grid = vec![vec![' '; width + 1]; height];
+    for cell in ship.cells() {
+        let (x, y) = to_grid(ship.cell_positions[cell.0]);
+        let room = ship.cell_room(cell) as u32;
+        grid[y][x] = char::from_digit(room % 36, 36).unwrap();
+    }
+    for &door in ship.doors {
+        if let Door::Exterior(cell, dir) = door {
+            let (x, y) = to_grid(ship.cell_positions[cell.0]);
+            let offset = dir.offset() / 17.5;
+            let (x, y) = (x as i32 + offset.x as i32, y as i32 + offset.y as i32);
+            if let Some(spot) = grid
+                .get_mut(y as usize)
+                .and_then(|row| row.get_mut(x as usize))
+            {
+                *spot = '@';
+            }
+        }
+    }
+    // Positive y is up, so the last row goes on top
+    grid.iter()
+        .rev()
+        .map(|row| row.iter().collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ship::{Room, SHIPS};
+
+    #[test]
+    fn ships_check_out() {
+        for ship in &SHIPS {
+            assert_eq!(check_ship(&ship.into()), Vec::<String>::new());
+            assert_eq!(check_beam_lengths(ship), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn catches_shared_cells_and_bad_doors() {
+        let ship = ShipType {
+            rooms: &[
+                Room {
+                    cells: &[Cell(0), Cell(1)],
+                },
+                Room { cells: &[Cell(1)] },
+            ],
+            ..SHIPS[0]
+        };
+        let problems = check_ship(&(&ship).into());
+        assert!(problems.iter().any(|x| x.contains("Cell(1) is in rooms")));
+        assert!(problems
+            .iter()
+            .any(|x| x.contains("Cell(2) isn't in any room")));
+
+        let ship = ShipType {
+            doors: &[Door::Interior(Cell(0), Cell(15))],
+            ..SHIPS[0]
+        };
+        let problems = check_ship(&(&ship).into());
+        assert!(problems.iter().any(|x| x.contains("aren't neighbors")));
+    }
+}
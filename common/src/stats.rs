@@ -0,0 +1,108 @@
+//! Running per-player statistics for the current match, kept up to date by the server and shown on
+//! the post-game screen.
+
+use std::{collections::HashMap, time::Duration};
+
+use bevy::prelude::*;
+use bevy_replicon::core::ClientId;
+use serde::{Deserialize, Serialize};
+
+use crate::lobby::Team;
+
+/// Keyed by client rather than ship entity so it can be replicated as a plain resource.
+#[derive(Resource, Serialize, Deserialize, Debug, Default, Clone)]
+pub struct MatchStats {
+    /// By captain. Copilots share their captain's line.
+    pub players: HashMap<ClientId, PlayerStats>,
+    /// Each copilot's captain.
+    pub copilots: HashMap<ClientId, ClientId>,
+    /// Each captain's team.
+    pub teams: HashMap<ClientId, Team>,
+}
+
+impl MatchStats {
+    /// The client whose line `client`'s stats are on: their captain if they're a copilot,
+    /// otherwise themselves.
+    pub fn captain(&self, client: ClientId) -> ClientId {
+        self.copilots.get(&client).copied().unwrap_or(client)
+    }
+
+    /// Stats for the ship `client` is aboard, whether as captain or copilot.
+    pub fn player(&self, client: ClientId) -> Option<&PlayerStats> {
+        self.players.get(&self.captain(client))
+    }
+
+    /// Stats for every captain on a different team from `client`, in a stable order.
+    pub fn opponents(&self, client: ClientId) -> Vec<PlayerStats> {
+        let captain = self.captain(client);
+        let team = self.teams.get(&captain);
+        let mut opponents = self
+            .players
+            .iter()
+            .filter(|&(&id, _)| id != captain && (team.is_none() || self.teams.get(&id) != team))
+            .collect::<Vec<_>>();
+        opponents.sort_by_key(|&(&id, _)| id.get());
+        opponents.into_iter().map(|(_, &x)| x).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+pub struct PlayerStats {
+    /// Hull damage dealt to the enemy.
+    pub damage_dealt: usize,
+    /// Hull damage taken from the enemy.
+    pub damage_taken: usize,
+    pub shots_fired: usize,
+    /// Shots that reached the enemy's hull.
+    pub shots_hit: usize,
+    /// Enemy crew killed by our weapons.
+    pub crew_kills: usize,
+    /// Enemy systems taken from working to fully destroyed.
+    pub systems_destroyed: usize,
+    /// Time our oxygen system spent unpowered.
+    pub oxygen_downtime: Duration,
+}
+
+impl PlayerStats {
+    /// Fraction of shots fired that reached the enemy hull, in `[0, 1]`.
+    pub fn hit_rate(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            self.shots_hit as f32 / self.shots_fired as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opponents_are_on_other_teams() {
+        let client = ClientId::new;
+        let line = |damage_dealt| PlayerStats {
+            damage_dealt,
+            ..default()
+        };
+        let stats = MatchStats {
+            players: HashMap::from([
+                (client(1), line(1)),
+                (client(2), line(2)),
+                (client(3), line(3)),
+                (client(4), line(4)),
+            ]),
+            copilots: HashMap::from([(client(5), client(1))]),
+            teams: HashMap::from([
+                (client(1), Team(0)),
+                (client(2), Team(0)),
+                (client(3), Team(1)),
+                (client(4), Team(1)),
+            ]),
+        };
+        let dealt = |x: Vec<PlayerStats>| x.iter().map(|x| x.damage_dealt).collect::<Vec<_>>();
+        assert_eq!(dealt(stats.opponents(client(1))), vec![3, 4]);
+        assert_eq!(dealt(stats.opponents(client(5))), vec![3, 4]);
+        assert_eq!(dealt(stats.opponents(client(4))), vec![1, 2]);
+    }
+}
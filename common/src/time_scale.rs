@@ -0,0 +1,24 @@
+//! Simulation speed, for testing against bots without waiting around for weapons to charge. The
+//! server applies it to virtual time, so the fixed-rate gameplay systems simply run more or less
+//! often and every per-tick rate scales along with it.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The speeds a client can ask for.
+pub const TIME_SCALES: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+#[derive(Resource, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Ask the server to run the simulation at a different speed, one of [`TIME_SCALES`]. In local
+/// matches this takes effect right away; otherwise every connected client has to ask for the same
+/// speed first.
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct RequestTimeScale(pub f32);
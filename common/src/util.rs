@@ -92,6 +92,17 @@ pub fn intersect(
     (start <= end).then_some(start..=end)
 }
 
+/// FNV-1a offset basis, where every [`fnv1a`] hash starts out.
+pub const FNV1A_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Fold `bytes` into an FNV-1a `hash`. For hashes that client and server compare, which can't use
+/// `DefaultHasher` since it may hash differently in builds made with another Rust.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
 pub fn init_resource<R: Resource + FromWorld>(mut commands: Commands) {
     commands.init_resource::<R>();
 }
@@ -118,3 +129,37 @@ pub fn enable<C: Component>(e: Entity, world: &mut World) {
         world.entity_mut(e).insert(c);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_intersection() {
+        assert_eq!(intersect(0.0..=2.0, 1.0..=3.0), Some(1.0..=2.0));
+        assert_eq!(intersect(0.0..=1.0, 1.0..=2.0), Some(1.0..=1.0));
+        assert_eq!(intersect(0.0..=1.0, 2.0..=3.0), None);
+    }
+
+    #[test]
+    fn fnv1a_matches_reference() {
+        assert_eq!(fnv1a(FNV1A_BASIS, b""), FNV1A_BASIS);
+        assert_eq!(fnv1a(FNV1A_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        // Hashing in pieces is the same as hashing all at once
+        assert_eq!(
+            fnv1a(fnv1a(FNV1A_BASIS, b"foo"), b"bar"),
+            fnv1a(FNV1A_BASIS, b"foobar")
+        );
+    }
+
+    #[test]
+    fn aabb_transforms() {
+        let aabb = Aabb::from_corners(Vec2::new(2.0, 4.0), Vec2::new(1.0, 1.0)) - Vec2::ONE;
+        assert_eq!(aabb.x_range(), 0.0..=1.0);
+        assert_eq!(aabb.y_range(), 0.0..=3.0);
+        // Flipping an axis keeps the corners ordered
+        let flipped = aabb.scale_about_origin(Vec2::new(-2.0, 1.0));
+        assert_eq!(flipped.x_range(), -2.0..=0.0);
+        assert_eq!(flipped.y_range(), 0.0..=3.0);
+    }
+}
@@ -1,9 +1,13 @@
-use crate::bullets::{BeamTarget, RoomTarget};
-use bevy::ecs::entity::{EntityMapper, MapEntities};
-use serde::{Deserialize, Serialize};
+use crate::{
+    bullets::{BeamTarget, ProjectileKind, RoomTarget},
+    ship::SHIPS,
+};
+use bevy::ecs::entity::{Entity, EntityMapper, MapEntities};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// This represents an "physical" weapon. It is non-clonable because new instances must be produced
 /// from a store or event, for example. A ship can mount these,
+#[derive(Serialize, Deserialize, Debug)]
 pub enum Weapon {
     Projectile(ProjectileWeapon),
     Beam(BeamWeapon),
@@ -25,6 +29,7 @@ impl Weapon {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ProjectileWeapon(usize);
 
 impl Into<WeaponId> for ProjectileWeaponId {
@@ -45,6 +50,7 @@ impl std::fmt::Debug for ProjectileWeapon {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BeamWeapon(usize);
 
 impl Into<WeaponId> for BeamWeaponId {
@@ -71,8 +77,16 @@ pub struct ProjectileStats {
     pub shot_speed: f32,
     pub volley_size: usize,
     pub shield_pierce: usize,
+    /// Chance in `[0, 1]` for a shot that reaches the hull to land a critical hit, see
+    /// [`HitQuality`](crate::bullets::HitQuality).
+    pub crit_chance: f32,
+    pub kind: ProjectileKind,
     pub uses_missile: bool,
     pub can_target_self: bool,
+    /// How far from the room aimed at each shot in a volley can land, in ship coordinates. Every
+    /// shot picks a room whose center is within this of the target's. Zero for weapons that hit
+    /// exactly where they're aimed.
+    pub spread: f32,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -80,10 +94,55 @@ pub struct BeamStats {
     pub common: CommonStats,
     pub speed: f32,
     pub length: f32,
+    pub shield_rule: ShieldRule,
+}
+
+/// How a beam's swath gets past shields. Either way a beam never takes down shield layers, and a
+/// beam with no hull damage to soak up (like the Anti-Bio Beam) is stopped by any shield at all.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShieldRule {
+    /// Every room the beam crosses takes a point less damage per shield layer.
+    #[default]
+    PerRoom,
+    /// The shield soaks up a point of damage per layer once, from the first rooms the beam
+    /// crosses. The rest of the swath lands at full strength.
+    Soak,
+}
+
+impl ShieldRule {
+    /// One room's worth of beam `damage` after getting past shields with `layers` up, or `None` if
+    /// none of it gets through. `soaked` is how many points the shields have already taken out of
+    /// this beam, and is kept up to date.
+    pub fn apply(
+        self,
+        damage: DamageSpec,
+        layers: usize,
+        soaked: &mut usize,
+    ) -> Option<DamageSpec> {
+        match self {
+            ShieldRule::PerRoom => damage.through_shields(layers),
+            ShieldRule::Soak => {
+                if layers > 0 && damage.hull == 0 {
+                    return None;
+                }
+                let absorbed = layers.saturating_sub(*soaked).min(damage.hull);
+                *soaked += absorbed;
+                damage.through_shields(absorbed)
+            }
+        }
+    }
+
+    /// Whether shields with `layers` up keep a beam doing `damage` off the hull entirely.
+    pub fn blocks(self, damage: DamageSpec, layers: usize) -> bool {
+        match self {
+            ShieldRule::PerRoom => damage.through_shields(layers).is_none(),
+            ShieldRule::Soak => layers > 0 && damage.hull == 0,
+        }
+    }
 }
 
-pub trait Weaponlike: std::fmt::Debug {
-    type Target: Copy + std::fmt::Debug;
+pub trait Weaponlike: std::fmt::Debug + Serialize + DeserializeOwned {
+    type Target: Copy + std::fmt::Debug + Serialize + DeserializeOwned + MapEntities;
     type Stats: Copy + std::fmt::Debug;
     type Id: Copy + std::fmt::Debug + Into<WeaponId>;
 
@@ -113,12 +172,89 @@ impl Weaponlike for BeamWeapon {
 #[derive(Debug, Clone, Copy)]
 pub struct CommonStats {
     pub name: &'static str,
-    pub damage: usize,
+    pub damage: DamageSpec,
     pub power: usize,
     pub charge_time: f32,
+    pub presentation: Presentation,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+/// How a weapon looks and sounds. Only the client reads this, so none of it touches balance.
+/// Paths are relative to the client's `assets` folder.
+#[derive(Debug, Clone, Copy)]
+pub struct Presentation {
+    /// Image each shot is drawn with. Lasers and beams are stretched out of theirs.
+    pub sprite: &'static str,
+    /// Tint for each shot, as sRGB.
+    pub color: [f32; 3],
+    /// Played each time the weapon fires.
+    pub fire_sound: Option<&'static str>,
+    /// Played when a shot lands on the hull. Beams don't land all at once, so they never play it.
+    pub hit_sound: Option<&'static str>,
+    /// Flash at the mount each time the weapon fires, if any.
+    pub muzzle_flash: Option<MuzzleFlash>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MuzzleFlash {
+    /// As sRGB.
+    pub color: [f32; 3],
+    /// Diameter at its brightest.
+    pub size: f32,
+}
+
+/// `css::RED`, for lasers and beams that do a point of damage.
+const RED: [f32; 3] = [1.0, 0.0, 0.0];
+/// `css::ORANGE_RED`, for lasers and beams that hit harder than that.
+const ORANGE_RED: [f32; 3] = [1.0, 0.27, 0.0];
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+const LASER_FLASH: MuzzleFlash = MuzzleFlash {
+    color: [1.0, 0.8, 0.5],
+    size: 12.0,
+};
+
+/// Everything a shot does when it lands.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DamageSpec {
+    pub hull: usize,
+    /// Damage to the system in the room hit, if any.
+    pub system: usize,
+    /// Health taken from each crew member in the room hit.
+    pub crew: f32,
+    /// Chance in `[0, 1]` of starting a fire in the cell hit.
+    pub fire_chance: f32,
+    /// Chance in `[0, 1]` of breaching the hull in the cell hit.
+    pub breach_chance: f32,
+}
+
+impl DamageSpec {
+    /// The usual damage profile: hull and system take the same damage, and crew lose 15 health
+    /// per point.
+    pub const fn standard(damage: usize) -> Self {
+        Self {
+            hull: damage,
+            system: damage,
+            crew: 15.0 * damage as f32,
+            fire_chance: 0.0,
+            breach_chance: 0.0,
+        }
+    }
+
+    /// What's left of a beam after `layers` of shields soak it up, or `None` if it's stopped
+    /// outright. Shields take away from hull and system damage, and a beam they reduce to nothing
+    /// doesn't hurt crew or start fires either.
+    pub fn through_shields(self, layers: usize) -> Option<Self> {
+        if layers > 0 && layers >= self.hull {
+            return None;
+        }
+        Some(Self {
+            hull: self.hull - layers,
+            system: self.system.saturating_sub(layers),
+            ..self
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub struct ProjectileWeaponId(usize);
 
 impl std::fmt::Debug for ProjectileWeaponId {
@@ -135,7 +271,7 @@ impl std::ops::Deref for ProjectileWeaponId {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub struct BeamWeaponId(usize);
 
 impl std::fmt::Debug for BeamWeaponId {
@@ -152,7 +288,7 @@ impl std::ops::Deref for BeamWeaponId {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WeaponId {
     Projectile(ProjectileWeaponId),
     Beam(BeamWeaponId),
@@ -172,14 +308,47 @@ impl WeaponId {
             WeaponId::Beam(_) => false,
         }
     }
+
+    /// Every weapon there is, projectiles first.
+    pub fn all() -> impl Iterator<Item = WeaponId> {
+        let projectiles =
+            (0..PROJECTILE_WEAPONS.len()).map(|x| Self::Projectile(ProjectileWeaponId(x)));
+        let beams = (0..BEAM_WEAPONS.len()).map(|x| Self::Beam(BeamWeaponId(x)));
+        projectiles.chain(beams)
+    }
+
+    /// The full stat block if this is a projectile weapon.
+    pub fn projectile_stats(&self) -> Option<&'static ProjectileStats> {
+        match self {
+            WeaponId::Projectile(id) => Some(&PROJECTILE_WEAPONS[id.0]),
+            WeaponId::Beam(_) => None,
+        }
+    }
+
+    /// The full stat block if this is a beam weapon.
+    pub fn beam_stats(&self) -> Option<&'static BeamStats> {
+        match self {
+            WeaponId::Projectile(_) => None,
+            WeaponId::Beam(id) => Some(&BEAM_WEAPONS[id.0]),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum WeaponTarget {
     Projectile(RoomTarget),
     Beam(BeamTarget),
 }
 
+impl WeaponTarget {
+    pub fn ship(&self) -> Entity {
+        match self {
+            WeaponTarget::Projectile(target) => target.ship,
+            WeaponTarget::Beam(target) => target.ship,
+        }
+    }
+}
+
 impl MapEntities for WeaponTarget {
     fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
         match self {
@@ -189,68 +358,241 @@ impl MapEntities for WeaponTarget {
     }
 }
 
-const PROJECTILE_WEAPONS: [ProjectileStats; 3] = [
+/// Why a weapon can't be aimed at a room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomTargetError {
+    NotAProjectileWeapon,
+    /// The target ship has no room with that index.
+    NoSuchRoom,
+    /// The target is the firing ship or a teammate, and the weapon isn't allowed to shoot those.
+    Friendly,
+}
+
+impl std::fmt::Display for RoomTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomTargetError::NotAProjectileWeapon => "only projectile weapons can target rooms",
+            RoomTargetError::NoSuchRoom => "target ship has no such room",
+            RoomTargetError::Friendly => "weapon cannot target own or allied ships",
+        }
+        .fmt(f)
+    }
+}
+
+/// Check whether `weapon` may be aimed at `room` on a ship of type `ship_type`. `friendly` is
+/// whether that ship is the firing ship or one of its teammates. The client checks this before
+/// sending a target and the server checks it again before accepting one, so hand-crafted targets
+/// can't get past the server.
+pub fn validate_room_target(
+    weapon: WeaponId,
+    ship_type: usize,
+    room: usize,
+    friendly: bool,
+) -> Result<(), RoomTargetError> {
+    let WeaponId::Projectile(weapon) = weapon else {
+        return Err(RoomTargetError::NotAProjectileWeapon);
+    };
+    if SHIPS.get(ship_type).is_none_or(|x| room >= x.rooms.len()) {
+        return Err(RoomTargetError::NoSuchRoom);
+    }
+    if friendly && !weapon.can_target_self {
+        return Err(RoomTargetError::Friendly);
+    }
+    Ok(())
+}
+
+const PROJECTILE_WEAPONS: [ProjectileStats; 5] = [
     ProjectileStats {
         common: CommonStats {
             name: "Heavy Laser",
-            damage: 2,
+            damage: DamageSpec::standard(2),
             power: 1,
             charge_time: 9.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                color: ORANGE_RED,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: Some(MuzzleFlash {
+                    color: [1.0, 0.7, 0.4],
+                    size: 16.0,
+                }),
+            },
         },
         shot_speed: 0.35,
         volley_size: 1,
         shield_pierce: 0,
+        crit_chance: 0.1,
+        kind: ProjectileKind::Laser,
         uses_missile: false,
         can_target_self: false,
+        spread: 0.0,
     },
     ProjectileStats {
         common: CommonStats {
             name: "Hermes Missiles",
-            damage: 3,
+            damage: DamageSpec::standard(3),
             power: 3,
             charge_time: 14.0,
+            presentation: Presentation {
+                sprite: "missile-1.png",
+                color: WHITE,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: Some(MuzzleFlash {
+                    color: [1.0, 0.85, 0.6],
+                    size: 20.0,
+                }),
+            },
         },
         shot_speed: 0.6,
         volley_size: 1,
         shield_pierce: 5,
+        crit_chance: 0.15,
+        kind: ProjectileKind::Missile,
         uses_missile: true,
         can_target_self: false,
+        spread: 0.0,
     },
     ProjectileStats {
         common: CommonStats {
             name: "Burst Laser Mk I",
-            damage: 1,
+            damage: DamageSpec::standard(1),
             power: 2,
             charge_time: 11.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                color: RED,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: Some(LASER_FLASH),
+            },
         },
         shot_speed: 0.6,
         volley_size: 2,
         shield_pierce: 0,
+        crit_chance: 0.05,
+        kind: ProjectileKind::Laser,
         uses_missile: false,
         can_target_self: false,
+        spread: 0.0,
+    },
+    ProjectileStats {
+        common: CommonStats {
+            name: "Breach Bomb",
+            damage: DamageSpec {
+                hull: 0,
+                system: 1,
+                crew: 0.0,
+                fire_chance: 0.0,
+                breach_chance: 1.0,
+            },
+            power: 2,
+            charge_time: 17.0,
+            presentation: Presentation {
+                sprite: "bomb.png",
+                color: WHITE,
+                fire_sound: None,
+                hit_sound: None,
+                // Bombs are teleported aboard, there's nothing to see leaving the ship
+                muzzle_flash: None,
+            },
+        },
+        shot_speed: 0.5,
+        volley_size: 1,
+        shield_pierce: 5,
+        crit_chance: 0.0,
+        kind: ProjectileKind::Bomb,
+        uses_missile: true,
+        can_target_self: false,
+        spread: 0.0,
+    },
+    ProjectileStats {
+        common: CommonStats {
+            name: "Flak I",
+            damage: DamageSpec::standard(1),
+            power: 2,
+            charge_time: 10.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                color: RED,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: Some(LASER_FLASH),
+            },
+        },
+        shot_speed: 0.5,
+        volley_size: 3,
+        shield_pierce: 0,
+        crit_chance: 0.0,
+        kind: ProjectileKind::Laser,
+        uses_missile: false,
+        can_target_self: false,
+        spread: 60.0,
     },
 ];
 
-const BEAM_WEAPONS: [BeamStats; 2] = [
+const BEAM_WEAPONS: [BeamStats; 3] = [
     BeamStats {
         common: CommonStats {
             name: "Pike Beam",
-            damage: 1,
+            damage: DamageSpec::standard(1),
             power: 2,
             charge_time: 16.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                color: RED,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: None,
+            },
         },
         speed: 0.8,
         length: 170.0,
+        shield_rule: ShieldRule::Soak,
     },
     BeamStats {
         common: CommonStats {
             name: "Halberd Beam",
-            damage: 2,
+            damage: DamageSpec::standard(2),
             power: 3,
             charge_time: 17.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                color: ORANGE_RED,
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: None,
+            },
         },
         speed: 1.0,
         length: 80.0,
+        shield_rule: ShieldRule::PerRoom,
+    },
+    BeamStats {
+        common: CommonStats {
+            name: "Anti-Bio Beam",
+            damage: DamageSpec {
+                hull: 0,
+                system: 0,
+                crew: 60.0,
+                fire_chance: 0.0,
+                breach_chance: 0.0,
+            },
+            power: 2,
+            charge_time: 16.0,
+            presentation: Presentation {
+                sprite: "beam.png",
+                // `css::LIME`, since it only hurts crew
+                color: [0.0, 1.0, 0.0],
+                fire_sound: None,
+                hit_sound: None,
+                muzzle_flash: None,
+            },
+        },
+        speed: 0.8,
+        length: 120.0,
+        shield_rule: ShieldRule::PerRoom,
     },
 ];
 
@@ -259,3 +601,95 @@ pub const HERMES_MISSILES: WeaponId = WeaponId::Projectile(ProjectileWeaponId(1)
 pub const BURST_LASER_MK_I: WeaponId = WeaponId::Projectile(ProjectileWeaponId(2));
 pub const PIKE_BEAM: WeaponId = WeaponId::Beam(BeamWeaponId(0));
 pub const HALBERD_BEAM: WeaponId = WeaponId::Beam(BeamWeaponId(1));
+pub const BREACH_BOMB: WeaponId = WeaponId::Projectile(ProjectileWeaponId(3));
+pub const ANTI_BIO_BEAM: WeaponId = WeaponId::Beam(BeamWeaponId(2));
+pub const FLAK_I: WeaponId = WeaponId::Projectile(ProjectileWeaponId(4));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn room_target_validation() {
+        let rooms = SHIPS[0].rooms.len();
+        assert_eq!(validate_room_target(HEAVY_LASER, 0, 0, false), Ok(()));
+        assert_eq!(
+            validate_room_target(HEAVY_LASER, 0, rooms, false),
+            Err(RoomTargetError::NoSuchRoom)
+        );
+        assert_eq!(
+            validate_room_target(HEAVY_LASER, SHIPS.len(), 0, false),
+            Err(RoomTargetError::NoSuchRoom)
+        );
+        assert_eq!(
+            validate_room_target(HEAVY_LASER, 0, 0, true),
+            Err(RoomTargetError::Friendly)
+        );
+        assert_eq!(
+            validate_room_target(PIKE_BEAM, 0, 0, false),
+            Err(RoomTargetError::NotAProjectileWeapon)
+        );
+    }
+
+    #[test]
+    fn beams_through_shields() {
+        let halberd = HALBERD_BEAM.common().damage;
+        assert_eq!(halberd.through_shields(0), Some(halberd));
+        let weakened = halberd.through_shields(1).unwrap();
+        assert_eq!((weakened.hull, weakened.system), (1, 1));
+        assert_eq!(weakened.crew, halberd.crew);
+        assert_eq!(halberd.through_shields(2), None);
+        // No hull damage to soak up, so any shield at all stops it
+        let anti_bio = ANTI_BIO_BEAM.common().damage;
+        assert_eq!(anti_bio.through_shields(0), Some(anti_bio));
+        assert_eq!(anti_bio.through_shields(1), None);
+    }
+
+    #[test]
+    fn beam_shield_rules() {
+        // A 2 damage beam across four rooms, against each number of layers
+        let hits = |rule: ShieldRule, layers| {
+            let damage = DamageSpec::standard(2);
+            let mut soaked = 0;
+            (0..4)
+                .map(|_| {
+                    rule.apply(damage, layers, &mut soaked)
+                        .map_or(0, |x| x.hull)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hits(ShieldRule::PerRoom, 0), [2, 2, 2, 2]);
+        assert_eq!(hits(ShieldRule::PerRoom, 1), [1, 1, 1, 1]);
+        assert_eq!(hits(ShieldRule::PerRoom, 2), [0, 0, 0, 0]);
+        assert_eq!(hits(ShieldRule::Soak, 0), [2, 2, 2, 2]);
+        assert_eq!(hits(ShieldRule::Soak, 1), [1, 2, 2, 2]);
+        assert_eq!(hits(ShieldRule::Soak, 3), [0, 1, 2, 2]);
+        assert_eq!(hits(ShieldRule::Soak, 8), [0, 0, 0, 0]);
+
+        // Crew-only beams get stopped by any shield whatever the rule
+        let anti_bio = ANTI_BIO_BEAM.common().damage;
+        for rule in [ShieldRule::PerRoom, ShieldRule::Soak] {
+            assert!(rule.apply(anti_bio, 0, &mut 0).is_some());
+            assert!(rule.apply(anti_bio, 1, &mut 0).is_none());
+            assert!(rule.blocks(anti_bio, 1));
+        }
+        // A soaking beam is never blocked outright by shields it can outlast
+        let pike = PIKE_BEAM.beam_stats().unwrap();
+        assert_eq!(pike.shield_rule, ShieldRule::Soak);
+        assert!(!pike.shield_rule.blocks(pike.common.damage, 4));
+    }
+
+    #[test]
+    fn presentation_assets_exist() {
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../client/assets");
+        for weapon in WeaponId::all() {
+            let x = weapon.common().presentation;
+            for path in [Some(x.sprite), x.fire_sound, x.hit_sound]
+                .into_iter()
+                .flatten()
+            {
+                assert!(assets.join(path).exists(), "{weapon:?} needs {path}");
+            }
+        }
+    }
+}
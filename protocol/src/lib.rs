@@ -0,0 +1,218 @@
+//! Public API for writing clients against the server. Everything a client needs to talk to the
+//! server is re-exported here, so bots, TUIs and other alternative frontends only need to depend on
+//! this crate (plus Bevy and replicon, which you'll have anyway).
+//!
+//! # Getting connected
+//!
+//! Add [`RepliconPlugins`](bevy_replicon::prelude::RepliconPlugins),
+//! [`RepliconRenetPlugins`](bevy_replicon_renet::RepliconRenetPlugins) and [`ProtocolPlugin`] to
+//! your app, then call [`connect`] once the app has started (for example from a `Startup` system).
+//! [`resolve_server`] turns a hostname or address typed in by a player into something to connect to.
+//! [`connect`] attaches a [`Handshake`] to the connection so the server can check that you speak
+//! the same protocol: the same [`PROTOCOL_VERSION`], and the same [`ProtocolHash`] of everything
+//! [`ProtocolPlugin`] registers. If you don't, the server disconnects you immediately and
+//! [`ProtocolPlugin`] reports it and exits.
+//!
+//! # What you'll see
+//!
+//! The server is authoritative about everything. Clients never simulate, they just read intel and
+//! send requests:
+//! - Each ship is a replicated entity. Your own ship carries a [`SelfIntel`](intel::SelfIntel),
+//!   and every ship carries a [`ShipIntel`](intel::ShipIntel) pointing at the intel chunk entities
+//!   for that ship. See the [`intel`] module for which chunks you can expect to see and when. Intel
+//!   chunks come and go as visibility changes, so don't assume they stay around.
+//! - Projectiles and beams are replicated entities with [`bullets`] components. Their targets and
+//!   origins reference ship entities, which replicon maps to your local entities for you.
+//! - [`ReadyState`](lobby::ReadyState) is replicated as a resource while in the lobby and removed
+//!   once the game starts. Send [`PlayerReady`](lobby::PlayerReady) to ready up.
+//! - Ships that have been destroyed get a [`Dead`](ship::Dead) component.
+//!
+//! # Invariants
+//!
+//! - The server simulates at a fixed 64 ticks per second. Rates in intel (charge, oxygen, etc.)
+//!   are per-tick unless documented otherwise.
+//! - Requests in [`events`] are validated server-side. Most refused requests (not enough power,
+//!   bad indices, a door path that doesn't exist) come back as a
+//!   [`CommandRejected`](events::CommandRejected) with a reason to show the player, but some (like
+//!   acting on behalf of a dead ship) are still dropped silently. Either way, don't rely on a request
+//!   being applied -- read the next intel update instead.
+//! - Events that reference entities (targets) must use entities you received through replication.
+//! - Everything is registered in a fixed order by [`ProtocolPlugin`]. Client and server must
+//!   register exactly the same set, which is what [`ProtocolHash`] checks. [`PROTOCOL_VERSION`]
+//!   covers changes to what the registered types look like.
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::{
+    netcode::{ClientAuthentication, NetcodeClientTransport},
+    renet::{ConnectionConfig, RenetClient},
+    RenetChannelsExt as _,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::SystemTime,
+};
+
+pub use common::{
+    bullets, events,
+    handshake::{
+        Handshake, PlayerId, PlayerKey, ProtocolHash, ReservationToken, Role, PROTOCOL_VERSION,
+    },
+    intel, lobby, nav, ship, weapon, Crew, CrewTask, DoorState, DEFAULT_PORT, PROTOCOL_ID, RACES,
+};
+
+/// Registers every replicated component, resource and event the server uses. Must be added after
+/// [`RepliconPlugins`].
+pub struct ProtocolPlugin;
+
+impl Plugin for ProtocolPlugin {
+    fn build(&self, app: &mut App) {
+        common::protocol_plugin(app);
+        app.add_systems(Update, report_rejection);
+    }
+}
+
+/// The server turns away clients it won't take by disconnecting them before they've joined, which
+/// is all we get to see of it. Rather than sit there with nothing on screen, say what most likely
+/// happened and quit.
+fn report_rejection(
+    client: Res<RepliconClient>,
+    mut was_connected: Local<bool>,
+    mut joined: Local<bool>,
+    ships: Query<(), With<intel::ShipIntel>>,
+    protocol_hash: Res<ProtocolHash>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if client.is_connected() {
+        *was_connected = true;
+        *joined |= !ships.is_empty();
+    } else if *was_connected && !*joined {
+        *was_connected = false;
+        eprintln!(
+            "The server turned us away before we joined. Either it runs a different version (we \
+            speak protocol {PROTOCOL_VERSION}, {:016x}) or it isn't taking new players right now. \
+            The server log says which.",
+            protocol_hash.0,
+        );
+        exit.send(AppExit::error());
+    }
+}
+
+/// Connect to the server at `server_addr`. Inserts the renet client and netcode transport
+/// resources; replicon takes it from there. The handshake's [`PlayerId`] should be stable across
+/// runs if you want to be able to rejoin a match the server restored from a snapshot. Its
+/// [`ProtocolHash`] is filled in from the app, so [`ProtocolPlugin`] has to be added first.
+pub fn connect(
+    world: &mut World,
+    server_addr: SocketAddr,
+    handshake: Handshake,
+) -> std::io::Result<()> {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    // Only has to be unique among connected clients, so the current time works well enough
+    let client_id = current_time.as_millis() as u64;
+    // An IPv4 socket can't reach an IPv6 server or the other way around, so match the server
+    let local_ip = match server_addr {
+        SocketAddr::V4(_) => IpAddr::from(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::from(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind((local_ip, 0))?;
+    println!("Connecting to {server_addr} from {}.", socket.local_addr()?);
+    let handshake = handshake.with_protocol_hash(*world.resource::<ProtocolHash>());
+    let authentication = client_authentication(server_addr, client_id, handshake);
+    let channels = world.resource::<RepliconChannels>();
+    let client = RenetClient::new(ConnectionConfig {
+        server_channels_config: channels.get_server_configs(),
+        client_channels_config: channels.get_client_configs(),
+        ..default()
+    });
+    let transport = NetcodeClientTransport::new(current_time, authentication, socket)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    world.insert_resource(client);
+    world.insert_resource(transport);
+    Ok(())
+}
+
+/// Look up the server at `host`, which can be a hostname or an IP address, with or without a port
+/// (e.g. `example.com`, `example.com:6000`, `::1`, `[::1]:6000`). Without one, it's
+/// [`DEFAULT_PORT`]. If a hostname resolves to several addresses, the first one wins.
+pub fn resolve_server(host: &str) -> std::io::Result<SocketAddr> {
+    // A bare IPv6 address looks like it has a port on the end, so try plain IPs first
+    if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_PORT));
+    }
+    let mut addrs = match host.to_socket_addrs() {
+        Ok(addrs) => addrs,
+        Err(_) => (host, DEFAULT_PORT).to_socket_addrs()?,
+    };
+    addrs.next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("`{host}` didn't resolve to any address"),
+        )
+    })
+}
+
+/// The server named by a `--server <host>` command line argument, or the local machine if there
+/// isn't one. See [`resolve_server`] for what `host` can look like.
+pub fn server_addr_from_args() -> std::io::Result<SocketAddr> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let host = args
+        .windows(2)
+        .find(|x| x[0] == "--server")
+        .map_or("127.0.0.1", |x| &x[1]);
+    resolve_server(host)
+}
+
+/// This machine's player key, stored in `.ftl-player-key` in the working directory. A new one gets
+/// generated (and saved) the first time this is called. Keep the file private: whoever has it can
+/// play as you. A `--player-key <secret>` command line argument wins over the file, so several
+/// clients can run from the same directory without being taken for one player reconnecting.
+pub fn local_player_key() -> PlayerKey {
+    const PATH: &str = ".ftl-player-key";
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(secret) = args.windows(2).find(|x| x[0] == "--player-key") {
+        return PlayerKey::from_secret(secret[1].as_bytes());
+    }
+    if let Some(key) = std::fs::read(PATH).ok().and_then(|x| x.try_into().ok()) {
+        return PlayerKey(key);
+    }
+    let key = PlayerKey(rand::random());
+    if let Err(e) = std::fs::write(PATH, key.0) {
+        eprintln!("Couldn't save player key to {PATH}: {e}");
+    }
+    key
+}
+
+/// The netcode authentication to use when connecting, with `handshake` packed into the user data.
+/// Use this if you need more control over the transport than [`connect`] gives you, and remember to
+/// give the handshake your app's [`ProtocolHash`].
+pub fn client_authentication(
+    server_addr: SocketAddr,
+    client_id: u64,
+    handshake: Handshake,
+) -> ClientAuthentication {
+    ClientAuthentication::Unsecure {
+        client_id,
+        protocol_id: PROTOCOL_ID,
+        server_addr,
+        user_data: Some(handshake.to_user_data()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_literal_addresses() {
+        let v4 = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), DEFAULT_PORT));
+        assert_eq!(resolve_server("10.0.0.1").unwrap(), v4);
+        let v6 = SocketAddr::from((Ipv6Addr::LOCALHOST, DEFAULT_PORT));
+        assert_eq!(resolve_server("::1").unwrap(), v6);
+        assert_eq!(resolve_server("[::1]").unwrap(), v6);
+        let v6 = SocketAddr::from((Ipv6Addr::LOCALHOST, 6000));
+        assert_eq!(resolve_server("[::1]:6000").unwrap(), v6);
+    }
+}
@@ -0,0 +1,72 @@
+//! Loading [`BalanceConfig`] from a RON file, and optionally reloading it whenever the file changes
+//! so numbers can be tuned mid-match. Anything the file leaves out keeps its default.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bevy::prelude::*;
+use common::balance::BalanceConfig;
+
+/// How often to check whether the balance file changed.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn load_balance(path: &Path) -> Result<BalanceConfig, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    ron::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// The balance file to watch for changes. Only exists if hot reloading is on.
+#[derive(Resource)]
+pub struct BalanceFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+impl BalanceFile {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified_time(&path);
+        Self {
+            path,
+            modified,
+            timer: Timer::new(POLL_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+/// Load the balance file again if it's been saved since we last looked. A file that doesn't parse
+/// leaves the current numbers alone, so a half-finished edit can't break a running match.
+pub fn reload_balance(
+    mut file: ResMut<BalanceFile>,
+    mut balance: ResMut<BalanceConfig>,
+    time: Res<Time>,
+) {
+    file.timer.tick(time.delta());
+    if !file.timer.just_finished() {
+        return;
+    }
+    let modified = modified_time(&file.path);
+    if modified == file.modified {
+        return;
+    }
+    file.modified = modified;
+    match load_balance(&file.path) {
+        Ok(new) => {
+            if balance.set_if_neq(new) {
+                println!("Reloaded balance from {}.", file.path.display());
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "Couldn't reload balance from {}, keeping the old numbers: {e}",
+                file.path.display()
+            );
+        }
+    }
+}
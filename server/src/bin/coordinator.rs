@@ -0,0 +1,84 @@
+//! Ranked queue coordinator, see `server/src/queue.rs`. Needs the `profiles` feature.
+//!
+//! `coordinator --profiles ranked.sqlite [--bind [::]:5002] [--ports 5010-5073]`
+
+use std::{net::SocketAddr, ops::Range, path::PathBuf};
+
+use common::{queue::QUEUE_PORT, DEFAULT_PORT};
+use server::queue::Coordinator;
+
+fn main() {
+    let mut database = None;
+    let mut bind = SocketAddr::from(([0; 16], QUEUE_PORT));
+    let mut ports = DEFAULT_PORT + 10..DEFAULT_PORT + 74;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profiles" => {
+                let Some(path) = args.next() else {
+                    eprintln!("`--profiles` needs a database file.");
+                    return;
+                };
+                database = Some(PathBuf::from(path));
+            }
+            "--bind" => {
+                let Some(addr) = args.next() else {
+                    eprintln!("`--bind` needs an address, e.g. `[::]:{QUEUE_PORT}`.");
+                    return;
+                };
+                match addr.parse() {
+                    Ok(addr) => bind = addr,
+                    Err(e) => {
+                        eprintln!("Bad bind address `{addr}`: {e}");
+                        return;
+                    }
+                }
+            }
+            "--ports" => {
+                let Some(range) = args.next() else {
+                    eprintln!("`--ports` needs a range, e.g. `5010-5073`.");
+                    return;
+                };
+                match parse_ports(&range) {
+                    Some(range) => ports = range,
+                    None => {
+                        eprintln!("Bad port range `{range}`.");
+                        return;
+                    }
+                }
+            }
+            _ => {
+                eprintln!("Unknown argument `{arg}`.");
+                return;
+            }
+        }
+    }
+    let Some(database) = database else {
+        eprintln!("Pass the profile database match servers should share with `--profiles`.");
+        return;
+    };
+    // Match servers are started from the `server` binary built alongside this one
+    let server = std::env::current_exe()
+        .map(|x| x.with_file_name(format!("server{}", std::env::consts::EXE_SUFFIX)));
+    let server = match server {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Couldn't find the server binary: {e}");
+            return;
+        }
+    };
+    match Coordinator::new(bind, database, server, ports) {
+        Ok(mut coordinator) => {
+            println!("Taking queue requests on {bind}.");
+            coordinator.run();
+        }
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+/// `first-last`, inclusive.
+fn parse_ports(range: &str) -> Option<Range<u16>> {
+    let (first, last) = range.split_once('-')?;
+    let (first, last) = (first.parse::<u16>().ok()?, last.parse::<u16>().ok()?);
+    (first <= last).then(|| first..last.saturating_add(1))
+}
@@ -0,0 +1,48 @@
+//! Checks every ship definition for layout mistakes and prints a diagram of each, see
+//! `common/src/ship_check.rs`. Exits with an error if anything's wrong, so it can gate CI.
+//!
+//! `ship_check`
+
+use common::{
+    ship::{Door, SHIPS},
+    ship_check::{check_beam_lengths, check_ship, diagram},
+};
+
+fn main() {
+    let mut failed = false;
+    for (i, ship) in SHIPS.iter().enumerate() {
+        println!("Ship {i}");
+        let problems = check_ship(&ship.into());
+        if !problems.is_empty() {
+            // The diagram needs every cell in a room, so don't try to draw it
+            failed = true;
+            for x in problems {
+                println!("  {x}");
+            }
+            continue;
+        }
+        println!("{}", diagram(ship));
+        for (room, x) in ship.rooms.iter().enumerate() {
+            let system = ship.room_systems[room]
+                .map(|x| x.to_string())
+                .or_else(|| ship.room_subsystems[room].map(|x| x.to_string()))
+                .unwrap_or_else(|| "empty".into());
+            println!("  room {room}: {system}, {:?}", x.cells);
+        }
+        for (door, x) in ship.doors.iter().enumerate() {
+            match *x {
+                Door::Interior(a, b) => println!("  door {door}: {a:?} to {b:?}"),
+                Door::Exterior(cell, dir) => println!("  door {door}: {cell:?} out {dir:?}"),
+            }
+        }
+        let beams = check_beam_lengths(ship);
+        failed |= !beams.is_empty();
+        for x in beams {
+            println!("  {x}");
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    println!("All ships check out.");
+}
@@ -1,18 +1,35 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{f32::consts::TAU, time::Duration};
 
-use bevy::{math::FloatOrd, prelude::*};
-use bevy_replicon::core::replication::Replicated;
+use bevy::{ecs::system::SystemParam, math::FloatOrd, prelude::*};
+use bevy_replicon::prelude::*;
 use common::{
-    bullets::{BeamTarget, FiredFrom, NeedsDodgeTest, Progress, RoomTarget, WeaponDamage},
-    compute_dodge_chance,
-    nav::Cell,
-    ship::SHIPS,
-    util::{intersect, Aabb},
-    weapon::{BeamWeaponId, ProjectileWeaponId},
+    balance::BalanceConfig,
+    bullets::{
+        BeamHits, BeamTarget, FiredFrom, HitQuality, HullImpact, Incidence, NeedsDodgeTest,
+        Progress, ProjectileKind, RoomTarget, ShieldImpact, TraversalSpeed, WeaponDamage,
+        SHIELD_PROGRESS,
+    },
+    fairness::DodgeSeed,
+    journal::MatchEvent,
+    ship::{SubsystemId, SHIPS},
+    weapon::{BeamWeaponId, ProjectileWeaponId, ShieldRule},
 };
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{ship::ShipState, ship_system::ShipSystem};
+use crate::{
+    ship::ShipState,
+    ship_action::{Hit, ShipAction, ShipChanged, ShipEvent},
+    ship_system::ShipSystem,
+    MatchScoped,
+};
+
+/// Drop a shot whose ship is gone, e.g. because the game was reset with the shot still in flight.
+pub fn despawn_orphan(commands: &mut Commands, shot: Entity, ship: Entity) {
+    eprintln!("Despawning shot {shot:?}: ship {ship:?} no longer exists.");
+    commands.entity(shot).despawn();
+}
 
 pub fn bullet_traversal(mut projectiles: Query<(&TraversalSpeed, &mut Progress)>) {
     for (&TraversalSpeed(speed), mut progress) in &mut projectiles {
@@ -20,100 +37,300 @@ pub fn bullet_traversal(mut projectiles: Query<(&TraversalSpeed, &mut Progress)>
     }
 }
 
+/// Where every roll in a match comes from, seeded fresh for each one. Dodge rolls are journaled and
+/// can be checked against the seed, see [`common::fairness`]. Everything else the match leaves to
+/// chance comes from a second stream of the same seed, see [`MatchRng::chance`].
+#[derive(Resource)]
+pub struct MatchRng {
+    pub seed: DodgeSeed,
+    rng: ChaCha8Rng,
+    chance: ChaCha8Rng,
+}
+
+impl Default for MatchRng {
+    fn default() -> Self {
+        let seed = thread_rng().gen();
+        let mut chance = ChaCha8Rng::from_seed(seed);
+        chance.set_stream(1);
+        Self {
+            seed,
+            rng: ChaCha8Rng::from_seed(seed),
+            chance,
+        }
+    }
+}
+
+impl MatchRng {
+    fn roll(&mut self) -> usize {
+        self.rng.gen_range(0..100)
+    }
+
+    /// Crits, where shots land, fires, breaches, hazards and the like. Kept apart from the dodge
+    /// rolls so those still replay from the seed alone.
+    pub fn chance(&mut self) -> &mut ChaCha8Rng {
+        &mut self.chance
+    }
+}
+
 /// Once a projectile reaches a certain point (say, 80% traversal) we need to
 /// check if the ship dodges. At that point, we determine the effective dodge
 /// chance of the target and decide whether the projectile hit. If it hits, we
 /// remove `NeedsDodgeTest` so this system doesn't pick it up again. If it
 /// misses, we simply remove `ShieldPierce` and `Damage` so the projectile
-/// doesn't interact with the shields or hull. Dodge chance is equal to 5% per
-/// unit power in the target's engines subsystem.
+/// doesn't interact with the shields or hull. Dodge chance comes from the
+/// target's engine power, see [`BalanceConfig::dodge_chance`], and is zero
+/// with nobody flying the ship.
 pub fn projectile_test_dodge(
-    projectiles: Query<(Entity, &Progress, &RoomTarget), With<NeedsDodgeTest>>,
+    projectiles: Query<(Entity, &Progress, &RoomTarget, &FiredFrom), With<NeedsDodgeTest>>,
     ships: Query<&ShipState>,
+    balance: Res<BalanceConfig>,
+    mut rng: ResMut<MatchRng>,
+    mut match_events: EventWriter<MatchEvent>,
     mut commands: Commands,
 ) {
-    for (projectile, &progress, target) in &projectiles {
+    for (projectile, &progress, target, fired_from) in &projectiles {
         if *progress < 0.8 {
             continue;
         }
-        let ship = ships.get(target.ship).unwrap();
+        let Ok(ship) = ships.get(target.ship) else {
+            despawn_orphan(&mut commands, projectile, target.ship);
+            continue;
+        };
         let dodge_chance = ship
             .systems
             .engines
             .as_ref()
-            .map(|engines| compute_dodge_chance(engines.current_power()))
+            .filter(|_| ship.subsystems.is_working(SubsystemId::Piloting))
+            .map(|engines| balance.dodge_chance(engines.current_power()))
             .unwrap_or_default();
-        let roll = thread_rng().gen_range(0..100);
+        let roll = rng.roll();
+        match_events.send(MatchEvent::DodgeRoll {
+            attacker: fired_from.ship,
+            target: target.ship,
+            weapon_index: fired_from.weapon_index,
+            chance: dodge_chance,
+            roll,
+        });
         if roll < dodge_chance {
             commands
                 .entity(projectile)
                 .remove::<(WeaponDamage, ShieldPierce)>();
+            match_events.send(MatchEvent::Dodged {
+                attacker: fired_from.ship,
+                target: target.ship,
+            });
         }
         commands.entity(projectile).remove::<NeedsDodgeTest>();
     }
 }
 
+/// A projectile that hasn't been past the shields yet.
+type ShieldBoundShot<'a> = (
+    Entity,
+    &'a Progress,
+    &'a ShieldPierce,
+    &'a RoomTarget,
+    &'a FiredFrom,
+    &'a Incidence,
+    Option<&'a WeaponDamage>,
+);
+
 /// Once a projectile reaches the shields (say, 85% traversal) we decide how it
 /// interacts. The interaction depends on the weapon's shield pierce. If our
 /// shield pierce is higher than the target's shields at this point, we simply
 /// remove the projectile's `ShieldPierce` so this system doesn't pick it up
 /// again. The projectile will continue through to the ship hull. Otherwise, we
 /// need to decrement the target's shield and despawn the projectile.
+///
+/// Before any of that, a super shield gets first go at the projectile. Shield pierce doesn't help
+/// against it: it eats the projectile whole, losing as much strength as the shot would have done
+/// hull damage (at least one).
 pub fn projectile_shield_interact(
-    projectiles: Query<(Entity, &Progress, &ShieldPierce, &RoomTarget)>,
+    projectiles: Query<ShieldBoundShot>,
     mut ships: Query<&mut ShipState>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut impacts: EventWriter<ToClients<ShieldImpact>>,
     mut commands: Commands,
 ) {
-    for (projectile, &progress, &shield_pierce, target) in &projectiles {
-        if *progress < 0.85 {
+    for (projectile, &progress, &shield_pierce, target, fired_from, &incidence, damage) in
+        &projectiles
+    {
+        if *progress < SHIELD_PROGRESS {
             continue;
         }
-        let mut ship = ships.get_mut(target.ship).unwrap();
+        let Ok(mut ship) = ships.get_mut(target.ship) else {
+            despawn_orphan(&mut commands, projectile, target.ship);
+            continue;
+        };
         let Some(shields) = ship.systems.shields.as_mut() else {
             continue;
         };
+        if shields.super_shield > 0 {
+            let hit = damage.map_or(1, |x| x.hull.max(1));
+            shields.super_shield = shields.super_shield.saturating_sub(hit);
+            commands.entity(projectile).despawn();
+            match_events.send(MatchEvent::ShieldHit {
+                attacker: fired_from.ship,
+                target: target.ship,
+            });
+            impacts.send(ToClients {
+                mode: SendMode::Broadcast,
+                event: ShieldImpact {
+                    ship: target.ship,
+                    incidence: *incidence,
+                },
+            });
+            continue;
+        }
         if *shield_pierce >= shields.layers {
             commands.entity(projectile).remove::<ShieldPierce>();
         } else {
             shields.layers -= 1;
             commands.entity(projectile).despawn();
+            match_events.send(MatchEvent::ShieldHit {
+                attacker: fired_from.ship,
+                target: target.ship,
+            });
+            impacts.send(ToClients {
+                mode: SendMode::Broadcast,
+                event: ShieldImpact {
+                    ship: target.ship,
+                    incidence: *incidence,
+                },
+            });
         }
     }
 }
 
+/// A projectile on its way to the hull.
+type HullBoundShot<'a> = (
+    Entity,
+    &'a Progress,
+    &'a RoomTarget,
+    &'a WeaponDamage,
+    &'a FiredFrom,
+    Option<&'a CritChance>,
+);
+
+/// What a hit did, for the combat log and the match journal. Bundled to keep
+/// [`projectile_collide_hull`] within the system parameter limit.
+#[derive(SystemParam)]
+pub struct HitLog<'w> {
+    match_events: EventWriter<'w, MatchEvent>,
+    ship_changes: EventWriter<'w, ShipChanged>,
+}
+
+impl HitLog<'_> {
+    /// Log what a hit by `attacker` did to `target`.
+    pub fn hit(
+        &mut self,
+        attacker: Entity,
+        target: Entity,
+        quality: HitQuality,
+        events: Vec<ShipEvent>,
+    ) {
+        self.match_events.send_batch(
+            events
+                .iter()
+                .filter_map(|x| hit_event(x, attacker, target, quality, false)),
+        );
+        self.ship_changes
+            .send_batch(ShipChanged::all(target, events));
+    }
+}
+
 /// Once a projectile reaches 100% traversal, it impacts the hull. We deal
-/// damage to the target hull and system (if the target room houses a system)
-/// and despawn the projectile.
+/// damage to the target hull (less whatever the armor over that room soaks up)
+/// and system (if the target room houses a system) and despawn the projectile.
+/// Each impact first rolls for a crit or a graze, which scales the damage dealt. Crit chance was
+/// settled when the shot was fired, see [`CritChance`].
 pub fn projectile_collide_hull(
-    projectiles: Query<(Entity, &Progress, &RoomTarget, &WeaponDamage)>,
+    projectiles: Query<HullBoundShot>,
     mut ships: Query<&mut ShipState>,
+    balance: Res<BalanceConfig>,
+    mut rng: ResMut<MatchRng>,
+    mut hit_log: HitLog,
+    mut impacts: EventWriter<ToClients<HullImpact>>,
     mut commands: Commands,
 ) {
-    for (projectile, &progress, target, &damage) in &projectiles {
+    for (projectile, &progress, target, &damage, fired_from, crit_chance) in &projectiles {
         if *progress < 1.0 {
             continue;
         }
+        let crit_chance = crit_chance.map_or(0.0, |x| **x);
+        let rng = rng.chance();
+        let quality = HitQuality::from_roll(rng.gen(), crit_chance, balance.graze_chance);
+        let damage = quality.apply(*damage);
 
-        let mut ship = ships.get_mut(target.ship).unwrap();
-        let ship = ship.as_mut();
-        ship.damage = (ship.damage + *damage).min(ship.max_hull);
+        let Ok(mut ship) = ships.get_mut(target.ship) else {
+            despawn_orphan(&mut commands, projectile, target.ship);
+            continue;
+        };
         commands.entity(projectile).despawn();
-        for crew in &mut ship.crew {
-            let crew_cell = crew.nav_status.current_cell();
-            let crew_room = SHIPS[ship.ship_type].cell_room(crew_cell);
-            if crew_room == target.room {
-                crew.health -= 15.0 * *damage as f32;
-            }
-        }
-        ship.crew.retain(|crew| crew.health > 0.0);
-        if let Some(system) = SHIPS[ship.ship_type].room_systems[target.room] {
-            if let Some(system) = ship.systems.system_mut(system) {
-                system.damage_system(*damage, &mut ship.reactor);
-            }
-        }
+        impacts.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: HullImpact {
+                ship: target.ship,
+                room: target.room,
+                quality,
+            },
+        });
+        // Projectiles aim for a room, so pick which of its cells takes the hit
+        let cells = SHIPS[ship.ship_type].rooms[target.room].cells;
+        let hit = Hit {
+            cell: cells[rng.gen_range(0..cells.len())],
+            hits_room: true,
+            damage,
+            fire_roll: rng.gen(),
+            breach_roll: rng.gen(),
+        };
+        let events = ship.apply(ShipAction::TakeHit(hit)).unwrap_or_default();
+        hit_log.hit(fired_from.ship, target.ship, quality, events);
     }
 }
 
+/// What the combat log makes of `event`, from a hit by `attacker` on `target`.
+fn hit_event(
+    event: &ShipEvent,
+    attacker: Entity,
+    target: Entity,
+    quality: HitQuality,
+    repeat: bool,
+) -> Option<MatchEvent> {
+    Some(match *event {
+        ShipEvent::HullDamaged { room, damage } => MatchEvent::HullHit {
+            attacker,
+            target,
+            room,
+            damage,
+            quality,
+            repeat,
+        },
+        ShipEvent::SystemDamaged {
+            system,
+            damage,
+            destroyed,
+        } => MatchEvent::SystemDamaged {
+            attacker,
+            target,
+            system,
+            damage,
+            destroyed,
+        },
+        ShipEvent::FireStarted { room } => MatchEvent::FireStarted {
+            attacker,
+            target,
+            room,
+        },
+        ShipEvent::CrewDied { ref name } => MatchEvent::CrewDied {
+            ship: target,
+            name: name.clone(),
+            killer: Some(attacker),
+        },
+        _ => return None,
+    })
+}
+
 /// Not sure about this one still, but I don't necessarily want to despawn
 /// projectiles straight away. Instead, we'll let them continue on and ignore
 /// them until they reach 150% traversal and are completely offscreen, then
@@ -130,11 +347,28 @@ pub fn projectile_timeout(
     }
 }
 
+/// A beam partway through its sweep.
+type SweepingBeam<'a> = (
+    Entity,
+    &'a Progress,
+    &'a BeamTarget,
+    &'a WeaponDamage,
+    &'a FiredFrom,
+    &'a mut BeamHits,
+    &'a mut BeamShielding,
+    &'a mut BeamLanded,
+);
+
 pub fn beam_damage(
-    mut beams: Query<(&Progress, &BeamTarget, &WeaponDamage, &mut BeamHits)>,
+    mut beams: Query<SweepingBeam>,
     mut ships: Query<&mut ShipState>,
+    mut rng: ResMut<MatchRng>,
+    mut hit_log: HitLog,
+    mut commands: Commands,
 ) {
-    for (&progress, target, &damage, mut hits) in &mut beams {
+    for (beam, &progress, target, &damage, fired_from, mut hits, mut shielding, mut landed) in
+        &mut beams
+    {
         let Some(next_t) = hits.first_key_value().map(|(&FloatOrd(t), _)| t) else {
             continue;
         };
@@ -143,98 +377,137 @@ pub fn beam_damage(
         } else {
             continue;
         };
-        let mut target = ships.get_mut(target.ship).unwrap();
+        let target_e = target.ship;
+        let Ok(mut target) = ships.get_mut(target_e) else {
+            despawn_orphan(&mut commands, beam, target_e);
+            continue;
+        };
         let target = target.as_mut();
-        let target_ship = &SHIPS[target.ship_type];
-        let shield_layers = target.systems.shields.as_mut().map_or(0, |x| x.layers);
-        let damage = damage.saturating_sub(shield_layers);
-
-        for crew in &mut target.crew {
-            let crew_cell = crew.nav_status.current_cell();
-            let crew_room = target_ship.cell_room(crew_cell);
-            if crew_room == target_ship.cell_room(next_cell) {
-                crew.health -= 15.0 * damage as f32;
-            }
-        }
-        target.crew.retain(|crew| crew.health > 0.0);
-        if let Some(next_room) = next_room {
-            target.damage = (target.damage + damage).min(target.max_hull);
-            if let Some(system) = SHIPS[target.ship_type].room_systems[next_room] {
-                if let Some(system) = target.systems.system_mut(system) {
-                    system.damage_system(damage, &mut target.reactor);
-                }
-            }
+        // Beams burn down a super shield one hit at a time before they can touch anything else
+        if let Some(shields) = target
+            .systems
+            .shields
+            .as_mut()
+            .filter(|x| x.super_shield > 0)
+        {
+            shields.super_shield = shields.super_shield.saturating_sub(damage.hull.max(1));
+            continue;
         }
+        let shield_layers = target.systems.shields.as_mut().map_or(0, |x| x.layers);
+        let BeamShielding { rule, soaked } = shielding.as_mut();
+        let Some(damage) = rule.apply(*damage, shield_layers, soaked) else {
+            continue;
+        };
+
+        let rng = rng.chance();
+        let hit = Hit {
+            cell: next_cell,
+            hits_room: next_room.is_some(),
+            damage,
+            fire_roll: rng.gen(),
+            breach_roll: rng.gen(),
+        };
+        let events = target.apply(ShipAction::TakeHit(hit)).unwrap_or_default();
+        // A beam sweeping over armor or an empty room doesn't make the log
+        let logged = events.iter().filter(|x| {
+            !matches!(
+                x,
+                ShipEvent::HullDamaged { damage: 0, .. }
+                    | ShipEvent::SystemDamaged { damage: 0, .. }
+            )
+        });
+        let hit_hull = logged
+            .clone()
+            .any(|x| matches!(x, ShipEvent::HullDamaged { .. }));
+        hit_log.match_events.send_batch(
+            logged.filter_map(|x| {
+                hit_event(x, fired_from.ship, target_e, HitQuality::Normal, landed.0)
+            }),
+        );
+        landed.0 |= hit_hull;
+        hit_log
+            .ship_changes
+            .send_batch(ShipChanged::all(target_e, events));
+    }
+}
+
+/// Where a shot aimed at `target` on a ship of `ship_type` actually lands: any room within
+/// `spread` of the one aimed at, see [`ProjectileStats::spread`](common::weapon::ProjectileStats).
+pub fn scatter(
+    target: RoomTarget,
+    ship_type: usize,
+    spread: f32,
+    rng: &mut impl Rng,
+) -> RoomTarget {
+    if spread <= 0.0 {
+        return target;
+    }
+    let rooms = SHIPS[ship_type]
+        .rooms_within(target.room, spread)
+        .collect::<Vec<_>>();
+    RoomTarget {
+        room: rooms[rng.gen_range(0..rooms.len())],
+        ..target
     }
 }
 
+pub fn random_incidence(rng: &mut impl Rng) -> Incidence {
+    Incidence(Dir2::new_unchecked(Vec2::from_angle(
+        rng.gen_range(0.0..TAU),
+    )))
+}
+
 #[derive(Bundle)]
 pub struct ProjectileBundle {
     pub replicated: Replicated,
+    pub match_scoped: MatchScoped,
     pub damage: WeaponDamage,
     pub target: RoomTarget,
     pub fired_from: FiredFrom,
     pub traversal_speed: TraversalSpeed,
     pub traversal_progress: Progress,
+    pub incidence: Incidence,
     pub needs_dodge_test: NeedsDodgeTest,
     pub shield_pierce: ShieldPierce,
+    pub crit_chance: CritChance,
+    pub kind: ProjectileKind,
 }
 
 #[derive(Bundle)]
 pub struct BeamBundle {
     pub replicated: Replicated,
+    pub match_scoped: MatchScoped,
     pub damage: WeaponDamage,
     pub target: BeamTarget,
     pub hits: BeamHits,
     pub fired_from: FiredFrom,
     pub traversal_speed: TraversalSpeed,
     pub traversal_progress: Progress,
+    pub incidence: Incidence,
+    pub shielding: BeamShielding,
+    pub landed: BeamLanded,
 }
 
-#[derive(Component, Deref, Debug, Clone, Copy, PartialEq)]
-pub struct TraversalSpeed(pub f32);
-
-#[derive(Component, Deref, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Serialize, Deserialize, Deref, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ShieldPierce(pub usize);
 
-#[derive(Component, Debug, Deref, DerefMut)]
-pub struct BeamHits(BTreeMap<FloatOrd, (Cell, Option<usize>)>);
-
-impl BeamHits {
-    pub fn compute(ship_type: usize, beam_len: f32, target: &BeamTarget) -> Self {
-        let ship = &SHIPS[ship_type];
-        let dir = *target.dir * beam_len;
-        // find an intersection `t` for each cell, sort them, map each one to a room and then filter duplicate rooms
-        let beam_impact_time = |aabb: Aabb| {
-            // Transform aabb into beam space, meaning scale and translate the aabb such that the
-            // beam moves from `(0, 0)` to `(1, 1)`.
-            let aabb = (aabb - target.start).scale_about_origin(1.0 / dir);
-            intersect(0.0..=1.0, aabb.x_range())
-                .and_then(|x| intersect(0.0..=1.0, aabb.y_range()).map(|y| (x, y)))
-                .and_then(move |(x, y)| intersect(x, y))
-                .map(|x| *x.start())
-        };
-        let mut hits = ship
-            .cells()
-            .map(|x| (ship.cell_aabb(x), x))
-            .filter_map(|(aabb, x)| beam_impact_time(aabb).map(|t| (t, x)))
-            .collect::<Vec<_>>();
-        hits.sort_by_key(|(t, _)| FloatOrd(*t));
-        let mut result = BTreeMap::new();
-        for (t, cell) in hits {
-            let room = ship.cell_room(cell);
-            // if we already hit this room, None, else Some(room)
-            let room = result
-                .values()
-                .all(|&(_, x)| x != Some(room))
-                .then_some(room);
-            result.insert(FloatOrd(t), (cell, room));
-        }
-        Self(result)
-    }
+/// Whether a beam has damaged the hull yet, so only its first hull hit counts as the shot landing.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeamLanded(pub bool);
+
+/// How a beam gets past shields, and how many points of its damage they've soaked up so far.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeamShielding {
+    pub rule: ShieldRule,
+    pub soaked: usize,
 }
 
-#[derive(Component)]
+/// Chance for a projectile to crit when it hits the hull, see [`HitQuality`]. The weapon's own
+/// chance, plus [`BalanceConfig::manned_crit_bonus`] if someone was manning weapons when it fired.
+#[derive(Component, Serialize, Deserialize, Default, Deref, Debug, Clone, Copy, PartialEq)]
+pub struct CritChance(pub f32);
+
+#[derive(Component, Serialize, Deserialize)]
 pub struct DelayedProjectile {
     pub remaining: Duration,
     pub weapon: ProjectileWeaponId,
@@ -242,7 +515,7 @@ pub struct DelayedProjectile {
     pub fired_from: FiredFrom,
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize)]
 pub struct DelayedBeam {
     pub remaining: Duration,
     pub weapon: BeamWeaponId,
@@ -0,0 +1,50 @@
+use common::ship::SystemId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reactor::Reactor,
+    ship_system::{boring_add_power, boring_remove_power, PowerContext, ShipSystem, SystemStatus},
+};
+
+/// Brings dead crew back, one at a time, as long as it's powered and in one piece. The queue of
+/// who's waiting lives on [`ShipState`](crate::ship::ShipState) so it survives the bay being
+/// knocked out.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct CloneBay {
+    status: SystemStatus,
+    current_power: usize,
+}
+
+impl CloneBay {
+    /// Whether the bay can make progress on a clone right now.
+    pub fn is_working(&self) -> bool {
+        self.current_power > 0 && self.damage() == 0
+    }
+}
+
+impl ShipSystem for CloneBay {
+    fn system_status(&self) -> SystemStatus {
+        self.status
+    }
+
+    fn system_status_mut(&mut self) -> &mut SystemStatus {
+        &mut self.status
+    }
+
+    fn current_power(&self) -> usize {
+        self.current_power
+    }
+
+    fn add_power(&mut self, reactor: &mut Reactor, _context: PowerContext) {
+        boring_add_power(
+            self.status.max_power(),
+            &mut self.current_power,
+            reactor,
+            SystemId::CloneBay,
+        );
+    }
+
+    fn remove_power(&mut self, reactor: &mut Reactor) {
+        boring_remove_power(&mut self.current_power, reactor, SystemId::CloneBay);
+    }
+}
@@ -0,0 +1,173 @@
+//! Commands typed into the server's terminal. Stdin is read on a background thread so the main
+//! loop never blocks on it; lines get handed over through a channel and handled once per update.
+//!
+//! Commands:
+//! - `save [path]`: save a snapshot of the current match, by default to `snapshots/<time>.json`
+//! - `augment <client id> <augment>`: install an augment (e.g. `automated_reloader`) on a client's
+//!   ship
+//! - `debug`: print tick times, replicated entity counts, and each client's round trip time and
+//!   which intel it can see
+
+use std::{
+    collections::VecDeque,
+    io::BufRead,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::RenetServer;
+use common::{augment::AugmentId, intel::ShipIntel};
+
+use crate::{
+    ship::ShipState,
+    snapshot::{default_snapshot_path, save_snapshot},
+    ClientShips,
+};
+
+#[derive(Resource)]
+pub struct Console(Mutex<Receiver<String>>);
+
+impl Console {
+    pub fn spawn() -> Self {
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self(Mutex::new(receiver))
+    }
+}
+
+pub fn console_commands(world: &mut World) {
+    let lines = world
+        .resource::<Console>()
+        .0
+        .lock()
+        .unwrap()
+        .try_iter()
+        .collect::<Vec<_>>();
+    for line in lines {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("save") => {
+                let path = words
+                    .next()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(default_snapshot_path);
+                save_snapshot(world, &path);
+            }
+            Some("augment") => {
+                let (Some(client), Some(augment)) = (words.next(), words.next()) else {
+                    eprintln!("Usage: augment <client id> <augment>");
+                    continue;
+                };
+                install_augment(world, client, augment);
+            }
+            Some("debug") => print_debug(world),
+            Some(command) => eprintln!("Unknown command `{command}`."),
+            None => {}
+        }
+    }
+}
+
+/// How many fixed ticks [`TickTimes`] keeps around.
+const TICK_SAMPLES: usize = 64;
+
+/// How long the last few fixed ticks took to run, for `debug`.
+#[derive(Resource, Default)]
+pub struct TickTimes {
+    started: Option<Instant>,
+    recent: VecDeque<Duration>,
+}
+
+pub fn start_tick(mut times: ResMut<TickTimes>) {
+    times.started = Some(Instant::now());
+}
+
+pub fn end_tick(mut times: ResMut<TickTimes>) {
+    let Some(started) = times.started.take() else {
+        return;
+    };
+    times.recent.push_front(started.elapsed());
+    times.recent.truncate(TICK_SAMPLES);
+}
+
+fn print_debug(world: &mut World) {
+    if let Some(times) = world.get_resource::<TickTimes>() {
+        let total = times.recent.iter().sum::<Duration>();
+        let average = total / times.recent.len().max(1) as u32;
+        let worst = times.recent.iter().max().copied().unwrap_or_default();
+        println!("Fixed tick: {average:.2?} average, {worst:.2?} worst of the last {TICK_SAMPLES}");
+    }
+    let replicated = world
+        .query_filtered::<(), With<Replicated>>()
+        .iter(world)
+        .count();
+    println!("Replicated entities: {replicated}");
+
+    let intel = world
+        .query::<(Entity, &ShipIntel)>()
+        .iter(world)
+        .map(|(ship, x)| {
+            let entities = [
+                ("crew vision", x.crew_vision),
+                ("interior", x.interior),
+                ("weapon charge", x.weapon_charge),
+                ("enemy weapon charge", x.enemy_weapon_charge),
+                ("systems", x.systems),
+                ("doors", x.doors),
+            ];
+            (ship, entities)
+        })
+        .collect::<Vec<_>>();
+    let server = world.get_resource::<RenetServer>();
+    let client_ships = world.resource::<ClientShips>();
+    for client in world.resource::<ReplicatedClients>().iter() {
+        let id = client.id();
+        let rtt = server
+            .and_then(|x| x.network_info(id.get()).ok())
+            .map_or("unknown".into(), |x| format!("{:.0} ms", x.rtt * 1000.0));
+        let ship = client_ships.get(&id);
+        println!("Client {}: ship {ship:?}, round trip {rtt}", id.get());
+        for (ship, entities) in &intel {
+            let visible = entities
+                .iter()
+                .filter(|&&(_, e)| client.visibility().is_visible(e))
+                .map(|&(name, _)| name)
+                .collect::<Vec<_>>();
+            println!("  sees {ship:?}: {}", visible.join(", "));
+        }
+    }
+}
+
+fn install_augment(world: &mut World, client: &str, augment: &str) {
+    let Ok(client) = client.parse::<u64>() else {
+        eprintln!("`{client}` isn't a client id.");
+        return;
+    };
+    let Ok(augment) = augment.parse::<AugmentId>() else {
+        eprintln!("Unknown augment `{augment}`.");
+        return;
+    };
+    let Some(&ship) = world.resource::<ClientShips>().get(&ClientId::new(client)) else {
+        eprintln!("Client {client} doesn't have a ship.");
+        return;
+    };
+    let Some(mut ship) = world.get_mut::<ShipState>(ship) else {
+        eprintln!("Client {client}'s ship is gone.");
+        return;
+    };
+    ship.install_augment(augment);
+}
@@ -1,214 +1,875 @@
+use std::{collections::HashMap, time::Duration};
+
 use bevy::prelude::*;
 use bevy_replicon::prelude::*;
 use common::{
+    balance::BalanceConfig,
     events::{
-        AdjustPower, CrewStations, MoveWeapon, PowerDir, SetAutofire, SetBeamWeaponTarget,
-        SetCrewGoal, SetDoorsOpen, SetProjectileWeaponTarget, WeaponPower,
+        AdjustPower, CommandRejected, CrewStations, InstallWeapon, MoveWeapon, QueuePower,
+        RepairHull, ReroutePower, SetAutofire, SetBeamWeaponTarget, SetCrewGoal, SetDepowerOrder,
+        SetDoorAutomation, SetDoorsOpen, SetGroupTarget, SetMissileFloor,
+        SetProjectileWeaponTarget, SetRepairPriority, ShipCommand, StoreWeapon, WeaponPower,
     },
-    ship::{Dead, Door, SHIPS},
+    journal::MatchEvent,
+    lobby::Team,
+    ship::{Dead, Door, SubsystemId, SystemId, SHIPS},
+    weapon::{validate_room_target, WeaponId},
+};
+
+use crate::{
+    ship::ShipState,
+    ship_action::{ShipAction, ShipChanged, ShipEvent},
+    ClientShips,
 };
 
-use crate::{ship::ShipState, ClientShips};
+/// How long a power change holds off other clients sharing the ship from touching the same system
+/// or weapon.
+const POWER_CLAIM_TIME: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerSlot {
+    System(SystemId),
+    Weapon(usize),
+}
+
+/// Who last changed power to each system and weapon, and when. Stops a captain and copilot from
+/// undoing each other's power changes every tick: while someone else's claim is fresh, a copilot's
+/// change is dropped. Captains always get their way.
+#[derive(Resource, Default, Debug)]
+pub struct PowerClaims(HashMap<(Entity, PowerSlot), (ClientId, Duration)>);
+
+impl PowerClaims {
+    /// Whether `client` may claim `slot` on `ship`: nobody else has it, or `client` is allowed to
+    /// override them.
+    fn may_claim(
+        &self,
+        client_ships: &ClientShips,
+        client: ClientId,
+        ship: Entity,
+        slot: PowerSlot,
+        now: Duration,
+    ) -> bool {
+        let held_by_other = self
+            .0
+            .get(&(ship, slot))
+            .is_some_and(|&(holder, at)| holder != client && now - at < POWER_CLAIM_TIME);
+        !(held_by_other && client_ships.is_copilot(client))
+    }
+
+    /// Claim `slot` on `ship` for `client`, unless someone else has it and `client` isn't allowed
+    /// to override them. Returns whether the claim went through.
+    fn claim(
+        &mut self,
+        client_ships: &ClientShips,
+        client: ClientId,
+        ship: Entity,
+        slot: PowerSlot,
+        now: Duration,
+    ) -> bool {
+        if !self.may_claim(client_ships, client, ship, slot, now) {
+            return false;
+        }
+        self.0.insert((ship, slot), (client, now));
+        true
+    }
+}
+
+/// Whether `client_id` is allowed to give orders to `ship`. Tells them why not if they aren't.
+pub(crate) fn may_command(
+    client_ships: &ClientShips,
+    rejections: &mut EventWriter<ToClients<CommandRejected>>,
+    client_id: ClientId,
+    ship: Entity,
+) -> bool {
+    let allowed = client_ships.controls(client_id, ship);
+    if !allowed {
+        reject(rejections, client_id, "You don't control that ship");
+    }
+    allowed
+}
+
+/// Carry out `action` on `ship`, recording what changed in the journal, or telling `client_id` why
+/// nothing did. Returns what changed.
+fn carry_out(
+    ship: &mut ShipState,
+    ship_e: Entity,
+    action: ShipAction,
+    client_id: ClientId,
+    ship_changes: &mut EventWriter<ShipChanged>,
+    rejections: &mut EventWriter<ToClients<CommandRejected>>,
+) -> Vec<ShipEvent> {
+    match ship.apply(action) {
+        Ok(events) => {
+            ship_changes.send_batch(ShipChanged::all(ship_e, events.clone()));
+            events
+        }
+        Err(reason) => {
+            reject(rejections, client_id, reason);
+            Vec::new()
+        }
+    }
+}
+
+/// Tell `client_id` their command was refused and why, and log it here too.
+pub(crate) fn reject(
+    rejections: &mut EventWriter<ToClients<CommandRejected>>,
+    client_id: ClientId,
+    reason: impl Into<String>,
+) {
+    let reason = reason.into();
+    eprintln!("Rejecting command from {client_id:?}: {reason}.");
+    rejections.send(ToClients {
+        mode: SendMode::Direct(client_id),
+        event: CommandRejected { reason },
+    });
+}
 
 pub fn adjust_power(
-    mut events: EventReader<FromClient<AdjustPower>>,
+    mut events: EventReader<FromClient<ShipCommand<AdjustPower>>>,
     client_ships: Res<ClientShips>,
+    mut claims: ResMut<PowerClaims>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    time: Res<Time>,
 ) {
     for &FromClient {
         client_id,
-        event: AdjustPower { dir, system },
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let AdjustPower { dir, system } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        let slot = PowerSlot::System(system);
+        if !claims.claim(&client_ships, client_id, client_ship, slot, time.elapsed()) {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("{system} was just adjusted by your captain"),
+            );
+            continue;
+        }
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::AdjustPower { system, dir },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn queue_power(
+    mut events: EventReader<FromClient<ShipCommand<QueuePower>>>,
+    client_ships: Res<ClientShips>,
+    mut claims: ResMut<PowerClaims>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    time: Res<Time>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let QueuePower { system, queued } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        match dir {
-            PowerDir::Request => ship.request_power(system),
-            PowerDir::Remove => ship.remove_power(system),
+        let slot = PowerSlot::System(system);
+        if !claims.claim(&client_ships, client_id, client_ship, slot, time.elapsed()) {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("{system} was just adjusted by your captain"),
+            );
+            continue;
         }
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::QueuePower { system, queued },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
     }
 }
 
-pub fn weapon_power(
-    mut events: EventReader<FromClient<WeaponPower>>,
+/// Rerouting touches two systems, so it needs a claim on both, same as adjusting each of them would.
+pub fn reroute_power(
+    mut events: EventReader<FromClient<ShipCommand<ReroutePower>>>,
     client_ships: Res<ClientShips>,
+    mut claims: ResMut<PowerClaims>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    time: Res<Time>,
 ) {
     for &FromClient {
         client_id,
-        event: WeaponPower {
-            dir,
-            weapon_index: index,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
         },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let ReroutePower { from, to } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        let now = time.elapsed();
+        // Check both before claiming either, so a refused reroute doesn't hold on to `from`
+        let slots = [from, to].map(PowerSlot::System);
+        let claimable = slots
+            .iter()
+            .all(|&slot| claims.may_claim(&client_ships, client_id, client_ship, slot, now));
+        if !claimable {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("{from} or {to} was just adjusted by your captain"),
+            );
+            continue;
+        }
+        for slot in slots {
+            claims.claim(&client_ships, client_id, client_ship, slot, now);
+        }
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::ReroutePower { from, to },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn weapon_power(
+    mut events: EventReader<FromClient<ShipCommand<WeaponPower>>>,
+    client_ships: Res<ClientShips>,
+    mut claims: ResMut<PowerClaims>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    time: Res<Time>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let WeaponPower {
+            dir,
+            weapon_index: index,
+        } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        match dir {
-            PowerDir::Request => ship.power_weapon(index),
-            PowerDir::Remove => ship.depower_weapon(index),
+        let slot = PowerSlot::Weapon(index);
+        if !claims.claim(&client_ships, client_id, client_ship, slot, time.elapsed()) {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("Weapon {} was just adjusted by your captain", index + 1),
+            );
+            continue;
         }
+        let action = ShipAction::WeaponPower {
+            weapon_index: index,
+            dir,
+        };
+        carry_out(
+            &mut ship,
+            client_ship,
+            action,
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
     }
 }
 
+/// `RoomTargetError` reads as the tail end of a log line, so start it with a capital for a toast.
+fn capitalize(reason: &str) -> String {
+    let mut chars = reason.chars();
+    chars
+        .next()
+        .map(|x| x.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Why a target was turned down for being out of the weapon mount's reach.
+const OUT_OF_ARC: &str = "target is outside that weapon's firing arc";
+
+/// Whether `target` is `ship` itself or one of its teammates.
+pub(crate) fn is_friendly(teams: &Query<&Team>, ship: Entity, target: Entity) -> bool {
+    ship == target || matches!((teams.get(ship), teams.get(target)), (Ok(a), Ok(b)) if a == b)
+}
+
 pub fn set_projectile_weapon_target(
-    mut events: EventReader<FromClient<SetProjectileWeaponTarget>>,
+    mut events: EventReader<FromClient<ShipCommand<SetProjectileWeaponTarget>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    teams: Query<&Team>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
 ) {
     for &FromClient {
         client_id,
-        event: SetProjectileWeaponTarget {
-            weapon_index,
-            target,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
         },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let SetProjectileWeaponTarget {
+            weapon_index,
+            target,
+        } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
             continue;
+        }
+        let target_type = match target.map(|x| ships.get(x.ship)) {
+            Some(Ok(target_ship)) => Some(target_ship.ship_type),
+            Some(Err(_)) => {
+                reject(&mut rejections, client_id, "That ship's been destroyed");
+                continue;
+            }
+            None => None,
         };
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        let targeting_self = target.map(|x| x.ship == client_ship).unwrap_or_default();
-        ship.set_projectile_weapon_target(weapon_index, target, targeting_self);
+        if let (Some(target), Some(target_type)) = (target, target_type) {
+            let Some(weapon) = ship
+                .systems
+                .weapons
+                .as_ref()
+                .and_then(|x| x.weapons().get(weapon_index))
+                .map(|x| x.weapon())
+            else {
+                let reason = format!("No weapon in slot {}", weapon_index + 1);
+                reject(&mut rejections, client_id, reason);
+                continue;
+            };
+            let friendly = is_friendly(&teams, client_ship, target.ship);
+            if let Err(e) = validate_room_target(weapon, target_type, target.room, friendly) {
+                reject(&mut rejections, client_id, capitalize(&e.to_string()));
+                continue;
+            }
+            let center = SHIPS[target_type].room_center(target.room);
+            if !friendly && !SHIPS[ship.ship_type].mount_covers(weapon_index, center) {
+                reject(&mut rejections, client_id, capitalize(OUT_OF_ARC));
+                continue;
+            }
+        }
+        ship.set_projectile_weapon_target(weapon_index, target);
     }
 }
 
 pub fn set_beam_weapon_target(
-    mut events: EventReader<FromClient<SetBeamWeaponTarget>>,
+    mut events: EventReader<FromClient<ShipCommand<SetBeamWeaponTarget>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    teams: Query<&Team>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
 ) {
     for &FromClient {
         client_id,
-        event: SetBeamWeaponTarget {
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let SetBeamWeaponTarget {
             weapon_index,
             target,
+        } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        if target.is_some_and(|x| !ships.contains(x.ship)) {
+            reject(&mut rejections, client_id, "That ship's been destroyed");
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        if target.is_some_and(|x| is_friendly(&teams, client_ship, x.ship)) {
+            reject(
+                &mut rejections,
+                client_id,
+                "Beams can't target your own or allied ships",
+            );
+            continue;
+        }
+        if target.is_some_and(|x| !SHIPS[ship.ship_type].mount_covers(weapon_index, x.start)) {
+            reject(&mut rejections, client_id, capitalize(OUT_OF_ARC));
+            continue;
+        }
+        ship.set_beam_weapon_target(weapon_index, target);
+    }
+}
+
+pub fn set_group_target(
+    mut events: EventReader<FromClient<ShipCommand<SetGroupTarget>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    teams: Query<&Team>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+) {
+    for FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
         },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let SetGroupTarget { weapons, target } = command;
+        let (client_id, client_ship) = (*client_id, *client_ship);
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
             continue;
+        }
+        let target = match target.map(|x| ships.get(x.ship).map(|ship| (x, ship.ship_type))) {
+            Some(Ok(target)) => Some(target),
+            Some(Err(_)) => {
+                reject(&mut rejections, client_id, "That ship's been destroyed");
+                continue;
+            }
+            None => None,
         };
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        if let Some(target) = target {
-            if target.ship == client_ship {
-                eprintln!("Beams cannot target own ship.");
+        let Some(installed) = ship
+            .systems
+            .weapons
+            .as_ref()
+            .map(|x| x.weapons().iter().map(|x| x.weapon()).collect::<Vec<_>>())
+        else {
+            eprintln!("Can't set group target, weapons system not installed.");
+            continue;
+        };
+        let friendly = target.is_some_and(|(x, _)| is_friendly(&teams, client_ship, x.ship));
+        for &weapon_index in weapons {
+            let Some(&weapon) = installed.get(weapon_index) else {
+                eprintln!("Skipping group weapon {weapon_index}, no weapon in that slot.");
                 continue;
+            };
+            match (weapon, target) {
+                (WeaponId::Projectile(_), Some((target, target_type))) => {
+                    if let Err(e) = validate_room_target(weapon, target_type, target.room, friendly)
+                    {
+                        let name = weapon.common().name;
+                        reject(&mut rejections, client_id, format!("{name}: {e}"));
+                        continue;
+                    }
+                    let center = SHIPS[target_type].room_center(target.room);
+                    if !friendly && !SHIPS[ship.ship_type].mount_covers(weapon_index, center) {
+                        let name = weapon.common().name;
+                        reject(&mut rejections, client_id, format!("{name}: {OUT_OF_ARC}"));
+                        continue;
+                    }
+                    ship.set_projectile_weapon_target(weapon_index, Some(target));
+                }
+                (WeaponId::Projectile(_), None) => {
+                    ship.set_projectile_weapon_target(weapon_index, None);
+                }
+                (WeaponId::Beam(_), None) => ship.set_beam_weapon_target(weapon_index, None),
+                (WeaponId::Beam(_), Some(_)) => {}
             }
         }
-        ship.set_beam_weapon_target(weapon_index, target);
     }
 }
 
 pub fn move_weapon(
-    mut events: EventReader<FromClient<MoveWeapon>>,
+    mut events: EventReader<FromClient<ShipCommand<MoveWeapon>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
 ) {
     for &FromClient {
         client_id,
-        event: MoveWeapon {
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let MoveWeapon {
+            weapon_index,
+            target_index,
+        } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        let action = ShipAction::MoveWeapon {
             weapon_index,
             target_index,
+        };
+        carry_out(
+            &mut ship,
+            client_ship,
+            action,
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn install_weapon(
+    mut events: EventReader<FromClient<ShipCommand<InstallWeapon>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
         },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let InstallWeapon { cargo_index, slot } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::InstallWeapon { cargo_index, slot },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn store_weapon(
+    mut events: EventReader<FromClient<ShipCommand<StoreWeapon>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let StoreWeapon { weapon_index } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        ship.move_weapon(weapon_index, target_index);
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::StoreWeapon { weapon_index },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
     }
 }
 
 pub fn set_crew_goal(
-    mut events: EventReader<FromClient<SetCrewGoal>>,
+    mut events: EventReader<FromClient<ShipCommand<SetCrewGoal>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
 ) {
     for &FromClient {
         client_id,
-        event: SetCrewGoal {
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let SetCrewGoal {
+            crew,
+            room: target_room,
+        } = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        let action = ShipAction::SetCrewGoal {
             crew,
             room: target_room,
+        };
+        carry_out(
+            &mut ship,
+            client_ship,
+            action,
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn set_autofire(
+    mut events: EventReader<FromClient<ShipCommand<SetAutofire>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
         },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let SetAutofire(autofire) = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::SetAutofire(autofire),
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn set_missile_floor(
+    mut events: EventReader<FromClient<ShipCommand<SetMissileFloor>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let SetMissileFloor(floor) = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        ship.set_crew_goal(crew, target_room);
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::SetMissileFloor(floor),
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
     }
 }
 
-pub fn set_autofire(
-    mut events: EventReader<FromClient<SetAutofire>>,
+pub fn set_depower_order(
+    mut events: EventReader<FromClient<ShipCommand<SetDepowerOrder>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
 ) {
     for &FromClient {
         client_id,
-        event: SetAutofire(autofire),
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let SetDepowerOrder(order) = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::SetDepowerOrder(order),
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn set_repair_priority(
+    mut events: EventReader<FromClient<ShipCommand<SetRepairPriority>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for FromClient {
+        client_id,
+        event:
+            ShipCommand {
+                ship: client_ship,
+                command: SetRepairPriority(priority),
+            },
     } in events.read()
     {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+        let (client_id, client_ship) = (*client_id, *client_ship);
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::SetRepairPriority(priority.clone()),
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+    }
+}
+
+pub fn set_door_automation(
+    mut events: EventReader<FromClient<ShipCommand<SetDoorAutomation>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command,
+        },
+    } in events.read()
+    {
+        let SetDoorAutomation(automation) = command;
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
-        ship.set_autofire(autofire);
+        carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::SetDoorAutomation(automation),
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
     }
 }
 
 pub fn set_doors_open(
-    mut events: EventReader<FromClient<SetDoorsOpen>>,
+    mut events: EventReader<FromClient<ShipCommand<SetDoorsOpen>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
 ) {
-    for &FromClient { client_id, event } in events.read() {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command: event,
+        },
+    } in events.read()
+    {
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
             continue;
-        };
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
         };
+        if !ship.subsystems.is_working(SubsystemId::Doors) {
+            reject(&mut rejections, client_id, "Door control is destroyed");
+            continue;
+        }
         match event {
             SetDoorsOpen::Single { door, open } => {
-                ship.doors[door].open = open;
+                let Some(door) = ship.doors.get_mut(door) else {
+                    reject(&mut rejections, client_id, format!("No door {door}"));
+                    continue;
+                };
+                door.open = open;
             }
             SetDoorsOpen::All { open } => {
                 if open {
@@ -233,20 +894,34 @@ pub fn set_doors_open(
                     }
                 }
             }
+            SetDoorsOpen::VentUnoccupied => ship.vent_unoccupied(),
+            SetDoorsOpen::OpenPath { from, to } => {
+                if let Err(reason) = ship.open_door_path(from, to) {
+                    reject(&mut rejections, client_id, reason);
+                }
+            }
+            SetDoorsOpen::CloseExterior => ship.close_exterior_doors(),
         }
     }
 }
 
 pub fn crew_stations(
-    mut events: EventReader<FromClient<CrewStations>>,
+    mut events: EventReader<FromClient<ShipCommand<CrewStations>>>,
     client_ships: Res<ClientShips>,
     mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
 ) {
-    for &FromClient { client_id, event } in events.read() {
-        let Some(&client_ship) = client_ships.get(&client_id) else {
-            eprintln!("No ship entry for client {client_id:?}.");
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship,
+            command: event,
+        },
+    } in events.read()
+    {
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
             continue;
-        };
+        }
         let Ok(mut ship) = ships.get_mut(client_ship) else {
             eprintln!("Entity {client_ship:?} is not a ship.");
             continue;
@@ -258,6 +933,54 @@ pub fn crew_stations(
             CrewStations::Return => {
                 ship.crew_return_to_stations();
             }
+            CrewStations::AutoReturn(enabled) => {
+                ship.auto_return = enabled;
+            }
+        }
+    }
+}
+
+pub fn repair_hull(
+    mut events: EventReader<FromClient<ShipCommand<RepairHull>>>,
+    client_ships: Res<ClientShips>,
+    balance: Res<BalanceConfig>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    mut match_events: EventWriter<MatchEvent>,
+) {
+    for &FromClient {
+        client_id,
+        event: ShipCommand {
+            ship: client_ship, ..
+        },
+    } in events.read()
+    {
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        let events = carry_out(
+            &mut ship,
+            client_ship,
+            ShipAction::RepairHull {
+                amount: balance.hull_repair_amount,
+                cooldown: balance.hull_repair_cooldown,
+            },
+            client_id,
+            &mut ship_changes,
+            &mut rejections,
+        );
+        for event in events {
+            if let ShipEvent::HullRepaired { amount } = event {
+                match_events.send(MatchEvent::HullRepaired {
+                    ship: client_ship,
+                    amount,
+                });
+            }
         }
     }
 }
@@ -0,0 +1,204 @@
+//! Environmental hazards from [`HazardState`]. Each one is a system that only acts while its hazard
+//! is enabled, reusing the normal ship and projectile machinery to do its damage.
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use common::{
+    bullets::{
+        FiredFrom, HitQuality, NeedsDodgeTest, Progress, ProjectileKind, RoomTarget,
+        TraversalSpeed, WeaponDamage,
+    },
+    hazard::{Hazard, HazardState, ToggleHazard},
+    lobby::ReadyState,
+    nav::Cell,
+    ship::{Dead, SHIPS},
+    weapon::DamageSpec,
+};
+use rand::Rng;
+
+use crate::{
+    bullets::{random_incidence, CritChance, HitLog, MatchRng, ProjectileBundle, ShieldPierce},
+    ship::ShipState,
+    ship_action::{Hit, ShipAction},
+    MatchScoped,
+};
+
+/// Seconds between ion storm surges.
+const ION_STORM_CALM: f32 = 20.0;
+/// How long each surge holds reactors down, in seconds.
+const ION_STORM_SURGE: f32 = 6.0;
+/// Range of seconds between asteroid strikes.
+const ASTEROID_INTERVAL: std::ops::Range<f32> = 8.0..16.0;
+const ASTEROID_SPEED: f32 = 0.4;
+/// Range of seconds between solar flares.
+const SOLAR_FLARE_INTERVAL: std::ops::Range<f32> = 10.0..20.0;
+
+/// Stands in as the ship that "fired" hazard projectiles, so they fit through the same pipeline as
+/// weapon fire without crediting any player.
+#[derive(Component)]
+pub struct Environment;
+
+pub fn toggle_hazard(
+    mut events: EventReader<FromClient<ToggleHazard>>,
+    mut ready_state: ResMut<ReadyState>,
+    mut hazards: ResMut<HazardState>,
+) {
+    for &FromClient {
+        client_id,
+        event: ToggleHazard(hazard),
+    } in events.read()
+    {
+        let ReadyState::AwaitingClients { ready_clients } = ready_state.as_mut() else {
+            eprintln!("Discarding hazard change from {client_id:?}, game is already starting.");
+            continue;
+        };
+        if let Some(index) = hazards.enabled.iter().position(|&x| x == hazard) {
+            hazards.enabled.remove(index);
+        } else {
+            hazards.enabled.push(hazard);
+        }
+        // Nobody should end up in a match with hazards they didn't agree to
+        ready_clients.clear();
+    }
+}
+
+/// Cycle between calm and surges, knocking half of every reactor offline for each surge.
+pub fn ion_storm(
+    mut hazards: ResMut<HazardState>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut elapsed: Local<f32>,
+    time: Res<Time>,
+) {
+    let surge = if hazards.is_enabled(Hazard::IonStorm) {
+        *elapsed = (*elapsed + time.delta_secs()) % (ION_STORM_CALM + ION_STORM_SURGE);
+        *elapsed >= ION_STORM_CALM
+    } else {
+        *elapsed = 0.0;
+        false
+    };
+    if hazards.ion_surge == surge {
+        return;
+    }
+    hazards.ion_surge = surge;
+    for mut ship in &mut ships {
+        let bars = if surge {
+            ship.reactor.upgrade_level / 2
+        } else {
+            0
+        };
+        ship.set_ionized_power(bars);
+    }
+}
+
+/// Every so often, send an asteroid at a random room of every ship still in the fight.
+pub fn asteroid_field(
+    hazards: Res<HazardState>,
+    ships: Query<(Entity, &ShipState), Without<Dead>>,
+    environment: Single<Entity, With<Environment>>,
+    mut next_strike: Local<Option<f32>>,
+    mut rng: ResMut<MatchRng>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    if !hazards.is_enabled(Hazard::AsteroidField) {
+        *next_strike = None;
+        return;
+    }
+    let rng = rng.chance();
+    let remaining = next_strike.get_or_insert_with(|| rng.gen_range(ASTEROID_INTERVAL));
+    *remaining -= time.delta_secs();
+    if *remaining > 0.0 {
+        return;
+    }
+    *next_strike = None;
+    for (ship, state) in &ships {
+        let room = rng.gen_range(0..SHIPS[state.ship_type].rooms.len());
+        commands.spawn(ProjectileBundle {
+            replicated: Replicated,
+            match_scoped: MatchScoped,
+            damage: WeaponDamage(DamageSpec::standard(1)),
+            target: RoomTarget { ship, room },
+            fired_from: FiredFrom {
+                ship: *environment,
+                weapon_index: 0,
+            },
+            traversal_speed: TraversalSpeed(ASTEROID_SPEED),
+            // Asteroids come from nowhere in particular, so skip the half of the trip spent
+            // leaving a ship
+            traversal_progress: Progress(0.5),
+            incidence: random_incidence(rng),
+            needs_dodge_test: NeedsDodgeTest,
+            shield_pierce: ShieldPierce(0),
+            crit_chance: CritChance(0.0),
+            kind: ProjectileKind::Asteroid,
+        });
+    }
+}
+
+/// Every so often, set a random cell of every ship still in the fight on fire.
+pub fn solar_flare(
+    hazards: Res<HazardState>,
+    mut ships: Query<(Entity, &mut ShipState), Without<Dead>>,
+    environment: Single<Entity, With<Environment>>,
+    mut next_flare: Local<Option<f32>>,
+    mut rng: ResMut<MatchRng>,
+    time: Res<Time>,
+    mut hit_log: HitLog,
+) {
+    if !hazards.is_enabled(Hazard::SolarFlare) {
+        *next_flare = None;
+        return;
+    }
+    let rng = rng.chance();
+    let remaining = next_flare.get_or_insert_with(|| rng.gen_range(SOLAR_FLARE_INTERVAL));
+    *remaining -= time.delta_secs();
+    if *remaining > 0.0 {
+        return;
+    }
+    *next_flare = None;
+    for (ship, mut state) in &mut ships {
+        let cell = Cell(rng.gen_range(0..state.cells.len()));
+        let events = state
+            .apply(ShipAction::TakeHit(flare(cell)))
+            .unwrap_or_default();
+        hit_log.hit(*environment, ship, HitQuality::Normal, events);
+    }
+}
+
+/// A flare landing on `cell`: it catches fire, and that's all.
+fn flare(cell: Cell) -> Hit {
+    Hit {
+        cell,
+        hits_room: false,
+        damage: DamageSpec {
+            hull: 0,
+            system: 0,
+            crew: 0.0,
+            fire_chance: 1.0,
+            breach_chance: 0.0,
+        },
+        fire_roll: 0.0,
+        breach_roll: 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ship_action::ShipEvent;
+
+    #[test]
+    fn flares_only_start_fires() {
+        let mut ship = ShipState::new();
+        let cell = Cell(0);
+        let room = SHIPS[ship.ship_type].cell_room(cell);
+        let events = ship.apply(ShipAction::TakeHit(flare(cell))).unwrap();
+        assert_eq!(events, vec![ShipEvent::FireStarted { room }]);
+        assert!(ship.cells[0].on_fire);
+        assert!(!ship.cells[0].breached);
+        assert_eq!(ship.damage, 0);
+        // A cell that's already burning can't catch fire again
+        let events = ship.apply(ShipAction::TakeHit(flare(cell))).unwrap();
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Forfeits ships nobody's playing, so one player walking away can't hold a public server hostage.
+//! A ship counts as active while anyone aboard is giving it commands or its crew are on the move.
+
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use common::{
+    events::{
+        AdjustPower, CommandEvent, CrewStations, IdleWarning, InstallWeapon, LaunchShuttle,
+        MoveWeapon, QueuePower, RepairHull, ReroutePower, SetAutofire, SetBeamWeaponTarget,
+        SetCrewGoal, SetDepowerOrder, SetDoorAutomation, SetDoorsOpen, SetGroupTarget,
+        SetMissileFloor, SetProjectileWeaponTarget, SetRepairPriority, ShipCommand, StoreWeapon,
+        WeaponPower,
+    },
+    journal::MatchEvent,
+    lobby::{match_outcome, MatchOutcome, Team},
+    nav::CrewNavStatus,
+    ship::{Dead, Destroyed},
+};
+
+use crate::{ship::ShipState, ClientShips};
+
+/// Idle time before the players aboard start getting warned.
+const IDLE_WARNING: Duration = Duration::from_secs(120);
+/// Idle time before the ship forfeits.
+const IDLE_FORFEIT: Duration = Duration::from_secs(180);
+
+/// Keeps [`LastCommand`] up to date with every kind of ship command.
+pub fn idle_plugin(app: &mut App) {
+    app.init_resource::<LastCommand>().add_systems(
+        PreUpdate,
+        (
+            (
+                note_commands::<AdjustPower>,
+                note_commands::<WeaponPower>,
+                note_commands::<ReroutePower>,
+                note_commands::<QueuePower>,
+                note_commands::<SetDepowerOrder>,
+            ),
+            (
+                note_commands::<SetProjectileWeaponTarget>,
+                note_commands::<SetBeamWeaponTarget>,
+                note_commands::<SetGroupTarget>,
+                note_commands::<MoveWeapon>,
+                note_commands::<InstallWeapon>,
+                note_commands::<StoreWeapon>,
+                note_commands::<SetAutofire>,
+                note_commands::<SetMissileFloor>,
+                note_commands::<LaunchShuttle>,
+            ),
+            (
+                note_commands::<SetCrewGoal>,
+                note_commands::<CrewStations>,
+                note_commands::<SetDoorsOpen>,
+                note_commands::<SetDoorAutomation>,
+                note_commands::<RepairHull>,
+                note_commands::<SetRepairPriority>,
+            ),
+        )
+            .after(ServerSet::Receive)
+            .run_if(server_or_singleplayer),
+    );
+}
+
+/// When each client last gave a ship a command, in real time.
+#[derive(Resource, Deref, Debug, Default)]
+pub struct LastCommand(HashMap<ClientId, Duration>);
+
+fn note_commands<C: CommandEvent>(
+    mut events: EventReader<FromClient<ShipCommand<C>>>,
+    mut last: ResMut<LastCommand>,
+    time: Res<Time<Real>>,
+) {
+    for &FromClient { client_id, .. } in events.read() {
+        last.0.insert(client_id, time.elapsed());
+    }
+}
+
+/// When each ship in the match was last active, in real time, and the last countdown its players
+/// were warned with.
+#[derive(Resource, Default)]
+pub struct IdleShips(HashMap<Entity, (Duration, Option<u32>)>);
+
+/// Who's aboard each ship and when they last gave a command, in real time. Bundled to keep
+/// [`check_idle`] within the system parameter limit.
+#[derive(SystemParam)]
+pub struct Activity<'w> {
+    last_command: Res<'w, LastCommand>,
+    client_ships: Res<'w, ClientShips>,
+    time: Res<'w, Time<Real>>,
+}
+
+pub fn check_idle(
+    mut idle: ResMut<IdleShips>,
+    activity: Activity,
+    ships: Query<(Entity, &ShipState), Without<Dead>>,
+    teams: Query<(&Team, Has<Dead>), With<ShipState>>,
+    mut warnings: EventWriter<ToClients<IdleWarning>>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+) {
+    if match_outcome(teams.iter().map(|(&team, dead)| (team, dead))) != MatchOutcome::Ongoing {
+        return;
+    }
+    let Activity {
+        last_command,
+        client_ships,
+        time,
+    } = activity;
+    let now = time.elapsed();
+    for (e, ship) in &ships {
+        let (last_active, warned) = idle.0.entry(e).or_insert((now, None));
+        let aboard = client_ships
+            .iter()
+            .filter(|&(_, &x)| x == e)
+            .map(|(&client, _)| client)
+            .collect::<Vec<_>>();
+        if let Some(&commanded) = aboard.iter().filter_map(|x| last_command.get(x)).max() {
+            *last_active = (*last_active).max(commanded);
+        }
+        let crew_moving = ship
+            .crew
+            .iter()
+            .any(|x| matches!(x.nav_status, CrewNavStatus::Navigating(_)));
+        if crew_moving {
+            *last_active = now;
+        }
+
+        let idle_for = now.saturating_sub(*last_active);
+        if idle_for >= IDLE_FORFEIT {
+            println!("Ship {e:?} forfeits after {}s idle.", idle_for.as_secs());
+            commands.entity(e).insert((Dead, Destroyed::default()));
+            match_events.send(MatchEvent::ShipDestroyed { ship: e });
+        } else if idle_for >= IDLE_WARNING {
+            let forfeit_in = (IDLE_FORFEIT - idle_for).as_secs_f32().ceil() as u32;
+            if *warned != Some(forfeit_in) {
+                *warned = Some(forfeit_in);
+                for client_id in aboard {
+                    warnings.send(ToClients {
+                        mode: SendMode::Direct(client_id),
+                        event: IdleWarning { forfeit_in },
+                    });
+                }
+            }
+        } else {
+            *warned = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_count_as_activity() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<FromClient<ShipCommand<AdjustPower>>>()
+            .add_event::<FromClient<ShipCommand<SetCrewGoal>>>()
+            .add_systems(
+                PreUpdate,
+                (note_commands::<AdjustPower>, note_commands::<SetCrewGoal>),
+            )
+            .init_resource::<LastCommand>();
+        app.update();
+        assert!(app.world().resource::<LastCommand>().is_empty());
+
+        let client_id = ClientId::new(1);
+        app.world_mut().send_event(FromClient {
+            client_id,
+            event: SetCrewGoal { crew: 0, room: 0 }.for_ship(Entity::PLACEHOLDER),
+        });
+        app.update();
+        let last = app.world().resource::<LastCommand>();
+        assert_eq!(last.keys().collect::<Vec<_>>(), [&client_id]);
+    }
+}
@@ -0,0 +1,232 @@
+//! Per-match journal. Every [`MatchEvent`] gets appended to `<start time>.jsonl` in the
+//! [`JournalDir`] as it happens, one JSON record per line. The first record maps ship entities to
+//! players, and the last one (if the match finished) is the same [`MatchSummary`] clients get.
+//! Clients also hear about each event as it's written, as a [`CombatLogEntry`], unless it happened
+//! inside a ship their sensors can't see into. Every [`ShipEvent`] goes in too, so the journal has
+//! what each order and hit did to its ship.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use common::{
+    fairness::DodgeCommitment,
+    handshake::PlayerId,
+    journal::{CombatLogEntry, MatchEvent, MatchSummary, ShipSummary},
+    lobby::{match_outcome, FinalResult, MatchOutcome, Team},
+    rules::MatchRules,
+    ship::Dead,
+};
+use serde::Serialize;
+
+use crate::{
+    bullets::MatchRng,
+    sensor_level,
+    ship::ShipState,
+    ship_action::{ShipChanged, ShipEvent},
+    ClientShips, PlayerIds,
+};
+
+#[derive(Serialize)]
+enum JournalRecord<'a> {
+    Start {
+        /// Unix time in milliseconds.
+        started_at: u128,
+        players: HashMap<Entity, PlayerId>,
+        dodge_commitment: DodgeCommitment,
+    },
+    Event {
+        /// Seconds since the match started.
+        time: f32,
+        event: &'a MatchEvent,
+    },
+    Ship {
+        /// Seconds since the match started.
+        time: f32,
+        ship: Entity,
+        event: &'a ShipEvent,
+    },
+    End(&'a MatchSummary),
+}
+
+/// Where journals get written, `matches` in the working directory unless something says otherwise.
+#[derive(Resource)]
+pub struct JournalDir(pub PathBuf);
+
+impl Default for JournalDir {
+    fn default() -> Self {
+        Self(PathBuf::from("matches"))
+    }
+}
+
+#[derive(Resource)]
+pub struct Journal {
+    file: Option<BufWriter<File>>,
+    started: Duration,
+    ships: Vec<ShipSummary>,
+}
+
+impl Journal {
+    fn write(&mut self, record: &JournalRecord) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let result = serde_json::to_writer(&mut *file, record)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(file))
+            .and_then(|()| file.flush());
+        if let Err(e) = result {
+            eprintln!("Couldn't write to match journal, giving up on it: {e}");
+            self.file = None;
+        }
+    }
+}
+
+/// Open a new journal for the match that's just starting.
+pub fn start_journal(world: &mut World) {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let player_ids = world.resource::<PlayerIds>();
+    let players = world
+        .resource::<ClientShips>()
+        .captains()
+        .filter_map(|(client, ship)| Some((ship, *player_ids.get(&client)?)))
+        .collect::<HashMap<_, _>>();
+    let dir = world.resource::<JournalDir>().0.clone();
+    let path = dir.join(format!("{}.jsonl", now.as_secs()));
+    let file = std::fs::create_dir_all(&dir)
+        .and_then(|()| File::create(&path))
+        .map(BufWriter::new);
+    let file = match file {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("Couldn't create match journal {}: {e}", path.display());
+            None
+        }
+    };
+    let mut journal = Journal {
+        file,
+        started: world.resource::<Time>().elapsed(),
+        ships: players.keys().map(|&x| ShipSummary::new(x)).collect(),
+    };
+    journal.write(&JournalRecord::Start {
+        started_at: now.as_millis(),
+        players,
+        dodge_commitment: *world.resource::<DodgeCommitment>(),
+    });
+    world.insert_resource(journal);
+}
+
+// Everything that happens in a match ends up here, so there's a lot to take in
+#[allow(clippy::too_many_arguments)]
+pub fn write_journal(
+    mut events: EventReader<MatchEvent>,
+    mut ship_changes: EventReader<ShipChanged>,
+    journal: Option<ResMut<Journal>>,
+    ships: Query<(&Team, Has<Dead>), With<ShipState>>,
+    states: Query<(&ShipState, Option<&Team>)>,
+    client_ships: Res<ClientShips>,
+    rules: Res<MatchRules>,
+    time: Res<Time>,
+    rng: Res<MatchRng>,
+    mut summaries: EventWriter<ToClients<MatchSummary>>,
+    mut combat_log: EventWriter<ToClients<CombatLogEntry>>,
+    mut commands: Commands,
+) {
+    let Some(mut journal) = journal else {
+        events.clear();
+        ship_changes.clear();
+        return;
+    };
+    let time_since_start = time.elapsed() - journal.started;
+    for ShipChanged { ship, event } in ship_changes.read() {
+        journal.write(&JournalRecord::Ship {
+            time: time_since_start.as_secs_f32(),
+            ship: *ship,
+            event,
+        });
+    }
+    let mut ship_destroyed = false;
+    let mut tiebreak = false;
+    for event in events.read() {
+        journal.write(&JournalRecord::Event {
+            time: time_since_start.as_secs_f32(),
+            event,
+        });
+        for ship in &mut journal.ships {
+            ship.record(event);
+        }
+        // Rolls are only there to check the dodge seed against once it's revealed
+        if !matches!(event, MatchEvent::DodgeRoll { .. }) {
+            let entry = CombatLogEntry {
+                time: time_since_start.as_secs_f32(),
+                event: event.clone(),
+            };
+            match event.interior_of() {
+                Some(ship) => {
+                    for (&client_id, &own_ship) in client_ships.iter() {
+                        if sees_interior(own_ship, ship, &states, &rules) {
+                            combat_log.send(ToClients {
+                                mode: SendMode::Direct(client_id),
+                                event: entry.clone(),
+                            });
+                        }
+                    }
+                }
+                None => {
+                    combat_log.send(ToClients {
+                        mode: SendMode::Broadcast,
+                        event: entry,
+                    });
+                }
+            }
+        }
+        ship_destroyed |= matches!(event, MatchEvent::ShipDestroyed { .. });
+        tiebreak |= matches!(event, MatchEvent::WonTiebreak { .. });
+    }
+    if ship_destroyed {
+        // A team is only out once all of its ships are
+        let result = match match_outcome(ships.iter().map(|(&team, dead)| (team, dead))) {
+            MatchOutcome::Ongoing => return,
+            MatchOutcome::Won(team) if tiebreak => FinalResult::WonTiebreak(team),
+            MatchOutcome::Won(team) => FinalResult::Won(team),
+            MatchOutcome::Draw => FinalResult::Draw,
+        };
+        let summary = MatchSummary {
+            duration: time_since_start,
+            result,
+            ships: journal.ships.clone(),
+            dodge_seed: rng.seed,
+        };
+        journal.write(&JournalRecord::End(&summary));
+        summaries.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: summary,
+        });
+        commands.remove_resource::<Journal>();
+    }
+}
+
+/// Whether whoever's on `own_ship` can see inside `ship`. Same rule as the interior intel: always
+/// for your own team, otherwise only with sensors above level 1.
+fn sees_interior(
+    own_ship: Entity,
+    ship: Entity,
+    states: &Query<(&ShipState, Option<&Team>)>,
+    rules: &MatchRules,
+) -> bool {
+    let Ok((own_state, own_team)) = states.get(own_ship) else {
+        return false;
+    };
+    let team = states.get(ship).ok().and_then(|(_, team)| team);
+    own_ship == ship
+        || (own_team.is_some() && own_team == team)
+        || sensor_level(Some(own_state), rules) > 1
+}
@@ -0,0 +1,69 @@
+//! Advertising the server to players on the same network, see [`common::lan`].
+
+use std::{
+    net::{Ipv4Addr, UdpSocket},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use common::{
+    lan::{LanBeacon, LAN_DISCOVERY_PORT},
+    lobby::ReadyState,
+};
+
+use crate::{transport::BindAddr, ClientShips};
+
+/// How often to tell the network we're here.
+const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Broadcasts a [`LanBeacon`] every [`BEACON_INTERVAL`]. Needs [`transport_plugin`] for the port
+/// to advertise.
+///
+/// [`transport_plugin`]: crate::transport::transport_plugin
+pub fn lan_plugin(app: &mut App) {
+    app.add_systems(Startup, open_beacon)
+        .add_systems(Update, send_beacon.run_if(resource_exists::<Beacon>));
+}
+
+#[derive(Resource)]
+struct Beacon {
+    socket: UdpSocket,
+    timer: Timer,
+}
+
+fn open_beacon(mut commands: Commands) {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).and_then(|socket| {
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    });
+    match socket {
+        Ok(socket) => {
+            println!("Advertising on the LAN.");
+            commands.insert_resource(Beacon {
+                socket,
+                timer: Timer::new(BEACON_INTERVAL, TimerMode::Repeating),
+            });
+        }
+        Err(e) => eprintln!("Couldn't advertise on the LAN: {e}"),
+    }
+}
+
+fn send_beacon(
+    mut beacon: ResMut<Beacon>,
+    bind_addr: Res<BindAddr>,
+    client_ships: Option<Res<ClientShips>>,
+    ready_state: Option<Res<ReadyState>>,
+    time: Res<Time>,
+) {
+    if !beacon.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let players = client_ships.map_or(0, |x| x.captains().count());
+    let message = LanBeacon::new(bind_addr.port(), players as u8, ready_state.is_some());
+    // Nobody listening is the usual case, and not worth a complaint every second
+    let _ = beacon.socket.send_to(
+        &message.to_bytes(),
+        (Ipv4Addr::BROADCAST, LAN_DISCOVERY_PORT),
+    );
+}
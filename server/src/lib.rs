@@ -0,0 +1,1274 @@
+//! The authoritative game server. [`server_plugin`] runs the simulation and [`transport`] connects
+//! it to clients, so the server binary, the client's host-and-play mode and tests can all embed it.
+
+pub mod balance;
+mod bullets;
+mod clone_bay;
+pub mod console;
+mod engines;
+mod events;
+mod hazards;
+mod idle;
+mod journal;
+pub mod lan;
+#[cfg(test)]
+mod loopback_tests;
+mod match_clock;
+#[cfg(feature = "matchmaking")]
+pub mod matchmaking;
+mod oxygen;
+#[cfg(feature = "profiles")]
+pub mod profiles;
+#[cfg(feature = "profiles")]
+pub mod queue;
+mod reactor;
+mod rules;
+mod shields;
+mod ship;
+mod ship_action;
+mod ship_system;
+mod shuttle;
+mod snapshot;
+mod stats;
+mod subsystems;
+mod tiebreak;
+mod time_scale;
+pub mod transport;
+mod weapons;
+
+use bevy::{app::ScheduleRunnerPlugin, ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::RenetServer;
+use bullets::{
+    beam_damage, bullet_traversal, despawn_orphan, projectile_collide_hull,
+    projectile_shield_interact, projectile_test_dodge, projectile_timeout, random_incidence,
+    scatter, BeamBundle, BeamShielding, CritChance, DelayedBeam, DelayedProjectile, MatchRng,
+    ProjectileBundle, ShieldPierce,
+};
+use common::{
+    balance::BalanceConfig,
+    bullets::{BeamHits, FiredFrom, NeedsDodgeTest, TraversalSpeed, WeaponDamage},
+    events::CommandRejected,
+    fairness::DodgeCommitment,
+    handshake::{Handshake, PlayerId, ProtocolHash, Role},
+    hazard::HazardState,
+    intel::{SelfIntel, ShipIntel},
+    journal::MatchEvent,
+    lobby::{
+        last_hit_winner, match_outcome, ChooseTeam, CustomizeCrew, MatchEpoch, MatchOutcome,
+        PlayerReady, ReadyState, Team, MAX_TEAMS,
+    },
+    match_clock::MatchClock,
+    protocol_plugin,
+    rules::{LobbyHost, MatchRules, Round, Tiebreak},
+    ship::{Dead, Destroyed, ShipPlacement, SubsystemId, SystemId},
+    stats::MatchStats,
+    time_scale::TimeScale,
+    weapon::WeaponId,
+};
+use events::{
+    adjust_power, crew_stations, install_weapon, move_weapon, queue_power, reject, repair_hull,
+    reroute_power, set_autofire, set_beam_weapon_target, set_crew_goal, set_depower_order,
+    set_door_automation, set_doors_open, set_group_target, set_missile_floor,
+    set_projectile_weapon_target, set_repair_priority, store_weapon, weapon_power, PowerClaims,
+};
+use hazards::{asteroid_field, ion_storm, solar_flare, toggle_hazard, Environment};
+use idle::{check_idle, idle_plugin, IdleShips};
+use journal::{start_journal, write_journal, Journal, JournalDir};
+use match_clock::{start_match_clock, tick_match_clock, MatchTimer};
+use rules::{set_match_rules, starting_ship, update_lobby_host};
+use ship::{Intruder, ShipState};
+use ship_action::ShipChanged;
+use shuttle::{launch_shuttle, shuttle_intercept, shuttle_land};
+use snapshot::{default_snapshot_path, save_snapshot, RestoredMatch, Snapshot};
+use stats::{handle_rematch_requests, update_match_stats};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+use tiebreak::{track_last_hits, LastHits};
+use time_scale::{apply_time_scale, request_time_scale};
+
+pub use time_scale::LocalMatch;
+
+/// Most simulation time caught up on after a long frame, four ticks' worth. Anything past that is
+/// dropped, so the match runs a little slow instead of lurching forward.
+const MAX_CATCH_UP: Duration = Duration::from_nanos(4 * 1_000_000_000 / 64);
+
+/// A server that runs on its own: the simulation, replication and the UDP transport, ticking in a
+/// loop of its own. Insert a [`BindAddr`](transport::BindAddr) to listen somewhere other than the
+/// default port, then [`App::run`] it.
+pub fn headless_server() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(5))),
+        RepliconPlugins.set(ServerPlugin {
+            visibility_policy: VisibilityPolicy::Blacklist,
+            ..default()
+        }),
+        protocol_plugin,
+        server_plugin,
+        transport::transport_plugin,
+    ))
+    // After a long frame, catch up on at most a few ticks and let the rest go rather than trying to
+    // simulate the whole gap in one burst
+    .insert_resource(Time::<Virtual>::from_max_delta(MAX_CATCH_UP));
+    app
+}
+
+/// Everything the server simulates, independent of how clients reach it. Needs [`RepliconPlugins`]
+/// and [`protocol_plugin`], plus a [`RenetServer`] to turn clients away
+/// with, which [`transport_plugin`](transport::transport_plugin) sets up over UDP.
+pub fn server_plugin(app: &mut App) {
+    #[cfg(feature = "matchmaking")]
+    app.add_plugins(matchmaking::matchmaking_plugin);
+    #[cfg(feature = "profiles")]
+    app.add_plugins(profiles::profiles_plugin);
+    app.add_plugins(idle_plugin)
+        .init_resource::<TimeScale>()
+        .init_resource::<Handshakes>()
+        .init_resource::<JournalDir>()
+        .add_event::<MatchEvent>()
+        .add_event::<ShipChanged>()
+        .add_systems(
+            Startup,
+            (
+                reset_gamestate,
+                restore_snapshot
+                    .run_if(resource_exists::<RestoreFrom>)
+                    .after(reset_gamestate),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                (resend_on_connect::<TimeScale>, request_time_scale),
+                apply_time_scale.run_if(resource_changed::<TimeScale>),
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                resend_on_connect::<HazardState>,
+                resend_on_connect::<MatchRules>,
+                resend_on_connect::<LobbyHost>,
+                resend_on_connect::<Round>,
+                resend_on_connect::<DodgeCommitment>,
+                resend_on_connect::<MatchEpoch>,
+                resend_on_connect::<MatchStats>,
+                resend_on_connect::<MatchClock>.run_if(resource_exists::<MatchClock>),
+            ),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                (handle_connections, update_lobby_host),
+                player_ready,
+                (
+                    handle_player_ready,
+                    (
+                        choose_team,
+                        customize_crew,
+                        toggle_hazard,
+                        set_match_rules,
+                        start_game,
+                        advance_startup_countdown,
+                    )
+                        .run_if(resource_exists::<ReadyState>),
+                ),
+                place_ships,
+                (
+                    adjust_power,
+                    queue_power,
+                    reroute_power,
+                    weapon_power,
+                    set_projectile_weapon_target,
+                    set_beam_weapon_target,
+                    set_group_target,
+                    move_weapon,
+                    install_weapon,
+                    store_weapon,
+                    set_crew_goal,
+                    set_autofire,
+                    set_missile_floor,
+                    set_depower_order,
+                    set_doors_open,
+                    crew_stations,
+                    launch_shuttle,
+                    repair_hull,
+                    set_repair_priority,
+                    set_door_automation,
+                ),
+                (
+                    bullet_traversal,
+                    projectile_test_dodge,
+                    projectile_shield_interact,
+                    projectile_collide_hull,
+                    projectile_timeout,
+                    (shuttle_intercept, shuttle_land),
+                    beam_damage,
+                    tick_match_clock.run_if(resource_exists::<MatchTimer>),
+                    // Local matches are for testing, with nobody to hold hostage
+                    check_idle.run_if(not(resource_exists::<LocalMatch>)),
+                    (track_last_hits, update_dead).chain(),
+                    advance_destruction,
+                    clear_targets_on_dead,
+                    (update_ships, (fire_beams, fire_projectiles)).chain(),
+                    (ion_storm, asteroid_field, solar_flare),
+                )
+                    .run_if(not(resource_exists::<ReadyState>)),
+                (update_intel, update_intel_visibility).chain(),
+                write_journal,
+                (update_match_stats, handle_rematch_requests)
+                    .run_if(not(resource_exists::<ReadyState>)),
+            )
+                .chain(),
+        );
+}
+
+/// Resources only get replicated when they change, so mark `R` changed whenever someone connects to
+/// make sure they hear about it too.
+fn resend_on_connect<R: Resource>(mut events: EventReader<ServerEvent>, mut resource: ResMut<R>) {
+    if events
+        .read()
+        .any(|x| matches!(x, ServerEvent::ClientConnected { .. }))
+    {
+        resource.set_changed();
+    }
+}
+
+pub fn player_ready(
+    mut events: EventReader<FromClient<PlayerReady>>,
+    mut ready_state: Option<ResMut<ReadyState>>,
+) {
+    // Early out if there are no ready notifications, otherwise we'll trigger change
+    // detection and send some useless network traffic every frame
+    if events.is_empty() {
+        return;
+    }
+    let Some(ReadyState::AwaitingClients { ready_clients }) =
+        ready_state.as_mut().map(|x| x.as_mut())
+    else {
+        eprintln!("Discarding client ready notification, game has already started.");
+        return;
+    };
+    for &FromClient { client_id, .. } in events.read() {
+        ready_clients.insert(client_id);
+    }
+}
+
+/// The ship each client controls. Copilots share a ship with its captain, so several clients can
+/// map to the same entity.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct ClientShips {
+    #[deref]
+    ships: HashMap<ClientId, Entity>,
+    copilots: HashSet<ClientId>,
+}
+
+impl ClientShips {
+    /// Whether `client` may give orders to `ship`. For now that's just the one ship they were
+    /// assigned, whether as captain or copilot.
+    pub fn controls(&self, client: ClientId, ship: Entity) -> bool {
+        self.ships.get(&client) == Some(&ship)
+    }
+
+    pub fn is_copilot(&self, client: ClientId) -> bool {
+        self.copilots.contains(&client)
+    }
+
+    /// The client that `ship` belongs to, ignoring any copilots.
+    pub fn captain_of(&self, ship: Entity) -> Option<ClientId> {
+        self.captains()
+            .find(|&(_, x)| x == ship)
+            .map(|(client, _)| client)
+    }
+
+    pub fn captains(&self) -> impl Iterator<Item = (ClientId, Entity)> + '_ {
+        self.ships
+            .iter()
+            .filter(|(client, _)| !self.copilots.contains(client))
+            .map(|(&client, &ship)| (client, ship))
+    }
+
+    fn remove_client(&mut self, client: ClientId) {
+        self.ships.remove(&client);
+        self.copilots.remove(&client);
+    }
+}
+
+/// The persistent player ID each connected client sent in its handshake.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct PlayerIds(HashMap<ClientId, PlayerId>);
+
+/// Handshakes from clients that just connected, waiting for [`handle_connections`] to check them.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct Handshakes(HashMap<ClientId, Handshake>);
+
+/// Ships restored from a snapshot whose players haven't reconnected yet. While this is non-empty,
+/// only those players are let in.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct PendingPlayers(HashMap<PlayerId, Entity>);
+
+/// The team each captain last picked, so it sticks across rematches.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct ClientTeams(HashMap<ClientId, Team>);
+
+/// How each captain has customized their crew, by crew index, so it sticks across rematches and
+/// rule changes.
+#[derive(Resource, Deref, DerefMut, Debug, Default, Clone)]
+pub struct ClientCrew(HashMap<ClientId, HashMap<usize, CustomizeCrew>>);
+
+impl ClientCrew {
+    /// Dress up the crew of `client`'s freshly built `ship` the way they had them.
+    pub fn apply(&self, client: ClientId, ship: &mut ShipState) {
+        for (&index, customize) in self.get(&client).into_iter().flatten() {
+            if let Some(crew) = ship.crew.get_mut(index) {
+                customize.apply(crew);
+            }
+        }
+    }
+}
+
+/// Snapshot file to pick a match back up from on startup.
+#[derive(Resource)]
+pub struct RestoreFrom(pub PathBuf);
+
+fn restore_snapshot(world: &mut World) {
+    let path = world.remove_resource::<RestoreFrom>().unwrap().0;
+    match Snapshot::load(&path) {
+        Ok(snapshot) => {
+            snapshot.restore(world);
+            println!("Restored match from {}.", path.display());
+        }
+        Err(e) => {
+            eprintln!("Couldn't restore snapshot from {}: {e}", path.display());
+            world.send_event(AppExit::error());
+        }
+    }
+}
+
+/// Save a snapshot of the match when the app exits, so it can be restored with [`RestoreFrom`].
+pub fn save_on_exit(mut exits: EventReader<AppExit>, mut commands: Commands) {
+    if exits.read().next().is_some() {
+        commands.queue(|world: &mut World| save_snapshot(world, &default_snapshot_path()));
+    }
+}
+
+fn handle_player_ready(
+    mut events: EventReader<FromClient<PlayerReady>>,
+    mut ready_state: Option<ResMut<ReadyState>>,
+) {
+    let Some(ReadyState::AwaitingClients { ready_clients }) =
+        ready_state.as_mut().map(|x| x.as_mut())
+    else {
+        events.clear();
+        return;
+    };
+    for &FromClient { client_id, .. } in events.read() {
+        ready_clients.insert(client_id);
+    }
+}
+
+/// Move a captain's ship to the team they picked in the lobby.
+fn choose_team(
+    mut events: EventReader<FromClient<ChooseTeam>>,
+    ready_state: Res<ReadyState>,
+    client_ships: Res<ClientShips>,
+    mut client_teams: ResMut<ClientTeams>,
+    mut commands: Commands,
+) {
+    for &FromClient {
+        client_id,
+        event: ChooseTeam(team),
+    } in events.read()
+    {
+        if !matches!(*ready_state, ReadyState::AwaitingClients { .. }) {
+            eprintln!("Discarding team choice from {client_id:?}, game is already starting.");
+            continue;
+        }
+        if team.0 >= MAX_TEAMS {
+            eprintln!(
+                "Discarding team choice from {client_id:?}: no team {}.",
+                team.0
+            );
+            continue;
+        }
+        if client_ships.is_copilot(client_id) {
+            eprintln!("Discarding team choice from {client_id:?}: copilots follow their captain.");
+            continue;
+        }
+        let Some(&ship) = client_ships.get(&client_id) else {
+            eprintln!("No ship entry for client {client_id:?}.");
+            continue;
+        };
+        client_teams.insert(client_id, team);
+        commands.entity(ship).insert(team);
+    }
+}
+
+fn customize_crew(
+    mut events: EventReader<FromClient<CustomizeCrew>>,
+    ready_state: Res<ReadyState>,
+    client_ships: Res<ClientShips>,
+    mut client_crew: ResMut<ClientCrew>,
+    mut ships: Query<&mut ShipState>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+) {
+    for FromClient {
+        client_id,
+        event: customize,
+    } in events.read()
+    {
+        let client_id = *client_id;
+        if !matches!(*ready_state, ReadyState::AwaitingClients { .. }) {
+            reject(&mut rejections, client_id, "The game is already starting");
+            continue;
+        }
+        if client_ships.is_copilot(client_id) {
+            reject(
+                &mut rejections,
+                client_id,
+                "Only your captain can change the crew",
+            );
+            continue;
+        }
+        if let Err(e) = customize.validate() {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("Can't change crew: {e}"),
+            );
+            continue;
+        }
+        let Some(mut ship) = client_ships
+            .get(&client_id)
+            .and_then(|&x| ships.get_mut(x).ok())
+        else {
+            reject(&mut rejections, client_id, "You don't have a ship yet");
+            continue;
+        };
+        let Some(crew) = ship.crew.get_mut(customize.crew) else {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("No crew {}", customize.crew),
+            );
+            continue;
+        };
+        customize.apply(crew);
+        client_crew
+            .entry(client_id)
+            .or_default()
+            .insert(customize.crew, customize.clone());
+    }
+}
+
+/// Line each side's ships up on its half of the field, whenever a ship joins or changes teams.
+fn place_ships(
+    changed: Query<(), (Changed<Team>, With<ShipState>)>,
+    ships: Query<(Entity, &Team), With<ShipState>>,
+    mut commands: Commands,
+) {
+    if changed.is_empty() {
+        return;
+    }
+    let mut ships = ships.iter().collect::<Vec<_>>();
+    // Teams sharing a side stay together
+    ships.sort_by_key(|&(e, team)| (team.0 % 2, team.0, e));
+    let mut index = 0;
+    let mut last_side = None;
+    for (e, team) in ships {
+        let side = team.0 % 2;
+        index = if last_side == Some(side) {
+            index + 1
+        } else {
+            0
+        };
+        last_side = Some(side);
+        commands
+            .entity(e)
+            .insert(ShipPlacement::for_team(*team, index));
+    }
+}
+
+fn start_game(
+    clients: Res<ConnectedClients>,
+    ready_states: Res<ReadyState>,
+    pending: Res<PendingPlayers>,
+    ships: Query<(&Team, Has<Dead>), With<ShipState>>,
+    mut commands: Commands,
+) {
+    let ReadyState::AwaitingClients { ready_clients } = ready_states.as_ref() else {
+        return;
+    };
+    // Need at least two sides to fight, and everyone from a restored match back on board
+    let outcome = match_outcome(ships.iter().map(|(&team, dead)| (team, dead)));
+    if outcome == MatchOutcome::Ongoing
+        && pending.is_empty()
+        && clients.iter().all(|x| ready_clients.contains(&x.id()))
+    {
+        commands.insert_resource(ReadyState::Starting {
+            countdown: Duration::from_secs(5),
+        });
+    }
+}
+
+/// Everything that belongs to a single match: ships, their intel, shots in flight. Tearing a match
+/// down despawns exactly these, so entities that outlive matches are left alone.
+#[derive(Component, Default)]
+pub struct MatchScoped;
+
+fn despawn_all<C: Component>(world: &mut World) {
+    let to_despawn = world
+        .query_filtered::<Entity, With<C>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for e in to_despawn {
+        world.entity_mut(e).despawn();
+    }
+}
+
+pub fn update_ships(
+    mut ships: Query<(Entity, &mut ShipState), Without<Dead>>,
+    balance: Res<BalanceConfig>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+) {
+    for (e, mut ship) in &mut ships {
+        let effects = ship.augment_effects();
+        if let Some(shields) = &mut ship.systems.shields {
+            shields.charge_shield(effects.shield_charge_rate, &balance);
+        }
+        if let Some(volleys) = ship.update_weapons() {
+            for (weapon_index, volley) in volleys.enumerate() {
+                match volley {
+                    Some(weapons::Volley::Projectile(volley)) => {
+                        for i in 0..volley.weapon.volley_size {
+                            commands.spawn((
+                                DelayedProjectile {
+                                    remaining: Duration::from_millis(300 * i as u64),
+                                    weapon: volley.weapon,
+                                    target: volley.target,
+                                    fired_from: FiredFrom {
+                                        ship: e,
+                                        weapon_index,
+                                    },
+                                },
+                                MatchScoped,
+                            ));
+                        }
+                    }
+                    Some(weapons::Volley::Beam(volley)) => {
+                        commands.spawn((
+                            DelayedBeam {
+                                remaining: Duration::from_millis(150),
+                                weapon: volley.weapon,
+                                target: volley.target,
+                                fired_from: FiredFrom {
+                                    ship: e,
+                                    weapon_index,
+                                },
+                            },
+                            MatchScoped,
+                        ));
+                    }
+                    None => {}
+                }
+            }
+        }
+        let (defenders, intruders) = ship.update_intruders(&balance);
+        for (crew, killer) in defenders {
+            match_events.send(MatchEvent::CrewDied {
+                ship: e,
+                name: crew.name,
+                killer,
+            });
+        }
+        // Boarders who die away from home are too far from their own clone bay to come back
+        for Intruder { crew, owner } in intruders {
+            match_events.send(MatchEvent::CrewDied {
+                ship: owner,
+                name: crew.name,
+                killer: Some(e),
+            });
+        }
+        for crew in ship.update_crew(&balance) {
+            match_events.send(MatchEvent::CrewDied {
+                ship: e,
+                name: crew.name,
+                killer: None,
+            });
+        }
+        for name in ship.update_cloning(&balance) {
+            match_events.send(MatchEvent::CrewCloned { ship: e, name });
+        }
+        ship.update_repair_status();
+        ship.update_hull_repair();
+        ship.update_doors(&balance);
+        ship.update_door_automation();
+        ship.update_oxygen(&balance);
+        ship.apply_power_queue();
+    }
+}
+
+fn fire_projectiles(
+    ships: Query<&ShipState>,
+    mut pending: Query<(Entity, &mut DelayedProjectile)>,
+    balance: Res<BalanceConfig>,
+    mut rng: ResMut<MatchRng>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (e, mut projectile) in &mut pending {
+        let Ok(ship) = ships.get(projectile.fired_from.ship) else {
+            despawn_orphan(&mut commands, e, projectile.fired_from.ship);
+            continue;
+        };
+        if let Some(new_remaining) = projectile.remaining.checked_sub(time.delta()) {
+            projectile.remaining = new_remaining;
+        } else {
+            if let Some(weapons) = &ship.systems.weapons {
+                let index = projectile.fired_from.weapon_index;
+                if weapons.weapons().get(index).is_some_and(|x| x.is_powered()) {
+                    match_events.send(MatchEvent::ShotFired {
+                        ship: projectile.fired_from.ship,
+                        weapon_index: projectile.fired_from.weapon_index,
+                        weapon: WeaponId::Projectile(projectile.weapon),
+                    });
+                    // Area weapons pick each shot's room as it leaves the barrel
+                    let target = match ships.get(projectile.target.ship) {
+                        Ok(target) => scatter(
+                            projectile.target,
+                            target.ship_type,
+                            projectile.weapon.spread,
+                            rng.chance(),
+                        ),
+                        Err(_) => projectile.target,
+                    };
+                    let incidence = random_incidence(rng.chance());
+                    let crit_bonus = if ship.is_manned(SystemId::Weapons) {
+                        balance.manned_crit_bonus
+                    } else {
+                        0.0
+                    };
+                    commands.queue(move |world: &mut World| {
+                        let info = world.entity_mut(e).take::<DelayedProjectile>().unwrap();
+                        world.spawn(ProjectileBundle {
+                            replicated: Replicated,
+                            match_scoped: MatchScoped,
+                            damage: WeaponDamage(info.weapon.common.damage),
+                            target,
+                            fired_from: info.fired_from,
+                            traversal_speed: TraversalSpeed(info.weapon.shot_speed),
+                            traversal_progress: default(),
+                            incidence,
+                            needs_dodge_test: NeedsDodgeTest,
+                            shield_pierce: ShieldPierce(info.weapon.shield_pierce),
+                            crit_chance: CritChance(info.weapon.crit_chance + crit_bonus),
+                            kind: info.weapon.kind,
+                        });
+                    });
+                }
+            }
+            commands.entity(e).despawn();
+        }
+    }
+}
+
+fn fire_beams(
+    ships: Query<&ShipState>,
+    mut pending: Query<(Entity, &mut DelayedBeam)>,
+    mut rng: ResMut<MatchRng>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (e, mut beam) in &mut pending {
+        let Ok(ship) = ships.get(beam.fired_from.ship) else {
+            despawn_orphan(&mut commands, e, beam.fired_from.ship);
+            continue;
+        };
+        if let Some(new_remaining) = beam.remaining.checked_sub(time.delta()) {
+            beam.remaining = new_remaining;
+        } else {
+            let ship_type = ship.ship_type;
+            if let Some(weapons) = &ship.systems.weapons {
+                // TODO When the player rearranges weapons, we'll want to make sure to adjust the
+                // `weapon_index` for all entities storing it -- delayed and in-world weapon shots,
+                // maybe more?
+                let index = beam.fired_from.weapon_index;
+                if weapons.weapons().get(index).is_some_and(|x| x.is_powered()) {
+                    match_events.send(MatchEvent::ShotFired {
+                        ship: beam.fired_from.ship,
+                        weapon_index: beam.fired_from.weapon_index,
+                        weapon: WeaponId::Beam(beam.weapon),
+                    });
+                    let incidence = random_incidence(rng.chance());
+                    commands.queue(move |world: &mut World| {
+                        let info = world.entity_mut(e).take::<DelayedBeam>().unwrap();
+                        world.spawn(BeamBundle {
+                            replicated: Replicated,
+                            match_scoped: MatchScoped,
+                            damage: WeaponDamage(info.weapon.common.damage),
+                            target: info.target,
+                            hits: BeamHits::compute(ship_type, info.weapon.length, &info.target),
+                            fired_from: info.fired_from,
+                            traversal_speed: TraversalSpeed(info.weapon.speed),
+                            traversal_progress: default(),
+                            incidence,
+                            shielding: BeamShielding {
+                                rule: info.weapon.shield_rule,
+                                soaked: 0,
+                            },
+                            landed: default(),
+                        });
+                    });
+                }
+            }
+            commands.entity(e).despawn();
+        }
+    }
+}
+
+/// How far a ship can see, 0-4 with 4 being level 3 + manned. Set by the match rules, as long as
+/// the ship's sensors are in one piece.
+pub(crate) fn sensor_level(ship: Option<&ShipState>, rules: &MatchRules) -> usize {
+    let sensors_working = ship.is_none_or(|x| x.subsystems.is_working(SubsystemId::Sensors));
+    if sensors_working {
+        rules.sensors.level()
+    } else {
+        0
+    }
+}
+
+fn update_intel_visibility(
+    mut clients: ResMut<ReplicatedClients>,
+    client_ships: Res<ClientShips>,
+    self_intel: Query<(Entity, &SelfIntel)>,
+    ships: Query<(Entity, &ShipIntel, Option<&Team>)>,
+    states: Query<&ShipState>,
+    rules: Res<MatchRules>,
+) {
+    // For each client, make sure they only see entities based on their ship's sensors level
+    for client in clients.iter_mut() {
+        let client_id = client.id();
+        let client_visibility = client.visibility_mut();
+        // Clients that are about to be turned away never get a ship
+        let Some(&own_ship) = client_ships.get(&client_id) else {
+            continue;
+        };
+        let own_team = ships.get(own_ship).ok().and_then(|(_, _, team)| team);
+        let sensor_level = sensor_level(states.get(own_ship).ok(), &rules);
+
+        // Hide self intel for all but owning player
+        for (self_intel, SelfIntel { ship, .. }) in &self_intel {
+            client_visibility.set_visibility(self_intel, own_ship == *ship);
+        }
+
+        for (ship, intel, team) in &ships {
+            let allied = team.is_some() && team == own_team;
+            if ship == own_ship || allied {
+                // Clients always get their own (and their teammates') crew vision and operational
+                // status
+                client_visibility.set_visibility(intel.crew_vision, true);
+                client_visibility.set_visibility(intel.weapon_charge, true);
+                client_visibility.set_visibility(intel.enemy_weapon_charge, false);
+                client_visibility.set_visibility(intel.systems, true);
+                client_visibility.set_visibility(intel.doors, true);
+                client_visibility.set_visibility(intel.interior, sensor_level > 0);
+            } else {
+                client_visibility.set_visibility(intel.interior, sensor_level > 1);
+                client_visibility.set_visibility(intel.doors, sensor_level > 1);
+                // Never the exact charge, only pips of it
+                client_visibility.set_visibility(intel.weapon_charge, false);
+                client_visibility.set_visibility(intel.enemy_weapon_charge, sensor_level > 2);
+                client_visibility.set_visibility(intel.systems, sensor_level > 3);
+            }
+        }
+    }
+}
+
+fn update_intel(
+    mut ships: Query<(&ShipState, &mut ShipIntel)>,
+    mut self_intel: Query<&mut SelfIntel>,
+    balance: Res<BalanceConfig>,
+    mut commands: Commands,
+) {
+    let balance_hash = balance.hash();
+    for mut self_intel in &mut self_intel {
+        let (ship, mut intel) = ships.get_mut(self_intel.ship).unwrap();
+        *self_intel = ship.self_intel(self_intel.ship, balance_hash);
+        intel.basic = ship.basic_intel();
+        commands
+            .entity(intel.crew_vision)
+            .insert(ship.crew_vision_intel());
+        commands
+            .entity(intel.interior)
+            .insert(ship.interior_intel());
+        commands
+            .entity(intel.weapon_charge)
+            .insert(ship.weapon_charge_intel());
+        commands
+            .entity(intel.enemy_weapon_charge)
+            .insert(ship.enemy_weapon_charge_intel());
+        commands
+            .entity(intel.systems)
+            .insert((ship.systems_intel(), ship.subsystems_intel()));
+        commands.entity(intel.doors).insert(ship.doors_intel());
+    }
+}
+
+fn update_dead(
+    mut ships: Query<(Entity, &mut ShipState, &Team), Without<Dead>>,
+    rules: Res<MatchRules>,
+    last_hits: Res<LastHits>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+) {
+    // If this tick takes out everyone left, the rules might pull one team back from the brink
+    let spared = match rules.tiebreak {
+        Tiebreak::Draw => None,
+        Tiebreak::LastHit => last_hit_winner(
+            ships
+                .iter()
+                .map(|(e, ship, &team)| (team, ship.damage == ship.max_hull, last_hits.get(e))),
+        ),
+    };
+    for (e, mut ship, &team) in &mut ships {
+        if ship.damage != ship.max_hull {
+            continue;
+        }
+        if Some(team) == spared {
+            ship.damage -= 1;
+            match_events.send(MatchEvent::WonTiebreak { ship: e });
+            continue;
+        }
+        commands.entity(e).insert((Dead, Destroyed::default()));
+        match_events.send(MatchEvent::ShipDestroyed { ship: e });
+    }
+}
+
+fn advance_destruction(mut wrecks: Query<&mut Destroyed>, time: Res<Time>) {
+    for mut wreck in &mut wrecks {
+        if !wreck.is_finished() {
+            wreck.remaining = wreck.remaining.saturating_sub(time.delta());
+        }
+    }
+}
+
+/// Once a ship dies, nobody should keep shooting at it. Clear every weapon target pointing at it and
+/// call off any shots that haven't left the barrel yet. Clients see their targets disappear through
+/// [`SelfIntel`].
+fn clear_targets_on_dead(
+    dead: Query<Entity, Added<Dead>>,
+    mut ships: Query<&mut ShipState>,
+    projectiles: Query<(Entity, &DelayedProjectile)>,
+    beams: Query<(Entity, &DelayedBeam)>,
+    mut commands: Commands,
+) {
+    for dead in &dead {
+        for mut ship in &mut ships {
+            if let Some(weapons) = &mut ship.systems.weapons {
+                weapons.clear_targets_on(dead);
+            }
+        }
+        for (e, projectile) in &projectiles {
+            if projectile.target.ship == dead {
+                commands.entity(e).despawn();
+            }
+        }
+        for (e, beam) in &beams {
+            if beam.target.ship == dead {
+                commands.entity(e).despawn();
+            }
+        }
+    }
+}
+
+fn advance_startup_countdown(
+    ready_state: Res<ReadyState>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    if let ReadyState::Starting { countdown } = ready_state.as_ref() {
+        if let Some(new_countdown) = countdown.checked_sub(time.delta()) {
+            commands.insert_resource(ReadyState::Starting {
+                countdown: new_countdown,
+            });
+        } else {
+            commands.remove_resource::<ReadyState>();
+            commands.queue(start_journal);
+            commands.queue(start_match_clock);
+        }
+    }
+}
+
+/// What the server keeps on each client, bundled to keep [`handle_connections`] within the system
+/// parameter limit.
+#[derive(SystemParam)]
+struct ClientRecords<'w> {
+    ships: ResMut<'w, ClientShips>,
+    teams: ResMut<'w, ClientTeams>,
+    crew: ResMut<'w, ClientCrew>,
+}
+
+fn handle_connections(
+    mut server_events: EventReader<ServerEvent>,
+    mut handshakes: ResMut<Handshakes>,
+    protocol_hash: Res<ProtocolHash>,
+    mut server: ResMut<RenetServer>,
+    mut clients: ClientRecords,
+    mut commands: Commands,
+) {
+    for event in server_events.read() {
+        match event {
+            ServerEvent::ClientConnected { client_id } => {
+                let Some(handshake) = handshakes.remove(client_id) else {
+                    eprintln!("Rejecting client {client_id:?}: no handshake.");
+                    server.disconnect(client_id.get());
+                    continue;
+                };
+                if let Some(mismatch) = handshake.mismatch(*protocol_hash) {
+                    eprintln!("Rejecting client {client_id:?}: {mismatch}.");
+                    server.disconnect(client_id.get());
+                    continue;
+                }
+                println!("New client {client_id:?} connected.");
+                let client_id = *client_id;
+                commands.queue(move |world: &mut World| {
+                    join(world, client_id, handshake);
+                });
+            }
+            ServerEvent::ClientDisconnected { client_id, reason } => {
+                println!("Client {client_id:?} disconnected: {reason}");
+                // Rejected clients never got a ship, so there's nothing to reset. A copilot leaving
+                // doesn't end the match either, the captain can carry on alone.
+                let copilot = clients.ships.is_copilot(*client_id);
+                if clients.ships.contains_key(client_id) && !copilot {
+                    commands.insert_resource(Round::default());
+                    commands.queue(reset_gamestate);
+                }
+                clients.ships.remove_client(*client_id);
+                clients.teams.remove(client_id);
+                clients.crew.remove(client_id);
+            }
+        }
+    }
+}
+
+/// Give a newly connected client a ship. If we're waiting on players from a restored match, they
+/// get their old ship back and everyone else is turned away. Copilots board an existing ship.
+fn join(world: &mut World, client_id: ClientId, handshake: Handshake) {
+    let Handshake { key, role, .. } = handshake;
+    let player = key.player_id();
+    #[cfg(feature = "matchmaking")]
+    if let Err(reason) = matchmaking::check_reservation(world, role, handshake.reservation) {
+        eprintln!("Rejecting client {client_id:?}: {reason}.");
+        world
+            .resource_mut::<RenetServer>()
+            .disconnect(client_id.get());
+        return;
+    }
+    #[cfg(feature = "profiles")]
+    if let Some(Err(reason)) = world
+        .get_resource::<queue::RankedMatch>()
+        .map(|x| x.check_seat(player, role, handshake.reservation))
+    {
+        eprintln!("Rejecting client {client_id:?}: {reason}.");
+        world
+            .resource_mut::<RenetServer>()
+            .disconnect(client_id.get());
+        return;
+    }
+    world.resource_mut::<PlayerIds>().insert(client_id, player);
+    let mut pending = world.resource_mut::<PendingPlayers>();
+    let restored = match role {
+        Role::Captain => pending.remove(&player),
+        Role::Copilot => None,
+    };
+    if let Some(ship) = restored {
+        println!("Player {player} rejoined the restored match.");
+        world.resource_mut::<ClientShips>().insert(client_id, ship);
+        if let Some(&team) = world.get::<Team>(ship) {
+            world.resource_mut::<ClientTeams>().insert(client_id, team);
+        }
+    } else if !pending.is_empty() {
+        eprintln!("Rejecting client {client_id:?}: waiting on players from a restored match.");
+        world
+            .resource_mut::<RenetServer>()
+            .disconnect(client_id.get());
+    } else if role == Role::Copilot {
+        board_as_copilot(world, client_id);
+    } else {
+        spawn_player(world, client_id);
+    }
+}
+
+fn reset_gamestate(world: &mut World) {
+    world.init_resource::<ReadyState>();
+    world.init_resource::<ClientShips>();
+    world.init_resource::<PlayerIds>();
+    world.init_resource::<ClientTeams>();
+    world.init_resource::<ClientCrew>();
+    world.init_resource::<HazardState>();
+    world.init_resource::<MatchRules>();
+    world.init_resource::<BalanceConfig>();
+    world.init_resource::<LobbyHost>();
+    world.init_resource::<Round>();
+    world.remove_resource::<RestoredMatch>();
+    world.insert_resource(PendingPlayers::default());
+    world.remove_resource::<Journal>();
+    world.remove_resource::<MatchTimer>();
+    world.insert_resource(IdleShips::default());
+    world.remove_resource::<MatchClock>();
+    world.insert_resource(MatchStats::default());
+    world.insert_resource(LastHits::default());
+    world.insert_resource(PowerClaims::default());
+    let match_rng = MatchRng::default();
+    world.insert_resource(DodgeCommitment::new(&match_rng.seed));
+    world.insert_resource(match_rng);
+    despawn_all::<MatchScoped>(world);
+    world.spawn((Environment, Replicated, MatchScoped));
+    // Hazards carry over to the rematch, but the next match starts calm
+    world.resource_mut::<HazardState>().ion_surge = false;
+
+    let clients = world
+        .resource::<ConnectedClients>()
+        .iter()
+        .map(|x| x.id())
+        .collect::<Vec<_>>();
+    // Captains need their new ships before copilots can board them
+    let (copilots, captains) = clients
+        .into_iter()
+        .partition::<Vec<_>, _>(|&x| world.resource::<ClientShips>().is_copilot(x));
+    for client in captains {
+        spawn_player(world, client);
+    }
+    for client in copilots {
+        board_as_copilot(world, client);
+    }
+    // Only announce the new match once it's all in place
+    world.init_resource::<MatchEpoch>();
+    world.resource_mut::<MatchEpoch>().0 += 1;
+}
+
+/// Put `client_id` aboard whichever captain's ship has the fewest copilots, or turn them away if
+/// nobody has a ship yet.
+fn board_as_copilot(world: &mut World, client_id: ClientId) {
+    let mut client_ships = world.resource_mut::<ClientShips>();
+    let ship = client_ships
+        .captains()
+        .map(|(_, ship)| ship)
+        .min_by_key(|&ship| client_ships.values().filter(|&&x| x == ship).count());
+    let Some(ship) = ship else {
+        eprintln!("Rejecting client {client_id:?}: no ship to copilot.");
+        client_ships.remove_client(client_id);
+        world
+            .resource_mut::<RenetServer>()
+            .disconnect(client_id.get());
+        return;
+    };
+    println!("Client {client_id:?} is copiloting {ship:?}.");
+    client_ships.insert(client_id, ship);
+    client_ships.copilots.insert(client_id);
+}
+
+fn spawn_player(world: &mut World, client_id: ClientId) {
+    let mut ship = starting_ship(world.resource::<MatchRules>());
+    world.resource::<ClientCrew>().apply(client_id, &mut ship);
+
+    let team = team_for(world, client_id);
+    world.resource_mut::<ClientTeams>().insert(client_id, team);
+    let ship_e = world.spawn_empty().id();
+    spawn_ship(world, ship_e, ship, team);
+    let mut client_ships = world.resource_mut::<ClientShips>();
+    client_ships.copilots.remove(&client_id);
+    client_ships.insert(client_id, ship_e);
+}
+
+/// The team `client_id` picked last time, or else the first team nobody's on yet so that everyone
+/// starts out on their own.
+fn team_for(world: &mut World, client_id: ClientId) -> Team {
+    let client_teams = world.resource::<ClientTeams>();
+    if let Some(&team) = client_teams.get(&client_id) {
+        return team;
+    }
+    let taken = client_teams.values().copied().collect::<HashSet<_>>();
+    let taken = world
+        .query::<&Team>()
+        .iter(world)
+        .copied()
+        .chain(taken)
+        .collect::<HashSet<_>>();
+    (0..MAX_TEAMS)
+        .map(Team)
+        .find(|x| !taken.contains(x))
+        .unwrap_or(Team(0))
+}
+
+/// Turn `ship_e` into a ship with the given state, spawning its intel entities alongside it.
+fn spawn_ship(world: &mut World, ship_e: Entity, ship: ShipState, team: Team) {
+    let crew_vision = world
+        .spawn((Replicated, MatchScoped, ship.crew_vision_intel()))
+        .id();
+    let interior = world
+        .spawn((Replicated, MatchScoped, ship.interior_intel()))
+        .id();
+    let weapon_charge = world
+        .spawn((Replicated, MatchScoped, ship.weapon_charge_intel()))
+        .id();
+    let enemy_weapon_charge = world
+        .spawn((Replicated, MatchScoped, ship.enemy_weapon_charge_intel()))
+        .id();
+    let systems = world
+        .spawn((
+            Replicated,
+            MatchScoped,
+            ship.systems_intel(),
+            ship.subsystems_intel(),
+        ))
+        .id();
+    let doors = world
+        .spawn((Replicated, MatchScoped, ship.doors_intel()))
+        .id();
+    world.entity_mut(ship_e).insert((
+        Replicated,
+        MatchScoped,
+        team,
+        ShipIntel {
+            basic: ship.basic_intel(),
+            crew_vision,
+            interior,
+            weapon_charge,
+            enemy_weapon_charge,
+            systems,
+            doors,
+        },
+    ));
+    let balance_hash = world.resource::<BalanceConfig>().hash();
+    world.spawn((
+        Replicated,
+        MatchScoped,
+        ship.self_intel(ship_e, balance_hash),
+    ));
+    world.entity_mut(ship_e).insert(ship);
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        bullets::{BeamTarget, HullImpact, RoomTarget, ShieldImpact},
+        weapon::{HALBERD_BEAM, HEAVY_LASER},
+    };
+
+    use super::*;
+
+    #[test]
+    fn reset_with_shots_in_flight() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<MatchEvent>()
+            .add_event::<ShipChanged>()
+            .add_event::<ToClients<ShieldImpact>>()
+            .add_event::<ToClients<HullImpact>>()
+            .init_resource::<ConnectedClients>()
+            .init_resource::<MatchRng>()
+            .init_resource::<BalanceConfig>()
+            .add_systems(
+                Update,
+                (
+                    fire_projectiles,
+                    fire_beams,
+                    bullet_traversal,
+                    projectile_test_dodge,
+                    projectile_shield_interact,
+                    projectile_collide_hull,
+                    beam_damage,
+                )
+                    .chain(),
+            );
+
+        let world = app.world_mut();
+        let ship = world.spawn_empty().id();
+        spawn_ship(world, ship, ShipState::new(), Team(0));
+        let fired_from = FiredFrom {
+            ship,
+            weapon_index: 0,
+        };
+        let (WeaponId::Projectile(laser), WeaponId::Beam(beam)) = (HEAVY_LASER, HALBERD_BEAM)
+        else {
+            unreachable!();
+        };
+        world.spawn(DelayedProjectile {
+            remaining: Duration::ZERO,
+            weapon: laser,
+            target: RoomTarget { ship, room: 0 },
+            fired_from,
+        });
+        world.spawn(DelayedBeam {
+            remaining: Duration::ZERO,
+            weapon: beam,
+            target: BeamTarget {
+                ship,
+                start: Vec2::ZERO,
+                dir: Dir2::X,
+            },
+            fired_from,
+        });
+        // Left without `MatchScoped` so they outlive the reset and the ship they came from
+        reset_gamestate(world);
+
+        // Used to panic looking up the ship that fired these
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(world.query::<&DelayedProjectile>().iter(world).count(), 0);
+        assert_eq!(world.query::<&DelayedBeam>().iter(world).count(), 0);
+    }
+
+    #[test]
+    fn reset_only_despawns_match_entities() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ConnectedClients>();
+        let world = app.world_mut();
+        reset_gamestate(world);
+        let epoch = *world.resource::<MatchEpoch>();
+        let ship = world.spawn_empty().id();
+        spawn_ship(world, ship, ShipState::new(), Team(0));
+        let lasting = world.spawn(Replicated).id();
+
+        reset_gamestate(world);
+        assert!(!world.entities().contains(ship));
+        assert!(world.entities().contains(lasting));
+        assert_eq!(world.query::<&ShipIntel>().iter(world).count(), 0);
+        assert_eq!(world.query::<&SelfIntel>().iter(world).count(), 0);
+        assert_eq!(world.resource::<MatchEpoch>().0, epoch.0 + 1);
+    }
+
+    #[test]
+    fn teams_sharing_a_side_dont_overlap() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<BalanceConfig>()
+            .add_systems(Update, place_ships);
+        let world = app.world_mut();
+        for team in [0, 2, 1] {
+            let ship = world.spawn_empty().id();
+            spawn_ship(world, ship, ShipState::new(), Team(team));
+        }
+        app.update();
+
+        let world = app.world_mut();
+        let left = world
+            .query::<&ShipPlacement>()
+            .iter(world)
+            .filter(|x| x.position.x < 0.0)
+            .collect::<Vec<_>>();
+        assert_eq!(left.len(), 2);
+        assert_ne!(left[0], left[1]);
+    }
+}
@@ -0,0 +1,229 @@
+//! End-to-end tests: the real server simulation with headless client apps, passing replicon's
+//! messages between them by hand instead of over the network. These catch protocol regressions that
+//! only show up once both ends are talking, like something never being replicated.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{ConnectionConfig, RenetServer};
+use common::{
+    bullets::RoomTarget,
+    events::{CommandEvent, PowerDir, SetProjectileWeaponTarget, WeaponPower},
+    handshake::{Handshake, PlayerKey, ProtocolHash},
+    intel::{InteriorIntel, SelfIntel, ShipIntel},
+    lobby::{PlayerReady, ReadyState},
+    protocol_plugin,
+    rules::MatchRules,
+};
+
+use crate::{journal::JournalDir, server_plugin, Handshakes};
+
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 64);
+
+/// A server and its clients, connected by a perfect network.
+struct Loopback {
+    server: App,
+    clients: Vec<App>,
+}
+
+impl Loopback {
+    fn new(clients: u64) -> Self {
+        let mut server = App::new();
+        server
+            .add_plugins((
+                MinimalPlugins,
+                RepliconPlugins.set(ServerPlugin {
+                    tick_policy: TickPolicy::EveryFrame,
+                    visibility_policy: VisibilityPolicy::Blacklist,
+                    ..default()
+                }),
+                protocol_plugin,
+                server_plugin,
+            ))
+            // One fixed tick per update
+            .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+            .insert_resource(RenetServer::new(ConnectionConfig::default()))
+            // Keep test matches out of the working tree
+            .insert_resource(JournalDir(
+                std::env::temp_dir().join("ftl-loopback-matches"),
+            ))
+            .finish();
+        server
+            .world_mut()
+            .resource_mut::<RepliconServer>()
+            .set_running(true);
+
+        let mut loopback = Self {
+            server,
+            clients: Vec::new(),
+        };
+        for player_id in 1..=clients {
+            loopback.connect(ClientId::new(player_id), player_id);
+        }
+        loopback
+    }
+
+    fn connect(&mut self, client_id: ClientId, player_id: u64) {
+        let mut client = App::new();
+        client
+            .add_plugins((MinimalPlugins, RepliconPlugins, protocol_plugin))
+            .finish();
+        // Replicon sizes the client's receive channels in `Startup`
+        client.update();
+        client
+            .world_mut()
+            .resource_mut::<RepliconClient>()
+            .set_status(RepliconClientStatus::Connected {
+                client_id: Some(client_id),
+            });
+        let world = self.server.world_mut();
+        let handshake = Handshake::new(PlayerKey::from_secret(&player_id.to_le_bytes()))
+            .with_protocol_hash(*client.world().resource::<ProtocolHash>());
+        world
+            .resource_mut::<Handshakes>()
+            .insert(client_id, handshake);
+        world.send_event(ServerEvent::ClientConnected { client_id });
+        self.clients.push(client);
+    }
+
+    /// Deliver everything each side has sent since last time.
+    fn exchange(&mut self) {
+        let sent = self
+            .server
+            .world_mut()
+            .resource_mut::<RepliconServer>()
+            .drain_sent()
+            .collect::<Vec<_>>();
+        for (client_id, channel_id, message) in sent {
+            let client = self
+                .clients
+                .iter_mut()
+                .find(|x| x.world().resource::<RepliconClient>().id() == Some(client_id))
+                .unwrap();
+            client
+                .world_mut()
+                .resource_mut::<RepliconClient>()
+                .insert_received(channel_id, message);
+        }
+        for client in &mut self.clients {
+            let mut replicon_client = client.world_mut().resource_mut::<RepliconClient>();
+            let client_id = replicon_client.id().unwrap();
+            let sent = replicon_client.drain_sent().collect::<Vec<_>>();
+            let mut server = self.server.world_mut().resource_mut::<RepliconServer>();
+            for (channel_id, message) in sent {
+                server.insert_received(client_id, channel_id, message);
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        self.server.update();
+        self.exchange();
+        for client in &mut self.clients {
+            client.update();
+        }
+        self.exchange();
+    }
+
+    /// Step until `done` holds, failing the test if it takes more than `ticks`.
+    fn step_until(&mut self, ticks: usize, what: &str, mut done: impl FnMut(&mut Self) -> bool) {
+        for _ in 0..ticks {
+            self.step();
+            if done(self) {
+                return;
+            }
+        }
+        panic!("Gave up waiting for {what} after {ticks} ticks.");
+    }
+}
+
+fn count<C: Component>(app: &mut App) -> usize {
+    let world = app.world_mut();
+    world.query::<&C>().iter(world).count()
+}
+
+fn own_ship(app: &mut App) -> Option<Entity> {
+    let world = app.world_mut();
+    world
+        .query::<&SelfIntel>()
+        .get_single(world)
+        .ok()
+        .map(|x| x.ship)
+}
+
+fn enemy_ship(app: &mut App) -> Entity {
+    let own = own_ship(app).unwrap();
+    let world = app.world_mut();
+    world
+        .query_filtered::<Entity, With<ShipIntel>>()
+        .iter(world)
+        .find(|&x| x != own)
+        .unwrap()
+}
+
+fn hull(app: &App, ship: Entity) -> usize {
+    app.world().get::<ShipIntel>(ship).unwrap().basic.hull
+}
+
+#[test]
+fn two_clients_fight_over_loopback() {
+    let mut loopback = Loopback::new(2);
+    loopback.step_until(64, "both clients to see both ships", |x| {
+        x.clients
+            .iter_mut()
+            .all(|x| own_ship(x).is_some() && count::<ShipIntel>(x) == 2)
+    });
+    for client in &mut loopback.clients {
+        // Level 1 sensors only show our own interior
+        assert_eq!(count::<InteriorIntel>(client), 1);
+        assert!(client.world().contains_resource::<ReadyState>());
+        assert!(client.world().contains_resource::<MatchRules>());
+    }
+
+    for client in &mut loopback.clients {
+        client.world_mut().send_event(PlayerReady);
+    }
+    loopback.step_until(64 * 10, "the match to start", |x| {
+        x.clients
+            .iter()
+            .all(|x| !x.world().contains_resource::<ReadyState>())
+    });
+
+    // Nobody has powered shields or engines, so the first shot can't miss
+    let attacker = &mut loopback.clients[0];
+    let target = enemy_ship(attacker);
+    let max_hull = hull(attacker, target);
+    let own = own_ship(attacker).unwrap();
+    attacker.world_mut().send_event(
+        WeaponPower {
+            dir: PowerDir::Request,
+            weapon_index: 0,
+        }
+        .for_ship(own),
+    );
+    loopback.step_until(64, "the weapon to power up", |x| {
+        let attacker = &mut x.clients[0];
+        let own = own_ship(attacker).unwrap();
+        let intel = attacker.world().get::<ShipIntel>(own).unwrap();
+        intel.basic.weapons.as_ref().unwrap().weapons[0].powered
+    });
+    let attacker = &mut loopback.clients[0];
+    attacker.world_mut().send_event(
+        SetProjectileWeaponTarget {
+            weapon_index: 0,
+            target: Some(RoomTarget {
+                ship: target,
+                room: 0,
+            }),
+        }
+        .for_ship(own),
+    );
+    loopback.step_until(64 * 30, "hull damage to replicate", |x| {
+        let defender = &mut x.clients[1];
+        let own = own_ship(defender).unwrap();
+        hull(defender, own) < max_hull
+    });
+    let attacker = &loopback.clients[0];
+    assert!(hull(attacker, target) < max_hull);
+}
@@ -0,0 +1,187 @@
+use std::{cmp::Reverse, collections::HashMap, time::Duration};
+
+use bevy::{ecs::system::SystemParam, math::FloatOrd, prelude::*};
+use common::{
+    journal::MatchEvent,
+    lobby::{match_outcome, MatchOutcome, Team},
+    match_clock::{MatchClock, SuddenDeath},
+    rules::MatchRules,
+    ship::{Dead, Destroyed},
+    stats::MatchStats,
+};
+
+use crate::{
+    ship::ShipState,
+    ship_action::{ShipAction, ShipChanged},
+    ClientShips,
+};
+
+/// Time between each point of hull damage once sudden death starts burning hulls down.
+const HULL_BURN_INTERVAL: Duration = Duration::from_secs(3);
+
+/// The precise time left, which [`MatchClock`] only shows to the second. Only present in matches
+/// with a time limit.
+#[derive(Resource)]
+pub struct MatchTimer {
+    remaining: Duration,
+    sudden_death: SuddenDeath,
+    burn: Timer,
+}
+
+/// Start counting down, if the rules for the match that's just starting set a time limit.
+pub fn start_match_clock(world: &mut World) {
+    world.remove_resource::<MatchTimer>();
+    world.remove_resource::<MatchClock>();
+    let Some(limit) = world.resource::<MatchRules>().time_limit else {
+        return;
+    };
+    let remaining = Duration::from_secs(60 * limit.minutes as u64);
+    world.insert_resource(MatchTimer {
+        remaining,
+        sudden_death: limit.sudden_death,
+        burn: Timer::new(HULL_BURN_INTERVAL, TimerMode::Repeating),
+    });
+    world.insert_resource(MatchClock::Remaining(remaining.as_secs() as u32));
+}
+
+/// The match's time, both to the tick and as clients see it. Bundled with [`DamageDealt`] to keep
+/// [`tick_match_clock`] within the system parameter limit.
+#[derive(SystemParam)]
+pub struct Clock<'w> {
+    timer: ResMut<'w, MatchTimer>,
+    clock: ResMut<'w, MatchClock>,
+    time: Res<'w, Time>,
+}
+
+/// How much damage each ship's captain has dealt this match, for sudden death on points.
+#[derive(SystemParam)]
+pub struct DamageDealt<'w> {
+    client_ships: Res<'w, ClientShips>,
+    stats: Res<'w, MatchStats>,
+}
+
+impl DamageDealt<'_> {
+    fn by(&self, ship: Entity) -> usize {
+        self.client_ships
+            .captain_of(ship)
+            .and_then(|x| self.stats.players.get(&x))
+            .map_or(0, |x| x.damage_dealt)
+    }
+}
+
+/// Count the match down, then run sudden death once time's up. The clock stops as soon as the
+/// match is decided.
+pub fn tick_match_clock(
+    mut clock: Clock,
+    mut ships: Query<(Entity, &mut ShipState, &Team), Without<Dead>>,
+    teams: Query<(&Team, Has<Dead>), With<ShipState>>,
+    damage_dealt: DamageDealt,
+    mut match_events: EventWriter<MatchEvent>,
+    mut ship_changes: EventWriter<ShipChanged>,
+    mut commands: Commands,
+) {
+    if match_outcome(teams.iter().map(|(&team, dead)| (team, dead))) != MatchOutcome::Ongoing {
+        return;
+    }
+    let Clock { timer, clock, time } = &mut clock;
+    if let Some(remaining) = timer
+        .remaining
+        .checked_sub(time.delta())
+        .filter(|x| !x.is_zero())
+    {
+        timer.remaining = remaining;
+        clock.set_if_neq(MatchClock::Remaining(remaining.as_secs_f32().ceil() as u32));
+        return;
+    }
+
+    if **clock != MatchClock::SuddenDeath {
+        timer.remaining = Duration::ZERO;
+        **clock = MatchClock::SuddenDeath;
+        println!("Time's up, sudden death.");
+        if timer.sudden_death == SuddenDeath::Score {
+            let scores = ships.iter().map(|(e, ship, &team)| {
+                let score = Score {
+                    hull: ship.max_hull - ship.damage,
+                    max_hull: ship.max_hull,
+                    damage_dealt: damage_dealt.by(e),
+                };
+                (team, score)
+            });
+            if let Some(winner) = score_winner(scores) {
+                for (e, _, &team) in &ships {
+                    if team != winner {
+                        commands.entity(e).insert((Dead, Destroyed::default()));
+                        match_events.send(MatchEvent::ShipDestroyed { ship: e });
+                    }
+                }
+                return;
+            }
+            println!("Dead even on points, burning hulls instead.");
+        }
+    }
+
+    timer.burn.tick(time.delta());
+    for _ in 0..timer.burn.times_finished_this_tick() {
+        for (e, mut ship, _) in &mut ships {
+            let events = ship.apply(ShipAction::BurnHull(1)).unwrap_or_default();
+            ship_changes.send_batch(ShipChanged::all(e, events));
+        }
+    }
+}
+
+/// How a ship stands when the clock runs out and sudden death goes to points.
+#[derive(Default, Debug, Clone, Copy)]
+struct Score {
+    hull: usize,
+    max_hull: usize,
+    damage_dealt: usize,
+}
+
+/// The team with the largest share of its hull left, then the most damage dealt. `None` if the top
+/// two teams are tied on both.
+fn score_winner(ships: impl IntoIterator<Item = (Team, Score)>) -> Option<Team> {
+    let mut teams = HashMap::<Team, Score>::new();
+    for (team, score) in ships {
+        let total = teams.entry(team).or_default();
+        total.hull += score.hull;
+        total.max_hull += score.max_hull;
+        total.damage_dealt += score.damage_dealt;
+    }
+    let mut ranked = teams
+        .into_iter()
+        .map(|(team, x)| {
+            let hull = FloatOrd(x.hull as f32 / x.max_hull.max(1) as f32);
+            ((hull, x.damage_dealt), team)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by_key(|x| Reverse(x.0));
+    match ranked.as_slice() {
+        [(_, team)] => Some(*team),
+        [(best, team), (next, _), ..] if best > next => Some(*team),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_goes_to_hull_then_damage() {
+        let (a, b) = (Team(0), Team(1));
+        let score = |hull, damage_dealt| Score {
+            hull,
+            max_hull: 30,
+            damage_dealt,
+        };
+        assert_eq!(
+            score_winner([(a, score(20, 5)), (b, score(10, 9))]),
+            Some(a)
+        );
+        assert_eq!(
+            score_winner([(a, score(20, 5)), (b, score(20, 9))]),
+            Some(b)
+        );
+        assert_eq!(score_winner([(a, score(20, 5)), (b, score(20, 5))]), None);
+    }
+}
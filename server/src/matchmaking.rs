@@ -0,0 +1,235 @@
+//! Optional hooks for listing the server with a matchmaking service, behind the `matchmaking`
+//! feature. The server sends the service a [`Heartbeat`] every so often so it can show up in a
+//! server browser, and the service answers with the seats it has promised to players on their way
+//! over. Those players connect with the matching [`ReservationToken`], and nobody else gets a held
+//! seat.
+//!
+//! The service itself is behind [`MatchmakingBackend`]. [`HttpBackend`] speaks a bare-bones JSON
+//! API, but anything else can be plugged in by inserting [`Matchmaking::new`] with a different
+//! backend.
+
+use std::{
+    collections::HashSet,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use common::{
+    handshake::{ReservationToken, Role},
+    lobby::{ReadyState, MAX_TEAMS},
+    rules::MatchRules,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{transport::BindAddr, ClientShips};
+
+/// How often to tell the service we're still here.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What the server tells the matchmaking service about itself.
+#[derive(Serialize, Debug, Clone)]
+pub struct Heartbeat {
+    pub port: u16,
+    /// Captains only, since copilots don't take a ship.
+    pub players: usize,
+    pub max_players: usize,
+    /// Whether a match is underway, as opposed to players waiting in the lobby.
+    pub in_match: bool,
+    pub rules: MatchRules,
+    /// Reservations that were claimed since the last heartbeat, so the service can stop holding
+    /// them.
+    pub redeemed: Vec<ReservationToken>,
+}
+
+/// The service's answer to a [`Heartbeat`].
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct HeartbeatReply {
+    /// Every reservation the service is still holding on this server.
+    #[serde(default)]
+    pub reservations: Vec<ReservationToken>,
+}
+
+/// A matchmaking service. Calls are made on a background thread, so they're free to block.
+pub trait MatchmakingBackend: Send + Sync + 'static {
+    fn heartbeat(&self, heartbeat: &Heartbeat) -> Result<HeartbeatReply, String>;
+}
+
+/// Posts each heartbeat as JSON to `<url>/heartbeat` and reads a [`HeartbeatReply`] back.
+pub struct HttpBackend {
+    pub url: String,
+}
+
+impl MatchmakingBackend for HttpBackend {
+    fn heartbeat(&self, heartbeat: &Heartbeat) -> Result<HeartbeatReply, String> {
+        let url = format!("{}/heartbeat", self.url.trim_end_matches('/'));
+        ureq::post(&url)
+            .timeout(Duration::from_secs(5))
+            .send_json(heartbeat)
+            .map_err(|e| e.to_string())?
+            .into_json()
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// What came back from a heartbeat, along with the reservations it reported as redeemed.
+type HeartbeatOutcome = (Vec<ReservationToken>, Result<HeartbeatReply, String>);
+
+/// Connection to the matchmaking service. Nothing in this module runs unless this resource exists.
+#[derive(Resource)]
+pub struct Matchmaking {
+    backend: Arc<dyn MatchmakingBackend>,
+    replies: Mutex<Receiver<HeartbeatOutcome>>,
+    sender: Sender<HeartbeatOutcome>,
+    timer: Timer,
+    in_flight: bool,
+}
+
+impl Matchmaking {
+    pub fn new(backend: impl MatchmakingBackend) -> Self {
+        let (sender, receiver) = channel();
+        let mut timer = Timer::new(HEARTBEAT_INTERVAL, TimerMode::Repeating);
+        // Get listed right away instead of one interval after starting up
+        timer.tick(HEARTBEAT_INTERVAL);
+        Self {
+            backend: Arc::new(backend),
+            replies: Mutex::new(receiver),
+            sender,
+            timer,
+            in_flight: false,
+        }
+    }
+}
+
+/// Seats the matchmaking service is holding for players who haven't connected yet.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Reservations {
+    held: HashSet<ReservationToken>,
+    /// Claimed since the last heartbeat went out.
+    redeemed: Vec<ReservationToken>,
+}
+
+impl Reservations {
+    /// Let a captain in if they hold a reservation, or if there's a seat left over after the
+    /// reserved ones. `captains` is how many captains are already on board.
+    pub fn redeem(
+        &mut self,
+        token: Option<ReservationToken>,
+        captains: usize,
+    ) -> Result<(), &'static str> {
+        if let Some(token) = token.filter(|x| self.held.remove(x)) {
+            self.redeemed.push(token);
+            return Ok(());
+        }
+        let free = (MAX_TEAMS as usize).saturating_sub(captains);
+        if free <= self.held.len() {
+            return Err("the remaining seats are reserved");
+        }
+        Ok(())
+    }
+}
+
+pub fn matchmaking_plugin(app: &mut App) {
+    app.init_resource::<Reservations>().add_systems(
+        Update,
+        (receive_heartbeat_replies, send_heartbeats)
+            .chain()
+            .run_if(resource_exists::<Matchmaking>),
+    );
+}
+
+/// Check a joining client's reservation. Copilots don't take a seat, so they're always let in.
+pub fn check_reservation(
+    world: &mut World,
+    role: Role,
+    token: Option<ReservationToken>,
+) -> Result<(), &'static str> {
+    if role == Role::Copilot {
+        return Ok(());
+    }
+    let captains = world.resource::<ClientShips>().captains().count();
+    world.resource_mut::<Reservations>().redeem(token, captains)
+}
+
+fn send_heartbeats(
+    mut matchmaking: ResMut<Matchmaking>,
+    mut reservations: ResMut<Reservations>,
+    client_ships: Res<ClientShips>,
+    bind_addr: Res<BindAddr>,
+    ready_state: Option<Res<ReadyState>>,
+    rules: Res<MatchRules>,
+    time: Res<Time>,
+) {
+    matchmaking.timer.tick(time.delta());
+    if !matchmaking.timer.finished() || matchmaking.in_flight {
+        return;
+    }
+    let redeemed = std::mem::take(&mut reservations.redeemed);
+    let heartbeat = Heartbeat {
+        port: bind_addr.port(),
+        players: client_ships.captains().count(),
+        max_players: MAX_TEAMS as usize,
+        in_match: ready_state.is_none(),
+        rules: rules.clone(),
+        redeemed: redeemed.clone(),
+    };
+    let backend = matchmaking.backend.clone();
+    let sender = matchmaking.sender.clone();
+    std::thread::spawn(move || {
+        let _ = sender.send((redeemed, backend.heartbeat(&heartbeat)));
+    });
+    matchmaking.in_flight = true;
+}
+
+fn receive_heartbeat_replies(
+    mut matchmaking: ResMut<Matchmaking>,
+    mut reservations: ResMut<Reservations>,
+) {
+    let replies = matchmaking
+        .replies
+        .lock()
+        .unwrap()
+        .try_iter()
+        .collect::<Vec<_>>();
+    for (redeemed, reply) in replies {
+        matchmaking.in_flight = false;
+        match reply {
+            Ok(HeartbeatReply { reservations: held }) => {
+                // Anything claimed while the heartbeat was out is still on the service's list
+                let held = held
+                    .into_iter()
+                    .filter(|x| !reservations.redeemed.contains(x))
+                    .collect();
+                reservations.held = held;
+            }
+            Err(e) => {
+                eprintln!("Matchmaking heartbeat failed: {e}");
+                // Try again next time so the service still hears about them
+                reservations.redeemed.extend(redeemed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_seats_are_held() {
+        let mut reservations = Reservations {
+            held: HashSet::from([7]),
+            redeemed: Vec::new(),
+        };
+        let last_seat = MAX_TEAMS as usize - 1;
+        assert!(reservations.redeem(None, last_seat).is_err());
+        assert!(reservations.redeem(Some(3), last_seat).is_err());
+        assert_eq!(reservations.redeem(Some(7), last_seat), Ok(()));
+        assert_eq!(reservations.redeemed, vec![7]);
+        // The held seat is spoken for, so the next stranger can take the last one
+        assert_eq!(reservations.redeem(None, last_seat), Ok(()));
+    }
+}
@@ -1,11 +1,12 @@
 use common::ship::SystemId;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     reactor::Reactor,
     ship_system::{boring_add_power, boring_remove_power, PowerContext, ShipSystem, SystemStatus},
 };
 
-#[derive(Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct Oxygen {
     status: SystemStatus,
     current_power: usize,
@@ -0,0 +1,313 @@
+//! Optional player profiles, behind the `profiles` feature. Each player's rating, record and match
+//! history is kept in a SQLite database keyed by their [`PlayerId`], updated when a match ends, and
+//! handed to any client that asks with [`RequestProfiles`]. Nothing in this module runs unless a
+//! [`ProfileStore`] has been inserted.
+//!
+//! Clients never send their player ID, only the [`PlayerKey`](common::handshake::PlayerKey) it's
+//! worked out from, so playing under someone else's ID means knowing their key. The transport
+//! doesn't encrypt handshakes, though: someone who can watch a player's traffic can lift their key.
+
+use std::{
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use common::{
+    handshake::PlayerId,
+    journal::MatchEvent,
+    lobby::{match_outcome, MatchEpoch, MatchOutcome, Team},
+    profile::{
+        rating_change, MatchRecord, MatchResult, PlayerProfile, PlayerProfiles, RequestProfiles,
+        RECENT_MATCHES, STARTING_RATING,
+    },
+    ship::Dead,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{journal::write_journal, ship::ShipState, ClientShips, PlayerIds};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS players (
+        player_id INTEGER PRIMARY KEY,
+        rating INTEGER NOT NULL,
+        wins INTEGER NOT NULL,
+        losses INTEGER NOT NULL,
+        draws INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS matches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        player_id INTEGER NOT NULL REFERENCES players(player_id),
+        finished_at INTEGER NOT NULL,
+        result TEXT NOT NULL,
+        opponent_rating INTEGER NOT NULL,
+        rating_change INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS matches_by_player ON matches(player_id, finished_at);
+";
+
+/// How long to wait on another process's write before giving up. The coordinator and every ranked
+/// match server it starts share one database, so they'll sometimes collide.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connection to the profile database. SQLite connections can't be shared between threads, hence
+/// the lock.
+#[derive(Resource)]
+pub struct ProfileStore(Mutex<Connection>);
+
+impl ProfileStore {
+    /// Open the database at `path`, creating it if it doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let db = Connection::open(path)?;
+        db.busy_timeout(BUSY_TIMEOUT)?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Self(Mutex::new(db)))
+    }
+
+    /// `player`'s profile, or a fresh one if they've never finished a match here.
+    pub fn profile(&self, player: PlayerId) -> rusqlite::Result<PlayerProfile> {
+        let db = self.0.lock().unwrap();
+        let Some(mut profile) = db
+            .query_row(
+                "SELECT rating, wins, losses, draws FROM players WHERE player_id = ?1",
+                params![player as i64],
+                |row| {
+                    Ok(PlayerProfile {
+                        player_id: player,
+                        rating: row.get(0)?,
+                        wins: row.get(1)?,
+                        losses: row.get(2)?,
+                        draws: row.get(3)?,
+                        recent: Vec::new(),
+                    })
+                },
+            )
+            .optional()?
+        else {
+            return Ok(PlayerProfile::new(player));
+        };
+        let mut recent = db.prepare(
+            "SELECT finished_at, result, opponent_rating, rating_change FROM matches
+                WHERE player_id = ?1 ORDER BY finished_at DESC, id DESC LIMIT ?2",
+        )?;
+        profile.recent = recent
+            .query_map(params![player as i64, RECENT_MATCHES as i64], |row| {
+                let result = match row.get::<_, String>(1)?.as_str() {
+                    "win" => MatchResult::Win,
+                    "loss" => MatchResult::Loss,
+                    _ => MatchResult::Draw,
+                };
+                Ok(MatchRecord {
+                    finished_at: row.get(0)?,
+                    result,
+                    opponent_rating: row.get(2)?,
+                    rating_change: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(profile)
+    }
+
+    /// Add `record` to `player`'s history and apply its rating change.
+    pub fn record(&self, player: PlayerId, record: MatchRecord) -> rusqlite::Result<()> {
+        let mut db = self.0.lock().unwrap();
+        let tx = db.transaction()?;
+        let (wins, losses, draws) = match record.result {
+            MatchResult::Win => (1, 0, 0),
+            MatchResult::Loss => (0, 1, 0),
+            MatchResult::Draw => (0, 0, 1),
+        };
+        tx.execute(
+            "INSERT INTO players (player_id, rating, wins, losses, draws)
+                VALUES (?1, ?2 + ?3, ?4, ?5, ?6)
+                ON CONFLICT(player_id) DO UPDATE SET
+                    rating = rating + ?3,
+                    wins = wins + ?4,
+                    losses = losses + ?5,
+                    draws = draws + ?6",
+            params![
+                player as i64,
+                STARTING_RATING,
+                record.rating_change,
+                wins,
+                losses,
+                draws
+            ],
+        )?;
+        let result = match record.result {
+            MatchResult::Win => "win",
+            MatchResult::Loss => "loss",
+            MatchResult::Draw => "draw",
+        };
+        tx.execute(
+            "INSERT INTO matches (player_id, finished_at, result, opponent_rating, rating_change)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                player as i64,
+                record.finished_at,
+                result,
+                record.opponent_rating,
+                record.rating_change
+            ],
+        )?;
+        tx.commit()
+    }
+
+    /// Profiles of every connected client, as sent to clients.
+    fn connected(&self, player_ids: &PlayerIds) -> PlayerProfiles {
+        let players = player_ids
+            .iter()
+            .filter_map(|(&client, &player)| match self.profile(player) {
+                Ok(profile) => Some((client, profile)),
+                Err(e) => {
+                    eprintln!("Couldn't load profile of player {player}: {e}");
+                    None
+                }
+            })
+            .collect();
+        PlayerProfiles { players }
+    }
+}
+
+pub fn profiles_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (
+            send_profiles,
+            record_results
+                .after(write_journal)
+                .run_if(resource_exists::<MatchEpoch>),
+        )
+            .run_if(resource_exists::<ProfileStore>),
+    );
+}
+
+fn send_profiles(
+    mut requests: EventReader<FromClient<RequestProfiles>>,
+    store: Res<ProfileStore>,
+    player_ids: Res<PlayerIds>,
+    mut profiles: EventWriter<ToClients<PlayerProfiles>>,
+) {
+    for &FromClient { client_id, .. } in requests.read() {
+        profiles.send(ToClients {
+            mode: SendMode::Direct(client_id),
+            event: store.connected(&player_ids),
+        });
+    }
+}
+
+/// Where results get recorded and who hears about them. Bundled to keep [`record_results`] within
+/// the system parameter limit.
+#[derive(SystemParam)]
+struct Ratings<'w> {
+    store: Res<'w, ProfileStore>,
+    player_ids: Res<'w, PlayerIds>,
+    profiles: EventWriter<'w, ToClients<PlayerProfiles>>,
+}
+
+/// Once a match is decided, rate every captain in it against the average rating of the players
+/// on the other teams, then send everyone the updated profiles.
+fn record_results(
+    mut events: EventReader<MatchEvent>,
+    ships: Query<(&Team, Has<Dead>), With<ShipState>>,
+    client_ships: Res<ClientShips>,
+    epoch: Res<MatchEpoch>,
+    mut recorded: Local<Option<MatchEpoch>>,
+    ratings: Ratings,
+) {
+    let Ratings {
+        store,
+        player_ids,
+        mut profiles,
+    } = ratings;
+    let mut ship_destroyed = false;
+    for event in events.read() {
+        ship_destroyed |= matches!(event, MatchEvent::ShipDestroyed { .. });
+    }
+    if !ship_destroyed || *recorded == Some(*epoch) {
+        return;
+    }
+    let outcome = match_outcome(ships.iter().map(|(&team, dead)| (team, dead)));
+    if outcome == MatchOutcome::Ongoing {
+        return;
+    }
+    *recorded = Some(*epoch);
+    let players = client_ships
+        .captains()
+        .filter_map(|(client, ship)| {
+            let (&team, _) = ships.get(ship).ok()?;
+            let player = *player_ids.get(&client)?;
+            let rating = match store.profile(player) {
+                Ok(x) => x.rating,
+                Err(e) => {
+                    eprintln!("Couldn't load profile of player {player}: {e}");
+                    return None;
+                }
+            };
+            Some((player, team, rating))
+        })
+        .collect::<Vec<_>>();
+    let finished_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for &(player, team, rating) in &players {
+        let opponents = players
+            .iter()
+            .filter(|&&(_, x, _)| x != team)
+            .map(|&(_, _, x)| x)
+            .collect::<Vec<_>>();
+        if opponents.is_empty() {
+            // Nobody to be rated against, say everyone else left
+            continue;
+        }
+        let opponent_rating = opponents.iter().sum::<i32>() / opponents.len() as i32;
+        let result = match outcome {
+            MatchOutcome::Won(winner) if winner == team => MatchResult::Win,
+            MatchOutcome::Won(_) => MatchResult::Loss,
+            _ => MatchResult::Draw,
+        };
+        let record = MatchRecord {
+            finished_at,
+            result,
+            opponent_rating,
+            rating_change: rating_change(rating, opponent_rating, result),
+        };
+        if let Err(e) = store.record(player, record) {
+            eprintln!("Couldn't record result for player {player}: {e}");
+        }
+    }
+    profiles.send(ToClients {
+        mode: SendMode::Broadcast,
+        event: store.connected(&player_ids),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_add_up() {
+        let store = ProfileStore::open(":memory:").unwrap();
+        assert_eq!(store.profile(7).unwrap(), PlayerProfile::new(7));
+        for (finished_at, result, rating_change) in
+            [(1, MatchResult::Win, 16), (2, MatchResult::Loss, -15)]
+        {
+            let record = MatchRecord {
+                finished_at,
+                result,
+                opponent_rating: 1200,
+                rating_change,
+            };
+            store.record(7, record).unwrap();
+        }
+        let profile = store.profile(7).unwrap();
+        assert_eq!(profile.rating, 1201);
+        assert_eq!((profile.wins, profile.losses, profile.draws), (1, 1, 0));
+        let results = profile.recent.iter().map(|x| x.result).collect::<Vec<_>>();
+        assert_eq!(results, vec![MatchResult::Loss, MatchResult::Win]);
+    }
+}
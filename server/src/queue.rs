@@ -0,0 +1,356 @@
+//! Ranked queue, behind the `profiles` feature. The coordinator (`src/bin/coordinator.rs`) takes
+//! queue requests from clients, pairs up players with close ratings, and starts a server process
+//! for each pair running with [`RankedMatch`]. Each player gets a reservation for their seat, and
+//! the server turns away anyone who doesn't hold one. It records the result in the same profile
+//! database the coordinator rates players from, then shuts itself down once everyone's left.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    ops::Range,
+    path::PathBuf,
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use common::{
+    handshake::{PlayerId, ReservationToken, Role, PROTOCOL_VERSION},
+    queue::{QueueReply, QueueRequest},
+};
+
+use crate::profiles::ProfileStore;
+
+/// Ratings this far apart are always close enough to pair.
+const BASE_WINDOW: i32 = 100;
+/// How much further apart ratings may be for every second the longer-waiting player has queued.
+const WINDOW_PER_SEC: f32 = 10.0;
+const MAX_WINDOW: i32 = 600;
+/// How often queued players hear that we're still looking.
+const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a ranked server waits for its players before giving up on them.
+const NO_SHOW_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How far apart two ratings may be to pair players, once one of them has waited `waited`.
+pub fn rating_window(waited: Duration) -> i32 {
+    let widened = BASE_WINDOW + (waited.as_secs_f32() * WINDOW_PER_SEC) as i32;
+    widened.min(MAX_WINDOW)
+}
+
+/// Pair up players by rating, given each one's rating and time in the queue. Whoever's waited
+/// longest gets matched first, with the closest rating in their window. Returns pairs of indices.
+pub fn pair(players: &[(i32, Duration)]) -> Vec<(usize, usize)> {
+    let mut order = (0..players.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(players[i].1));
+    let mut paired = vec![false; players.len()];
+    let mut pairs = Vec::new();
+    for &a in &order {
+        if paired[a] {
+            continue;
+        }
+        let (rating, waited) = players[a];
+        let window = rating_window(waited);
+        let closest = order
+            .iter()
+            .copied()
+            .filter(|&b| b != a && !paired[b])
+            .map(|b| (b, (players[b].0 - rating).abs()))
+            .filter(|&(_, diff)| diff <= window)
+            .min_by_key(|&(_, diff)| diff);
+        if let Some((b, _)) = closest {
+            paired[a] = true;
+            paired[b] = true;
+            pairs.push((a, b));
+        }
+    }
+    pairs
+}
+
+struct Seeker {
+    player: PlayerId,
+    rating: i32,
+    since: Instant,
+    stream: TcpStream,
+}
+
+/// Takes queue requests and starts a server per match. Blocks whoever calls [`Coordinator::run`].
+pub struct Coordinator {
+    listener: TcpListener,
+    store: ProfileStore,
+    /// Passed on to match servers so they record results where we read ratings from.
+    database: PathBuf,
+    /// The `server` binary to start matches with.
+    server: PathBuf,
+    /// Ports match servers get, one each.
+    ports: Range<u16>,
+    queue: Vec<Seeker>,
+    matches: Vec<(u16, Child)>,
+}
+
+impl Coordinator {
+    pub fn new(
+        bind: SocketAddr,
+        database: PathBuf,
+        server: PathBuf,
+        ports: Range<u16>,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(bind).map_err(|e| format!("Couldn't bind {bind}: {e}"))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Couldn't set up {bind}: {e}"))?;
+        let store = ProfileStore::open(&database)
+            .map_err(|e| format!("Couldn't open profile database {}: {e}", database.display()))?;
+        Ok(Self {
+            listener,
+            store,
+            database,
+            server,
+            ports,
+            queue: Vec::new(),
+            matches: Vec::new(),
+        })
+    }
+
+    pub fn run(&mut self) -> ! {
+        let mut last_status = Instant::now();
+        loop {
+            self.accept();
+            self.reap_matches();
+            self.start_matches();
+            if last_status.elapsed() >= STATUS_INTERVAL {
+                last_status = Instant::now();
+                self.send_status();
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn accept(&mut self) {
+        while let Ok((mut stream, addr)) = self.listener.accept() {
+            // Requests are tiny and sent right away, so a short blocking read is fine
+            let mut bytes = [0; QueueRequest::BYTES];
+            let read = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(Duration::from_secs(1))))
+                .and_then(|()| stream.read_exact(&mut bytes));
+            if let Err(e) = read {
+                eprintln!("Dropping queue connection from {addr}: {e}");
+                continue;
+            }
+            let Some(request) = QueueRequest::from_bytes(&bytes) else {
+                eprintln!("Dropping queue connection from {addr}, not a queue request.");
+                continue;
+            };
+            if request.version != PROTOCOL_VERSION {
+                let _ = stream.write_all(&QueueReply::WrongVersion.to_bytes());
+                continue;
+            }
+            let player = request.key.player_id();
+            let rating = match self.store.profile(player) {
+                Ok(profile) => profile.rating,
+                Err(e) => {
+                    eprintln!("Couldn't load profile of player {player}: {e}");
+                    continue;
+                }
+            };
+            // Queuing again from somewhere else replaces the old spot
+            self.queue.retain(|x| x.player != player);
+            println!("Player {player} queued at {rating}.");
+            self.queue.push(Seeker {
+                player,
+                rating,
+                since: Instant::now(),
+                stream,
+            });
+        }
+    }
+
+    /// Forget matches that have finished, freeing their ports.
+    fn reap_matches(&mut self) {
+        self.matches
+            .retain_mut(|(_, child)| matches!(child.try_wait(), Ok(None)));
+    }
+
+    fn free_port(&self) -> Option<u16> {
+        self.ports
+            .clone()
+            .find(|port| self.matches.iter().all(|(x, _)| x != port))
+    }
+
+    fn start_matches(&mut self) {
+        let now = Instant::now();
+        let players = self
+            .queue
+            .iter()
+            .map(|x| (x.rating, now - x.since))
+            .collect::<Vec<_>>();
+        let mut matched = Vec::new();
+        for (a, b) in pair(&players) {
+            let Some(port) = self.free_port() else {
+                eprintln!("Out of ports for ranked matches, holding the queue.");
+                break;
+            };
+            let seats = [a, b].map(|i| (rand::random(), self.queue[i].player));
+            let child = Command::new(&self.server)
+                .args(["--bind", &format!("[::]:{port}")])
+                .args(["--ranked", &seats_arg(&seats)])
+                .arg("--profiles")
+                .arg(&self.database)
+                .spawn();
+            let child = match child {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("Couldn't start {}: {e}", self.server.display());
+                    break;
+                }
+            };
+            println!(
+                "Players {} and {} matched on port {port}.",
+                self.queue[a].player, self.queue[b].player
+            );
+            self.matches.push((port, child));
+            for (i, (reservation, _)) in [a, b].into_iter().zip(seats) {
+                let _ = self.queue[i]
+                    .stream
+                    .write_all(&QueueReply::MatchFound { port, reservation }.to_bytes());
+                matched.push(i);
+            }
+        }
+        let mut i = 0;
+        self.queue.retain(|_| {
+            i += 1;
+            !matched.contains(&(i - 1))
+        });
+    }
+
+    /// Tell everyone still waiting how many are queued, dropping anyone who's hung up.
+    fn send_status(&mut self) {
+        let players = self.queue.len().min(u16::MAX as usize) as u16;
+        let status = QueueReply::Waiting { players }.to_bytes();
+        self.queue.retain_mut(|x| {
+            let sent = x.stream.write_all(&status).is_ok();
+            if !sent {
+                println!("Player {} left the queue.", x.player);
+            }
+            sent
+        });
+    }
+}
+
+/// How a ranked server's seats are passed to it on the command line: `token:player` pairs,
+/// separated by commas.
+pub fn seats_arg(seats: &[(ReservationToken, PlayerId)]) -> String {
+    seats
+        .iter()
+        .map(|(token, player)| format!("{token}:{player}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The seats in an argument written by [`seats_arg`], or `None` if it's malformed.
+pub fn parse_seats(arg: &str) -> Option<HashMap<ReservationToken, PlayerId>> {
+    arg.split(',')
+        .map(|seat| {
+            let (token, player) = seat.split_once(':')?;
+            Some((token.parse().ok()?, player.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Marks a server started by the coordinator for a single ranked match. Only the players it was
+/// started for get in. It exits once everyone has left, or if nobody shows up at all.
+#[derive(Resource)]
+pub struct RankedMatch {
+    /// Who each reservation was handed to.
+    seats: HashMap<ReservationToken, PlayerId>,
+    started: Instant,
+    anyone_joined: bool,
+}
+
+impl RankedMatch {
+    pub fn new(seats: HashMap<ReservationToken, PlayerId>) -> Self {
+        Self {
+            seats,
+            started: Instant::now(),
+            anyone_joined: false,
+        }
+    }
+
+    /// Let `player` in only if `token` is the reservation they were given. Copilots have no seat,
+    /// so they're never let in.
+    pub fn check_seat(
+        &self,
+        player: PlayerId,
+        role: Role,
+        token: Option<ReservationToken>,
+    ) -> Result<(), &'static str> {
+        if role == Role::Copilot {
+            return Err("ranked matches don't take copilots");
+        }
+        match token.and_then(|x| self.seats.get(&x)) {
+            Some(&x) if x == player => Ok(()),
+            Some(_) => Err("that reservation is someone else's"),
+            None => Err("no reservation for this ranked match"),
+        }
+    }
+}
+
+/// Add this alongside a [`RankedMatch`].
+pub fn ranked_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        end_ranked_match.run_if(resource_exists::<RankedMatch>),
+    );
+}
+
+fn end_ranked_match(
+    mut ranked: ResMut<RankedMatch>,
+    clients: Res<ConnectedClients>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if clients.iter().next().is_some() {
+        ranked.anyone_joined = true;
+    } else if ranked.anyone_joined {
+        println!("Everyone's left the ranked match, shutting down.");
+        exit.send(AppExit::Success);
+    } else if ranked.started.elapsed() > NO_SHOW_TIMEOUT {
+        println!("Nobody showed up for the ranked match, shutting down.");
+        exit.send(AppExit::Success);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_close_ratings_and_widens_with_time() {
+        let fresh = Duration::ZERO;
+        // 1500 is too far from everyone to start with
+        let players = [(1200, fresh), (1500, fresh), (1250, fresh), (1180, fresh)];
+        let pairs = pair(&players);
+        assert_eq!(pairs.len(), 1);
+        let (a, b) = pairs[0];
+        assert!(a != 1 && b != 1);
+
+        // After long enough in the queue, anyone will do
+        let players = [(1200, fresh), (1500, Duration::from_secs(60))];
+        assert_eq!(pair(&players), vec![(1, 0)]);
+        assert_eq!(rating_window(Duration::from_secs(3600)), MAX_WINDOW);
+    }
+
+    #[test]
+    fn only_seated_players_get_in() {
+        let seats = parse_seats(&seats_arg(&[(11, 1), (22, 2)])).unwrap();
+        assert_eq!(seats, HashMap::from([(11, 1), (22, 2)]));
+        assert_eq!(parse_seats("11:1,22"), None);
+        let ranked = RankedMatch::new(seats);
+        assert_eq!(ranked.check_seat(1, Role::Captain, Some(11)), Ok(()));
+        assert!(ranked.check_seat(1, Role::Captain, Some(22)).is_err());
+        assert!(ranked.check_seat(3, Role::Captain, None).is_err());
+        assert!(ranked.check_seat(3, Role::Captain, Some(33)).is_err());
+        assert!(ranked.check_seat(2, Role::Copilot, Some(22)).is_err());
+    }
+}
@@ -1,7 +1,12 @@
-#[derive(Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Reactor {
     pub upgrade_level: usize,
     pub available: usize,
+    /// Power knocked offline by an ion storm. Neither available nor in use by any system.
+    #[serde(default)]
+    pub ionized: usize,
 }
 
 impl Reactor {
@@ -9,6 +14,7 @@ impl Reactor {
         Self {
             upgrade_level,
             available: upgrade_level,
+            ionized: 0,
         }
     }
 
@@ -0,0 +1,137 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use common::{
+    lobby::ReadyState,
+    nav::{Cell, CrewNavStatus},
+    rules::{LobbyHost, MatchRules, Round, SetMatchRules},
+    ship::{SubsystemId, SystemId},
+    weapon::Weapon,
+    Crew, CrewTask,
+};
+use strum::IntoEnumIterator;
+
+use crate::{ship::ShipState, snapshot::RestoredMatch, ClientCrew, ClientShips};
+
+/// A fresh ship built to `rules`.
+pub fn starting_ship(rules: &MatchRules) -> ShipState {
+    let mut ship = ShipState::new();
+    for _ in 0..rules.reactor {
+        ship.reactor.upgrade();
+    }
+    ship.max_hull = rules.hull;
+    ship.scrap = rules.scrap;
+
+    for system in SystemId::iter() {
+        ship.install_system(system);
+    }
+    for subsystem in SubsystemId::iter() {
+        ship.install_subsystem(subsystem);
+    }
+    for system in [SystemId::Shields, SystemId::Engines, SystemId::Weapons] {
+        let system = ship.systems.system_mut(system).unwrap();
+        for _ in 0..rules.system_upgrades {
+            system.upgrade();
+        }
+    }
+    let weapons = ship.systems.weapons.as_mut().unwrap();
+    for (index, &weapon) in rules.weapons.iter().enumerate() {
+        weapons.install_weapon(index, Weapon::new(weapon));
+    }
+    for &augment in &rules.augments {
+        ship.install_augment(augment);
+    }
+    if ship.augment_effects().precharged_weapons {
+        ship.precharge_weapons();
+    }
+
+    // TODO Add a dedicated API to bring on crew
+    for (name, cell) in [("Fish", 2), ("Virus", 6), ("Stick", 10)] {
+        ship.crew.push(Crew {
+            race: 0,
+            name: name.into(),
+            nav_status: CrewNavStatus::At(Cell(cell)),
+            health: 100.0,
+            task: CrewTask::Idle,
+            station: None,
+            color: default(),
+        });
+    }
+    ship
+}
+
+/// Keep [`LobbyHost`] pointed at whoever has been connected the longest.
+pub fn update_lobby_host(
+    mut events: EventReader<ServerEvent>,
+    mut connected: Local<Vec<ClientId>>,
+    mut host: ResMut<LobbyHost>,
+) {
+    for event in events.read() {
+        match event {
+            ServerEvent::ClientConnected { client_id } => connected.push(*client_id),
+            ServerEvent::ClientDisconnected { client_id, .. } => {
+                connected.retain(|x| x != client_id);
+            }
+        }
+    }
+    host.set_if_neq(LobbyHost(connected.first().copied()));
+}
+
+/// Who's in the lobby: the host, who captains each ship and how they've customized their crew.
+/// Bundled to keep [`set_match_rules`] within the system parameter limit.
+#[derive(SystemParam)]
+pub struct Lobby<'w> {
+    host: Res<'w, LobbyHost>,
+    client_ships: Res<'w, ClientShips>,
+    client_crew: Res<'w, ClientCrew>,
+}
+
+impl Lobby<'_> {
+    /// Dress up the crew of `ship` the way its captain had them, if it has one.
+    fn customize_crew(&self, ship_e: Entity, ship: &mut ShipState) {
+        if let Some(captain) = self.client_ships.captain_of(ship_e) {
+            self.client_crew.apply(captain, ship);
+        }
+    }
+}
+
+/// Take new rules from the host and rebuild every ship in the lobby to match. Everyone has to ready
+/// up again, and the series starts over.
+pub fn set_match_rules(
+    mut events: EventReader<FromClient<SetMatchRules>>,
+    lobby: Lobby,
+    restored: Option<Res<RestoredMatch>>,
+    mut ready_state: ResMut<ReadyState>,
+    mut rules: ResMut<MatchRules>,
+    mut round: ResMut<Round>,
+    mut ships: Query<(Entity, &mut ShipState)>,
+) {
+    for FromClient {
+        client_id,
+        event: SetMatchRules(new_rules),
+    } in events.read()
+    {
+        if **lobby.host != Some(*client_id) {
+            eprintln!("Discarding match rules from {client_id:?}: only the host can change them.");
+            continue;
+        }
+        let ReadyState::AwaitingClients { ready_clients } = ready_state.as_mut() else {
+            eprintln!("Discarding match rules from {client_id:?}, game is already starting.");
+            continue;
+        };
+        if restored.is_some() {
+            eprintln!("Discarding match rules from {client_id:?}: ships come from a snapshot.");
+            continue;
+        }
+        if let Err(e) = new_rules.validate() {
+            eprintln!("Discarding match rules from {client_id:?}: {e}.");
+            continue;
+        }
+        *rules = new_rules.clone();
+        *round = Round::default();
+        ready_clients.clear();
+        for (e, mut ship) in &mut ships {
+            *ship = starting_ship(&rules);
+            lobby.customize_crew(e, &mut ship);
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use common::balance::BalanceConfig;
+use serde::{Deserialize, Serialize};
+
 use crate::{
     reactor::Reactor,
     ship_system::{PowerContext, ShipSystem, SystemStatus},
 };
 
-#[derive(Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Shields {
     status: SystemStatus,
     /// Current reactor power allocated to shields. `layers` will never
@@ -13,24 +16,22 @@ pub struct Shields {
     pub layers: usize,
     /// Current progress toward recovering the next shield layer.
     pub charge: f32,
+    /// Super shield left. It sits outside the normal layers, gets hit first and never recharges.
+    #[serde(default)]
+    pub super_shield: usize,
 }
 
 impl Shields {
-    pub fn charge_shield(&mut self) {
+    /// Charge shields for one tick. `rate_multiplier` scales the recharge rate.
+    pub fn charge_shield(&mut self, rate_multiplier: f32, balance: &BalanceConfig) {
         let target = self.current_power / 2;
         if self.layers > target {
             self.layers = target;
         }
         if self.layers < target {
-            // Rate in layers per second
-            let charge_rate = match self.layers {
-                0 | 1 => 0.5,
-                2 => 0.58,
-                3 => 0.67,
-                _ => 0.75,
-            };
+            let charge_rate = balance.shield_charge_rate(self.layers);
             // Multiply by fixed update step to get frame charge
-            self.charge += charge_rate / 64.0;
+            self.charge += rate_multiplier * charge_rate / 64.0;
         } else {
             self.charge = 0.0;
         }
@@ -74,6 +75,11 @@ impl ShipSystem for Shields {
         self.current_power += diff;
     }
 
+    fn next_power_step(&self) -> Option<usize> {
+        let next_level = (self.current_power + 2) / 2 * 2;
+        (next_level <= self.status.max_power()).then_some(next_level - self.current_power)
+    }
+
     fn remove_power(&mut self, reactor: &mut Reactor) {
         if self.current_power == 0 {
             eprintln!("Can't remove power from shields, system power is already zero.");
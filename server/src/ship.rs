@@ -3,40 +3,143 @@ use std::{
     iter::zip,
 };
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::entity::{EntityMapper, MapEntities},
+    prelude::*,
+};
 use common::{
+    alarm::raised_alarms,
+    augment::{AugmentEffects, AugmentId, MAX_AUGMENTS},
+    balance::BalanceConfig,
     bullets::{BeamTarget, RoomTarget},
+    events::{DepowerOrder, DoorAutomation},
     intel::{
-        BasicIntel, CrewVisionIntel, InteriorIntel, RoomIntel, SelfIntel, ShieldIntel,
-        SystemsIntel, WeaponChargeIntel, WeaponIntel, WeaponsIntel,
+        BasicIntel, CellIntel, CloningIntel, CrewVisionIntel, DoorsIntel, EnemyWeaponChargeIntel,
+        InteriorIntel, RoomIntel, SelfIntel, ShieldIntel, SubsystemsIntel, SystemsIntel,
+        WeaponChargeIntel, WeaponIntel, WeaponsIntel,
     },
-    nav::{Cell, CrewNav, CrewNavStatus, NavMesh, PathGraph},
-    ship::{Door, SystemId, SHIPS},
+    nav::{Cell, CrewNav, CrewNavStatus, NavLocation, NavMesh, PathGraph, Pathfinder},
+    ship::{Door, SubsystemId, SystemId, SHIPS},
     util::IterAvg,
-    Crew, DoorState,
+    weapon::DamageSpec,
+    Crew, CrewTask, DoorState, RACES,
 };
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 
 use crate::{
     reactor::Reactor,
     ship_system::{PowerContext, ShipSystem, ShipSystems},
-    weapons::Volley,
+    subsystems::Subsystems,
+    weapons::{MissileSupply, Volley},
 };
 
-#[derive(Component, Debug)]
+/// A dead crew member waiting on the clone bay.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cloning {
+    pub crew: Crew,
+    /// Progress in `[0, 1]` toward bringing them back.
+    pub progress: f32,
+}
+
+/// Crew from another ship who came aboard by shuttle.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Intruder {
+    pub crew: Crew,
+    /// The ship they came from.
+    pub owner: Entity,
+}
+
+/// Fire and hull damage in a single cell.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub struct CellStatus {
+    pub on_fire: bool,
+    pub breached: bool,
+    /// Crew progress in `[0, 1]` toward putting out the fire, or patching the breach once the fire
+    /// is out.
+    pub repair_progress: f32,
+}
+
+impl CellStatus {
+    fn repair(&mut self, amount: f32) {
+        self.repair_progress += amount;
+        if self.repair_progress < 1.0 {
+            return;
+        }
+        self.repair_progress = 0.0;
+        if self.on_fire {
+            self.on_fire = false;
+        } else {
+            self.breached = false;
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Debug)]
 pub struct ShipState {
     pub ship_type: usize,
     pub reactor: Reactor,
     pub systems: ShipSystems,
+    #[serde(default)]
+    pub subsystems: Subsystems,
     pub max_hull: usize,
     pub damage: usize,
     pub crew: Vec<Crew>,
     pub missiles: usize,
-    /// Oxygen level for each room in `[0, 1]`. Crew take damage below `x < 0.05`.
+    #[serde(default)]
+    pub scrap: usize,
+    /// Oxygen level for each cell in `[0, 1]`. Crew take damage below `x < 0.05`.
     pub oxygen: Vec<f32>,
+    /// Seconds until average oxygen falls to [`BalanceConfig::suffocation_oxygen`] at the rate it
+    /// fell last tick, or `None` if it isn't falling.
+    #[serde(default)]
+    pub suffocation_in: Option<f32>,
+    /// Fires and breaches for each cell.
+    #[serde(default)]
+    pub cells: Vec<CellStatus>,
     pub doors: Vec<DoorState>,
+    #[serde(default)]
+    pub augments: Vec<AugmentId>,
+    /// Dead crew the clone bay will bring back, first in line first.
+    #[serde(default)]
+    pub cloning: VecDeque<Cloning>,
+    /// Boarders from other ships.
+    #[serde(default)]
+    pub intruders: Vec<Intruder>,
+    /// Systems waiting for reactor power to free up, first in line first.
+    #[serde(default)]
+    pub power_queue: Vec<SystemId>,
+    /// Crew head back to their saved stations once they're out of work.
+    #[serde(default)]
+    pub auto_return: bool,
+    /// Hull repairs made so far, out of what the ship's augments allow.
+    #[serde(default)]
+    pub hull_repairs_used: usize,
+    /// Seconds until the hull can be repaired again.
+    #[serde(default)]
+    pub hull_repair_cooldown: f32,
+    /// Damaged systems crew head for once they finish a job, most important first.
+    #[serde(default)]
+    pub repair_priority: Vec<SystemId>,
+    #[serde(default)]
+    pub door_automation: DoorAutomation,
+    /// Whether each room was on fire and breached last tick, so door automation only steps in
+    /// when that changes.
+    #[serde(default)]
+    room_hazards: Vec<(bool, bool)>,
     nav_mesh: NavMesh,
-    path_graph: PathGraph,
+    pathfinder: Pathfinder,
+}
+
+impl MapEntities for ShipState {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let Some(weapons) = &mut self.systems.weapons {
+            weapons.map_entities(entity_mapper);
+        }
+        for intruder in &mut self.intruders {
+            intruder.owner = entity_mapper.map_entity(intruder.owner);
+        }
+    }
 }
 
 impl ShipState {
@@ -47,42 +150,73 @@ impl ShipState {
             ship_type,
             reactor: Reactor::new(0),
             systems: default(),
+            subsystems: default(),
             max_hull: 30,
             damage: 0,
             crew: default(),
             missiles: 10,
-            oxygen: vec![1.0; SHIPS[ship_type].rooms.len()],
+            scrap: 0,
+            oxygen: vec![1.0; SHIPS[ship_type].cell_positions.len()],
+            suffocation_in: None,
+            cells: vec![default(); SHIPS[ship_type].cell_positions.len()],
             doors: SHIPS[ship_type]
                 .doors
                 .iter()
                 .map(|_| DoorState::default())
                 .collect(),
+            augments: default(),
+            cloning: default(),
+            intruders: default(),
+            power_queue: default(),
+            auto_return: false,
+            hull_repairs_used: 0,
+            hull_repair_cooldown: 0.0,
+            repair_priority: default(),
+            door_automation: default(),
+            room_hazards: default(),
             nav_mesh: NavMesh {
                 lines: nav_lines.into(),
                 squares: nav_squares.into(),
             },
-            path_graph: PathGraph {
-                edges: SHIPS[ship_type]
-                    .path_graph
-                    .iter()
-                    .map(|&(key, values)| (key, values.iter().copied().collect()))
-                    .collect(),
-            },
+            pathfinder: Pathfinder::new(
+                PathGraph {
+                    edges: SHIPS[ship_type]
+                        .path_graph
+                        .iter()
+                        .map(|&(key, values)| (key, values.iter().copied().collect()))
+                        .collect(),
+                },
+                SHIPS[ship_type].cell_positions.to_vec(),
+            ),
         }
     }
 
-    pub fn self_intel(&self, ship: Entity) -> SelfIntel {
+    pub fn self_intel(&self, ship: Entity, balance_hash: u64) -> SelfIntel {
+        let oxygen = self.oxygen.iter().copied().average().unwrap();
         SelfIntel {
             ship,
-            max_power: self.reactor.upgrade_level,
+            max_power: self.reactor.upgrade_level - self.reactor.ionized,
             free_power: self.reactor.available,
             missiles: self.missiles,
+            reserved_missiles: self.reserved_missiles(),
+            missile_floor: self
+                .systems
+                .weapons
+                .as_ref()
+                .map_or(0, |weapons| weapons.missile_floor),
+            scrap: self.scrap,
             weapon_targets: self
                 .systems
                 .weapons
                 .as_ref()
                 .map(|weapons| weapons.weapons().iter().map(|x| x.target()).collect())
                 .unwrap_or_default(),
+            cargo: self
+                .systems
+                .weapons
+                .as_ref()
+                .map(|weapons| weapons.cargo().iter().map(|x| x.id()).collect())
+                .unwrap_or_default(),
             crew: self.crew.clone(),
             autofire: self
                 .systems
@@ -90,8 +224,96 @@ impl ShipState {
                 .as_ref()
                 .map(|weapons| weapons.autofire)
                 .unwrap_or(false),
-            oxygen: self.oxygen.iter().copied().average().unwrap(),
+            oxygen,
+            suffocation_in: self.suffocation_in,
+            augments: self.augments.clone(),
+            alarms: raised_alarms(
+                oxygen,
+                self.suffocation_in,
+                self.max_hull - self.damage,
+                self.max_hull,
+                self.cells.iter().any(|x| x.on_fire),
+                !self.intruders.is_empty(),
+            ),
+            balance_hash,
+            cloning: self
+                .cloning
+                .iter()
+                .map(|x| CloningIntel {
+                    name: x.crew.name.clone(),
+                    progress: x.progress,
+                })
+                .collect(),
+            power_queue: self.power_queue.clone(),
+            auto_return: self.auto_return,
+            hull_repairs: self.hull_repairs_left(),
+            hull_repair_cooldown: self.hull_repair_cooldown,
+            repair_priority: self.repair_priority.clone(),
+            door_automation: self.door_automation,
+            depower_order: self
+                .systems
+                .weapons
+                .as_ref()
+                .map_or_else(default, |weapons| weapons.depower_order),
+            depower_next: self
+                .systems
+                .weapons
+                .as_ref()
+                .and_then(|weapons| weapons.depower_next()),
+        }
+    }
+
+    pub fn install_augment(&mut self, augment: AugmentId) {
+        if self.augments.len() >= MAX_AUGMENTS {
+            eprintln!("Can't install {augment:?}, ship already has {MAX_AUGMENTS} augments.");
+            return;
         }
+        self.augments.push(augment);
+        self.raise_super_shield();
+    }
+
+    pub fn augment_effects(&self) -> AugmentEffects {
+        AugmentEffects::of(&self.augments)
+    }
+
+    pub fn hull_repairs_left(&self) -> usize {
+        self.augment_effects()
+            .hull_repairs
+            .saturating_sub(self.hull_repairs_used)
+    }
+
+    /// Set which damaged systems crew go and fix first. Anything listed twice keeps its first spot.
+    pub fn set_repair_priority(&mut self, priority: Vec<SystemId>) {
+        self.repair_priority.clear();
+        for system in priority {
+            if !self.repair_priority.contains(&system) {
+                self.repair_priority.push(system);
+            }
+        }
+    }
+
+    /// Spend a hull repair of up to `amount`, returning how much hull it patched up. The next one
+    /// is `cooldown` seconds away.
+    pub fn repair_hull(&mut self, amount: usize, cooldown: f32) -> Result<usize, String> {
+        if self.hull_repairs_left() == 0 {
+            return Err("No hull repairs left".into());
+        }
+        if self.hull_repair_cooldown > 0.0 {
+            let secs = self.hull_repair_cooldown.ceil();
+            return Err(format!("Hull repair ready in {secs}s"));
+        }
+        if self.damage == 0 {
+            return Err("Hull is already in one piece".into());
+        }
+        let amount = self.damage.min(amount);
+        self.damage -= amount;
+        self.hull_repairs_used += 1;
+        self.hull_repair_cooldown = cooldown;
+        Ok(amount)
+    }
+
+    pub fn update_hull_repair(&mut self) {
+        self.hull_repair_cooldown = (self.hull_repair_cooldown - 1.0 / 64.0).max(0.0);
     }
 
     pub fn basic_intel(&self) -> BasicIntel {
@@ -110,6 +332,7 @@ impl ShipState {
                 layers: shields.layers,
                 charge: shields.charge,
                 damage: shields.damage_intel(),
+                super_shield: shields.super_shield,
             }),
             engines: self
                 .systems
@@ -132,10 +355,21 @@ impl ShipState {
                 .oxygen
                 .as_ref()
                 .map(|oxygen| oxygen.damage_intel()),
-            doors: self.doors.clone(),
+            clone_bay: self
+                .systems
+                .clone_bay
+                .as_ref()
+                .map(|clone_bay| clone_bay.damage_intel()),
+            subsystems: SubsystemId::iter()
+                .filter_map(|x| Some((x, self.subsystems.subsystem(x)?.damage_intel())))
+                .collect(),
         }
     }
 
+    pub fn doors_intel(&self) -> DoorsIntel {
+        DoorsIntel(self.doors.clone())
+    }
+
     pub fn crew_vision_intel(&self) -> CrewVisionIntel {
         CrewVisionIntel
     }
@@ -153,13 +387,37 @@ impl ShipState {
                         .filter(|x| x.is_in_room(room))
                         .map(|x| x.intel())
                         .collect(),
-                    oxygen: self.oxygen[i],
+                    intruders: self
+                        .intruders
+                        .iter()
+                        .filter(|x| x.crew.is_in_room(room))
+                        .map(|x| x.crew.intel())
+                        .collect(),
+                    oxygen: SHIPS[self.ship_type].room_average(i, &self.oxygen),
+                })
+                .collect(),
+            cells: zip(&self.cells, &self.oxygen)
+                .map(|(status, &oxygen)| CellIntel {
+                    on_fire: status.on_fire,
+                    breached: status.breached,
+                    oxygen,
                 })
                 .collect(),
-            cells: default(),
         }
     }
 
+    pub fn enemy_weapon_charge_intel(&self) -> EnemyWeaponChargeIntel {
+        let Some(weapons) = &self.systems.weapons else {
+            return EnemyWeaponChargeIntel { pips: Vec::new() };
+        };
+        EnemyWeaponChargeIntel::new(
+            weapons
+                .weapons()
+                .iter()
+                .map(|x| (x.charge(), x.weapon().common().charge_time)),
+        )
+    }
+
     pub fn weapon_charge_intel(&self) -> WeaponChargeIntel {
         WeaponChargeIntel {
             levels: self
@@ -179,85 +437,539 @@ impl ShipState {
         )
     }
 
+    pub fn subsystems_intel(&self) -> SubsystemsIntel {
+        SubsystemsIntel(
+            SubsystemId::iter()
+                .filter_map(|x| Some((x, self.subsystems.subsystem(x)?.intel())))
+                .collect(),
+        )
+    }
+
     pub fn update_weapons(&mut self) -> Option<impl Iterator<Item = Option<Volley>> + '_> {
+        let charge_rate = self.augment_effects().weapon_charge_rate;
         self.systems.weapons.as_mut().map(|weapons| {
-            let missiles = &mut self.missiles;
+            let mut missiles = MissileSupply::new(&mut self.missiles, weapons);
             let autofire = weapons.autofire;
             weapons
                 .weapons_mut()
-                .map(move |x| x.charge_and_fire(missiles, autofire))
+                .map(move |x| x.charge_and_fire(&mut missiles, autofire, charge_rate))
         })
     }
 
     pub fn update_repair_status(&mut self) {
         for (i, room) in SHIPS[self.ship_type].rooms.iter().enumerate() {
-            if let Some(system) = SHIPS[self.ship_type].room_systems[i] {
-                if !self.crew.iter().any(|x| x.is_in_room(room)) {
-                    let system = self.systems.system_mut(system).unwrap();
-                    system.cancel_repair();
+            let occupied = self.crew.iter().any(|x| x.is_in_room(room))
+                || self.intruders.iter().any(|x| x.crew.is_in_room(room));
+            if occupied {
+                continue;
+            }
+            // Not every ship has every system its layout has room for
+            if let Some(system) =
+                SHIPS[self.ship_type].room_systems[i].and_then(|x| self.systems.system_mut(x))
+            {
+                system.cancel_repair();
+            }
+            if let Some(subsystem) = SHIPS[self.ship_type].room_subsystems[i]
+                .and_then(|x| self.subsystems.subsystem_mut(x))
+            {
+                subsystem.cancel_repair();
+            }
+        }
+    }
+
+    /// Take any crew with no health left off the ship, returning them. If the ship has a clone
+    /// bay, they're also queued up to be brought back.
+    pub fn remove_dead_crew(&mut self) -> Vec<Crew> {
+        let (alive, dead): (_, Vec<_>) = std::mem::take(&mut self.crew)
+            .into_iter()
+            .partition(|x| x.health > 0.0);
+        self.crew = alive;
+        if self.systems.clone_bay.is_some() {
+            self.cloning.extend(dead.iter().map(|crew| Cloning {
+                crew: crew.clone(),
+                progress: 0.0,
+            }));
+        }
+        dead
+    }
+
+    /// Take crew `indices` off the ship to fly a boarding shuttle, returning them.
+    pub fn board_shuttle(
+        &mut self,
+        indices: &[usize],
+        capacity: usize,
+    ) -> Result<Vec<Crew>, String> {
+        if indices.is_empty() {
+            return Err("No crew selected to board the shuttle".into());
+        }
+        if indices.len() > capacity {
+            return Err(format!("The shuttle only holds {capacity} crew"));
+        }
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if let Some(&index) = indices.iter().find(|&&x| x >= self.crew.len()) {
+            return Err(format!("No crew member {index}"));
+        }
+        // Back to front so earlier indices stay put
+        let mut boarders = indices
+            .into_iter()
+            .rev()
+            .map(|x| self.crew.remove(x))
+            .collect::<Vec<_>>();
+        boarders.reverse();
+        Ok(boarders)
+    }
+
+    /// Put crew from `owner`'s shuttle aboard, spread over the cells of `room`.
+    pub fn take_boarders(&mut self, boarders: Vec<Crew>, owner: Entity, room: usize) {
+        let cells = SHIPS[self.ship_type].rooms[room].cells;
+        for (mut crew, &cell) in boarders.into_iter().zip(cells.iter().cycle()) {
+            crew.nav_status = CrewNavStatus::At(cell);
+            crew.task = CrewTask::Idle;
+            crew.station = None;
+            self.intruders.push(Intruder { crew, owner });
+        }
+    }
+
+    /// Advance boarders by one tick. Boarders and crew sharing a room fight, and boarders with a
+    /// room to themselves sabotage its system, or go looking for one to sabotage. Boarders breathe
+    /// the same air as everyone else. Returns defenders killed along with the ship whose boarders
+    /// did it, then any boarders that died.
+    pub fn update_intruders(
+        &mut self,
+        balance: &BalanceConfig,
+    ) -> (Vec<(Crew, Option<Entity>)>, Vec<Intruder>) {
+        let dt = 1.0 / 64.0;
+        self.move_intruders(balance);
+        let mut killers = Vec::new();
+        for (i, room) in SHIPS[self.ship_type].rooms.iter().enumerate() {
+            let intruders = self
+                .intruders
+                .iter()
+                .filter(|x| x.crew.is_in_room(room))
+                .count();
+            if intruders == 0 {
+                continue;
+            }
+            let defenders = self.crew.iter().filter(|x| x.is_in_room(room)).count();
+            if defenders > 0 {
+                // Everyone gangs up on the first one they find
+                let damage = balance.crew_combat_damage * dt;
+                let intruder = self
+                    .intruders
+                    .iter_mut()
+                    .find(|x| x.crew.is_in_room(room))
+                    .unwrap();
+                intruder.crew.health -= damage * defenders as f32;
+                let owner = intruder.owner;
+                let defender = self.crew.iter_mut().find(|x| x.is_in_room(room)).unwrap();
+                defender.health -= damage * intruders as f32;
+                if defender.health <= 0.0 {
+                    killers.push((defender.name.clone(), owner));
                 }
+                // TODO upgrade crew combat skill
+            } else if let Some(system) =
+                SHIPS[self.ship_type].room_systems[i].and_then(|x| self.systems.system_mut(x))
+            {
+                let amount = intruders as f32 / (64.0 * balance.sabotage_time);
+                system.crew_damage(amount, &mut self.reactor);
+            }
+        }
+        for Intruder { crew, .. } in &mut self.intruders {
+            let Cell(cell) = crew.nav_status.current_cell();
+            if self.oxygen[cell] < balance.suffocation_oxygen {
+                crew.health -= balance.suffocation_damage * dt;
+            }
+            if self.cells[cell].on_fire {
+                crew.health -= balance.fire_crew_damage * dt;
             }
         }
+        let (alive, dead_intruders) = std::mem::take(&mut self.intruders)
+            .into_iter()
+            .partition(|x| x.crew.health > 0.0);
+        self.intruders = alive;
+        let dead_defenders = self
+            .remove_dead_crew()
+            .into_iter()
+            .map(|crew| {
+                let killer = killers.iter().find(|(name, _)| *name == crew.name);
+                (crew, killer.map(|&(_, owner)| owner))
+            })
+            .collect();
+        (dead_defenders, dead_intruders)
     }
 
-    pub fn update_crew(&mut self) {
+    /// Boarders with nothing left to sabotage where they stand head for the nearest room with a
+    /// working system, one room at a time. Closed doors in the way get broken down.
+    fn move_intruders(&mut self, balance: &BalanceConfig) {
+        let ship_type = &SHIPS[self.ship_type];
+        let mut breaking = vec![0; self.doors.len()];
+        let traffic = self.traffic();
+        for i in 0..self.intruders.len() {
+            let others = traffic_except(&traffic, self.crew.len() + i);
+            self.intruders[i]
+                .crew
+                .nav_status
+                .step(&self.nav_mesh, &others);
+            let CrewNavStatus::At(cell) = self.intruders[i].crew.nav_status else {
+                continue;
+            };
+            let room = ship_type.cell_room(cell);
+            let defended = self
+                .crew
+                .iter()
+                .any(|x| x.is_in_room(&ship_type.rooms[room]));
+            if defended || self.can_sabotage(room) {
+                continue;
+            }
+            let Some(door) = (0..ship_type.rooms.len())
+                .filter(|&x| self.can_sabotage(x))
+                .filter_map(|x| ship_type.door_path(room, x))
+                .min_by_key(|x| x.len())
+                .and_then(|x| x.first().copied())
+            else {
+                continue;
+            };
+            if !self.doors[door].is_open() {
+                breaking[door] += 1;
+                continue;
+            }
+            let next_room = ship_type
+                .door_rooms(door)
+                .into_iter()
+                .find(|&x| x != room)
+                .unwrap();
+            let is_unoccupied = |cell: Cell| {
+                self.intruders
+                    .iter()
+                    .all(|x| x.crew.nav_status.occupied_cell() != cell)
+            };
+            let Some(&target_cell) = ship_type.rooms[next_room]
+                .cells
+                .iter()
+                .find(|&&x| is_unoccupied(x))
+            else {
+                continue;
+            };
+            // Not every room is reachable on foot, in which case they stay put and keep trying
+            let _ = Self::path_crew_to(
+                &mut self.pathfinder,
+                &self.nav_mesh,
+                &mut self.intruders[i].crew.nav_status,
+                target_cell,
+            );
+        }
+        // Sturdier doors for every working level of the doors subsystem
+        let door_level = self
+            .subsystems
+            .subsystem(SubsystemId::Doors)
+            .map_or(1, |x| x.upgrade_level() - x.damage())
+            .max(1);
+        let break_time = balance.door_break_time * door_level as f32;
+        for (door, boarders) in zip(&mut self.doors, breaking) {
+            if boarders == 0 {
+                door.break_progress = 0.0;
+                continue;
+            }
+            door.break_progress += boarders as f32 / (64.0 * break_time);
+            if door.break_progress >= 1.0 {
+                door.break_progress = 0.0;
+                door.broken_timer = balance.door_broken_time;
+            }
+        }
+    }
+
+    /// Whether `room` has a system boarders haven't finished wrecking yet.
+    fn can_sabotage(&self, room: usize) -> bool {
+        SHIPS[self.ship_type].room_systems[room]
+            .and_then(|x| self.systems.system(x))
+            .is_some_and(|x| x.damage() < x.upgrade_level())
+    }
+
+    /// Whether any of our crew are at the station for `system`, in the room it's installed in.
+    pub fn is_manned(&self, system: SystemId) -> bool {
+        SHIPS[self.ship_type]
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(system))
+            .is_some_and(|room| self.has_crew_in(room))
+    }
+
+    fn has_crew_in(&self, room: usize) -> bool {
+        let room = &SHIPS[self.ship_type].rooms[room];
+        self.crew.iter().any(|x| x.is_in_room(room))
+    }
+
+    /// Whether any of our crew are at the station for `subsystem`, like [`Self::is_manned`].
+    pub fn is_subsystem_manned(&self, subsystem: SubsystemId) -> bool {
+        SHIPS[self.ship_type]
+            .room_subsystems
+            .iter()
+            .position(|&x| x == Some(subsystem))
+            .is_some_and(|room| self.has_crew_in(room))
+    }
+
+    /// Broken doors fix themselves over time, quicker with someone manning the doors subsystem.
+    pub fn update_doors(&mut self, balance: &BalanceConfig) {
+        let dt = 1.0 / 64.0;
+        let rate = if self.is_subsystem_manned(SubsystemId::Doors) {
+            1.0 + balance.door_broken_time / balance.door_repair_time
+        } else {
+            1.0
+        };
+        for door in &mut self.doors {
+            if door.broken() {
+                door.broken_timer = (door.broken_timer - rate * dt).max(0.0);
+            }
+        }
+    }
+
+    /// Shut the doors of rooms that have just caught fire or been breached, or vent the fires, as
+    /// [`DoorAutomation`] says. Nothing happens while door control is down.
+    pub fn update_door_automation(&mut self) {
+        let ship_type = &SHIPS[self.ship_type];
+        let hazards = ship_type
+            .rooms
+            .iter()
+            .map(|room| {
+                let on_fire = room.cells.iter().any(|&Cell(x)| self.cells[x].on_fire);
+                let breached = room.cells.iter().any(|&Cell(x)| self.cells[x].breached);
+                (on_fire, breached)
+            })
+            .collect::<Vec<_>>();
+        let previous = std::mem::replace(&mut self.room_hazards, hazards.clone());
+        if self.door_automation == DoorAutomation::Off
+            || !self.subsystems.is_working(SubsystemId::Doors)
+        {
+            return;
+        }
+        let occupied = self
+            .crew
+            .iter()
+            .map(|x| ship_type.cell_room(x.nav_status.occupied_cell()))
+            .collect::<HashSet<_>>();
+        for (room, &(on_fire, breached)) in hazards.iter().enumerate() {
+            let (was_on_fire, was_breached) = previous.get(room).copied().unwrap_or_default();
+            let caught_fire = on_fire && !was_on_fire;
+            let got_breached = breached && !was_breached;
+            if !caught_fire && !got_breached {
+                continue;
+            }
+            // A breach is already letting the air out
+            let vent = caught_fire
+                && !breached
+                && self.door_automation == DoorAutomation::SealAndVent
+                && !occupied.contains(&room);
+            for (index, door) in self.doors.iter_mut().enumerate() {
+                if !ship_type.door_rooms(index).contains(&room) {
+                    continue;
+                }
+                door.open = vent && matches!(ship_type.doors[index], Door::Exterior(..));
+            }
+        }
+    }
+
+    /// Advance the clone bay by one tick. It only works on the first crew member in line, and only
+    /// while it's powered and undamaged. A finished clone waits in the bay until there's a free
+    /// cell with air and no fire to put them in. Returns the names of any crew that came back.
+    ///
+    /// Clones only come back weakened, not less skilled, since crew don't have skills to lose.
+    pub fn update_cloning(&mut self, balance: &BalanceConfig) -> Vec<String> {
+        let Some(clone_bay) = &self.systems.clone_bay else {
+            return Vec::new();
+        };
+        if !clone_bay.is_working() {
+            return Vec::new();
+        }
+        let Some(next) = self.cloning.front_mut() else {
+            return Vec::new();
+        };
+        next.progress += 1.0 / (64.0 * balance.clone_time);
+        if next.progress < 1.0 {
+            return Vec::new();
+        }
+        let Some(room) = SHIPS[self.ship_type]
+            .room_systems
+            .iter()
+            .position(|x| *x == Some(SystemId::CloneBay))
+        else {
+            return Vec::new();
+        };
+        let occupied = self
+            .crew
+            .iter()
+            .map(|x| &x.nav_status)
+            .chain(self.intruders.iter().map(|x| &x.crew.nav_status))
+            .map(CrewNavStatus::occupied_cell)
+            .collect::<Vec<_>>();
+        let Some(&cell) = SHIPS[self.ship_type].rooms[room]
+            .cells
+            .iter()
+            .find(|&&cell| {
+                let Cell(index) = cell;
+                !occupied.contains(&cell)
+                    && !self.cells[index].on_fire
+                    && self.oxygen[index] >= balance.suffocation_oxygen
+            })
+        else {
+            next.progress = 1.0;
+            return Vec::new();
+        };
+        let Cloning { mut crew, .. } = self.cloning.pop_front().unwrap();
+        crew.nav_status = CrewNavStatus::At(cell);
+        crew.health = RACES[crew.race].max_health * balance.clone_health;
+        crew.task = CrewTask::Idle;
+        crew.station = None;
+        let name = crew.name.clone();
+        self.crew.push(crew);
+        vec![name]
+    }
+
+    /// Advance crew by one tick. Returns any crew that died (suffocated or burned) this tick.
+    pub fn update_crew(&mut self, balance: &BalanceConfig) -> Vec<Crew> {
+        let effects = self.augment_effects();
         for crew in &mut self.crew {
-            let cell = crew.nav_status.current_cell();
+            let Cell(cell) = crew.nav_status.current_cell();
+            let dt = 1.0 / 64.0;
+            if self.oxygen[cell] < balance.suffocation_oxygen {
+                crew.health -= balance.suffocation_damage * effects.suffocation_damage * dt;
+            }
+            if self.cells[cell].on_fire {
+                crew.health -= balance.fire_crew_damage * dt;
+            }
+        }
+        let dead = self.remove_dead_crew();
+        let traffic = self.traffic();
+        // Where each crew member is headed, so nobody heads back to a station someone else is
+        // already bound for
+        let mut reserved = self
+            .crew
+            .iter()
+            .map(|x| x.nav_status.occupied_cell())
+            .collect::<Vec<_>>();
+        for (i, crew) in self.crew.iter_mut().enumerate() {
+            crew.nav_status
+                .step(&self.nav_mesh, &traffic_except(&traffic, i));
+            let &CrewNavStatus::At(cell) = &crew.nav_status else {
+                // Whatever they were doing, they've walked away from it
+                crew.task = CrewTask::Idle;
+                continue;
+            };
             let room = SHIPS[self.ship_type]
                 .rooms
                 .iter()
-                .position(|x| x.cells.iter().any(|x| *x == cell))
+                .position(|x| x.cells.contains(&cell))
                 .unwrap();
-            if self.oxygen[room] < 0.05 {
-                let rate = -6.4;
-                let dt = 1.0 / 64.0;
-                crew.health += rate * dt;
+            let room_cells = SHIPS[self.ship_type].rooms[room].cells;
+            let fire = room_cells.iter().find(|&&Cell(x)| self.cells[x].on_fire);
+            let breach = room_cells.iter().find(|&&Cell(x)| self.cells[x].breached);
+            let boarders = self
+                .intruders
+                .iter()
+                .any(|x| x.crew.is_in_room(&SHIPS[self.ship_type].rooms[room]));
+            let task = if boarders {
+                // The fighting itself happens in `update_intruders`
+                CrewTask::Fight
+            } else if let Some(&Cell(x)) = fire.or(breach) {
+                // Stop drop and roll, then fix the hull
+                self.cells[x].repair(1.0 / (64.0 * balance.cell_repair_time));
+                CrewTask::RepairHull
+            } else if let Some(system) = SHIPS[self.ship_type].room_systems[room]
+                .and_then(|x| self.systems.system_mut(x))
+                .filter(|x| x.damage() > 0)
+            {
+                system.crew_repair(1.0 / (64.0 * balance.system_repair_time));
+                CrewTask::RepairSystem
+            } else if let Some(subsystem) = SHIPS[self.ship_type].room_subsystems[room]
+                .and_then(|x| self.subsystems.subsystem_mut(x))
+                .filter(|x| x.damage() > 0)
+            {
+                subsystem.crew_repair(1.0 / (64.0 * balance.system_repair_time));
+                CrewTask::RepairSystem
+            } else {
+                // Move to manning station if unoccupied
+                // Man system
+                CrewTask::Idle
+            };
+            let previous = std::mem::replace(&mut crew.task, task);
+            let finished =
+                !matches!(previous, CrewTask::Idle) && matches!(crew.task, CrewTask::Idle);
+            if finished {
+                let repair = repair_destination(
+                    self.ship_type,
+                    &self.systems,
+                    &self.repair_priority,
+                    &reserved,
+                );
+                let heading_off = repair.is_some_and(|target| {
+                    Self::path_crew_to(
+                        &mut self.pathfinder,
+                        &self.nav_mesh,
+                        &mut crew.nav_status,
+                        target,
+                    )
+                    .is_ok()
+                });
+                if heading_off {
+                    reserved[i] = crew.nav_status.occupied_cell();
+                    continue;
+                }
             }
-        }
-        self.crew.retain(|x| x.health > 0.0);
-        for crew in &mut self.crew {
-            crew.nav_status.step(&self.nav_mesh);
-            if let &CrewNavStatus::At(cell) = &crew.nav_status {
-                let room = SHIPS[self.ship_type]
-                    .rooms
+            if let Some(station) = crew.station.filter(|&x| x != cell && finished) {
+                let taken = reserved
                     .iter()
-                    .position(|x| x.cells.iter().any(|x| *x == cell))
-                    .unwrap();
-                // if enemy_crew_in_room {
-                //     KILL HIM
-                // } else if fire_in_room {
-                //     stop drop and roll
-                // } else if hull_breach_in_room {
-                //     fix it
-                // } else
-                if let Some(system) = SHIPS[self.ship_type].room_systems[room] {
-                    let system = self.systems.system_mut(system).unwrap();
-                    if system.damage() > 0 {
-                        system.crew_repair(1.0 / 768.0);
-                    } else {
-                        // Move to manning station if unoccupied
-                        // Man system
-                    }
+                    .enumerate()
+                    .any(|(j, &x)| j != i && x == station);
+                if self.auto_return && !taken {
+                    let _ = Self::path_crew_to(
+                        &mut self.pathfinder,
+                        &self.nav_mesh,
+                        &mut crew.nav_status,
+                        station,
+                    );
+                    reserved[i] = crew.nav_status.occupied_cell();
                 }
             }
         }
+        dead
     }
 
-    pub fn update_oxygen(&mut self) {
-        let fill_rate = match self
-            .systems
-            .oxygen
-            .as_ref()
-            .map_or(0, |x| x.current_power())
-        {
-            1 => 0.012,
-            2 => 0.048,
-            3 => 0.084,
-            _ => -0.012,
+    pub fn update_oxygen(&mut self, balance: &BalanceConfig) {
+        let oxygen_system = self.systems.oxygen.as_ref();
+        let power = oxygen_system.map_or(0, |x| x.current_power());
+        let fill_rate = match oxygen_system {
+            Some(x) if x.damage() >= x.upgrade_level() => balance.oxygen_destroyed_rate,
+            _ => balance.oxygen_fill_rate(power),
         };
         let ship = &SHIPS[self.ship_type];
-        let room_count = ship.rooms.len();
-        let mut fill_rate = vec![fill_rate; room_count];
+        let mut fill_rate = vec![fill_rate; self.oxygen.len()];
+        if power == 0 {
+            // Nothing's topping the air up, so everyone aboard is breathing down what's left
+            let breathing = self
+                .crew
+                .iter()
+                .chain(self.intruders.iter().map(|x| &x.crew));
+            for crew in breathing {
+                let room = &ship.rooms[ship.cell_room(crew.nav_status.current_cell())];
+                for &Cell(cell) in room.cells {
+                    fill_rate[cell] -= balance.crew_oxygen_use / room.cells.len() as f32;
+                }
+            }
+        }
+        for (Cell(a), Cell(b)) in ship.room_adjacencies() {
+            let diff = balance.cell_flow * (self.oxygen[b] - self.oxygen[a]);
+            fill_rate[a] += diff;
+            fill_rate[b] -= diff;
+        }
+        for (cell, status) in self.cells.iter().enumerate() {
+            if status.breached {
+                fill_rate[cell] -= balance.door_flow * self.oxygen[cell];
+            }
+            if status.on_fire {
+                fill_rate[cell] -= balance.fire_burn_rate;
+            }
+        }
         for door in ship
             .doors
             .iter()
@@ -266,31 +978,75 @@ impl ShipState {
             .map(|(_, x)| *x)
         {
             match door {
-                Door::Interior(a, b) => {
-                    let a_room = ship.cell_room(a);
-                    let b_room = ship.cell_room(b);
-                    let diff = self.oxygen[b_room] - self.oxygen[a_room];
-                    fill_rate[a_room] += diff;
-                    fill_rate[b_room] -= diff;
+                Door::Interior(Cell(a), Cell(b)) => {
+                    let diff = balance.door_flow * (self.oxygen[b] - self.oxygen[a]);
+                    fill_rate[a] += diff;
+                    fill_rate[b] -= diff;
                 }
-                Door::Exterior(cell, _) => {
-                    let room = ship.cell_room(cell);
-                    fill_rate[room] -= self.oxygen[room];
+                Door::Exterior(Cell(cell), _) => {
+                    fill_rate[cell] -= balance.door_flow * self.oxygen[cell];
                 }
             }
         }
+        // Air moving between cells cancels out, leaving what's made and what's lost
+        let average = self.oxygen.iter().copied().average().unwrap_or_default();
+        let average_rate = fill_rate.iter().copied().average().unwrap_or_default();
+        self.suffocation_in = (average_rate < 0.0)
+            .then(|| (average - balance.suffocation_oxygen).max(0.0) / -average_rate);
         let dt = 1.0 / 64.0;
-        for (room_oxygen, fill_rate) in zip(&mut self.oxygen, fill_rate) {
-            *room_oxygen = (*room_oxygen + fill_rate * dt).clamp(0.0, 1.0);
+        for (cell_oxygen, fill_rate) in zip(&mut self.oxygen, fill_rate) {
+            *cell_oxygen = (*cell_oxygen + fill_rate * dt).clamp(0.0, 1.0);
+        }
+        // Fires starve without air
+        for (status, &oxygen) in zip(&mut self.cells, &self.oxygen) {
+            if oxygen < balance.fire_min_oxygen {
+                status.on_fire = false;
+            }
         }
     }
 
+    /// Check `fire_roll` and `breach_roll` against the chances of a hit on `cell`. Returns whether
+    /// it started a fire.
+    pub fn damage_cell(
+        &mut self,
+        Cell(cell): Cell,
+        damage: &DamageSpec,
+        fire_roll: f32,
+        breach_roll: f32,
+    ) -> bool {
+        let status = &mut self.cells[cell];
+        let ignited = !status.on_fire && fire_roll < damage.fire_chance;
+        status.on_fire |= ignited;
+        status.breached |= breach_roll < damage.breach_chance;
+        ignited
+    }
+
+    pub fn install_subsystem(&mut self, subsystem: SubsystemId) {
+        if self.subsystems.subsystem(subsystem).is_some() {
+            eprintln!("Can't install {subsystem} on ship, subsystem is already installed.");
+            return;
+        }
+        self.subsystems.install(subsystem);
+    }
+
     pub fn install_system(&mut self, system: SystemId) {
         if self.systems.system(system).is_some() {
             eprintln!("Can't install {system} on ship, system is already installed.");
             return;
         }
-        self.systems.install(system);
+        self.systems.install(system, self.ship_type);
+        if system == SystemId::Shields {
+            self.raise_super_shield();
+        }
+    }
+
+    /// Bring the super shield up to full strength: whatever the hull comes with plus what the
+    /// augments add. This is the only way it comes back, it doesn't recharge on its own.
+    pub fn raise_super_shield(&mut self) {
+        let strength = SHIPS[self.ship_type].super_shield + self.augment_effects().super_shield;
+        if let Some(shields) = &mut self.systems.shields {
+            shields.super_shield = strength;
+        }
     }
 
     pub fn request_power(&mut self, system: SystemId) {
@@ -306,6 +1062,64 @@ impl ShipState {
         );
     }
 
+    /// Wait for reactor power to free up for `system`, then give it one more increment. A system
+    /// only gets one place in line.
+    pub fn queue_power(&mut self, system: SystemId) {
+        let Some(status) = self.systems.system(system) else {
+            eprintln!("Can't queue power for {system}, system not installed.");
+            return;
+        };
+        if status.next_power_step().is_none() {
+            eprintln!("Can't queue power for {system}, system can't take any more.");
+            return;
+        }
+        if !self.power_queue.contains(&system) {
+            self.power_queue.push(system);
+        }
+    }
+
+    pub fn cancel_queued_power(&mut self, system: SystemId) {
+        self.power_queue.retain(|&x| x != system);
+    }
+
+    /// Hand out free reactor power to queued systems in order. Requests that don't fit yet keep
+    /// their place; ones that can never be served (the system is full, or the power was there
+    /// and it still wouldn't take it) are dropped.
+    pub fn apply_power_queue(&mut self) {
+        for system in std::mem::take(&mut self.power_queue) {
+            let Some(status) = self.systems.system(system) else {
+                continue;
+            };
+            let Some(step) = status.next_power_step() else {
+                continue;
+            };
+            if step > self.reactor.available {
+                self.power_queue.push(system);
+                continue;
+            }
+            self.request_power(system);
+        }
+    }
+
+    /// Knock `bars` of reactor power offline, or bring it back online with a smaller number. If
+    /// there isn't enough free power, systems are depowered until there is, the clone bay and
+    /// oxygen first since they take the longest to matter.
+    pub fn set_ionized_power(&mut self, bars: usize) {
+        let bars = bars.min(self.reactor.upgrade_level);
+        self.reactor.available += self.reactor.ionized;
+        self.reactor.ionized = 0;
+        for system in SystemId::iter().rev() {
+            let Some(system) = self.systems.system_mut(system) else {
+                continue;
+            };
+            while self.reactor.available < bars && system.current_power() > 0 {
+                system.remove_power(&mut self.reactor);
+            }
+        }
+        self.reactor.ionized = bars.min(self.reactor.available);
+        self.reactor.available -= self.reactor.ionized;
+    }
+
     pub fn remove_power(&mut self, system: SystemId) {
         let Some(system) = self.systems.system_mut(system) else {
             eprintln!("Can't remove power from {system}, system not installed.");
@@ -314,6 +1128,36 @@ impl ShipState {
         system.remove_power(&mut self.reactor);
     }
 
+    /// Take power from `from`, one step at a time, until `to` can take one more increment. If it
+    /// can't even with `from` drained, put everything back the way it was.
+    pub fn reroute_power(&mut self, from: SystemId, to: SystemId) {
+        if from == to {
+            eprintln!("Can't reroute power from {from} into itself.");
+            return;
+        }
+        let (Some(_), Some(target)) = (self.systems.system(from), self.systems.system(to)) else {
+            eprintln!("Can't reroute power from {from} to {to}, system not installed.");
+            return;
+        };
+        let before = target.current_power();
+        let mut taken = 0;
+        loop {
+            self.request_power(to);
+            if self.systems.system(to).unwrap().current_power() > before {
+                return;
+            }
+            if self.systems.system(from).unwrap().current_power() == 0 {
+                break;
+            }
+            self.remove_power(from);
+            taken += 1;
+        }
+        eprintln!("Can't reroute power from {from} to {to}, {to} can't take any more.");
+        for _ in 0..taken {
+            self.request_power(from);
+        }
+    }
+
     pub fn power_weapon(&mut self, index: usize) {
         let Some(weapons) = &mut self.systems.weapons else {
             eprintln!("Can't power weapon, weapons system not installed.");
@@ -322,6 +1166,20 @@ impl ShipState {
         weapons.power_weapon(index, self.missiles, &mut self.reactor);
     }
 
+    /// Power up every weapon the reactor can cover, in slot order, and charge them all the way.
+    pub fn precharge_weapons(&mut self) {
+        let Some(weapons) = &mut self.systems.weapons else {
+            eprintln!("Can't precharge weapons, weapons system not installed.");
+            return;
+        };
+        for index in 0..weapons.weapons().len() {
+            weapons.power_weapon(index, self.missiles, &mut self.reactor);
+        }
+        for weapon in weapons.weapons_mut() {
+            weapon.fill_charge();
+        }
+    }
+
     pub fn depower_weapon(&mut self, index: usize) {
         let Some(weapons) = &mut self.systems.weapons else {
             eprintln!("Can't depower weapon, weapons system not installed.");
@@ -334,13 +1192,12 @@ impl ShipState {
         &mut self,
         weapon_index: usize,
         target: Option<RoomTarget>,
-        targeting_self: bool,
     ) {
         let Some(weapons) = &mut self.systems.weapons else {
             eprintln!("Can't set weapon target, weapons system notinstalled.");
             return;
         };
-        weapons.set_projectile_weapon_target(weapon_index, target, targeting_self);
+        weapons.set_projectile_weapon_target(weapon_index, target);
     }
 
     pub fn set_beam_weapon_target(&mut self, weapon_index: usize, target: Option<BeamTarget>) {
@@ -351,6 +1208,22 @@ impl ShipState {
         weapons.set_beam_weapon_target(weapon_index, target);
     }
 
+    pub fn install_from_cargo(&mut self, cargo_index: usize, slot: usize) {
+        let Some(weapons) = &mut self.systems.weapons else {
+            eprintln!("Can't install weapon, weapons system not installed.");
+            return;
+        };
+        weapons.install_from_cargo(cargo_index, slot, &mut self.reactor);
+    }
+
+    pub fn store_weapon(&mut self, weapon_index: usize) {
+        let Some(weapons) = &mut self.systems.weapons else {
+            eprintln!("Can't store weapon, weapons system not installed.");
+            return;
+        };
+        weapons.store_weapon(weapon_index, &mut self.reactor);
+    }
+
     pub fn move_weapon(&mut self, weapon_index: usize, target_index: usize) {
         let Some(weapons) = &mut self.systems.weapons else {
             eprintln!("Can't move weapon, weapons system not installed.");
@@ -359,10 +1232,47 @@ impl ShipState {
         weapons.move_weapon(weapon_index, target_index);
     }
 
-    pub fn set_crew_goal(&mut self, crew_index: usize, room_index: usize) {
+    /// Open every door that doesn't touch a room our crew are in, and close every one that does.
+    pub fn vent_unoccupied(&mut self) {
+        let ship_type = &SHIPS[self.ship_type];
+        let occupied = self
+            .crew
+            .iter()
+            .map(|x| ship_type.cell_room(x.nav_status.occupied_cell()))
+            .collect::<HashSet<_>>();
+        for (index, door) in self.doors.iter_mut().enumerate() {
+            let rooms = ship_type.door_rooms(index);
+            door.open = !rooms.iter().any(|x| occupied.contains(x));
+        }
+    }
+
+    pub fn open_door_path(&mut self, from: usize, to: usize) -> Result<(), String> {
+        let ship_type = &SHIPS[self.ship_type];
+        if from >= ship_type.rooms.len() || to >= ship_type.rooms.len() {
+            return Err(format!(
+                "Can't open a path from room {from} to {to}, no such room"
+            ));
+        }
+        let Some(path) = ship_type.door_path(from, to) else {
+            return Err(format!("Rooms {from} and {to} aren't connected"));
+        };
+        for door in path {
+            self.doors[door].open = true;
+        }
+        Ok(())
+    }
+
+    pub fn close_exterior_doors(&mut self) {
+        for (door, state) in SHIPS[self.ship_type].doors.iter().zip(&mut self.doors) {
+            if matches!(door, Door::Exterior(_, _)) {
+                state.open = false;
+            }
+        }
+    }
+
+    pub fn set_crew_goal(&mut self, crew_index: usize, room_index: usize) -> Result<(), String> {
         let Some(room) = SHIPS[self.ship_type].rooms.get(room_index) else {
-            eprintln!("Can't set crew goal, room {room_index} doesn't exist");
-            return;
+            return Err(format!("Room {room_index} doesn't exist"));
         };
         let is_unoccupied = |cell: Cell| {
             // cell is unoccupied if all crew are not in it
@@ -371,12 +1281,10 @@ impl ShipState {
                 .all(|crew| crew.nav_status.occupied_cell() != cell)
         };
         let Some(target_cell) = room.cells.iter().cloned().find(|&x| is_unoccupied(x)) else {
-            eprintln!("Can't set crew goal, room {room_index} is fully occupied.");
-            return;
+            return Err(format!("Room {room_index} is full"));
         };
         let Some(crew) = self.crew.get_mut(crew_index) else {
-            eprintln!("Can't set crew goal, crew {crew_index} doesn't exist.");
-            return;
+            return Err(format!("Crew {crew_index} doesn't exist"));
         };
         let crew = &mut crew.nav_status;
         let occupied_room = SHIPS[self.ship_type]
@@ -385,27 +1293,33 @@ impl ShipState {
             .position(|x| x.cells.iter().any(|x| *x == crew.occupied_cell()))
             .unwrap();
         if room_index == occupied_room {
-            eprintln!("Can't set crew goal, crew is already in room {room_index}.");
-            return;
+            // Already there, nothing to do
+            return Ok(());
         }
 
-        match Self::path_crew_to(&self.path_graph, &self.nav_mesh, crew, target_cell) {
-            Ok(()) => {}
-            Err(()) => {
-                eprintln!("Can't set crew {crew_index} goal, room {room_index} is unreachable.");
-            }
-        }
+        Self::path_crew_to(&mut self.pathfinder, &self.nav_mesh, crew, target_cell)
+            .map_err(|()| format!("Room {room_index} is unreachable"))
+    }
+
+    /// Where everyone aboard is partway across the nav mesh, crew first, then boarders.
+    fn traffic(&self) -> Vec<Option<NavLocation>> {
+        self.crew
+            .iter()
+            .chain(self.intruders.iter().map(|x| &x.crew))
+            .map(|x| x.nav_status.nav_location())
+            .collect()
     }
 
     #[must_use]
     fn path_crew_to(
-        path_graph: &PathGraph,
+        pathfinder: &mut Pathfinder,
         nav_mesh: &NavMesh,
         crew: &mut CrewNavStatus,
         target_cell: Cell,
     ) -> Result<(), ()> {
-        let pathing = path_graph.pathing_to(target_cell);
-        let Some(path) = nav_mesh.find_path(&pathing, crew.current_location()) else {
+        let location = crew.current_location();
+        let pathing = pathfinder.pathing_to(target_cell, &location.cells());
+        let Some(path) = nav_mesh.find_path(pathing, location) else {
             // Path unreachable by crew
             return Err(());
         };
@@ -431,6 +1345,30 @@ impl ShipState {
         weapons.autofire = autofire;
     }
 
+    pub fn set_missile_floor(&mut self, floor: usize) {
+        let Some(weapons) = &mut self.systems.weapons else {
+            eprintln!("Can't set missile floor, weapons system not installed.");
+            return;
+        };
+        weapons.missile_floor = floor;
+    }
+
+    pub fn set_depower_order(&mut self, order: DepowerOrder) {
+        let Some(weapons) = &mut self.systems.weapons else {
+            eprintln!("Can't set depower order, weapons system not installed.");
+            return;
+        };
+        weapons.depower_order = order;
+    }
+
+    /// Missiles set aside for the next shot of each powered missile weapon.
+    pub fn reserved_missiles(&self) -> usize {
+        self.systems
+            .weapons
+            .as_ref()
+            .map_or(0, |weapons| weapons.reserved_missiles())
+    }
+
     pub fn save_crew_stations(&mut self) {
         for crew in &mut self.crew {
             crew.station = Some(crew.nav_status.occupied_cell());
@@ -441,7 +1379,7 @@ impl ShipState {
         for (i, crew) in self.crew.iter_mut().enumerate() {
             if let Some(target_cell) = crew.station {
                 match Self::path_crew_to(
-                    &self.path_graph,
+                    &mut self.pathfinder,
                     &self.nav_mesh,
                     &mut crew.nav_status,
                     target_cell,
@@ -470,7 +1408,7 @@ impl ShipState {
                         true,
                     );
                     Self::path_crew_to(
-                        &self.path_graph,
+                        &mut self.pathfinder,
                         &self.nav_mesh,
                         &mut self.crew[i].nav_status,
                         new_cell,
@@ -486,7 +1424,7 @@ impl ShipState {
     /// obstructions. Finally, caller must specify whether to only consider cells reachable from the
     /// given room. For moving out of the way of other crew, this should be true. For teleporting
     /// onto a ship, the can be false. This may strand crew in unconnected "islands" of cells.
-    fn room_or_nearby(&self, room: usize, crew: usize, reachable_only: bool) -> Cell {
+    fn room_or_nearby(&mut self, room: usize, crew: usize, reachable_only: bool) -> Cell {
         // Breadth-first search for a room with space, beginning with the specified room
         let mut frontier = VecDeque::new();
         frontier.push_back(room);
@@ -496,11 +1434,9 @@ impl ShipState {
         while let Some(current) = frontier.pop_front() {
             for &cell in SHIPS[self.ship_type].rooms[current].cells {
                 if reachable_only {
-                    let pathing = self.path_graph.pathing_to(cell);
-                    let reachable = self
-                        .nav_mesh
-                        .find_path(&pathing, self.crew[crew].nav_status.current_location())
-                        .is_some();
+                    let location = self.crew[crew].nav_status.current_location();
+                    let pathing = self.pathfinder.pathing_to(cell, &location.cells());
+                    let reachable = self.nav_mesh.find_path(pathing, location).is_some();
                     if !reachable {
                         continue;
                     }
@@ -527,3 +1463,618 @@ impl ShipState {
         panic!("Ship is overstuffed!");
     }
 }
+
+/// A free cell in the first room on `priority` whose system is damaged and that nobody's in or
+/// headed for yet, going by the cells in `reserved`.
+fn repair_destination(
+    ship_type: usize,
+    systems: &ShipSystems,
+    priority: &[SystemId],
+    reserved: &[Cell],
+) -> Option<Cell> {
+    let ship = &SHIPS[ship_type];
+    priority
+        .iter()
+        .filter(|&&x| systems.system(x).is_some_and(|x| x.damage() > 0))
+        .filter_map(|&x| ship.room_systems.iter().position(|&y| y == Some(x)))
+        .map(|room| ship.rooms[room].cells)
+        .filter(|cells| !cells.iter().any(|x| reserved.contains(x)))
+        .find_map(|cells| cells.first().copied())
+}
+
+/// Everyone in `traffic` but whoever's at `index`.
+fn traffic_except(traffic: &[Option<NavLocation>], index: usize) -> Vec<NavLocation> {
+    traffic
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != index)
+        .filter_map(|(_, x)| *x)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::starting_ship;
+    use common::rules::MatchRules;
+
+    /// The ship every player starts a default match with.
+    fn default_ship() -> ShipState {
+        starting_ship(&MatchRules::default())
+    }
+
+    #[test]
+    fn venting_drains_the_airlock_cell_first() {
+        let mut ship = ShipState::new();
+        // Cell 0 has an airlock and shares its room with cell 1
+        let airlock = SHIPS[ship.ship_type]
+            .doors
+            .iter()
+            .position(|x| matches!(x, Door::Exterior(Cell(0), _)))
+            .unwrap();
+        ship.doors[airlock].open = true;
+        for _ in 0..64 {
+            ship.update_oxygen(&BalanceConfig::default());
+        }
+        assert!(ship.oxygen[0] < ship.oxygen[1]);
+        // The rest of the room is losing air too, but the next room over is behind a closed door
+        assert!(ship.oxygen[1] < ship.oxygen[6]);
+    }
+
+    #[test]
+    fn reroute_takes_only_what_it_needs() {
+        let mut ship = ShipState::new();
+        for _ in 0..5 {
+            ship.reactor.upgrade();
+        }
+        for system in [SystemId::Shields, SystemId::Oxygen] {
+            ship.install_system(system);
+        }
+        for _ in 0..3 {
+            ship.systems
+                .system_mut(SystemId::Shields)
+                .unwrap()
+                .upgrade();
+        }
+        ship.systems.system_mut(SystemId::Oxygen).unwrap().upgrade();
+        ship.request_power(SystemId::Oxygen);
+        ship.request_power(SystemId::Oxygen);
+        ship.request_power(SystemId::Shields);
+        let power = |ship: &ShipState, system| ship.systems.system(system).unwrap().current_power();
+
+        // One bar is free, so the second shield layer only needs one of oxygen's two
+        ship.reroute_power(SystemId::Oxygen, SystemId::Shields);
+        assert_eq!(power(&ship, SystemId::Shields), 4);
+        assert_eq!(power(&ship, SystemId::Oxygen), 1);
+
+        // Shields are full now, so rerouting again leaves everything alone
+        ship.reroute_power(SystemId::Oxygen, SystemId::Shields);
+        assert_eq!(power(&ship, SystemId::Shields), 4);
+        assert_eq!(power(&ship, SystemId::Oxygen), 1);
+        assert_eq!(ship.reactor.available, 0);
+    }
+
+    #[test]
+    fn queued_power_waits_for_the_reactor() {
+        let mut ship = ShipState::new();
+        for _ in 0..2 {
+            ship.reactor.upgrade();
+        }
+        for system in [SystemId::Shields, SystemId::Oxygen] {
+            ship.install_system(system);
+        }
+        ship.systems
+            .system_mut(SystemId::Shields)
+            .unwrap()
+            .upgrade();
+        ship.request_power(SystemId::Oxygen);
+        ship.request_power(SystemId::Shields);
+        let power = |ship: &ShipState, system| ship.systems.system(system).unwrap().current_power();
+        assert_eq!(power(&ship, SystemId::Shields), 0);
+
+        ship.queue_power(SystemId::Shields);
+        ship.apply_power_queue();
+        assert_eq!(ship.power_queue, [SystemId::Shields]);
+
+        // Shields need both bars, so they get them only once oxygen lets go
+        ship.remove_power(SystemId::Oxygen);
+        ship.apply_power_queue();
+        assert_eq!(power(&ship, SystemId::Shields), 2);
+        assert!(ship.power_queue.is_empty());
+    }
+
+    #[test]
+    fn zoltan_shield_comes_up_with_the_augment() {
+        let mut ship = ShipState::new();
+        ship.install_system(SystemId::Shields);
+        assert_eq!(ship.systems.shields.as_ref().unwrap().super_shield, 0);
+        ship.install_augment(AugmentId::ZoltanShield);
+        assert_eq!(ship.systems.shields.as_ref().unwrap().super_shield, 5);
+    }
+
+    #[test]
+    fn hull_repairs_wait_out_the_cooldown() {
+        let (amount, cooldown) = (5, 30.0);
+        let mut ship = ShipState::new();
+        ship.damage = 12;
+        assert!(ship.repair_hull(amount, cooldown).is_err());
+        ship.install_augment(AugmentId::HullRepairKit);
+        assert_eq!(ship.repair_hull(amount, cooldown), Ok(amount));
+        assert_eq!(ship.damage, 12 - amount);
+        assert!(ship.repair_hull(amount, cooldown).is_err());
+        for _ in 0..(cooldown * 64.0) as usize {
+            ship.update_hull_repair();
+        }
+        assert_eq!(ship.repair_hull(amount, cooldown), Ok(amount));
+        assert_eq!(ship.hull_repairs_left(), 1);
+    }
+
+    #[test]
+    fn pre_igniter_starts_weapons_charged() {
+        let rules = MatchRules {
+            augments: vec![AugmentId::WeaponPreIgniter],
+            ..default()
+        };
+        let ship = starting_ship(&rules);
+        let weapons = ship.systems.weapons.as_ref().unwrap().weapons();
+        // Whatever the weapons system has room to power comes in hot
+        assert!(weapons[0].is_powered());
+        for weapon in weapons {
+            assert_eq!(weapon.charge(), weapon.weapon().common().charge_time);
+        }
+    }
+
+    #[test]
+    fn depower_order_picks_which_weapon_a_hit_takes_offline() {
+        for (order, kept) in [(DepowerOrder::LastSlot, 0), (DepowerOrder::LeastCharged, 1)] {
+            let mut ship = default_ship();
+            let weapons = ship.systems.weapons.as_mut().unwrap();
+            for index in 0..weapons.weapons().len() {
+                if weapons.weapons()[index].is_powered() {
+                    weapons.depower_weapon(index, &mut ship.reactor);
+                }
+            }
+            weapons.power_weapon(0, 0, &mut ship.reactor);
+            weapons.power_weapon(1, 0, &mut ship.reactor);
+            weapons.weapons_mut().nth(1).unwrap().fill_charge();
+            weapons.depower_order = order;
+            // Leave the system one short of what the two weapons need
+            let excess = weapons.system_status().max_power() - weapons.current_power();
+            weapons.damage_system(excess + 1, &mut ship.reactor);
+            let powered = weapons.weapons().iter().map(|x| x.is_powered());
+            let powered = powered.take(2).collect::<Vec<_>>();
+            assert_eq!(powered, [kept == 0, kept == 1]);
+        }
+    }
+
+    #[test]
+    fn clone_bay_needs_power() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        ship.crew[0].health = 0.0;
+        let dead = ship.remove_dead_crew();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(ship.cloning.len(), 1);
+
+        // Nothing happens until the bay has power
+        let ticks = (64.0 * balance.clone_time) as usize + 1;
+        for _ in 0..ticks {
+            assert!(ship.update_cloning(&balance).is_empty());
+        }
+        ship.request_power(SystemId::CloneBay);
+        let cloned = (0..ticks)
+            .flat_map(|_| ship.update_cloning(&balance))
+            .collect::<Vec<_>>();
+        assert_eq!(cloned, vec![dead[0].name.clone()]);
+        assert!(ship.cloning.is_empty());
+        let clone = ship.crew.last().unwrap();
+        assert_eq!(
+            clone.health,
+            RACES[clone.race].max_health * balance.clone_health
+        );
+        let room = SHIPS[ship.ship_type].cell_room(clone.nav_status.current_cell());
+        assert_eq!(
+            SHIPS[ship.ship_type].room_systems[room],
+            Some(SystemId::CloneBay)
+        );
+    }
+
+    #[test]
+    fn clones_wait_for_a_breathable_cell() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        ship.request_power(SystemId::CloneBay);
+        ship.crew[0].health = 0.0;
+        ship.remove_dead_crew();
+        let layout = &SHIPS[ship.ship_type];
+        let room = layout
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::CloneBay))
+            .unwrap();
+        for &Cell(cell) in layout.rooms[room].cells {
+            ship.oxygen[cell] = 0.0;
+        }
+        let ticks = (64.0 * balance.clone_time) as usize + 1;
+        for _ in 0..ticks {
+            assert!(ship.update_cloning(&balance).is_empty());
+        }
+        assert_eq!(ship.cloning.len(), 1);
+
+        let &Cell(breathable) = layout.rooms[room].cells.last().unwrap();
+        ship.oxygen[breathable] = 1.0;
+        assert_eq!(ship.update_cloning(&balance).len(), 1);
+        let clone = ship.crew.last().unwrap();
+        assert_eq!(clone.nav_status.current_cell(), Cell(breathable));
+    }
+
+    #[test]
+    fn boarders_sabotage_until_confronted() {
+        let balance = BalanceConfig::default();
+        let mut attacker = default_ship();
+        let mut target = default_ship();
+        assert!(attacker.board_shuttle(&[0, 1, 2], 2).is_err());
+        let boarders = attacker.board_shuttle(&[2, 0], 2).unwrap();
+        assert_eq!(boarders.len(), 2);
+        assert_eq!(attacker.crew.len(), 1);
+
+        // Land in an empty system room and get to work on it
+        let room = SHIPS[target.ship_type]
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::Shields))
+            .unwrap();
+        target.crew.retain(|x| !x.is_in_room(&SHIPS[0].rooms[room]));
+        target.take_boarders(boarders, Entity::PLACEHOLDER, room);
+        let damage = |ship: &ShipState| ship.systems.system(SystemId::Shields).unwrap().damage();
+        // Two boarders work twice as fast
+        for _ in 0..=(32.0 * balance.sabotage_time) as usize {
+            target.update_intruders(&balance);
+            target.update_repair_status();
+        }
+        assert_eq!(damage(&target), 1);
+
+        // A defender walks in and the fight is on instead
+        let mut defender = target.crew[0].clone();
+        defender.name = "Defender".into();
+        defender.nav_status = CrewNavStatus::At(SHIPS[0].rooms[room].cells[0]);
+        target.crew.push(defender);
+        let mut dead = Vec::new();
+        for _ in 0..64 * 30 {
+            let (defenders, intruders) = target.update_intruders(&balance);
+            dead.extend(defenders.into_iter().map(|(_, killer)| killer));
+            if !intruders.is_empty() || target.intruders.is_empty() {
+                break;
+            }
+        }
+        // Two on one doesn't go well for the defender
+        assert_eq!(dead, vec![Some(Entity::PLACEHOLDER)]);
+        assert_eq!(target.intruders.len(), 2);
+    }
+
+    #[test]
+    fn boarders_break_doors_that_fix_themselves() {
+        let balance = BalanceConfig::default();
+        let mut target = default_ship();
+        target.crew.clear();
+        for door in &mut target.doors {
+            door.open = false;
+        }
+        // Nothing to sabotage where they land, so they try for the next room over
+        let room = SHIPS[target.ship_type]
+            .room_systems
+            .iter()
+            .position(Option::is_none)
+            .unwrap();
+        let boarder = default_ship().crew.remove(0);
+        target.take_boarders(vec![boarder], Entity::PLACEHOLDER, room);
+        for _ in 0..(64.0 * balance.door_break_time) as usize - 1 {
+            target.update_intruders(&balance);
+        }
+        assert!(target.doors.iter().all(|x| !x.broken()));
+        assert!(target.doors.iter().any(|x| x.break_progress > 0.9));
+        target.update_intruders(&balance);
+        target.update_intruders(&balance);
+        let broken = target.doors.iter().position(|x| x.broken()).unwrap();
+        assert!(target.doors[broken].is_open());
+
+        for _ in 0..=(64.0 * balance.door_broken_time) as usize {
+            target.update_doors(&balance);
+        }
+        assert!(!target.doors[broken].is_open());
+    }
+
+    #[test]
+    fn manning_the_doors_fixes_broken_doors_sooner() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        ship.crew.truncate(1);
+        let layout = &SHIPS[ship.ship_type];
+        let doors_room = layout
+            .room_subsystems
+            .iter()
+            .position(|&x| x == Some(SubsystemId::Doors))
+            .unwrap();
+        let elsewhere = (doors_room + 1) % layout.rooms.len();
+        ship.crew[0].nav_status = CrewNavStatus::At(layout.rooms[elsewhere].cells[0]);
+        ship.doors[0].broken_timer = balance.door_broken_time;
+        ship.update_doors(&balance);
+        let unmanned = ship.doors[0].broken_timer;
+
+        ship.crew[0].nav_status = CrewNavStatus::At(layout.rooms[doors_room].cells[0]);
+        assert!(ship.is_subsystem_manned(SubsystemId::Doors));
+        ship.doors[0].broken_timer = balance.door_broken_time;
+        ship.update_doors(&balance);
+        assert!(ship.doors[0].broken_timer < unmanned);
+    }
+
+    #[test]
+    fn crew_return_to_stations_after_repairs() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        ship.crew.truncate(1);
+        let rooms = SHIPS[ship.ship_type].rooms;
+        let breach = rooms[0].cells[0];
+        let station = rooms[1].cells[0];
+        ship.crew[0].nav_status = CrewNavStatus::At(breach);
+        ship.crew[0].station = Some(station);
+        ship.cells[breach.0].breached = true;
+        ship.auto_return = true;
+        for _ in 0..(64.0 * balance.cell_repair_time) as usize + 2 {
+            ship.update_crew(&balance);
+        }
+        assert!(!ship.cells[breach.0].breached);
+        for _ in 0..64 * 10 {
+            ship.update_crew(&balance);
+        }
+        assert!(matches!(ship.crew[0].nav_status, CrewNavStatus::At(x) if x == station));
+    }
+
+    #[test]
+    fn crew_head_for_priority_repairs() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        ship.crew.truncate(1);
+        let layout = &SHIPS[ship.ship_type];
+        let shields_room = layout
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::Shields))
+            .unwrap();
+        let breach = layout.rooms[(shields_room + 1) % layout.rooms.len()].cells[0];
+        ship.crew[0].nav_status = CrewNavStatus::At(breach);
+        ship.cells[breach.0].breached = true;
+        let shields = ship.systems.shields.as_mut().unwrap();
+        shields.damage_system(1, &mut ship.reactor);
+        ship.set_repair_priority(vec![SystemId::Shields, SystemId::Shields]);
+        assert_eq!(ship.repair_priority, [SystemId::Shields]);
+
+        for _ in 0..(64.0 * balance.cell_repair_time) as usize + 2 {
+            ship.update_crew(&balance);
+        }
+        assert!(!ship.cells[breach.0].breached);
+        for _ in 0..64 * 10 + (64.0 * balance.system_repair_time) as usize {
+            ship.update_crew(&balance);
+        }
+        assert_eq!(ship.systems.shields.as_ref().unwrap().damage(), 0);
+    }
+
+    #[test]
+    fn doors_seal_and_vent_on_their_own() {
+        let mut ship = ShipState::new();
+        let layout = &SHIPS[ship.ship_type];
+        // Cell 0 has an airlock
+        let airlock = layout
+            .doors
+            .iter()
+            .position(|x| matches!(x, Door::Exterior(Cell(0), _)))
+            .unwrap();
+        let room = layout.cell_room(Cell(0));
+        let room_doors = (0..layout.doors.len())
+            .filter(|&x| layout.door_rooms(x).contains(&room))
+            .collect::<Vec<_>>();
+        for door in &mut ship.doors {
+            door.open = true;
+        }
+
+        // Left alone, doors stay as they are
+        ship.update_door_automation();
+        ship.cells[0].on_fire = true;
+        ship.update_door_automation();
+        assert!(room_doors.iter().all(|&x| ship.doors[x].open));
+
+        ship.cells[0].on_fire = false;
+        ship.update_door_automation();
+        ship.door_automation = DoorAutomation::SealAndVent;
+        ship.cells[0].on_fire = true;
+        ship.update_door_automation();
+        for &door in &room_doors {
+            assert_eq!(ship.doors[door].open, door == airlock);
+        }
+
+        // Breaches get sealed in, airlocks and all
+        ship.cells[0].breached = true;
+        ship.update_door_automation();
+        assert!(room_doors.iter().all(|&x| !ship.doors[x].open));
+    }
+
+    #[test]
+    fn air_runs_out_faster_when_crowded_or_destroyed() {
+        let balance = BalanceConfig::default();
+        // Ships start with nothing powered
+        let unpowered = || default_ship();
+        let suffocation_in = |mut ship: ShipState| {
+            ship.update_oxygen(&balance);
+            ship.suffocation_in.unwrap()
+        };
+        let mut empty = unpowered();
+        empty.crew.clear();
+        let mut destroyed = unpowered();
+        destroyed.crew.clear();
+        let oxygen = destroyed.systems.oxygen.as_mut().unwrap();
+        oxygen.damage_system(oxygen.upgrade_level(), &mut destroyed.reactor);
+        let empty = suffocation_in(empty);
+        assert!(suffocation_in(unpowered()) < empty);
+        assert!(suffocation_in(destroyed) < empty);
+
+        let mut powered = unpowered();
+        powered
+            .systems
+            .oxygen
+            .as_mut()
+            .unwrap()
+            .add_power(&mut powered.reactor, PowerContext { missiles: 0 });
+        powered.update_oxygen(&balance);
+        assert_eq!(powered.suffocation_in, None);
+    }
+
+    #[test]
+    fn crew_repair_subsystems() {
+        let balance = BalanceConfig::default();
+        let mut ship = default_ship();
+        let room = SHIPS[ship.ship_type]
+            .room_subsystems
+            .iter()
+            .position(|&x| x == Some(SubsystemId::Piloting))
+            .unwrap();
+        let piloting = ship.subsystems.piloting.as_mut().unwrap();
+        piloting.damage_system(1, &mut ship.reactor);
+        assert!(!ship.subsystems.is_working(SubsystemId::Piloting));
+        // Damage doesn't cost the reactor anything
+        assert_eq!(ship.reactor.available, ship.reactor.upgrade_level);
+
+        ship.crew.truncate(1);
+        ship.crew[0].nav_status = CrewNavStatus::At(SHIPS[ship.ship_type].rooms[room].cells[0]);
+        for _ in 0..=(64.0 * balance.system_repair_time) as usize {
+            ship.update_crew(&balance);
+        }
+        assert!(ship.subsystems.is_working(SubsystemId::Piloting));
+    }
+
+    #[test]
+    fn venting_spares_occupied_rooms() {
+        let mut ship = default_ship();
+        ship.vent_unoccupied();
+        let occupied = ship
+            .crew
+            .iter()
+            .map(|x| SHIPS[ship.ship_type].cell_room(x.nav_status.occupied_cell()))
+            .collect::<HashSet<_>>();
+        for (index, door) in ship.doors.iter().enumerate() {
+            let rooms = SHIPS[ship.ship_type].door_rooms(index);
+            assert_eq!(door.open, rooms.iter().all(|x| !occupied.contains(x)));
+        }
+        assert!(ship.doors.iter().any(|x| x.open));
+
+        ship.close_exterior_doors();
+        for (door, state) in SHIPS[ship.ship_type].doors.iter().zip(&ship.doors) {
+            if matches!(door, Door::Exterior(_, _)) {
+                assert!(!state.open);
+            }
+        }
+    }
+
+    #[test]
+    fn weapon_slots_stop_at_hull_mounts() {
+        let mut ship = ShipState::new();
+        ship.install_system(SystemId::Weapons);
+        let mounts = SHIPS[ship.ship_type].weapon_mounts.len();
+        let weapons = ship.systems.weapons.as_mut().unwrap();
+        let mut slots = vec![weapons.slots()];
+        for _ in 0..mounts + 2 {
+            weapons.upgrade();
+            slots.push(weapons.slots());
+        }
+        assert!(slots.windows(2).all(|x| x[0] <= x[1]));
+        assert_eq!(slots.last(), Some(&mounts));
+    }
+
+    #[test]
+    fn cargo_fills_once_slots_run_out() {
+        use common::weapon::{Weapon, BURST_LASER_MK_I, HEAVY_LASER};
+
+        let mut ship = ShipState::new();
+        ship.install_system(SystemId::Weapons);
+        let weapons = ship.systems.weapons.as_mut().unwrap();
+        while weapons.slots() < 1 {
+            weapons.upgrade();
+        }
+        weapons.install_weapon(0, Weapon::new(HEAVY_LASER));
+        weapons.install_weapon(1, Weapon::new(BURST_LASER_MK_I));
+        let mounted = |ship: &ShipState| {
+            let weapons = ship.systems.weapons.as_ref().unwrap();
+            let mounted = weapons
+                .weapons()
+                .iter()
+                .map(|x| x.weapon())
+                .collect::<Vec<_>>();
+            let cargo = weapons.cargo().iter().map(|x| x.id()).collect::<Vec<_>>();
+            (mounted, cargo)
+        };
+        assert_eq!(mounted(&ship), (vec![HEAVY_LASER], vec![BURST_LASER_MK_I]));
+
+        // Installing over an occupied slot swaps the two
+        ship.install_from_cargo(0, 0);
+        assert_eq!(mounted(&ship), (vec![BURST_LASER_MK_I], vec![HEAVY_LASER]));
+
+        // No second slot to mount into
+        ship.install_from_cargo(0, 1);
+        assert_eq!(mounted(&ship), (vec![BURST_LASER_MK_I], vec![HEAVY_LASER]));
+
+        ship.store_weapon(0);
+        assert_eq!(
+            mounted(&ship),
+            (vec![], vec![HEAVY_LASER, BURST_LASER_MK_I])
+        );
+        ship.install_from_cargo(1, 0);
+        assert_eq!(mounted(&ship), (vec![BURST_LASER_MK_I], vec![HEAVY_LASER]));
+    }
+
+    #[test]
+    fn missile_weapons_reserve_their_ammo() {
+        use common::weapon::{Weapon, HERMES_MISSILES};
+
+        let mut ship = ShipState::new();
+        ship.reactor = Reactor::new(6);
+        ship.install_system(SystemId::Weapons);
+        let weapons = ship.systems.weapons.as_mut().unwrap();
+        while weapons.system_status().max_power() < 6 {
+            weapons.upgrade();
+        }
+        weapons.install_weapon(0, Weapon::new(HERMES_MISSILES));
+        weapons.install_weapon(1, Weapon::new(HERMES_MISSILES));
+        let powered = |ship: &ShipState| {
+            let weapons = ship.systems.weapons.as_ref().unwrap().weapons();
+            weapons.iter().map(|x| x.is_powered()).collect::<Vec<_>>()
+        };
+
+        // Only one missile to go round, so the second launcher can't have it too
+        ship.missiles = 1;
+        ship.power_weapon(0);
+        ship.power_weapon(1);
+        assert_eq!(powered(&ship), [true, false]);
+        assert_eq!(ship.reserved_missiles(), 1);
+
+        // Autofire keeps launching until the supply is down to the floor
+        ship.missiles = 4;
+        ship.power_weapon(1);
+        ship.set_autofire(true);
+        ship.set_missile_floor(1);
+        let target = RoomTarget {
+            ship: Entity::PLACEHOLDER,
+            room: 0,
+        };
+        ship.set_projectile_weapon_target(0, Some(target));
+        ship.set_projectile_weapon_target(1, Some(target));
+        let mut fired = 0;
+        for _ in 0..3 {
+            for weapon in ship.systems.weapons.as_mut().unwrap().weapons_mut() {
+                weapon.fill_charge();
+            }
+            fired += ship.update_weapons().unwrap().flatten().count();
+        }
+        assert_eq!(fired, 3);
+        assert_eq!(ship.missiles, 1);
+        assert_eq!(ship.reserved_missiles(), 1);
+    }
+}
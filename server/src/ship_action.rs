@@ -0,0 +1,491 @@
+//! Orders to a single ship as plain data, and what came of them. The network handlers in
+//! [`events`](crate::events) only check who's allowed to give an order and who got to the power
+//! first, then hand it to [`ShipState::apply`] as a [`ShipAction`]. Everything the order actually
+//! does lives here, so the simulation can be driven by feeding a ship a list of actions, no app or
+//! clients needed, and what changed comes back as [`ShipEvent`]s ready to be recorded. Shots
+//! landing go through here too, as [`ShipAction::TakeHit`], and so does sudden death burning down
+//! hulls, as [`ShipAction::BurnHull`].
+
+use bevy::prelude::*;
+use common::{
+    events::{DepowerOrder, DoorAutomation, PowerDir},
+    nav::Cell,
+    ship::{SystemId, SHIPS},
+    weapon::{DamageSpec, WeaponId},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ship::ShipState, ship_system::ShipSystem};
+
+/// Something a ship has been told to do. Orders that reach across to other ships (targeting,
+/// boarding) need more than one ship to check, and aren't actions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ShipAction {
+    AdjustPower {
+        system: SystemId,
+        dir: PowerDir,
+    },
+    QueuePower {
+        system: SystemId,
+        queued: bool,
+    },
+    ReroutePower {
+        from: SystemId,
+        to: SystemId,
+    },
+    WeaponPower {
+        weapon_index: usize,
+        dir: PowerDir,
+    },
+    MoveWeapon {
+        weapon_index: usize,
+        target_index: usize,
+    },
+    InstallWeapon {
+        cargo_index: usize,
+        slot: usize,
+    },
+    StoreWeapon {
+        weapon_index: usize,
+    },
+    SetCrewGoal {
+        crew: usize,
+        room: usize,
+    },
+    SetAutofire(bool),
+    SetMissileFloor(usize),
+    SetDepowerOrder(DepowerOrder),
+    RepairHull {
+        amount: usize,
+        cooldown: f32,
+    },
+    SetRepairPriority(Vec<SystemId>),
+    SetDoorAutomation(DoorAutomation),
+    TakeHit(Hit),
+    /// Sudden death wearing the hull down by this much, see
+    /// [`tick_match_clock`](crate::match_clock::tick_match_clock).
+    BurnHull(usize),
+}
+
+/// A shot landing on a ship. Anything left to chance has already been rolled, so a hit plays out
+/// the same way every time it's applied.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub cell: Cell,
+    /// Whether the hull and whatever's installed in the cell's room take damage too. Beams only
+    /// damage each room once, however many of its cells they cross.
+    pub hits_room: bool,
+    pub damage: DamageSpec,
+    /// Rolls in `[0, 1)` against the shot's fire and breach chances.
+    pub fire_roll: f32,
+    pub breach_roll: f32,
+}
+
+/// Something that changed on a ship because of a [`ShipAction`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ShipEvent {
+    SystemPower {
+        system: SystemId,
+        power: usize,
+    },
+    PowerQueued {
+        system: SystemId,
+        queued: bool,
+    },
+    WeaponPower {
+        weapon_index: usize,
+        powered: bool,
+    },
+    /// Weapons were mounted, stowed or shuffled around. Lists what's now in each slot and in
+    /// cargo.
+    Loadout {
+        weapons: Vec<WeaponId>,
+        cargo: Vec<WeaponId>,
+    },
+    CrewGoal {
+        crew: usize,
+        room: usize,
+    },
+    Autofire(bool),
+    MissileFloor(usize),
+    DepowerOrder(DepowerOrder),
+    HullRepaired {
+        amount: usize,
+    },
+    RepairPriority(Vec<SystemId>),
+    DoorAutomation(DoorAutomation),
+    /// The hull took `damage` from a hit on `room`, after armor. Can be zero.
+    HullDamaged {
+        room: usize,
+        damage: usize,
+    },
+    SystemDamaged {
+        system: SystemId,
+        damage: usize,
+        /// Whether this hit took the system from working to fully destroyed.
+        destroyed: bool,
+    },
+    FireStarted {
+        room: usize,
+    },
+    CrewDied {
+        name: String,
+    },
+    /// Sudden death took `damage` off the hull. Less than it was told to once the hull runs out.
+    HullBurned {
+        damage: usize,
+    },
+}
+
+/// A [`ShipEvent`] on `ship`, on its way into the match journal.
+#[derive(Event, Debug, Clone)]
+pub struct ShipChanged {
+    pub ship: Entity,
+    pub event: ShipEvent,
+}
+
+impl ShipChanged {
+    /// Everything `events` says happened to `ship`.
+    pub fn all(ship: Entity, events: Vec<ShipEvent>) -> impl Iterator<Item = Self> {
+        events.into_iter().map(move |event| Self { ship, event })
+    }
+}
+
+impl ShipState {
+    /// Carry out `action`, returning what changed, or why nothing did.
+    pub fn apply(&mut self, action: ShipAction) -> Result<Vec<ShipEvent>, String> {
+        match action {
+            ShipAction::AdjustPower { system, dir } => {
+                let before = system_power(self, system);
+                match dir {
+                    PowerDir::Request => self.request_power(system),
+                    PowerDir::Remove => self.remove_power(system),
+                }
+                match system_power(self, system) {
+                    Some(power) if Some(power) != before => {
+                        Ok(vec![ShipEvent::SystemPower { system, power }])
+                    }
+                    _ => Err(system_power_rejection(self, system, dir)),
+                }
+            }
+            ShipAction::QueuePower {
+                system,
+                queued: false,
+            } => {
+                self.cancel_queued_power(system);
+                Ok(vec![ShipEvent::PowerQueued {
+                    system,
+                    queued: false,
+                }])
+            }
+            ShipAction::QueuePower {
+                system,
+                queued: true,
+            } => {
+                self.queue_power(system);
+                if !self.power_queue.contains(&system) {
+                    return Err(system_power_rejection(self, system, PowerDir::Request));
+                }
+                Ok(vec![ShipEvent::PowerQueued {
+                    system,
+                    queued: true,
+                }])
+            }
+            ShipAction::ReroutePower { from, to } => {
+                let before = system_power(self, to);
+                self.reroute_power(from, to);
+                match (system_power(self, from), system_power(self, to)) {
+                    (Some(from_power), Some(to_power)) if Some(to_power) != before => Ok(vec![
+                        ShipEvent::SystemPower {
+                            system: from,
+                            power: from_power,
+                        },
+                        ShipEvent::SystemPower {
+                            system: to,
+                            power: to_power,
+                        },
+                    ]),
+                    _ => Err(format!("Couldn't reroute power from {from} to {to}")),
+                }
+            }
+            ShipAction::WeaponPower { weapon_index, dir } => {
+                let before = weapon_powered(self, weapon_index);
+                match dir {
+                    PowerDir::Request => self.power_weapon(weapon_index),
+                    PowerDir::Remove => self.depower_weapon(weapon_index),
+                }
+                match weapon_powered(self, weapon_index) {
+                    Some(powered) if Some(powered) != before => Ok(vec![ShipEvent::WeaponPower {
+                        weapon_index,
+                        powered,
+                    }]),
+                    _ => Err(weapon_power_rejection(self, weapon_index, dir)),
+                }
+            }
+            ShipAction::MoveWeapon {
+                weapon_index,
+                target_index,
+            } => self.change_loadout(|ship| ship.move_weapon(weapon_index, target_index)),
+            ShipAction::InstallWeapon { cargo_index, slot } => {
+                self.change_loadout(|ship| ship.install_from_cargo(cargo_index, slot))
+            }
+            ShipAction::StoreWeapon { weapon_index } => {
+                self.change_loadout(|ship| ship.store_weapon(weapon_index))
+            }
+            ShipAction::SetCrewGoal { crew, room } => {
+                self.set_crew_goal(crew, room)?;
+                Ok(vec![ShipEvent::CrewGoal { crew, room }])
+            }
+            ShipAction::SetAutofire(autofire) => {
+                if self.systems.weapons.is_none() {
+                    return Err("Weapons system isn't installed".into());
+                }
+                self.set_autofire(autofire);
+                Ok(vec![ShipEvent::Autofire(autofire)])
+            }
+            ShipAction::SetMissileFloor(floor) => {
+                if self.systems.weapons.is_none() {
+                    return Err("Weapons system isn't installed".into());
+                }
+                self.set_missile_floor(floor);
+                Ok(vec![ShipEvent::MissileFloor(floor)])
+            }
+            ShipAction::SetDepowerOrder(order) => {
+                if self.systems.weapons.is_none() {
+                    return Err("Weapons system isn't installed".into());
+                }
+                self.set_depower_order(order);
+                Ok(vec![ShipEvent::DepowerOrder(order)])
+            }
+            ShipAction::RepairHull { amount, cooldown } => {
+                let amount = self.repair_hull(amount, cooldown)?;
+                Ok(vec![ShipEvent::HullRepaired { amount }])
+            }
+            ShipAction::SetRepairPriority(priority) => {
+                self.set_repair_priority(priority);
+                Ok(vec![ShipEvent::RepairPriority(
+                    self.repair_priority.clone(),
+                )])
+            }
+            ShipAction::SetDoorAutomation(automation) => {
+                self.door_automation = automation;
+                Ok(vec![ShipEvent::DoorAutomation(automation)])
+            }
+            ShipAction::TakeHit(hit) => Ok(self.take_hit(hit)),
+            ShipAction::BurnHull(damage) => {
+                let before = self.damage;
+                self.damage = (self.damage + damage).min(self.max_hull);
+                Ok(vec![ShipEvent::HullBurned {
+                    damage: self.damage - before,
+                }])
+            }
+        }
+    }
+
+    fn take_hit(&mut self, hit: Hit) -> Vec<ShipEvent> {
+        let Hit { cell, damage, .. } = hit;
+        let ship_type = &SHIPS[self.ship_type];
+        let room = ship_type.cell_room(cell);
+        let mut events = Vec::new();
+        if hit.hits_room {
+            let hull_damage = ship_type.armored_hull_damage(room, damage.hull);
+            self.damage = (self.damage + hull_damage).min(self.max_hull);
+            events.push(ShipEvent::HullDamaged {
+                room,
+                damage: hull_damage,
+            });
+        }
+        for crew in &mut self.crew {
+            if ship_type.cell_room(crew.nav_status.current_cell()) == room {
+                crew.health -= damage.crew;
+            }
+        }
+        if self.damage_cell(cell, &damage, hit.fire_roll, hit.breach_roll) {
+            events.push(ShipEvent::FireStarted { room });
+        }
+        for crew in self.remove_dead_crew() {
+            events.push(ShipEvent::CrewDied { name: crew.name });
+        }
+        if !hit.hits_room {
+            return events;
+        }
+        if let Some(system_id) = ship_type.room_systems[room] {
+            if let Some(system) = self.systems.system_mut(system_id) {
+                let was_destroyed = system.damage() == system.upgrade_level();
+                system.damage_system(damage.system, &mut self.reactor);
+                events.push(ShipEvent::SystemDamaged {
+                    system: system_id,
+                    damage: damage.system,
+                    destroyed: !was_destroyed && system.damage() == system.upgrade_level(),
+                });
+            }
+        }
+        if let Some(subsystem) =
+            ship_type.room_subsystems[room].and_then(|x| self.subsystems.subsystem_mut(x))
+        {
+            subsystem.damage_system(damage.system, &mut self.reactor);
+        }
+        events
+    }
+
+    /// Run `change` on the weapons, reporting the new loadout if it did anything.
+    fn change_loadout(
+        &mut self,
+        change: impl FnOnce(&mut ShipState),
+    ) -> Result<Vec<ShipEvent>, String> {
+        let before = loadout(self);
+        change(self);
+        let after = loadout(self);
+        match after {
+            None => Err("Weapons system isn't installed".into()),
+            _ if after == before => Err("Those weapons can't be rearranged like that".into()),
+            Some((weapons, cargo)) => Ok(vec![ShipEvent::Loadout { weapons, cargo }]),
+        }
+    }
+}
+
+fn loadout(ship: &ShipState) -> Option<(Vec<WeaponId>, Vec<WeaponId>)> {
+    let weapons = ship.systems.weapons.as_ref()?;
+    Some((
+        weapons.weapons().iter().map(|x| x.weapon()).collect(),
+        weapons.cargo().iter().map(|x| x.id()).collect(),
+    ))
+}
+
+fn system_power(ship: &ShipState, system: SystemId) -> Option<usize> {
+    ship.systems.system(system).map(|x| x.current_power())
+}
+
+fn weapon_powered(ship: &ShipState, index: usize) -> Option<bool> {
+    let weapons = ship.systems.weapons.as_ref()?;
+    weapons.weapons().get(index).map(|x| x.is_powered())
+}
+
+/// Why moving `system`'s power in `dir` didn't do anything.
+fn system_power_rejection(ship: &ShipState, system: SystemId, dir: PowerDir) -> String {
+    let Some(status) = ship.systems.system(system) else {
+        return format!("{system} isn't installed");
+    };
+    match dir {
+        PowerDir::Request if status.current_power() >= status.system_status().max_power() => {
+            format!("{system} can't take any more power")
+        }
+        PowerDir::Request => format!("Not enough reactor power for {system}"),
+        PowerDir::Remove => format!("{system} has no power to remove"),
+    }
+}
+
+/// Why powering or depowering the weapon in `index` didn't do anything.
+fn weapon_power_rejection(ship: &ShipState, index: usize, dir: PowerDir) -> String {
+    let Some(weapons) = &ship.systems.weapons else {
+        return "Weapons system isn't installed".into();
+    };
+    let Some(entry) = weapons.weapons().get(index) else {
+        return format!("No weapon in slot {}", index + 1);
+    };
+    let weapon = entry.weapon();
+    let name = weapon.common().name;
+    match dir {
+        PowerDir::Request if entry.is_powered() => format!("{name} is already powered"),
+        PowerDir::Request if weapon.uses_missile() && ship.missiles == 0 => {
+            format!("No missiles left for {name}")
+        }
+        PowerDir::Request if ship.missiles <= ship.reserved_missiles() && weapon.uses_missile() => {
+            format!("Every missile left is reserved for another weapon, none for {name}")
+        }
+        PowerDir::Request if ship.reactor.available < weapon.common().power => {
+            format!("Not enough reactor power for {name}")
+        }
+        PowerDir::Request => format!("Weapons system can't power {name}"),
+        PowerDir::Remove => format!("{name} isn't powered"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::starting_ship;
+    use common::rules::MatchRules;
+
+    #[test]
+    fn actions_play_out_without_an_app() {
+        let mut ship = starting_ship(&MatchRules::default());
+        let shields = system_power(&ship, SystemId::Shields).unwrap();
+        let actions = [
+            ShipAction::AdjustPower {
+                system: SystemId::Shields,
+                dir: PowerDir::Request,
+            },
+            ShipAction::SetAutofire(true),
+            ShipAction::StoreWeapon { weapon_index: 0 },
+        ];
+        let events = actions
+            .into_iter()
+            .map(|x| ship.apply(x))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(matches!(
+            events[0][..],
+            [ShipEvent::SystemPower { system: SystemId::Shields, power }] if power > shields
+        ));
+        assert_eq!(events[1], [ShipEvent::Autofire(true)]);
+        assert!(matches!(&events[2][..], [ShipEvent::Loadout { cargo, .. }] if cargo.len() == 1));
+
+        // Orders that can't do anything say why, and change nothing
+        let bogus = ShipAction::WeaponPower {
+            weapon_index: 99,
+            dir: PowerDir::Request,
+        };
+        assert_eq!(ship.apply(bogus), Err("No weapon in slot 100".into()));
+    }
+
+    #[test]
+    fn hits_report_the_damage_done() {
+        let mut ship = starting_ship(&MatchRules::default());
+        let room = SHIPS[ship.ship_type]
+            .room_systems
+            .iter()
+            .position(|&x| x == Some(SystemId::Shields))
+            .unwrap();
+        let cell = SHIPS[ship.ship_type].rooms[room].cells[0];
+        let hit = Hit {
+            cell,
+            hits_room: true,
+            damage: DamageSpec {
+                fire_chance: 0.5,
+                ..DamageSpec::standard(1)
+            },
+            fire_roll: 0.2,
+            breach_roll: 0.9,
+        };
+        let events = ship.apply(ShipAction::TakeHit(hit)).unwrap();
+        let damage = ship.damage;
+        assert!(damage > 0);
+        assert!(events.contains(&ShipEvent::HullDamaged { room, damage }));
+        assert!(events.contains(&ShipEvent::FireStarted { room }));
+        assert!(events.iter().any(|x| matches!(
+            x,
+            ShipEvent::SystemDamaged {
+                system: SystemId::Shields,
+                damage: 1,
+                ..
+            }
+        )));
+        assert!(ship.cells[cell.0].on_fire);
+        assert!(!ship.cells[cell.0].breached);
+
+        // The same hit again only sets fires where there aren't any
+        let events = ship.apply(ShipAction::TakeHit(hit)).unwrap();
+        assert!(!events.contains(&ShipEvent::FireStarted { room }));
+    }
+
+    #[test]
+    fn hull_burns_down_to_nothing() {
+        let mut ship = starting_ship(&MatchRules::default());
+        ship.damage = ship.max_hull - 1;
+        let burns = [ShipAction::BurnHull(1), ShipAction::BurnHull(1)].map(|x| ship.apply(x));
+        assert_eq!(burns[0], Ok(vec![ShipEvent::HullBurned { damage: 1 }]));
+        assert_eq!(burns[1], Ok(vec![ShipEvent::HullBurned { damage: 0 }]));
+        assert_eq!(ship.damage, ship.max_hull);
+    }
+}
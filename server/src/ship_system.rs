@@ -1,17 +1,21 @@
 use crate::{
-    engines::Engines, oxygen::Oxygen, reactor::Reactor, shields::Shields, weapons::Weapons,
+    clone_bay::CloneBay, engines::Engines, oxygen::Oxygen, reactor::Reactor, shields::Shields,
+    weapons::Weapons,
 };
 use common::{
     intel::{SystemDamageIntel, SystemIntel},
     ship::SystemId,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ShipSystems {
     pub shields: Option<Shields>,
     pub weapons: Option<Weapons>,
     pub engines: Option<Engines>,
     pub oxygen: Option<Oxygen>,
+    #[serde(default)]
+    pub clone_bay: Option<CloneBay>,
 }
 
 impl ShipSystems {
@@ -21,6 +25,7 @@ impl ShipSystems {
             SystemId::Weapons => self.weapons.as_ref().map(|x| x as &dyn ShipSystem),
             SystemId::Engines => self.engines.as_ref().map(|x| x as &dyn ShipSystem),
             SystemId::Oxygen => self.oxygen.as_ref().map(|x| x as &dyn ShipSystem),
+            SystemId::CloneBay => self.clone_bay.as_ref().map(|x| x as &dyn ShipSystem),
         }
     }
 
@@ -30,16 +35,18 @@ impl ShipSystems {
             SystemId::Weapons => self.weapons.as_mut().map(|x| x as &mut dyn ShipSystem),
             SystemId::Engines => self.engines.as_mut().map(|x| x as &mut dyn ShipSystem),
             SystemId::Oxygen => self.oxygen.as_mut().map(|x| x as &mut dyn ShipSystem),
+            SystemId::CloneBay => self.clone_bay.as_mut().map(|x| x as &mut dyn ShipSystem),
         }
     }
 
-    pub fn install(&mut self, system: SystemId) {
+    /// Install `system` on a ship of type `ship_type`.
+    pub fn install(&mut self, system: SystemId, ship_type: usize) {
         match system {
             SystemId::Shields => {
                 self.shields = Some(Default::default());
             }
             SystemId::Weapons => {
-                self.weapons = Some(Default::default());
+                self.weapons = Some(Weapons::new(ship_type));
             }
             SystemId::Engines => {
                 self.engines = Some(Default::default());
@@ -47,11 +54,14 @@ impl ShipSystems {
             SystemId::Oxygen => {
                 self.oxygen = Some(Default::default());
             }
+            SystemId::CloneBay => {
+                self.clone_bay = Some(Default::default());
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct SystemStatus {
     upgrade_level: usize,
     damage: usize,
@@ -90,6 +100,12 @@ pub trait ShipSystem {
     fn add_power(&mut self, reactor: &mut Reactor, context: PowerContext);
     fn remove_power(&mut self, reactor: &mut Reactor);
 
+    /// Reactor power the next [`add_power`](Self::add_power) would draw, or `None` if the system
+    /// can't take any more no matter how much is free.
+    fn next_power_step(&self) -> Option<usize> {
+        (self.current_power() < self.system_status().max_power()).then_some(1)
+    }
+
     fn intel(&self) -> SystemIntel {
         let status = self.system_status();
         SystemIntel {
@@ -146,7 +162,7 @@ pub trait ShipSystem {
         }
     }
 
-    fn _crew_damage(&mut self, amount: f32, reactor: &mut Reactor) {
+    fn crew_damage(&mut self, amount: f32, reactor: &mut Reactor) {
         let damage_progress = &mut self.system_status_mut().damage_progress;
         *damage_progress += amount;
         if *damage_progress >= 1.0 {
@@ -0,0 +1,231 @@
+//! Boarding shuttles, for getting crew onto an enemy ship without a teleporter. A shuttle flies
+//! through the same traversal machinery as a projectile, but it's slow, the target's weapons get a
+//! shot at it on the way in (see [`BalanceConfig::point_defense_chance`]), and instead of doing
+//! damage it drops its crew off as [`Intruder`](crate::ship::Intruder)s in the room it was aimed
+//! at. It's a one way trip.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_replicon::prelude::*;
+use common::{
+    balance::BalanceConfig,
+    bullets::{
+        Incidence, LaunchedFrom, Progress, RoomTarget, Shuttle, TraversalSpeed, SHIELD_PROGRESS,
+    },
+    events::{CommandRejected, LaunchShuttle, ShipCommand},
+    journal::MatchEvent,
+    lobby::Team,
+    ship::{Dead, SHIPS},
+    Crew,
+};
+use rand::Rng;
+
+use crate::{
+    bullets::{despawn_orphan, random_incidence, MatchRng},
+    events::{is_friendly, may_command, reject},
+    ship::ShipState,
+    ClientShips, MatchScoped,
+};
+
+/// The crew riding a shuttle. Server-only: clients just see how many there are on [`Shuttle`].
+#[derive(Component, Debug, Clone)]
+pub struct Passengers(pub Vec<Crew>);
+
+/// Marks a shuttle the target's weapons haven't had their shot at yet.
+#[derive(Component, Default, Clone, Copy)]
+pub struct NeedsInterceptTest;
+
+#[derive(Bundle)]
+pub struct ShuttleBundle {
+    pub replicated: Replicated,
+    pub match_scoped: MatchScoped,
+    pub shuttle: Shuttle,
+    pub passengers: Passengers,
+    pub target: RoomTarget,
+    pub launched_from: LaunchedFrom,
+    pub traversal_speed: TraversalSpeed,
+    pub traversal_progress: Progress,
+    pub incidence: Incidence,
+    pub needs_intercept_test: NeedsInterceptTest,
+}
+
+/// What it takes to put a shuttle in the air. Bundled to keep [`launch_shuttle`] within the system
+/// parameter limit.
+#[derive(SystemParam)]
+pub struct Hangar<'w, 's> {
+    balance: Res<'w, BalanceConfig>,
+    rng: ResMut<'w, MatchRng>,
+    commands: Commands<'w, 's>,
+}
+
+impl Hangar<'_, '_> {
+    fn launch(&mut self, from: Entity, target: RoomTarget, passengers: Vec<Crew>) {
+        self.commands.spawn(ShuttleBundle {
+            replicated: Replicated,
+            match_scoped: MatchScoped,
+            shuttle: Shuttle {
+                crew: passengers.len(),
+            },
+            passengers: Passengers(passengers),
+            target,
+            launched_from: LaunchedFrom(from),
+            traversal_speed: TraversalSpeed(self.balance.shuttle_speed),
+            traversal_progress: default(),
+            incidence: random_incidence(self.rng.chance()),
+            needs_intercept_test: NeedsInterceptTest,
+        });
+    }
+}
+
+pub fn launch_shuttle(
+    mut events: EventReader<FromClient<ShipCommand<LaunchShuttle>>>,
+    client_ships: Res<ClientShips>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    shuttles: Query<&LaunchedFrom, With<Shuttle>>,
+    teams: Query<&Team>,
+    mut rejections: EventWriter<ToClients<CommandRejected>>,
+    mut hangar: Hangar,
+) {
+    for FromClient {
+        client_id,
+        event:
+            ShipCommand {
+                ship: client_ship,
+                command: LaunchShuttle { crew, target },
+            },
+    } in events.read()
+    {
+        let (client_id, client_ship, target) = (*client_id, *client_ship, *target);
+        if !may_command(&client_ships, &mut rejections, client_id, client_ship) {
+            continue;
+        }
+        if is_friendly(&teams, client_ship, target.ship) {
+            reject(
+                &mut rejections,
+                client_id,
+                "Shuttles can only board enemy ships",
+            );
+            continue;
+        }
+        let Ok(target_ship) = ships.get(target.ship) else {
+            reject(&mut rejections, client_id, "That ship's been destroyed");
+            continue;
+        };
+        if target.room >= SHIPS[target_ship.ship_type].rooms.len() {
+            reject(
+                &mut rejections,
+                client_id,
+                format!("No room {}", target.room),
+            );
+            continue;
+        }
+        if target_ship
+            .systems
+            .shields
+            .as_ref()
+            .is_some_and(|x| x.super_shield > 0)
+        {
+            reject(
+                &mut rejections,
+                client_id,
+                "Their super shield keeps boarders out",
+            );
+            continue;
+        }
+        if shuttles.iter().any(|x| x.0 == client_ship) {
+            reject(&mut rejections, client_id, "The shuttle is already out");
+            continue;
+        }
+        let Ok(mut ship) = ships.get_mut(client_ship) else {
+            eprintln!("Entity {client_ship:?} is not a ship.");
+            continue;
+        };
+        match ship.board_shuttle(crew, hangar.balance.shuttle_capacity) {
+            Ok(passengers) => hangar.launch(client_ship, target, passengers),
+            Err(reason) => reject(&mut rejections, client_id, reason),
+        }
+    }
+}
+
+/// Give the target's powered weapons a shot at an incoming shuttle once it reaches their shields.
+/// A shuttle that gets shot down takes its crew with it.
+pub fn shuttle_intercept(
+    shuttles: Query<
+        (Entity, &Progress, &RoomTarget, &LaunchedFrom, &Passengers),
+        With<NeedsInterceptTest>,
+    >,
+    ships: Query<&ShipState>,
+    balance: Res<BalanceConfig>,
+    mut rng: ResMut<MatchRng>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+) {
+    for (shuttle, &progress, target, &LaunchedFrom(launched_from), Passengers(passengers)) in
+        &shuttles
+    {
+        if *progress < SHIELD_PROGRESS {
+            continue;
+        }
+        let Ok(ship) = ships.get(target.ship) else {
+            despawn_orphan(&mut commands, shuttle, target.ship);
+            continue;
+        };
+        let powered_weapons = ship
+            .systems
+            .weapons
+            .as_ref()
+            .map_or(0, |x| x.weapons().iter().filter(|x| x.is_powered()).count());
+        // Not a dodge roll, so it comes out of the other stream
+        let chance = balance.point_defense_chance(powered_weapons);
+        if rng.chance().gen_range(0..100) >= chance {
+            commands.entity(shuttle).remove::<NeedsInterceptTest>();
+            continue;
+        }
+        commands.entity(shuttle).despawn();
+        match_events.send(MatchEvent::ShuttleShotDown {
+            ship: launched_from,
+            target: target.ship,
+        });
+        for crew in passengers {
+            match_events.send(MatchEvent::CrewDied {
+                ship: launched_from,
+                name: crew.name.clone(),
+                killer: Some(target.ship),
+            });
+        }
+    }
+}
+
+/// Drop a shuttle's crew off once it reaches the target.
+pub fn shuttle_land(
+    mut shuttles: Query<(
+        Entity,
+        &Progress,
+        &RoomTarget,
+        &LaunchedFrom,
+        &mut Passengers,
+    )>,
+    mut ships: Query<&mut ShipState, Without<Dead>>,
+    mut match_events: EventWriter<MatchEvent>,
+    mut commands: Commands,
+) {
+    for (shuttle, &progress, target, &LaunchedFrom(launched_from), mut passengers) in &mut shuttles
+    {
+        if *progress < 1.0 {
+            continue;
+        }
+        commands.entity(shuttle).despawn();
+        let passengers = std::mem::take(&mut passengers.0);
+        let Ok(mut ship) = ships.get_mut(target.ship) else {
+            // Nothing left to board
+            for crew in passengers {
+                match_events.send(MatchEvent::CrewDied {
+                    ship: launched_from,
+                    name: crew.name,
+                    killer: None,
+                });
+            }
+            continue;
+        };
+        ship.take_boarders(passengers, launched_from, target.room);
+    }
+}
@@ -0,0 +1,376 @@
+//! Match snapshots. A snapshot captures everything needed to pick a match back up after a server
+//! restart: every ship, every shot in flight and which player owns which ship. Connections don't
+//! survive a restart, so ships are keyed by [`PlayerId`] rather than client ID, and the restored
+//! ships wait for their players to reconnect and ready up again before the match resumes.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{
+    ecs::entity::{EntityMapper, MapEntities},
+    prelude::*,
+};
+use bevy_replicon::prelude::Replicated;
+use common::{
+    bullets::{
+        BeamHits, BeamTarget, FiredFrom, Incidence, NeedsDodgeTest, Progress, ProjectileKind,
+        RoomTarget, TraversalSpeed, WeaponDamage,
+    },
+    handshake::PlayerId,
+    lobby::{ReadyState, Team},
+    nav::Cell,
+    ship::{Dead, Destroyed, SHIPS},
+    weapon::DamageSpec,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bullets::{
+        BeamBundle, BeamLanded, BeamShielding, CritChance, DelayedBeam, DelayedProjectile,
+        ProjectileBundle, ShieldPierce,
+    },
+    ship::ShipState,
+    spawn_ship, ClientShips, MatchScoped, PendingPlayers, PlayerIds,
+};
+
+/// Present while the lobby holds ships restored from a snapshot, which match rules mustn't rebuild.
+#[derive(Resource)]
+pub struct RestoredMatch;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    ships: Vec<ShipSnapshot>,
+    projectiles: Vec<ProjectileSnapshot>,
+    beams: Vec<BeamSnapshot>,
+    delayed_projectiles: Vec<DelayedProjectile>,
+    delayed_beams: Vec<DelayedBeam>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShipSnapshot {
+    /// The ship's entity when the snapshot was taken. Only used to fix up references to the ship
+    /// (weapon targets, shots in flight) when restoring.
+    entity: Entity,
+    player: PlayerId,
+    team: Team,
+    dead: bool,
+    state: ShipState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectileSnapshot {
+    /// `None` if the projectile was dodged.
+    damage: Option<WeaponDamage>,
+    target: RoomTarget,
+    fired_from: FiredFrom,
+    speed: TraversalSpeed,
+    progress: Progress,
+    incidence: Incidence,
+    needs_dodge_test: bool,
+    shield_pierce: Option<ShieldPierce>,
+    #[serde(default)]
+    crit_chance: CritChance,
+    #[serde(default)]
+    kind: ProjectileKind,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BeamSnapshot {
+    damage: WeaponDamage,
+    target: BeamTarget,
+    hits: Vec<(f32, Cell, Option<usize>)>,
+    fired_from: FiredFrom,
+    speed: TraversalSpeed,
+    progress: Progress,
+    incidence: Incidence,
+    #[serde(default)]
+    shielding: BeamShielding,
+    #[serde(default)]
+    landed: BeamLanded,
+}
+
+/// Maps entities from the world a snapshot was taken in to the world it's being restored into.
+/// Anything we don't know about maps to [`Entity::PLACEHOLDER`].
+struct SnapshotEntityMapper(HashMap<Entity, Entity>);
+
+impl EntityMapper for SnapshotEntityMapper {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.0.get(&entity).copied().unwrap_or(Entity::PLACEHOLDER)
+    }
+}
+
+impl Snapshot {
+    pub fn take(world: &mut World) -> Self {
+        // Ships belonging to connected players plus any restored ships still waiting on theirs
+        let player_ids = world.resource::<PlayerIds>();
+        let mut owners = world
+            .resource::<ClientShips>()
+            .captains()
+            .filter_map(|(client, ship)| Some((ship, *player_ids.get(&client)?)))
+            .collect::<HashMap<_, _>>();
+        owners.extend(
+            world
+                .resource::<PendingPlayers>()
+                .iter()
+                .map(|(&player, &ship)| (ship, player)),
+        );
+
+        let ships = world
+            .query::<(Entity, &ShipState, &Team, Has<Dead>)>()
+            .iter(world)
+            .filter_map(|(entity, state, &team, dead)| {
+                Some(ShipSnapshot {
+                    entity,
+                    player: *owners.get(&entity)?,
+                    team,
+                    dead,
+                    state: clone_via_serde(state),
+                })
+            })
+            .collect();
+        let projectiles = world
+            .query::<(
+                Option<&WeaponDamage>,
+                &RoomTarget,
+                &FiredFrom,
+                &TraversalSpeed,
+                &Progress,
+                &Incidence,
+                Has<NeedsDodgeTest>,
+                Option<&ShieldPierce>,
+                Option<&CritChance>,
+                Option<&ProjectileKind>,
+            )>()
+            .iter(world)
+            .map(
+                |(
+                    damage,
+                    &target,
+                    &fired_from,
+                    &speed,
+                    &progress,
+                    &incidence,
+                    needs_dodge_test,
+                    pierce,
+                    crit,
+                    kind,
+                )| {
+                    ProjectileSnapshot {
+                        damage: damage.copied(),
+                        target,
+                        fired_from,
+                        speed,
+                        progress,
+                        incidence,
+                        needs_dodge_test,
+                        shield_pierce: pierce.copied(),
+                        crit_chance: crit.copied().unwrap_or_default(),
+                        kind: kind.copied().unwrap_or_default(),
+                    }
+                },
+            )
+            .collect();
+        let beams = world
+            .query::<(
+                &WeaponDamage,
+                &BeamTarget,
+                &BeamHits,
+                &FiredFrom,
+                &TraversalSpeed,
+                &Progress,
+                &Incidence,
+                &BeamShielding,
+                &BeamLanded,
+            )>()
+            .iter(world)
+            .map(
+                |(
+                    &damage,
+                    &target,
+                    hits,
+                    &fired_from,
+                    &speed,
+                    &progress,
+                    &incidence,
+                    &shielding,
+                    &landed,
+                )| {
+                    BeamSnapshot {
+                        damage,
+                        target,
+                        hits: hits.entries(),
+                        fired_from,
+                        speed,
+                        progress,
+                        incidence,
+                        shielding,
+                        landed,
+                    }
+                },
+            )
+            .collect();
+        let delayed_projectiles = world
+            .query::<&DelayedProjectile>()
+            .iter(world)
+            .map(clone_via_serde)
+            .collect();
+        let delayed_beams = world
+            .query::<&DelayedBeam>()
+            .iter(world)
+            .map(clone_via_serde)
+            .collect();
+        Self {
+            ships,
+            projectiles,
+            beams,
+            delayed_projectiles,
+            delayed_beams,
+        }
+    }
+
+    /// Spawn everything in this snapshot into `world`, which should have just been reset. Ships
+    /// are parked in [`PendingPlayers`] until their owners reconnect.
+    pub fn restore(self, world: &mut World) {
+        let mut mapper = SnapshotEntityMapper(
+            self.ships
+                .iter()
+                .map(|x| (x.entity, world.spawn_empty().id()))
+                .collect(),
+        );
+
+        let mut pending = HashMap::new();
+        for mut ship in self.ships {
+            let entity = mapper.map_entity(ship.entity);
+            ship.state.map_entities(&mut mapper);
+            let cell_count = SHIPS[ship.state.ship_type].cell_positions.len();
+            if ship.state.oxygen.len() != cell_count {
+                // Snapshots from before oxygen was tracked per cell have one entry per room
+                eprintln!("Snapshot has no per-cell oxygen for {entity:?}, refilling its air.");
+                ship.state.oxygen = vec![1.0; cell_count];
+            }
+            if ship.state.cells.len() != cell_count {
+                ship.state.cells = vec![default(); cell_count];
+            }
+            // Doors added to the layout since the snapshot was taken start out closed
+            let door_count = SHIPS[ship.state.ship_type].doors.len();
+            ship.state.doors.resize_with(door_count, default);
+            spawn_ship(world, entity, ship.state, ship.team);
+            if ship.dead {
+                world
+                    .entity_mut(entity)
+                    .insert((Dead, Destroyed::finished()));
+            }
+            pending.insert(ship.player, entity);
+        }
+
+        for mut projectile in self.projectiles {
+            projectile.target.map_entities(&mut mapper);
+            projectile.fired_from.map_entities(&mut mapper);
+            let mut entity = world.spawn(ProjectileBundle {
+                replicated: Replicated,
+                match_scoped: MatchScoped,
+                damage: projectile
+                    .damage
+                    .unwrap_or(WeaponDamage(DamageSpec::standard(0))),
+                target: projectile.target,
+                fired_from: projectile.fired_from,
+                traversal_speed: projectile.speed,
+                traversal_progress: projectile.progress,
+                incidence: projectile.incidence,
+                needs_dodge_test: NeedsDodgeTest,
+                shield_pierce: projectile.shield_pierce.unwrap_or(ShieldPierce(0)),
+                crit_chance: projectile.crit_chance,
+                kind: projectile.kind,
+            });
+            if projectile.damage.is_none() {
+                entity.remove::<WeaponDamage>();
+            }
+            if !projectile.needs_dodge_test {
+                entity.remove::<NeedsDodgeTest>();
+            }
+            if projectile.shield_pierce.is_none() {
+                entity.remove::<ShieldPierce>();
+            }
+        }
+
+        for mut beam in self.beams {
+            beam.target.map_entities(&mut mapper);
+            beam.fired_from.map_entities(&mut mapper);
+            world.spawn(BeamBundle {
+                replicated: Replicated,
+                match_scoped: MatchScoped,
+                damage: beam.damage,
+                target: beam.target,
+                hits: BeamHits::from_entries(beam.hits),
+                fired_from: beam.fired_from,
+                traversal_speed: beam.speed,
+                traversal_progress: beam.progress,
+                incidence: beam.incidence,
+                shielding: beam.shielding,
+                landed: beam.landed,
+            });
+        }
+
+        for mut delayed in self.delayed_projectiles {
+            delayed.target.map_entities(&mut mapper);
+            delayed.fired_from.map_entities(&mut mapper);
+            world.spawn((delayed, MatchScoped));
+        }
+        for mut delayed in self.delayed_beams {
+            delayed.target.map_entities(&mut mapper);
+            delayed.fired_from.map_entities(&mut mapper);
+            world.spawn((delayed, MatchScoped));
+        }
+
+        world.insert_resource(PendingPlayers(pending));
+        world.insert_resource(RestoredMatch);
+        // Ready flags belong to connections that no longer exist, so everyone readies up again
+        world.insert_resource(ReadyState::default());
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Where to write a snapshot when no path is given: `snapshots/<unix time>.json`.
+pub fn default_snapshot_path() -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    PathBuf::from("snapshots").join(format!("{}.json", now.as_secs()))
+}
+
+/// Take a snapshot of the current match and write it to `path`, logging the outcome.
+pub fn save_snapshot(world: &mut World, path: &Path) {
+    if world.query::<&ShipState>().iter(world).next().is_none() {
+        println!("No match in progress, not saving a snapshot.");
+        return;
+    }
+    match Snapshot::take(world).save(path) {
+        Ok(()) => println!("Saved snapshot to {}.", path.display()),
+        Err(e) => eprintln!("Couldn't save snapshot to {}: {e}", path.display()),
+    }
+}
+
+/// Ships and delayed shots are deliberately not `Clone` (weapons are "physical" objects that can't
+/// be duplicated in-game), but a snapshot needs an owned copy. Round-tripping through serde gets
+/// us one without opening up `Clone` to the rest of the server.
+fn clone_via_serde<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> T {
+    serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+}
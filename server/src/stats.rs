@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use common::{
+    journal::MatchEvent,
+    lobby::{match_outcome, MatchOutcome, RequestRematch, Team},
+    rules::{MatchRules, Round},
+    ship::Dead,
+    stats::{MatchStats, PlayerStats},
+};
+
+use crate::{reset_gamestate, ship::ShipState, ship_system::ShipSystem, ClientShips};
+
+/// Fold this tick's [`MatchEvent`]s into [`MatchStats`], and charge oxygen downtime to any living
+/// ship whose oxygen system is unpowered. Stops counting once the match is decided.
+pub fn update_match_stats(
+    mut events: EventReader<MatchEvent>,
+    mut stats: ResMut<MatchStats>,
+    client_ships: Res<ClientShips>,
+    ships: Query<&ShipState, Without<Dead>>,
+    teams: Query<(&Team, Has<Dead>), With<ShipState>>,
+    time: Res<Time>,
+) {
+    let copilots = client_ships
+        .iter()
+        .filter(|&(&client, _)| client_ships.is_copilot(client))
+        .filter_map(|(&client, &ship)| Some((client, client_ships.captain_of(ship)?)))
+        .collect::<HashMap<_, _>>();
+    if stats.copilots != copilots {
+        stats.copilots = copilots;
+    }
+    let captain_teams = client_ships
+        .captains()
+        .filter_map(|(client, ship)| Some((client, *teams.get(ship).ok()?.0)))
+        .collect::<HashMap<_, _>>();
+    if stats.teams != captain_teams {
+        stats.teams = captain_teams;
+    }
+    for event in events.read() {
+        match *event {
+            MatchEvent::ShotFired { ship, .. } => {
+                if let Some(stats) = player_stats(&mut stats, &client_ships, ship) {
+                    stats.shots_fired += 1;
+                }
+            }
+            MatchEvent::HullHit {
+                attacker,
+                target,
+                damage,
+                repeat,
+                ..
+            } => {
+                if let Some(stats) = player_stats(&mut stats, &client_ships, attacker) {
+                    if !repeat {
+                        stats.shots_hit += 1;
+                    }
+                    stats.damage_dealt += damage;
+                }
+                if let Some(stats) = player_stats(&mut stats, &client_ships, target) {
+                    stats.damage_taken += damage;
+                }
+            }
+            MatchEvent::SystemDamaged {
+                attacker,
+                destroyed: true,
+                ..
+            } => {
+                if let Some(stats) = player_stats(&mut stats, &client_ships, attacker) {
+                    stats.systems_destroyed += 1;
+                }
+            }
+            MatchEvent::CrewDied {
+                killer: Some(killer),
+                ..
+            } => {
+                if let Some(stats) = player_stats(&mut stats, &client_ships, killer) {
+                    stats.crew_kills += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if match_over(&teams) {
+        return;
+    }
+    for (client, ship) in client_ships.captains() {
+        let Ok(ship) = ships.get(ship) else {
+            continue;
+        };
+        let oxygen_powered = ship
+            .systems
+            .oxygen
+            .as_ref()
+            .is_some_and(|x| x.current_power() > 0);
+        if !oxygen_powered {
+            stats.players.entry(client).or_default().oxygen_downtime += time.delta();
+        }
+    }
+}
+
+fn player_stats<'a>(
+    stats: &'a mut MatchStats,
+    client_ships: &ClientShips,
+    ship: Entity,
+) -> Option<&'a mut PlayerStats> {
+    // Copilots share their captain's line
+    let client = client_ships.captain_of(ship)?;
+    Some(stats.players.entry(client).or_default())
+}
+
+fn match_over(teams: &Query<(&Team, Has<Dead>), With<ShipState>>) -> bool {
+    match_outcome(teams.iter().map(|(&team, dead)| (team, dead))) != MatchOutcome::Ongoing
+}
+
+/// Collect rematch requests from the post-game screen. Once every connected client has asked, the
+/// match is torn down and everyone goes back to the ready phase for the next round of the series.
+pub fn handle_rematch_requests(
+    mut events: EventReader<FromClient<RequestRematch>>,
+    mut requested: Local<HashSet<ClientId>>,
+    clients: Res<ConnectedClients>,
+    teams: Query<(&Team, Has<Dead>), With<ShipState>>,
+    rules: Res<MatchRules>,
+    mut round: ResMut<Round>,
+    mut commands: Commands,
+) {
+    if !match_over(&teams) {
+        // Only finished matches can be restarted
+        events.clear();
+        requested.clear();
+        return;
+    }
+    for &FromClient { client_id, .. } in events.read() {
+        requested.insert(client_id);
+    }
+    if !requested.is_empty() && clients.iter().all(|x| requested.contains(&x.id())) {
+        requested.clear();
+        *round = round.next(&rules);
+        commands.queue(reset_gamestate);
+    }
+}
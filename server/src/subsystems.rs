@@ -0,0 +1,86 @@
+use common::ship::SubsystemId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reactor::Reactor,
+    ship_system::{PowerContext, ShipSystem, SystemStatus},
+};
+
+/// A [`SubsystemId`]. Takes damage and gets repaired like any other system, but never draws reactor
+/// power, so it only stops working once it's destroyed outright.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Subsystem {
+    status: SystemStatus,
+}
+
+impl Subsystem {
+    pub fn is_working(&self) -> bool {
+        self.damage() < self.upgrade_level()
+    }
+}
+
+impl ShipSystem for Subsystem {
+    fn system_status(&self) -> SystemStatus {
+        self.status
+    }
+
+    fn system_status_mut(&mut self) -> &mut SystemStatus {
+        &mut self.status
+    }
+
+    fn current_power(&self) -> usize {
+        0
+    }
+
+    fn add_power(&mut self, _reactor: &mut Reactor, _context: PowerContext) {
+        eprintln!("Can't add power to a subsystem, they don't use any.");
+    }
+
+    fn remove_power(&mut self, _reactor: &mut Reactor) {
+        eprintln!("Can't remove power from a subsystem, they don't use any.");
+    }
+
+    fn next_power_step(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Subsystems {
+    pub piloting: Option<Subsystem>,
+    pub doors: Option<Subsystem>,
+    pub sensors: Option<Subsystem>,
+}
+
+impl Subsystems {
+    pub fn subsystem(&self, subsystem: SubsystemId) -> Option<&Subsystem> {
+        match subsystem {
+            SubsystemId::Piloting => self.piloting.as_ref(),
+            SubsystemId::Doors => self.doors.as_ref(),
+            SubsystemId::Sensors => self.sensors.as_ref(),
+        }
+    }
+
+    pub fn subsystem_mut(&mut self, subsystem: SubsystemId) -> Option<&mut Subsystem> {
+        match subsystem {
+            SubsystemId::Piloting => self.piloting.as_mut(),
+            SubsystemId::Doors => self.doors.as_mut(),
+            SubsystemId::Sensors => self.sensors.as_mut(),
+        }
+    }
+
+    pub fn install(&mut self, subsystem: SubsystemId) {
+        let slot = match subsystem {
+            SubsystemId::Piloting => &mut self.piloting,
+            SubsystemId::Doors => &mut self.doors,
+            SubsystemId::Sensors => &mut self.sensors,
+        };
+        *slot = Some(Subsystem::default());
+    }
+
+    /// Whether `subsystem` is doing its job. Ships from before subsystems existed don't have any,
+    /// and get along the way they always did.
+    pub fn is_working(&self, subsystem: SubsystemId) -> bool {
+        self.subsystem(subsystem).is_none_or(Subsystem::is_working)
+    }
+}
@@ -0,0 +1,38 @@
+//! Keeps track of who hit whom last, for [`Tiebreak::LastHit`] to settle ships destroying each
+//! other on the same tick. See `update_dead` for where it's applied.
+//!
+//! [`Tiebreak::LastHit`]: common::rules::Tiebreak::LastHit
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use common::journal::MatchEvent;
+
+/// When each ship last damaged an enemy's hull, counting hull hits from the start of the match.
+/// Hits landed in the same tick still come in order, so no two ships share a count.
+#[derive(Resource, Default)]
+pub struct LastHits {
+    hits: u64,
+    by_ship: HashMap<Entity, u64>,
+}
+
+impl LastHits {
+    pub fn get(&self, ship: Entity) -> Option<u64> {
+        self.by_ship.get(&ship).copied()
+    }
+}
+
+pub fn track_last_hits(mut events: EventReader<MatchEvent>, mut last_hits: ResMut<LastHits>) {
+    for event in events.read() {
+        if let MatchEvent::HullHit {
+            attacker, damage, ..
+        } = *event
+        {
+            if damage > 0 {
+                last_hits.hits += 1;
+                let hits = last_hits.hits;
+                last_hits.by_ship.insert(attacker, hits);
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use common::time_scale::{RequestTimeScale, TimeScale, TIME_SCALES};
+
+/// Inserted when the server is started with `--local`. Nobody else's consent is needed to change
+/// the game speed in a local match.
+#[derive(Resource)]
+pub struct LocalMatch;
+
+/// Change the game speed once it's been agreed on. Outside of local matches, every connected client
+/// has to ask for the same speed before it takes effect.
+pub fn request_time_scale(
+    mut events: EventReader<FromClient<RequestTimeScale>>,
+    mut requested: Local<HashMap<ClientId, f32>>,
+    local: Option<Res<LocalMatch>>,
+    clients: Res<ConnectedClients>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    for &FromClient {
+        client_id,
+        event: RequestTimeScale(scale),
+    } in events.read()
+    {
+        if !TIME_SCALES.contains(&scale) {
+            eprintln!("Client {client_id:?} asked for unsupported game speed {scale}x.");
+            continue;
+        }
+        if local.is_some() {
+            time_scale.set_if_neq(TimeScale(scale));
+            continue;
+        }
+        requested.insert(client_id, scale);
+        let agreed = clients
+            .iter()
+            .all(|x| requested.get(&x.id()) == Some(&scale));
+        if agreed {
+            requested.clear();
+            time_scale.set_if_neq(TimeScale(scale));
+        }
+    }
+}
+
+pub fn apply_time_scale(time_scale: Res<TimeScale>, mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(**time_scale);
+}
@@ -0,0 +1,123 @@
+//! How clients reach the server: a netcode transport over UDP. Kept apart from [`server_plugin`] so
+//! the simulation can run without sockets, e.g. inside tests.
+//!
+//! [`server_plugin`]: crate::server_plugin
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::{
+    netcode::{NetcodeServerTransport, ServerAuthentication, ServerConfig},
+    renet::{ConnectionConfig, RenetServer},
+    RenetChannelsExt, RepliconRenetPlugins,
+};
+use common::{handshake::Handshake, lobby::MAX_TEAMS, DEFAULT_PORT, PROTOCOL_ID};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::{handle_connections, Handshakes};
+
+/// Listens for clients on [`BindAddr`] (the default port on every interface unless one's inserted
+/// first) and hands their handshakes to [`server_plugin`](crate::server_plugin).
+pub fn transport_plugin(app: &mut App) {
+    app.add_plugins(RepliconRenetPlugins)
+        .init_resource::<BindAddr>()
+        .add_systems(Startup, start_transport)
+        .add_systems(FixedUpdate, read_handshakes.before(handle_connections));
+}
+
+/// Where to listen for clients.
+#[derive(Resource, Debug, Clone, Copy)]
+pub enum BindAddr {
+    /// Take both IPv4 and IPv6 clients on one socket, falling back to IPv4 only if this machine
+    /// doesn't do IPv6.
+    DualStack(u16),
+    /// Just this address, e.g. `0.0.0.0:5000` for IPv4 only.
+    Exactly(SocketAddr),
+}
+
+impl Default for BindAddr {
+    fn default() -> Self {
+        Self::DualStack(DEFAULT_PORT)
+    }
+}
+
+impl BindAddr {
+    pub fn port(&self) -> u16 {
+        match *self {
+            Self::DualStack(port) => port,
+            Self::Exactly(addr) => addr.port(),
+        }
+    }
+
+    fn bind(&self) -> std::io::Result<UdpSocket> {
+        match *self {
+            Self::DualStack(port) => bind_dual_stack(port).or_else(|e| {
+                eprintln!("Couldn't bind IPv6 ({e}), only taking IPv4 clients.");
+                UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))
+            }),
+            Self::Exactly(addr) => UdpSocket::bind(addr),
+        }
+    }
+}
+
+/// An IPv6 socket that also accepts IPv4 clients, as v4-mapped addresses. Whether that's the
+/// default depends on the OS, so ask for it explicitly.
+fn bind_dual_stack(port: u16) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(false)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    Ok(socket.into())
+}
+
+fn start_transport(
+    bind_addr: Res<BindAddr>,
+    channels: Res<RepliconChannels>,
+    mut commands: Commands,
+) {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let socket = bind_addr
+        .bind()
+        .unwrap_or_else(|e| panic!("Couldn't bind {bind_addr:?}: {e}"));
+    match socket.local_addr() {
+        Ok(addr) if matches!(*bind_addr, BindAddr::DualStack(_)) && addr.is_ipv6() => {
+            println!("Listening on {addr}, IPv4 and IPv6.");
+        }
+        Ok(addr) => println!("Listening on {addr}."),
+        Err(e) => eprintln!("Listening, but couldn't tell where: {e}"),
+    }
+    let server_config = ServerConfig {
+        current_time,
+        // Every team's captain, each with room for a copilot
+        max_clients: 2 * MAX_TEAMS as usize,
+        protocol_id: PROTOCOL_ID,
+        authentication: ServerAuthentication::Unsecure,
+        public_addresses: vec![],
+    };
+    commands.insert_resource(RenetServer::new(ConnectionConfig {
+        server_channels_config: channels.get_server_configs(),
+        client_channels_config: channels.get_client_configs(),
+        ..default()
+    }));
+    commands.insert_resource(NetcodeServerTransport::new(server_config, socket).unwrap());
+}
+
+/// Pull each new client's handshake out of the netcode transport.
+fn read_handshakes(
+    mut server_events: EventReader<ServerEvent>,
+    transport: Res<NetcodeServerTransport>,
+    mut handshakes: ResMut<Handshakes>,
+) {
+    for event in server_events.read() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            if let Some(user_data) = transport.user_data(client_id.get()) {
+                handshakes.insert(*client_id, Handshake::from_user_data(&user_data));
+            }
+        }
+    }
+}
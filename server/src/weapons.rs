@@ -2,26 +2,87 @@ use crate::{
     reactor::Reactor,
     ship_system::{PowerContext, ShipSystem, SystemStatus},
 };
+use bevy::ecs::entity::{Entity, EntityMapper, MapEntities};
 use common::{
     bullets::{BeamTarget, RoomTarget},
+    events::DepowerOrder,
+    ship::SHIPS,
     weapon::{BeamWeapon, ProjectileWeapon, Weapon, WeaponId, WeaponTarget, Weaponlike},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Weapons {
     status: SystemStatus,
+    /// Index into [`SHIPS`] of the hull this system is installed on, which limits how many weapons
+    /// it can mount.
+    #[serde(default)]
+    ship_type: usize,
     entries: Vec<WeaponEntry>,
+    /// Weapons on board but not mounted, in the order they were stowed.
+    #[serde(default)]
+    cargo: Vec<Weapon>,
     pub autofire: bool,
+    /// Autofire stops launching missiles once the supply is down to this many, keeping some back
+    /// for aiming by hand.
+    #[serde(default)]
+    pub missile_floor: usize,
+    #[serde(default)]
+    pub depower_order: DepowerOrder,
+}
+
+/// A ship's missiles, as its weapons charge and fire over a tick. Each powered missile weapon has
+/// one missile set aside for its next shot, so two launchers can't both count on the last one.
+pub struct MissileSupply<'a> {
+    stock: &'a mut usize,
+    /// Missiles in stock not set aside for any weapon.
+    free: usize,
+    floor: usize,
+}
+
+impl<'a> MissileSupply<'a> {
+    pub fn new(stock: &'a mut usize, weapons: &Weapons) -> Self {
+        let free = stock.saturating_sub(weapons.reserved_missiles());
+        Self {
+            stock,
+            free,
+            floor: weapons.missile_floor,
+        }
+    }
+
+    /// Set aside a free missile, if there are any.
+    fn reserve(&mut self) -> bool {
+        let Some(free) = self.free.checked_sub(1) else {
+            return false;
+        };
+        self.free = free;
+        true
+    }
 }
 
 impl Weapons {
+    pub fn new(ship_type: usize) -> Self {
+        Self {
+            ship_type,
+            ..Default::default()
+        }
+    }
+
     pub fn charge_and_fire_weapons<'a>(
         &'a mut self,
         missiles: &'a mut usize,
+        charge_rate: f32,
     ) -> impl Iterator<Item = Volley> + 'a {
+        let autofire = self.autofire;
+        let mut supply = MissileSupply::new(missiles, self);
         self.entries
             .iter_mut()
-            .filter_map(|x| x.charge_and_fire(missiles, self.autofire))
+            .filter_map(move |x| x.charge_and_fire(&mut supply, autofire, charge_rate))
+    }
+
+    /// Missiles set aside for the next shot of each powered missile weapon.
+    pub fn reserved_missiles(&self) -> usize {
+        self.entries.iter().filter(|x| x.missile_reserved()).count()
     }
 
     pub fn weapons(&self) -> &Vec<WeaponEntry> {
@@ -32,6 +93,14 @@ impl Weapons {
         self.entries.iter_mut()
     }
 
+    pub fn cargo(&self) -> &[Weapon] {
+        &self.cargo
+    }
+
+    pub fn slots(&self) -> usize {
+        SHIPS[self.ship_type].weapon_slots(self.upgrade_level())
+    }
+
     pub fn current_power(&self) -> usize {
         self.entries
             .iter()
@@ -39,8 +108,15 @@ impl Weapons {
             .fold(0, |x, y| x + y.weapon().common().power)
     }
 
+    /// Power the weapon in `index`. Missile weapons need a missile from the `missiles` in stock
+    /// that isn't already set aside for another weapon.
     pub fn power_weapon(&mut self, index: usize, missiles: usize, reactor: &mut Reactor) {
+        if index >= self.slots() {
+            eprintln!("Can't power weapon at index {index}, slot is locked.");
+            return;
+        }
         let used_power = self.current_power();
+        let free_missiles = missiles.saturating_sub(self.reserved_missiles());
         let Some(weapon) = self.entries.get_mut(index) else {
             eprintln!("Can't power nonexistent weapon at index {index}.");
             return;
@@ -54,8 +130,8 @@ impl Weapons {
             eprintln!("Can't add power to weapons, system power would exceed upgrade level.");
             return;
         }
-        if weapon.weapon().uses_missile() && missiles == 0 {
-            eprintln!("Can't power weapon, no missiles in supply.");
+        if weapon.weapon().uses_missile() && free_missiles == 0 {
+            eprintln!("Can't power weapon, no free missiles in supply.");
             return;
         }
         let Some(new_reactor) = reactor.available.checked_sub(requested_power) else {
@@ -64,6 +140,35 @@ impl Weapons {
         };
         reactor.available = new_reactor;
         weapon.add_power();
+        if weapon.weapon().uses_missile() {
+            weapon.set_missile_reserved(true);
+        }
+    }
+
+    /// The powered weapon that loses its power first when the system can't keep it all, going by
+    /// [`depower_order`](Self::depower_order).
+    pub fn next_to_depower(&self) -> Option<usize> {
+        let mut powered = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.is_powered());
+        match self.depower_order {
+            DepowerOrder::LastSlot => powered.next_back().map(|(index, _)| index),
+            DepowerOrder::LeastCharged => powered
+                // `max_by` keeps the last of equals, so ties go to the later slot
+                .max_by(|(_, a), (_, b)| a.charge_left().total_cmp(&b.charge_left()))
+                .map(|(index, _)| index),
+        }
+    }
+
+    /// The weapon that would lose its power if the system took a point of damage now. `None` if
+    /// it has power to spare.
+    pub fn depower_next(&self) -> Option<usize> {
+        if self.current_power() < self.status.max_power() {
+            return None;
+        }
+        self.next_to_depower()
     }
 
     pub fn depower_weapon(&mut self, index: usize, reactor: &mut Reactor) {
@@ -84,13 +189,12 @@ impl Weapons {
         &mut self,
         weapon_index: usize,
         target: Option<RoomTarget>,
-        targeting_self: bool,
     ) {
         let Some(weapon) = self.entries.get_mut(weapon_index) else {
             eprintln!("Can't set weapon target, no weapon in slot {weapon_index}.");
             return;
         };
-        weapon.set_room_target(target, targeting_self);
+        weapon.set_room_target(target);
     }
 
     pub fn set_beam_weapon_target(&mut self, weapon_index: usize, target: Option<BeamTarget>) {
@@ -101,7 +205,21 @@ impl Weapons {
         weapon.set_beam_target(target);
     }
 
+    /// Drop every target pointing at `ship`, so nothing (autofire included) keeps shooting at it.
+    pub fn clear_targets_on(&mut self, ship: Entity) {
+        for entry in &mut self.entries {
+            if entry.target().is_some_and(|x| x.ship() == ship) {
+                entry.clear_target();
+            }
+        }
+    }
+
+    /// Mount `weapon` at `index`, or stow it in cargo if every slot is taken.
     pub fn install_weapon(&mut self, index: usize, weapon: Weapon) {
+        if self.entries.len() >= self.slots() {
+            self.cargo.push(weapon);
+            return;
+        }
         if index > self.entries.len() {
             eprintln!("Can't add weapon at index {index}, not enough weapons installed.");
             return;
@@ -109,7 +227,7 @@ impl Weapons {
         self.entries.insert(index, WeaponEntry::new(weapon));
     }
 
-    pub fn _remove_weapon(&mut self, index: usize, reactor: &mut Reactor) -> Result<Weapon, ()> {
+    pub fn remove_weapon(&mut self, index: usize, reactor: &mut Reactor) -> Result<Weapon, ()> {
         if index >= self.entries.len() {
             eprintln!("Can't remove weapon, no weapon in slot {index}.");
             return Err(());
@@ -120,6 +238,35 @@ impl Weapons {
         Ok(self.entries.remove(index).take())
     }
 
+    /// Mount cargo weapon `cargo_index` in `slot`. If something's already mounted there, it goes
+    /// to cargo in its place. Otherwise `slot` has to be the next free one.
+    pub fn install_from_cargo(&mut self, cargo_index: usize, slot: usize, reactor: &mut Reactor) {
+        if cargo_index >= self.cargo.len() {
+            eprintln!("Can't install weapon, nothing in cargo slot {cargo_index}.");
+            return;
+        }
+        if slot < self.entries.len() {
+            if self.entries[slot].is_powered() {
+                self.depower_weapon(slot, reactor);
+            }
+            let weapon = WeaponEntry::new(self.cargo.remove(cargo_index));
+            let stowed = std::mem::replace(&mut self.entries[slot], weapon).take();
+            self.cargo.insert(cargo_index, stowed);
+        } else if slot == self.entries.len() && slot < self.slots() {
+            let weapon = self.cargo.remove(cargo_index);
+            self.entries.push(WeaponEntry::new(weapon));
+        } else {
+            eprintln!("Can't install weapon in slot {slot}, it isn't the next free slot.");
+        }
+    }
+
+    /// Unmount the weapon in `index` and stow it in cargo.
+    pub fn store_weapon(&mut self, index: usize, reactor: &mut Reactor) {
+        if let Ok(weapon) = self.remove_weapon(index, reactor) {
+            self.cargo.push(weapon);
+        }
+    }
+
     pub fn move_weapon(&mut self, index: usize, target: usize) {
         if index >= self.entries.len() {
             eprintln!("Can't move weapon, no weapon in slot {index}.");
@@ -131,6 +278,23 @@ impl Weapons {
         }
         let element = self.entries.remove(index);
         self.entries.insert(target, element);
+        // Everything in between changed mounts, and might not reach its target from the new one
+        for entry in &mut self.entries[index.min(target)..=index.max(target)] {
+            entry.clear_target();
+        }
+    }
+}
+
+impl MapEntities for Weapons {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for entry in &mut self.entries {
+            match entry {
+                WeaponEntry::Projectile(status) => {
+                    status.power_targeting.map_entities(entity_mapper)
+                }
+                WeaponEntry::Beam(status) => status.power_targeting.map_entities(entity_mapper),
+            }
+        }
     }
 }
 
@@ -155,17 +319,23 @@ impl ShipSystem for Weapons {
         self.power_weapon(next_depowered, context.missiles, reactor);
     }
 
+    fn next_power_step(&self) -> Option<usize> {
+        let next_depowered = self.entries.iter().position(|x| !x.is_powered())?;
+        let power = self.entries[next_depowered].weapon().common().power;
+        let fits = self.current_power() + power <= self.status.max_power();
+        (next_depowered < self.slots() && fits).then_some(power)
+    }
+
     fn remove_power(&mut self, reactor: &mut Reactor) {
-        let Some(next_powered) = self.entries.iter().rev().position(|x| x.is_powered()) else {
+        let Some(next_powered) = self.next_to_depower() else {
             eprintln!("Can't decrease power to weapons, no weapons are powered.");
             return;
         };
-        let next_powered = self.entries.len() - 1 - next_powered;
         self.depower_weapon(next_powered, reactor);
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub enum WeaponEntry {
     Projectile(WeaponStatus<ProjectileWeapon>),
     Beam(WeaponStatus<BeamWeapon>),
@@ -178,11 +348,15 @@ impl WeaponEntry {
                 weapon,
                 power_targeting: PowerTargetingStatus::Unpowered,
                 charge: 0.0,
+                missile_reserved: false,
+                autofire_target: false,
             }),
             Weapon::Beam(weapon) => Self::Beam(WeaponStatus {
                 weapon,
                 power_targeting: PowerTargetingStatus::Unpowered,
                 charge: 0.0,
+                missile_reserved: false,
+                autofire_target: false,
             }),
         }
     }
@@ -212,29 +386,64 @@ impl WeaponEntry {
         }
     }
 
+    /// Depower the weapon, handing back any missile set aside for it.
     pub fn remove_power(&mut self) {
         match self {
             WeaponEntry::Projectile(status) => {
                 status.power_targeting = PowerTargetingStatus::Unpowered;
+                status.missile_reserved = false;
             }
             WeaponEntry::Beam(status) => {
                 status.power_targeting = PowerTargetingStatus::Unpowered;
+                status.missile_reserved = false;
             }
         }
     }
 
-    pub fn charge_and_fire(&mut self, missiles: &mut usize, autofire: bool) -> Option<Volley> {
+    /// Whether a missile is set aside for this weapon's next shot.
+    pub fn missile_reserved(&self) -> bool {
+        match self {
+            WeaponEntry::Projectile(status) => status.missile_reserved,
+            WeaponEntry::Beam(status) => status.missile_reserved,
+        }
+    }
+
+    fn set_missile_reserved(&mut self, reserved: bool) {
+        match self {
+            WeaponEntry::Projectile(status) => status.missile_reserved = reserved,
+            WeaponEntry::Beam(status) => status.missile_reserved = reserved,
+        }
+    }
+
+    pub fn clear_target(&mut self) {
+        match self {
+            WeaponEntry::Projectile(status) => status.clear_target(),
+            WeaponEntry::Beam(status) => status.clear_target(),
+        }
+    }
+
+    /// Charge the weapon for one tick, firing it if it's charged and has a target. `charge_rate`
+    /// scales how fast it charges.
+    pub fn charge_and_fire(
+        &mut self,
+        missiles: &mut MissileSupply,
+        autofire: bool,
+        charge_rate: f32,
+    ) -> Option<Volley> {
         match self {
             WeaponEntry::Projectile(status) => status
-                .charge_and_fire(missiles, autofire)
+                .charge_and_fire(missiles, autofire, charge_rate)
                 .map(Volley::Projectile),
-            WeaponEntry::Beam(status) => {
-                status.charge_and_fire(missiles, autofire).map(Volley::Beam)
-            }
+            WeaponEntry::Beam(status) => status
+                .charge_and_fire(missiles, autofire, charge_rate)
+                .map(Volley::Beam),
         }
     }
 
-    pub fn set_room_target(&mut self, new_target: Option<RoomTarget>, targeting_self: bool) {
+    /// Callers are expected to have checked the target with [`validate_room_target`] already.
+    ///
+    /// [`validate_room_target`]: common::weapon::validate_room_target
+    pub fn set_room_target(&mut self, new_target: Option<RoomTarget>) {
         let Self::Projectile(status) = self else {
             eprintln!("Can't set weapon target to room, weapon is not a projectile weapon.");
             return;
@@ -243,11 +452,8 @@ impl WeaponEntry {
             eprintln!("Can't set weapon target, weapon is unpowered.");
             return;
         };
-        if targeting_self && !status.weapon.id().can_target_self {
-            eprintln!("Can't set weapon target, weapon cannot target self.");
-            return;
-        }
         *target = new_target;
+        status.autofire_target = false;
     }
 
     pub fn set_beam_target(&mut self, new_target: Option<BeamTarget>) {
@@ -260,6 +466,7 @@ impl WeaponEntry {
             return;
         };
         *target = new_target;
+        status.autofire_target = false;
     }
 
     pub fn target(&self) -> Option<WeaponTarget> {
@@ -276,6 +483,19 @@ impl WeaponEntry {
         }
     }
 
+    /// Seconds of charging left before the weapon is ready.
+    pub fn charge_left(&self) -> f32 {
+        self.weapon().common().charge_time - self.charge()
+    }
+
+    /// Charge the weapon all the way, ready to fire the moment it has a target.
+    pub fn fill_charge(&mut self) {
+        match self {
+            WeaponEntry::Projectile(status) => status.fill_charge(),
+            WeaponEntry::Beam(status) => status.fill_charge(),
+        }
+    }
+
     pub fn take(self) -> Weapon {
         match self {
             WeaponEntry::Projectile(x) => Weapon::Projectile(x.weapon),
@@ -284,13 +504,20 @@ impl WeaponEntry {
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "")]
 pub struct WeaponStatus<Kind: Weaponlike + 'static> {
     /// The "physical" weapon. This can't be cloned. It can only be moved around and eventually
     /// destructed (tossed off into space).
     pub weapon: Kind,
     power_targeting: PowerTargetingStatus<Kind>,
     pub charge: f32,
+    /// Whether a missile is set aside for the next shot. Only ever set on missile weapons.
+    #[serde(default)]
+    missile_reserved: bool,
+    /// Whether the target was kept on by autofire after the last shot, rather than picked by hand.
+    #[serde(default)]
+    autofire_target: bool,
 }
 
 impl<Kind: Weaponlike + 'static> WeaponStatus<Kind> {
@@ -305,21 +532,49 @@ impl<Kind: Weaponlike + 'static> WeaponStatus<Kind> {
         }
     }
 
+    pub fn fill_charge(&mut self) {
+        let weapon = <Kind::Id as Into<WeaponId>>::into(self.weapon.id());
+        self.charge = weapon.common().charge_time;
+    }
+
+    pub fn clear_target(&mut self) {
+        if let PowerTargetingStatus::Powered { target } = &mut self.power_targeting {
+            *target = None;
+        }
+    }
+
+    /// Charge the weapon for one tick and fire it if it's ready. A missile weapon only fires with a
+    /// missile set aside for it, and sets aside the next one straight away if there's one free.
     #[must_use]
     pub fn charge_and_fire(
         &mut self,
-        missiles: &mut usize,
+        missiles: &mut MissileSupply,
         autofire: bool,
+        charge_rate: f32,
     ) -> Option<VolleyInner<Kind>> {
         let weapon = <Kind::Id as Into<WeaponId>>::into(self.weapon.id());
         if let PowerTargetingStatus::Powered { target } = &mut self.power_targeting {
-            self.charge = (self.charge + 1.0 / 64.0).min(weapon.common().charge_time);
-            if self.charge == weapon.common().charge_time {
+            self.charge = (self.charge + charge_rate / 64.0).min(weapon.common().charge_time);
+            if weapon.uses_missile() && !self.missile_reserved {
+                // Missiles might have come in since the last one ran out
+                self.missile_reserved = missiles.reserve();
+            }
+            let armed = self.missile_reserved || !weapon.uses_missile();
+            if self.charge == weapon.common().charge_time && armed {
+                if weapon.uses_missile()
+                    && self.autofire_target
+                    && *missiles.stock <= missiles.floor
+                {
+                    // Autofire leaves the last few for aiming by hand
+                    *target = None;
+                }
                 if let Some(target_room) = target.take() {
                     self.charge = 0.0;
                     if weapon.uses_missile() {
-                        *missiles -= 1;
+                        *missiles.stock = missiles.stock.saturating_sub(1);
+                        self.missile_reserved = missiles.reserve();
                     }
+                    self.autofire_target = autofire;
                     if autofire {
                         *target = Some(target_room);
                     }
@@ -336,7 +591,8 @@ impl<Kind: Weaponlike + 'static> WeaponStatus<Kind> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(bound = "")]
 pub enum PowerTargetingStatus<Kind: Weaponlike> {
     Unpowered,
     Powered {
@@ -344,6 +600,17 @@ pub enum PowerTargetingStatus<Kind: Weaponlike> {
     },
 }
 
+impl<Kind: Weaponlike> MapEntities for PowerTargetingStatus<Kind> {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let PowerTargetingStatus::Powered {
+            target: Some(target),
+        } = self
+        {
+            target.map_entities(entity_mapper);
+        }
+    }
+}
+
 pub enum Volley {
     Projectile(VolleyInner<ProjectileWeapon>),
     Beam(VolleyInner<BeamWeapon>),